@@ -4,6 +4,7 @@
 #include <mgba/core/log.h>
 #include <mgba/core/sync.h>
 #include <mgba/core/thread.h>
+#include <mgba/core/version.h>
 #include <mgba/gba/core.h>
 #include <mgba/internal/gba/gba.h>
 #include <mgba/internal/gba/serialize.h>
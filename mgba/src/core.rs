@@ -5,6 +5,13 @@ use super::trapper;
 use super::vfile;
 use std::ffi::CString;
 
+/// How many cores exist right now, for spotting ones that outlive whatever they were made for.
+static LIVE_CORES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+pub fn live_count() -> usize {
+    LIVE_CORES.load(std::sync::atomic::Ordering::Relaxed)
+}
+
 pub struct Core {
     pub(super) ptr: *mut mgba_sys::mCore,
     video_buffer: Option<Vec<u8>>,
@@ -34,6 +41,7 @@ impl Core {
             mgba_sys::mCoreConfigLoad(&mut ptr.as_mut().unwrap().config);
         }
 
+        LIVE_CORES.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         Ok(Core {
             ptr,
             video_buffer: None,
@@ -41,6 +49,32 @@ impl Core {
         })
     }
 
+    /// Overrides a core option, taking precedence over anything loaded from the config file, and applies it to the core.
+    pub fn set_config_override(&mut self, key: &str, value: &str) {
+        let key_cstr = CString::new(key).unwrap();
+        let value_cstr = CString::new(value).unwrap();
+        unsafe {
+            mgba_sys::mCoreConfigSetOverrideValue(
+                &mut self.ptr.as_mut().unwrap().config,
+                key_cstr.as_ptr(),
+                value_cstr.as_ptr(),
+            );
+            mgba_sys::mCoreLoadConfig(self.ptr);
+        }
+    }
+
+    /// Returns the effective value of a core option, if it's set.
+    pub fn config_value(&self, key: &str) -> Option<String> {
+        let key_cstr = CString::new(key).unwrap();
+        unsafe {
+            let value = mgba_sys::mCoreConfigGetValue(&(*self.ptr).config, key_cstr.as_ptr());
+            if value.is_null() {
+                return None;
+            }
+            Some(std::ffi::CStr::from_ptr(value).to_string_lossy().to_string())
+        }
+    }
+
     pub fn enable_video_buffer(&mut self) {
         let (width, height) = self.as_ref().desired_video_dimensions();
         let mut buffer = vec![0u8; (width * height * 4) as usize];
@@ -83,6 +117,7 @@ impl Drop for Core {
             mgba_sys::mCoreConfigDeinit(&mut self.ptr.as_mut().unwrap().config);
             (*self.ptr).deinit.unwrap()(self.ptr)
         }
+        LIVE_CORES.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
     }
 }
 
@@ -202,6 +237,13 @@ impl<'a> CoreMutRef<'a> {
         Ok(())
     }
 
+    pub fn load_bios(&mut self, mut vf: vfile::VFile) -> anyhow::Result<()> {
+        if !unsafe { (*self.ptr).loadBIOS.unwrap()(self.ptr, vf.release(), 0) } {
+            anyhow::bail!("failed to load bios")
+        }
+        Ok(())
+    }
+
     pub fn load_save(&mut self, mut vf: vfile::VFile) -> anyhow::Result<()> {
         if !unsafe { (*self.ptr).loadSave.unwrap()(self.ptr, vf.release()) } {
             anyhow::bail!("failed to load save")
@@ -299,6 +341,30 @@ impl<'a> CoreMutRef<'a> {
         unsafe { (*self.ptr).reset.unwrap()(self.ptr) }
     }
 
+    /// Returns a copy of the cartridge save data as it currently is.
+    pub fn savedata_clone(&mut self) -> Vec<u8> {
+        unsafe {
+            let mut ptr = std::ptr::null_mut();
+            let size = (*self.ptr).savedataClone.unwrap()(self.ptr, &mut ptr);
+            if ptr.is_null() {
+                return vec![];
+            }
+            let buf = std::slice::from_raw_parts(ptr as *const u8, size as usize).to_vec();
+            mgba_sys::free(ptr);
+            buf
+        }
+    }
+
+    /// Fixes the cartridge RTC to the given time, in milliseconds since the UNIX epoch, instead of following the wall clock.
+    ///
+    /// This is not part of the save state, so it must be set again on any core that loads a state that depends on it.
+    pub fn set_rtc_fixed(&mut self, ms: i64) {
+        unsafe {
+            (*self.ptr).rtc.override_ = mgba_sys::mRTCGenericType_RTC_FIXED;
+            (*self.ptr).rtc.value = ms;
+        }
+    }
+
     pub fn audio_buffer_size(&mut self) -> u64 {
         unsafe { (*self.ptr).getAudioBufferSize.unwrap()(self.ptr) }
     }
@@ -307,6 +373,32 @@ impl<'a> CoreMutRef<'a> {
         unsafe { (*self.ptr).setAudioBufferSize.unwrap()(self.ptr, size) }
     }
 
+    /// Turns mixing every audio channel into the output on or off.
+    ///
+    /// This only affects what comes out: the sound hardware is still emulated the same way, so it doesn't change anything in the save state.
+    pub fn set_audio_enabled(&mut self, enabled: bool) {
+        unsafe {
+            let mut channels = std::ptr::null();
+            let n = (*self.ptr).listAudioChannels.unwrap()(self.ptr, &mut channels);
+            for i in 0..n as usize {
+                (*self.ptr).enableAudioChannel.unwrap()(self.ptr, (*channels.add(i)).id, enabled);
+            }
+        }
+    }
+
+    /// Turns drawing every video layer on or off.
+    ///
+    /// Like set_audio_enabled, this only affects what comes out and not the emulated state.
+    pub fn set_video_enabled(&mut self, enabled: bool) {
+        unsafe {
+            let mut layers = std::ptr::null();
+            let n = (*self.ptr).listVideoLayers.unwrap()(self.ptr, &mut layers);
+            for i in 0..n as usize {
+                (*self.ptr).enableVideoLayer.unwrap()(self.ptr, (*layers.add(i)).id, enabled);
+            }
+        }
+    }
+
     pub fn audio_channel(&mut self, ch: i32) -> blip::BlipMutRef {
         blip::BlipMutRef {
             ptr: unsafe { (*self.ptr).getAudioChannel.unwrap()(self.ptr, ch) },
@@ -12,4 +12,5 @@ pub mod sync;
 pub mod thread;
 pub mod timing;
 pub mod trapper;
+pub mod version;
 pub mod vfile;
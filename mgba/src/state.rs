@@ -5,6 +5,9 @@ pub struct State(pub(super) Box<mgba_sys::GBASerializedState>);
 unsafe impl Send for State {}
 
 impl State {
+    /// The size of a serialized state in bytes.
+    pub const SIZE: usize = std::mem::size_of::<mgba_sys::GBASerializedState>();
+
     pub fn rom_title(&self) -> String {
         let title = unsafe { &*(&self.0.title as *const [std::os::raw::c_char] as *const [u8]) };
         let cstr = match std::ffi::CString::new(title) {
@@ -54,6 +57,7 @@ impl State {
         }
     }
 
+    /// Panics if the slice is not exactly `SIZE` bytes long.
     pub fn from_slice(slice: &[u8]) -> Self {
         unsafe {
             let layout = std::alloc::Layout::new::<mgba_sys::GBASerializedState>();
@@ -20,6 +20,7 @@ struct ThreadImpl {
     raw: mgba_sys::mCoreThread,
     frame_callback: Option<Box<dyn Fn(core::CoreMutRef, &[u8], InThreadHandle) + Send + 'static>>,
     current_callback: std::cell::RefCell<Option<Box<dyn Fn(crate::core::CoreMutRef<'_>) + Send + Sync>>>,
+    ended: bool,
 }
 
 unsafe impl Send for ThreadImpl {}
@@ -84,6 +85,7 @@ impl Thread {
             raw: unsafe { std::mem::zeroed::<mgba_sys::mCoreThread>() },
             frame_callback: None,
             current_callback: std::cell::RefCell::new(None),
+            ended: false,
         });
         t.raw.core = core_ptr;
         t.raw.logger.d = unsafe { *mgba_sys::mLogGetContext() };
@@ -106,16 +108,57 @@ impl Thread {
         }
         Ok(())
     }
+
+    /// Ends and joins the thread now, rather than when the last handle to it is dropped, and drops its callbacks.
+    ///
+    /// The callbacks often hold handles to the thread themselves, in which case the thread would otherwise never be dropped. Ending a thread that has already ended does nothing.
+    pub fn end(&self) {
+        let raw = {
+            let mut t = self.0.lock();
+            if t.ended {
+                return;
+            }
+            t.ended = true;
+            &mut t.raw as *mut mgba_sys::mCoreThread
+        };
+        // The lock isn't held while joining, as whatever the thread is waiting on to finish its frame may need it. The raw thread stays put, as it's boxed and we hold onto the box.
+        unsafe { mgba_sys::mCoreThreadEnd(raw) }
+        unsafe { mgba_sys::mCoreThreadJoin(raw) }
+        log::info!("thread ended and joined");
+
+        let mut t = self.0.lock();
+        let frame_callback = t.frame_callback.take();
+        let current_callback = t.current_callback.borrow_mut().take();
+        drop(t);
+        drop(frame_callback);
+        drop(current_callback);
+    }
+
+    pub fn downgrade(&self) -> WeakThread {
+        WeakThread(std::sync::Arc::downgrade(&self.0))
+    }
 }
 
 impl Drop for ThreadImpl {
     fn drop(&mut self) {
+        if self.ended {
+            return;
+        }
         unsafe { mgba_sys::mCoreThreadEnd(&mut self.raw) }
         unsafe { mgba_sys::mCoreThreadJoin(&mut self.raw) }
         log::info!("thread is being dropped, ended and joined");
     }
 }
 
+/// Refers to a thread without keeping it alive, for checking that it's actually gone.
+pub struct WeakThread(std::sync::Weak<parking_lot::Mutex<Box<ThreadImpl>>>);
+
+impl WeakThread {
+    pub fn is_alive(&self) -> bool {
+        self.0.strong_count() > 0
+    }
+}
+
 #[derive(Clone)]
 pub struct Handle {
     thread: std::sync::Arc<parking_lot::Mutex<Box<ThreadImpl>>>,
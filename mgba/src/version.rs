@@ -0,0 +1,13 @@
+/// The version of the mGBA core, e.g. "0.10.0".
+pub fn project_version() -> String {
+    unsafe { std::ffi::CStr::from_ptr(mgba_sys::projectVersion) }
+        .to_string_lossy()
+        .to_string()
+}
+
+/// The git commit the mGBA core was built from.
+pub fn git_commit() -> String {
+    unsafe { std::ffi::CStr::from_ptr(mgba_sys::gitCommit) }
+        .to_string_lossy()
+        .to_string()
+}
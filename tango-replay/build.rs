@@ -0,0 +1,6 @@
+use std::io::Result;
+
+fn main() -> Result<()> {
+    prost_build::compile_protos(&["src/protos/replay11.proto", "src/protos/replay10.proto"], &["src/"])?;
+    Ok(())
+}
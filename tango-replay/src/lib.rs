@@ -0,0 +1,677 @@
+//! Reading and writing Tango replay files.
+//!
+//! This is the same code Tango uses to read and write its own replays, so tools built on it keep working as the format changes.
+//!
+//! # Format
+//!
+//! All integers are little endian.
+//!
+//! | Field          | Size             | Description                                                              |
+//! |----------------|------------------|--------------------------------------------------------------------------|
+//! | `magic`        | 4                | Always `TOOT`.                                                           |
+//! | `version`      | 1                | The format version, see below.                                           |
+//! | `num_inputs`   | 4                | The number of input records, or 0 if the replay was never finished.      |
+//! | `metadata_len` | 4                |                                                                          |
+//! | `metadata`     | `metadata_len`   | A protobuf-encoded [`Metadata`].                                         |
+//! | `sections`     | variable         | Since 0x12: a u32 count, then for each section a 4 byte tag, a u32 length and the data. |
+//! | `stream`       | rest of the file | zstd-compressed.                                                         |
+//!
+//! The decompressed stream contains:
+//!
+//! | Field                | Size                   | Description                                                  |
+//! |----------------------|------------------------|--------------------------------------------------------------|
+//! | `local_player_index` | 1                      | Which player recorded the replay.                            |
+//! | `input_size`         | 1                      | The size of each player's packet in an input record.         |
+//! | `local_state_len`    | 4                      |                                                              |
+//! | `local_state`        | `local_state_len`      | The mGBA save state of the recording player at the start.    |
+//! | `remote_state_len`   | 4                      |                                                              |
+//! | `remote_state`       | `remote_state_len`     | The mGBA save state of the other player at the start.        |
+//! | `records`            | until the end          | Input records, see [`Record`].                               |
+//!
+//! Unfinished replays (e.g. from a crash) may end in the middle of a record: readers stop at the last complete one.
+//!
+//! ## Versions
+//!
+//! - 0x10: The original format, with a different metadata message that's converted on read.
+//! - 0x11: The current metadata message.
+//! - 0x12: Adds sections.
+//!
+//! ## Sections
+//!
+//! - `RBDP`: For each input record, how many ticks the recording player had already simulated past it with predicted input by the time it was committed, i.e. how far they had to roll back. One byte per record, capped at 255.
+//!
+//! # Compatibility
+//!
+//! Files of every version listed above can be read by every later release of this crate. Within a major version of the crate, new sections and new metadata fields may be added, and unknown sections are returned as-is in [`Header::unknown_sections`], so older readers can still read newer files. Anything else that would make existing files unreadable, or change the types in this crate incompatibly, is a new major version.
+
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
+use prost::Message;
+use std::io::{Read, Write};
+
+mod protos {
+    pub mod replay11 {
+        include!(concat!(env!("OUT_DIR"), "/tango.replay.protos.replay11.rs"));
+    }
+
+    pub mod replay10 {
+        include!(concat!(env!("OUT_DIR"), "/tango.replay.protos.replay10.rs"));
+    }
+}
+mod replay10;
+
+pub use protos::replay11::metadata;
+pub type Metadata = protos::replay11::Metadata;
+
+/// The magic bytes every replay file starts with.
+pub const MAGIC: &[u8; 4] = b"TOOT";
+
+/// The version new replays are written with.
+pub const VERSION: u8 = 0x12;
+
+/// The first version with sections.
+const SECTIONS_VERSION: u8 = 0x12;
+
+/// The tag of the rollback depths section.
+pub const ROLLBACK_DEPTHS_TAG: &[u8; 4] = b"RBDP";
+
+fn invalid_data(message: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+/// A section of a replay file, identified by its tag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub tag: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+/// The uncompressed start of a replay file, which can be read without decompressing anything else.
+#[derive(Clone, Debug)]
+pub struct Header {
+    pub version: u8,
+
+    /// The number of input records, or 0 if the replay was never finished.
+    pub num_inputs: u32,
+
+    pub metadata: Metadata,
+
+    /// How far the recording player rolled back for each input record, if it was recorded.
+    pub rollback_depths: Option<Vec<u8>>,
+
+    /// Sections this version of the crate doesn't know about, in the order they appear in the file.
+    pub unknown_sections: Vec<Section>,
+}
+
+impl Header {
+    /// Whether the replay was finished. Unfinished replays can still be read, but end early.
+    pub fn is_finished(&self) -> bool {
+        self.num_inputs > 0
+    }
+}
+
+fn decode_metadata(version: u8, raw: &[u8]) -> std::io::Result<Metadata> {
+    Ok(match version {
+        0x10 => replay10::decode_metadata(raw)?,
+        0x11 | 0x12 => Metadata::decode(raw)?,
+        _ => {
+            return Err(invalid_data(format!("invalid version: {:02x}", version)));
+        }
+    })
+}
+
+/// Reads a field of a length read from the file. The length isn't trusted enough to allocate up front, so a corrupt one fails when the data runs out rather than by allocating gigabytes.
+fn read_field(r: &mut impl Read, len: u32, name: &str) -> std::io::Result<Vec<u8>> {
+    let mut data = vec![];
+    r.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len as usize {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("{} is truncated", name),
+        ));
+    }
+    Ok(data)
+}
+
+fn read_sections(r: &mut impl Read) -> std::io::Result<Vec<Section>> {
+    let num_sections = r.read_u32::<byteorder::LittleEndian>()?;
+    let mut sections = vec![];
+    for _ in 0..num_sections {
+        let mut tag = [0u8; 4];
+        r.read_exact(&mut tag)?;
+        let len = r.read_u32::<byteorder::LittleEndian>()?;
+        let data = read_field(r, len, "section")?;
+        sections.push(Section { tag, data });
+    }
+    Ok(sections)
+}
+
+fn write_sections(w: &mut impl Write, sections: &[Section]) -> std::io::Result<()> {
+    w.write_u32::<byteorder::LittleEndian>(sections.len() as u32)?;
+    for section in sections {
+        w.write_all(&section.tag)?;
+        w.write_u32::<byteorder::LittleEndian>(section.data.len() as u32)?;
+        w.write_all(&section.data)?;
+    }
+    Ok(())
+}
+
+/// Reads the header of a replay file, leaving the reader at the start of the compressed stream.
+pub fn read_header(r: &mut impl Read) -> std::io::Result<Header> {
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("invalid header"));
+    }
+
+    let version = r.read_u8()?;
+    let num_inputs = r.read_u32::<byteorder::LittleEndian>()?;
+    let metadata_len = r.read_u32::<byteorder::LittleEndian>()?;
+    let raw = read_field(r, metadata_len, "metadata")?;
+    let metadata = decode_metadata(version, &raw)?;
+
+    let mut rollback_depths = None;
+    let mut unknown_sections = vec![];
+    if version >= SECTIONS_VERSION {
+        for section in read_sections(r)? {
+            if &section.tag == ROLLBACK_DEPTHS_TAG {
+                rollback_depths = Some(section.data);
+            } else {
+                unknown_sections.push(section);
+            }
+        }
+    }
+
+    Ok(Header {
+        version,
+        num_inputs,
+        metadata,
+        rollback_depths,
+        unknown_sections,
+    })
+}
+
+/// One player's input for a tick.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerInput {
+    pub joyflags: u16,
+    pub packet: Vec<u8>,
+}
+
+/// An input record: both players' inputs for a tick.
+///
+/// On disk, this is `local_tick` (u32), `remote_tick` (u32), then for each of player 1 and player 2 their joyflags (u16) followed by their packet (`input_size` bytes).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// The tick of the recording player.
+    pub local_tick: u32,
+    /// The tick of the other player, as the recording player saw it.
+    pub remote_tick: u32,
+    pub p1: PlayerInput,
+    pub p2: PlayerInput,
+}
+
+/// Reads a replay file as a stream: the header and states are read up front, then records are read one by one by iterating.
+pub struct Reader<R: Read> {
+    header: Header,
+    local_player_index: u8,
+    input_size: usize,
+    local_state: Vec<u8>,
+    remote_state: Vec<u8>,
+    stream: zstd::stream::read::Decoder<'static, std::io::BufReader<R>>,
+    done: bool,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut r: R) -> std::io::Result<Self> {
+        let header = read_header(&mut r)?;
+
+        let mut stream = zstd::stream::read::Decoder::new(r)?;
+        let local_player_index = stream.read_u8()?;
+        let input_size = stream.read_u8()? as usize;
+
+        let local_state_len = stream.read_u32::<byteorder::LittleEndian>()?;
+        let local_state = read_field(&mut stream, local_state_len, "local state")?;
+
+        let remote_state_len = stream.read_u32::<byteorder::LittleEndian>()?;
+        let remote_state = read_field(&mut stream, remote_state_len, "remote state")?;
+
+        Ok(Self {
+            header,
+            local_player_index,
+            input_size,
+            local_state,
+            remote_state,
+            stream,
+            done: false,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Which player (0 or 1) recorded the replay.
+    pub fn local_player_index(&self) -> u8 {
+        self.local_player_index
+    }
+
+    /// The size of each player's packet in an input record.
+    pub fn input_size(&self) -> usize {
+        self.input_size
+    }
+
+    /// The mGBA save state of the recording player at the start, if there was one.
+    pub fn local_state(&self) -> Option<&[u8]> {
+        if self.local_state.is_empty() {
+            None
+        } else {
+            Some(&self.local_state)
+        }
+    }
+
+    /// The mGBA save state of the other player at the start, if there was one.
+    pub fn remote_state(&self) -> Option<&[u8]> {
+        if self.remote_state.is_empty() {
+            None
+        } else {
+            Some(&self.remote_state)
+        }
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = std::io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut buf = vec![0u8; 4 + 4 + (2 + self.input_size) * 2];
+        if let Err(e) = self.stream.read_exact(&mut buf) {
+            self.done = true;
+            return if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                // Either the end of the replay or a record cut off by the replay not being finished.
+                None
+            } else {
+                Some(Err(e))
+            };
+        }
+
+        let p1_offset = 8;
+        let p2_offset = p1_offset + 2 + self.input_size;
+        Some(Ok(Record {
+            local_tick: byteorder::LittleEndian::read_u32(&buf[0..4]),
+            remote_tick: byteorder::LittleEndian::read_u32(&buf[4..8]),
+            p1: PlayerInput {
+                joyflags: byteorder::LittleEndian::read_u16(&buf[p1_offset..p1_offset + 2]),
+                packet: buf[p1_offset + 2..p2_offset].to_vec(),
+            },
+            p2: PlayerInput {
+                joyflags: byteorder::LittleEndian::read_u16(&buf[p2_offset..p2_offset + 2]),
+                packet: buf[p2_offset + 2..].to_vec(),
+            },
+        }))
+    }
+}
+
+/// Writes a replay file of the current version.
+///
+/// The states must be written before any records. The writer must be finished for the replay to be marked as finished, otherwise it will be read back as unfinished.
+pub struct Writer<W: Write + std::io::Seek> {
+    encoder: Option<zstd::stream::write::Encoder<'static, W>>,
+    num_inputs: u32,
+}
+
+impl<W: Write + std::io::Seek> Writer<W> {
+    pub fn new(
+        mut writer: W,
+        metadata: &Metadata,
+        sections: &[Section],
+        local_player_index: u8,
+        input_size: u8,
+    ) -> std::io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_u8(VERSION)?;
+        writer.write_u32::<byteorder::LittleEndian>(0)?;
+        let raw_metadata = metadata.encode_to_vec();
+        writer.write_u32::<byteorder::LittleEndian>(raw_metadata.len() as u32)?;
+        writer.write_all(&raw_metadata[..])?;
+        write_sections(&mut writer, sections)?;
+
+        let mut encoder = zstd::Encoder::new(writer, 3)?;
+        encoder.write_u8(local_player_index)?;
+        encoder.write_u8(input_size)?;
+        encoder.flush()?;
+        Ok(Self {
+            encoder: Some(encoder),
+            num_inputs: 0,
+        })
+    }
+
+    /// Writes a state. The local state must be written first, then the remote one.
+    pub fn write_state(&mut self, state: &[u8]) -> std::io::Result<()> {
+        let encoder = self.encoder.as_mut().unwrap();
+        encoder.write_u32::<byteorder::LittleEndian>(state.len() as u32)?;
+        encoder.write_all(state)?;
+        encoder.flush()?;
+        Ok(())
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> std::io::Result<()> {
+        let encoder = self.encoder.as_mut().unwrap();
+        encoder.write_u32::<byteorder::LittleEndian>(record.local_tick)?;
+        encoder.write_u32::<byteorder::LittleEndian>(record.remote_tick)?;
+        encoder.write_u16::<byteorder::LittleEndian>(record.p1.joyflags)?;
+        encoder.write_all(&record.p1.packet)?;
+        encoder.write_u16::<byteorder::LittleEndian>(record.p2.joyflags)?;
+        encoder.write_all(&record.p2.packet)?;
+        self.num_inputs += 1;
+        Ok(())
+    }
+
+    /// Whether the writer has been finished. Unfinished writers are finished on drop, but errors are lost.
+    pub fn is_finished(&self) -> bool {
+        self.encoder.is_none()
+    }
+
+    /// Finishes the replay, marking it as finished.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        let mut w = self.encoder.take().unwrap().finish()?;
+        w.seek(std::io::SeekFrom::Start((MAGIC.len() + 1) as u64))?;
+        w.write_u32::<byteorder::LittleEndian>(self.num_inputs)?;
+        Ok(w)
+    }
+}
+
+impl<W: Write + std::io::Seek> Drop for Writer<W> {
+    fn drop(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            // The replay will still read back as unfinished, but at least the compressed stream will be complete.
+            let _ = encoder.finish();
+        }
+    }
+}
+
+/// Rewrites the metadata of a replay file, leaving the rest of it untouched.
+///
+/// Only replays of the current version can be updated.
+pub fn update_metadata(raw: &[u8], f: impl FnOnce(&mut Metadata)) -> std::io::Result<Vec<u8>> {
+    let mut r = raw;
+
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("invalid header"));
+    }
+
+    let version = r.read_u8()?;
+    if version != VERSION {
+        return Err(invalid_data(format!(
+            "cannot update metadata of version: {:02x}",
+            version
+        )));
+    }
+
+    let num_inputs = r.read_u32::<byteorder::LittleEndian>()?;
+    let metadata_len = r.read_u32::<byteorder::LittleEndian>()? as usize;
+    if r.len() < metadata_len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "metadata is truncated",
+        ));
+    }
+    let mut metadata = Metadata::decode(&r[..metadata_len])?;
+    f(&mut metadata);
+
+    let raw_metadata = metadata.encode_to_vec();
+    let mut buf = Vec::with_capacity(raw.len());
+    buf.write_all(MAGIC)?;
+    buf.write_u8(VERSION)?;
+    buf.write_u32::<byteorder::LittleEndian>(num_inputs)?;
+    buf.write_u32::<byteorder::LittleEndian>(raw_metadata.len() as u32)?;
+    buf.write_all(&raw_metadata[..])?;
+    // Sections and the stream are copied as-is.
+    buf.write_all(&r[metadata_len..])?;
+    Ok(buf)
+}
+
+/// Adds a section to a replay file, replacing any existing section with the same tag and leaving the rest of it untouched.
+///
+/// Only replays of the current version can be updated.
+pub fn set_section(raw: &[u8], section: &Section) -> std::io::Result<Vec<u8>> {
+    let mut r = raw;
+
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("invalid header"));
+    }
+
+    let version = r.read_u8()?;
+    if version != VERSION {
+        return Err(invalid_data(format!(
+            "cannot update sections of version: {:02x}",
+            version
+        )));
+    }
+
+    let num_inputs = r.read_u32::<byteorder::LittleEndian>()?;
+    let metadata_len = r.read_u32::<byteorder::LittleEndian>()? as usize;
+    if r.len() < metadata_len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "metadata is truncated",
+        ));
+    }
+    let raw_metadata = &r[..metadata_len];
+    r = &r[metadata_len..];
+
+    let mut sections = read_sections(&mut r)?;
+    sections.retain(|s| s.tag != section.tag);
+    sections.push(section.clone());
+
+    let mut buf = Vec::with_capacity(raw.len() + section.data.len() + 8);
+    buf.write_all(MAGIC)?;
+    buf.write_u8(VERSION)?;
+    buf.write_u32::<byteorder::LittleEndian>(num_inputs)?;
+    buf.write_u32::<byteorder::LittleEndian>(raw_metadata.len() as u32)?;
+    buf.write_all(raw_metadata)?;
+    write_sections(&mut buf, &sections)?;
+    // The stream is copied as-is.
+    buf.write_all(r)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replays of each version, all of the same made up match: three records, with player 1's joyflags and packets counting up from 0x10 and 0xa0/0xb0 and player 2's from 0x20 and 0xc0/0xd0.
+    const V10: &[u8] = include_bytes!("../testdata/v10.tangoreplay");
+    const V11: &[u8] = include_bytes!("../testdata/v11.tangoreplay");
+    const V12: &[u8] = include_bytes!("../testdata/v12.tangoreplay");
+
+    fn check_fixture(raw: &[u8], version: u8) -> Header {
+        let reader = Reader::new(raw).unwrap();
+        let header = reader.header().clone();
+        assert_eq!(header.version, version);
+        assert_eq!(header.num_inputs, 3);
+        assert!(header.is_finished());
+        assert_eq!(header.metadata.ts, 1650000000000);
+        assert_eq!(header.metadata.link_code, "fixture");
+
+        let local_side = header.metadata.local_side.as_ref().unwrap();
+        assert_eq!(local_side.nickname, "alice");
+        let local_game_info = local_side.game_info.as_ref().unwrap();
+        assert_eq!(
+            (local_game_info.rom_family.as_str(), local_game_info.rom_variant),
+            ("bn6", 1)
+        );
+        let remote_side = header.metadata.remote_side.as_ref().unwrap();
+        assert_eq!(remote_side.nickname, "bob");
+        let remote_game_info = remote_side.game_info.as_ref().unwrap();
+        assert_eq!(
+            (remote_game_info.rom_family.as_str(), remote_game_info.rom_variant),
+            ("bn6", 0)
+        );
+
+        assert_eq!(reader.local_player_index(), 1);
+        assert_eq!(reader.input_size(), 2);
+        assert_eq!(reader.local_state(), Some(&b"local state"[..]));
+        assert_eq!(reader.remote_state(), Some(&b"remote state"[..]));
+
+        let records = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            records,
+            (0..3u8)
+                .map(|i| Record {
+                    local_tick: i as u32,
+                    remote_tick: i as u32 + 2,
+                    p1: PlayerInput {
+                        joyflags: 0x10 + i as u16,
+                        packet: vec![0xa0 + i, 0xb0 + i],
+                    },
+                    p2: PlayerInput {
+                        joyflags: 0x20 + i as u16,
+                        packet: vec![0xc0 + i, 0xd0 + i],
+                    },
+                })
+                .collect::<Vec<_>>()
+        );
+
+        header
+    }
+
+    #[test]
+    fn test_read_v10() {
+        let header = check_fixture(V10, 0x10);
+        assert_eq!(header.metadata.fingerprint, None);
+        assert_eq!(header.rollback_depths, None);
+        assert!(header.unknown_sections.is_empty());
+    }
+
+    #[test]
+    fn test_read_v11() {
+        let header = check_fixture(V11, 0x11);
+        assert_eq!(header.rollback_depths, None);
+        assert!(header.unknown_sections.is_empty());
+    }
+
+    #[test]
+    fn test_read_v12() {
+        let header = check_fixture(V12, 0x12);
+        assert_eq!(header.rollback_depths, Some(vec![0, 1, 2]));
+        assert_eq!(
+            header.unknown_sections,
+            vec![Section {
+                tag: *b"TEST",
+                data: b"unknown".to_vec(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let metadata = read_header(&mut &V12[..]).unwrap().metadata;
+        let mut writer = Writer::new(
+            std::io::Cursor::new(vec![]),
+            &metadata,
+            &[Section {
+                tag: *ROLLBACK_DEPTHS_TAG,
+                data: vec![0, 1, 2],
+            }],
+            1,
+            2,
+        )
+        .unwrap();
+        writer.write_state(b"local state").unwrap();
+        writer.write_state(b"remote state").unwrap();
+        for record in Reader::new(V12).unwrap() {
+            writer.write_record(&record.unwrap()).unwrap();
+        }
+        let raw = writer.finish().unwrap().into_inner();
+
+        let header = check_fixture(&raw, VERSION);
+        assert!(header.unknown_sections.is_empty());
+    }
+
+    #[test]
+    fn test_read_unfinished() {
+        let mut raw = std::io::Cursor::new(vec![]);
+        {
+            let mut writer = Writer::new(&mut raw, &Metadata::default(), &[], 0, 2).unwrap();
+            writer.write_state(b"local state").unwrap();
+            writer.write_state(b"remote state").unwrap();
+            for record in Reader::new(V12).unwrap() {
+                writer.write_record(&record.unwrap()).unwrap();
+            }
+        }
+
+        let reader = Reader::new(&raw.get_ref()[..]).unwrap();
+        assert!(!reader.header().is_finished());
+        assert_eq!(reader.count(), 3);
+    }
+
+    /// The header of the v0x11 fixture, up to the start of the stream.
+    fn v11_header() -> &'static [u8] {
+        let metadata_len = byteorder::LittleEndian::read_u32(&V11[9..13]) as usize;
+        &V11[..13 + metadata_len]
+    }
+
+    #[test]
+    fn test_read_truncated_header() {
+        for len in 0..v11_header().len() {
+            assert_eq!(
+                read_header(&mut &V11[..len]).unwrap_err().kind(),
+                std::io::ErrorKind::UnexpectedEof,
+                "{}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_oversized_metadata() {
+        let mut raw = V11[..9].to_vec();
+        raw.write_u32::<byteorder::LittleEndian>(u32::MAX).unwrap();
+        raw.extend_from_slice(&V11[13..]);
+        assert_eq!(
+            read_header(&mut &raw[..]).unwrap_err().kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_read_oversized_section() {
+        let mut raw = v11_header().to_vec();
+        raw[4] = 0x12;
+        raw.write_u32::<byteorder::LittleEndian>(1).unwrap();
+        raw.write_all(b"TEST").unwrap();
+        raw.write_u32::<byteorder::LittleEndian>(u32::MAX).unwrap();
+        raw.write_all(b"short").unwrap();
+        assert_eq!(
+            read_header(&mut &raw[..]).unwrap_err().kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_read_oversized_state() {
+        for (local_state_len, remote_state_len) in [(u32::MAX, 0), (0, u32::MAX)] {
+            let mut stream = vec![];
+            stream.write_u8(0).unwrap();
+            stream.write_u8(2).unwrap();
+            stream.write_u32::<byteorder::LittleEndian>(local_state_len).unwrap();
+            stream.write_u32::<byteorder::LittleEndian>(remote_state_len).unwrap();
+            stream.write_all(b"short").unwrap();
+
+            let mut raw = v11_header().to_vec();
+            raw.extend(zstd::encode_all(&stream[..], 3).unwrap());
+            assert_eq!(
+                Reader::new(&raw[..]).err().unwrap().kind(),
+                std::io::ErrorKind::UnexpectedEof
+            );
+        }
+    }
+}
@@ -33,6 +33,8 @@ fn convert_side(v10: &super::protos::replay10::metadata::Side) -> Result<super::
     Ok(super::metadata::Side {
         nickname: v10.nickname.clone(),
         reveal_setup: v10.reveal_setup,
+        client_info: None,
+        input_delay: None,
         game_info: v10
             .game_info
             .as_ref()
@@ -67,5 +69,11 @@ pub fn decode_metadata(raw: &[u8]) -> Result<super::Metadata, std::io::Error> {
             .remote_side
             .map(|side| convert_side(&side))
             .map_or(Ok(None), |v| v.map(Some))?,
+        fixed_rtc: None,
+        fingerprint: None,
+        desync: None,
+        round_time_limit: None,
+        score: None,
+        match_type: None,
     })
 }
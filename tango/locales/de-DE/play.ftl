@@ -21,7 +21,6 @@ play-connection-task-waiting = Waiting for opponent...
 select-save = Save-Datei auswählen
     .select = Auswählen
     .no-save-selected = Keine Save-Datei ausgewählt
-    .return-to-games-list = Zurück zur Spieleliste
     .open-folder = Ordner öffnen
 lobby-issue-no-local-rom = You do not have a copy of the opponent's game: { $game_name }
 lobby-issue-no-remote-rom = The opponent does not have a copy of the game: { $game_name }
@@ -2,4 +2,11 @@ copy-to-clipboard = Copy to clipboard
     .copied = Copied!
 
 window-title = Tango
-    .running = Tango (running)
+    .lobby = Tango — waiting on { $link_code }
+    .lobby-hidden = Tango — waiting for opponent
+    .single-player = Tango — { $game }
+    .match = Tango — { $local } vs { $remote } ({ $game })
+    .replay = Tango — { $name }
+    .paused = [paused] { $title }
+    .progress = [{ $percent }%] { $title }
+    .progress-indeterminate = […] { $title }
@@ -1 +1,6 @@
 debug = Debug
+log-viewer = Log
+    .copy = Copy
+    .copy-tooltip = Copies the entries shown. Your home folder is replaced with ~ in anything copied.
+log-viewer-subsystem = Subsystem
+    .all = All subsystems
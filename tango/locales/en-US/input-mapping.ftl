@@ -11,7 +11,15 @@ input-button-r = R
 input-button-start = START
 input-button-select = SELECT
 input-button-speed-up = Speed up
+input-button-slow-motion = Slow motion
 input-button-menu = Menu
+input-button-save-state = Save state
+input-button-load-state = Load state
+input-button-save-states = Save state slots
+input-button-replay-loop-start = Replay loop start (A)
+input-button-replay-loop-end = Replay loop end (B)
+input-button-replay-step-back = Replay step back
+input-button-toggle-streamer-mode = Toggle streamer privacy mode
 
 physical-input-button-dpup = DPad ⬆
 physical-input-button-dpdown = DPad ⬇
@@ -42,3 +50,18 @@ physical-input-axis-motion-lefttrigger-minus = LTrigger
 physical-input-axis-motion-lefttrigger-plus = LTrigger
 physical-input-axis-motion-righttrigger-minus = RTrigger
 physical-input-axis-motion-righttrigger-plus = RTrigger
+
+input-device-keyboard = Keyboard
+input-device-polling-interval = Polling every { $interval } ms
+    .unknown = Move the controller to measure polling
+input-device-not-tested = Latency not tested
+input-device-test-latency = Test latency
+
+input-latency-test = Input latency test
+input-latency-test-progress = Trial { $current } of { $total }
+input-latency-test-early-presses = { $count } early { $count ->
+        [one] press
+       *[other] presses
+    } ignored
+input-latency-test-prompt = Press any button as soon as this lights up
+input-latency-summary = Median { $median } (min { $min }, max { $max })
@@ -1,9 +1,47 @@
 crash = Oops, Tango has encountered an error and has crashed!
 
-    When reporting this crash, please include the following log file:
+    When reporting this crash, please include the following:
 
     {$path}
 
 crash-no-log = Oops, Tango has encountered an error and has crashed!
 
     {$error}
+
+crash-report = Tango crashed last time
+    .reveal = Show in file manager
+    .dismiss = Dismiss
+crash-report-description =
+    Sorry about that! A crash report was saved to:
+
+    { $path }
+
+    If you report this crash, please attach this folder. It contains logs and the replay of the round in progress, but never your save files. Nothing has been sent anywhere.
+
+file-drop-ignored-session = Dropped files are ignored while a game is running.
+file-drop-ignored-netplay = Dropped files are ignored while connecting to or in a lobby with an opponent.
+file-drop-imported = Imported:
+file-drop-imported-save = opened in the save viewer
+file-drop-imported-save-copied = copied to your saves and opened in the save viewer
+file-drop-imported-replay = opened in the replay viewer
+file-drop-imported-patch = opened for installing
+file-drop-imported-rom = copied to your ROMs
+file-drop-rejected = Not imported:
+file-drop-rejected-unsupported = not a save, replay, patch or ROM
+file-drop-rejected-unreadable = couldn't be read: { $error }
+file-drop-rejected-not-a-save = not a save for any supported game
+file-drop-rejected-not-a-rom = not a supported game: { $error }
+file-drop-rejected-no-rom = you don't have a copy of { $game }
+file-drop-rejected-unsupported-game = recorded with a game this version of Tango doesn't support
+file-drop-rejected-invalid-replay = not a valid replay: { $error }
+file-drop-rejected-missing-patch = needs { $patch_name } v{ $patch_version }, which you don't have
+file-drop-rejected-already-watching = only one replay can be watched at a time
+file-drop-rejected-copy-failed = couldn't be copied: { $error }
+file-drop-rejected-declined = not copied
+file-drop-copy = Copy
+    .confirm = Copy
+    .cancel = Don't copy
+file-drop-copy-save = Copy save?
+    .description = { $file_name } is a save for { $game }. Copy it into your saves folder so it shows up with the rest?
+file-drop-copy-rom = Copy ROM?
+    .description = { $file_name } is { $game }. Copy it into your ROMs folder so it can be played?
@@ -0,0 +1,18 @@
+opponents = Opponents
+
+opponents-search = Search nicknames and notes...
+opponents-empty = You haven't played anyone yet.
+opponents-record = { $wins }W { $losses }L { $draws }D
+opponents-last-played = Last played { $date }
+opponents-never-played = Not played yet
+opponents-also-known-as = Also played as: { $nicknames }
+opponents-pinned = Pinned
+    .tooltip = Results are only recorded here without asking if they play from an installation they've played from before. Anyone else using this nickname has to be confirmed first.
+opponents-notes = Notes
+    .placeholder = e.g. likes PA folders, bans FstGauge
+opponents-merge = Merge into
+    .confirm = Merge
+    .tooltip = For when someone renamed and was added again: their record and notes are combined into the other entry.
+opponents-remove = Remove
+    .confirm = Really remove
+    .cancel = Cancel
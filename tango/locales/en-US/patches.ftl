@@ -2,9 +2,46 @@ patches = Patches
 
 patches-open-folder = Open folder
 patches-update = Update
+patches-create = Create patch
+patches-install = Install patch
+
+create-patch = Create patch
+create-patch-base-game = Base game
+create-patch-modified-rom = Modified ROM
+    .choose = Choose…
+create-patch-name = Patch name
+create-patch-version = Version
+    .invalid = Not a valid version
+create-patch-create = Create
+create-patch-error = Failed to create patch: { $error }
+
+install-patch = Install patch
+install-patch-description = Choose a .zip containing a patch's info.toml and its versions. You can also drop a .zip onto the window.
+install-patch-choose = Choose…
+install-patch-install = Install
+install-patch-error = Failed to install patch: { $error }
 
 patches-details-authors = Authors
 patches-details-license = License
     .all-rights-reserved = All rights reserved
+    .unrecognized = Not a recognized license. It's shown here as the patch gives it.
 patches-details-source = Website
-patches-details-games = Supported games
+patches-details-versions = Versions
+patches-details-netplay-compatibility = Netplay compatibility: { $netplay_compatibility }
+patches-details-requires = Requires: { $patches }
+patches-details-conflicts = Conflicts with: { $patches }
+patches-details-includes = Includes: { $patches }
+patch-dependency-missing = { $dependency } is not installed
+patch-dependency-conflict = { $patch_name } conflicts with { $dependency }
+patch-dependency-cycle = { $patch_name } requires itself
+patches-details-rom = ROM
+    .present = You have this game.
+    .missing = You don't have this game.
+patches-validate = Validate
+    .description = Tries applying this version to your ROMs, to check ahead of time that it'll work.
+patches-validation-no-roms = None of your ROMs are supported by this version.
+patches-validation-applies = { $game_name }: applies cleanly
+patches-validation-source-checksum-mismatch = { $game_name }: your ROM isn't the one this patch was made for
+patches-validation-target-checksum-mismatch = { $game_name }: the patched ROM doesn't come out as expected
+patches-validation-failed = { $game_name }: failed to apply: { $error }
+patches-validation-stale = { $game_name }: the patch has changed since it was validated
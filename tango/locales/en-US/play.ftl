@@ -2,42 +2,222 @@ play = Play
 
 play-play = Play
 play-fight = Fight!
+    .banned-nickname = Your nickname contains "{ $word }", which isn't allowed here. Change it in the settings to fight.
 play-leave = Leave
 play-random = Generate random code
 play-ready = I'm ready!
+play-save-requirements-unmet = This save doesn't meet the requirements of the selected patch:
+play-save-requirements-blocking = This save doesn't meet a requirement the selected patch can't be played without.
+play-save-changed-on-disk = 💾 Save changed on disk
+    .description = Your save file has changed since you selected it. It will be read again when you're ready.
+play-save-reload-error-io = Couldn't read your save again: { $error }
+play-save-reload-error-invalid = Your save file has changed and is no longer a valid save for the selected game.
+play-save-reload-error-illegal = Your save has values that can't be obtained in the game, so it can't be used for netplay: { $error }
+play-save-requirement-flag = Story progress (flag { $flag }) must be reached
+play-save-requirement-library-count = At least { $count } chips must be in the library
+play-save-requirement-banned-chips = The equipped folder can't contain: { $chips }
+play-save-requirement-hard = (required)
+play-save-requirement-unchecked = (couldn't be checked for this game)
+play-save-migration = This save was last played with v{ $from } of this patch, which numbers some chips, NaviCust parts or modcards differently.
+    .run = Migrate save to v{ $to }
+    .unmapped = It can't be migrated, as v{ $to } has nothing that replaces: { $ids }
+    .done = Your save was migrated. The original was backed up to { $backup }.
+    .error = Couldn't migrate your save: { $error }
 play-link-code = Link code
+    .too-long = Link codes can be at most { $max } characters long.
+    .invalid-character = Link codes can only contain letters, numbers and dashes, not "{ $char }".
+play-share = Share link code
+    .description = Send this link to your opponent: opening it will connect them to you straight away. It has also been copied to your clipboard.
+    .hidden = The link is hidden in streamer mode.
+    .copy = Copy
+play-direct-connect = Direct connect
+    .description = Play someone on the same local network without going through the matchmaking server. One of you hosts and the other joins by address.
+play-direct-connect-port = Port
+play-direct-connect-host = Host
+play-direct-connect-address = Host address, e.g. 192.168.1.23:12639
+play-direct-connect-join = Join
+play-direct-connect-addresses = Your opponent can join you at:
+play-direct-connect-no-addresses = Couldn't work out your local network address. Check your computer's network settings for it.
 play-no-game = None
 play-no-patch = None
+play-patch-requires-tango = Requires Tango { $version } or newer.
+play-patch-version-suggestion = Your opponent doesn't have v{ $version } of this patch.
+    .switch = Switch to v{ $version }
+    .none = You and your opponent don't have any version of this patch in common.
+play-patch-details = Patch details
+play-patch-apply-error = Couldn't apply { $patch_name }: { $error }
+play-patch-unmet-dependencies = Couldn't select { $patch_name }:
+    { $problems }
+play-patch-fetching-dependencies = Checking the patch repository for missing patches…
+play-presets = Preset
+    .none = None
+    .save = Save as preset
+    .copy = Copy preset to clipboard
+    .paste = Add preset from clipboard
+    .delete = Delete preset
+play-presets-save-name = Preset name
+    .exists = A preset with this name already exists and will be replaced.
+play-presets-save-confirm = Save
+play-presets-restore-error = Couldn't restore preset "{ $name }":
+play-presets-import-error = Couldn't read the preset: { $error }
+play-presets-problem-unknown-game = The game { $family } (variant { $variant }) isn't supported by this version of Tango.
+play-presets-problem-no-rom = You don't have a copy of { $game_name }.
+play-presets-problem-no-save = The save { $path } is missing.
+play-presets-problem-no-patch = You don't have the patch { $patch_name }.
+play-presets-problem-no-patch-version = You don't have a usable copy of v{ $patch_version } of { $patch_name }.
+play-presets-problem-patch-does-not-apply = { $patch_name } v{ $patch_version } couldn't be applied to your ROM.
+play-presets-problem-unsupported-match-type = The match type isn't available for this game.
 play-you = You
+play-remote-client-info = Version: { $version }
+    .platform = Platform: { $platform }
+    .release = Release build
+    .dev = Development build
+    .unknown = unknown
+play-latency-percentiles = Round trip time over the last { $window } pings: { $p50 }ms median, { $p95 }ms 95th percentile, { $p99 }ms 99th percentile
 play-cancel = Cancel
 
 play-details-game = Game
     .unrecognized = Unknown
 play-details-match-type = Match type
 play-details-reveal-setup = Reveal setup
+play-details-round-time-limit = Round time limit
+    .none = None
+    .description = When time runs out, the navi with less HP is deleted. Only some games support this.
+play-capabilities = Netplay support:
+    .rollback = Rollback
+    .opponent-name-replacement = Opponent nickname in game
+    .round-time-limit = Round time limit
+    .desync-detection = Desync detection
+play-details-replay-recording = Replays
+    .description = Whether this match may be recorded. If either side says never, neither side records it.
+    .always = Always record
+    .ask = Ask before each match
+    .never = Never record
+    .not-recorded = This match won't be recorded.
+play-replay-consent = Record this match?
+    .description = Your opponent is fine with this match being recorded. Do you want a replay of it to be saved?
+    .allow = Record
+    .deny = Don't record
+desync-risk = Desync risk
+    .description = An estimate of how likely this match is to desync, from the connection, both sides' games and emulators, and past matches against this opponent. It's only advice: nothing stops you from playing.
+    .low = Low desync risk
+    .elevated = Some desync risk
+    .high = High desync risk
+    .no-factors = Nothing known makes this match likely to desync.
+desync-risk-confirm = High desync risk
+    .description = This match looks likely to desync. Ready up anyway?
+    .ready-anyway = Ready anyway
+    .cancel = Cancel
+desync-risk-factor-measuring-connection = The connection is still being measured.
+    .advice = Wait a few seconds for the estimate to take the connection into account.
+desync-risk-factor-latency = The connection is slow ({ $p50 } ms round trip).
+    .advice = Use a wired connection, close anything else using the network, or raise the input delay.
+desync-risk-factor-jitter = The connection is unsteady (spikes of { $spread } ms over the usual round trip).
+    .advice = Use a wired connection and close anything else using the network. Unsteady connections often come from Wi-Fi or packet loss.
+desync-risk-factor-no-rollback = { $game_name } can't be fully rolled back.
+    .advice = Raise the input delay so fewer inputs have to be predicted.
+desync-risk-factor-rom-mismatch = Your opponent's game doesn't come out the same here as it does for them.
+    .advice = Make sure both of you have the same dump of the game and the same version of any patch.
+desync-risk-factor-unknown-configuration = Your or your opponent's emulator configuration couldn't be checked.
+    .advice = Make sure both of you are on the latest version of Tango.
+desync-risk-factor-configuration-mismatch = Emulator configuration does not match the opponent's: { $differences }
+    .advice = Make sure both of you are using the same BIOS, or neither of you is.
+desync-risk-factor-desync-history = { $desyncs } of { $rounds } rounds against this opponent have desynced.
+    .advice = Check your game, patches and emulator configuration against your opponent's.
+play-unsupported-match-type = Unsupported match type
+    .description = { $match_type } is known not to work properly with netplay for this game. Use it anyway?
+    .confirm = Use anyway
 play-details-input-delay = Input delay
     .suggest = Suggest
+    .preview = Preview
+play-details-input-delay-handicap = Input delay handicap
+    .description = Extra input delay you take on so your opponent doesn't have to, e.g. if their connection is much worse than yours. Both sides see each other's delay before starting.
+    .total = { $input_delay } + { $input_delay_handicap } = { $total } frames
+
+play-delay-preview = Delay preview
+    .configured = Input delay: { $delay } frames
+    .suggested = Suggested for this connection: { $delay } frames
+    .description = Use your controls: the cursor moves with the delay you've picked, the way your inputs will arrive in the match.
+    .pressed = Pressed:
+    .delayed = Delayed:
 
 play-connection-task-starting = Starting connection...
 play-connection-task-signaling = Connecting to matchmaking server...
 play-connection-task-waiting = Waiting for opponent...
+play-connection-task-cancelling = Cancelling...
+play-connection-task-listening = Waiting for opponent to connect...
+play-connection-task-dialing = Connecting to { $addr }...
+play-connection-task-exchanging = Starting match... (sent { $sent }/{ $total }, received { $received }/{ $total })
+
+notification-opponent-joined = { $nickname } joined the lobby.
+notification-opponent-compatible = { $nickname }'s settings are now compatible with yours.
+notification-opponent-ready = { $nickname } is ready!
+notification-audio-device-lost = The audio device was disconnected. Tango will keep trying to reconnect.
+notification-audio-device-restored = Audio is playing on { $device } again.
+    .default = the default device
 
 select-save = Select save
     .select = Select
     .no-save-selected = No save selected
-    .return-to-games-list = Return to games list
     .open-folder = Open folder
+    .rescan = Rescan
+    .search = Search
+    .no-saves = No saves found
+    .unrecognized = Unrecognized files
+    .duplicate-roms = Duplicate ROMs detected
+    .duplicate-saves = Duplicate saves detected
+    .duplicates-canonical = Using { $path }, with the same contents as:
+    .duplicates-move = Move copies into the "{ $dir }" folder
+    .duplicate = Duplicate
+    .rename = Rename
+    .trash = Move to trash
+    .reveal = Show in folder
+    .in-use = This save is in use in the lobby.
+    .trashed = Moved { $path } to the trash.
+    .undo = Undo
+    .error = Couldn't change save files: { $error }
+
+select-save-history = { $matches ->
+    [one] 1 match
+   *[other] { $matches } matches
+}
+    .last-used = Last used { $time }
+    .patches = Played with:
+    .matches = Matches:
+    .match = { $time }: vs { $nickname } @ { $link_code }
+    .more-matches = ...and { $count } more
+    .replays = { $count ->
+        [one] 1 replay
+       *[other] { $count } replays
+    }
+
+select-save-rename-error-empty = The name can't be empty.
+select-save-rename-error-illegal-char = The name can't contain "{ $char }".
+select-save-rename-error-exists = There's already a save with that name.
 
 lobby-issue-no-local-rom = You do not have a copy of the opponent's game: {$game_name}
 lobby-issue-no-remote-rom = The opponent does not have a copy of the game: {$game_name}
 lobby-issue-no-local-patch = You do not have a copy of the opponent's patch: {$patch_name} v{$patch_version}
 lobby-issue-no-remote-patch = The opponent does not have a copy of the patch: {$patch_name} v{$patch_version}
 lobby-issue-no-remote-patches = The opponent does not have any copy of the patch: {$patch_name}
+lobby-issue-patch-does-not-apply = This patch was validated against your copy of the game, and does not apply to it.
+lobby-issue-unmet-patch-dependencies = This patch needs other patches that can't be applied with it:
+    { $problems }
+lobby-issue-patch-requirements-mismatch = The patches applied along with { $patch_name } are different from the opponent's.
 lobby-issue-unrecognized-game = The opponent selected an unrecognized game.
 lobby-issue-incompatible = Game is not compatible with the opponent's.
 lobby-issue-match-type-mismatch = Match type does not match the opponent's.
+lobby-issue-round-time-limit-mismatch = Round time limit does not match the opponent's.
 lobby-issue-no-local-selection = You have not selected a game.
 lobby-issue-no-remote-selection = The opponent has not selected a game.
+lobby-issue-missing-remote-capabilities = The opponent's version of Tango doesn't support: { $capabilities }
+lobby-issue-configuration-mismatch = Emulator configuration does not match the opponent's: { $differences }
+
+configuration-difference-unset = unset
+configuration-difference-bios-emulated = emulated
+configuration-difference-core-version = core version { $local } vs. { $remote }
+configuration-difference-option = { $key } { $local } vs. { $remote }
+configuration-difference-bios = BIOS { $local } vs. { $remote }
 
 opponent-setup = Opponent's setup
 own-setup = Own setup
@@ -46,7 +226,116 @@ connection-error = Connection error
 connection-error-remote-protocol-version-too-old = Unable to connect to the other player: they are using an older version of Tango.
 connection-error-remote-protocol-version-too-new = The other player is using a newer version of Tango. Please update.
 connection-error-eof = The other player disconnected.
+connection-error-connected-to-self = You've connected to yourself: this link code was also joined by another Tango running with your settings. Use a different code, or have your opponent join it instead.
+connection-error-illegal-remote-save = Your opponent's save has values that can't be obtained in the game, so the match was not started: { $error }
+connection-error-invalid-remote-state = Your opponent sent invalid data, so the match was not started: { $error }
+connection-error-remote-cancelled = Your opponent cancelled before the match could start.
+connection-error-proxy-unreachable = Couldn't reach your proxy at { $proxy }, so the matchmaking server couldn't be reached either. Check the proxy in the network settings. ({ $error })
+connection-error-proxy-failed = Your proxy at { $proxy } was reached, but it couldn't connect to the matchmaking server: { $error }
+connection-error-server-unreachable = Couldn't reach the matchmaking server. If your network only lets you out through a proxy, set it in the network settings. ({ $error })
+connection-error-match-start-timed-out = Timed out waiting for your opponent's data to start the match ({ $received }/{ $total } chunks received).
 connection-error-other = A connection error has occurred: { $error }
 connection-error-confirm = Damn!
 
+match-timer-match = Match { $time }
+match-timer-round = Round { $round }: { $time }
+match-timer-round-limited = Round { $round }: { $time } / { $limit }
+match-not-recorded = Not recorded
+shadow-view = Shadow at tick { $tick } ({ $behind } behind)
+    .frame-hash = Frame { $hash }
+    .state-matches = State matched at tick { $tick }
+    .state-diverged = State diverged at tick { $tick }
+    .state-unknown = No state compared yet
+
+scoreboard-draws = { $draws ->
+    [one] 1 draw
+   *[other] { $draws } draws
+}
+scoreboard-round = Round { $round }
+
+desync-detected = Desync detected
+desync-detected-description = Your game and the opponent's stopped matching in round { $round } at tick { $tick }, so the match has been stopped. The replay of this round has been kept.
+
+system-suspended = System suspended
+system-suspended-description = The system was suspended or its clock jumped, so the game has been paused.
+system-suspended-description-pvp = The system was suspended or its clock jumped. The connection to your opponent may have dropped.
+system-suspended-continue = Continue
+session-hung = Not responding
+session-hung-description = The game hasn't moved in { $seconds } seconds. It may still come back, or you can end it.
+session-hung-description-pvp = The game hasn't moved in { $seconds } seconds. It may still come back, or you can end the match: your opponent will be told you forfeited, and the replay of this round will be kept as far as it got.
+session-hung-keep-waiting = Keep waiting
+session-hung-end = End
+session-unfocused-netplay = Tango isn't focused: your controller still plays, but keyboard input doesn't reach the game.
+
+match-summary = Match summary
+match-summary-ending-forfeited = You ended the match early. Only finished rounds are shown.
+match-summary-ending-opponent-forfeited = Your opponent ended the match early. Only finished rounds are shown.
+match-summary-ending-disconnected = The connection to your opponent was lost partway through a round. Only finished rounds are shown.
+match-summary-ending-desynced = The match was stopped because your game and the opponent's stopped matching in round { $round }. Only finished rounds are shown.
+match-summary-round = Round { $round }
+match-summary-result-win = Win
+match-summary-result-loss = Loss
+match-summary-result-draw = Draw
+match-summary-replay-play = Watch replay
+match-summary-replay-reveal = Show in folder
+match-summary-replay-error = Couldn't open the replay: { $error }
+match-summary-duration = Duration
+match-summary-latency = Latency
+match-summary-latency-value = { $mean }ms average, { $p95 }ms 95th percentile
+match-summary-rollback = Rollback
+match-summary-rollback-value = { $ticks } frames rolled back
+match-summary-verify-fairness = Verify fairness
+    .tooltip = Check that neither side could have rigged the match's randomness, using the report saved with its replays.
+match-summary-fairness-verified = Fair: checked { $rounds } rounds against their replays.
+match-summary-fairness-failed = Fairness check failed: { $error }
+match-summary-rematch = Rematch
+    .tooltip = Joins the same link code again. Your opponent has to join it too.
+match-summary-save-opponent = Save opponent
+match-summary-opponent-saved = Opponent saved
+match-summary-upload-replays = Upload this match's replays
+    .tooltip = Replays are uploaded to the endpoint set in the settings once this summary is closed.
+match-summary-close = Close
+replay-upload-uploaded = Uploaded a replay. { $remaining ->
+    [0] All replays are uploaded.
+    [one] 1 replay is left to upload.
+   *[other] { $remaining } replays are left to upload.
+}
+replay-upload-retrying = Couldn't upload replays, will keep trying: { $error }
+replay-upload-gave-up = Gave up uploading { $filename }: { $error }
+match-summary-dismiss-hint = Press any key to close.
+
 play-show-link-code = Show link code
+
+practice-overlay = Practice
+practice-overlay-not-in-battle = Not in battle.
+practice-overlay-custom-gauge-frames-remaining = Custom gauge (frames left)
+practice-overlay-player = You
+practice-overlay-opponent = Opponent
+practice-overlay-hp = HP
+practice-overlay-invulnerability-frames = Invulnerability (frames)
+practice-overlay-chip-in-hand = Chip in hand
+    .none = None
+practice-overlay-log-to-csv = Log to CSV
+practice-panel = Practice tools
+practice-panel-fill-custom-gauge = Fill custom gauge
+practice-panel-set-hp = Set HP
+practice-panel-give-chip = Give chip
+practice-panel-freeze-rng = Freeze RNG
+    .tooltip = Keeps every random roll the same as when this was turned on.
+practice-panel-description = Changes are only made during battles.
+practice-modified = MODIFIED
+
+save-states = Save states
+save-states-slot = Slot { $slot }
+save-states-empty = Empty
+save-states-save = Save
+save-states-load = Load
+save-states-error = Failed: { $error }
+save-states-patch-version-mismatch = Slot { $slot } was saved with version { $saved } of this patch, but you are playing version { $current }. Loading it may not work correctly.
+    .load-anyway = Load anyway
+    .cancel = Cancel
+
+play-opponent-unknown = You haven't played { $nickname } before.
+play-opponent-candidates = More than one person has played as { $nickname }. Who is this?
+    .pinned = { $nickname } is pinned, but this isn't an installation they've played from before. Is it them?
+    .someone-new = Someone new
@@ -3,6 +3,31 @@ replays = Replays
 replays-export = Export
 replays-play = Play
 replays-scanning = Scanning...
+replays-search = Search notes, tags, nicknames, games...
+replays-delete = Delete
+    .confirm = Really delete
+    .cancel = Cancel
+replays-save = Save:
+    .unknown = Not recorded
+    .missing = { $path } (no longer there)
+    .show = Show this save in the save list
+replays-tags = Tags:
+    .add = Add a tag
+replays-notes = Notes
+    .placeholder = e.g. game 3, the one with the comeback
+replays-dump-state = Dump state at tick
+    .final-tick = of { $tick }
+    .run = Choose folder and dump
+    .done = Dumped to { $path }
+    .error = Couldn't dump state: { $error }
+replays-view-setups = View setups
+replays-setups =
+    .error = Couldn't load this setup: { $error }
+    .missing-patch = { $name } { $version } isn't installed anymore, so this is shown with the unpatched game's names and icons, which may not match.
+    .export = Export
+    .export-description = Write this save to a new file. It's never written over one that's already there.
+    .export-done = Exported to { $path }
+    .export-error = Couldn't export: { $error }
 
 replay-subtitle = {$game_family} @ {$link_code}: vs {$nickname}
 
@@ -17,5 +42,14 @@ replays-export-confirm-success = Yay!
 replays-export-confirm-error = Damn!
 
 replay-viewer-pause = Pause
+replay-viewer-step-back = Step back
 replay-viewer-step = Step
 replay-viewer-speed = Speed
+replay-viewer-loop-start = Set loop start (A) here
+replay-viewer-loop-end = Set loop end (B) here
+replay-viewer-loop-clear = Clear loop
+replay-viewer-frame = Frame { $frame } / { $total }
+replay-viewer-rollback-depth = Rollback: { $depth ->
+    [one] 1 frame
+   *[other] { $depth } frames
+}
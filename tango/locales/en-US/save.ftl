@@ -9,6 +9,8 @@ save-cover-description = This tab intentionally left blank.
 
 save-group = Group by chip
 
+save-modcard-unknown = Unknown modcard (#{ $id })
+
 dark-ai-secondary-standard-chips = Standard chips (secondary)
 dark-ai-standard-chips = Standard chips
 dark-ai-mega-chips = Mega chips
@@ -19,3 +21,38 @@ dark-ai-unset = Unset
 
 copy-navicust-image-to-clipboard = Copy NaviCust image to clipboard
     .copied = Copied!
+
+save-changes = Save changes?
+    .overwrite = Overwrite
+    .save-as-new = Save as new file
+    .discard = Discard changes
+save-changes-description = The game changed { $path } during this session:
+save-changes-summary-equipped-folder = Equipped folder changed from { $from } to { $to }
+save-changes-summary-folder = Folder { $folder }: { $added } chip(s) added, { $removed } chip(s) removed
+save-changes-summary-navi = Navi changed
+save-changes-summary-zenny = Zenny: { $from } → { $to } ({ $delta })
+save-changes-summary-bugfrags = BugFrags: { $from } → { $to } ({ $delta })
+save-changes-summary-other = Other changes that can't be summarized
+save-changes-error = Failed to write save: { $error }
+
+save-diff = Compare saves
+    .compare-with = Compare with...
+    .pick = Pick a save to compare { $path } with.
+    .cancel = Cancel
+    .no-differences = No differences found.
+save-diff-folder = Folder { $folder }
+save-diff-navicust = NaviCust parts
+save-diff-library = Library
+save-diff-navi = Navi: { $before } → { $after }
+save-diff-zenny = Zenny: { $before } → { $after }
+save-diff-bugfrags = BugFrags: { $before } → { $after }
+save-diff-unknown-chip = Unknown chip (#{ $id })
+
+copy-share-code = Copy share code
+paste-share-code = Paste share code
+share-code-imported = Showing the pasted share code instead of the save.
+share-code-dismiss = Back to save
+share-code-import-error = Couldn't read the share code: { $error }
+share-code-unmatched-unknown = Line { $line }: { $name } isn't in this game.
+share-code-unmatched-invalid-code = Line { $line }: { $name } has code { $code }, which isn't in this game.
+share-code-unmatched-out-of-bounds = Line { $line }: { $name } doesn't fit in the NaviCust.
@@ -4,7 +4,9 @@ settings-tab-general = General
 settings-tab-input = Input
 settings-tab-graphics = Graphics
 settings-tab-audio = Audio
+settings-tab-accessibility = Accessibility
 settings-tab-netplay = Netplay
+settings-tab-network = Network
 settings-tab-patches = Patches
 settings-tab-advanced = Advanced
 settings-tab-about = About
@@ -13,6 +15,8 @@ settings-theme = Theme
     .light = Light
     .dark = Dark
     .system = Follow system setting
+    .reload = Reload themes from the themes folder
+    .open-folder = Open themes folder
 
 settings-video-filter = Video filter
     .null = None
@@ -24,20 +28,92 @@ settings-max-scale = Max scale
     .unset = Unset
 
 settings-language = Language
+settings-game-data-language = Game data language
+    .override = Show game data in a different language
+    .tooltip = Chip names and other text in the save viewer are normally shown in the language of the game or patch. Pick a different language here to use names the patch provides for it instead.
 settings-nickname = Nickname
+    .banned-word = "{ $word }" isn't allowed in nicknames here.
 settings-streamer-mode = Streamer privacy mode
-    .tooltip = Enabling this mode will add an additional "Cover" tab to the save viewer that hides all information about your current save file.
+    .tooltip = Enabling this mode will add an additional "Cover" tab to the save viewer that hides all information about your current save file. During netplay, the custom screen is also covered while it's open in BN5 and BN6.
+    .enabled = Streamer privacy mode on
+    .disabled = Streamer privacy mode off
 settings-debug = Show debug information
+settings-shadow-view = Show shadow core
+    .tooltip = Shows what the shadow core, which plays your opponent's side, renders during matches. Takes effect from the next match.
+settings-log-levels = Log levels
+    .tooltip = How much each part of Tango writes to the log. Changes take effect immediately.
+    .open-viewer = Open log viewer
+settings-texture-cache-capacity = Save viewer texture cache size
+    .usage = { $entries } loaded (~{ $kib } KiB VRAM), { $loads } loads, { $evictions } evictions
+settings-hang-timeout = Emulator hang timeout
+    .tooltip = How long the game can go without moving before Tango offers to end it.
+settings-enable-broadcast-api = Enable broadcast API
+    .tooltip = Runs a local WebSocket server that publishes lobby and match events (nicknames, games, round results) for stream overlays. Inputs and save data are never exposed.
 settings-input-delay = Input delay
 settings-ui-scale = UI scale
 settings-max-queue-length = Max queue length
+settings-match-start-timeout = Match start timeout
+    .tooltip = How long to wait for your opponent's data once you're both ready before giving up.
+settings-replay-recording = Replay recording
+    .tooltip = What to tell opponents about recording matches. This can still be changed in the lobby.
+settings-desync-detection = Desync detection
+    .tooltip = Periodically compare battle state with the opponent and stop the match as soon as it stops matching.
 settings-matchmaking-endpoint = Matchmaking endpoint
 settings-replaycollector-endpoint = Replay collector endpoint
+settings-replay-upload-endpoint = Replay upload endpoint
+    .tooltip = Replays are uploaded here after each match, e.g. for a league that collects its matches' replays. Leave empty to not upload replays.
+    .invalid = Replays are only uploaded to HTTPS endpoints.
+settings-replay-upload-token = Replay upload token
+settings-replay-upload-include-notes = Include replay notes in uploads
+    .tooltip = Send the notes and tags you've written on replays along with them. Otherwise, they never leave this computer.
+settings-proxy-mode = Proxy
+    .direct = None
+    .system = Use system proxy settings
+    .manual = Manual
+settings-proxy-scheme = Proxy type
+settings-proxy-address = Proxy address
+settings-proxy-username = Proxy username
+    .none = None
+settings-proxy-password = Proxy password
+settings-network-test = Test connection
+    .run = Test
+    .succeeded = Reached the matchmaking server directly.
+    .succeeded-through-proxy = Reached the matchmaking server through { $proxy }.
+    .failed = { $error }
+settings-turn-servers = Extra TURN servers
+    .tooltip = Used to relay matches when you and your opponent can't connect to each other directly, in addition to the ones the matchmaking server provides.
+    .username = Username
+    .credential = Credential
+    .add = Add TURN server
 settings-patch-repo = Patches repository
 settings-enable-patch-autoupdate = Enable autoupdate
 settings-data-path = Data path
     .open = Open
     .change = Change
+settings-bios = GBA BIOS
+    .tooltip = Runs games on a real GBA BIOS instead of the emulated one. Your opponent has to use the same BIOS, or none.
+    .emulated = Emulated
+    .change = Choose…
+    .clear = Use emulated
+    .error = Couldn't use that BIOS: { $error }
+settings-setup = First-run setup
+    .run = Run again
+settings-profile = Config profile
+    .export = Export…
+    .import = Import…
+settings-profile-export = Export config profile
+    .description = Choose what to include. Paths are left out by default, as they're usually different on other machines.
+    .run = Export…
+settings-profile-import = Import config profile
+    .description = These settings will change:
+    .no-changes = This profile doesn't change any settings.
+    .apply = Apply
+    .cancel = Cancel
+    .error = Couldn't read the profile: { $error }
+settings-profile-section-general = General settings
+settings-profile-section-input = Input mapping
+settings-profile-section-hotkeys = Hotkeys
+settings-profile-section-paths = Paths
 settings-full-screen = Full screen
 settings-graphics-backend = Graphics backend (requires restart)
     .glutin = Glutin (safe)
@@ -46,7 +122,38 @@ settings-audio-backend = Audio backend (requires restart)
     .sdl2 = SDL2 (safe)
     .cpal = cpal (experimental)
 settings-volume = Volume
+settings-audio-device = Output device
+    .default = Default
+    .missing = { $device } (not connected)
+settings-notification-sound-volume = Notification sound volume
+    .mute = Mute
+settings-assist = Input assists
+    .description = These only apply in single player. Inputs in netplay are always sent exactly as pressed, and practice logs note which inputs were assisted.
+settings-assist-toggle-hold = Toggle hold
+    .tooltip = Press these buttons once to hold them down, and again to let go.
+settings-assist-turbo = Turbo
+    .tooltip = Holding these buttons presses them repeatedly.
+settings-assist-turbo-interval = Turbo interval
+    .suffix = { " " }frames
+settings-assist-slow-motion = Slow motion speed
+    .tooltip = How fast the game runs while the slow motion input is held. Bind it in the Input tab.
 settings-enable-updater = Enable updater
 settings-allow-prerelease-upgrades = Allow prerelease upgrades
 settings-show-own-setup = Show own setup
+settings-show-practice-overlay = Show practice overlay
+    .tooltip = In single player, show training data read from the game, such as HP and invulnerability frames. Only some games are supported.
+settings-pause-on-focus-loss = Pause when window loses focus
+    .tooltip = Pause single player games and replays while Tango isn't the focused window, counting down before resuming. Netplay matches are never paused.
 settings-integer-scaling = Integer scaling
+settings-vsync = V-sync (requires restart)
+    .tooltip = Wait for the display to refresh before showing each frame. Turning this off can reduce latency, but may cause tearing.
+settings-frame-interpolation = Frame interpolation
+    .tooltip = Blend between game frames on displays that refresh faster than the game runs. This smooths out motion, but shows everything one frame later.
+settings-notify-on-opponent-join = Notify when opponent joins
+settings-notify-on-opponent-compatible = Notify when opponent's settings become compatible
+settings-notify-on-opponent-ready = Notify when opponent is ready
+settings-save-write-back-policy = When a single player session changes the save
+    .ask = Ask
+    .discard = Discard changes
+    .overwrite = Overwrite the original
+    .save-as-new = Save as a new file
@@ -1,14 +1,40 @@
 welcome-heading = Welcome to Tango!
-welcome-description = There's just a few steps you'll need to complete before you can start playing.
+welcome-step = Step { $step } of { $steps }
 
-welcome-step-1 = Add your ROMs
-welcome-step-1-description = Make sure they're pristine, unpatched ROMs.
+welcome-directories = Choose where to keep your files
+welcome-directories-description = Tango keeps your ROMs, saves, patches and replays in folders inside its data folder. Any missing folders will be created for you.
+welcome-directories-roms = ROMs
+welcome-directories-saves = Saves
+welcome-directories-patches = Patches
+welcome-directories-replays = Replays
 
-welcome-step-2 = Add your saves
-welcome-step-2-description = Make sure they're compatible with the games you've put in the ROMs folder. They should be .sav or .sa1 files.
+welcome-scan = Check what was found
+welcome-scan-roms = ROMs
+    .missing = No ROMs were found. Put pristine, unpatched .gba ROMs, or .zip archives containing them, in the ROMs folder.
+    .game = { $game_name } ({ $saves ->
+        [one] 1 save
+       *[other] { $saves } saves
+    })
+welcome-scan-saves = Saves
+    .missing = No saves were found for any of your games. Put .sav or .sa1 files for the games you have ROMs for in the saves folder.
+welcome-scan-patches = Patches (optional)
+    .description = { $count ->
+        [one] 1 patch was found.
+       *[other] { $count } patches were found.
+    } Patches can also be installed later.
 
-welcome-step-3 = Set your nickname
-welcome-step-3-description = You can change this at any time.
+welcome-profile = Set up your profile
+welcome-profile-nickname =
+    .description = This is what your opponents see. You can change it at any time.
+    .missing = Please enter a nickname.
+    .banned-word = "{ $word }" isn't allowed in nicknames here.
+
+welcome-check = Check your audio and controls (optional)
+welcome-check-description = You can change these at any time in settings.
+welcome-check-play-sound = Play a test sound
 
 welcome-open-folder = Open folder
-welcome-continue = I'm done!
+welcome-rescan = Scan again
+welcome-back = Back
+welcome-continue = Continue
+welcome-finish = Finish
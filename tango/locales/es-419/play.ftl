@@ -21,7 +21,6 @@ play-connection-task-waiting = Esperando al oponente...
 select-save = Seleccione partida guardada
     .select = Seleccionar
     .no-save-selected = No guardar seleccionado
-    .return-to-games-list = Volver a la lista de juegos
     .open-folder = Abrir carpeta
 lobby-issue-no-local-rom = No tienes una copia del juego del oponente: { $game_name }
 lobby-issue-no-remote-rom = El oponente no tiene una copia del juego: { $game_name }
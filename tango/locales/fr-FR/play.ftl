@@ -21,7 +21,6 @@ play-connection-task-waiting = Waiting for opponent...
 select-save = Sélectionner un fichier de sauvegarde
     .select = Sélectionner
     .no-save-selected = Aucun fichier de sauvegarde sélectionné
-    .return-to-games-list = Retourner à la liste des jeux
     .open-folder = Ouvrir le dossier
 lobby-issue-no-local-rom = You do not have a copy of the opponent's game: { $game_name }
 lobby-issue-no-remote-rom = The opponent does not have a copy of the game: { $game_name }
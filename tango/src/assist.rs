@@ -0,0 +1,105 @@
+/// Input assists for players who find holding or mashing buttons hard.
+///
+/// They only ever apply to single player sessions. Netplay inputs go to the opponent exactly as they were pressed, so replays of netplay are unaffected too.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Settings {
+    /// GBA keys that are held down by pressing them once, and let go of by pressing them again.
+    pub toggle_hold_keys: u32,
+    /// GBA keys that are pressed and released over and over while held.
+    pub turbo_keys: u32,
+    /// How many frames turbo keys stay pressed, and then released, for.
+    pub turbo_interval_frames: u32,
+    /// How fast the game runs while the slow motion input is held, in percent of full speed.
+    pub slow_motion_percent: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            toggle_hold_keys: 0,
+            turbo_keys: 0,
+            turbo_interval_frames: 2,
+            slow_motion_percent: 50,
+        }
+    }
+}
+
+pub const MIN_SLOW_MOTION_PERCENT: u32 = 10;
+pub const MAX_SLOW_MOTION_PERCENT: u32 = 90;
+pub const MAX_TURBO_INTERVAL_FRAMES: u32 = 30;
+
+/// What the assists did on a frame, so practice logs show which inputs weren't pressed as they were sent.
+#[derive(Clone, Copy, Debug, Default, serde::Serialize)]
+pub struct Applied {
+    /// The keys the assists pressed or let go of on the player's behalf.
+    pub keys: u32,
+    pub slow_motion: bool,
+}
+
+/// Turns the keys the player is holding into the keys sent to the game, frame by frame.
+pub struct Transform {
+    settings: Settings,
+    slow_motion: bool,
+    last_keys: u32,
+    toggled_keys: u32,
+    /// Frames since a turbo key was first held, so each press starts out pressed.
+    turbo_frame: u32,
+    applied: Applied,
+}
+
+impl Transform {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            slow_motion: false,
+            last_keys: 0,
+            toggled_keys: 0,
+            turbo_frame: 0,
+            applied: Applied::default(),
+        }
+    }
+
+    /// Changes the settings, letting go of any toggled keys that no longer toggle.
+    pub fn set_settings(&mut self, settings: &Settings) {
+        if self.settings == *settings {
+            return;
+        }
+        self.settings = settings.clone();
+        self.toggled_keys &= self.settings.toggle_hold_keys;
+    }
+
+    /// Notes whether slow motion is on. The session's speed is changed by whoever calls this: this is only for the record.
+    pub fn set_slow_motion(&mut self, slow_motion: bool) {
+        self.slow_motion = slow_motion;
+    }
+
+    /// Applies the assists to the keys held on a frame. This must be called exactly once per frame.
+    pub fn apply(&mut self, keys: u32) -> u32 {
+        let pressed = keys & !self.last_keys;
+        self.last_keys = keys;
+
+        self.toggled_keys ^= pressed & self.settings.toggle_hold_keys;
+        let mut assisted_keys = (keys & !self.settings.toggle_hold_keys) | self.toggled_keys;
+
+        if keys & self.settings.turbo_keys == 0 {
+            self.turbo_frame = 0;
+        } else {
+            let interval = self.settings.turbo_interval_frames.max(1);
+            if (self.turbo_frame / interval) % 2 == 1 {
+                assisted_keys &= !self.settings.turbo_keys;
+            }
+            self.turbo_frame = self.turbo_frame.wrapping_add(1);
+        }
+
+        self.applied = Applied {
+            keys: keys ^ assisted_keys,
+            slow_motion: self.slow_motion,
+        };
+        assisted_keys
+    }
+
+    pub fn applied(&self) -> Applied {
+        self.applied
+    }
+}
@@ -23,10 +23,18 @@ impl Drop for Binding {
     }
 }
 
+/// A one-shot mono sound that is mixed on top of whatever stream is bound.
+struct Sound {
+    samples: &'static [i16],
+    volume: i32,
+    pos: usize,
+}
+
 #[derive(Clone)]
 pub struct LateBinder {
     sample_rate: u32,
     stream: std::sync::Arc<parking_lot::Mutex<Option<Box<dyn Stream + Send + 'static>>>>,
+    sound: std::sync::Arc<parking_lot::Mutex<Option<Sound>>>,
 }
 
 impl LateBinder {
@@ -34,9 +42,21 @@ impl LateBinder {
         Self {
             sample_rate,
             stream: std::sync::Arc::new(parking_lot::Mutex::new(None)),
+            sound: std::sync::Arc::new(parking_lot::Mutex::new(None)),
         }
     }
 
+    /// Plays a sound over the currently bound stream, replacing any sound that is already playing.
+    ///
+    /// The samples must be mono at the binder's sample rate. Volume is out of 0x100.
+    pub fn play_sound(&self, samples: &'static [i16], volume: i32) {
+        *self.sound.lock() = Some(Sound {
+            samples,
+            volume,
+            pos: 0,
+        });
+    }
+
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate
     }
@@ -54,16 +74,43 @@ impl LateBinder {
 
 impl Stream for LateBinder {
     fn fill(&mut self, buf: &mut [[i16; NUM_CHANNELS]]) -> usize {
-        let mut stream = self.stream.lock();
-        let stream = if let Some(stream) = &mut *stream {
-            stream
-        } else {
-            for v in buf.iter_mut() {
-                *v = [0, 0];
+        let n = {
+            let mut stream = self.stream.lock();
+            if let Some(stream) = &mut *stream {
+                stream.fill(buf)
+            } else {
+                for v in buf.iter_mut() {
+                    *v = [0, 0];
+                }
+                buf.len() / 2
             }
-            return buf.len() / 2;
         };
-        stream.fill(buf)
+
+        let mut sound_guard = self.sound.lock();
+        let sound = if let Some(sound) = &mut *sound_guard {
+            sound
+        } else {
+            return n;
+        };
+
+        // If we're mixing a sound in, we need to make sure the whole buffer is filled, otherwise the tail of the sound gets cut off.
+        for v in buf[n..].iter_mut() {
+            *v = [0, 0];
+        }
+
+        for (v, sample) in std::iter::zip(buf.iter_mut(), sound.samples[sound.pos..].iter()) {
+            let sample = *sample as i32 * sound.volume / 0x100;
+            for c in v.iter_mut() {
+                *c = (*c as i32 + sample).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            }
+            sound.pos += 1;
+        }
+
+        if sound.pos >= sound.samples.len() {
+            *sound_guard = None;
+        }
+
+        buf.len()
     }
 }
 
@@ -116,4 +163,270 @@ impl Stream for MGBAStream {
     }
 }
 
-pub trait Backend {}
+/// An output device a stream is playing on.
+pub trait Device {
+    /// Whether the device has stopped working, e.g. because it was unplugged.
+    fn is_lost(&self) -> bool;
+}
+
+/// An audio backend, which can open output devices.
+pub trait Backend {
+    fn output_device_names(&self) -> Vec<String>;
+
+    /// The name of the device the system currently uses by default, if the backend can tell.
+    fn default_output_device_name(&self) -> Option<String>;
+
+    /// Opens an output device by name, or the default one, and starts playing the stream on it.
+    fn open(
+        &self,
+        device_name: Option<&str>,
+        stream: Box<dyn Stream + Send + 'static>,
+    ) -> Result<Box<dyn Device>, anyhow::Error>;
+}
+
+/// How long streams take to fade in and out when switching devices, so switching doesn't pop.
+const FADE_DURATION: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// How often to try to reopen a lost device.
+const RECONNECT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How often to check whether a different device should be used, e.g. because the default device changed.
+const DEVICE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Wraps the binder for a device, fading in when it starts and out when it's asked to.
+struct FadingStream {
+    binder: LateBinder,
+    fading_out: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    gain: f32,
+    step: f32,
+}
+
+impl Stream for FadingStream {
+    fn fill(&mut self, buf: &mut [[i16; NUM_CHANNELS]]) -> usize {
+        let n = self.binder.fill(buf);
+        let target = if self.fading_out.load(std::sync::atomic::Ordering::Relaxed) {
+            0.0
+        } else {
+            1.0
+        };
+        if self.gain == target {
+            if target == 0.0 {
+                for v in buf[..n].iter_mut() {
+                    *v = [0, 0];
+                }
+            }
+            return n;
+        }
+        for v in buf[..n].iter_mut() {
+            self.gain = if self.gain < target {
+                (self.gain + self.step).min(target)
+            } else {
+                (self.gain - self.step).max(target)
+            };
+            for c in v.iter_mut() {
+                *c = (*c as f32 * self.gain) as i16;
+            }
+        }
+        n
+    }
+}
+
+/// Consumes samples in real time while there's no device to play them on, so the emulator never stalls waiting for audio to be taken.
+struct NullSink {
+    stop: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl NullSink {
+    fn spawn(mut binder: LateBinder) -> Self {
+        let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let thread = std::thread::spawn({
+            let stop = stop.clone();
+            move || {
+                const TICK: std::time::Duration = std::time::Duration::from_millis(10);
+                let mut buf = vec![];
+                let mut last = std::time::Instant::now();
+                while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                    std::thread::sleep(TICK);
+                    let now = std::time::Instant::now();
+                    let frames = ((now - last).as_secs_f32() * binder.sample_rate() as f32) as usize;
+                    last = now;
+                    buf.resize(frames.min(binder.sample_rate() as usize), [0i16; NUM_CHANNELS]);
+                    binder.fill(&mut buf);
+                }
+            }
+        });
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for NullSink {
+    fn drop(&mut self) {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct OpenDevice {
+    device: Box<dyn Device>,
+    /// The name of the device, if it's known.
+    name: Option<String>,
+    fading_out: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+#[derive(Debug, Clone)]
+pub enum OutputEvent {
+    DeviceLost,
+    DeviceRestored(Option<String>),
+}
+
+/// Keeps the binder playing on the selected output device, reopening it when the selection changes or the device is lost.
+///
+/// While no device is open, samples are consumed into the void so emulation keeps running.
+pub struct Output {
+    backend: Box<dyn Backend>,
+    binder: LateBinder,
+    selected_device_name: Option<String>,
+    device: Option<OpenDevice>,
+    null_sink: Option<NullSink>,
+    device_names: std::sync::Arc<parking_lot::Mutex<Vec<String>>>,
+    last_reconnect_at: std::time::Instant,
+    last_poll_at: std::time::Instant,
+}
+
+impl Output {
+    pub fn new(backend: Box<dyn Backend>, binder: LateBinder, selected_device_name: Option<String>) -> Self {
+        let now = std::time::Instant::now();
+        let mut output = Self {
+            device_names: std::sync::Arc::new(parking_lot::Mutex::new(backend.output_device_names())),
+            backend,
+            binder,
+            selected_device_name,
+            device: None,
+            null_sink: None,
+            last_reconnect_at: now,
+            last_poll_at: now,
+        };
+        if let Err(e) = output.open() {
+            log::error!("failed to open audio device: {:?}", e);
+            output.null_sink = Some(NullSink::spawn(output.binder.clone()));
+        }
+        output
+    }
+
+    /// The names of the available output devices, as of the last time they were checked.
+    pub fn device_names(&self) -> std::sync::Arc<parking_lot::Mutex<Vec<String>>> {
+        self.device_names.clone()
+    }
+
+    /// Whether there's currently no device to play on.
+    pub fn is_lost(&self) -> bool {
+        self.device.is_none()
+    }
+
+    /// The device that should be open: the selected one if it's available, otherwise the default.
+    fn wanted_device_name(&self) -> Option<String> {
+        self.selected_device_name
+            .as_ref()
+            .filter(|name| self.device_names.lock().contains(*name))
+            .cloned()
+            .or_else(|| self.backend.default_output_device_name())
+    }
+
+    fn open(&mut self) -> Result<(), anyhow::Error> {
+        let name = self.wanted_device_name();
+        let fading_out = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let device = self.backend.open(
+            name.as_deref(),
+            Box::new(FadingStream {
+                binder: self.binder.clone(),
+                fading_out: fading_out.clone(),
+                gain: 0.0,
+                step: 1.0 / (self.binder.sample_rate() as f32 * FADE_DURATION.as_secs_f32()),
+            }),
+        )?;
+        log::info!("opened audio device: {:?}", name);
+        self.null_sink = None;
+        self.device = Some(OpenDevice {
+            device,
+            name,
+            fading_out,
+        });
+        Ok(())
+    }
+
+    /// Closes the current device, fading it out first, and opens the wanted one.
+    fn reopen(&mut self) {
+        if let Some(device) = self.device.take() {
+            device.fading_out.store(true, std::sync::atomic::Ordering::Relaxed);
+            // Give the device's callback a chance to play the fade out before it's closed.
+            std::thread::sleep(FADE_DURATION * 2);
+        }
+        if let Err(e) = self.open() {
+            log::error!("failed to open audio device: {:?}", e);
+            self.null_sink = Some(NullSink::spawn(self.binder.clone()));
+        }
+    }
+
+    /// Checks on the device, switching to or reopening it as needed. This should be called regularly.
+    pub fn update(&mut self, selected_device_name: Option<&str>) -> Option<OutputEvent> {
+        let now = std::time::Instant::now();
+
+        if selected_device_name != self.selected_device_name.as_deref() {
+            self.selected_device_name = selected_device_name.map(|name| name.to_string());
+            *self.device_names.lock() = self.backend.output_device_names();
+            self.last_poll_at = now;
+            self.reopen();
+            return None;
+        }
+
+        if self
+            .device
+            .as_ref()
+            .map(|device| device.device.is_lost())
+            .unwrap_or(false)
+        {
+            log::warn!("audio device was lost");
+            self.device = None;
+            self.null_sink = Some(NullSink::spawn(self.binder.clone()));
+            self.last_reconnect_at = now;
+            return Some(OutputEvent::DeviceLost);
+        }
+
+        if self.device.is_none() {
+            if now.duration_since(self.last_reconnect_at) < RECONNECT_INTERVAL {
+                return None;
+            }
+            self.last_reconnect_at = now;
+            *self.device_names.lock() = self.backend.output_device_names();
+            return match self.open() {
+                Ok(()) => Some(OutputEvent::DeviceRestored(
+                    self.device.as_ref().and_then(|device| device.name.clone()),
+                )),
+                Err(e) => {
+                    log::warn!("failed to reopen audio device: {:?}", e);
+                    None
+                }
+            };
+        }
+
+        if now.duration_since(self.last_poll_at) >= DEVICE_POLL_INTERVAL {
+            self.last_poll_at = now;
+            *self.device_names.lock() = self.backend.output_device_names();
+            let wanted_device_name = self.wanted_device_name();
+            if wanted_device_name.is_some()
+                && wanted_device_name != self.device.as_ref().and_then(|device| device.name.clone())
+            {
+                log::info!("switching audio device to {:?}", wanted_device_name);
+                self.reopen();
+            }
+        }
+
+        None
+    }
+}
@@ -1,5 +1,5 @@
 use crate::audio;
-use cpal::traits::DeviceTrait;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 
 pub fn get_supported_config(device: &cpal::Device) -> anyhow::Result<cpal::SupportedStreamConfig> {
     let mut supported_configs = device.supported_output_configs()?.collect::<Vec<_>>();
@@ -21,8 +21,14 @@ fn open_stream(
     device: &cpal::Device,
     supported_config: &cpal::SupportedStreamConfig,
     mut stream: impl audio::Stream + Send + 'static,
+    lost: std::sync::Arc<std::sync::atomic::AtomicBool>,
 ) -> Result<cpal::Stream, anyhow::Error> {
-    let error_callback = |err| log::error!("audio stream error: {}", err);
+    let error_callback = move |err| {
+        log::error!("audio stream error: {}", err);
+        if let cpal::StreamError::DeviceNotAvailable = err {
+            lost.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+    };
     let config = supported_config.config();
     let channels = config.channels;
 
@@ -114,18 +120,58 @@ fn realign_samples(buf: &mut [i16], channels: u16) {
     }
 }
 
-pub struct Backend {
+pub struct Device {
     _audio_device: cpal::Device,
     _stream: cpal::Stream,
+    lost: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl audio::Device for Device {
+    fn is_lost(&self) -> bool {
+        self.lost.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+pub struct Backend {
+    host: cpal::Host,
 }
 
 impl Backend {
-    pub fn new(stream: impl audio::Stream + Send + 'static) -> Result<Self, anyhow::Error> {
-        use cpal::traits::{HostTrait, StreamTrait};
+    pub fn new() -> Self {
+        Self {
+            host: cpal::default_host(),
+        }
+    }
+}
+
+impl audio::Backend for Backend {
+    fn output_device_names(&self) -> Vec<String> {
+        match self.host.output_devices() {
+            Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+            Err(e) => {
+                log::warn!("failed to enumerate audio devices: {}", e);
+                vec![]
+            }
+        }
+    }
 
-        let audio_device = cpal::default_host()
-            .default_output_device()
-            .ok_or_else(|| anyhow::format_err!("could not open audio device"))?;
+    fn default_output_device_name(&self) -> Option<String> {
+        self.host.default_output_device().and_then(|device| device.name().ok())
+    }
+
+    fn open(
+        &self,
+        device_name: Option<&str>,
+        stream: Box<dyn audio::Stream + Send + 'static>,
+    ) -> Result<Box<dyn audio::Device>, anyhow::Error> {
+        let audio_device = if let Some(device_name) = device_name {
+            self.host
+                .output_devices()?
+                .find(|device| device.name().ok().as_deref() == Some(device_name))
+        } else {
+            self.host.default_output_device()
+        }
+        .ok_or_else(|| anyhow::format_err!("could not open audio device"))?;
         log::info!(
             "cpal supported audio output configs: {:?}",
             audio_device.supported_output_configs()?.collect::<Vec<_>>()
@@ -133,14 +179,14 @@ impl Backend {
         let audio_supported_config = audio::cpal::get_supported_config(&audio_device)?;
         log::info!("selected audio config: {:?}", audio_supported_config);
 
-        let stream = open_stream(&audio_device, &audio_supported_config, stream)?;
+        let lost = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let stream = open_stream(&audio_device, &audio_supported_config, stream, lost.clone())?;
         stream.play()?;
 
-        Ok(Self {
+        Ok(Box::new(Device {
             _audio_device: audio_device,
             _stream: stream,
-        })
+            lost,
+        }))
     }
 }
-
-impl audio::Backend for Backend {}
@@ -14,32 +14,58 @@ impl sdl2::audio::AudioCallback for StreamWrapper {
     }
 }
 
+pub struct Device {
+    audio_device: sdl2::audio::AudioDevice<StreamWrapper>,
+}
+
+impl audio::Device for Device {
+    fn is_lost(&self) -> bool {
+        // SDL stops devices that have been disconnected, but keeps calling their callbacks so samples keep being consumed.
+        self.audio_device.status() == sdl2::audio::AudioStatus::Stopped
+    }
+}
+
 pub struct Backend {
-    _audio_device: sdl2::audio::AudioDevice<StreamWrapper>,
+    audio: sdl2::AudioSubsystem,
 }
 
 impl Backend {
-    pub fn new(
-        audio: &sdl2::AudioSubsystem,
-        stream: impl audio::Stream + Send + 'static,
-    ) -> Result<Self, anyhow::Error> {
-        let audio_device = audio
+    pub fn new(audio: &sdl2::AudioSubsystem) -> Self {
+        Self { audio: audio.clone() }
+    }
+}
+
+impl audio::Backend for Backend {
+    fn output_device_names(&self) -> Vec<String> {
+        (0..self.audio.num_audio_playback_devices().unwrap_or(0))
+            .filter_map(|i| self.audio.audio_playback_device_name(i).ok())
+            .collect()
+    }
+
+    fn default_output_device_name(&self) -> Option<String> {
+        // SDL only follows the default device if it's opened without a name, so there's no name to switch to.
+        None
+    }
+
+    fn open(
+        &self,
+        device_name: Option<&str>,
+        stream: Box<dyn audio::Stream + Send + 'static>,
+    ) -> Result<Box<dyn audio::Device>, anyhow::Error> {
+        let audio_device = self
+            .audio
             .open_playback(
-                None,
+                device_name,
                 &sdl2::audio::AudioSpecDesired {
                     freq: Some(48000),
                     channels: Some(audio::NUM_CHANNELS as u8),
                     samples: Some(512),
                 },
-                |_| StreamWrapper(Box::new(stream)),
+                |_| StreamWrapper(stream),
             )
             .map_err(|e| anyhow::format_err!("{}", e))?;
         log::info!("sdl2 audio spec: {:?}", audio_device.spec());
         audio_device.resume();
-        Ok(Self {
-            _audio_device: audio_device,
-        })
+        Ok(Box::new(Device { audio_device }))
     }
 }
-
-impl audio::Backend for Backend {}
@@ -1,21 +1,157 @@
 use rand::Rng;
 
+use crate::broadcast;
 use crate::config;
+use crate::crashreport;
+use crate::determinism;
+use crate::fairness;
+use crate::fs;
 use crate::game;
 use crate::lockstep;
 use crate::net;
+use crate::opponents;
 use crate::replay;
 use crate::replayer;
+use crate::save;
 use crate::session;
 use crate::shadow;
 use crate::stats;
 
+/// How many checksums back a side's are kept waiting for the other side's. This is far more than either side falls behind by, so the other side's are never coming, e.g. because it has desync detection turned off.
+const STATE_HASH_WINDOWS: u32 = 4;
+
+/// The tick rounds are ended on, if the match has a round time limit.
+fn round_time_limit_tick(settings: &net::protocol::Settings) -> Option<u32> {
+    settings
+        .round_time_limit_secs
+        .map(|secs| secs * session::EXPECTED_FPS as u32)
+}
+
+/// Replays are only recorded if neither side asked not to be.
+fn records_replays(local_settings: &net::protocol::Settings, remote_settings: &net::protocol::Settings) -> bool {
+    local_settings.replay_recording != net::protocol::ReplayRecording::Never
+        && remote_settings.replay_recording != net::protocol::ReplayRecording::Never
+}
+
+fn input_delay_to_replay(settings: &net::protocol::Settings) -> replay::metadata::InputDelay {
+    replay::metadata::InputDelay {
+        base: settings.input_delay,
+        handicap: settings.input_delay_handicap,
+    }
+}
+
+fn client_info_to_replay(client_info: &net::protocol::ClientInfo) -> replay::metadata::ClientInfo {
+    replay::metadata::ClientInfo {
+        version: client_info.version.clone(),
+        platform: client_info.platform.clone(),
+        is_release: client_info.is_release,
+    }
+}
+
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum BattleResult {
     Loss,
     Win,
 }
 
+/// A match's running score, from the local side's point of view.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Score {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+/// How often the opponent's packets were guessed wrong before they arrived, for judging how well a game's prediction works.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct PredictionStats {
+    /// Predicted packets whose real packet has since arrived.
+    pub checked: u64,
+    pub mispredicted: u64,
+    /// How many bytes were wrong, across all mispredicted packets.
+    pub mispredicted_bytes: u64,
+}
+
+impl PredictionStats {
+    pub fn misprediction_rate(&self) -> f32 {
+        if self.checked == 0 {
+            return 0.0;
+        }
+        self.mispredicted as f32 / self.checked as f32
+    }
+}
+
+/// Predicted packets waiting on the real ones to check them against.
+#[derive(Default)]
+struct Predictions {
+    pending: std::collections::BTreeMap<u32, Vec<u8>>,
+    stats: PredictionStats,
+}
+
+impl Predictions {
+    fn predict(&mut self, tick: u32, packet: &[u8]) {
+        self.pending.insert(tick, packet.to_vec());
+    }
+
+    /// Checks the last prediction for a tick against its real packet, if one was made.
+    fn commit(&mut self, tick: u32, packet: &[u8]) {
+        let predicted = self.pending.remove(&tick);
+        self.pending = self.pending.split_off(&tick);
+        let predicted = if let Some(predicted) = predicted {
+            predicted
+        } else {
+            return;
+        };
+        self.stats.checked += 1;
+        let wrong_bytes = predicted.iter().zip(packet.iter()).filter(|(a, b)| a != b).count()
+            + predicted.len().abs_diff(packet.len());
+        if wrong_bytes > 0 {
+            self.stats.mispredicted += 1;
+            self.stats.mispredicted_bytes += wrong_bytes as u64;
+        }
+    }
+}
+
+/// How a round went, for summing up the match once it's over.
+#[derive(Clone, Debug)]
+pub struct RoundRecord {
+    pub number: u8,
+    pub result: Option<BattleResult>,
+    pub was_draw: bool,
+    /// How long the round lasted, in ticks both sides committed to.
+    pub ticks: u32,
+    /// How many ticks were rolled back and run again over the round, counted the same way as in its replay.
+    pub rollback_ticks: u64,
+    /// Where the round's replay was written, if it was recorded.
+    pub replay_path: Option<std::path::PathBuf>,
+    /// What the game's RNGs were seeded with at the start of the round, for the fairness report.
+    pub rng_states: Vec<u32>,
+}
+
+/// What's kept about a match for its summary. Like the score, this outlives the match.
+#[derive(Clone, Debug, Default)]
+pub struct Record {
+    pub rounds: Vec<RoundRecord>,
+    /// Every round trip to the opponent measured over the match, rather than just the recent ones the latency counter keeps.
+    pub latencies: Vec<std::time::Duration>,
+    /// Set if the connection was lost partway through a round.
+    pub disconnected: bool,
+    /// Set if the opponent gave up on the match partway through, e.g. because their emulator stopped responding.
+    pub opponent_forfeited: bool,
+}
+
+/// The two sides' battle states diverged.
+#[derive(thiserror::Error, Debug, Clone, Copy)]
+#[error("desync detected in round {round_number} at tick {tick}")]
+pub struct DesyncError {
+    pub round_number: u8,
+    pub tick: u32,
+}
+
+/// Input to play instead of the local player's, by the tick it's for, so a match can be driven without anyone at the controls.
+#[cfg(all(test, feature = "core-tests"))]
+pub type InputScript = std::sync::Arc<dyn Fn(u32) -> u16 + Send + Sync>;
+
 #[derive(Clone)]
 pub struct CommittedState {
     pub state: mgba::state::State,
@@ -23,44 +159,145 @@ pub struct CommittedState {
     pub packet: Vec<u8>,
 }
 
+impl From<BattleResult> for broadcast::RoundResult {
+    fn from(result: BattleResult) -> Self {
+        match result {
+            BattleResult::Win => broadcast::RoundResult::Win,
+            BattleResult::Loss => broadcast::RoundResult::Loss,
+        }
+    }
+}
+
 pub struct RoundState {
     pub number: u8,
     pub round: Option<Round>,
     pub last_result: Option<BattleResult>,
+    last_result_was_draw: bool,
+    score: std::sync::Arc<parking_lot::Mutex<Score>>,
+    record: std::sync::Arc<parking_lot::Mutex<Record>>,
+    broadcast_server: broadcast::Server,
+    completed_rounds_ticks: u32,
+    records_replays: bool,
+    /// Who the opponent was recognized as in the lobby, if anyone, for keeping a head-to-head record.
+    opponent: Option<opponents::Recorder>,
+    /// The save being played with, for keeping track of which replays it was in.
+    save_recorder: Option<save::registry::Recorder>,
 }
 
 impl RoundState {
     pub async fn end_round(&mut self) -> anyhow::Result<()> {
-        match self.round.take() {
+        let (ticks, rollback_ticks, replay_path, rng_states) = match self.round.take() {
             Some(round) => {
                 log::info!("round ended at {:x}", round.current_tick);
+                let prediction_stats = round.prediction_stats();
+                log::info!(
+                    "round {} mispredicted {} of {} remote packets ({:.1}%), {} bytes wrong",
+                    round.number,
+                    prediction_stats.mispredicted,
+                    prediction_stats.checked,
+                    prediction_stats.misprediction_rate() * 100.0,
+                    prediction_stats.mispredicted_bytes
+                );
+                self.completed_rounds_ticks += round.committed_tick();
+                (
+                    round.committed_tick(),
+                    round.rollback_ticks,
+                    Some(round.replay_filename.clone()).filter(|_| self.records_replays),
+                    round.rng_states,
+                )
             }
             None => {
                 return Ok(());
             }
+        };
+        {
+            let mut score = self.score.lock();
+            match self.last_result {
+                _ if self.last_result_was_draw => {
+                    score.draws += 1;
+                }
+                Some(BattleResult::Win) => {
+                    score.wins += 1;
+                }
+                Some(BattleResult::Loss) => {
+                    score.losses += 1;
+                }
+                None => {}
+            }
+        }
+        if let (Some(save_recorder), Some(replay_path)) = (self.save_recorder.as_ref(), replay_path.as_ref()) {
+            save_recorder.record_replay(replay_path);
         }
+        self.record.lock().rounds.push(RoundRecord {
+            number: self.number,
+            result: self.last_result,
+            was_draw: self.last_result_was_draw,
+            ticks,
+            rollback_ticks,
+            replay_path,
+            rng_states,
+        });
+        if let Some(opponent) = self.opponent.as_ref() {
+            opponent.record(self.last_result, self.last_result_was_draw);
+        }
+        self.last_result_was_draw = false;
+        log::info!(
+            "round {} result: {:?}, replay recorded: {}",
+            self.number,
+            self.last_result,
+            self.records_replays
+        );
+        self.broadcast_server.publish(broadcast::Event::RoundEnded {
+            number: self.number,
+            result: self.last_result.map(|r| r.into()),
+            score: Default::default(),
+            replay_recorded: self.records_replays,
+        });
         Ok(())
     }
 
     pub fn set_last_result(&mut self, last_result: BattleResult) {
         self.last_result = Some(last_result);
+        self.last_result_was_draw = false;
+    }
+
+    /// Records that the round was a draw. Who goes first next round is decided by the draw rule, but the score counts it as a draw.
+    pub fn set_draw_result(&mut self) {
+        let last_result = self.round.as_ref().expect("round").on_draw_result();
+        self.last_result = Some(last_result);
+        self.last_result_was_draw = true;
+    }
+
+    /// The score from the rounds that have ended so far.
+    pub fn score(&self) -> Score {
+        *self.score.lock()
+    }
+
+    /// The number of ticks both sides have committed to over the whole match so far.
+    pub fn match_elapsed_ticks(&self) -> u32 {
+        self.completed_rounds_ticks + self.round.as_ref().map(|round| round.committed_tick()).unwrap_or(0)
     }
 }
 
 pub struct Match {
-    shadow: std::sync::Arc<parking_lot::Mutex<shadow::Shadow>>,
-    rom: Vec<u8>,
+    shadow: std::sync::Arc<shadow::Thread>,
+    shadow_view: Option<shadow::View>,
+    rom: std::sync::Arc<[u8]>,
+    /// The BIOS every core in the match runs on, if it isn't the emulated one.
+    bios: Option<std::sync::Arc<[u8]>>,
     link_code: String,
     netplay_compatiblity: String,
     local_game: &'static (dyn game::Game + Send + Sync),
     local_settings: net::protocol::Settings,
     remote_settings: net::protocol::Settings,
+    remote_client_info: Option<net::protocol::ClientInfo>,
     sender: std::sync::Arc<tokio::sync::Mutex<net::Sender>>,
-    _peer_conn: datachannel_wrapper::PeerConnection,
-    rng: tokio::sync::Mutex<rand_pcg::Mcg128Xsl64>,
+    _transport: net::Transport,
+    rng: tokio::sync::Mutex<fairness::AuditedRng>,
     cancellation_token: tokio_util::sync::CancellationToken,
     replays_path: std::path::PathBuf,
     match_type: (u8, u8),
+    battle_backgrounds: Option<Vec<u8>>,
     config: std::sync::Arc<parking_lot::RwLock<config::Config>>,
     is_offerer: bool,
     round_state: tokio::sync::Mutex<RoundState>,
@@ -68,6 +305,13 @@ pub struct Match {
     round_started_tx: tokio::sync::mpsc::Sender<u8>,
     round_started_rx: tokio::sync::Mutex<tokio::sync::mpsc::Receiver<u8>>,
     connection_latency_counter: tokio::sync::Mutex<stats::DeltaCounter>,
+    broadcast_server: broadcast::Server,
+    fixed_rtc: parking_lot::Mutex<Option<i64>>,
+    fingerprint: determinism::Fingerprint,
+    desync: std::sync::Arc<parking_lot::Mutex<Option<DesyncError>>>,
+    record: std::sync::Arc<parking_lot::Mutex<Record>>,
+    #[cfg(all(test, feature = "core-tests"))]
+    input_script: parking_lot::Mutex<Option<InputScript>>,
 }
 
 impl Match {
@@ -75,20 +319,30 @@ impl Match {
         config: std::sync::Arc<parking_lot::RwLock<config::Config>>,
         link_code: String,
         netplay_compatiblity: String,
-        rom: Vec<u8>,
+        rom: std::sync::Arc<[u8]>,
+        bios: Option<std::sync::Arc<[u8]>>,
         local_game: &'static (dyn game::Game + Send + Sync),
         local_settings: net::protocol::Settings,
         remote_settings: net::protocol::Settings,
+        remote_client_info: Option<net::protocol::ClientInfo>,
         cancellation_token: tokio_util::sync::CancellationToken,
         sender: net::Sender,
-        peer_conn: datachannel_wrapper::PeerConnection,
-        mut rng: rand_pcg::Mcg128Xsl64,
+        transport: net::Transport,
+        mut rng: fairness::AuditedRng,
         is_offerer: bool,
         primary_thread_handle: mgba::thread::Handle,
         remote_rom: &[u8],
         remote_save: &[u8],
         replays_path: std::path::PathBuf,
         match_type: (u8, u8),
+        battle_backgrounds: Option<Vec<u8>>,
+        fingerprint: determinism::Fingerprint,
+        desync: std::sync::Arc<parking_lot::Mutex<Option<DesyncError>>>,
+        score: std::sync::Arc<parking_lot::Mutex<Score>>,
+        record: std::sync::Arc<parking_lot::Mutex<Record>>,
+        broadcast_server: broadcast::Server,
+        opponent: Option<opponents::Recorder>,
+        save_recorder: Option<save::registry::Recorder>,
     ) -> anyhow::Result<std::sync::Arc<Self>> {
         let (round_started_tx, round_started_rx) = tokio::sync::mpsc::channel(1);
         let did_polite_win_last_round = rng.gen::<bool>();
@@ -97,38 +351,87 @@ impl Match {
         } else {
             BattleResult::Loss
         };
+        let records_replays = records_replays(&local_settings, &remote_settings);
+        if !records_replays {
+            log::info!("not recording replays: a side asked not to be recorded");
+        }
+        let local_client_info = net::protocol::ClientInfo::local();
+        log::info!(
+            "starting match: local is tango {} on {}, remote is {}",
+            local_client_info.version,
+            local_client_info.platform,
+            remote_client_info
+                .as_ref()
+                .map(|client_info| format!("tango {} on {}", client_info.version, client_info.platform))
+                .unwrap_or_else(|| "unknown".to_string())
+        );
+        broadcast_server.publish(broadcast::Event::MatchStarted {
+            local: broadcast::Side::from_settings(&local_settings),
+            remote: broadcast::Side::from_settings(&remote_settings),
+            match_type,
+        });
+        let shadow_view = {
+            let config = config.read();
+            Some(shadow::View::new()).filter(|_| config.show_debug && config.show_shadow_view)
+        };
         let match_ = std::sync::Arc::new(Self {
-            shadow: std::sync::Arc::new(parking_lot::Mutex::new(shadow::Shadow::new(
+            shadow: std::sync::Arc::new(shadow::Thread::spawn(shadow::Shadow::new(
                 &remote_rom,
                 &remote_save,
+                bios.as_deref(),
                 match_type,
                 is_offerer,
                 last_result,
-                rng.clone(),
-            )?)),
+                rng.inner().clone(),
+                round_time_limit_tick(&local_settings),
+                battle_backgrounds.clone(),
+                shadow_view.clone(),
+            )?)?),
+            shadow_view,
             link_code,
             netplay_compatiblity,
             local_game,
             local_settings,
             remote_settings,
+            remote_client_info,
             rom,
+            bios,
             sender: std::sync::Arc::new(tokio::sync::Mutex::new(sender)),
-            _peer_conn: peer_conn,
+            _transport: transport,
             rng: tokio::sync::Mutex::new(rng),
             cancellation_token,
             replays_path,
             match_type,
+            battle_backgrounds,
             config,
             round_state: tokio::sync::Mutex::new(RoundState {
                 number: 0,
                 round: None,
                 last_result: Some(last_result),
+                last_result_was_draw: false,
+                score,
+                record: record.clone(),
+                broadcast_server: broadcast_server.clone(),
+                completed_rounds_ticks: 0,
+                records_replays,
+                opponent,
+                save_recorder,
             }),
             is_offerer,
             primary_thread_handle,
             round_started_tx,
             round_started_rx: tokio::sync::Mutex::new(round_started_rx),
-            connection_latency_counter: tokio::sync::Mutex::new(stats::DeltaCounter::new(5)),
+            connection_latency_counter: tokio::sync::Mutex::new(stats::DeltaCounter::new(
+                stats::window::MATCH_LATENCY,
+                net::MAX_LATENCY_SAMPLE,
+            )),
+            broadcast_server,
+            fixed_rtc: parking_lot::Mutex::new(None),
+            fingerprint,
+            desync,
+            record,
+            #[cfg(all(test, feature = "core-tests"))]
+            input_script: parking_lot::Mutex::new(None),
         });
         Ok(match_)
     }
@@ -146,11 +449,11 @@ impl Match {
     }
 
     pub async fn advance_shadow_until_round_end(&self) -> anyhow::Result<()> {
-        self.shadow.lock().advance_until_round_end()
+        self.shadow.advance_until_round_end()
     }
 
     pub async fn advance_shadow_until_first_committed_state(&self) -> anyhow::Result<mgba::state::State> {
-        self.shadow.lock().advance_until_first_committed_state()
+        self.shadow.advance_until_first_committed_state()
     }
 
     pub async fn latency(&self) -> std::time::Duration {
@@ -163,7 +466,7 @@ impl Match {
         'l: loop {
             tokio::select! {
                 _ = ping_timer.tick() => {
-                    self.sender.lock().await.send_ping(std::time::SystemTime::now()).await?;
+                    self.sender.lock().await.send_ping(stats::monotonic_timestamp()).await?;
                 }
                 p = receiver.receive() => {
                     match p? {
@@ -171,8 +474,9 @@ impl Match {
                             self.sender.lock().await.send_pong(ping.ts).await?;
                         }
                         net::protocol::Packet::Pong(pong) => {
-                            if let Ok(dt) = std::time::SystemTime::now().duration_since(pong.ts) {
+                            if let Some(dt) = stats::monotonic_elapsed(pong.ts) {
                                 self.connection_latency_counter.lock().await.mark(dt);
+                                self.record.lock().latencies.push(dt);
                             }
                         }
                         net::protocol::Packet::Input(input) => {
@@ -238,6 +542,28 @@ impl Match {
                                 joyflags: input.joyflags as u16,
                             });
                         }
+                        net::protocol::Packet::StateHash(state_hash) => {
+                            let mut round_state = self.round_state.lock().await;
+                            if state_hash.round_number != round_state.number {
+                                log::info!("state hash is not for the current round, dropping it");
+                                continue 'l;
+                            }
+
+                            let round = match &mut round_state.round {
+                                None => {
+                                    log::info!("no round in progress, dropping state hash");
+                                    continue 'l;
+                                }
+                                Some(b) => b,
+                            };
+
+                            round.add_remote_state_hash(state_hash.tick, state_hash.hash)?;
+                        }
+                        net::protocol::Packet::Forfeit(_) => {
+                            log::info!("opponent forfeited the match");
+                            self.record.lock().opponent_forfeited = true;
+                            break 'l;
+                        }
                         p => anyhow::bail!("unknown packet: {:?}", p),
                     }
                 }
@@ -247,14 +573,44 @@ impl Match {
         Ok(())
     }
 
+    /// Whether a round is being played, as opposed to sitting in the comm menu between them. If the round state is busy, a round is assumed to be starting or ending.
+    pub fn is_round_active(&self) -> bool {
+        self.round_state
+            .try_lock()
+            .map(|round_state| round_state.round.is_some())
+            .unwrap_or(true)
+    }
+
     pub async fn lock_round_state(&self) -> tokio::sync::MutexGuard<'_, RoundState> {
         self.round_state.lock().await
     }
 
-    pub async fn lock_rng(&self) -> tokio::sync::MutexGuard<'_, rand_pcg::Mcg128Xsl64> {
+    /// Locks the round state if nothing else has it, for when waiting on it isn't an option.
+    pub fn try_lock_round_state(&self) -> Option<tokio::sync::MutexGuard<'_, RoundState>> {
+        self.round_state.try_lock().ok()
+    }
+
+    /// Tells the opponent we're giving up on the match.
+    pub async fn forfeit(&self) -> std::io::Result<()> {
+        self.sender.lock().await.send_forfeit().await
+    }
+
+    /// Finishes the replay of the round in progress with what's been recorded of it so far, for when the match is being abandoned partway through.
+    pub async fn finish_partial_replay(&self) {
+        if let Some(round) = self.round_state.lock().await.round.as_mut() {
+            round.finish_partial_replay();
+        }
+    }
+
+    pub async fn lock_rng(&self) -> tokio::sync::MutexGuard<'_, fairness::AuditedRng> {
         self.rng.lock().await
     }
 
+    /// Everything drawn from the match RNG so far.
+    pub async fn rng_draws(&self) -> Vec<fairness::Draw> {
+        self.rng.lock().await.draws().to_vec()
+    }
+
     pub fn match_type(&self) -> (u8, u8) {
         self.match_type
     }
@@ -263,6 +619,36 @@ impl Match {
         self.is_offerer
     }
 
+    /// The battle backgrounds the patch being played picks from instead of the game's own, if it does.
+    pub fn battle_backgrounds(&self) -> Option<&[u8]> {
+        self.battle_backgrounds.as_deref()
+    }
+
+    pub fn round_time_limit_tick(&self) -> Option<u32> {
+        round_time_limit_tick(&self.local_settings)
+    }
+
+    /// Records the time the primary's cartridge RTC was fixed to, so it can be applied to fastforwarders and replays.
+    pub fn set_fixed_rtc(&self, ms: i64) {
+        *self.fixed_rtc.lock() = Some(ms);
+    }
+
+    /// Plays scripted input instead of the local player's, from the next round on.
+    #[cfg(all(test, feature = "core-tests"))]
+    pub fn set_input_script(&self, input_script: InputScript) {
+        *self.input_script.lock() = Some(input_script);
+    }
+
+    /// What the shadow core is doing, if it was asked to show it when the match started.
+    pub fn shadow_view(&self) -> Option<&shadow::View> {
+        self.shadow_view.as_ref()
+    }
+
+    /// Whether rounds are being saved as replays.
+    pub fn records_replays(&self) -> bool {
+        records_replays(&self.local_settings, &self.remote_settings)
+    }
+
     pub async fn start_round(self: &std::sync::Arc<Self>) -> anyhow::Result<()> {
         let mut round_state = self.round_state.lock().await;
         round_state.number += 1;
@@ -289,21 +675,46 @@ impl Match {
             .filter(|c| "/\\?%*:|\"<>. ".chars().any(|c2| c2 != *c))
             .collect::<String>(),
         );
-        log::info!("open replay: {}", replay_filename.display());
+        if round_state.records_replays {
+            log::info!("open replay: {}", replay_filename.display());
+        }
 
-        let replay_file = std::fs::File::create(&replay_filename)?;
+        crashreport::update_context(|context| {
+            context.round_number = Some(round_state.number);
+            context.replay_path = Some(replay_filename.clone()).filter(|_| round_state.records_replays);
+        });
 
         log::info!("preparing round state");
 
         let (first_state_committed_local_packet, first_state_committed_rx) = tokio::sync::oneshot::channel();
 
-        let (input_delay, max_queue_length) = {
+        let (max_queue_length, desync_detection) = {
             let config = self.config.read();
-            (config.input_delay, config.max_queue_length)
+            (config.max_queue_length, config.desync_detection)
         };
 
+        // Each side fills its own delay, handicap included, into its queue, so the sides' queues may be different depths. Ticks still line up because every input carries the tick it's for.
+        let input_delay = self.local_settings.total_input_delay();
+        let remote_input_delay = self.remote_settings.total_input_delay();
+        if std::cmp::max(input_delay, remote_input_delay) >= max_queue_length {
+            anyhow::bail!(
+                "input delay does not fit in input queue: local = {}, remote = {}, max queue length = {}",
+                input_delay,
+                remote_input_delay,
+                max_queue_length
+            );
+        }
+
         let mut iq = lockstep::PairQueue::new(max_queue_length as usize, input_delay);
-        log::info!("filling {} ticks of input delay", input_delay);
+        log::info!(
+            "filling {} ticks of input delay ({} + {} handicap), remote has {} ({} + {} handicap)",
+            input_delay,
+            self.local_settings.input_delay,
+            self.local_settings.input_delay_handicap,
+            remote_input_delay,
+            self.remote_settings.input_delay,
+            self.remote_settings.input_delay_handicap
+        );
 
         {
             let mut sender = self.sender.lock().await;
@@ -322,26 +733,13 @@ impl Match {
         let local_game_settings = self.local_settings.game_info.as_ref().unwrap();
         let remote_game_settings = self.remote_settings.game_info.as_ref().unwrap();
 
-        round_state.round = Some(Round {
-            config: self.config.clone(),
-            hooks,
-            number: round_state.number,
-            local_player_index,
-            current_tick: 0,
-            dtick: 0,
-            iq,
-            last_committed_remote_input: lockstep::Input {
-                local_tick: 0,
-                remote_tick: 0,
-                joyflags: 0,
-                packet: vec![0u8; hooks.packet_size()],
-            },
-            first_state_committed_local_packet: Some(first_state_committed_local_packet),
-            first_state_committed_rx: Some(first_state_committed_rx),
-            committed_state: None,
-            replay_filename,
-            replay_writer: Some(replay::Writer::new(
-                Box::new(replay_file),
+        let fixed_rtc = *self.fixed_rtc.lock();
+        let round_time_limit_tick = self.round_time_limit_tick();
+
+        // If either side asked not to be recorded, the writer isn't made at all, so nothing is ever written for the round.
+        let replay_writer = if round_state.records_replays {
+            Some(replay::Writer::new(
+                &replay_filename,
                 replay::Metadata {
                     ts: std::time::SystemTime::now()
                         .duration_since(std::time::UNIX_EPOCH)
@@ -363,6 +761,8 @@ impl Match {
                             },
                         }),
                         reveal_setup: self.local_settings.reveal_setup,
+                        client_info: Some(client_info_to_replay(&net::protocol::ClientInfo::local())),
+                        input_delay: Some(input_delay_to_replay(&self.local_settings)),
                     }),
                     remote_side: Some(replay::metadata::Side {
                         nickname: self.remote_settings.nickname.clone(),
@@ -379,28 +779,102 @@ impl Match {
                             },
                         }),
                         reveal_setup: self.remote_settings.reveal_setup,
+                        client_info: self.remote_client_info.as_ref().map(client_info_to_replay),
+                        input_delay: Some(input_delay_to_replay(&self.remote_settings)),
+                    }),
+                    fixed_rtc: fixed_rtc.map(|ms| replay::metadata::FixedRtc { ms }),
+                    fingerprint: Some(self.fingerprint.to_replay_fingerprint()),
+                    desync: None,
+                    round_time_limit: round_time_limit_tick.map(|ticks| replay::metadata::RoundTimeLimit { ticks }),
+                    score: Some({
+                        let score = round_state.score();
+                        replay::metadata::Score {
+                            wins: score.wins,
+                            losses: score.losses,
+                            draws: score.draws,
+                        }
+                    }),
+                    match_type: Some(replay::metadata::MatchType {
+                        r#type: self.match_type.0 as u32,
+                        subtype: self.match_type.1 as u32,
                     }),
                 },
                 local_player_index,
                 hooks.packet_size() as u8,
-            )?),
-            replayer: replayer::Fastforwarder::new(&self.rom, hooks, local_player_index)?,
+            )?)
+        } else {
+            None
+        };
+
+        round_state.round = Some(Round {
+            config: self.config.clone(),
+            hooks,
+            number: round_state.number,
+            local_player_index,
+            current_tick: 0,
+            round_time_limit_tick,
+            dtick: 0,
+            iq,
+            last_committed_remote_input: lockstep::Input {
+                local_tick: 0,
+                remote_tick: 0,
+                joyflags: 0,
+                packet: vec![0u8; hooks.packet_size()],
+            },
+            first_state_committed_local_packet: Some(first_state_committed_local_packet),
+            first_state_committed_rx: Some(first_state_committed_rx),
+            committed_state: None,
+            replay_filename: replay_filename.clone(),
+            replay_writer,
+            rollback_ticks: 0,
+            rng_states: vec![],
+            predictions: std::sync::Arc::new(parking_lot::Mutex::new(Predictions::default())),
+            replayer: replayer::Fastforwarder::new(
+                &self.rom,
+                self.bios.as_deref(),
+                hooks,
+                local_player_index,
+                fixed_rtc,
+                // The shadow view compares against these too, even if they aren't being sent.
+                desync_detection || self.shadow_view.is_some(),
+                round_time_limit_tick,
+            )?,
             primary_thread_handle: self.primary_thread_handle.clone(),
             sender: self.sender.clone(),
             shadow: self.shadow.clone(),
+            shadow_view: self.shadow_view.clone(),
+            desync_detection,
+            local_state_hashes: std::collections::BTreeMap::new(),
+            remote_state_hashes: std::collections::BTreeMap::new(),
+            desync: self.desync.clone(),
+            opponent: round_state.opponent.clone(),
+            #[cfg(all(test, feature = "core-tests"))]
+            input_script: self.input_script.lock().clone(),
         });
         self.round_started_tx.send(round_state.number).await?;
+        self.broadcast_server.publish(broadcast::Event::RoundStarted {
+            number: round_state.number,
+        });
         log::info!("round has started");
         Ok(())
     }
 }
 
+impl Drop for Match {
+    fn drop(&mut self) {
+        self.broadcast_server.publish(broadcast::Event::MatchEnded {
+            score: Default::default(),
+        });
+    }
+}
+
 pub struct Round {
     config: std::sync::Arc<parking_lot::RwLock<config::Config>>,
     hooks: &'static (dyn game::Hooks + Send + Sync),
     number: u8,
     local_player_index: u8,
     current_tick: u32,
+    round_time_limit_tick: Option<u32>,
     dtick: i32,
     iq: lockstep::PairQueue<lockstep::PartialInput, lockstep::PartialInput>,
     last_committed_remote_input: lockstep::Input,
@@ -409,10 +883,22 @@ pub struct Round {
     committed_state: Option<CommittedState>,
     replay_filename: std::path::PathBuf,
     replay_writer: Option<replay::Writer>,
+    rollback_ticks: u64,
+    rng_states: Vec<u32>,
+    predictions: std::sync::Arc<parking_lot::Mutex<Predictions>>,
     replayer: replayer::Fastforwarder,
     primary_thread_handle: mgba::thread::Handle,
     sender: std::sync::Arc<tokio::sync::Mutex<net::Sender>>,
-    shadow: std::sync::Arc<parking_lot::Mutex<shadow::Shadow>>,
+    shadow: std::sync::Arc<shadow::Thread>,
+    shadow_view: Option<shadow::View>,
+    desync_detection: bool,
+    local_state_hashes: std::collections::BTreeMap<u32, u32>,
+    remote_state_hashes: std::collections::BTreeMap<u32, u32>,
+    desync: std::sync::Arc<parking_lot::Mutex<Option<DesyncError>>>,
+    /// Who the opponent was recognized as, so a desync counts against playing them again.
+    opponent: Option<opponents::Recorder>,
+    #[cfg(all(test, feature = "core-tests"))]
+    input_script: Option<InputScript>,
 }
 
 impl Round {
@@ -420,10 +906,34 @@ impl Round {
         self.current_tick
     }
 
+    fn finish_partial_replay(&mut self) {
+        if let Some(replay_writer) = self.replay_writer.take() {
+            if let Err(e) = replay_writer.finish() {
+                log::error!(
+                    "failed to finish partial replay {}: {:?}",
+                    self.replay_filename.display(),
+                    e
+                );
+                return;
+            }
+            log::info!("partial replay finished at {:x}", self.current_tick);
+        }
+    }
+
     pub fn increment_current_tick(&mut self) {
         self.current_tick += 1;
     }
 
+    /// The last tick both sides' inputs are in for, which unlike the current tick is never rolled back.
+    pub fn committed_tick(&self) -> u32 {
+        self.committed_state.as_ref().map(|state| state.tick).unwrap_or(0)
+    }
+
+    /// The tick the round is ended on if it's still undecided, if the round is time limited.
+    pub fn round_time_limit_tick(&self) -> Option<u32> {
+        self.round_time_limit_tick
+    }
+
     pub fn local_player_index(&self) -> u8 {
         self.local_player_index
     }
@@ -434,16 +944,17 @@ impl Round {
         remote_state: mgba::state::State,
         first_packet: &[u8],
     ) {
-        self.replay_writer
-            .as_mut()
-            .unwrap()
-            .write_state(&state)
-            .expect("write local state");
-        self.replay_writer
-            .as_mut()
-            .unwrap()
-            .write_state(&remote_state)
-            .expect("write remote state");
+        if let Some(replay_writer) = self.replay_writer.as_mut() {
+            replay_writer.write_state(&state).expect("write local state");
+            replay_writer.write_state(&remote_state).expect("write remote state");
+        }
+        self.rng_states = match self.replayer.rng_states(&state) {
+            Ok(rng_states) => rng_states,
+            Err(e) => {
+                log::error!("failed to read rng states: {:?}", e);
+                vec![]
+            }
+        };
         self.committed_state = Some(CommittedState {
             state,
             tick: 0,
@@ -461,6 +972,12 @@ impl Round {
     ) -> anyhow::Result<Option<BattleResult>> {
         let local_tick = self.current_tick + self.local_delay();
         let remote_tick = self.last_committed_remote_input.local_tick;
+        #[cfg(all(test, feature = "core-tests"))]
+        let joyflags = self
+            .input_script
+            .as_ref()
+            .map(|input_script| input_script(local_tick))
+            .unwrap_or(joyflags);
 
         // We do it in this order such that:
         // 1. We make sure that the input buffer does not overflow if we were to add an input.
@@ -522,6 +1039,7 @@ impl Round {
             .collect::<Vec<lockstep::Pair<lockstep::PartialInput, lockstep::PartialInput>>>();
         let last_local_input = input_pairs.last().unwrap().local.clone();
 
+        let shadow_inputs = std::sync::Arc::new(parking_lot::Mutex::new(self.shadow.inputs()));
         let ff_result = self.replayer.fastforward(
             &last_committed_state.state,
             input_pairs,
@@ -530,28 +1048,32 @@ impl Round {
             dirty_tick,
             &last_committed_state.packet,
             Box::new({
-                let shadow = self.shadow.clone();
+                let shadow_inputs = shadow_inputs.clone();
                 let hooks = self.hooks;
+                let predictions = self.predictions.clone();
                 let mut last_commit = self.last_committed_remote_input.packet.clone();
                 move |ip| {
                     let local_tick = ip.local.local_tick;
                     Ok(if ip.local.local_tick < commit_tick {
-                        let r = shadow.lock().apply_input(ip)?;
+                        let r = shadow_inputs.lock().apply(ip)?;
                         assert!(
                             r.tick == local_tick,
                             "shadow input did not match current tick: {} != {}",
                             r.tick,
                             local_tick
                         );
+                        predictions.lock().commit(local_tick, &r.packet);
                         last_commit = r.packet.clone();
                         r.packet
                     } else {
                         hooks.predict_rx(&mut last_commit);
+                        predictions.lock().predict(local_tick, &last_commit);
                         last_commit.clone()
                     })
                 }
             }),
         )?;
+        shadow_inputs.lock().finish()?;
 
         for ip in &ff_result.output_pairs {
             if ip.local.local_tick >= commit_tick {
@@ -563,9 +1085,11 @@ impl Round {
                 .map(|rr| ip.local.local_tick < rr.tick)
                 .unwrap_or(true)
             {
+                let rollback_depth = dirty_tick.saturating_sub(ip.local.local_tick);
+                self.rollback_ticks += rollback_depth as u64;
                 if let Some(replay_writer) = self.replay_writer.as_mut() {
                     replay_writer
-                        .write_input(self.local_player_index, ip)
+                        .write_input(self.local_player_index, ip, rollback_depth)
                         .expect("write input");
                 }
             }
@@ -575,6 +1099,26 @@ impl Round {
         core.load_state(&ff_result.dirty_state.state).expect("load dirty state");
         self.committed_state = Some(ff_result.committed_state);
 
+        for (tick, hash) in ff_result.state_hashes {
+            if let Some(shadow_view) = self.shadow_view.as_ref() {
+                shadow_view.add_primary_state_hash(tick, hash);
+            }
+            if !self.desync_detection {
+                continue;
+            }
+            self.sender
+                .lock()
+                .await
+                .send_state_hash(self.number, tick, hash)
+                .await?;
+            self.local_state_hashes.insert(tick, hash);
+            self.check_state_hash(tick)?;
+
+            let oldest_tick = tick.saturating_sub(replayer::STATE_HASH_INTERVAL * STATE_HASH_WINDOWS);
+            self.local_state_hashes.retain(|tick, _| *tick >= oldest_tick);
+            self.remote_state_hashes.retain(|tick, _| *tick >= oldest_tick);
+        }
+
         self.dtick = last_local_input.lag() - self.last_committed_remote_input.lag();
 
         core.gba_mut()
@@ -603,13 +1147,13 @@ impl Round {
             // Need to submit replay to replay collector.
             let replaycollector_endpoint = self.config.read().replaycollector_endpoint.clone();
             if !replaycollector_endpoint.is_empty() {
+                let client = self.config.read().network.http_client();
                 tokio::spawn({
                     let replay_path = self.replay_filename.clone();
                     async move {
                         let replay_path2 = replay_path.clone();
                         if let Err(e) = (move || async move {
-                            let client = reqwest::Client::new();
-                            let replay_file = tokio::fs::File::open(&replay_path2).await?;
+                            let replay_file = tokio::fs::File::open(fs::extended(&replay_path2)).await?;
 
                             client
                                 .post(replaycollector_endpoint)
@@ -671,6 +1215,72 @@ impl Round {
         self.iq.add_remote_input(input);
     }
 
+    pub fn add_remote_state_hash(&mut self, tick: u32, hash: u32) -> Result<(), DesyncError> {
+        if !self.desync_detection {
+            return Ok(());
+        }
+        self.remote_state_hashes.insert(tick, hash);
+        self.check_state_hash(tick)
+    }
+
+    /// Compares the checksums for a tick, once both sides' are in.
+    fn check_state_hash(&mut self, tick: u32) -> Result<(), DesyncError> {
+        let (local_hash, remote_hash) = match (self.local_state_hashes.get(&tick), self.remote_state_hashes.get(&tick))
+        {
+            (Some(local_hash), Some(remote_hash)) => (*local_hash, *remote_hash),
+            _ => {
+                return Ok(());
+            }
+        };
+        self.local_state_hashes.remove(&tick);
+        self.remote_state_hashes.remove(&tick);
+
+        if local_hash == remote_hash {
+            return Ok(());
+        }
+
+        log::error!(
+            "desync detected in round {} at tick {}: local state hash {:08x} != remote state hash {:08x}",
+            self.number,
+            tick,
+            local_hash,
+            remote_hash
+        );
+        let err = DesyncError {
+            round_number: self.number,
+            tick,
+        };
+        *self.desync.lock() = Some(err);
+        if let Some(opponent) = self.opponent.as_ref() {
+            opponent.record_desync();
+        }
+
+        if let Some(replay_writer) = self.replay_writer.take() {
+            if let Err(e) = replay_writer.finish().and_then(|_| {
+                replay::update_metadata(&self.replay_filename, |metadata| {
+                    metadata.desync = Some(replay::metadata::Desync { tick });
+                })
+            }) {
+                log::error!(
+                    "failed to record desync in replay {}: {:?}",
+                    self.replay_filename.display(),
+                    e
+                );
+            }
+        }
+
+        Err(err)
+    }
+
+    pub fn prediction_stats(&self) -> PredictionStats {
+        self.predictions.lock().stats
+    }
+
+    /// How many ticks the last fastforward caught up on, and how long each one typically takes.
+    pub fn fastforward_cost(&self) -> (u32, std::time::Duration) {
+        (self.replayer.last_ticks(), self.replayer.tick_cost())
+    }
+
     pub fn tps_adjustment(&self) -> f32 {
         (self.dtick * session::EXPECTED_FPS as i32) as f32 / self.iq.max_length() as f32
     }
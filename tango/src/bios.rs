@@ -0,0 +1,84 @@
+//! Running on a real GBA BIOS rather than the one mGBA emulates.
+//!
+//! Games can behave subtly differently on the two, so both sides of a match have to agree on which is used: the BIOS is part of the emulator configuration fingerprint for that reason.
+
+use crate::{determinism, replay};
+
+pub const SIZE: usize = 0x4000;
+
+/// CRC32 of the retail GBA BIOS, which is also what the Game Boy Player ships.
+const KNOWN_CRC32: u32 = 0xbaae187f;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("BIOS should be 16384 bytes, but is {0} bytes")]
+    WrongSize(usize),
+
+    #[error("BIOS checksum {0:08x} doesn't match the GBA BIOS")]
+    UnknownChecksum(u32),
+
+    #[error("the replay was recorded on a GBA BIOS, but none is set up")]
+    MissingForReplay,
+
+    #[error("the replay was recorded on a different BIOS than the one set up")]
+    MismatchedForReplay,
+}
+
+/// Reads a BIOS, making sure it's the GBA BIOS.
+pub fn load(path: &std::path::Path) -> Result<std::sync::Arc<[u8]>, Error> {
+    let bios = std::fs::read(path)?;
+    if bios.len() != SIZE {
+        return Err(Error::WrongSize(bios.len()));
+    }
+    let crc32 = crc32fast::hash(&bios);
+    if crc32 != KNOWN_CRC32 {
+        return Err(Error::UnknownChecksum(crc32));
+    }
+    Ok(bios.into())
+}
+
+/// Reads the BIOS set up in the config, if there is one.
+///
+/// If it can no longer be read, the emulated BIOS is used instead: this is logged rather than stopping the session from starting.
+pub fn load_configured(path: Option<&std::path::Path>) -> Option<std::sync::Arc<[u8]>> {
+    let path = path?;
+    match load(path) {
+        Ok(bios) => Some(bios),
+        Err(e) => {
+            log::error!("failed to load bios {}, using emulated bios: {:?}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Works out which BIOS a replay has to be played back on, so it plays back the same way it was recorded.
+///
+/// Replays recorded on the emulated BIOS are played back on it even if a BIOS is set up.
+pub fn for_replay(
+    metadata: &replay::Metadata,
+    path: Option<&std::path::Path>,
+) -> Result<Option<std::sync::Arc<[u8]>>, Error> {
+    let recorded_hash = if let Some(hash) = metadata
+        .fingerprint
+        .as_ref()
+        .and_then(|fingerprint| determinism::Fingerprint::from_replay_fingerprint(fingerprint).bios_hash)
+    {
+        hash
+    } else {
+        return Ok(None);
+    };
+
+    let bios = load(path.ok_or(Error::MissingForReplay)?)?;
+    if determinism::hash_bios(&bios) != recorded_hash {
+        return Err(Error::MismatchedForReplay);
+    }
+    Ok(Some(bios))
+}
+
+/// Loads a BIOS into a core. This has to be done before the core is reset.
+pub fn load_into(core: &mut mgba::core::Core, bios: &[u8]) -> anyhow::Result<()> {
+    core.as_mut().load_bios(mgba::vfile::VFile::open_memory(bios))
+}
@@ -0,0 +1,358 @@
+//! Broadcast API.
+//!
+//! This is a small, read-only JSON-over-WebSocket server bound to localhost, intended for stream overlays. It publishes lobby and match events as they happen and, on connect (or when sent `{"type": "get_state"}`), the current state so that an overlay connecting mid-match can sync up.
+//!
+//! Only information that is already visible on screen is published: nicknames, games, patches, match type and round results. Inputs and save data must never be sent over this interface.
+
+use futures_util::{SinkExt, StreamExt};
+
+use crate::{config, net};
+
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Side {
+    pub nickname: String,
+    pub game: Option<(String, u8)>,
+    pub patch: Option<(String, String)>,
+}
+
+impl Side {
+    pub fn from_settings(settings: &net::protocol::Settings) -> Self {
+        Self {
+            nickname: settings.nickname.clone(),
+            game: settings.game_info.as_ref().map(|gi| gi.family_and_variant.clone()),
+            patch: settings
+                .game_info
+                .as_ref()
+                .and_then(|gi| gi.patch.as_ref())
+                .map(|pi| (pi.name.clone(), pi.version.to_string())),
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundResult {
+    Win,
+    Loss,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Score {
+    pub wins: u32,
+    pub losses: u32,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    LobbyJoined {
+        local: Side,
+        remote: Side,
+    },
+    LobbyLeft,
+    SettingsChanged {
+        local: Side,
+        remote: Side,
+        match_type: (u8, u8),
+    },
+    MatchStarted {
+        local: Side,
+        remote: Side,
+        match_type: (u8, u8),
+    },
+    RoundStarted {
+        number: u8,
+    },
+    /// `score` is filled in by the server from its running tally.
+    RoundEnded {
+        number: u8,
+        result: Option<RoundResult>,
+        score: Score,
+        /// Whether the round was saved as a replay, which it isn't if either side asked not to be recorded.
+        replay_recorded: bool,
+    },
+    /// `score` is filled in by the server from its running tally.
+    MatchEnded {
+        score: Score,
+    },
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Idle,
+    Lobby,
+    Match,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct State {
+    pub phase: Phase,
+    pub local: Option<Side>,
+    pub remote: Option<Side>,
+    pub match_type: Option<(u8, u8)>,
+    pub round_number: Option<u8>,
+    /// Milliseconds since the UNIX epoch at which the current round started, for computing a round timer.
+    pub round_started_at: Option<u64>,
+    pub score: Score,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            phase: Phase::Idle,
+            local: None,
+            remote: None,
+            match_type: None,
+            round_number: None,
+            round_started_at: None,
+            score: Score::default(),
+        }
+    }
+}
+
+impl State {
+    fn apply(&mut self, event: &mut Event) {
+        match event {
+            Event::LobbyJoined { local, remote } => {
+                *self = State {
+                    phase: Phase::Lobby,
+                    local: Some(local.clone()),
+                    remote: Some(remote.clone()),
+                    ..Default::default()
+                };
+            }
+            Event::LobbyLeft => {
+                *self = State::default();
+            }
+            Event::SettingsChanged {
+                local,
+                remote,
+                match_type,
+            } => {
+                self.local = Some(local.clone());
+                self.remote = Some(remote.clone());
+                self.match_type = Some(*match_type);
+            }
+            Event::MatchStarted {
+                local,
+                remote,
+                match_type,
+            } => {
+                *self = State {
+                    phase: Phase::Match,
+                    local: Some(local.clone()),
+                    remote: Some(remote.clone()),
+                    match_type: Some(*match_type),
+                    ..Default::default()
+                };
+            }
+            Event::RoundStarted { number } => {
+                self.round_number = Some(*number);
+                self.round_started_at = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .ok()
+                    .map(|d| d.as_millis() as u64);
+            }
+            Event::RoundEnded { result, score, .. } => {
+                match result {
+                    Some(RoundResult::Win) => {
+                        self.score.wins += 1;
+                    }
+                    Some(RoundResult::Loss) => {
+                        self.score.losses += 1;
+                    }
+                    None => {}
+                }
+                self.round_started_at = None;
+                *score = self.score;
+            }
+            Event::MatchEnded { score } => {
+                *score = self.score;
+                self.phase = Phase::Idle;
+                self.round_number = None;
+                self.round_started_at = None;
+            }
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message<'a> {
+    State(&'a State),
+}
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    GetState,
+}
+
+struct Inner {
+    config: std::sync::Arc<parking_lot::RwLock<config::Config>>,
+    state: parking_lot::Mutex<State>,
+    events_tx: tokio::sync::broadcast::Sender<String>,
+    listener: parking_lot::Mutex<Option<(u16, tokio_util::sync::CancellationToken)>>,
+}
+
+#[derive(Clone)]
+pub struct Server {
+    inner: std::sync::Arc<Inner>,
+}
+
+impl Server {
+    pub fn new(config: std::sync::Arc<parking_lot::RwLock<config::Config>>) -> Self {
+        let (events_tx, _) = tokio::sync::broadcast::channel(64);
+        Self {
+            inner: std::sync::Arc::new(Inner {
+                config,
+                state: parking_lot::Mutex::new(State::default()),
+                events_tx,
+                listener: parking_lot::Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Publishes an event to all connected clients.
+    ///
+    /// This is cheap if nobody is listening, so it's fine to call it unconditionally.
+    pub fn publish(&self, mut event: Event) {
+        self.inner.state.lock().apply(&mut event);
+        let raw = match serde_json::to_string(&event) {
+            Ok(raw) => raw,
+            Err(e) => {
+                log::error!("failed to serialize broadcast event: {:?}", e);
+                return;
+            }
+        };
+        let _ = self.inner.events_tx.send(raw);
+    }
+
+    pub fn state(&self) -> State {
+        self.inner.state.lock().clone()
+    }
+
+    fn start(&self, port: u16) {
+        let mut listener = self.inner.listener.lock();
+        if let Some((current_port, cancellation_token)) = listener.take() {
+            if current_port == port {
+                *listener = Some((current_port, cancellation_token));
+                return;
+            }
+            cancellation_token.cancel();
+        }
+
+        log::info!("starting broadcast API on 127.0.0.1:{}", port);
+        let cancellation_token = tokio_util::sync::CancellationToken::new();
+        tokio::task::spawn({
+            let server = self.clone();
+            let cancellation_token = cancellation_token.clone();
+            async move {
+                tokio::select! {
+                    r = server.serve(port, cancellation_token.clone()) => {
+                        if let Err(e) = r {
+                            log::error!("broadcast API failed: {:?}", e);
+                        }
+                    }
+                    _ = cancellation_token.cancelled() => { }
+                }
+                log::info!("stopped broadcast API");
+            }
+        });
+        *listener = Some((port, cancellation_token));
+    }
+
+    fn stop(&self) {
+        if let Some((_, cancellation_token)) = self.inner.listener.lock().take() {
+            cancellation_token.cancel();
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        if enabled {
+            let port = self.inner.config.read().broadcast_api_port;
+            self.start(port);
+        } else {
+            self.stop();
+        }
+    }
+
+    async fn serve(&self, port: u16, cancellation_token: tokio_util::sync::CancellationToken) -> anyhow::Result<()> {
+        let listener = tokio::net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, port)).await?;
+        loop {
+            let (stream, addr) = listener.accept().await?;
+            log::info!("broadcast API client connected: {}", addr);
+            tokio::task::spawn({
+                let server = self.clone();
+                let cancellation_token = cancellation_token.clone();
+                async move {
+                    tokio::select! {
+                        r = server.handle_client(stream) => {
+                            if let Err(e) = r {
+                                log::warn!("broadcast API client {} errored: {:?}", addr, e);
+                            }
+                        }
+                        _ = cancellation_token.cancelled() => { }
+                    }
+                    log::info!("broadcast API client disconnected: {}", addr);
+                }
+            });
+        }
+    }
+
+    async fn handle_client(&self, stream: tokio::net::TcpStream) -> anyhow::Result<()> {
+        let mut ws = tokio_tungstenite::accept_async(stream).await?;
+        let mut events_rx = self.inner.events_tx.subscribe();
+
+        ws.send(tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(
+            &Message::State(&self.state()),
+        )?))
+        .await?;
+
+        loop {
+            tokio::select! {
+                event = events_rx.recv() => {
+                    let raw = match event {
+                        Ok(raw) => raw,
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                            // We fell behind: just resync the client with the full state.
+                            serde_json::to_string(&Message::State(&self.state()))?
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                            return Ok(());
+                        }
+                    };
+                    ws.send(tokio_tungstenite::tungstenite::Message::Text(raw)).await?;
+                }
+                msg = ws.next() => {
+                    let msg = if let Some(msg) = msg {
+                        msg?
+                    } else {
+                        return Ok(());
+                    };
+
+                    match msg {
+                        tokio_tungstenite::tungstenite::Message::Text(raw) => {
+                            match serde_json::from_str::<Request>(&raw) {
+                                Ok(Request::GetState) => {
+                                    ws.send(tokio_tungstenite::tungstenite::Message::Text(serde_json::to_string(
+                                        &Message::State(&self.state()),
+                                    )?))
+                                    .await?;
+                                }
+                                Err(e) => {
+                                    log::warn!("invalid broadcast API request: {:?}", e);
+                                }
+                            }
+                        }
+                        tokio_tungstenite::tungstenite::Message::Close(_) => {
+                            return Ok(());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use crate::{config, fairness, fs, game, i18n, patch, replay, save};
+
+#[derive(clap::Parser)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Compares two saves of the same game and prints what changed between them as JSON.
+    SaveDiff {
+        #[clap(parse(from_os_str))]
+        before: std::path::PathBuf,
+        #[clap(parse(from_os_str))]
+        after: std::path::PathBuf,
+    },
+    /// Lists the translation keys each language is missing or no longer needs, compared to the fallback language.
+    LocaleCoverage {
+        /// The locales directory from the source tree, with a directory of .ftl files for each language.
+        #[clap(parse(from_os_str), default_value = "locales")]
+        locales: std::path::PathBuf,
+    },
+    /// Plays a replay up to a tick and writes the emulator state there, a summary of the battle and the inputs around it into a directory.
+    ReplayDumpState {
+        #[clap(parse(from_os_str))]
+        replay: std::path::PathBuf,
+        #[clap(long)]
+        tick: u32,
+        #[clap(long, parse(from_os_str))]
+        out: std::path::PathBuf,
+        /// The unpatched ROM of the game the replay is of.
+        #[clap(long, parse(from_os_str))]
+        rom: std::path::PathBuf,
+        /// Where to find the patch the replay is of, if any. Defaults to the configured patches directory.
+        #[clap(long, parse(from_os_str))]
+        patches: Option<std::path::PathBuf>,
+        /// The GBA BIOS to play back replays recorded on one with. Defaults to the configured BIOS.
+        #[clap(long, parse(from_os_str))]
+        bios: Option<std::path::PathBuf>,
+    },
+    /// Checks a match's fairness report against the commitments in it and the replays next to it.
+    FairnessVerify {
+        #[clap(parse(from_os_str))]
+        report: std::path::PathBuf,
+        /// The unpatched ROM of the game the match was played on.
+        #[clap(long, parse(from_os_str))]
+        rom: std::path::PathBuf,
+        /// Where to find the patch the match was played on, if any. Defaults to the configured patches directory.
+        #[clap(long, parse(from_os_str))]
+        patches: Option<std::path::PathBuf>,
+        /// The GBA BIOS to play back replays recorded on one with. Defaults to the configured BIOS.
+        #[clap(long, parse(from_os_str))]
+        bios: Option<std::path::PathBuf>,
+    },
+}
+
+/// The subcommands that can be given instead of starting the GUI.
+///
+/// Anything else on the command line, e.g. a link code, is left for the GUI to handle.
+const COMMANDS: &[&str] = &["save-diff", "locale-coverage", "replay-dump-state", "fairness-verify"];
+
+fn save_diff(before: &std::path::Path, after: &std::path::Path) -> Result<(), anyhow::Error> {
+    let before = std::fs::read(before)?;
+    let after = std::fs::read(after)?;
+
+    let (before, after) = game::GAMES
+        .iter()
+        .find_map(|game| Some((game.parse_save(&before).ok()?, game.parse_save(&after).ok()?)))
+        .ok_or_else(|| anyhow::anyhow!("saves are not of the same game, or not recognized"))?;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&save::diff::diff(before.as_ref(), after.as_ref()))?
+    );
+    Ok(())
+}
+
+/// Collects the keys defined in a language's .ftl files: each message that has a value, and each of its attributes as `message.attribute`, which is how they're looked up.
+fn locale_keys(path: &std::path::Path) -> Result<std::collections::BTreeSet<String>, anyhow::Error> {
+    let mut keys = std::collections::BTreeSet::new();
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.extension().map(|ext| ext != "ftl").unwrap_or(true) {
+            continue;
+        }
+
+        let mut message = None;
+        let mut in_attribute = false;
+        for line in std::fs::read_to_string(&path)?.lines() {
+            let (indented, line) = (line.starts_with(char::is_whitespace), line.trim());
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if !indented {
+                let (id, value) = if let Some((id, value)) = line.split_once('=') {
+                    (id.trim(), value.trim())
+                } else {
+                    message = None;
+                    continue;
+                };
+                // Terms (starting with -) can't be looked up by themselves, but their attributes still shouldn't be counted as the previous message's.
+                message = Some(id.to_string()).filter(|id| !id.starts_with('-'));
+                in_attribute = false;
+                // A message with only attributes can't be looked up by itself.
+                if let (Some(message), false) = (message.as_ref(), value.is_empty()) {
+                    keys.insert(message.clone());
+                }
+                continue;
+            }
+            let message = if let Some(message) = message.as_ref() {
+                message
+            } else {
+                continue;
+            };
+            if let Some((attribute, _)) = line.strip_prefix('.').and_then(|line| line.split_once('=')) {
+                keys.insert(format!("{}.{}", message, attribute.trim()));
+                in_attribute = true;
+            } else if !in_attribute {
+                // The value is on the lines after the message.
+                keys.insert(message.clone());
+            }
+        }
+    }
+    Ok(keys)
+}
+
+fn locale_coverage(locales: &std::path::Path) -> Result<(), anyhow::Error> {
+    let baseline = locale_keys(&locales.join(i18n::FALLBACK_LANG))?;
+
+    let mut langs = std::fs::read_dir(locales)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<Result<Vec<_>, _>>()?;
+    langs.sort();
+
+    for lang in langs {
+        let lang = lang.to_string_lossy();
+        if lang == i18n::FALLBACK_LANG {
+            continue;
+        }
+        let keys = locale_keys(&locales.join(&*lang))?;
+        let missing = baseline.difference(&keys).collect::<Vec<_>>();
+        let unused = keys.difference(&baseline).collect::<Vec<_>>();
+
+        println!(
+            "{}: {} of {} translated, {} missing, {} unused",
+            lang,
+            baseline.len() - missing.len(),
+            baseline.len(),
+            missing.len(),
+            unused.len()
+        );
+        for key in missing {
+            println!("  missing: {}", key);
+        }
+        for key in unused {
+            println!("  unused: {}", key);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the ROM a replay was recorded on, with its patch applied if it had one.
+fn load_replay_rom(
+    replay: &replay::Replay,
+    rom_path: &std::path::Path,
+    patches_path: Option<&std::path::Path>,
+) -> Result<(&'static (dyn game::Game + Send + Sync), Vec<u8>), anyhow::Error> {
+    let game_info = replay
+        .metadata
+        .local_side
+        .as_ref()
+        .and_then(|side| side.game_info.as_ref())
+        .ok_or_else(|| anyhow::anyhow!("missing game info"))?;
+    let game = game::find_by_family_and_variant(&game_info.rom_family, game_info.rom_variant as u8)
+        .ok_or_else(|| anyhow::anyhow!("game not found: {} {}", game_info.rom_family, game_info.rom_variant))?;
+
+    let mut rom = fs::read(rom_path)?;
+    let rom_game = game::detect(&rom)?;
+    if rom_game != game {
+        anyhow::bail!("ROM is of {:?}, but the replay is of {:?}", rom_game, game);
+    }
+
+    if let Some(patch_info) = game_info.patch.as_ref() {
+        let patches_path = match patches_path {
+            Some(patches_path) => patches_path.to_path_buf(),
+            None => config::Config::load_or_create()?.patches_path(),
+        };
+        rom = patch::apply_patch_from_disk(
+            &rom,
+            game,
+            &patches_path,
+            &patch_info.name,
+            &semver::Version::parse(&patch_info.version)?,
+        )?;
+    }
+    Ok((game, rom))
+}
+
+/// Reads the configured BIOS path if one wasn't given.
+fn bios_path_or_configured(bios_path: Option<&std::path::Path>) -> Result<Option<std::path::PathBuf>, anyhow::Error> {
+    Ok(match bios_path {
+        Some(bios_path) => Some(bios_path.to_path_buf()),
+        None => config::Config::load_or_create()?.bios_path,
+    })
+}
+
+fn replay_dump_state(
+    replay_path: &std::path::Path,
+    tick: u32,
+    out: &std::path::Path,
+    rom_path: &std::path::Path,
+    patches_path: Option<&std::path::Path>,
+    bios_path: Option<&std::path::Path>,
+) -> Result<(), anyhow::Error> {
+    let replay = replay::Replay::decode(std::fs::File::open(replay_path)?)?;
+    let (game, rom) = load_replay_rom(&replay, rom_path, patches_path)?;
+    let bios_path = bios_path_or_configured(bios_path)?;
+    replay::dump::dump_state(&rom, bios_path.as_deref(), game, &replay, tick, out)
+}
+
+fn fairness_verify(
+    report_path: &std::path::Path,
+    rom_path: &std::path::Path,
+    patches_path: Option<&std::path::Path>,
+    bios_path: Option<&std::path::Path>,
+) -> Result<(), anyhow::Error> {
+    // The game and patch are read from the first replay, as the report itself doesn't say.
+    let report = fairness::Report::read(report_path)?;
+    let replay_name = report
+        .rounds
+        .iter()
+        .find_map(|round| round.replay.as_ref())
+        .ok_or_else(|| anyhow::anyhow!("no rounds in the report were recorded"))?;
+    let replay_path = report_path
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new("."))
+        .join(replay_name);
+    let replay = replay::Replay::decode(std::fs::File::open(&replay_path)?)?;
+    let (game, rom) = load_replay_rom(&replay, rom_path, patches_path)?;
+    let bios_path = bios_path_or_configured(bios_path)?;
+
+    let rounds_checked = fairness::verify(report_path, &rom, game.hooks(), bios_path.as_deref())?;
+    println!(
+        "fair: seed {}, {} draws and {} of {} rounds checked",
+        report
+            .reveal
+            .seed
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>(),
+        report.draws.len(),
+        rounds_checked,
+        report.rounds.len()
+    );
+    Ok(())
+}
+
+/// Runs the subcommand given on the command line, if there is one.
+pub fn run() -> Option<Result<(), anyhow::Error>> {
+    let command = std::env::args_os().nth(1)?;
+    if !COMMANDS.iter().any(|c| command == **c) {
+        return None;
+    }
+
+    Some(match <Cli as clap::Parser>::parse().command {
+        Command::SaveDiff { before, after } => save_diff(&before, &after),
+        Command::LocaleCoverage { locales } => locale_coverage(&locales),
+        Command::ReplayDumpState {
+            replay,
+            tick,
+            out,
+            rom,
+            patches,
+            bios,
+        } => replay_dump_state(&replay, tick, &out, &rom, patches.as_deref(), bios.as_deref()),
+        Command::FairnessVerify {
+            report,
+            rom,
+            patches,
+            bios,
+        } => fairness_verify(&report, &rom, patches.as_deref(), bios.as_deref()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locales_path() -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("locales")
+    }
+
+    #[test]
+    fn test_locale_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("test.ftl"),
+            "\
+# A comment = not a key
+message = Value
+    .attribute = Attribute
+multiline =
+    First line
+    second line = not a key
+attributes-only =
+    .attribute = Attribute
+        continued
+-term = Term
+    .term-attribute = Not a key either
+after-term = Value
+    .attribute = Attribute
+",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("ignored.txt"), "ignored = Ignored").unwrap();
+
+        assert_eq!(
+            locale_keys(dir.path()).unwrap().into_iter().collect::<Vec<_>>(),
+            vec![
+                "after-term",
+                "after-term.attribute",
+                "attributes-only.attribute",
+                "message",
+                "message.attribute",
+                "multiline"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_locale_keys_resolve() {
+        let lang = i18n::FALLBACK_LANG.parse().unwrap();
+        let keys = locale_keys(&locales_path().join(i18n::FALLBACK_LANG)).unwrap();
+        assert!(!keys.is_empty());
+        for key in keys {
+            assert!(i18n::LOCALES.try_lookup(&lang, &key).is_some(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_locale_coverage() {
+        locale_coverage(&locales_path()).unwrap();
+    }
+}
@@ -2,7 +2,9 @@ use std::io::{Read, Write};
 
 use serde::Deserialize;
 
-use crate::{i18n, input};
+use crate::{assist, contentpolicy, fs, i18n, input, inputlatency, net, replay};
+
+pub mod profile;
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
 pub enum GraphicsBackend {
@@ -40,11 +42,27 @@ impl Default for AudioBackend {
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SaveWriteBackPolicy {
+    Ask,
+    Discard,
+    Overwrite,
+    SaveAsNew,
+}
+
+impl Default for SaveWriteBackPolicy {
+    fn default() -> Self {
+        Self::Ask
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
 pub enum Theme {
     System,
     Light,
     Dark,
+    /// A theme loaded from a file in the themes directory, by name.
+    User(String),
 }
 
 impl Default for Theme {
@@ -53,6 +71,38 @@ impl Default for Theme {
     }
 }
 
+/// The patch a lobby preset plays with.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct LobbyPresetPatch {
+    pub name: String,
+    pub version: semver::Version,
+}
+
+/// Everything needed to set up the play pane the same way again: the game, save and patch, and the lobby options to use with them.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct LobbyPreset {
+    pub name: String,
+    pub game_family: String,
+    pub game_variant: u8,
+    /// The save, relative to the saves directory if it's in there.
+    pub save_path: std::path::PathBuf,
+    pub match_type: (u8, u8),
+    pub reveal_setup: bool,
+    // Tables have to come last in TOML, so this has to stay at the end.
+    pub patch: Option<LobbyPresetPatch>,
+}
+
+impl LobbyPreset {
+    /// Writes the preset out as a TOML snippet, for sharing.
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+}
+
 fn serialize_language_identifier<S>(v: &unic_langid::LanguageIdentifier, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
@@ -68,63 +118,152 @@ where
     buf.parse().map_err(serde::de::Error::custom)
 }
 
+fn serialize_optional_language_identifier<S>(
+    v: &Option<unic_langid::LanguageIdentifier>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match v {
+        Some(v) => serializer.serialize_some(&v.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_optional_language_identifier<'de, D>(
+    deserializer: D,
+) -> Result<Option<unic_langid::LanguageIdentifier>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|buf| buf.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
 #[serde(default)]
 pub struct Config {
+    /// Identifies this installation to peers, so connecting to ourselves can be told apart from connecting to someone else.
+    pub installation_id: String,
     pub nickname: Option<String>,
     pub theme: Theme,
     pub show_debug: bool,
+    /// Shows what the shadow core renders during matches. This only does anything along with show_debug, and only from the next match.
+    pub show_shadow_view: bool,
     #[serde(
         serialize_with = "serialize_language_identifier",
         deserialize_with = "deserialize_language_identifier"
     )]
     pub language: unic_langid::LanguageIdentifier,
+    /// The language to show game data such as chip names in, if it should be different from what the game or patch is in.
+    #[serde(
+        serialize_with = "serialize_optional_language_identifier",
+        deserialize_with = "deserialize_optional_language_identifier"
+    )]
+    pub game_data_language: Option<unic_langid::LanguageIdentifier>,
     pub max_queue_length: u32,
     pub video_filter: String,
     pub max_scale: u32,
     pub input_mapping: input::Mapping,
+    /// Input assists for single player sessions.
+    pub assist: assist::Settings,
+    pub input_latency_reports: std::collections::BTreeMap<String, inputlatency::Report>,
     pub matchmaking_endpoint: String,
     pub replaycollector_endpoint: String,
     pub patch_repo: String,
     pub enable_patch_autoupdate: bool,
     pub input_delay: u32,
+    pub desync_detection: bool,
+    /// How long to wait for the opponent's data once both sides are ready, before giving up on starting the match.
+    pub match_start_timeout_secs: u32,
+    /// How long the emulator can go without finishing a tick while it's supposed to be running before it's considered hung.
+    pub hang_timeout_secs: u32,
     pub default_match_type: u8,
+    /// The variation of the default match type to pick, e.g. a weight class. Games that don't have it get the plain match type instead.
+    pub default_match_subtype: u8,
     pub data_path: std::path::PathBuf,
+    /// A GBA BIOS to run games on instead of the emulated one. Both sides of a match have to use the same BIOS, or neither.
+    pub bios_path: Option<std::path::PathBuf>,
     pub full_screen: bool,
     pub streamer_mode: bool,
     pub show_own_setup: bool,
+    pub show_practice_overlay: bool,
+    /// Whether to pause single player sessions and replays while the window isn't focused. Netplay is never paused.
+    pub pause_on_focus_loss: bool,
     pub graphics_backend: GraphicsBackend,
     pub audio_backend: AudioBackend,
+    /// The name of the output device to play audio on. If it's missing, the default device is used instead.
+    pub audio_device: Option<String>,
     pub volume: i32,
     pub ui_scale_percent: u32,
     pub allow_prerelease_upgrades: bool,
     pub enable_updater: bool,
     pub integer_scaling: bool,
+    /// Whether presenting waits for the display's vertical sync. Emulation keeps its own pace either way.
+    pub vsync: bool,
+    /// Whether to blend between emulator frames when the display refreshes faster than the game runs.
+    pub frame_interpolation: bool,
+    pub enable_broadcast_api: bool,
+    pub broadcast_api_port: u16,
+    pub notify_on_opponent_join: bool,
+    pub notify_on_opponent_compatible: bool,
+    pub notify_on_opponent_ready: bool,
+    pub notification_sound_volume: i32,
+    pub notification_sound_muted: bool,
+    pub save_write_back_policy: SaveWriteBackPolicy,
+    pub lobby_presets: Vec<LobbyPreset>,
+    /// How many chip and element textures each save view keeps loaded before freeing the least recently used ones.
+    pub texture_cache_capacity: usize,
+    /// Whether to record matches as replays, which can still be changed in the lobby for each match.
+    pub replay_recording: net::protocol::ReplayRecording,
+    /// Log levels by subsystem, e.g. `"net": "debug"`. Subsystems left out are logged at the default level.
+    pub log_levels: std::collections::BTreeMap<String, String>,
+    /// Content rules for public events, which only organizers should need to set.
+    pub content_policy: contentpolicy::Policy,
+    /// How to get out to the matchmaking server and the internet at large, e.g. through a proxy.
+    pub network: net::proxy::Settings,
+    /// Where to upload replays to automatically, if anywhere.
+    pub replay_upload: replay::upload::Settings,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            installation_id: format!("{:032x}", rand::random::<u128>()),
             nickname: None,
             theme: Theme::System,
             show_debug: Default::default(),
+            show_shadow_view: Default::default(),
             language: i18n::FALLBACK_LANG.parse().unwrap(),
+            game_data_language: None,
             max_queue_length: 1200,
             video_filter: "".to_string(),
             max_scale: 0,
             input_mapping: Default::default(),
+            assist: Default::default(),
+            input_latency_reports: Default::default(),
             matchmaking_endpoint: "".to_string(),
             replaycollector_endpoint: "https://replaycollector.tango.n1gp.net".to_string(),
             patch_repo: "".to_string(),
             enable_patch_autoupdate: true,
             input_delay: 2,
+            desync_detection: true,
+            match_start_timeout_secs: 30,
+            hang_timeout_secs: 10,
             default_match_type: 1,
+            default_match_subtype: 0,
             data_path: "".into(),
+            bios_path: None,
             full_screen: false,
             streamer_mode: false,
             show_own_setup: false,
+            show_practice_overlay: false,
+            pause_on_focus_loss: true,
             graphics_backend: Default::default(),
             audio_backend: Default::default(),
+            audio_device: None,
             volume: 0x100,
             ui_scale_percent: 100,
             allow_prerelease_upgrades: !env!("CARGO_PKG_VERSION")
@@ -134,6 +273,23 @@ impl Default for Config {
                 .is_empty(),
             enable_updater: true,
             integer_scaling: false,
+            vsync: true,
+            frame_interpolation: false,
+            enable_broadcast_api: false,
+            broadcast_api_port: DEFAULT_BROADCAST_API_PORT,
+            notify_on_opponent_join: true,
+            notify_on_opponent_compatible: true,
+            notify_on_opponent_ready: true,
+            notification_sound_volume: 0x100,
+            notification_sound_muted: false,
+            save_write_back_policy: Default::default(),
+            lobby_presets: vec![],
+            texture_cache_capacity: 300,
+            replay_recording: Default::default(),
+            log_levels: Default::default(),
+            content_policy: Default::default(),
+            network: Default::default(),
+            replay_upload: Default::default(),
         }
     }
 }
@@ -156,6 +312,14 @@ pub fn get_updater_path() -> Result<std::path::PathBuf, anyhow::Error> {
         .join("updater"))
 }
 
+pub fn get_scan_cache_path(name: &str) -> Result<std::path::PathBuf, anyhow::Error> {
+    Ok(get_project_dirs()
+        .ok_or_else(|| anyhow::anyhow!("could not get tango project directory"))?
+        .config_dir()
+        .join("scan_cache")
+        .join(format!("{}.json", name)))
+}
+
 const DATA_DIR_NAME: &str = "Tango";
 
 impl Config {
@@ -170,8 +334,9 @@ impl Config {
 
         Ok(Self {
             language: sys_locale::get_locale()
-                .unwrap_or(i18n::FALLBACK_LANG.to_string())
-                .parse()?,
+                .and_then(|locale| locale.parse().ok())
+                .map(|locale| i18n::closest_supported(&locale))
+                .unwrap_or_else(|| i18n::FALLBACK_LANG.parse().unwrap()),
             data_path: tango_data_dir,
             ..Default::default()
         })
@@ -180,19 +345,24 @@ impl Config {
     pub fn create() -> Result<Self, anyhow::Error> {
         let config_path = get_config_path()?;
         let config = Self::system_defaults()?;
-        std::fs::create_dir_all(config_path.parent().unwrap())?;
-        std::fs::write(&config_path, serde_json::to_string(&config)?)?;
+        fs::create_dir_all(config_path.parent().unwrap())?;
+        fs::write(&config_path, serde_json::to_string(&config)?)?;
         Ok(config)
     }
 
     pub fn load_or_create() -> Result<Self, anyhow::Error> {
         let config_path = get_config_path()?;
-        match std::fs::File::open(&config_path) {
+        match fs::open(&config_path) {
             Ok(mut file) => {
                 let mut contents = String::new();
                 file.read_to_string(&mut contents)?;
-                match serde_json::from_str(&contents) {
-                    Ok(config) => Ok(config),
+                match serde_json::from_str::<serde_json::Value>(&contents).and_then(Self::migrate) {
+                    Ok((config, needs_save)) => {
+                        if needs_save {
+                            config.save()?;
+                        }
+                        Ok(config)
+                    }
                     Err(err) => {
                         log::error!("error loading config, creating new config: {}", err);
                         Self::create()
@@ -204,9 +374,29 @@ impl Config {
         }
     }
 
+    /// Reads a config written by any version of Tango, bringing it up to date. This also returns whether the result must be saved right away.
+    ///
+    /// Everything a config is read from goes through here, including imported profiles.
+    pub fn migrate(mut value: serde_json::Value) -> Result<(Self, bool), serde_json::Error> {
+        // Buttons used to be bound to a single physical input rather than a list of them.
+        if let Some(input_mapping) = value.get_mut("input_mapping").and_then(|v| v.as_object_mut()) {
+            for bindings in input_mapping.values_mut() {
+                if bindings.is_null() {
+                    *bindings = serde_json::Value::Array(vec![]);
+                } else if !bindings.is_array() {
+                    *bindings = serde_json::Value::Array(vec![bindings.take()]);
+                }
+            }
+        }
+
+        // Configs from before installation IDs were a thing just got a new one: it has to be saved right away so it doesn't change on every launch.
+        let needs_installation_id = value.get("installation_id").is_none();
+        Ok((serde_json::from_value::<Self>(value)?, needs_installation_id))
+    }
+
     pub fn save(&self) -> Result<(), anyhow::Error> {
         let contents = serde_json::to_string(self)?;
-        let mut file = std::fs::File::create(get_config_path()?)?;
+        let mut file = fs::create(get_config_path()?)?;
         file.write_all(contents.as_bytes())?;
         Ok(())
     }
@@ -223,6 +413,27 @@ impl Config {
         self.data_path.join("replays")
     }
 
+    pub fn opponents_path(&self) -> std::path::PathBuf {
+        self.data_path.join("opponents.json")
+    }
+
+    pub fn replay_upload_queue_path(&self) -> std::path::PathBuf {
+        self.data_path.join("replay_upload_queue.json")
+    }
+
+    pub fn save_patch_history_path(&self) -> std::path::PathBuf {
+        self.data_path.join("save_patches.json")
+    }
+
+    pub fn save_registry_path(&self) -> std::path::PathBuf {
+        self.data_path.join("save_registry.json")
+    }
+
+    /// Where saves are copied before being migrated. It's outside the saves directory so the copies aren't picked up as saves.
+    pub fn save_backups_path(&self) -> std::path::PathBuf {
+        self.data_path.join("save_backups")
+    }
+
     pub fn patches_path(&self) -> std::path::PathBuf {
         self.data_path.join("patches")
     }
@@ -235,16 +446,42 @@ impl Config {
         self.data_path.join("crashstates")
     }
 
+    pub fn states_path(&self) -> std::path::PathBuf {
+        self.data_path.join("states")
+    }
+
+    pub fn crash_reports_path(&self) -> std::path::PathBuf {
+        self.data_path.join("crash_reports")
+    }
+
+    pub fn themes_path(&self) -> std::path::PathBuf {
+        self.data_path.join("themes")
+    }
+
     pub fn ensure_dirs(&self) -> Result<(), anyhow::Error> {
-        std::fs::create_dir_all(&self.saves_path())?;
-        std::fs::create_dir_all(&self.roms_path())?;
-        std::fs::create_dir_all(&self.replays_path())?;
-        std::fs::create_dir_all(&self.patches_path())?;
-        std::fs::create_dir_all(&self.logs_path())?;
-        std::fs::create_dir_all(&self.crashstates_path())?;
+        fs::create_dir_all(&self.saves_path())?;
+        fs::create_dir_all(&self.roms_path())?;
+        fs::create_dir_all(&self.replays_path())?;
+        fs::create_dir_all(&self.patches_path())?;
+        fs::create_dir_all(&self.logs_path())?;
+        fs::create_dir_all(&self.crashstates_path())?;
+        fs::create_dir_all(&self.crash_reports_path())?;
+        fs::create_dir_all(&self.states_path())?;
+        fs::create_dir_all(&self.themes_path())?;
+        Ok(())
+    }
+
+    /// Moves the data directory, creating everything expected in it. If that fails, the old data directory is kept.
+    pub fn set_data_path(&mut self, data_path: std::path::PathBuf) -> Result<(), anyhow::Error> {
+        let old_data_path = std::mem::replace(&mut self.data_path, data_path);
+        if let Err(e) = self.ensure_dirs() {
+            self.data_path = old_data_path;
+            return Err(e);
+        }
         Ok(())
     }
 }
 
 pub const DEFAULT_MATCHMAKING_ENDPOINT: &str = "wss://matchmaking.tango.n1gp.net";
 pub const DEFAULT_PATCH_REPO: &str = "https://patches.tango.n1gp.net";
+pub const DEFAULT_BROADCAST_API_PORT: u16 = 12463;
@@ -0,0 +1,305 @@
+//! Portable snapshots of parts of the config, for setting up Tango the same way on another machine.
+//!
+//! Profiles are TOML, with a table for each section they include. Settings are stored under the same names as in the config, so anything a newer or older version of Tango wrote goes through the same migration as the config itself.
+
+use crate::config;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Section {
+    General,
+    Input,
+    Hotkeys,
+    Paths,
+}
+
+impl Section {
+    pub const ALL: &'static [Section] = &[Section::General, Section::Input, Section::Hotkeys, Section::Paths];
+
+    /// The table the section is written to.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Section::General => "general",
+            Section::Input => "input",
+            Section::Hotkeys => "hotkeys",
+            Section::Paths => "paths",
+        }
+    }
+
+    /// Whether the section is exported unless it's deselected. Paths differ from machine to machine, so they aren't.
+    pub fn is_exported_by_default(&self) -> bool {
+        *self != Section::Paths
+    }
+}
+
+/// Inputs in the input mapping that control Tango rather than the game.
+const HOTKEYS: &[&str] = &[
+    "speed_up",
+    "slow_motion",
+    "menu",
+    "save_state",
+    "load_state",
+    "save_states",
+    "replay_loop_start",
+    "replay_loop_end",
+    "replay_step_back",
+    "toggle_streamer_mode",
+];
+
+const PATHS: &[&str] = &["data_path", "bios_path"];
+
+/// Settings that are never exported, as they identify this installation or were measured on its hardware.
+const MACHINE_SPECIFIC: &[&str] = &["installation_id", "input_latency_reports"];
+
+/// Credentials inside otherwise exported settings, which are left out as profiles get passed around. Importing a profile keeps whatever is set here.
+const CREDENTIALS: &[&[&str]] = &[&["network", "proxy", "password"], &["replay_upload", "token"]];
+
+/// TURN server fields left out for the same reason. Servers are matched up by URL when importing.
+const TURN_SERVER_CREDENTIALS: &[&str] = &["username", "credential"];
+
+fn turn_servers_mut(config: &mut serde_json::Value) -> impl Iterator<Item = &mut serde_json::Value> {
+    config
+        .get_mut("network")
+        .and_then(|network| network.get_mut("turn_servers"))
+        .and_then(|turn_servers| turn_servers.as_array_mut())
+        .into_iter()
+        .flatten()
+}
+
+fn strip_credentials(config: &mut serde_json::Value) {
+    for path in CREDENTIALS {
+        if let Some((last, parents)) = path.split_last() {
+            if let Some(parent) = parents.iter().try_fold(&mut *config, |value, key| value.get_mut(key)) {
+                if let Some(parent) = parent.as_object_mut() {
+                    parent.remove(*last);
+                }
+            }
+        }
+    }
+    for turn_server in turn_servers_mut(config) {
+        if let Some(turn_server) = turn_server.as_object_mut() {
+            for key in TURN_SERVER_CREDENTIALS {
+                turn_server.remove(*key);
+            }
+        }
+    }
+}
+
+/// Puts the credentials from `current` back into a config that had a profile applied to it.
+fn keep_credentials(config: &mut serde_json::Value, current: &serde_json::Value) {
+    for path in CREDENTIALS {
+        let path = path.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+        if let Some(credential) = get(current, &path) {
+            set(config, &path, credential.clone());
+        }
+    }
+    let current_turn_servers = current
+        .get("network")
+        .and_then(|network| network.get("turn_servers"))
+        .and_then(|turn_servers| turn_servers.as_array())
+        .map(|turn_servers| turn_servers.as_slice())
+        .unwrap_or_default();
+    for turn_server in turn_servers_mut(config) {
+        let current_turn_server = if let Some(current_turn_server) = current_turn_servers
+            .iter()
+            .find(|current_turn_server| current_turn_server.get("url") == turn_server.get("url"))
+        {
+            current_turn_server
+        } else {
+            continue;
+        };
+        for key in TURN_SERVER_CREDENTIALS {
+            if let Some(credential) = current_turn_server.get(*key) {
+                turn_server[*key] = credential.clone();
+            }
+        }
+    }
+}
+
+/// A single setting, by its path in the config, e.g. `input_mapping.a`.
+#[derive(Clone, Debug)]
+struct Entry {
+    section: Section,
+    path: Vec<String>,
+    value: serde_json::Value,
+}
+
+fn entries(config: &serde_json::Value) -> Vec<Entry> {
+    let mut config = config.clone();
+    strip_credentials(&mut config);
+
+    let mut entries = vec![];
+    for (key, value) in config.as_object().into_iter().flatten() {
+        if MACHINE_SPECIFIC.contains(&key.as_str()) {
+            continue;
+        }
+
+        // Input mappings and hotkeys live together in the config, but it makes sense to carry them over separately.
+        if key == "input_mapping" {
+            for (input, value) in value.as_object().into_iter().flatten() {
+                entries.push(Entry {
+                    section: if HOTKEYS.contains(&input.as_str()) {
+                        Section::Hotkeys
+                    } else {
+                        Section::Input
+                    },
+                    path: vec![key.clone(), input.clone()],
+                    value: value.clone(),
+                });
+            }
+            continue;
+        }
+
+        entries.push(Entry {
+            section: if PATHS.contains(&key.as_str()) {
+                Section::Paths
+            } else if key == "assist" {
+                Section::Input
+            } else {
+                Section::General
+            },
+            path: vec![key.clone()],
+            value: value.clone(),
+        });
+    }
+    entries
+}
+
+fn get<'a>(value: &'a serde_json::Value, path: &[String]) -> Option<&'a serde_json::Value> {
+    path.iter().try_fold(value, |value, key| value.get(key))
+}
+
+fn set(value: &mut serde_json::Value, path: &[String], new_value: serde_json::Value) {
+    let (last, parents) = if let Some(split) = path.split_last() {
+        split
+    } else {
+        return;
+    };
+    let mut value = value;
+    for key in parents {
+        if !value.get(key).map(|v| v.is_object()).unwrap_or(false) {
+            value[key] = serde_json::Value::Object(serde_json::Map::new());
+        }
+        value = &mut value[key];
+    }
+    value[last] = new_value;
+}
+
+/// TOML has no null, so unset settings are left out instead.
+fn strip_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            for v in map.values_mut() {
+                strip_nulls(v);
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for v in values.iter_mut() {
+                strip_nulls(v);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Writes out the settings in the given sections as a profile.
+pub fn export(config: &config::Config, sections: &std::collections::HashSet<Section>) -> anyhow::Result<String> {
+    let mut profile = serde_json::json!({
+        "profile": {
+            "tango_version": env!("CARGO_PKG_VERSION"),
+        },
+    });
+    for entry in entries(&serde_json::to_value(config)?) {
+        if !sections.contains(&entry.section) {
+            continue;
+        }
+        let mut path = vec![entry.section.key().to_string()];
+        path.extend(entry.path);
+        set(&mut profile, &path, entry.value);
+    }
+    strip_nulls(&mut profile);
+    Ok(toml::to_string_pretty(&toml::Value::try_from(profile)?)?)
+}
+
+/// A setting a profile would change.
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub section: Section,
+    /// Where the setting is in the config, e.g. `input_mapping.a`.
+    pub path: String,
+    pub current: serde_json::Value,
+    pub imported: serde_json::Value,
+}
+
+/// A profile read on top of the current config, which can be looked over before it's applied.
+pub struct Import {
+    config: config::Config,
+    pub sections: Vec<Section>,
+    pub changes: Vec<Change>,
+}
+
+impl Import {
+    /// Reads a profile, working out what the config would be with it applied.
+    pub fn new(current: &config::Config, contents: &str) -> anyhow::Result<Self> {
+        let profile = serde_json::to_value(toml::from_str::<toml::Value>(contents)?)?;
+        let current_value = serde_json::to_value(current)?;
+
+        let sections = Section::ALL
+            .iter()
+            .filter(|section| profile.get(section.key()).map(|v| v.is_object()).unwrap_or(false))
+            .cloned()
+            .collect::<Vec<_>>();
+        if sections.is_empty() {
+            anyhow::bail!("no config sections in profile");
+        }
+
+        let mut value = current_value.clone();
+        for section in sections.iter() {
+            let section_value = &profile[section.key()];
+            for entry in entries(&current_value) {
+                if entry.section != *section {
+                    continue;
+                }
+                if let Some(imported) = get(section_value, &entry.path) {
+                    set(&mut value, &entry.path, imported.clone());
+                }
+            }
+        }
+        keep_credentials(&mut value, &current_value);
+
+        let (config, _) = config::Config::migrate(value)?;
+
+        let imported_entries = entries(&serde_json::to_value(&config)?);
+        let changes = entries(&current_value)
+            .into_iter()
+            .filter(|entry| sections.contains(&entry.section))
+            .flat_map(|entry| {
+                let imported = imported_entries
+                    .iter()
+                    .find(|imported| imported.path == entry.path)
+                    .map(|imported| imported.value.clone())
+                    .unwrap_or(serde_json::Value::Null);
+                if imported == entry.value {
+                    return None;
+                }
+                Some(Change {
+                    section: entry.section,
+                    path: entry.path.join("."),
+                    current: entry.value,
+                    imported,
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            config,
+            sections,
+            changes,
+        })
+    }
+
+    /// The config with the profile applied.
+    pub fn into_config(self) -> config::Config {
+        self.config
+    }
+}
@@ -0,0 +1,109 @@
+//! Content rules for public events, e.g. open brackets run by a tournament organizer.
+//!
+//! Everything here is off unless configured. Our own nickname is checked before we can fight with it, while the opponent's is masked wherever it's shown rather than rejected: they may not be playing under the same rules.
+
+use crate::{linkcode, net};
+
+/// What's shown in place of each character of a banned word.
+const MASK: char = '*';
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Policy {
+    /// Words nicknames may not contain, matched regardless of case.
+    pub banned_words: Vec<String>,
+    /// The longest nickname allowed, if it should be shorter than the protocol allows.
+    pub max_nickname_length: Option<usize>,
+    /// A prefix link codes are locked to, e.g. `weekly42-`, so participants can't typo their way into someone else's bracket.
+    pub link_code_prefix: Option<String>,
+}
+
+lazy_static! {
+    static ref POLICY: parking_lot::RwLock<Policy> = parking_lot::RwLock::new(Policy::default());
+    /// A link code prefix given on the command line, which takes precedence over the configured one without being saved to the config.
+    static ref LINK_CODE_PREFIX_OVERRIDE: parking_lot::RwLock<Option<String>> = parking_lot::RwLock::new(None);
+}
+
+/// Applies the configured policy.
+pub fn set(policy: &Policy) {
+    *POLICY.write() = policy.clone();
+}
+
+/// Locks link codes to a prefix for as long as we're running, whatever the config says.
+pub fn override_link_code_prefix(prefix: String) {
+    *LINK_CODE_PREFIX_OVERRIDE.write() = Some(prefix);
+}
+
+/// The prefix link codes are locked to, if any, normalized like a link code as typed. Invalid prefixes are ignored, as nothing could ever be connected to with them.
+pub fn link_code_prefix() -> Option<String> {
+    let prefix = LINK_CODE_PREFIX_OVERRIDE
+        .read()
+        .clone()
+        .or_else(|| POLICY.read().link_code_prefix.clone())?;
+    let prefix = linkcode::normalize(&prefix);
+    if prefix.is_empty() {
+        return None;
+    }
+    if let Err(e) = linkcode::validate(&prefix) {
+        log::warn!("ignoring link code prefix {:?}: {}", prefix, e);
+        return None;
+    }
+    Some(prefix)
+}
+
+/// The longest nickname allowed, in characters.
+pub fn max_nickname_length() -> usize {
+    POLICY
+        .read()
+        .max_nickname_length
+        .map(|max| max.min(net::protocol::MAX_NICKNAME_LENGTH))
+        .unwrap_or(net::protocol::MAX_NICKNAME_LENGTH)
+}
+
+fn lowercase_chars(s: &str) -> Vec<char> {
+    // Only the first character of each lowercasing is kept, so positions line up with the original.
+    s.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect()
+}
+
+/// Finds where each banned word appears in a nickname, as ranges of character positions.
+fn banned_ranges(nickname: &str) -> Vec<(String, std::ops::Range<usize>)> {
+    let policy = POLICY.read();
+    let nickname = lowercase_chars(nickname);
+    let mut ranges = vec![];
+    for word in policy.banned_words.iter() {
+        let needle = lowercase_chars(word.trim());
+        if needle.is_empty() || needle.len() > nickname.len() {
+            continue;
+        }
+        for start in 0..=nickname.len() - needle.len() {
+            if nickname[start..start + needle.len()] == needle[..] {
+                ranges.push((word.clone(), start..start + needle.len()));
+            }
+        }
+    }
+    ranges
+}
+
+/// Finds a banned word in a nickname, if there is one.
+pub fn banned_word_in(nickname: &str) -> Option<String> {
+    banned_ranges(nickname).into_iter().next().map(|(word, _)| word)
+}
+
+/// How a nickname should be shown: with any banned words masked out. Every nickname from someone else should go through this before it's shown.
+pub fn display_nickname(nickname: &str) -> String {
+    let ranges = banned_ranges(nickname);
+    if ranges.is_empty() {
+        return nickname.to_string();
+    }
+    nickname
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if ranges.iter().any(|(_, range)| range.contains(&i)) {
+                MASK
+            } else {
+                c
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,237 @@
+//! Local crash reports.
+//!
+//! When Tango crashes, a report directory is written under the crash reports path so that it can be attached to a bug report. Nothing here ever leaves the user's machine.
+//!
+//! Reports contain the panic message and backtrace, the most recent log lines, a summary of what was going on at the time, and the replay of the round in progress, if any. Save files must never be copied into a report.
+
+const MAX_LOG_LINES: usize = 500;
+
+/// Reports containing this file haven't been acknowledged by the user yet.
+const UNSEEN_MARKER_FILENAME: &str = "unseen";
+
+lazy_static! {
+    static ref CONTEXT: parking_lot::Mutex<Context> = parking_lot::Mutex::new(Context::default());
+    static ref SAVE_RESCUE: parking_lot::Mutex<Option<Box<dyn Fn() + Send>>> = parking_lot::Mutex::new(None);
+}
+
+static REPORT_WRITTEN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// What Tango was doing, for inclusion in crash reports.
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    pub mode: Option<&'static str>,
+    pub game: Option<(String, u8)>,
+    pub patch: Option<(String, semver::Version)>,
+    pub match_type: Option<(u8, u8)>,
+    pub round_number: Option<u8>,
+    pub replay_path: Option<std::path::PathBuf>,
+}
+
+impl std::fmt::Display for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode = if let Some(mode) = self.mode {
+            mode
+        } else {
+            return writeln!(f, "mode: none");
+        };
+        writeln!(f, "mode: {}", mode)?;
+        if let Some((family, variant)) = self.game.as_ref() {
+            writeln!(f, "game: {} {}", family, variant)?;
+        }
+        if let Some((name, version)) = self.patch.as_ref() {
+            writeln!(f, "patch: {} v{}", name, version)?;
+        }
+        if let Some((typ, subtype)) = self.match_type {
+            writeln!(f, "match type: {} {}", typ, subtype)?;
+        }
+        if let Some(round_number) = self.round_number {
+            writeln!(f, "round: {}", round_number)?;
+        }
+        Ok(())
+    }
+}
+
+/// Updates what gets recorded as the current state of things in crash reports.
+pub fn update_context(f: impl FnOnce(&mut Context)) {
+    f(&mut CONTEXT.lock());
+}
+
+/// Sets what to run on a crash to keep unsaved changes to a save from being lost along with it. The save itself stays out of the report.
+pub fn set_save_rescue(rescue: Option<Box<dyn Fn() + Send>>) {
+    *SAVE_RESCUE.lock() = rescue;
+}
+
+fn new_report_path(crash_reports_path: &std::path::Path) -> std::path::PathBuf {
+    crash_reports_path.join(
+        time::OffsetDateTime::from(std::time::SystemTime::now())
+            .format(time::macros::format_description!(
+                "[year padding:zero][month padding:zero repr:numerical][day padding:zero][hour padding:zero][minute padding:zero][second padding:zero]"
+            ))
+            .expect("format time"),
+    )
+}
+
+fn write_report(
+    crash_reports_path: &std::path::Path,
+    description: &str,
+    log_lines: &[String],
+    context: Option<&Context>,
+) -> std::io::Result<std::path::PathBuf> {
+    let path = new_report_path(crash_reports_path);
+    std::fs::create_dir_all(&path)?;
+
+    std::fs::write(path.join("crash.txt"), description)?;
+    std::fs::write(path.join("log.txt"), log_lines.join("\n"))?;
+
+    if let Some(context) = context {
+        std::fs::write(path.join("context.txt"), context.to_string())?;
+        if let Some(replay_path) = context.replay_path.as_ref() {
+            if let Some(filename) = replay_path.file_name() {
+                // The replay may be truncated, as the writer won't have been flushed.
+                if let Err(e) = std::fs::copy(replay_path, path.join(filename)) {
+                    log::error!(
+                        "failed to copy replay {} to crash report: {:?}",
+                        replay_path.display(),
+                        e
+                    );
+                }
+            }
+        }
+    } else {
+        std::fs::write(path.join("context.txt"), "unavailable\n")?;
+    }
+
+    std::fs::write(path.join(UNSEEN_MARKER_FILENAME), "")?;
+    Ok(path)
+}
+
+/// Installs a panic hook that writes a crash report, in addition to doing whatever the default hook does.
+///
+/// Only the first panic writes a report, as later ones are usually fallout from it.
+pub fn install_panic_hook(crash_reports_path: std::path::PathBuf) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        if REPORT_WRITTEN.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            return;
+        }
+
+        let message = if let Some(s) = info.payload().downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = info.payload().downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "(unknown)".to_string()
+        };
+        let description = format!(
+            "tango {} panicked on thread {}: {}\nlocation: {}\n\n{:?}",
+            crate::version::VERSION,
+            std::thread::current().name().unwrap_or("<unnamed>"),
+            message,
+            info.location()
+                .map(|location| location.to_string())
+                .unwrap_or_else(|| "(unknown)".to_string()),
+            backtrace::Backtrace::new()
+        );
+
+        // Don't wait forever on these: we may have panicked while holding one of them.
+        let log_lines = crate::logging::try_recent_lines(std::time::Duration::from_millis(100)).unwrap_or_default();
+        let log_lines = &log_lines[log_lines.len().saturating_sub(MAX_LOG_LINES)..];
+        let context = CONTEXT
+            .try_lock_for(std::time::Duration::from_millis(100))
+            .map(|context| context.clone());
+
+        match write_report(&crash_reports_path, &description, log_lines, context.as_ref()) {
+            Ok(path) => {
+                log::error!("wrote crash report to {}", path.display());
+            }
+            Err(e) => {
+                log::error!("failed to write crash report: {:?}", e);
+            }
+        }
+
+        if let Some(rescue) = SAVE_RESCUE.try_lock_for(std::time::Duration::from_millis(100)) {
+            if let Some(rescue) = rescue.as_ref() {
+                rescue();
+            }
+        }
+    }));
+}
+
+/// Writes a report for an emulator that stopped responding, while Tango itself carries on.
+///
+/// The replay of the round in progress is copied as far as it was written when the hang was noticed.
+pub fn write_report_for_hang(crash_reports_path: &std::path::Path, hang: &str) -> std::io::Result<std::path::PathBuf> {
+    let log_lines = crate::logging::try_recent_lines(std::time::Duration::from_secs(1)).unwrap_or_default();
+    let context = CONTEXT.lock().clone();
+    write_report(
+        crash_reports_path,
+        &format!(
+            "tango {}: emulator stopped responding\n{}\n",
+            crate::version::VERSION,
+            hang
+        ),
+        &log_lines[log_lines.len().saturating_sub(MAX_LOG_LINES)..],
+        Some(&context),
+    )
+}
+
+/// Writes a crash report for a child process that died without writing one itself, e.g. because it segfaulted.
+///
+/// All we have to go on is its exit status and the log it wrote.
+pub fn write_report_for_child(
+    crash_reports_path: &std::path::Path,
+    status: &std::process::ExitStatus,
+    log_path: &std::path::Path,
+) -> std::io::Result<std::path::PathBuf> {
+    let log = std::fs::read_to_string(log_path).unwrap_or_default();
+    let log_lines = log.lines().collect::<Vec<_>>();
+    let log_lines = log_lines[log_lines.len().saturating_sub(MAX_LOG_LINES)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>();
+    write_report(
+        crash_reports_path,
+        &format!("tango {} exited abnormally: {}\n", crate::version::VERSION, status),
+        &log_lines,
+        None,
+    )
+}
+
+/// Finds the most recent report written at or after the given time.
+pub fn latest_report_since(
+    crash_reports_path: &std::path::Path,
+    since: std::time::SystemTime,
+) -> Option<std::path::PathBuf> {
+    std::fs::read_dir(crash_reports_path)
+        .ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            if modified < since {
+                return None;
+            }
+            Some((modified, entry.path()))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Lists reports the user hasn't acknowledged yet, oldest first.
+pub fn unseen_reports(crash_reports_path: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut reports = std::fs::read_dir(crash_reports_path)
+        .map(|read_dir| {
+            read_dir
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.join(UNSEEN_MARKER_FILENAME).exists())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    reports.sort();
+    reports
+}
+
+pub fn mark_seen(report_path: &std::path::Path) -> std::io::Result<()> {
+    std::fs::remove_file(report_path.join(UNSEEN_MARKER_FILENAME))
+}
@@ -0,0 +1,486 @@
+//! A rough estimate of how likely a match is to desync, put together in the lobby from everything already known before committing.
+//!
+//! Each factor is rated on its own, and the match is rated as its worst factor. None of this stops anyone from playing: it's only there so nobody is surprised.
+
+use crate::{determinism, game, i18n, opponents, stats};
+
+/// How many round trips have to have been measured before the connection is rated at all.
+const MIN_LATENCY_SAMPLES: usize = 5;
+
+/// Median round trips past these make for deep rollbacks.
+const LATENCY_ELEVATED: std::time::Duration = std::time::Duration::from_millis(150);
+const LATENCY_HIGH: std::time::Duration = std::time::Duration::from_millis(300);
+
+/// How far the worst round trips are above the median. The data channel is reliable, so lost packets show up here as retransmission spikes.
+const JITTER_ELEVATED: std::time::Duration = std::time::Duration::from_millis(60);
+const JITTER_HIGH: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// The share of rounds against an opponent that desynced before it's rated high rather than elevated.
+const DESYNC_RATE_HIGH: f32 = 0.2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Low,
+    Elevated,
+    High,
+}
+
+impl Level {
+    pub fn description(&self, language: &unic_langid::LanguageIdentifier) -> String {
+        i18n::LOCALES.lookup(
+            language,
+            match self {
+                Level::Low => "desync-risk.low",
+                Level::Elevated => "desync-risk.elevated",
+                Level::High => "desync-risk.high",
+            },
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Factor {
+    /// Not enough round trips have been measured yet to say anything about the connection.
+    MeasuringConnection,
+    Latency {
+        p50: std::time::Duration,
+    },
+    Jitter {
+        spread: std::time::Duration,
+    },
+    /// A game that can't be fully rolled back, so mispredictions may not be undone properly.
+    NoRollback {
+        family: String,
+        variant: u8,
+    },
+    /// The opponent's ROM, patched as they picked, doesn't come out the same here as it does for them.
+    RomMismatch,
+    /// One side's emulator configuration couldn't be worked out, so it can't be checked.
+    UnknownConfiguration,
+    ConfigurationMismatch(Vec<determinism::Difference>),
+    DesyncHistory {
+        desyncs: u32,
+        rounds: u32,
+    },
+}
+
+impl Factor {
+    /// What the factor is.
+    pub fn description(&self, language: &unic_langid::LanguageIdentifier) -> String {
+        match self {
+            Factor::MeasuringConnection => i18n::LOCALES.lookup(language, "desync-risk-factor-measuring-connection"),
+            Factor::Latency { p50 } => i18n::LOCALES.lookup_with_args(
+                language,
+                "desync-risk-factor-latency",
+                &std::collections::HashMap::from([("p50", (p50.as_millis() as u64).into())]),
+            ),
+            Factor::Jitter { spread } => i18n::LOCALES.lookup_with_args(
+                language,
+                "desync-risk-factor-jitter",
+                &std::collections::HashMap::from([("spread", (spread.as_millis() as u64).into())]),
+            ),
+            Factor::NoRollback { family, variant } => i18n::LOCALES.lookup_with_args(
+                language,
+                "desync-risk-factor-no-rollback",
+                &std::collections::HashMap::from([(
+                    "game_name",
+                    i18n::LOCALES
+                        .lookup(language, &format!("game-{}.variant-{}", family, variant))
+                        .into(),
+                )]),
+            ),
+            Factor::RomMismatch => i18n::LOCALES.lookup(language, "desync-risk-factor-rom-mismatch"),
+            Factor::UnknownConfiguration => i18n::LOCALES.lookup(language, "desync-risk-factor-unknown-configuration"),
+            Factor::ConfigurationMismatch(differences) => i18n::LOCALES.lookup_with_args(
+                language,
+                "desync-risk-factor-configuration-mismatch",
+                &std::collections::HashMap::from([(
+                    "differences",
+                    determinism::describe_differences(differences, language).into(),
+                )]),
+            ),
+            Factor::DesyncHistory { desyncs, rounds } => i18n::LOCALES.lookup_with_args(
+                language,
+                "desync-risk-factor-desync-history",
+                &std::collections::HashMap::from([("desyncs", (*desyncs).into()), ("rounds", (*rounds).into())]),
+            ),
+        }
+    }
+
+    /// What can be done about the factor.
+    pub fn advice(&self, language: &unic_langid::LanguageIdentifier) -> String {
+        i18n::LOCALES.lookup(
+            language,
+            match self {
+                Factor::MeasuringConnection => "desync-risk-factor-measuring-connection.advice",
+                Factor::Latency { .. } => "desync-risk-factor-latency.advice",
+                Factor::Jitter { .. } => "desync-risk-factor-jitter.advice",
+                Factor::NoRollback { .. } => "desync-risk-factor-no-rollback.advice",
+                Factor::RomMismatch => "desync-risk-factor-rom-mismatch.advice",
+                Factor::UnknownConfiguration => "desync-risk-factor-unknown-configuration.advice",
+                Factor::ConfigurationMismatch(_) => "desync-risk-factor-configuration-mismatch.advice",
+                Factor::DesyncHistory { .. } => "desync-risk-factor-desync-history.advice",
+            },
+        )
+    }
+}
+
+/// A factor and how much it adds to the risk.
+#[derive(Clone, Debug)]
+pub struct Contribution {
+    pub factor: Factor,
+    pub level: Level,
+}
+
+impl Contribution {
+    fn new(factor: Factor, level: Level) -> Self {
+        Self { factor, level }
+    }
+}
+
+/// Rates the median round trip.
+pub fn evaluate_latency(latency: &stats::Percentiles) -> Option<Contribution> {
+    let level = if latency.p50 >= LATENCY_HIGH {
+        Level::High
+    } else if latency.p50 >= LATENCY_ELEVATED {
+        Level::Elevated
+    } else {
+        return None;
+    };
+    Some(Contribution::new(Factor::Latency { p50: latency.p50 }, level))
+}
+
+/// Rates how much worse the worst round trips are than the median.
+pub fn evaluate_jitter(latency: &stats::Percentiles) -> Option<Contribution> {
+    let spread = latency.p99.saturating_sub(latency.p50);
+    let level = if spread >= JITTER_HIGH {
+        Level::High
+    } else if spread >= JITTER_ELEVATED {
+        Level::Elevated
+    } else {
+        return None;
+    };
+    Some(Contribution::new(Factor::Jitter { spread }, level))
+}
+
+/// Rates whether both games can be fully rolled back. The opponent's game is judged by the capabilities their build reported for it.
+pub fn evaluate_rollback(
+    local_game: &'static (dyn game::Game + Send + Sync),
+    remote_game: &'static (dyn game::Game + Send + Sync),
+    remote_capabilities: u32,
+) -> Option<Contribution> {
+    let game = if !local_game.hooks().capabilities().rollback {
+        local_game
+    } else if remote_capabilities & game::Capabilities::ROLLBACK == 0 {
+        remote_game
+    } else {
+        return None;
+    };
+    let (family, variant) = game.family_and_variant();
+    Some(Contribution::new(
+        Factor::NoRollback {
+            family: family.to_string(),
+            variant,
+        },
+        Level::Elevated,
+    ))
+}
+
+/// Rates whether the opponent's ROM comes out the same here as it did for them, given the hash we got of it and the one they sent.
+pub fn evaluate_rom(local_hash: Option<&[u8; 16]>, remote_hash: Option<&[u8; 16]>) -> Option<Contribution> {
+    match (local_hash, remote_hash) {
+        (Some(local_hash), Some(remote_hash)) if local_hash != remote_hash => {
+            Some(Contribution::new(Factor::RomMismatch, Level::High))
+        }
+        _ => None,
+    }
+}
+
+/// Rates whether both emulators are configured the same.
+pub fn evaluate_fingerprint(
+    local: Option<&determinism::Fingerprint>,
+    remote: Option<&determinism::Fingerprint>,
+) -> Option<Contribution> {
+    let (local, remote) = match (local, remote) {
+        (Some(local), Some(remote)) => (local, remote),
+        _ => {
+            return Some(Contribution::new(Factor::UnknownConfiguration, Level::Elevated));
+        }
+    };
+    let differences = local.differences(remote);
+    if differences.is_empty() {
+        return None;
+    }
+    Some(Contribution::new(
+        Factor::ConfigurationMismatch(differences),
+        Level::High,
+    ))
+}
+
+/// Rates how often rounds against the opponent have desynced before.
+pub fn evaluate_history(entry: &opponents::Entry) -> Option<Contribution> {
+    if entry.desyncs == 0 {
+        return None;
+    }
+    let rounds = entry.rounds();
+    let level = if entry.desyncs as f32 >= rounds as f32 * DESYNC_RATE_HIGH {
+        Level::High
+    } else {
+        Level::Elevated
+    };
+    Some(Contribution::new(
+        Factor::DesyncHistory {
+            desyncs: entry.desyncs,
+            rounds,
+        },
+        level,
+    ))
+}
+
+/// Everything known in the lobby that goes into the assessment.
+pub struct Inputs<'a> {
+    pub latency: &'a stats::DeltaCounter,
+    pub local_game: Option<&'static (dyn game::Game + Send + Sync)>,
+    pub remote_game: Option<&'static (dyn game::Game + Send + Sync)>,
+    pub remote_capabilities: u32,
+    /// The hash of the opponent's ROM as we patched it.
+    pub local_rom_hash: Option<&'a [u8; 16]>,
+    /// The hash of the opponent's ROM as they patched it.
+    pub remote_rom_hash: Option<&'a [u8; 16]>,
+    pub local_fingerprint: Option<&'a determinism::Fingerprint>,
+    pub remote_fingerprint: Option<&'a determinism::Fingerprint>,
+    pub opponent: Option<&'a opponents::Entry>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Assessment {
+    /// Worst first.
+    pub contributions: Vec<Contribution>,
+}
+
+impl Assessment {
+    pub fn of(inputs: &Inputs) -> Self {
+        let mut contributions = vec![];
+
+        if inputs.latency.sample_count() < MIN_LATENCY_SAMPLES {
+            contributions.push(Contribution::new(Factor::MeasuringConnection, Level::Low));
+        } else {
+            let latency = inputs.latency.percentiles();
+            contributions.extend(evaluate_latency(&latency));
+            contributions.extend(evaluate_jitter(&latency));
+        }
+
+        if let (Some(local_game), Some(remote_game)) = (inputs.local_game, inputs.remote_game) {
+            contributions.extend(evaluate_rollback(local_game, remote_game, inputs.remote_capabilities));
+        }
+        contributions.extend(evaluate_rom(inputs.local_rom_hash, inputs.remote_rom_hash));
+        contributions.extend(evaluate_fingerprint(
+            inputs.local_fingerprint,
+            inputs.remote_fingerprint,
+        ));
+        if let Some(opponent) = inputs.opponent {
+            contributions.extend(evaluate_history(opponent));
+        }
+
+        contributions.sort_by(|a, b| b.level.cmp(&a.level));
+        Self { contributions }
+    }
+
+    pub fn level(&self) -> Level {
+        self.contributions
+            .iter()
+            .map(|contribution| contribution.level)
+            .max()
+            .unwrap_or(Level::Low)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> std::time::Duration {
+        std::time::Duration::from_millis(ms)
+    }
+
+    fn percentiles(p50: u64, p99: u64) -> stats::Percentiles {
+        stats::Percentiles {
+            p50: ms(p50),
+            p95: ms(p99),
+            p99: ms(p99),
+        }
+    }
+
+    fn level(contribution: Option<Contribution>) -> Option<Level> {
+        contribution.map(|contribution| contribution.level)
+    }
+
+    #[test]
+    fn test_evaluate_latency() {
+        assert_eq!(level(evaluate_latency(&percentiles(0, 0))), None);
+        assert_eq!(level(evaluate_latency(&percentiles(149, 149))), None);
+        assert_eq!(level(evaluate_latency(&percentiles(150, 150))), Some(Level::Elevated));
+        assert_eq!(level(evaluate_latency(&percentiles(299, 299))), Some(Level::Elevated));
+        assert_eq!(level(evaluate_latency(&percentiles(300, 300))), Some(Level::High));
+        assert!(matches!(
+            evaluate_latency(&percentiles(200, 400)).unwrap().factor,
+            Factor::Latency { p50 } if p50 == ms(200)
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_jitter() {
+        assert_eq!(level(evaluate_jitter(&percentiles(100, 100))), None);
+        assert_eq!(level(evaluate_jitter(&percentiles(100, 159))), None);
+        assert_eq!(level(evaluate_jitter(&percentiles(100, 160))), Some(Level::Elevated));
+        assert_eq!(level(evaluate_jitter(&percentiles(100, 299))), Some(Level::Elevated));
+        assert_eq!(level(evaluate_jitter(&percentiles(100, 300))), Some(Level::High));
+        // Only the spread counts, however high the latency itself is.
+        assert_eq!(level(evaluate_jitter(&percentiles(1000, 1050))), None);
+        assert!(matches!(
+            evaluate_jitter(&percentiles(100, 250)).unwrap().factor,
+            Factor::Jitter { spread } if spread == ms(150)
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_rollback() {
+        let game = game::GAMES[0];
+        assert!(game.hooks().capabilities().rollback);
+        assert_eq!(level(evaluate_rollback(game, game, game::Capabilities::ROLLBACK)), None);
+
+        let contribution = evaluate_rollback(game, game, 0).unwrap();
+        assert_eq!(contribution.level, Level::Elevated);
+        let (family, variant) = game.family_and_variant();
+        assert!(matches!(
+            contribution.factor,
+            Factor::NoRollback { family: f, variant: v } if f == family && v == variant
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_rom() {
+        let (a, b) = ([1; 16], [2; 16]);
+        assert_eq!(level(evaluate_rom(None, None)), None);
+        assert_eq!(level(evaluate_rom(Some(&a), None)), None);
+        assert_eq!(level(evaluate_rom(None, Some(&b))), None);
+        assert_eq!(level(evaluate_rom(Some(&a), Some(&a))), None);
+        assert_eq!(level(evaluate_rom(Some(&a), Some(&b))), Some(Level::High));
+    }
+
+    #[test]
+    fn test_evaluate_fingerprint() {
+        let fingerprint = determinism::Fingerprint {
+            core_version: "0.10.0".to_string(),
+            options: std::collections::BTreeMap::from([("skipBios".to_string(), "1".to_string())]),
+            bios_hash: None,
+        };
+        assert_eq!(
+            level(evaluate_fingerprint(Some(&fingerprint), Some(&fingerprint))),
+            None
+        );
+        assert_eq!(
+            level(evaluate_fingerprint(Some(&fingerprint), None)),
+            Some(Level::Elevated)
+        );
+        assert_eq!(
+            level(evaluate_fingerprint(None, Some(&fingerprint))),
+            Some(Level::Elevated)
+        );
+
+        let other = determinism::Fingerprint {
+            bios_hash: Some([0; 16]),
+            ..fingerprint.clone()
+        };
+        let contribution = evaluate_fingerprint(Some(&fingerprint), Some(&other)).unwrap();
+        assert_eq!(contribution.level, Level::High);
+        assert!(matches!(
+            contribution.factor,
+            Factor::ConfigurationMismatch(differences) if differences.len() == 1
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_history() {
+        let entry = |wins, desyncs| opponents::Entry {
+            wins,
+            desyncs,
+            ..Default::default()
+        };
+        assert_eq!(level(evaluate_history(&entry(0, 0))), None);
+        assert_eq!(level(evaluate_history(&entry(100, 0))), None);
+        assert_eq!(level(evaluate_history(&entry(100, 1))), Some(Level::Elevated));
+        assert_eq!(level(evaluate_history(&entry(9, 1))), Some(Level::Elevated));
+        // 2 of 10 rounds is exactly the rate that's rated high.
+        assert_eq!(level(evaluate_history(&entry(8, 2))), Some(Level::High));
+        assert_eq!(level(evaluate_history(&entry(0, 1))), Some(Level::High));
+        assert!(matches!(
+            evaluate_history(&entry(8, 2)).unwrap().factor,
+            Factor::DesyncHistory { desyncs: 2, rounds: 10 }
+        ));
+    }
+
+    fn inputs<'a>(latency: &'a stats::DeltaCounter, fingerprint: &'a determinism::Fingerprint) -> Inputs<'a> {
+        Inputs {
+            latency,
+            local_game: None,
+            remote_game: None,
+            remote_capabilities: 0,
+            local_rom_hash: None,
+            remote_rom_hash: None,
+            local_fingerprint: Some(fingerprint),
+            remote_fingerprint: Some(fingerprint),
+            opponent: None,
+        }
+    }
+
+    #[test]
+    fn test_assessment() {
+        let fingerprint = determinism::Fingerprint::default();
+
+        let mut latency = stats::DeltaCounter::new(10, ms(1000));
+        let assessment = Assessment::of(&inputs(&latency, &fingerprint));
+        assert!(matches!(
+            assessment.contributions.as_slice(),
+            [Contribution {
+                factor: Factor::MeasuringConnection,
+                level: Level::Low
+            }]
+        ));
+        assert_eq!(assessment.level(), Level::Low);
+
+        for _ in 0..MIN_LATENCY_SAMPLES {
+            latency.mark(ms(20));
+        }
+        let assessment = Assessment::of(&inputs(&latency, &fingerprint));
+        assert!(assessment.contributions.is_empty());
+        assert_eq!(assessment.level(), Level::Low);
+
+        // The match is rated as its worst factor, which is listed first.
+        for _ in 0..MIN_LATENCY_SAMPLES {
+            latency.mark(ms(200));
+        }
+        let opponent = opponents::Entry {
+            wins: 1,
+            desyncs: 1,
+            ..Default::default()
+        };
+        let assessment = Assessment::of(&Inputs {
+            opponent: Some(&opponent),
+            ..inputs(&latency, &fingerprint)
+        });
+        assert_eq!(
+            assessment
+                .contributions
+                .iter()
+                .map(|contribution| contribution.level)
+                .collect::<Vec<_>>(),
+            vec![Level::High, Level::Elevated]
+        );
+        assert!(matches!(
+            assessment.contributions[0].factor,
+            Factor::DesyncHistory { .. }
+        ));
+        assert_eq!(assessment.level(), Level::High);
+    }
+}
@@ -0,0 +1,217 @@
+use sha3::digest::{ExtendableOutput, Update};
+
+use crate::{i18n, replay};
+
+/// Core options that affect emulation, which we force to a known value during netplay rather than letting them differ.
+const NORMALIZED_OPTIONS: &[(&str, &str)] = &[
+    // Idle loop removal skips ahead while the game waits for an interrupt, which isn't guaranteed to land on the same cycle everywhere.
+    ("idleOptimization", "ignore"),
+];
+
+/// Core options that affect emulation but that we leave up to the user, so they must be checked instead.
+const CHECKED_OPTIONS: &[&str] = &["useBios", "skipBios"];
+
+/// Everything about the emulator configuration that must match for two cores to stay in sync.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct Fingerprint {
+    pub core_version: String,
+    pub options: std::collections::BTreeMap<String, String>,
+    /// None if the BIOS is emulated.
+    pub bios_hash: Option<[u8; 16]>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Difference {
+    CoreVersion {
+        local: String,
+        remote: String,
+    },
+    Option {
+        key: String,
+        local: Option<String>,
+        remote: Option<String>,
+    },
+    Bios {
+        local: Option<[u8; 16]>,
+        remote: Option<[u8; 16]>,
+    },
+}
+
+impl Difference {
+    pub fn description(&self, language: &unic_langid::LanguageIdentifier) -> String {
+        let option_value = |v: &Option<String>| {
+            v.clone()
+                .unwrap_or_else(|| i18n::LOCALES.lookup(language, "configuration-difference-unset"))
+        };
+        let bios = |v: &Option<[u8; 16]>| {
+            v.map(|hash| hash[..4].iter().map(|b| format!("{:02x}", b)).collect::<String>())
+                .unwrap_or_else(|| i18n::LOCALES.lookup(language, "configuration-difference-bios-emulated"))
+        };
+
+        match self {
+            Difference::CoreVersion { local, remote } => i18n::LOCALES.lookup_with_args(
+                language,
+                "configuration-difference-core-version",
+                &std::collections::HashMap::from([("local", local.clone().into()), ("remote", remote.clone().into())]),
+            ),
+            Difference::Option { key, local, remote } => i18n::LOCALES.lookup_with_args(
+                language,
+                "configuration-difference-option",
+                &std::collections::HashMap::from([
+                    ("key", key.clone().into()),
+                    ("local", option_value(local).into()),
+                    ("remote", option_value(remote).into()),
+                ]),
+            ),
+            Difference::Bios { local, remote } => i18n::LOCALES.lookup_with_args(
+                language,
+                "configuration-difference-bios",
+                &std::collections::HashMap::from([("local", bios(local).into()), ("remote", bios(remote).into())]),
+            ),
+        }
+    }
+}
+
+pub fn describe_differences(differences: &[Difference], language: &unic_langid::LanguageIdentifier) -> String {
+    differences
+        .iter()
+        .map(|d| d.description(language))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("emulator configuration mismatch: {0:?}")]
+pub struct MismatchError(pub Vec<Difference>);
+
+/// Forces the options we can to known values, so they can't cause desyncs.
+pub fn normalize(core: &mut mgba::core::Core) {
+    for (key, value) in NORMALIZED_OPTIONS {
+        core.set_config_override(key, value);
+    }
+}
+
+pub fn hash_bios(bios: &[u8]) -> [u8; 16] {
+    let mut shake128 = sha3::Shake128::default();
+    shake128.update(b"tango:bios:");
+    shake128.update(bios);
+    let mut hash = [0u8; 16];
+    shake128.finalize_xof_into(&mut hash);
+    hash
+}
+
+/// Hashes a ROM, patched if it is, so both sides can check they're running the same thing.
+pub fn hash_rom(rom: &[u8]) -> [u8; 16] {
+    let mut shake128 = sha3::Shake128::default();
+    shake128.update(b"tango:rom:");
+    shake128.update(rom);
+    let mut hash = [0u8; 16];
+    shake128.finalize_xof_into(&mut hash);
+    hash
+}
+
+impl Fingerprint {
+    /// Computes the fingerprint of a core, given the BIOS loaded into it, if any.
+    pub fn of(core: &mgba::core::Core, bios: Option<&[u8]>) -> Self {
+        Self {
+            core_version: format!("{} ({})", mgba::version::project_version(), mgba::version::git_commit()),
+            options: NORMALIZED_OPTIONS
+                .iter()
+                .map(|(key, _)| *key)
+                .chain(CHECKED_OPTIONS.iter().cloned())
+                .flat_map(|key| core.config_value(key).map(|value| (key.to_string(), value)))
+                .collect(),
+            bios_hash: bios.map(hash_bios),
+        }
+    }
+
+    /// Computes the fingerprint a normalized netplay core would have with the given BIOS, without having to start a session.
+    pub fn local(bios: Option<&[u8]>) -> anyhow::Result<Self> {
+        let mut core = mgba::core::Core::new_gba("tango")?;
+        normalize(&mut core);
+        Ok(Self::of(&core, bios))
+    }
+
+    pub fn differences(&self, remote: &Self) -> Vec<Difference> {
+        let mut differences = vec![];
+
+        if self.core_version != remote.core_version {
+            differences.push(Difference::CoreVersion {
+                local: self.core_version.clone(),
+                remote: remote.core_version.clone(),
+            });
+        }
+
+        let keys = self
+            .options
+            .keys()
+            .chain(remote.options.keys())
+            .collect::<std::collections::BTreeSet<_>>();
+        for key in keys {
+            let local = self.options.get(key);
+            let remote = remote.options.get(key);
+            if local != remote {
+                differences.push(Difference::Option {
+                    key: key.clone(),
+                    local: local.cloned(),
+                    remote: remote.cloned(),
+                });
+            }
+        }
+
+        if self.bios_hash != remote.bios_hash {
+            differences.push(Difference::Bios {
+                local: self.bios_hash,
+                remote: remote.bios_hash,
+            });
+        }
+
+        differences
+    }
+
+    pub fn to_replay_fingerprint(&self) -> replay::metadata::Fingerprint {
+        replay::metadata::Fingerprint {
+            core_version: self.core_version.clone(),
+            options: self.options.clone().into_iter().collect(),
+            bios_hash: self.bios_hash.map(|hash| hash.to_vec()).unwrap_or_default(),
+        }
+    }
+
+    pub fn from_replay_fingerprint(fingerprint: &replay::metadata::Fingerprint) -> Self {
+        Self {
+            core_version: fingerprint.core_version.clone(),
+            options: fingerprint.options.clone().into_iter().collect(),
+            bios_hash: fingerprint.bios_hash.as_slice().try_into().ok(),
+        }
+    }
+}
+
+/// Checks that a replay will play back on the given core, with the given BIOS loaded into it, the same way it was recorded.
+///
+/// Replays from before fingerprints were recorded can't be checked, so they're let through. Only the options and BIOS are held against a replay: the core version changes with every update, most of which don't touch emulation, so a different one is only warned about.
+pub fn check_replay(
+    core: &mgba::core::Core,
+    bios: Option<&[u8]>,
+    metadata: &replay::Metadata,
+) -> Result<(), MismatchError> {
+    let recorded = if let Some(recorded) = metadata.fingerprint.as_ref() {
+        Fingerprint::from_replay_fingerprint(recorded)
+    } else {
+        return Ok(());
+    };
+
+    let (core_versions, differences): (Vec<_>, Vec<_>) = Fingerprint::of(core, bios)
+        .differences(&recorded)
+        .into_iter()
+        .partition(|difference| matches!(difference, Difference::CoreVersion { .. }));
+    for difference in core_versions {
+        log::warn!(
+            "replay was recorded on a different core, it may not play back the same: {:?}",
+            difference
+        );
+    }
+    if !differences.is_empty() {
+        return Err(MismatchError(differences));
+    }
+    Ok(())
+}
@@ -1,8 +1,6 @@
 mod rpc;
 
 #[allow(dead_code)]
-use fluent_templates::Loader;
-
 use crate::{game, i18n};
 
 const APP_ID: u64 = 974089681333534750;
@@ -18,7 +16,7 @@ pub fn make_game_info(
     language: &unic_langid::LanguageIdentifier,
 ) -> GameInfo {
     let family = game.family_and_variant().0.to_string();
-    let mut title = i18n::LOCALES.lookup(language, &format!("game-{}", family)).unwrap();
+    let mut title = i18n::LOCALES.lookup(language, &format!("game-{}", family));
     if let Some((patch_name, patch_version)) = patch.as_ref() {
         title.push_str(&format!(" + {} v{}", patch_name, patch_version));
     }
@@ -44,7 +42,7 @@ pub fn make_looking_activity(
     game_info: Option<GameInfo>,
 ) -> rpc::activity::Activity {
     rpc::activity::Activity {
-        state: Some(i18n::LOCALES.lookup(lang, "discord-presence-looking").unwrap()),
+        state: Some(i18n::LOCALES.lookup(lang, "discord-presence-looking")),
         secrets: Some(rpc::activity::Secrets {
             join: Some(link_code.to_string()),
             ..Default::default()
@@ -63,7 +61,7 @@ pub fn make_single_player_activity(
     game_info: Option<GameInfo>,
 ) -> rpc::activity::Activity {
     rpc::activity::Activity {
-        state: Some(i18n::LOCALES.lookup(lang, "discord-presence-in-single-player").unwrap()),
+        state: Some(i18n::LOCALES.lookup(lang, "discord-presence-in-single-player")),
         timestamps: Some(rpc::activity::Timestamps {
             start: start_time
                 .duration_since(std::time::UNIX_EPOCH)
@@ -82,7 +80,7 @@ pub fn make_in_lobby_activity(
     game_info: Option<GameInfo>,
 ) -> rpc::activity::Activity {
     rpc::activity::Activity {
-        state: Some(i18n::LOCALES.lookup(lang, "discord-presence-in-lobby").unwrap()),
+        state: Some(i18n::LOCALES.lookup(lang, "discord-presence-in-lobby")),
         party: Some(rpc::activity::Party {
             id: Some(format!("party:{}", link_code)),
             size: Some([2, 2]),
@@ -98,7 +96,7 @@ pub fn make_in_progress_activity(
     game_info: Option<GameInfo>,
 ) -> rpc::activity::Activity {
     rpc::activity::Activity {
-        state: Some(i18n::LOCALES.lookup(lang, "discord-presence-in-progress").unwrap()),
+        state: Some(i18n::LOCALES.lookup(lang, "discord-presence-in-progress")),
         party: Some(rpc::activity::Party {
             id: Some(format!("party:{}", link_code)),
             size: Some([2, 2]),
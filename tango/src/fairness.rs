@@ -0,0 +1,286 @@
+//! Showing after a match that neither side could have rigged its randomness.
+//!
+//! Before a match, each side commits to a random nonce (along with its save) without revealing it, then both reveal them: the match RNG is seeded with the two nonces combined, so neither side could have picked the seed. Once the match is over, everything needed to check this is written out as a report alongside its replays, along with every draw taken from the match RNG and what each round's game RNG was seeded with.
+//!
+//! How a game turns draws into its own RNG states is game-specific, so the report checks the round states against the replays rather than re-deriving them from the draws.
+
+use rand::{RngCore, SeedableRng};
+use sha3::digest::{ExtendableOutput, Update};
+
+use crate::{battle, bios, game, net, replay, replayer};
+
+/// The extension reports are written with, in place of their first replay's.
+pub const EXTENSION: &str = "fairness.json";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Bios(#[from] bios::Error),
+
+    #[error("the {0} side's revealed state can't be read: {1}")]
+    InvalidState(&'static str, net::protocol::InvalidNegotiatedState),
+
+    #[error("the {0} side's commitment doesn't match its revealed state")]
+    CommitmentMismatch(&'static str),
+
+    #[error("the {0} side's nonce doesn't match its revealed state")]
+    NonceMismatch(&'static str),
+
+    #[error("the seed isn't the two sides' nonces combined")]
+    SeedMismatch,
+
+    #[error("draw {0} from the match RNG doesn't follow from the seed")]
+    DrawMismatch(usize),
+
+    #[error("round {0}'s replay has no starting state")]
+    MissingState(u8),
+
+    #[error("round {round}'s replay started with RNG states {actual:08x?}, but the report says {expected:08x?}")]
+    RoundMismatch {
+        round: u8,
+        expected: Vec<u32>,
+        actual: Vec<u32>,
+    },
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// What a side commits to before a match: its negotiated state, nonce and save included, hashed.
+pub fn commitment(buf: &[u8]) -> [u8; 16] {
+    let mut shake128 = sha3::Shake128::default();
+    shake128.update(b"tango:lobby:");
+    shake128.update(buf);
+    let mut commitment = [0u8; 16];
+    shake128.finalize_xof_into(&mut commitment);
+    commitment
+}
+
+/// The seed both sides derive from their nonces.
+pub fn seed(local_nonce: &[u8; 16], remote_nonce: &[u8; 16]) -> [u8; 16] {
+    let mut seed = [0u8; 16];
+    for (s, (x, y)) in seed.iter_mut().zip(std::iter::zip(local_nonce, remote_nonce)) {
+        *s = x ^ y;
+    }
+    seed
+}
+
+/// A single draw from the match RNG, as the method it was drawn with and what it gave.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Draw {
+    U32(u32),
+    U64(u64),
+    Bytes(Vec<u8>),
+}
+
+/// The match RNG, which keeps every draw taken from it so they can be checked against the seed afterwards.
+pub struct AuditedRng {
+    rng: rand_pcg::Mcg128Xsl64,
+    draws: Vec<Draw>,
+}
+
+impl AuditedRng {
+    pub fn new(seed: [u8; 16]) -> Self {
+        Self {
+            rng: rand_pcg::Mcg128Xsl64::from_seed(seed),
+            draws: vec![],
+        }
+    }
+
+    /// The RNG as it is now, without the log. Draws from a clone aren't logged.
+    pub fn inner(&self) -> &rand_pcg::Mcg128Xsl64 {
+        &self.rng
+    }
+
+    pub fn draws(&self) -> &[Draw] {
+        &self.draws
+    }
+}
+
+impl RngCore for AuditedRng {
+    fn next_u32(&mut self) -> u32 {
+        let v = self.rng.next_u32();
+        self.draws.push(Draw::U32(v));
+        v
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let v = self.rng.next_u64();
+        self.draws.push(Draw::U64(v));
+        v
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest);
+        self.draws.push(Draw::Bytes(dest.to_vec()));
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// What a side revealed in the lobby.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Side {
+    #[serde(with = "serde_hex::SerHex::<serde_hex::Strict>")]
+    pub nonce: [u8; 16],
+    #[serde(with = "serde_hex::SerHex::<serde_hex::Strict>")]
+    pub commitment: [u8; 16],
+    /// The encoded negotiated state the commitment is of.
+    pub negotiated_state: Vec<u8>,
+}
+
+impl Side {
+    fn verify(&self, name: &'static str) -> Result<(), Error> {
+        if commitment(&self.negotiated_state) != self.commitment {
+            return Err(Error::CommitmentMismatch(name));
+        }
+        let state =
+            net::protocol::NegotiatedState::decode(&self.negotiated_state).map_err(|e| Error::InvalidState(name, e))?;
+        if state.nonce != self.nonce {
+            return Err(Error::NonceMismatch(name));
+        }
+        Ok(())
+    }
+}
+
+/// Everything both sides revealed before the match, and the seed they derived from it.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Reveal {
+    #[serde(with = "serde_hex::SerHex::<serde_hex::Strict>")]
+    pub seed: [u8; 16],
+    pub local: Side,
+    pub remote: Side,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Round {
+    pub number: u8,
+    /// The file name of the round's replay, in the same directory as the report, if it was recorded.
+    pub replay: Option<String>,
+    /// What the game's RNGs were seeded with at the start of the round.
+    pub rng_states: Vec<u32>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Report {
+    #[serde(flatten)]
+    pub reveal: Reveal,
+    /// Whether the local side offered the connection, which decides which of the per-side draws was theirs.
+    pub is_offerer: bool,
+    pub draws: Vec<Draw>,
+    pub rounds: Vec<Round>,
+}
+
+impl Report {
+    pub fn new(reveal: Reveal, is_offerer: bool, draws: Vec<Draw>, record: &battle::Record) -> Self {
+        Self {
+            reveal,
+            is_offerer,
+            draws,
+            rounds: record
+                .rounds
+                .iter()
+                .map(|round| Round {
+                    number: round.number,
+                    replay: round
+                        .replay_path
+                        .as_ref()
+                        .and_then(|path| path.file_name())
+                        .map(|name| name.to_string_lossy().to_string()),
+                    rng_states: round.rng_states.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn read(path: &std::path::Path) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    /// Writes the report next to the first round's replay. Nothing is written if no round was recorded, as there would be nothing to check the rounds against.
+    pub fn write(&self, replays_path: &std::path::Path) -> Result<Option<std::path::PathBuf>, Error> {
+        let path = if let Some(replay) = self.rounds.iter().find_map(|round| round.replay.as_ref()) {
+            replays_path.join(replay).with_extension(EXTENSION)
+        } else {
+            return Ok(None);
+        };
+        std::fs::write(&path, serde_json::to_vec_pretty(self)?)?;
+        Ok(Some(path))
+    }
+}
+
+/// Checks a report written at `path`, returning how many rounds were checked against their replays.
+///
+/// The commitments have to match what was revealed, the seed has to come from the nonces, every draw has to follow from the seed, and each recorded round's replay has to start with the RNG states in the report. The replays are read from next to the report, and played back on `rom` with its game's `hooks`.
+pub fn verify(
+    path: &std::path::Path,
+    rom: &[u8],
+    hooks: &'static (dyn game::Hooks + Send + Sync),
+    bios_path: Option<&std::path::Path>,
+) -> Result<usize, Error> {
+    let report = Report::read(path)?;
+
+    report.reveal.local.verify("local")?;
+    report.reveal.remote.verify("remote")?;
+    if seed(&report.reveal.local.nonce, &report.reveal.remote.nonce) != report.reveal.seed {
+        return Err(Error::SeedMismatch);
+    }
+
+    let mut rng = rand_pcg::Mcg128Xsl64::from_seed(report.reveal.seed);
+    for (i, draw) in report.draws.iter().enumerate() {
+        let redrawn = match draw {
+            Draw::U32(_) => Draw::U32(rng.next_u32()),
+            Draw::U64(_) => Draw::U64(rng.next_u64()),
+            Draw::Bytes(bytes) => {
+                let mut buf = vec![0u8; bytes.len()];
+                rng.fill_bytes(&mut buf);
+                Draw::Bytes(buf)
+            }
+        };
+        if redrawn != *draw {
+            return Err(Error::DrawMismatch(i));
+        }
+    }
+
+    let dir = path.parent().unwrap_or_else(|| std::path::Path::new("."));
+    let mut rounds_checked = 0;
+    for round in report.rounds.iter() {
+        let replay_name = if let Some(replay_name) = round.replay.as_ref() {
+            replay_name
+        } else {
+            continue;
+        };
+        let replay = replay::Replay::decode(std::fs::File::open(dir.join(replay_name))?)?;
+        let local_state = replay.local_state.as_ref().ok_or(Error::MissingState(round.number))?;
+        let bios = bios::for_replay(&replay.metadata, bios_path)?;
+        let mut fastforwarder = replayer::Fastforwarder::new(
+            rom,
+            bios.as_deref(),
+            hooks,
+            replay.local_player_index,
+            replay.metadata.fixed_rtc.as_ref().map(|fixed_rtc| fixed_rtc.ms),
+            false,
+            None,
+        )?;
+        let actual = fastforwarder.rng_states(local_state)?;
+        if actual != round.rng_states {
+            return Err(Error::RoundMismatch {
+                round: round.number,
+                expected: round.rng_states.clone(),
+                actual,
+            });
+        }
+        rounds_checked += 1;
+    }
+    Ok(rounds_checked)
+}
@@ -0,0 +1,242 @@
+/// Paths at least this long need the extended-length prefix on Windows. It's MAX_PATH less room for an 8.3 filename, which is what creating directories is limited to.
+#[cfg(windows)]
+const MAX_PATH_LEN: usize = 248;
+
+/// Gets a form of the path that filesystem calls accept no matter how long it is.
+///
+/// On Windows, paths past MAX_PATH only work with the `\\?\` prefix, which also turns off all normalization: the path is made absolute and `.` and `..` are resolved before adding it. Short paths and paths on other platforms are returned as they are.
+#[cfg(windows)]
+pub fn extended(path: &std::path::Path) -> std::borrow::Cow<'_, std::path::Path> {
+    use std::path::{Component, Prefix};
+
+    if path.as_os_str().len() < MAX_PATH_LEN {
+        return std::borrow::Cow::Borrowed(path);
+    }
+
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else if let Ok(current_dir) = std::env::current_dir() {
+        current_dir.join(path)
+    } else {
+        return std::borrow::Cow::Borrowed(path);
+    };
+
+    let mut components = absolute.components();
+    let prefix = if let Some(Component::Prefix(prefix)) = components.next() {
+        prefix
+    } else {
+        return std::borrow::Cow::Borrowed(path);
+    };
+
+    let mut extended = match prefix.kind() {
+        Prefix::Disk(_) => {
+            let mut extended = std::ffi::OsString::from(r"\\?\");
+            extended.push(prefix.as_os_str());
+            extended
+        }
+        Prefix::UNC(server, share) => {
+            let mut extended = std::ffi::OsString::from(r"\\?\UNC\");
+            extended.push(server);
+            extended.push(r"\");
+            extended.push(share);
+            extended
+        }
+        // Already verbatim, or a device: these are passed through untouched anyway.
+        _ => {
+            return std::borrow::Cow::Borrowed(path);
+        }
+    };
+
+    let mut parts = vec![];
+    for component in components {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+    for part in parts {
+        extended.push(r"\");
+        extended.push(part);
+    }
+
+    std::borrow::Cow::Owned(extended.into())
+}
+
+#[cfg(not(windows))]
+pub fn extended(path: &std::path::Path) -> std::borrow::Cow<'_, std::path::Path> {
+    std::borrow::Cow::Borrowed(path)
+}
+
+/// Maps a path found under `extended(root)` back to being under `root`, so it can be shown and compared with paths that were never extended.
+pub fn unextended(path: std::path::PathBuf, root: &std::path::Path) -> std::path::PathBuf {
+    match path.strip_prefix(extended(root)) {
+        Ok(rest) if !rest.as_os_str().is_empty() => root.join(rest),
+        _ => path,
+    }
+}
+
+/// Adds the path to an error, so it can be shown to the user as it is. Everything in here does this.
+fn with_path(path: &std::path::Path, e: std::io::Error) -> std::io::Error {
+    std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+pub fn read(path: impl AsRef<std::path::Path>) -> std::io::Result<Vec<u8>> {
+    let path = path.as_ref();
+    std::fs::read(extended(path)).map_err(|e| with_path(path, e))
+}
+
+pub fn read_to_string(path: impl AsRef<std::path::Path>) -> std::io::Result<String> {
+    let path = path.as_ref();
+    std::fs::read_to_string(extended(path)).map_err(|e| with_path(path, e))
+}
+
+pub fn write(path: impl AsRef<std::path::Path>, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    std::fs::write(extended(path), contents).map_err(|e| with_path(path, e))
+}
+
+pub fn create_dir_all(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    std::fs::create_dir_all(extended(path)).map_err(|e| with_path(path, e))
+}
+
+pub fn metadata(path: impl AsRef<std::path::Path>) -> std::io::Result<std::fs::Metadata> {
+    let path = path.as_ref();
+    std::fs::metadata(extended(path)).map_err(|e| with_path(path, e))
+}
+
+/// Lists a directory. The entries' own paths may be extended, so join their file names onto `path` instead of using them.
+pub fn read_dir(path: impl AsRef<std::path::Path>) -> std::io::Result<std::fs::ReadDir> {
+    let path = path.as_ref();
+    std::fs::read_dir(extended(path)).map_err(|e| with_path(path, e))
+}
+
+pub fn open(path: impl AsRef<std::path::Path>) -> std::io::Result<std::fs::File> {
+    let path = path.as_ref();
+    std::fs::File::open(extended(path)).map_err(|e| with_path(path, e))
+}
+
+pub fn remove_file(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    std::fs::remove_file(extended(path)).map_err(|e| with_path(path, e))
+}
+
+pub fn copy(from: impl AsRef<std::path::Path>, to: impl AsRef<std::path::Path>) -> std::io::Result<u64> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    std::fs::copy(extended(from), extended(to)).map_err(|e| with_path(from, e))
+}
+
+pub fn rename(from: impl AsRef<std::path::Path>, to: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    std::fs::rename(extended(from), extended(to)).map_err(|e| with_path(from, e))
+}
+
+pub fn create(path: impl AsRef<std::path::Path>) -> std::io::Result<std::fs::File> {
+    let path = path.as_ref();
+    std::fs::File::create(extended(path)).map_err(|e| with_path(path, e))
+}
+
+/// Like `create`, but fails instead of truncating a file that's already there.
+pub fn create_new(path: impl AsRef<std::path::Path>) -> std::io::Result<std::fs::File> {
+    let path = path.as_ref();
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(extended(path))
+        .map_err(|e| with_path(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory name like the ones users with non-English Windows usernames end up with.
+    const CJK_DIR: &str = "ロックマン エグゼ";
+
+    #[test]
+    fn test_cjk_and_spaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join(CJK_DIR).join("Мои сохранения");
+        create_dir_all(&root).unwrap();
+
+        let path = root.join("バトル ネットワーク 6.sav");
+        write(&path, b"save").unwrap();
+        assert_eq!(read(&path).unwrap(), b"save");
+        assert_eq!(metadata(&path).unwrap().len(), 4);
+
+        let names = read_dir(&root)
+            .unwrap()
+            .map(|entry| unextended(entry.unwrap().path(), &root))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![path.clone()]);
+
+        let copied = root.join("コピー.sav");
+        copy(&path, &copied).unwrap();
+        let renamed = root.join("名前 変更.sav");
+        rename(&copied, &renamed).unwrap();
+        assert_eq!(read_to_string(&renamed).unwrap(), "save");
+        remove_file(&renamed).unwrap();
+        assert!(!renamed.exists());
+    }
+
+    #[test]
+    fn test_long_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut root = dir.path().to_path_buf();
+        while root.as_os_str().len() < 300 {
+            root.push(format!("{} {}", CJK_DIR, root.components().count()));
+        }
+        create_dir_all(&root).unwrap();
+
+        let path = root.join("exe6 (グレイガ).gba");
+        create_new(&path).unwrap();
+        assert!(create_new(&path).is_err());
+        write(&path, b"rom").unwrap();
+        assert_eq!(read(&path).unwrap(), b"rom");
+        assert_eq!(std::io::Read::bytes(open(&path).unwrap()).count(), 3);
+
+        let names = read_dir(&root)
+            .unwrap()
+            .map(|entry| unextended(entry.unwrap().path(), &root))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![path]);
+    }
+
+    #[test]
+    fn test_error_includes_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CJK_DIR).join("ない.bps");
+        let e = read(&path).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+        assert!(e.to_string().starts_with(&path.display().to_string()), "{}", e);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_extended() {
+        let short = std::path::Path::new(r"C:\Users\ユーザー\tango\saves\exe6.sav");
+        assert_eq!(extended(short), short);
+
+        let long = format!(r"C:\Users\ユーザー\{}\..\saves\.\exe6.sav", "a".repeat(MAX_PATH_LEN));
+        assert_eq!(
+            extended(std::path::Path::new(&long)).as_os_str(),
+            r"\\?\C:\Users\ユーザー\saves\exe6.sav"
+        );
+
+        let unc = format!(r"\\server\share\{}\exe6.sav", "a".repeat(MAX_PATH_LEN));
+        assert_eq!(
+            extended(std::path::Path::new(&unc)).as_os_str(),
+            std::ffi::OsString::from(format!(r"\\?\UNC\server\share\{}\exe6.sav", "a".repeat(MAX_PATH_LEN)))
+        );
+
+        // Already verbatim paths are left alone.
+        let verbatim = format!(r"\\?\C:\{}", "a".repeat(MAX_PATH_LEN));
+        assert_eq!(extended(std::path::Path::new(&verbatim)).as_os_str(), verbatim.as_str());
+
+        let root = std::path::Path::new(&long);
+        assert_eq!(unextended(extended(root).join("x"), root), root.join("x"));
+    }
+}
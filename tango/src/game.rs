@@ -1,6 +1,9 @@
 use std::any::Any;
+use std::io::Read;
 
-use crate::{battle, patch, replayer, rom, save, session, shadow};
+use rayon::prelude::*;
+
+use crate::{battle, fs, i18n, patch, replayer, rom, save, scanner, session, shadow};
 
 mod bn1;
 mod bn2;
@@ -54,49 +57,318 @@ pub const GAMES: &[&'static (dyn Game + Send + Sync)] = &[
     bn6::BN6F,
 ];
 
-pub fn scan_roms(path: &std::path::Path) -> std::collections::HashMap<&'static (dyn Game + Send + Sync), Vec<u8>> {
-    let mut roms = std::collections::HashMap::new();
+/// The largest ROM a GBA cartridge can hold.
+const MAX_ROM_SIZE: u64 = 32 * 1024 * 1024;
 
-    for entry in walkdir::WalkDir::new(path) {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(e) => {
-                log::error!("failed to read entry: {:?}", e);
+/// A ROM found inside a zip archive, by the name of its entry.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ArchivedRom {
+    name: String,
+    family_and_variant: (String, u8),
+}
+
+fn is_zip(path: &std::path::Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("zip"))
+        .unwrap_or(false)
+}
+
+fn open_zip(path: &std::path::Path) -> Result<zip::ZipArchive<std::io::BufReader<std::fs::File>>, anyhow::Error> {
+    Ok(zip::ZipArchive::new(std::io::BufReader::new(fs::open(path)?))?)
+}
+
+fn read_zip_entry(entry: &mut zip::read::ZipFile<'_>) -> Result<Vec<u8>, anyhow::Error> {
+    if entry.size() > MAX_ROM_SIZE {
+        anyhow::bail!("too large to be a ROM: {} bytes", entry.size());
+    }
+    // The size is only what the archive claims, so it can't be relied on to stop reading. Read one byte past the limit, so an entry that's too large can be told apart from one that's exactly at it.
+    let mut buf = Vec::with_capacity(entry.size() as usize);
+    entry.take(MAX_ROM_SIZE + 1).read_to_end(&mut buf)?;
+    if buf.len() as u64 > MAX_ROM_SIZE {
+        anyhow::bail!("too large to be a ROM: more than {} bytes", MAX_ROM_SIZE);
+    }
+    Ok(buf)
+}
+
+/// Finds the ROMs directly inside a zip archive: archives nested in it aren't looked into.
+///
+/// Entries are first matched against the known ROMs by the CRC32 the archive records for them, so only entries that can be one of them are decompressed.
+fn scan_zip(
+    path: &std::path::Path,
+) -> Result<Vec<(String, &'static (dyn Game + Send + Sync), Vec<u8>)>, anyhow::Error> {
+    let mut archive = open_zip(path)?;
+    let mut roms = vec![];
+    for i in 0..archive.len() {
+        let name = {
+            let entry = archive.by_index_raw(i)?;
+            if !entry.is_file()
+                || !entry.name().to_ascii_lowercase().ends_with(".gba")
+                || !GAMES.iter().any(|game| game.expected_crc32() == entry.crc32())
+            {
                 continue;
             }
+            entry.name().to_string()
         };
 
-        if !entry.file_type().is_file() {
-            continue;
-        }
-
-        let path = entry.path();
-
-        let rom = match std::fs::read(path) {
+        let rom = match read_zip_entry(&mut archive.by_index(i)?) {
             Ok(rom) => rom,
             Err(e) => {
-                log::warn!("{}: {}", path.display(), e);
+                log::warn!("{}: {}: {}", path.display(), name, e);
                 continue;
             }
         };
 
-        let game = match detect(&rom) {
+        match detect(&rom) {
             Ok(game) => {
-                log::info!("{}: {:?}", path.display(), game.family_and_variant());
-                game
+                log::info!("{}: {}: {:?}", path.display(), name, game.family_and_variant());
+                roms.push((name, game, rom));
             }
             Err(e) => {
-                log::warn!("{}: {}", path.display(), e);
-                continue;
+                log::warn!("{}: {}: {}", path.display(), name, e);
             }
-        };
+        }
+    }
+    Ok(roms)
+}
+
+/// Scans zip archives for ROMs, in parallel.
+///
+/// Archives that haven't changed since the last scan aren't looked through again: only the entries that were ROMs are re-read, and only if they're not already loaded.
+fn scan_rom_archives(
+    paths: Vec<(std::path::PathBuf, Option<scanner::FileStamp>)>,
+    full: bool,
+    progress: &scanner::Progress<'_, rom::ScannedRoms>,
+) -> Vec<(
+    std::path::PathBuf,
+    Option<scanner::FileStamp>,
+    Vec<(String, &'static (dyn Game + Send + Sync), std::sync::Arc<[u8]>)>,
+)> {
+    let mut cache = scanner::Cache::<Vec<ArchivedRom>>::load("rom-archives");
+
+    let results = paths
+        .into_par_iter()
+        .filter_map(|(path, stamp)| {
+            let cached = if !full {
+                stamp.as_ref().and_then(|stamp| cache.get(&path, stamp))
+            } else {
+                None
+            };
+
+            let roms = if let Some(cached) = cached {
+                let mut archive = None;
+                let mut roms = vec![];
+                for archived_rom in cached {
+                    let (family, variant) = &archived_rom.family_and_variant;
+                    let game = if let Some(game) = find_by_family_and_variant(family, *variant) {
+                        game
+                    } else {
+                        continue;
+                    };
 
-        roms.insert(game, rom);
+                    if let Some(rom) = progress.read().by_game.get(&game).cloned() {
+                        roms.push((archived_rom.name.clone(), game, rom));
+                        continue;
+                    }
+
+                    if archive.is_none() {
+                        archive = match open_zip(&path) {
+                            Ok(archive) => Some(archive),
+                            Err(e) => {
+                                log::warn!("{}: {}", path.display(), e);
+                                return None;
+                            }
+                        };
+                    }
+                    let rom = match archive
+                        .as_mut()
+                        .unwrap()
+                        .by_name(&archived_rom.name)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|mut entry| read_zip_entry(&mut entry))
+                    {
+                        Ok(rom) => rom.into(),
+                        Err(e) => {
+                            log::warn!("{}: {}: {}", path.display(), archived_rom.name, e);
+                            continue;
+                        }
+                    };
+                    roms.push((archived_rom.name.clone(), game, rom));
+                }
+                roms
+            } else {
+                match scan_zip(&path) {
+                    Ok(roms) => roms
+                        .into_iter()
+                        .map(|(name, game, rom)| (name, game, rom.into()))
+                        .collect(),
+                    Err(e) => {
+                        log::warn!("{}: {}", path.display(), e);
+                        vec![]
+                    }
+                }
+            };
+
+            progress.update(|progress_roms| {
+                for (_, game, rom) in roms.iter() {
+                    progress_roms.by_game.insert(*game, rom.clone());
+                }
+            });
+            Some((path, stamp, roms))
+        })
+        .collect::<Vec<_>>();
+
+    let mut entries = std::collections::HashMap::new();
+    for (path, stamp, roms) in results.iter() {
+        if let Some(stamp) = stamp {
+            entries.insert(
+                path.clone(),
+                (
+                    *stamp,
+                    roms.iter()
+                        .map(|(name, game, _)| {
+                            let (family, variant) = game.family_and_variant();
+                            ArchivedRom {
+                                name: name.clone(),
+                                family_and_variant: (family.to_string(), variant),
+                            }
+                        })
+                        .collect(),
+                ),
+            );
+        }
+    }
+
+    if let Err(e) = cache.replace_and_save(entries) {
+        log::warn!("failed to save ROM archive scan cache: {:?}", e);
+    }
+
+    results
+}
+
+/// Scans for ROMs, in parallel, including ones inside zip archives.
+///
+/// Unless `full` is set, files that haven't changed since the last scan are not re-read if they're already loaded, nor re-hashed.
+pub fn scan_roms(
+    path: &std::path::Path,
+    full: bool,
+    progress: &scanner::Progress<'_, rom::ScannedRoms>,
+) -> rom::ScannedRoms {
+    let mut cache = scanner::Cache::<Option<(String, u8)>>::load("roms");
+
+    let (archive_paths, paths): (Vec<_>, Vec<_>) = scanner::walk_files(path)
+        .into_iter()
+        .partition(|(path, _)| is_zip(path));
+
+    let archive_results = scan_rom_archives(archive_paths, full, progress);
+
+    let results = paths
+        .into_par_iter()
+        .filter_map(|(path, stamp)| {
+            let cached = if !full {
+                stamp.as_ref().and_then(|stamp| cache.get(&path, stamp))
+            } else {
+                None
+            };
+
+            let game = match cached {
+                Some(None) => {
+                    // We already know this isn't a ROM we're interested in.
+                    return Some((path, stamp, None));
+                }
+                Some(Some((family, variant))) => find_by_family_and_variant(family, *variant),
+                None => None,
+            };
+
+            if let Some(game) = game {
+                if !full {
+                    if let Some(rom) = progress.read().by_game.get(&game).cloned() {
+                        return Some((path, stamp, Some((game, rom))));
+                    }
+                }
+            }
+
+            let rom: std::sync::Arc<[u8]> = match fs::read(&path) {
+                Ok(rom) => rom.into(),
+                Err(e) => {
+                    log::warn!("{}", e);
+                    return None;
+                }
+            };
+
+            let game = if let Some(game) = game {
+                game
+            } else {
+                match detect(&rom) {
+                    Ok(game) => {
+                        log::info!("{}: {:?}", path.display(), game.family_and_variant());
+                        game
+                    }
+                    Err(e) => {
+                        log::warn!("{}: {}", path.display(), e);
+                        return Some((path, stamp, None));
+                    }
+                }
+            };
+
+            progress.update(|roms| {
+                roms.by_game.insert(game, rom.clone());
+            });
+            Some((path, stamp, Some((game, rom))))
+        })
+        .collect::<Vec<_>>();
+
+    let mut entries = std::collections::HashMap::new();
+    for (path, stamp, rom) in results.iter() {
+        if let Some(stamp) = stamp {
+            entries.insert(
+                path.clone(),
+                (
+                    *stamp,
+                    rom.as_ref().map(|(game, _)| {
+                        let (family, variant) = game.family_and_variant();
+                        (family.to_string(), variant)
+                    }),
+                ),
+            );
+        }
+    }
+
+    if let Err(e) = cache.replace_and_save(entries) {
+        log::warn!("failed to save ROM scan cache: {:?}", e);
+    }
+
+    // Every copy of a game's ROM should be the same, but whichever copy is used is the canonical one regardless, so both sides of a match always agree on which bytes they have.
+    let mut loose_roms = results
+        .iter()
+        .flat_map(|(path, _, rom)| rom.as_ref().map(|(game, rom)| (path, *game, rom)))
+        .collect::<Vec<_>>();
+    loose_roms.sort_by(|(a, _, _), (b, _, _)| scanner::canonical_order(a, b));
+
+    let mut roms = rom::ScannedRoms {
+        by_game: std::collections::HashMap::new(),
+        duplicates: scanner::find_duplicates(loose_roms.iter().map(|(path, _, rom)| (path.as_path(), &rom[..]))),
+    };
+    for (_, game, rom) in loose_roms.iter() {
+        roms.by_game.entry(*game).or_insert_with(|| (*rom).clone());
+    }
+
+    for (_, _, archived_roms) in archive_results {
+        for (_, game, rom) in archived_roms {
+            roms.by_game.entry(game).or_insert(rom);
+        }
     }
 
     roms
 }
 
+/// What a match type is called in a game family, subtype included. A match type as a whole goes by the name of its plain form, i.e. subtype 0.
+pub fn match_type_name(family: &str, language: &unic_langid::LanguageIdentifier, match_type: (u8, u8)) -> String {
+    i18n::LOCALES.lookup(
+        language,
+        &format!("game-{}.match-type-{}-{}", family, match_type.0, match_type.1),
+    )
+}
+
 pub fn sort_games(lang: &unic_langid::LanguageIdentifier, games: &mut [&'static (dyn Game + Send + Sync)]) {
     games.sort_by_key(|g| {
         (
@@ -152,7 +424,18 @@ where
     fn language(&self) -> unic_langid::LanguageIdentifier;
     fn rom_code_and_revision(&self) -> (&[u8; 4], u8);
     fn expected_crc32(&self) -> u32;
+    /// How many subtypes each match type has. Every match type has at least one, the first of which is its plain form.
     fn match_types(&self) -> &[usize];
+
+    /// The subtypes a match type can be played with, e.g. weight classes. Empty if the game has no such match type.
+    fn match_subtypes(&self, typ: u8) -> std::ops::Range<u8> {
+        0..self.match_types().get(typ as usize).copied().unwrap_or(0) as u8
+    }
+
+    /// Whether the game has a match type, subtype included.
+    fn has_match_type(&self, match_type: (u8, u8)) -> bool {
+        self.match_subtypes(match_type.0).contains(&match_type.1)
+    }
     fn hooks(&self) -> &'static (dyn Hooks + Send + Sync);
     fn parse_save(&self, data: &[u8]) -> Result<Box<dyn save::Save + Send + Sync>, anyhow::Error>;
     fn save_from_wram(&self, data: &[u8]) -> Result<Box<dyn save::Save + Send + Sync>, anyhow::Error>;
@@ -164,6 +447,75 @@ where
     ) -> Result<Box<dyn rom::Assets + Send + Sync>, anyhow::Error> {
         anyhow::bail!("not implemented");
     }
+
+    /// Art shipped with Tango for the game, as a PNG. Wherever a game has none, a placeholder is drawn in its place.
+    fn art(&self, _kind: ArtKind) -> Option<&'static [u8]> {
+        None
+    }
+}
+
+/// The pieces of art shown for a game, so it can be told apart at a glance.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ArtKind {
+    /// A wide image, shown with whatever is being played.
+    Banner,
+    /// A small square image, shown in lists of games.
+    Icon,
+}
+
+/// What netplay can and can't do for a game, so players can find out before they're in a match.
+///
+/// Each game's hooks declare their own. The supported features are also sent to the opponent as bits, so a build that supports less can be spotted in the lobby.
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    pub rollback: bool,
+    /// Whether the opponent's Tango nickname is shown in game instead of the name from their save.
+    pub opponent_name_replacement: bool,
+    pub round_time_limit: bool,
+    pub desync_detection: bool,
+    /// Match types, as (type, subtype), that can be picked but aren't expected to work properly.
+    pub unsupported_match_types: &'static [(u8, u8)],
+    /// Fluent message IDs for known problems players should be told about.
+    pub known_issues: &'static [&'static str],
+}
+
+impl Capabilities {
+    pub const ROLLBACK: u32 = 1 << 0;
+    pub const OPPONENT_NAME_REPLACEMENT: u32 = 1 << 1;
+    pub const ROUND_TIME_LIMIT: u32 = 1 << 2;
+    pub const DESYNC_DETECTION: u32 = 1 << 3;
+
+    /// Every feature bit, along with the Fluent message ID naming it.
+    pub const FEATURES: &'static [(u32, &'static str)] = &[
+        (Self::ROLLBACK, "play-capabilities.rollback"),
+        (
+            Self::OPPONENT_NAME_REPLACEMENT,
+            "play-capabilities.opponent-name-replacement",
+        ),
+        (Self::ROUND_TIME_LIMIT, "play-capabilities.round-time-limit"),
+        (Self::DESYNC_DETECTION, "play-capabilities.desync-detection"),
+    ];
+
+    pub fn bits(&self) -> u32 {
+        let mut bits = 0;
+        if self.rollback {
+            bits |= Self::ROLLBACK;
+        }
+        if self.opponent_name_replacement {
+            bits |= Self::OPPONENT_NAME_REPLACEMENT;
+        }
+        if self.round_time_limit {
+            bits |= Self::ROUND_TIME_LIMIT;
+        }
+        if self.desync_detection {
+            bits |= Self::DESYNC_DETECTION;
+        }
+        bits
+    }
+
+    pub fn supports_match_type(&self, match_type: (u8, u8)) -> bool {
+        !self.unsupported_match_types.contains(&match_type)
+    }
 }
 
 pub trait Hooks {
@@ -188,5 +540,146 @@ pub trait Hooks {
 
     fn prepare_for_fastforward(&self, core: mgba::core::CoreMutRef);
 
+    /// Guesses the opponent's next packet from their last one, while it's still on its way.
+    ///
+    /// Wrong guesses are rolled back once the real packet arrives, so this only needs to be right in the common case.
     fn predict_rx(&self, _rx: &mut Vec<u8>) {}
+
+    /// EWRAM regions checksummed during battle to detect desyncs.
+    ///
+    /// These must be identical on both sides at the same tick, so they must not contain anything that depends on which side is local. Keep them to a few KB at most, as they are checksummed while fastforwarding.
+    fn state_hash_regions(&self) -> Vec<std::ops::Range<u32>> {
+        vec![]
+    }
+
+    /// What the game's RNGs are at, for recording what each round was seeded with. Empty if the game doesn't know where they are.
+    fn rng_states(&self, _core: mgba::core::CoreMutRef) -> Vec<u32> {
+        vec![]
+    }
+
+    fn practice_telemetry(&self) -> Option<&(dyn PracticeTelemetry + Send + Sync)> {
+        None
+    }
+
+    fn practice_manipulation(&self) -> Option<&(dyn PracticeManipulation + Send + Sync)> {
+        None
+    }
+
+    /// The custom gauge, while a battle is in progress. Streamer mode reads this every frame to tell when the custom screen is open, so it has to be cheap. None if the game doesn't know where to find it.
+    fn custom_gauge(&self, _core: mgba::core::CoreMutRef) -> Option<u16> {
+        None
+    }
+
+    /// Whether the replayer and shadow traps end battles when the round time limit runs out.
+    ///
+    /// They do so by deleting the navi with less HP (or both, on a tie) on the limit tick, so the game ends the round itself on every core.
+    fn supports_round_time_limit(&self) -> bool {
+        false
+    }
+
+    fn capabilities(&self) -> Capabilities;
+}
+
+/// Reads training data out of game memory for the practice overlay.
+///
+/// Each reader is optional: games only implement what they know how to find, and the overlay shows whatever is available.
+pub trait PracticeTelemetry {
+    /// Whether a battle is in progress. Nothing else is read outside of battles.
+    fn in_battle(&self, core: mgba::core::CoreMutRef) -> bool;
+
+    fn current_tick(&self, _core: mgba::core::CoreMutRef) -> Option<u32> {
+        None
+    }
+
+    fn custom_gauge_frames_remaining(&self, _core: mgba::core::CoreMutRef) -> Option<u32> {
+        None
+    }
+
+    fn hp(&self, _core: mgba::core::CoreMutRef, _player_index: usize) -> Option<u16> {
+        None
+    }
+
+    fn invulnerability_frames(&self, _core: mgba::core::CoreMutRef, _player_index: usize) -> Option<u16> {
+        None
+    }
+
+    /// The chip the player is holding: `Some(None)` means they're known to be empty-handed.
+    fn chip_in_hand(&self, _core: mgba::core::CoreMutRef, _player_index: usize) -> Option<Option<usize>> {
+        None
+    }
+}
+
+/// Forces game state for labbing specific situations in practice.
+///
+/// Writing to game memory like this would desync a netplay match, so this is only ever installed in single player sessions.
+pub trait PracticeManipulation {
+    /// The trap writes are made from. This must be hit once per frame, somewhere the battle state isn't halfway through being updated.
+    fn trap_address(&self) -> u32;
+
+    /// Whether a battle is in progress. Battle state isn't written to outside of battles.
+    fn in_battle(&self, core: mgba::core::CoreMutRef) -> bool;
+
+    fn fill_custom_gauge(&self, core: mgba::core::CoreMutRef);
+
+    fn set_hp(&self, core: mgba::core::CoreMutRef, player_index: usize, hp: u16);
+
+    fn set_chip_in_hand(&self, core: mgba::core::CoreMutRef, player_index: usize, chip_id: u16);
+
+    /// The state of every RNG the game uses, for writing back to keep it frozen.
+    fn rng_state(&self, core: mgba::core::CoreMutRef) -> Vec<u32>;
+
+    fn set_rng_state(&self, core: mgba::core::CoreMutRef, state: &[u32]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes a zip archive with a single entry of `len` zeros, which claims to be `claimed_len` bytes long.
+    fn zip_with_claimed_size(len: usize, claimed_len: u32) -> Vec<u8> {
+        let mut buf = std::io::Cursor::new(vec![]);
+        {
+            let mut writer = zip::ZipWriter::new(&mut buf);
+            writer
+                .start_file(
+                    "rom.gba",
+                    zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated),
+                )
+                .unwrap();
+            std::io::Write::write_all(&mut writer, &vec![0; len]).unwrap();
+            writer.finish().unwrap();
+        }
+        let mut buf = buf.into_inner();
+
+        // The uncompressed size is in both the local file header and the central directory.
+        buf[22..26].copy_from_slice(&claimed_len.to_le_bytes());
+        let central = buf
+            .windows(4)
+            .rposition(|w| w == b"PK\x01\x02")
+            .expect("central directory");
+        buf[central + 24..central + 28].copy_from_slice(&claimed_len.to_le_bytes());
+        buf
+    }
+
+    fn read_only_entry(zip: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(zip))?;
+        let mut entry = archive.by_index(0)?;
+        read_zip_entry(&mut entry)
+    }
+
+    #[test]
+    fn test_read_zip_entry_at_limit() {
+        let rom = read_only_entry(zip_with_claimed_size(MAX_ROM_SIZE as usize, MAX_ROM_SIZE as u32)).unwrap();
+        assert_eq!(rom.len() as u64, MAX_ROM_SIZE);
+    }
+
+    #[test]
+    fn test_read_zip_entry_claimed_too_large() {
+        assert!(read_only_entry(zip_with_claimed_size(1024, MAX_ROM_SIZE as u32 + 1)).is_err());
+    }
+
+    #[test]
+    fn test_read_zip_entry_larger_than_claimed() {
+        assert!(read_only_entry(zip_with_claimed_size(MAX_ROM_SIZE as usize + 1, 1024)).is_err());
+    }
 }
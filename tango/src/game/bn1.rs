@@ -63,11 +63,7 @@ impl game::Game for EXE1Impl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::AREJ_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::JA_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::JA_CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -131,11 +127,7 @@ impl game::Game for BN1Impl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::AREE_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::EN_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::EN_CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -257,8 +257,8 @@ impl game::Hooks for Hooks {
                                 .expect("shadow save state"),
                             &munger.tx_packet(core),
                         );
-                        log::info!("primary rng state: {:08x}", munger.rng_state(core));
-                        log::info!("battle state committed on {}", round.current_tick());
+                        log::debug!("primary rng state: {:08x}", munger.rng_state(core));
+                        log::debug!("battle state committed on {}", round.current_tick());
                     }
 
                     'abort: loop {
@@ -484,8 +484,8 @@ impl game::Hooks for Hooks {
 
                         round
                             .set_first_committed_state(core.save_state().expect("save state"), &munger.tx_packet(core));
-                        log::info!("shadow rng state: {:08x}", munger.rng_state(core));
-                        log::info!("shadow state committed on {}", round.current_tick());
+                        log::debug!("shadow rng state: {:08x}", munger.rng_state(core));
+                        log::debug!("shadow state committed on {}", round.current_tick());
                         return;
                     }
 
@@ -782,4 +782,19 @@ impl game::Hooks for Hooks {
             .cpu_mut()
             .set_thumb_pc(self.offsets.rom.main_read_joyflags);
     }
+
+    fn rng_states(&self, core: mgba::core::CoreMutRef) -> Vec<u32> {
+        vec![self.munger().rng_state(core)]
+    }
+
+    fn capabilities(&self) -> game::Capabilities {
+        game::Capabilities {
+            rollback: true,
+            opponent_name_replacement: false,
+            round_time_limit: false,
+            desync_detection: false,
+            unsupported_match_types: &[],
+            known_issues: &[],
+        }
+    }
 }
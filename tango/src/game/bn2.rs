@@ -50,11 +50,7 @@ impl game::Game for EXE2Impl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::AE2J_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::JA_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::JA_CHARSET, overrides),
             rom.to_vec(),
             save.to_vec(),
         )))
@@ -105,11 +101,7 @@ impl game::Game for BN2Impl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::AE2E_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::EN_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::EN_CHARSET, overrides),
             rom.to_vec(),
             save.to_vec(),
         )))
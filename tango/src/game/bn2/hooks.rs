@@ -23,9 +23,11 @@ pub static AE2J_01: Hooks = Hooks {
     offsets: &offsets::AE2J_01,
 };
 
-fn random_background(rng: &mut impl rand::Rng) -> u8 {
+/// Picks a battle background from the patch's pool if it has one, or the game's own otherwise.
+fn random_background(rng: &mut impl rand::Rng, battle_backgrounds: Option<&[u8]>) -> u8 {
     const BATTLE_BACKGROUNDS: &[u8] = &[0x00, 0x01, 0x02, 0x03, 0x05, 0x08, 0x15, 0x18];
-    BATTLE_BACKGROUNDS[rng.gen_range(0..BATTLE_BACKGROUNDS.len())]
+    let battle_backgrounds = battle_backgrounds.unwrap_or(BATTLE_BACKGROUNDS);
+    battle_backgrounds[rng.gen_range(0..battle_backgrounds.len())]
 }
 
 fn step_rng(seed: u32) -> u32 {
@@ -125,7 +127,7 @@ impl game::Hooks for Hooks {
                             answerer_rng_state
                         },
                     );
-                    munger.start_battle_from_comm_menu(core, random_background(&mut *rng));
+                    munger.start_battle_from_comm_menu(core, random_background(&mut *rng, match_.battle_backgrounds()));
                 })
             }),
             (
@@ -206,11 +208,7 @@ impl game::Hooks for Hooks {
                     };
 
                     let mut round_state = sync::block_on(match_.lock_round_state());
-                    let result = {
-                        let round = round_state.round.as_ref().expect("round");
-                        round.on_draw_result()
-                    };
-                    round_state.set_last_result(result);
+                    round_state.set_draw_result();
                 })
             }),
             (self.offsets.rom.round_ending_entry1, {
@@ -330,8 +328,8 @@ impl game::Hooks for Hooks {
                                 .expect("shadow save state"),
                             &munger.tx_packet(core),
                         );
-                        log::info!("primary rng state: {:08x}", munger.rng_state(core));
-                        log::info!("battle state committed on {}", round.current_tick());
+                        log::debug!("primary rng state: {:08x}", munger.rng_state(core));
+                        log::debug!("battle state committed on {}", round.current_tick());
                     }
 
                     'abort: loop {
@@ -495,7 +493,10 @@ impl game::Hooks for Hooks {
                             offerer_rng_state
                         },
                     );
-                    munger.start_battle_from_comm_menu(core, random_background(&mut *rng));
+                    munger.start_battle_from_comm_menu(
+                        core,
+                        random_background(&mut *rng, shadow_state.battle_backgrounds()),
+                    );
                 })
             }),
             (self.offsets.rom.round_start_ret, {
@@ -586,8 +587,8 @@ impl game::Hooks for Hooks {
                     if !round.has_first_committed_state() {
                         round
                             .set_first_committed_state(core.save_state().expect("save state"), &munger.tx_packet(core));
-                        log::info!("shadow rng state: {:08x}", munger.rng_state(core));
-                        log::info!("shadow state committed on {}", round.current_tick());
+                        log::debug!("shadow rng state: {:08x}", munger.rng_state(core));
+                        log::debug!("shadow state committed on {}", round.current_tick());
                         return;
                     }
 
@@ -905,4 +906,19 @@ impl game::Hooks for Hooks {
             .cpu_mut()
             .set_thumb_pc(self.offsets.rom.main_read_joyflags);
     }
+
+    fn rng_states(&self, core: mgba::core::CoreMutRef) -> Vec<u32> {
+        vec![self.munger().rng_state(core)]
+    }
+
+    fn capabilities(&self) -> game::Capabilities {
+        game::Capabilities {
+            rollback: true,
+            opponent_name_replacement: false,
+            round_time_limit: false,
+            desync_detection: false,
+            unsupported_match_types: &[],
+            known_issues: &[],
+        }
+    }
 }
@@ -65,17 +65,7 @@ impl<'a> rom::Chip for Chip<'a> {
             id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+            rom::text::plain_text(&parts)
         } else {
             "???".to_string()
         }
@@ -93,19 +83,7 @@ impl<'a> rom::Chip for Chip<'a> {
             id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
-                .replace("-\n", "-")
-                .replace("\n", " ")
+            rom::text::plain_text(&parts).replace("-\n", "-").replace("\n", " ")
         } else {
             "???".to_string()
         }
@@ -176,7 +154,7 @@ impl<'a> rom::Chip for Chip<'a> {
 }
 
 impl Assets {
-    pub fn new(offsets: &'static Offsets, charset: Vec<String>, rom: Vec<u8>, wram: Vec<u8>) -> Self {
+    pub fn new(offsets: &'static Offsets, charset: rom::text::Charset, rom: Vec<u8>, wram: Vec<u8>) -> Self {
         let mapper = rom::MemoryMapper::new(rom, wram);
         let chip_icon_palette = rom::read_palette(
             &mapper.get(byteorder::LittleEndian::read_u32(
@@ -63,11 +63,7 @@ impl game::Game for EXE3WImpl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::A6BJ_01,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::JA_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::JA_CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -131,11 +127,7 @@ impl game::Game for EXE3BImpl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::A3XJ_01,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::JA_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::JA_CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -199,11 +191,7 @@ impl game::Game for BN3WImpl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::A6BE_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::EN_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::EN_CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -267,11 +255,7 @@ impl game::Game for BN3BImpl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::A3XE_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::EN_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::EN_CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -39,9 +39,11 @@ fn bn3_match_type(rng: &mut impl rand::Rng, match_type: (u8, u8)) -> u8 {
     }
 }
 
-fn random_background(rng: &mut impl rand::Rng) -> u8 {
+/// Picks a battle background from the patch's pool if it has one, or the game's own otherwise.
+fn random_background(rng: &mut impl rand::Rng, battle_backgrounds: Option<&[u8]>) -> u8 {
     const BATTLE_BACKGROUNDS: &[u8] = &[0x00, 0x04, 0x05, 0x06, 0x17, 0x10, 0x02, 0x0a];
-    BATTLE_BACKGROUNDS[rng.gen_range(0..BATTLE_BACKGROUNDS.len())]
+    let battle_backgrounds = battle_backgrounds.unwrap_or(BATTLE_BACKGROUNDS);
+    battle_backgrounds[rng.gen_range(0..battle_backgrounds.len())]
 }
 
 fn step_rng(seed: u32) -> u32 {
@@ -165,7 +167,7 @@ impl game::Hooks for Hooks {
                     munger.start_battle_from_comm_menu(
                         core,
                         bn3_match_type(&mut *rng, match_.match_type()),
-                        random_background(&mut *rng),
+                        random_background(&mut *rng, match_.battle_backgrounds()),
                     );
                 })
             }),
@@ -252,11 +254,7 @@ impl game::Hooks for Hooks {
                     };
 
                     let mut round_state = sync::block_on(match_.lock_round_state());
-                    let result = {
-                        let round = round_state.round.as_ref().expect("round");
-                        round.on_draw_result()
-                    };
-                    round_state.set_last_result(result);
+                    round_state.set_draw_result();
                 })
             }),
             (self.offsets.rom.round_ending_entry, {
@@ -369,12 +367,12 @@ impl game::Hooks for Hooks {
                                 .expect("shadow save state"),
                             &munger.tx_packet(core),
                         );
-                        log::info!(
+                        log::debug!(
                             "primary rng1 state: {:08x}, rng2 state: {:08x}",
                             munger.rng1_state(core),
                             munger.rng2_state(core),
                         );
-                        log::info!("battle state committed on {}", round.current_tick());
+                        log::debug!("battle state committed on {}", round.current_tick());
                     }
 
                     'abort: loop {
@@ -558,7 +556,7 @@ impl game::Hooks for Hooks {
                     munger.start_battle_from_comm_menu(
                         core,
                         bn3_match_type(&mut *rng, shadow_state.match_type()),
-                        random_background(&mut *rng),
+                        random_background(&mut *rng, shadow_state.battle_backgrounds()),
                     );
                 })
             }),
@@ -661,12 +659,12 @@ impl game::Hooks for Hooks {
 
                         round
                             .set_first_committed_state(core.save_state().expect("save state"), &munger.tx_packet(core));
-                        log::info!(
+                        log::debug!(
                             "shadow rng1 state: {:08x}, rng2 state: {:08x}",
                             munger.rng1_state(core),
                             munger.rng2_state(core)
                         );
-                        log::info!("shadow state committed on {}", round.current_tick());
+                        log::debug!("shadow state committed on {}", round.current_tick());
                         return;
                     }
 
@@ -893,6 +891,7 @@ impl game::Hooks for Hooks {
                     if current_tick == replayer_state.commit_tick() {
                         replayer_state.set_committed_state(core.save_state().expect("save committed state"));
                     }
+                    replayer_state.record_state_hash(core);
 
                     let ip = match replayer_state.peek_input_pair() {
                         Some(ip) => ip.clone(),
@@ -995,9 +994,34 @@ impl game::Hooks for Hooks {
         byteorder::LittleEndian::write_u32(&mut rx[0x4..0x8], tick + 1);
     }
 
+    fn state_hash_regions(&self) -> Vec<std::ops::Range<u32>> {
+        vec![
+            // Shared RNG state: nearly every desync changes how much of it gets consumed.
+            self.offsets.ewram.rng2_state..self.offsets.ewram.rng2_state + 4,
+            // Both players' last packets, in player order. The game builds them from its battle state, so they carry the current tick along with each player's inputs and selected chips.
+            self.offsets.ewram.rx_packet_arr..self.offsets.ewram.rx_packet_arr + 0x20,
+        ]
+    }
+
     fn prepare_for_fastforward(&self, mut core: mgba::core::CoreMutRef) {
         core.gba_mut()
             .cpu_mut()
             .set_thumb_pc(self.offsets.rom.main_read_joyflags);
     }
+
+    fn rng_states(&self, core: mgba::core::CoreMutRef) -> Vec<u32> {
+        let munger = self.munger();
+        vec![munger.rng1_state(core), munger.rng2_state(core)]
+    }
+
+    fn capabilities(&self) -> game::Capabilities {
+        game::Capabilities {
+            rollback: true,
+            opponent_name_replacement: false,
+            round_time_limit: false,
+            desync_detection: true,
+            unsupported_match_types: &[],
+            known_issues: &[],
+        }
+    }
 }
@@ -117,17 +117,7 @@ impl<'a> rom::Chip for Chip<'a> {
             id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+            rom::text::plain_text(&parts)
         } else {
             "???".to_string()
         }
@@ -145,19 +135,7 @@ impl<'a> rom::Chip for Chip<'a> {
             id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
-                .replace("-\n", "-")
-                .replace("\n", " ")
+            rom::text::plain_text(&parts).replace("-\n", "-").replace("\n", " ")
         } else {
             "???".to_string()
         }
@@ -264,17 +242,7 @@ impl<'a> rom::NavicustPart for NavicustPart<'a> {
             self.id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match &part {
-                        rom::text::Part::String(s) => s,
-                        _ => "",
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+            rom::text::plain_text(&parts)
         } else {
             "???".to_string()
         }
@@ -288,19 +256,7 @@ impl<'a> rom::NavicustPart for NavicustPart<'a> {
             self.id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
-                .replace("-\n", "-")
-                .replace("\n", " ")
+            rom::text::plain_text(&parts).replace("-\n", "-").replace("\n", " ")
         } else {
             "???".to_string()
         }
@@ -357,7 +313,7 @@ impl<'a> rom::NavicustPart for NavicustPart<'a> {
 }
 
 impl Assets {
-    pub fn new(offsets: &'static Offsets, charset: Vec<String>, rom: Vec<u8>, wram: Vec<u8>) -> Self {
+    pub fn new(offsets: &'static Offsets, charset: rom::text::Charset, rom: Vec<u8>, wram: Vec<u8>) -> Self {
         let mapper = rom::MemoryMapper::new(rom, wram);
         let chip_icon_palette = rom::read_palette(
             &mapper.get(byteorder::LittleEndian::read_u32(
@@ -403,17 +359,7 @@ impl<'a> rom::Style for Style<'a> {
             128 + typ * 5 + element,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match &part {
-                        rom::text::Part::String(s) => s,
-                        _ => "",
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+            rom::text::plain_text(&parts)
         } else {
             "???".to_string()
         }
@@ -68,11 +68,7 @@ impl game::Game for EXE4RSImpl {
                 .as_ref()
                 .and_then(|lang| rom::modcards::for_language(lang))
                 .unwrap_or(&rom::modcards::JA_MODCARDS),
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::JA_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::JA_CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -141,11 +137,7 @@ impl game::Game for EXE4BMImpl {
                 .as_ref()
                 .and_then(|lang| rom::modcards::for_language(lang))
                 .unwrap_or(&rom::modcards::JA_MODCARDS),
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::JA_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::JA_CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -214,11 +206,7 @@ impl game::Game for BN4RSImpl {
                 .as_ref()
                 .and_then(|lang| rom::modcards::for_language(lang))
                 .unwrap_or(&rom::modcards::EN_MODCARDS),
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::EN_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::EN_CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -287,11 +275,7 @@ impl game::Game for BN4BMImpl {
                 .as_ref()
                 .and_then(|lang| rom::modcards::for_language(lang))
                 .unwrap_or(&rom::modcards::EN_MODCARDS),
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::EN_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::EN_CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -209,11 +209,7 @@ impl game::Hooks for Hooks {
                     };
 
                     let mut round_state = sync::block_on(match_.lock_round_state());
-                    let result = {
-                        let round = round_state.round.as_ref().expect("round");
-                        round.on_draw_result()
-                    };
-                    round_state.set_last_result(result);
+                    round_state.set_draw_result();
                 })
             }),
             (self.offsets.rom.round_set_ending, {
@@ -345,13 +341,13 @@ impl game::Hooks for Hooks {
                                 .expect("shadow save state"),
                             &munger.tx_packet(core),
                         );
-                        log::info!(
+                        log::debug!(
                             "primary rng1 state: {:08x}, rng2 state: {:08x}, rng3 state: {:08x}",
                             munger.rng1_state(core),
                             munger.rng2_state(core),
                             munger.rng3_state(core),
                         );
-                        log::info!("battle state committed on {}", round.current_tick());
+                        log::debug!("battle state committed on {}", round.current_tick());
                     }
 
                     'abort: loop {
@@ -532,13 +528,13 @@ impl game::Hooks for Hooks {
                         // HACK: For some inexplicable reason, we don't always start on tick 0.
                         round
                             .set_first_committed_state(core.save_state().expect("save state"), &munger.tx_packet(core));
-                        log::info!(
+                        log::debug!(
                             "shadow rng1 state: {:08x}, rng2 state: {:08x}, rng3 state: {:08x}",
                             munger.rng1_state(core),
                             munger.rng2_state(core),
                             munger.rng3_state(core)
                         );
-                        log::info!("shadow state committed on {}", round.current_tick());
+                        log::debug!("shadow state committed on {}", round.current_tick());
                         return;
                     }
 
@@ -884,4 +880,24 @@ impl game::Hooks for Hooks {
             .cpu_mut()
             .set_thumb_pc(self.offsets.rom.main_read_joyflags);
     }
+
+    fn rng_states(&self, core: mgba::core::CoreMutRef) -> Vec<u32> {
+        let munger = self.munger();
+        vec![
+            munger.rng1_state(core),
+            munger.rng2_state(core),
+            munger.rng3_state(core),
+        ]
+    }
+
+    fn capabilities(&self) -> game::Capabilities {
+        game::Capabilities {
+            rollback: true,
+            opponent_name_replacement: false,
+            round_time_limit: false,
+            desync_detection: false,
+            unsupported_match_types: &[],
+            known_issues: &[],
+        }
+    }
 }
@@ -117,40 +117,19 @@ impl<'a> rom::Chip for Chip<'a> {
             id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        rom::text::Part::Command {
-                            op: EREADER_COMMAND,
-                            params,
-                        } => {
-                            if let Ok(parts) = rom::text::parse(
-                                &self.assets.mapper.get(0x02001772 + params[1] as u32 * 0x10),
-                                &self.assets.text_parse_options,
-                            ) {
-                                parts
-                                    .into_iter()
-                                    .flat_map(|part| {
-                                        match part {
-                                            rom::text::Part::String(s) => s,
-                                            _ => "".to_string(),
-                                        }
-                                        .chars()
-                                        .collect::<Vec<_>>()
-                                    })
-                                    .collect::<String>()
-                            } else {
-                                "???".to_string()
-                            }
-                        }
-                        _ => "".to_string(),
+            rom::text::plain_text_with(&parts, |op, params| match op {
+                EREADER_COMMAND => Some({
+                    if let Ok(parts) = rom::text::parse(
+                        &self.assets.mapper.get(0x02001772 + params[1] as u32 * 0x10),
+                        &self.assets.text_parse_options,
+                    ) {
+                        rom::text::plain_text(&parts)
+                    } else {
+                        "???".to_string()
                     }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+                }),
+                _ => None,
+            })
         } else {
             "???".to_string()
         }
@@ -168,42 +147,21 @@ impl<'a> rom::Chip for Chip<'a> {
             id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        rom::text::Part::Command {
-                            op: EREADER_COMMAND,
-                            params,
-                        } => {
-                            if let Ok(parts) = rom::text::parse(
-                                &self.assets.mapper.get(0x02000522 + params[1] as u32 * 0x5c),
-                                &self.assets.text_parse_options,
-                            ) {
-                                parts
-                                    .into_iter()
-                                    .flat_map(|part| {
-                                        match part {
-                                            rom::text::Part::String(s) => s,
-                                            _ => "".to_string(),
-                                        }
-                                        .chars()
-                                        .collect::<Vec<_>>()
-                                    })
-                                    .collect::<String>()
-                            } else {
-                                "???".to_string()
-                            }
-                        }
-                        _ => "".to_string(),
+            rom::text::plain_text_with(&parts, |op, params| match op {
+                EREADER_COMMAND => Some({
+                    if let Ok(parts) = rom::text::parse(
+                        &self.assets.mapper.get(0x02000522 + params[1] as u32 * 0x5c),
+                        &self.assets.text_parse_options,
+                    ) {
+                        rom::text::plain_text(&parts)
+                    } else {
+                        "???".to_string()
                     }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
-                .replace("-\n", "-")
-                .replace("\n", " ")
+                }),
+                _ => None,
+            })
+            .replace("-\n", "-")
+            .replace("\n", " ")
         } else {
             "???".to_string()
         }
@@ -311,17 +269,7 @@ impl<'a> rom::NavicustPart for NavicustPart<'a> {
             self.id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match &part {
-                        rom::text::Part::String(s) => s,
-                        _ => "",
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+            rom::text::plain_text(&parts)
         } else {
             "???".to_string()
         }
@@ -335,19 +283,7 @@ impl<'a> rom::NavicustPart for NavicustPart<'a> {
             self.id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
-                .replace("-\n", "-")
-                .replace("\n", " ")
+            rom::text::plain_text(&parts).replace("-\n", "-").replace("\n", " ")
         } else {
             "???".to_string()
         }
@@ -429,7 +365,7 @@ impl Assets {
     pub fn new(
         offsets: &'static Offsets,
         modcards: &'static [Modcard4; 133],
-        charset: Vec<String>,
+        charset: rom::text::Charset,
         rom: Vec<u8>,
         wram: Vec<u8>,
     ) -> Self {
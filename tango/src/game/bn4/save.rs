@@ -1,12 +1,15 @@
 use byteorder::ByteOrder;
 
-use crate::save;
+use crate::save::{self, ChipsView as _};
 
 const SRAM_SIZE: usize = 0x73d2;
 const MASK_OFFSET: usize = 0x1554;
 const GAME_NAME_OFFSET: usize = 0x2208;
 const CHECKSUM_OFFSET: usize = 0x21e8;
 const SHIFT_OFFSET: usize = 0x1550;
+const PACK_OFFSET: usize = 0x26e4;
+const PACK_ENTRY_SIZE: usize = 0xc;
+const NUM_CHIPS: usize = 389;
 
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum Variant {
@@ -159,6 +162,41 @@ impl save::Save for Save {
         Some(Box::new(DarkAIView { save: self }))
     }
 
+    fn remap_ids(&mut self, table: &save::migration::Table) -> Result<(), save::migration::Error> {
+        // Modcards are kept twice over depending on whether they're enabled, which isn't worth the risk of getting wrong.
+        if table.modcards.is_some() {
+            return Err(save::migration::Error::Unsupported);
+        }
+
+        let mut remapper = save::migration::Remapper::new(table);
+
+        let num_folders = ChipsView { save: self }.num_folders();
+        let chips_offset = self.shift + 0x262c;
+        remapper.packed_chips(&mut self.buf[chips_offset..chips_offset + num_folders * (30 * 2)])?;
+        let pack_offset = self.shift + PACK_OFFSET;
+        remapper.pack(
+            &mut self.buf[pack_offset..pack_offset + NUM_CHIPS * PACK_ENTRY_SIZE],
+            PACK_ENTRY_SIZE,
+        )?;
+
+        let ncp_offset = self.shift + 0x4564;
+        remapper.navicust_parts(&mut self.buf[ncp_offset..ncp_offset + 25 * 8])?;
+        remapper.finish()?;
+
+        // Worked out the same way it's checked when the save is loaded.
+        let checksum = compute_raw_checksum(&self.buf, self.shift) + checksum_start_for_variant(self.game_info.variant)
+            - if self.game_info.region == Region::JP {
+                self.buf[0] as u32
+            } else {
+                0
+            };
+        byteorder::LittleEndian::write_u32(
+            &mut self.buf[self.shift + CHECKSUM_OFFSET..self.shift + CHECKSUM_OFFSET + 4],
+            checksum,
+        );
+        Ok(())
+    }
+
     fn as_raw_wram(&self) -> &[u8] {
         &self.buf
     }
@@ -218,6 +256,19 @@ impl<'a> save::ChipsView<'a> for ChipsView<'a> {
             code: (raw >> 9) as usize,
         })
     }
+
+    fn pack_ids(&self) -> Option<Vec<usize>> {
+        let pack_offset = self.save.shift + PACK_OFFSET;
+        Some(
+            (0..NUM_CHIPS)
+                .filter(|id| {
+                    self.save.buf[pack_offset + id * PACK_ENTRY_SIZE..pack_offset + (id + 1) * PACK_ENTRY_SIZE]
+                        .iter()
+                        .any(|count| *count != 0)
+                })
+                .collect(),
+        )
+    }
 }
 
 pub struct NavicustView<'a> {
@@ -65,11 +65,8 @@ impl game::Game for EXE5BImpl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::BRBJ_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::JA_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::JA_CHARSET, overrides),
+            &overrides.modcard56_table,
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -135,11 +132,8 @@ impl game::Game for EXE5CImpl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::BRKJ_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::JA_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::JA_CHARSET, overrides),
+            &overrides.modcard56_table,
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -205,11 +199,8 @@ impl game::Game for BN5PImpl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::BRBE_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::EN_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::EN_CHARSET, overrides),
+            &overrides.modcard56_table,
             rom.to_vec(),
             wram.to_vec(),
         )))
@@ -275,11 +266,8 @@ impl game::Game for BN5CImpl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::BRKE_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::EN_CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::EN_CHARSET, overrides),
+            &overrides.modcard56_table,
             rom.to_vec(),
             wram.to_vec(),
         )))
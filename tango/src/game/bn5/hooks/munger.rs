@@ -84,6 +84,15 @@ impl Munger {
         core.raw_write_32(self.offsets.ewram.battle_state + 0x60, -1, v)
     }
 
+    pub(super) fn in_battle(&self, mut core: mgba::core::CoreMutRef) -> bool {
+        core.raw_read_8(self.offsets.ewram.battle_state + 0x00, -1) != 0
+    }
+
+    /// The battle state is laid out the same as in BN6, so the custom gauge is in the same place and fills the same way.
+    pub(super) fn custom_gauge(&self, mut core: mgba::core::CoreMutRef) -> u16 {
+        core.raw_read_16(self.offsets.ewram.battle_state + 0x1c, -1)
+    }
+
     pub(super) fn set_copy_data_input_state(&self, mut core: mgba::core::CoreMutRef, v: u8) {
         core.raw_write_8(self.offsets.ewram.copy_data_input_state, -1, v);
     }
@@ -1,6 +1,6 @@
 use byteorder::ByteOrder;
 
-use crate::rom;
+use crate::{patch, rom};
 
 pub struct Offsets {
     chip_data: u32,
@@ -102,6 +102,10 @@ pub struct Assets {
     mapper: rom::MemoryMapper,
     chip_icon_palette: [image::Rgba<u8>; 16],
     element_icon_palette: [image::Rgba<u8>; 16],
+    modcard_data: u32,
+    modcard_names_pointer: u32,
+    modcard_details_names_pointer: u32,
+    num_modcard56s: usize,
 }
 
 struct Chip<'a> {
@@ -130,40 +134,19 @@ impl<'a> rom::Chip for Chip<'a> {
             id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        rom::text::Part::Command {
-                            op: EREADER_COMMAND,
-                            params,
-                        } => {
-                            if let Ok(parts) = rom::text::parse(
-                                &self.assets.mapper.get(0x02001d16 + params[1] as u32 * 0x18),
-                                &self.assets.text_parse_options,
-                            ) {
-                                parts
-                                    .into_iter()
-                                    .flat_map(|part| {
-                                        match part {
-                                            rom::text::Part::String(s) => s,
-                                            _ => "".to_string(),
-                                        }
-                                        .chars()
-                                        .collect::<Vec<_>>()
-                                    })
-                                    .collect::<String>()
-                            } else {
-                                "???".to_string()
-                            }
-                        }
-                        _ => "".to_string(),
+            rom::text::plain_text_with(&parts, |op, params| match op {
+                EREADER_COMMAND => Some({
+                    if let Ok(parts) = rom::text::parse(
+                        &self.assets.mapper.get(0x02001d16 + params[1] as u32 * 0x18),
+                        &self.assets.text_parse_options,
+                    ) {
+                        rom::text::plain_text(&parts)
+                    } else {
+                        "???".to_string()
                     }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+                }),
+                _ => None,
+            })
         } else {
             "???".to_string()
         }
@@ -181,42 +164,21 @@ impl<'a> rom::Chip for Chip<'a> {
             id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        rom::text::Part::Command {
-                            op: EREADER_COMMAND,
-                            params,
-                        } => {
-                            if let Ok(parts) = rom::text::parse(
-                                &self.assets.mapper.get(0x02001376 + params[1] as u32 * 100),
-                                &self.assets.text_parse_options,
-                            ) {
-                                parts
-                                    .into_iter()
-                                    .flat_map(|part| {
-                                        match part {
-                                            rom::text::Part::String(s) => s,
-                                            _ => "".to_string(),
-                                        }
-                                        .chars()
-                                        .collect::<Vec<_>>()
-                                    })
-                                    .collect::<String>()
-                            } else {
-                                "???".to_string()
-                            }
-                        }
-                        _ => "".to_string(),
+            rom::text::plain_text_with(&parts, |op, params| match op {
+                EREADER_COMMAND => Some({
+                    if let Ok(parts) = rom::text::parse(
+                        &self.assets.mapper.get(0x02001376 + params[1] as u32 * 100),
+                        &self.assets.text_parse_options,
+                    ) {
+                        rom::text::plain_text(&parts)
+                    } else {
+                        "???".to_string()
                     }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
-                .replace("-\n", "-")
-                .replace("\n", " ")
+                }),
+                _ => None,
+            })
+            .replace("-\n", "-")
+            .replace("\n", " ")
         } else {
             "???".to_string()
         }
@@ -324,17 +286,7 @@ impl<'a> rom::NavicustPart for NavicustPart<'a> {
             self.id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match &part {
-                        rom::text::Part::String(s) => s,
-                        _ => "",
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+            rom::text::plain_text(&parts)
         } else {
             "???".to_string()
         }
@@ -348,19 +300,7 @@ impl<'a> rom::NavicustPart for NavicustPart<'a> {
             self.id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
-                .replace("-\n", "-")
-                .replace("\n", " ")
+            rom::text::plain_text(&parts).replace("-\n", "-").replace("\n", " ")
         } else {
             "???".to_string()
         }
@@ -414,7 +354,13 @@ impl<'a> rom::NavicustPart for NavicustPart<'a> {
 }
 
 impl Assets {
-    pub fn new(offsets: &'static Offsets, charset: Vec<String>, rom: Vec<u8>, wram: Vec<u8>) -> Self {
+    pub fn new(
+        offsets: &'static Offsets,
+        charset: rom::text::Charset,
+        modcard56_table: &patch::Modcard56TableOverride,
+        rom: Vec<u8>,
+        wram: Vec<u8>,
+    ) -> Self {
         let mapper = rom::MemoryMapper::new(rom, wram);
 
         let chip_icon_palette = rom::read_palette(
@@ -448,6 +394,12 @@ impl Assets {
             mapper,
             chip_icon_palette,
             element_icon_palette,
+            modcard_data: modcard56_table.data.unwrap_or(offsets.modcard_data),
+            modcard_names_pointer: modcard56_table.names_pointer.unwrap_or(offsets.modcard_names_pointer),
+            modcard_details_names_pointer: modcard56_table
+                .details_names_pointer
+                .unwrap_or(offsets.modcard_details_names_pointer),
+            num_modcard56s: modcard56_table.count.unwrap_or(112),
         }
     }
 }
@@ -458,11 +410,12 @@ struct Modcard56<'a> {
 }
 
 impl<'a> Modcard56<'a> {
-    pub fn raw_info(&self) -> Vec<u8> {
-        let buf = self.assets.mapper.get(self.assets.offsets.modcard_data);
-        buf[byteorder::LittleEndian::read_u16(&buf[self.id * 2..(self.id + 1) * 2]) as usize
-            ..byteorder::LittleEndian::read_u16(&buf[(self.id + 1) * 2..(self.id + 2) * 2]) as usize]
-            .to_vec()
+    /// Reads the modcard's entry from the data table, if the table actually has it: patches can get the count wrong.
+    pub fn raw_info(&self) -> Option<Vec<u8>> {
+        let buf = self.assets.mapper.get(self.assets.modcard_data);
+        let start = byteorder::LittleEndian::read_u16(buf.get(self.id * 2..(self.id + 1) * 2)?) as usize;
+        let end = byteorder::LittleEndian::read_u16(buf.get((self.id + 1) * 2..(self.id + 2) * 2)?) as usize;
+        buf.get(start..end).map(|raw| raw.to_vec())
     }
 }
 
@@ -474,22 +427,12 @@ impl<'a> rom::Modcard56 for Modcard56<'a> {
 
         if let Ok(parts) = rom::text::parse_entry(
             &self.assets.mapper.get(byteorder::LittleEndian::read_u32(
-                &self.assets.mapper.get(self.assets.offsets.modcard_names_pointer)[..4],
+                &self.assets.mapper.get(self.assets.modcard_names_pointer)[..4],
             )),
             self.id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+            rom::text::plain_text(&parts)
         } else {
             "???".to_string()
         }
@@ -502,8 +445,7 @@ impl<'a> rom::Modcard56 for Modcard56<'a> {
             return 0;
         }
 
-        let raw = self.raw_info();
-        raw[1]
+        self.raw_info().and_then(|raw| raw.get(1).cloned()).unwrap_or(0)
     }
 
     fn effects(&self) -> Vec<rom::Modcard56Effect> {
@@ -511,9 +453,13 @@ impl<'a> rom::Modcard56 for Modcard56<'a> {
             return vec![];
         }
 
-        let raw = self.raw_info();
+        let raw = if let Some(raw) = self.raw_info().filter(|raw| raw.len() >= 3) {
+            raw
+        } else {
+            return vec![];
+        };
         raw[3..]
-            .chunks(3)
+            .chunks_exact(3)
             .map(|chunk| {
                 let id = chunk[0];
                 let parameter = chunk[1];
@@ -522,10 +468,7 @@ impl<'a> rom::Modcard56 for Modcard56<'a> {
                     name: {
                         if let Ok(parts) = rom::text::parse_entry(
                             &self.assets.mapper.get(byteorder::LittleEndian::read_u32(
-                                &self
-                                    .assets
-                                    .mapper
-                                    .get(self.assets.offsets.modcard_details_names_pointer)[..4],
+                                &self.assets.mapper.get(self.assets.modcard_details_names_pointer)[..4],
                             )),
                             id as usize,
                             &self.assets.text_parse_options,
@@ -618,7 +561,7 @@ impl rom::Assets for Assets {
     }
 
     fn num_modcard56s(&self) -> usize {
-        112
+        self.num_modcard56s
     }
 }
 
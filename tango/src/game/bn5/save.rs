@@ -1,12 +1,15 @@
 use byteorder::ByteOrder;
 
-use crate::save;
+use crate::save::{self, ChipsView as _};
 
 const SRAM_START_OFFSET: usize = 0x0100;
 const SRAM_SIZE: usize = 0x7c14;
 const MASK_OFFSET: usize = 0x1a34;
 const GAME_NAME_OFFSET: usize = 0x29e0;
 const CHECKSUM_OFFSET: usize = 0x29dc;
+const PACK_OFFSET: usize = 0x2eac;
+const PACK_ENTRY_SIZE: usize = 0xc;
+const NUM_CHIPS: usize = 411;
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Region {
@@ -124,6 +127,24 @@ impl save::Save for Save {
         Some(Box::new(DarkAIView { save: self }))
     }
 
+    fn remap_ids(&mut self, table: &save::migration::Table) -> Result<(), save::migration::Error> {
+        let mut remapper = save::migration::Remapper::new(table);
+        let num_folders = ChipsView { save: self }.num_folders();
+        remapper.packed_chips(&mut self.buf[0x2df4..0x2df4 + num_folders * (30 * 2)])?;
+        remapper.pack(
+            &mut self.buf[PACK_OFFSET..PACK_OFFSET + NUM_CHIPS * PACK_ENTRY_SIZE],
+            PACK_ENTRY_SIZE,
+        )?;
+        remapper.navicust_parts(&mut self.buf[0x4d6c..0x4d6c + 25 * 8])?;
+        let count = self.buf[0x79a0] as usize;
+        remapper.modcard56s(&mut self.buf[0x79d0..0x79d0 + count])?;
+        remapper.finish()?;
+
+        let checksum = self.compute_checksum();
+        byteorder::LittleEndian::write_u32(&mut self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4], checksum);
+        Ok(())
+    }
+
     fn as_raw_wram(&self) -> &[u8] {
         &self.buf
     }
@@ -183,6 +204,18 @@ impl<'a> save::ChipsView<'a> for ChipsView<'a> {
             code: (raw >> 9) as usize,
         })
     }
+
+    fn pack_ids(&self) -> Option<Vec<usize>> {
+        Some(
+            (0..NUM_CHIPS)
+                .filter(|id| {
+                    self.save.buf[PACK_OFFSET + id * PACK_ENTRY_SIZE..PACK_OFFSET + (id + 1) * PACK_ENTRY_SIZE]
+                        .iter()
+                        .any(|count| *count != 0)
+                })
+                .collect(),
+        )
+    }
 }
 
 pub struct Modcard56sView<'a> {
@@ -183,11 +183,7 @@ impl game::Hooks for Hooks {
                     };
 
                     let mut round_state = sync::block_on(match_.lock_round_state());
-                    let result = {
-                        let round = round_state.round.as_ref().expect("round");
-                        round.on_draw_result()
-                    };
-                    round_state.set_last_result(result);
+                    round_state.set_draw_result();
                 })
             }),
             (self.offsets.rom.round_set_ending, {
@@ -356,13 +352,13 @@ impl game::Hooks for Hooks {
                             &munger.tx_packet(core),
                         );
 
-                        log::info!(
+                        log::debug!(
                             "primary rng1 state: {:08x}, rng2 state: {:08x}, rng3 state: {:08x}",
                             munger.rng1_state(core),
                             munger.rng2_state(core),
                             munger.rng3_state(core),
                         );
-                        log::info!("battle state committed on {}", round.current_tick());
+                        log::debug!("battle state committed on {}", round.current_tick());
                     }
 
                     let game_current_tick = munger.current_tick(core);
@@ -579,13 +575,13 @@ impl game::Hooks for Hooks {
 
                         round
                             .set_first_committed_state(core.save_state().expect("save state"), &munger.tx_packet(core));
-                        log::info!(
+                        log::debug!(
                             "shadow rng1 state: {:08x}, rng2 state: {:08x}, rng3 state: {:08x}",
                             munger.rng1_state(core),
                             munger.rng2_state(core),
                             munger.rng3_state(core)
                         );
-                        log::info!("shadow state committed on {}", round.current_tick());
+                        log::debug!("shadow state committed on {}", round.current_tick());
                         return;
                     }
 
@@ -598,6 +594,10 @@ impl game::Hooks for Hooks {
                         ));
                     }
 
+                    if round.is_round_time_limit_reached() {
+                        munger.end_battle_on_time_limit(core);
+                    }
+
                     if let Some(ip) = round.peek_shadow_input().clone() {
                         if ip.local.local_tick != ip.remote.local_tick {
                             shadow_state.set_anyhow_error(anyhow::anyhow!(
@@ -811,9 +811,14 @@ impl game::Hooks for Hooks {
                         panic!("round tick = {} but game tick = {}", current_tick, game_current_tick);
                     }
 
+                    if replayer_state.is_round_time_limit_reached() {
+                        munger.end_battle_on_time_limit(core);
+                    }
+
                     if current_tick == replayer_state.commit_tick() {
                         replayer_state.set_committed_state(core.save_state().expect("save committed state"));
                     }
+                    replayer_state.record_state_hash(core);
 
                     let ip = match replayer_state.peek_input_pair() {
                         Some(ip) => ip.clone(),
@@ -999,4 +1004,126 @@ impl game::Hooks for Hooks {
             .cpu_mut()
             .set_thumb_pc(self.offsets.rom.main_read_joyflags);
     }
+
+    fn rng_states(&self, core: mgba::core::CoreMutRef) -> Vec<u32> {
+        let munger = self.munger();
+        vec![
+            munger.rng1_state(core),
+            munger.rng2_state(core),
+            munger.rng3_state(core),
+        ]
+    }
+
+    fn state_hash_regions(&self) -> Vec<std::ops::Range<u32>> {
+        vec![
+            // Shared RNG state.
+            self.offsets.ewram.rng2_state..self.offsets.ewram.rng2_state + 4,
+            self.offsets.ewram.rng3_state..self.offsets.ewram.rng3_state + 4,
+            // Custom gauge.
+            self.offsets.ewram.battle_state + 0x1c..self.offsets.ewram.battle_state + 0x1e,
+            // Current tick.
+            self.offsets.ewram.battle_state + 0x60..self.offsets.ewram.battle_state + 0x64,
+        ]
+    }
+
+    fn practice_telemetry(&self) -> Option<&(dyn game::PracticeTelemetry + Send + Sync)> {
+        Some(self)
+    }
+
+    fn practice_manipulation(&self) -> Option<&(dyn game::PracticeManipulation + Send + Sync)> {
+        Some(self)
+    }
+
+    fn custom_gauge(&self, core: mgba::core::CoreMutRef) -> Option<u16> {
+        let munger = self.munger();
+        if !munger.in_battle(core) {
+            return None;
+        }
+        Some(munger.custom_gauge(core))
+    }
+
+    fn supports_round_time_limit(&self) -> bool {
+        true
+    }
+
+    fn capabilities(&self) -> game::Capabilities {
+        game::Capabilities {
+            rollback: true,
+            opponent_name_replacement: false,
+            round_time_limit: true,
+            desync_detection: true,
+            unsupported_match_types: &[],
+            known_issues: &[],
+        }
+    }
+}
+
+impl game::PracticeTelemetry for Hooks {
+    fn in_battle(&self, core: mgba::core::CoreMutRef) -> bool {
+        self.munger().in_battle(core)
+    }
+
+    fn current_tick(&self, core: mgba::core::CoreMutRef) -> Option<u32> {
+        Some(self.munger().current_tick(core))
+    }
+
+    fn custom_gauge_frames_remaining(&self, core: mgba::core::CoreMutRef) -> Option<u32> {
+        Some((0x4000u32.saturating_sub(self.munger().custom_gauge(core) as u32) + 0x1f) / 0x20)
+    }
+
+    fn hp(&self, core: mgba::core::CoreMutRef, player_index: usize) -> Option<u16> {
+        self.munger().navi_hp(core, player_index)
+    }
+
+    fn invulnerability_frames(&self, core: mgba::core::CoreMutRef, player_index: usize) -> Option<u16> {
+        self.munger().navi_invulnerability_frames(core, player_index)
+    }
+
+    fn chip_in_hand(&self, core: mgba::core::CoreMutRef, player_index: usize) -> Option<Option<usize>> {
+        self.munger().navi_chip_in_hand(core, player_index).map(|chip_id| {
+            if chip_id != 0 {
+                Some(chip_id as usize)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl game::PracticeManipulation for Hooks {
+    fn trap_address(&self) -> u32 {
+        self.offsets.rom.main_read_joyflags
+    }
+
+    fn in_battle(&self, core: mgba::core::CoreMutRef) -> bool {
+        self.munger().in_battle(core)
+    }
+
+    fn fill_custom_gauge(&self, core: mgba::core::CoreMutRef) {
+        self.munger().set_custom_gauge(core, 0x4000);
+    }
+
+    fn set_hp(&self, core: mgba::core::CoreMutRef, player_index: usize, hp: u16) {
+        self.munger().set_navi_hp(core, player_index, hp);
+    }
+
+    fn set_chip_in_hand(&self, core: mgba::core::CoreMutRef, player_index: usize, chip_id: u16) {
+        self.munger().set_navi_chip_in_hand(core, player_index, chip_id);
+    }
+
+    fn rng_state(&self, core: mgba::core::CoreMutRef) -> Vec<u32> {
+        let munger = self.munger();
+        vec![
+            munger.rng1_state(core),
+            munger.rng2_state(core),
+            munger.rng3_state(core),
+        ]
+    }
+
+    fn set_rng_state(&self, core: mgba::core::CoreMutRef, state: &[u32]) {
+        let munger = self.munger();
+        munger.set_rng1_state(core, state[0]);
+        munger.set_rng2_state(core, state[1]);
+        munger.set_rng3_state(core, state[2]);
+    }
 }
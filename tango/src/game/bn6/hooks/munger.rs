@@ -82,4 +82,79 @@ impl Munger {
     pub(super) fn set_copy_data_input_state(&self, mut core: mgba::core::CoreMutRef, v: u8) {
         core.raw_write_8(self.offsets.ewram.copy_data_input_state, -1, v);
     }
+
+    pub(super) fn in_battle(&self, mut core: mgba::core::CoreMutRef) -> bool {
+        core.raw_read_8(self.offsets.ewram.battle_state + 0x00, -1) != 0
+    }
+
+    /// The custom gauge fills from 0 to 0x4000, by 0x20 each frame at normal speed.
+    pub(super) fn custom_gauge(&self, mut core: mgba::core::CoreMutRef) -> u16 {
+        core.raw_read_16(self.offsets.ewram.battle_state + 0x1c, -1)
+    }
+
+    pub(super) fn set_custom_gauge(&self, mut core: mgba::core::CoreMutRef, v: u16) {
+        core.raw_write_16(self.offsets.ewram.battle_state + 0x1c, -1, v)
+    }
+
+    fn navi_object(&self, mut core: mgba::core::CoreMutRef, player_index: usize) -> Option<u32> {
+        let ptr = core.raw_read_32(self.offsets.ewram.battle_state + 0x78 + player_index as u32 * 4, -1);
+        if ptr == 0 {
+            return None;
+        }
+        Some(ptr)
+    }
+
+    pub(super) fn navi_hp(&self, mut core: mgba::core::CoreMutRef, player_index: usize) -> Option<u16> {
+        let obj = self.navi_object(core, player_index)?;
+        Some(core.raw_read_16(obj + 0x24, -1))
+    }
+
+    pub(super) fn set_navi_hp(&self, mut core: mgba::core::CoreMutRef, player_index: usize, hp: u16) {
+        if let Some(obj) = self.navi_object(core, player_index) {
+            core.raw_write_16(obj + 0x24, -1, hp);
+        }
+    }
+
+    /// Ends an undecided battle by deleting the navi with less HP, or both if they're tied.
+    ///
+    /// This only depends on the battle state, so every core that runs the same tick makes the same decision.
+    pub(super) fn end_battle_on_time_limit(&self, core: mgba::core::CoreMutRef) {
+        let (p1_hp, p2_hp) = match (self.navi_hp(core, 0), self.navi_hp(core, 1)) {
+            (Some(p1_hp), Some(p2_hp)) => (p1_hp, p2_hp),
+            _ => {
+                return;
+            }
+        };
+        if p1_hp == 0 || p2_hp == 0 {
+            return;
+        }
+        log::info!("round time limit reached: p1 hp = {}, p2 hp = {}", p1_hp, p2_hp);
+        if p1_hp <= p2_hp {
+            self.set_navi_hp(core, 0, 0);
+        }
+        if p2_hp <= p1_hp {
+            self.set_navi_hp(core, 1, 0);
+        }
+    }
+
+    pub(super) fn navi_invulnerability_frames(
+        &self,
+        mut core: mgba::core::CoreMutRef,
+        player_index: usize,
+    ) -> Option<u16> {
+        let obj = self.navi_object(core, player_index)?;
+        Some(core.raw_read_16(obj + 0x8e, -1))
+    }
+
+    /// The ID of the chip the navi is currently holding, or 0 if it's empty-handed.
+    pub(super) fn navi_chip_in_hand(&self, mut core: mgba::core::CoreMutRef, player_index: usize) -> Option<u16> {
+        let obj = self.navi_object(core, player_index)?;
+        Some(core.raw_read_16(obj + 0x6a, -1))
+    }
+
+    pub(super) fn set_navi_chip_in_hand(&self, mut core: mgba::core::CoreMutRef, player_index: usize, chip_id: u16) {
+        if let Some(obj) = self.navi_object(core, player_index) {
+            core.raw_write_16(obj + 0x6a, -1, chip_id);
+        }
+    }
 }
@@ -103,6 +103,10 @@ pub struct Assets {
     mapper: rom::MemoryMapper,
     chip_icon_palette: [image::Rgba<u8>; 16],
     element_icon_palette: [image::Rgba<u8>; 16],
+    modcard_data: u32,
+    modcard_names_pointer: u32,
+    modcard_details_names_pointer: u32,
+    num_modcard56s: usize,
 }
 
 struct Chip<'a> {
@@ -138,17 +142,7 @@ impl<'a> rom::Chip for Chip<'a> {
             id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+            rom::text::plain_text(&parts)
         } else {
             "???".to_string()
         }
@@ -173,42 +167,21 @@ impl<'a> rom::Chip for Chip<'a> {
             id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        rom::text::Part::Command {
-                            op: EREADER_COMMAND,
-                            params,
-                        } => {
-                            if let Ok(parts) = rom::text::parse(
-                                &self.assets.mapper.get(0x020007d6 + params[1] as u32 * 100),
-                                &self.assets.text_parse_options,
-                            ) {
-                                parts
-                                    .into_iter()
-                                    .flat_map(|part| {
-                                        match part {
-                                            rom::text::Part::String(s) => s,
-                                            _ => "".to_string(),
-                                        }
-                                        .chars()
-                                        .collect::<Vec<_>>()
-                                    })
-                                    .collect::<String>()
-                            } else {
-                                "???".to_string()
-                            }
-                        }
-                        _ => "".to_string(),
+            rom::text::plain_text_with(&parts, |op, params| match op {
+                EREADER_COMMAND => Some({
+                    if let Ok(parts) = rom::text::parse(
+                        &self.assets.mapper.get(0x020007d6 + params[1] as u32 * 100),
+                        &self.assets.text_parse_options,
+                    ) {
+                        rom::text::plain_text(&parts)
+                    } else {
+                        "???".to_string()
                     }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
-                .replace("-\n", "-")
-                .replace("\n", " ")
+                }),
+                _ => None,
+            })
+            .replace("-\n", "-")
+            .replace("\n", " ")
         } else {
             "???".to_string()
         }
@@ -321,17 +294,7 @@ impl<'a> rom::NavicustPart for NavicustPart<'a> {
             self.id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match &part {
-                        rom::text::Part::String(s) => s,
-                        _ => "",
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+            rom::text::plain_text(&parts)
         } else {
             "???".to_string()
         }
@@ -352,19 +315,7 @@ impl<'a> rom::NavicustPart for NavicustPart<'a> {
             self.id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
-                .replace("-\n", "-")
-                .replace("\n", " ")
+            rom::text::plain_text(&parts).replace("-\n", "-").replace("\n", " ")
         } else {
             "???".to_string()
         }
@@ -426,6 +377,7 @@ impl Assets {
         overrides: &patch::ROMOverrides,
     ) -> Self {
         let mapper = rom::MemoryMapper::new(rom, wram);
+        let modcard56_table = &overrides.modcard56_table;
 
         let chip_icon_palette = rom::read_palette(
             &mapper.get(byteorder::LittleEndian::read_u32(
@@ -443,11 +395,7 @@ impl Assets {
             offsets,
             overrides: overrides.clone(),
             text_parse_options: rom::text::ParseOptions {
-                charset: overrides
-                    .charset
-                    .as_ref()
-                    .cloned()
-                    .unwrap_or_else(|| default_charset.iter().map(|s| s.to_string()).collect()),
+                charset: rom::text::Charset::with_overrides(default_charset, overrides),
                 extension_ops: 0xe4..=0xe4,
                 eof_op: 0xe6,
                 newline_op: 0xe9,
@@ -463,6 +411,12 @@ impl Assets {
             mapper,
             chip_icon_palette,
             element_icon_palette,
+            modcard_data: modcard56_table.data.unwrap_or(offsets.modcard_data),
+            modcard_names_pointer: modcard56_table.names_pointer.unwrap_or(offsets.modcard_names_pointer),
+            modcard_details_names_pointer: modcard56_table
+                .details_names_pointer
+                .unwrap_or(offsets.modcard_details_names_pointer),
+            num_modcard56s: modcard56_table.count.unwrap_or(118),
         }
     }
 }
@@ -473,11 +427,12 @@ struct Modcard56<'a> {
 }
 
 impl<'a> Modcard56<'a> {
-    pub fn raw_info(&self) -> Vec<u8> {
-        let buf = self.assets.mapper.get(self.assets.offsets.modcard_data);
-        buf[byteorder::LittleEndian::read_u16(&buf[self.id * 2..(self.id + 1) * 2]) as usize
-            ..byteorder::LittleEndian::read_u16(&buf[(self.id + 1) * 2..(self.id + 2) * 2]) as usize]
-            .to_vec()
+    /// Reads the modcard's entry from the data table, if the table actually has it: patches can get the count wrong.
+    pub fn raw_info(&self) -> Option<Vec<u8>> {
+        let buf = self.assets.mapper.get(self.assets.modcard_data);
+        let start = byteorder::LittleEndian::read_u16(buf.get(self.id * 2..(self.id + 1) * 2)?) as usize;
+        let end = byteorder::LittleEndian::read_u16(buf.get((self.id + 1) * 2..(self.id + 2) * 2)?) as usize;
+        buf.get(start..end).map(|raw| raw.to_vec())
     }
 }
 
@@ -496,22 +451,12 @@ impl<'a> rom::Modcard56 for Modcard56<'a> {
 
         if let Ok(parts) = rom::text::parse_entry(
             &self.assets.mapper.get(byteorder::LittleEndian::read_u32(
-                &self.assets.mapper.get(self.assets.offsets.modcard_names_pointer)[..4],
+                &self.assets.mapper.get(self.assets.modcard_names_pointer)[..4],
             )),
             self.id,
             &self.assets.text_parse_options,
         ) {
-            parts
-                .into_iter()
-                .flat_map(|part| {
-                    match part {
-                        rom::text::Part::String(s) => s,
-                        _ => "".to_string(),
-                    }
-                    .chars()
-                    .collect::<Vec<_>>()
-                })
-                .collect::<String>()
+            rom::text::plain_text(&parts)
         } else {
             "???".to_string()
         }
@@ -524,8 +469,7 @@ impl<'a> rom::Modcard56 for Modcard56<'a> {
             return 0;
         }
 
-        let raw = self.raw_info();
-        raw[1]
+        self.raw_info().and_then(|raw| raw.get(1).cloned()).unwrap_or(0)
     }
 
     fn effects(&self) -> Vec<rom::Modcard56Effect> {
@@ -533,9 +477,13 @@ impl<'a> rom::Modcard56 for Modcard56<'a> {
             return vec![];
         }
 
-        let raw = self.raw_info();
+        let raw = if let Some(raw) = self.raw_info().filter(|raw| raw.len() >= 3) {
+            raw
+        } else {
+            return vec![];
+        };
         raw[3..]
-            .chunks(3)
+            .chunks_exact(3)
             .map(|chunk| {
                 let id = chunk[0];
                 let parameter = chunk[1];
@@ -549,10 +497,7 @@ impl<'a> rom::Modcard56 for Modcard56<'a> {
                     } else {
                         if let Ok(parts) = rom::text::parse_entry(
                             &self.assets.mapper.get(byteorder::LittleEndian::read_u32(
-                                &self
-                                    .assets
-                                    .mapper
-                                    .get(self.assets.offsets.modcard_details_names_pointer)[..4],
+                                &self.assets.mapper.get(self.assets.modcard_details_names_pointer)[..4],
                             )),
                             id as usize,
                             &self.assets.text_parse_options,
@@ -645,7 +590,7 @@ impl rom::Assets for Assets {
     }
 
     fn num_modcard56s(&self) -> usize {
-        118
+        self.num_modcard56s
     }
 }
 
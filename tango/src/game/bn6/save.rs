@@ -1,12 +1,21 @@
 use byteorder::ByteOrder;
 
-use crate::save::{self, NaviView as _};
+use crate::rom;
+use crate::save::{self, ChipsView as _, NaviView as _};
 
 const SRAM_START_OFFSET: usize = 0x0100;
 const SRAM_SIZE: usize = 0x6710;
 const MASK_OFFSET: usize = 0x1064;
 const GAME_NAME_OFFSET: usize = 0x1c70;
 const CHECKSUM_OFFSET: usize = 0x1c6c;
+const FLAGS_OFFSET: usize = 0x0000;
+const LIBRARY_FLAGS_OFFSET: usize = 0x0c00;
+const LIBRARY_FLAGS_SIZE: usize = 0x40;
+const PACK_OFFSET: usize = 0x2230;
+const PACK_ENTRY_SIZE: usize = 0xc;
+const NUM_CHIPS: usize = 411;
+/// The HP counter only has room for 4 digits.
+const MAX_HP: u32 = 9999;
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Region {
@@ -102,6 +111,15 @@ impl Save {
             }
     }
 
+    /// The current and max HP of the current navi.
+    pub fn hp(&self) -> (u32, u32) {
+        let navi_stats_offset = self.navi_stats_offset(NaviView { save: self }.navi());
+        (
+            byteorder::LittleEndian::read_u16(&self.buf[navi_stats_offset + 0x3e..navi_stats_offset + 0x40]) as u32,
+            byteorder::LittleEndian::read_u16(&self.buf[navi_stats_offset + 0x40..navi_stats_offset + 0x42]) as u32,
+        )
+    }
+
     fn navi_stats_offset(&self, id: usize) -> usize {
         (if self.game_info.region == Region::JP {
             0x478c
@@ -128,10 +146,50 @@ impl save::Save for Save {
         }
     }
 
+    fn view_progress(&self) -> Option<Box<dyn save::ProgressView + '_>> {
+        Some(Box::new(ProgressView { save: self }))
+    }
+
     // fn view_navi(&self) -> Option<Box<dyn save::NaviView + '_>> {
     //     Some(Box::new(NaviView { save: self }))
     // }
 
+    fn validate(&self, assets: &dyn rom::Assets) -> Result<(), save::validation::IllegalValue> {
+        let (hp, max_hp) = self.hp();
+        save::validation::check_hp(hp, max_hp, MAX_HP)?;
+        save::validation::check_chips(&ChipsView { save: self }, assets)?;
+        save::validation::check_navicust(&NavicustView { save: self }, assets)?;
+        Ok(())
+    }
+
+    fn remap_ids(&mut self, table: &save::migration::Table) -> Result<(), save::migration::Error> {
+        let mut remapper = save::migration::Remapper::new(table);
+
+        let num_folders = ChipsView { save: self }.num_folders();
+        remapper.packed_chips(&mut self.buf[0x2178..0x2178 + num_folders * (30 * 2)])?;
+        remapper.pack(
+            &mut self.buf[PACK_OFFSET..PACK_OFFSET + NUM_CHIPS * PACK_ENTRY_SIZE],
+            PACK_ENTRY_SIZE,
+        )?;
+
+        let ncp_offset = if self.game_info.region == Region::JP {
+            0x4150
+        } else {
+            0x4190
+        };
+        remapper.navicust_parts(&mut self.buf[ncp_offset..ncp_offset + 25 * 8])?;
+
+        if self.game_info.region == Region::JP {
+            let count = self.buf[0x65f0] as usize;
+            remapper.modcard56s(&mut self.buf[0x6620..0x6620 + count])?;
+        }
+        remapper.finish()?;
+
+        let checksum = self.compute_checksum();
+        byteorder::LittleEndian::write_u32(&mut self.buf[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4], checksum);
+        Ok(())
+    }
+
     fn as_raw_wram(&self) -> &[u8] {
         &self.buf
     }
@@ -200,6 +258,18 @@ impl<'a> save::ChipsView<'a> for ChipsView<'a> {
             code: (raw >> 9) as usize,
         })
     }
+
+    fn pack_ids(&self) -> Option<Vec<usize>> {
+        Some(
+            (0..NUM_CHIPS)
+                .filter(|id| {
+                    self.save.buf[PACK_OFFSET + id * PACK_ENTRY_SIZE..PACK_OFFSET + (id + 1) * PACK_ENTRY_SIZE]
+                        .iter()
+                        .any(|count| *count != 0)
+                })
+                .collect(),
+        )
+    }
 }
 
 pub struct Modcard56sView<'a> {
@@ -280,3 +350,32 @@ impl<'a> save::NaviView<'a> for NaviView<'a> {
         self.save.buf[0x1b81] as usize
     }
 }
+
+pub struct ProgressView<'a> {
+    save: &'a Save,
+}
+
+impl<'a> save::ProgressView<'a> for ProgressView<'a> {
+    fn flag(&self, id: usize) -> bool {
+        self.save
+            .buf
+            .get(FLAGS_OFFSET + (id >> 3))
+            .map(|b| b & (0x80 >> (id & 7)) != 0)
+            .unwrap_or(false)
+    }
+
+    fn library_count(&self) -> usize {
+        self.save.buf[LIBRARY_FLAGS_OFFSET..LIBRARY_FLAGS_OFFSET + LIBRARY_FLAGS_SIZE]
+            .iter()
+            .map(|b| b.count_ones() as usize)
+            .sum()
+    }
+
+    fn library(&self) -> Option<Vec<usize>> {
+        Some(
+            (0..LIBRARY_FLAGS_SIZE * 8)
+                .filter(|id| self.save.buf[LIBRARY_FLAGS_OFFSET + (id >> 3)] & (0x80 >> (id & 7)) != 0)
+                .collect(),
+        )
+    }
+}
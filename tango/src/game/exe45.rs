@@ -4,6 +4,9 @@ mod save;
 
 use crate::{game, patch};
 
+// Known limitations compared to the mainline games:
+// - Navi selection isn't player-controlled: the navi the game picks in the comm menu is used as is.
+// - The cartridge RTC is fixed per match from the shared RNG instead of following the wall clock, so any date-dependent content reflects that date rather than the real one.
 const MATCH_TYPES: &[usize] = &[1, 1];
 
 struct EXE45Impl;
@@ -50,11 +53,7 @@ impl game::Game for EXE45Impl {
     ) -> Result<Box<dyn crate::rom::Assets + Send + Sync>, anyhow::Error> {
         Ok(Box::new(rom::Assets::new(
             &rom::BR4J_00,
-            overrides
-                .charset
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| rom::CHARSET.iter().map(|s| s.to_string()).collect()),
+            crate::rom::text::Charset::with_overrides(rom::CHARSET, overrides),
             rom.to_vec(),
             wram.to_vec(),
         )))
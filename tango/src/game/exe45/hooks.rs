@@ -48,6 +48,14 @@ fn random_battle_settings_and_background(rng: &mut impl rand::Rng, match_type: u
     (battle_settings, rng.gen_range(0..0x18u8))
 }
 
+/// Picks a time for the cartridge RTC, in milliseconds since the UNIX epoch.
+///
+/// The game reads the RTC during battle, so it must agree between both sides (and with the replay): we pick it from the shared RNG rather than using the wall clock.
+fn random_fixed_rtc(rng: &mut impl rand::Rng) -> i64 {
+    // 2000-01-01 to 2099-12-31, which is what the RTC can represent.
+    rng.gen_range(946684800000..4102358400000)
+}
+
 impl game::Hooks for Hooks {
     fn common_traps(&self) -> Vec<(u32, Box<dyn Fn(mgba::core::CoreMutRef)>)> {
         vec![
@@ -99,7 +107,7 @@ impl game::Hooks for Hooks {
             (self.offsets.rom.comm_menu_init_ret, {
                 let match_ = match_.clone();
                 let munger = self.munger();
-                Box::new(move |core| {
+                Box::new(move |mut core| {
                     let match_ = sync::block_on(match_.lock());
                     let match_ = match &*match_ {
                         Some(match_) => match_,
@@ -113,6 +121,10 @@ impl game::Hooks for Hooks {
                     let (battle_settings, background) =
                         random_battle_settings_and_background(&mut *rng, match_.match_type().0);
 
+                    let fixed_rtc = random_fixed_rtc(&mut *rng);
+                    core.set_rtc_fixed(fixed_rtc);
+                    match_.set_fixed_rtc(fixed_rtc);
+
                     munger.start_battle_from_comm_menu(core, match_.match_type().0, battle_settings, background);
                 })
             }),
@@ -194,11 +206,7 @@ impl game::Hooks for Hooks {
                     };
 
                     let mut round_state = sync::block_on(match_.lock_round_state());
-                    let result = {
-                        let round = round_state.round.as_ref().expect("round");
-                        round.on_draw_result()
-                    };
-                    round_state.set_last_result(result);
+                    round_state.set_draw_result();
                 })
             }),
             (self.offsets.rom.round_set_ending, {
@@ -330,13 +338,13 @@ impl game::Hooks for Hooks {
                                 .expect("shadow save state"),
                             &munger.tx_packet(core),
                         );
-                        log::info!(
+                        log::debug!(
                             "primary rng1 state: {:08x}, rng2 state: {:08x}, rng3 state: {:08x}",
                             munger.rng1_state(core),
                             munger.rng2_state(core),
                             munger.rng3_state(core),
                         );
-                        log::info!("battle state committed on {}", round.current_tick());
+                        log::debug!("battle state committed on {}", round.current_tick());
                     }
 
                     'abort: loop {
@@ -398,12 +406,14 @@ impl game::Hooks for Hooks {
                 let shadow_state = shadow_state.clone();
                 (
                     self.offsets.rom.comm_menu_init_ret,
-                    Box::new(move |core| {
+                    Box::new(move |mut core| {
                         let mut rng = shadow_state.lock_rng();
 
                         let (battle_settings, background) =
                             random_battle_settings_and_background(&mut *rng, shadow_state.match_type().0);
 
+                        core.set_rtc_fixed(random_fixed_rtc(&mut *rng));
+
                         munger.start_battle_from_comm_menu(
                             core,
                             shadow_state.match_type().0,
@@ -536,13 +546,13 @@ impl game::Hooks for Hooks {
                         // HACK: For some inexplicable reason, we don't always start on tick 0.
                         round
                             .set_first_committed_state(core.save_state().expect("save state"), &munger.tx_packet(core));
-                        log::info!(
+                        log::debug!(
                             "shadow rng1 state: {:08x}, rng2 state: {:08x}, rng3 state: {:08x}",
                             munger.rng1_state(core),
                             munger.rng2_state(core),
                             munger.rng3_state(core)
                         );
-                        log::info!("shadow state committed on {}", round.current_tick());
+                        log::debug!("shadow state committed on {}", round.current_tick());
                         return;
                     }
 
@@ -888,4 +898,24 @@ impl game::Hooks for Hooks {
             .cpu_mut()
             .set_thumb_pc(self.offsets.rom.main_read_joyflags);
     }
+
+    fn rng_states(&self, core: mgba::core::CoreMutRef) -> Vec<u32> {
+        let munger = self.munger();
+        vec![
+            munger.rng1_state(core),
+            munger.rng2_state(core),
+            munger.rng3_state(core),
+        ]
+    }
+
+    fn capabilities(&self) -> game::Capabilities {
+        game::Capabilities {
+            rollback: true,
+            opponent_name_replacement: false,
+            round_time_limit: false,
+            desync_detection: false,
+            unsupported_match_types: &[],
+            known_issues: &[],
+        }
+    }
 }
@@ -1,32 +1,145 @@
-use fluent_templates::Loader;
-
-use crate::{audio, config, discord, game, i18n, input, patch, rom, save, session, stats, updater};
+use crate::{
+    audio, broadcast, config, discord, fs, game, i18n, input, inputlatency, linkcode, patch, replay, rom, save,
+    session, stats, updater,
+};
 use std::str::FromStr;
 
+mod config_profile_window;
+mod crash_report_window;
+mod create_patch_window;
 mod debug_window;
+mod delay_preview;
 mod escape_window;
+mod file_drop;
+mod fonts;
+mod game_art;
+mod input_latency_window;
+mod install_patch_window;
 mod language_select;
+mod lobby_presets;
+mod log_viewer_window;
 mod main_view;
+mod markdown;
+mod match_summary_window;
+mod notifications;
+mod opponents_pane;
+mod patch_details;
 mod patches_pane;
 mod play_pane;
+mod repaint;
 mod replay_dump_windows;
 mod replays_pane;
+mod save_changes_window;
+mod save_diff_window;
 mod save_select_view;
 mod save_view;
 mod session_view;
 mod settings_window;
 mod steal_input_window;
+mod theme;
 mod updater_window;
 mod warning;
 mod welcome;
+mod window_title;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ReloadSaveError {
+    #[error("could not read save: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("save is no longer valid for the selected game: {0}")]
+    Invalid(anyhow::Error),
+
+    #[error("save is not allowed: {0}")]
+    Illegal(#[from] save::validation::IllegalValue),
+}
+
+impl ReloadSaveError {
+    pub fn description(&self, language: &unic_langid::LanguageIdentifier) -> String {
+        match self {
+            ReloadSaveError::Io(e) => i18n::LOCALES.lookup_with_args(
+                language,
+                "play-save-reload-error-io",
+                &std::collections::HashMap::from([("error", e.to_string().into())]),
+            ),
+            ReloadSaveError::Invalid(_) => i18n::LOCALES.lookup(language, "play-save-reload-error-invalid"),
+            ReloadSaveError::Illegal(e) => i18n::LOCALES.lookup_with_args(
+                language,
+                "play-save-reload-error-illegal",
+                &std::collections::HashMap::from([("error", e.to_string().into())]),
+            ),
+        }
+    }
+}
 
 pub struct Selection {
     pub game: &'static (dyn game::Game + Send + Sync),
-    pub assets: Option<Box<dyn rom::Assets + Send + Sync>>,
+    pub assets: Option<std::sync::Arc<dyn rom::Assets + Send + Sync>>,
     pub save: save::ScannedSave,
-    pub rom: Vec<u8>,
+    /// The ROM with the patch applied if there is one, shared with the ROM scanner or the patched ROM cache rather than copied.
+    pub rom: std::sync::Arc<[u8]>,
     pub patch: Option<(String, semver::Version, patch::Version)>,
     pub save_view_state: save_view::State,
+    pub unmet_save_requirements: Vec<patch::UnmetSaveRequirement>,
+    /// How migrating the save to the selected patch version went, if it was: where the original was backed up to, or what went wrong.
+    pub save_migration: Option<Result<std::path::PathBuf, String>>,
+    /// The language the assets' names were loaded in.
+    pub game_data_language: unic_langid::LanguageIdentifier,
+}
+
+/// Gets the language to show a game's data in: the one the user picked if they picked one, otherwise the patch's if it overrides it, otherwise the game's own.
+pub fn game_data_language(
+    override_language: Option<&unic_langid::LanguageIdentifier>,
+    game: &'static (dyn game::Game + Send + Sync),
+    patch: Option<&patch::Version>,
+) -> unic_langid::LanguageIdentifier {
+    override_language
+        .cloned()
+        .or_else(|| patch.and_then(|metadata| metadata.rom_overrides.language.clone()))
+        .unwrap_or_else(|| game.language())
+}
+
+/// Loads a game's assets, with any names the patch ships for the given language swapped in.
+pub fn load_assets(
+    game: &'static (dyn game::Game + Send + Sync),
+    rom: &[u8],
+    wram: &[u8],
+    patch: Option<&patch::Version>,
+    language: &unic_langid::LanguageIdentifier,
+) -> Result<std::sync::Arc<dyn rom::Assets + Send + Sync>, anyhow::Error> {
+    let assets = game.load_rom_assets(
+        rom,
+        wram,
+        &patch
+            .map(|metadata| metadata.rom_overrides.clone())
+            .unwrap_or_else(|| Default::default()),
+    )?;
+    Ok(if let Some(metadata) = patch {
+        patch::strings::apply(assets, metadata, language)
+    } else {
+        assets
+    }
+    .into())
+}
+
+/// Rescans ROMs, saves and patches in the background, e.g. after the data path has changed.
+pub fn rescan_all(
+    egui_ctx: &egui::Context,
+    config: &config::Config,
+    roms_scanner: rom::Scanner,
+    saves_scanner: save::Scanner,
+    patches_scanner: patch::Scanner,
+) {
+    let egui_ctx = egui_ctx.clone();
+    let roms_path = config.roms_path();
+    let saves_path = config.saves_path();
+    let patches_path = config.patches_path();
+    tokio::task::spawn_blocking(move || {
+        roms_scanner.rescan_progressively(move |progress| Some(game::scan_roms(&roms_path, false, progress)));
+        saves_scanner.rescan_progressively(move |progress| Some(save::scan_saves(&saves_path, false, progress)));
+        patches_scanner.rescan(move || Some(patch::scan(&patches_path).unwrap_or_default()));
+        egui_ctx.request_repaint();
+    });
 }
 
 impl Selection {
@@ -34,34 +147,59 @@ impl Selection {
         game: &'static (dyn game::Game + Send + Sync),
         save: save::ScannedSave,
         patch: Option<(String, semver::Version, patch::Version)>,
-        rom: Vec<u8>,
+        rom: std::sync::Arc<[u8]>,
     ) -> Self {
-        let assets = game
-            .load_rom_assets(
-                &rom,
-                save.save.as_raw_wram(),
-                &patch
-                    .as_ref()
-                    .map(|(_, _, metadata)| metadata.rom_overrides.clone())
-                    .unwrap_or_else(|| Default::default()),
-            )
-            .ok();
-        Self {
+        let metadata = patch.as_ref().map(|(_, _, metadata)| metadata);
+        let game_data_language = game_data_language(None, game, metadata);
+        let assets = load_assets(game, &rom, save.save.as_raw_wram(), metadata, &game_data_language).ok();
+        let mut selection = Self {
             game,
             assets,
             save,
             patch,
             rom,
             save_view_state: save_view::State::new(),
-        }
+            unmet_save_requirements: vec![],
+            save_migration: None,
+            game_data_language,
+        };
+        selection.check_save_requirements();
+        selection
+    }
+
+    fn check_save_requirements(&mut self) {
+        self.unmet_save_requirements = if let Some((_, _, metadata)) = self.patch.as_ref() {
+            patch::unmet_save_requirements(&metadata.save_requirements, self.save.save.as_ref())
+        } else {
+            vec![]
+        };
     }
 
-    pub fn reload_save(&mut self) -> anyhow::Result<()> {
-        let raw = std::fs::read(&self.save.path)?;
-        self.save.save = self.game.parse_save(&raw)?;
+    /// Whether the save fails a requirement the patch marks as hard, which blocks readying up.
+    pub fn has_blocking_save_requirements(&self) -> bool {
+        self.unmet_save_requirements.iter().any(|r| r.is_blocking())
+    }
+
+    /// Reads the save from disk again. If it can't be read, or is no longer a save for the selected game, the selection is left as it was.
+    pub fn reload_save(&mut self) -> Result<(), ReloadSaveError> {
+        let raw = fs::read(&self.save.path)?;
+        self.save.save = self.game.parse_save(&raw).map_err(ReloadSaveError::Invalid)?;
         self.save_view_state = save_view::State::new();
+        self.check_save_requirements();
         Ok(())
     }
+
+    /// Reloads the assets if the language game data should be shown in has changed, e.g. because the user changed it in the settings.
+    pub fn update_game_data_language(&mut self, override_language: Option<&unic_langid::LanguageIdentifier>) {
+        let metadata = self.patch.as_ref().map(|(_, _, metadata)| metadata);
+        let language = game_data_language(override_language, self.game, metadata);
+        if language == self.game_data_language {
+            return;
+        }
+        self.assets = load_assets(self.game, &self.rom, self.save.save.as_raw_wram(), metadata, &language).ok();
+        self.save_view_state.clear_texture_cache();
+        self.game_data_language = language;
+    }
 }
 
 pub struct State {
@@ -69,6 +207,9 @@ pub struct State {
     pub session: std::sync::Arc<parking_lot::Mutex<Option<session::Session>>>,
     selection: Option<Selection>,
     pub steal_input: Option<steal_input_window::State>,
+    pub input_latency_tester: Option<inputlatency::Tester>,
+    input_latency_monitor: std::sync::Arc<parking_lot::Mutex<inputlatency::Monitor>>,
+    audio_device_names: std::sync::Arc<parking_lot::Mutex<Vec<String>>>,
     roms_scanner: rom::Scanner,
     saves_scanner: save::Scanner,
     patches_scanner: patch::Scanner,
@@ -78,16 +219,29 @@ pub struct State {
     emu_tps_counter: std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
     main_view: main_view::State,
     show_escape_window: Option<escape_window::State>,
+    crash_report_window: crash_report_window::State,
+    show_save_changes: Option<save_changes_window::State>,
     show_settings: Option<settings_window::State>,
+    match_summary: Option<match_summary_window::State>,
     replay_dump_windows: replay_dump_windows::State,
+    log_viewer: Option<log_viewer_window::State>,
     clipboard: arboard::Clipboard,
     font_data: std::collections::BTreeMap<String, egui::FontData>,
     font_families: FontFamilies,
-    themes: Themes,
+    font_fallbacks: fonts::Fallbacks,
+    themes: theme::Themes,
     current_language: Option<unic_langid::LanguageIdentifier>,
     session_view: Option<session_view::State>,
     welcome: Option<welcome::State>,
     discord_client: discord::Client,
+    broadcast_server: broadcast::Server,
+    notifications: notifications::State,
+    replay_uploader: replay::upload::Uploader,
+    /// Hotkeys are ignored and local sessions paused while this is false.
+    window_focused: bool,
+    window_title: window_title::State,
+    /// Set when the window was asked to close but the user still has to decide what to do with a save's changes first.
+    exit_requested: bool,
 }
 
 impl State {
@@ -95,12 +249,16 @@ impl State {
         ctx: &egui::Context,
         config: std::sync::Arc<parking_lot::RwLock<config::Config>>,
         discord_client: discord::Client,
+        broadcast_server: broadcast::Server,
         audio_binder: audio::LateBinder,
         fps_counter: std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
         emu_tps_counter: std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
         roms_scanner: rom::Scanner,
         saves_scanner: save::Scanner,
         patches_scanner: patch::Scanner,
+        input_latency_monitor: std::sync::Arc<parking_lot::Mutex<inputlatency::Monitor>>,
+        audio_device_names: std::sync::Arc<parking_lot::Mutex<Vec<String>>>,
+        invite: Option<linkcode::Invite>,
     ) -> Self {
         let font_families = FontFamilies {
             latn: FontFamily {
@@ -159,6 +317,12 @@ impl State {
         .into();
         ctx.set_style(style);
 
+        let crash_report_window = crash_report_window::State::new(&config.read().crash_reports_path());
+
+        let mut themes = theme::Themes::new();
+        themes.load_user_themes(&config.read().themes_path());
+        let replay_uploader = replay::upload::Uploader::new(config.read().replay_upload_queue_path(), config.clone());
+
         Self {
             config,
             session: std::sync::Arc::new(parking_lot::Mutex::new(None)),
@@ -167,16 +331,28 @@ impl State {
             roms_scanner,
             saves_scanner,
             patches_scanner,
-            main_view: main_view::State::new(),
+            main_view: main_view::State::new(
+                invite,
+                config.read().opponents_path(),
+                config.read().save_patch_history_path(),
+                config.read().save_registry_path(),
+            ),
             audio_binder,
             fps_counter,
             emu_tps_counter,
             steal_input: None,
+            input_latency_tester: None,
+            input_latency_monitor,
+            audio_device_names,
             show_settings: None,
             show_escape_window: None,
+            crash_report_window,
+            show_save_changes: None,
+            match_summary: None,
             session_view: None,
             welcome: None,
             replay_dump_windows: replay_dump_windows::State::new(),
+            log_viewer: None,
             clipboard: arboard::Clipboard::new().unwrap(),
             font_data: std::collections::BTreeMap::from([
                 (
@@ -205,29 +381,63 @@ impl State {
                 ),
             ]),
             font_families,
-            themes: Themes {
-                light: {
-                    let mut visuals = egui::style::Visuals::light();
-                    visuals.selection.bg_fill = egui::Color32::from_rgb(0x4c, 0xaf, 0x50);
-                    visuals.selection.stroke.color = egui::Color32::BLACK;
-                    visuals
-                },
-                dark: {
-                    let mut visuals = egui::style::Visuals::dark();
-                    visuals.selection.bg_fill = egui::Color32::from_rgb(0x4c, 0xaf, 0x50);
-                    visuals.selection.stroke.color = egui::Color32::WHITE;
-                    visuals
-                },
-            },
+            font_fallbacks: fonts::Fallbacks::new(),
+            themes,
             current_language: None,
             discord_client,
+            broadcast_server,
+            notifications: notifications::State::new(),
+            replay_uploader,
+            window_focused: true,
+            window_title: window_title::State::new(),
+            exit_requested: false,
         }
     }
-}
 
-struct Themes {
-    light: egui::style::Visuals,
-    dark: egui::style::Visuals,
+    pub fn handle_audio_output_event(&mut self, language: &unic_langid::LanguageIdentifier, event: audio::OutputEvent) {
+        self.notifications.toast(match event {
+            audio::OutputEvent::DeviceLost => i18n::LOCALES.lookup(language, "notification-audio-device-lost"),
+            audio::OutputEvent::DeviceRestored(name) => i18n::LOCALES.lookup_with_args(
+                language,
+                "notification-audio-device-restored",
+                &std::collections::HashMap::from([(
+                    "device",
+                    name.unwrap_or_else(|| {
+                        i18n::LOCALES.lookup(language, "notification-audio-device-restored.default")
+                    })
+                    .into(),
+                )]),
+            ),
+        });
+    }
+
+    pub fn set_window_focused(&mut self, focused: bool) {
+        self.window_focused = focused;
+        self.notifications.set_window_focused(focused);
+        repaint::set_window_focused(focused);
+    }
+
+    /// Ends any session in progress ahead of the window closing, applying the write-back policy to its save.
+    ///
+    /// Returns false if the user is being asked what to do with the save's changes, in which case the window should stay open until [`State::ready_to_exit`] says otherwise.
+    pub fn request_exit(&mut self, config: &config::Config) -> bool {
+        if let Some(session) = self.session.lock().take() {
+            save_changes_window::end_session(
+                session,
+                config.save_write_back_policy,
+                &config.language,
+                &mut self.show_save_changes,
+                &mut self.selection,
+            );
+        }
+        self.exit_requested = self.show_save_changes.is_some();
+        !self.exit_requested
+    }
+
+    /// Whether an exit held up by asking about a save's changes can now go ahead.
+    pub fn ready_to_exit(&self) -> bool {
+        self.exit_requested && self.show_save_changes.is_none()
+    }
 }
 
 pub struct FontFamily {
@@ -254,7 +464,7 @@ impl FontFamilies {
         }
     }
 
-    pub fn raw_for_language(&self, lang: &unic_langid::LanguageIdentifier) -> &[u8] {
+    pub fn raw_for_language(&self, lang: &unic_langid::LanguageIdentifier) -> &'static [u8] {
         let mut lang = lang.clone();
         lang.maximize();
         match lang.script {
@@ -278,96 +488,121 @@ pub fn show(
         let mut session = state.session.lock();
         if let Some(s) = session.as_ref() {
             if s.completed() {
-                *session = None;
+                if let Some(match_summary) = match_summary_window::State::new(
+                    s,
+                    false,
+                    Some(state.replay_uploader.clone()).filter(|_| config.replay_upload.is_enabled()),
+                ) {
+                    state.match_summary = Some(match_summary);
+                }
+                if let Some(s) = session.take() {
+                    s.shutdown();
+                }
+                // The framebuffer textures go with the session.
+                state.session_view = None;
             }
         }
     }
 
-    if state.current_language.as_ref() != Some(&config.language) {
-        let mut language = config.language.clone();
-        language.maximize();
+    let language_changed = state.current_language.as_ref() != Some(&config.language);
+    if language_changed {
+        fonts::request_language(&config.language);
+    }
+    let fallbacks_changed = state.font_fallbacks.update();
+
+    if language_changed || fallbacks_changed {
+        let primary_font = fonts::Script::for_language(&config.language)
+            .filter(|script| *script != fonts::Script::Kore)
+            .map(|script| script.font_name())
+            .unwrap_or("NotoSans-Regular");
+
+        // Fallbacks go after the primary font, such that e.g. kanji are drawn in the Japanese style if the UI is in Japanese.
+        let mut fallbacks = vec!["NotoSans-Regular".to_string()];
+        fallbacks.extend(state.font_fallbacks.font_names());
+        fallbacks.push("NotoEmoji-Regular".to_string());
 
-        let primary_font = match language.script {
-            Some(s) if s == unic_langid::subtags::Script::from_str("Jpan").unwrap() => "NotoSansJP-Regular",
-            Some(s) if s == unic_langid::subtags::Script::from_str("Hans").unwrap() => "NotoSansSC-Regular",
-            Some(s) if s == unic_langid::subtags::Script::from_str("Hant").unwrap() => "NotoSansTC-Regular",
-            _ => "NotoSans-Regular",
+        let stack = |primary_font: &str| {
+            let mut stack = vec![primary_font.to_string()];
+            stack.extend(fallbacks.iter().filter(|name| *name != primary_font).cloned());
+            stack
         };
 
-        let proportional = vec![
-            primary_font.to_string(),
-            "NotoSans-Regular".to_string(),
-            "NotoSansJP-Regular".to_string(),
-            "NotoSansSC-Regular".to_string(),
-            "NotoSansTC-Regular".to_string(),
-            "NotoEmoji-Regular".to_string(),
-        ];
+        let proportional = stack(primary_font);
 
         let mut monospace = vec!["NotoSansMono-Regular".to_string()];
         monospace.extend(proportional.clone());
 
+        let mut font_data = state.font_data.clone();
+        state.font_fallbacks.add_font_data(&mut font_data);
+
         ctx.set_fonts(egui::FontDefinitions {
-            font_data: state.font_data.clone(),
+            font_data,
             families: std::collections::BTreeMap::from([
                 (egui::FontFamily::Proportional, proportional),
                 (egui::FontFamily::Monospace, monospace),
-                (
-                    state.font_families.jpan.egui.clone(),
-                    vec!["NotoSansJP-Regular".to_string()],
-                ),
-                (
-                    state.font_families.hans.egui.clone(),
-                    vec!["NotoSansSC-Regular".to_string()],
-                ),
-                (
-                    state.font_families.hant.egui.clone(),
-                    vec!["NotoSansTC-Regular".to_string()],
-                ),
-                (
-                    state.font_families.latn.egui.clone(),
-                    vec!["NotoSans-Regular".to_string()],
-                ),
+                (state.font_families.jpan.egui.clone(), stack("NotoSansJP-Regular")),
+                (state.font_families.hans.egui.clone(), stack("NotoSansSC-Regular")),
+                (state.font_families.hant.egui.clone(), stack("NotoSansTC-Regular")),
+                (state.font_families.latn.egui.clone(), stack("NotoSans-Regular")),
             ]),
         });
-        state.current_language = Some(config.language.clone());
-        log::info!("language was changed to {}", state.current_language.as_ref().unwrap());
+
+        if language_changed {
+            state.current_language = Some(config.language.clone());
+            log::info!("language was changed to {}", state.current_language.as_ref().unwrap());
+        }
+        ctx.request_repaint();
     }
 
-    ctx.set_visuals(match config.theme {
-        config::Theme::System => match dark_light::detect() {
-            dark_light::Mode::Light => state.themes.light.clone(),
-            dark_light::Mode::Dark => state.themes.dark.clone(),
-        },
-        config::Theme::Light => state.themes.light.clone(),
-        config::Theme::Dark => state.themes.dark.clone(),
-    });
+    theme::apply(ctx, state.themes.resolve(&config.theme));
+    save_view::texture_cache::set_capacity(config.texture_cache_capacity);
 
-    if config.nickname.is_none() {
-        welcome::show(
+    if state.welcome.is_none() && welcome::is_needed(config, &state.roms_scanner) {
+        state.welcome = Some(welcome::State::new(config));
+    }
+
+    if let Some(welcome) = state.welcome.as_mut() {
+        if welcome::show(
             ctx,
             &state.font_families,
             config,
             state.roms_scanner.clone(),
             state.saves_scanner.clone(),
-            state.welcome.get_or_insert_with(|| welcome::State::new()),
-        );
-        return;
-    } else {
-        state.welcome = None;
+            state.patches_scanner.clone(),
+            &state.audio_binder,
+            &state.audio_device_names.lock(),
+            &mut state.steal_input,
+            &state.input_latency_monitor,
+            &mut state.input_latency_tester,
+            welcome,
+        ) {
+            state.welcome = None;
+        } else {
+            steal_input_window::show(ctx, &config.language, &mut state.steal_input);
+            input_latency_window::show(ctx, config, &mut state.input_latency_tester);
+            return;
+        }
     }
 
     settings_window::show(
         ctx,
         &mut state.show_settings,
         &state.font_families,
+        &mut state.themes,
         config,
         state.roms_scanner.clone(),
         state.saves_scanner.clone(),
         state.patches_scanner.clone(),
         window,
         &mut state.steal_input,
+        &state.input_latency_monitor,
+        &mut state.input_latency_tester,
+        &state.audio_device_names.lock(),
+        &mut state.welcome,
+        &mut state.log_viewer,
     );
     steal_input_window::show(ctx, &config.language, &mut state.steal_input);
+    input_latency_window::show(ctx, config, &mut state.input_latency_tester);
     escape_window::show(
         ctx,
         state.session.clone(),
@@ -375,30 +610,74 @@ pub fn show(
         &mut state.show_escape_window,
         &config.language,
         &mut state.show_settings,
+        config.save_write_back_policy,
+        &mut state.show_save_changes,
+        &mut state.match_summary,
+        Some(state.replay_uploader.clone()).filter(|_| config.replay_upload.is_enabled()),
     );
+    save_changes_window::show(
+        ctx,
+        &mut state.show_save_changes,
+        &config.language,
+        &mut state.selection,
+    );
+    crash_report_window::show(ctx, &mut state.crash_report_window, &config.language);
     replay_dump_windows::show(
         ctx,
         &mut state.replay_dump_windows,
         &config.language,
         &config.replays_path(),
+        config.bios_path.as_deref(),
     );
+    log_viewer_window::show(ctx, &config.language, &mut state.clipboard, &mut state.log_viewer);
 
+    let mut terminate_hung_session = false;
     if let Some(session) = state.session.lock().as_ref() {
-        window.set_title(&i18n::LOCALES.lookup(&config.language, "window-title.running").unwrap());
-        session_view::show(
+        if !ctx.input().raw.dropped_files.is_empty() {
+            state
+                .notifications
+                .toast(i18n::LOCALES.lookup(&config.language, "file-drop-ignored-session"));
+        }
+        if state.window_focused && input::is_any_pressed(&config.input_mapping.toggle_streamer_mode, input_state) {
+            config.streamer_mode = !config.streamer_mode;
+            state.notifications.toast(i18n::LOCALES.lookup(
+                &config.language,
+                if config.streamer_mode {
+                    "settings-streamer-mode.enabled"
+                } else {
+                    "settings-streamer-mode.disabled"
+                },
+            ));
+        }
+        if let session::Mode::PvP(pvp) = session.mode() {
+            // A new match makes the last one's summary moot.
+            state.match_summary = None;
+            pvp.set_custom_screen_covered(config.streamer_mode);
+        }
+        terminate_hung_session = session_view::show(
             ctx,
             &config.language,
+            config.game_data_language.as_ref(),
             &mut state.clipboard,
             &state.font_families,
             input_state,
             &config.input_mapping,
+            &config.assist,
             session,
             &config.video_filter,
             config.integer_scaling,
+            config.frame_interpolation,
             config.volume,
             config.max_scale,
             config.show_own_setup,
+            config.show_practice_overlay,
+            state.window_focused,
+            config.pause_on_focus_loss,
             &config.crashstates_path(),
+            &config.crash_reports_path(),
+            std::time::Duration::from_secs(config.hang_timeout_secs as u64),
+            &config.logs_path(),
+            &config.replays_path(),
             &state.last_mouse_motion_time,
             &mut state.show_escape_window,
             state.fps_counter.clone(),
@@ -409,7 +688,6 @@ pub fn show(
         );
     } else {
         state.session_view = None;
-        window.set_title(&i18n::LOCALES.lookup(&config.language, "window-title").unwrap());
         main_view::show(
             ctx,
             &state.font_families,
@@ -419,6 +697,7 @@ pub fn show(
             &mut state.show_settings,
             &mut state.replay_dump_windows,
             &mut state.clipboard,
+            input_state,
             state.audio_binder.clone(),
             state.roms_scanner.clone(),
             state.saves_scanner.clone(),
@@ -428,7 +707,69 @@ pub fn show(
             &mut state.selection,
             &mut state.main_view,
             &mut state.discord_client,
+            state.broadcast_server.clone(),
+            &mut state.notifications,
             updater,
         );
+        if let Some(link_code) = match_summary_window::show(
+            ctx,
+            &config.language,
+            &mut state.match_summary,
+            state.main_view.opponents(),
+            state.session.clone(),
+            state.audio_binder.clone(),
+            state.emu_tps_counter.clone(),
+            config.bios_path.as_deref(),
+        ) {
+            state.main_view.rematch(link_code);
+        }
     }
+
+    // Summing up the match would mean waiting on the hung emulator, so it isn't done. The save is still written back from what was last seen of it.
+    if terminate_hung_session {
+        if let Some(session) = state.session.lock().take() {
+            log::warn!("force-terminating hung session");
+            save_changes_window::end_session(
+                session,
+                config.save_write_back_policy,
+                &config.language,
+                &mut state.show_save_changes,
+                &mut state.selection,
+            );
+        }
+    }
+
+    for event in state.replay_uploader.take_events() {
+        state.notifications.toast(event.message(&config.language));
+    }
+    notifications::show(ctx, &mut state.notifications);
+
+    let (activity, paused) = if let Some(session) = state.session.lock().as_ref() {
+        (window_title::Activity::for_session(session), session.is_paused())
+    } else if let Some(link_code) = state.main_view.link_code_in_progress() {
+        (
+            window_title::Activity::Lobby {
+                link_code: Some(link_code).filter(|_| !config.streamer_mode),
+            },
+            false,
+        )
+    } else {
+        (window_title::Activity::Idle, false)
+    };
+    window_title::apply(
+        window,
+        &config.language,
+        &mut state.window_title,
+        window_title::Title {
+            activity,
+            paused,
+            progress: if let Some(fraction) = state.replay_dump_windows.progress() {
+                Some(window_title::Progress::Fraction(fraction))
+            } else if state.patches_scanner.is_scanning() {
+                Some(window_title::Progress::Indeterminate)
+            } else {
+                None
+            },
+        },
+    );
 }
@@ -0,0 +1,209 @@
+use crate::{config, gui, i18n, patch, rom, save};
+
+use config::profile;
+
+pub enum State {
+    Export {
+        sections: std::collections::HashSet<profile::Section>,
+        error: Option<String>,
+    },
+    Import(Result<profile::Import, String>),
+}
+
+impl State {
+    pub fn export() -> Self {
+        Self::Export {
+            sections: profile::Section::ALL
+                .iter()
+                .filter(|section| section.is_exported_by_default())
+                .cloned()
+                .collect(),
+            error: None,
+        }
+    }
+
+    /// Reads a profile for previewing against the current config.
+    pub fn import(config: &config::Config, path: &std::path::Path) -> Self {
+        Self::Import(
+            std::fs::read_to_string(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|contents| profile::Import::new(config, &contents))
+                .map_err(|e| {
+                    log::error!("failed to read config profile {}: {:?}", path.display(), e);
+                    e.to_string()
+                }),
+        )
+    }
+}
+
+fn section_label(language: &unic_langid::LanguageIdentifier, section: profile::Section) -> String {
+    i18n::LOCALES.lookup(language, &format!("settings-profile-section-{}", section.key()))
+}
+
+/// Shows a setting's value compactly, since they're only being compared.
+fn describe_value(value: &serde_json::Value) -> String {
+    const MAX_LEN: usize = 60;
+    let s = match value {
+        serde_json::Value::Null => "—".to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+    if s.chars().count() > MAX_LEN {
+        format!("{}…", s.chars().take(MAX_LEN).collect::<String>())
+    } else {
+        s
+    }
+}
+
+pub fn show(
+    ctx: &egui::Context,
+    state: &mut Option<State>,
+    config: &mut config::Config,
+    roms_scanner: rom::Scanner,
+    saves_scanner: save::Scanner,
+    patches_scanner: patch::Scanner,
+) {
+    let mut open = state.is_some();
+    let mut close = false;
+    let mut apply = false;
+    egui::Window::new(i18n::LOCALES.lookup(&config.language, "settings-profile"))
+        .id(egui::Id::new("config-profile-window"))
+        .open(&mut open)
+        .collapsible(false)
+        .show(ctx, |ui| match state.as_mut().unwrap() {
+            State::Export { sections, error } => {
+                ui.label(i18n::LOCALES.lookup(&config.language, "settings-profile-export.description"));
+                for section in profile::Section::ALL {
+                    let mut checked = sections.contains(section);
+                    if ui
+                        .checkbox(&mut checked, section_label(&config.language, *section))
+                        .changed()
+                    {
+                        if checked {
+                            sections.insert(*section);
+                        } else {
+                            sections.remove(section);
+                        }
+                    }
+                }
+
+                if let Some(error) = error.as_ref() {
+                    ui.colored_label(gui::theme::colors(ui.ctx()).warning, error.as_str());
+                }
+
+                if ui
+                    .add_enabled(
+                        !sections.is_empty(),
+                        egui::Button::new(i18n::LOCALES.lookup(&config.language, "settings-profile-export.run")),
+                    )
+                    .clicked()
+                {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("TOML", &["toml"])
+                        .set_file_name("tango-profile.toml")
+                        .save_file()
+                    {
+                        match profile::export(config, sections)
+                            .and_then(|contents| Ok(std::fs::write(&path, contents)?))
+                        {
+                            Ok(()) => {
+                                close = true;
+                            }
+                            Err(e) => {
+                                log::error!("failed to export config profile: {:?}", e);
+                                *error = Some(e.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+            State::Import(Err(error)) => {
+                ui.colored_label(
+                    gui::theme::colors(ui.ctx()).warning,
+                    i18n::LOCALES.lookup_with_args(
+                        &config.language,
+                        "settings-profile-import.error",
+                        &std::collections::HashMap::from([("error", error.as_str().into())]),
+                    ),
+                );
+            }
+            State::Import(Ok(import)) => {
+                if import.changes.is_empty() {
+                    ui.label(i18n::LOCALES.lookup(&config.language, "settings-profile-import.no-changes"));
+                } else {
+                    ui.label(i18n::LOCALES.lookup(&config.language, "settings-profile-import.description"));
+                    egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        let colors = gui::theme::colors(ui.ctx());
+                        for section in import.sections.iter() {
+                            let changes = import
+                                .changes
+                                .iter()
+                                .filter(|change| change.section == *section)
+                                .collect::<Vec<_>>();
+                            if changes.is_empty() {
+                                continue;
+                            }
+                            ui.strong(section_label(&config.language, *section));
+                            egui::Grid::new(("config-profile-changes", section.key()))
+                                .num_columns(3)
+                                .show(ui, |ui| {
+                                    for change in changes {
+                                        ui.monospace(&change.path);
+                                        ui.colored_label(
+                                            colors.warning,
+                                            format!("− {}", describe_value(&change.current)),
+                                        );
+                                        ui.colored_label(
+                                            colors.ready,
+                                            format!("+ {}", describe_value(&change.imported)),
+                                        );
+                                        ui.end_row();
+                                    }
+                                });
+                        }
+                    });
+                }
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !import.changes.is_empty(),
+                            egui::Button::new(i18n::LOCALES.lookup(&config.language, "settings-profile-import.apply")),
+                        )
+                        .clicked()
+                    {
+                        apply = true;
+                    }
+                    if ui
+                        .button(i18n::LOCALES.lookup(&config.language, "settings-profile-import.cancel"))
+                        .clicked()
+                    {
+                        close = true;
+                    }
+                });
+            }
+        });
+
+    if apply {
+        if let Some(State::Import(Ok(import))) = state.take() {
+            let mut imported = import.into_config();
+            // The data path is moved the usual way, so everything that's expected in it gets created.
+            let data_path = std::mem::replace(&mut imported.data_path, config.data_path.clone());
+            *config = imported;
+            if data_path != config.data_path {
+                match config.set_data_path(data_path) {
+                    Ok(()) => {
+                        gui::rescan_all(ctx, config, roms_scanner, saves_scanner, patches_scanner);
+                    }
+                    Err(e) => {
+                        log::error!("failed to change data path: {:?}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    if !open || close {
+        *state = None;
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{crashreport, i18n};
+
+pub struct State {
+    reports: Vec<std::path::PathBuf>,
+}
+
+impl State {
+    pub fn new(crash_reports_path: &std::path::Path) -> Self {
+        Self {
+            reports: crashreport::unseen_reports(crash_reports_path),
+        }
+    }
+}
+
+pub fn show(ctx: &egui::Context, state: &mut State, language: &unic_langid::LanguageIdentifier) {
+    let report_path = if let Some(report_path) = state.reports.last() {
+        report_path.clone()
+    } else {
+        return;
+    };
+
+    let mut dismissed = false;
+    egui::Window::new(i18n::LOCALES.lookup(language, "crash-report"))
+        .id(egui::Id::new("crash-report-window"))
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                "crash-report-description",
+                &std::collections::HashMap::from([("path", report_path.display().to_string().into())]),
+            ));
+            ui.horizontal(|ui| {
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "crash-report.reveal"))
+                    .clicked()
+                {
+                    let _ = open::that(&report_path);
+                }
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "crash-report.dismiss"))
+                    .clicked()
+                {
+                    dismissed = true;
+                }
+            });
+        });
+
+    if dismissed {
+        // Older unseen reports are from crashes that were already reported on at the time, so there's no need to bring them up again.
+        for report_path in state.reports.drain(..) {
+            if let Err(e) = crashreport::mark_seen(&report_path) {
+                log::error!("failed to mark crash report {} as seen: {:?}", report_path.display(), e);
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{game, i18n, patch, rom};
+
+enum Status {
+    Idle,
+    Creating,
+    Done(Result<String, String>),
+}
+
+pub struct State {
+    game: Option<&'static (dyn game::Game + Send + Sync)>,
+    modified_rom_path: Option<std::path::PathBuf>,
+    name: String,
+    version: String,
+    status: std::sync::Arc<parking_lot::Mutex<Status>>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            game: None,
+            modified_rom_path: None,
+            name: String::new(),
+            version: "0.1.0".to_string(),
+            status: std::sync::Arc::new(parking_lot::Mutex::new(Status::Idle)),
+        }
+    }
+}
+
+fn game_name(language: &unic_langid::LanguageIdentifier, game: &'static (dyn game::Game + Send + Sync)) -> String {
+    let (family, variant) = game.family_and_variant();
+    i18n::LOCALES.lookup(language, &format!("game-{}.variant-{}", family, variant))
+}
+
+pub fn show(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    state: &mut Option<State>,
+    roms_scanner: rom::Scanner,
+    patches_path: &std::path::Path,
+    patches_scanner: patch::Scanner,
+    patch_selection: &mut Option<String>,
+) {
+    let mut open = state.is_some();
+    let mut created = None;
+    egui::Window::new(i18n::LOCALES.lookup(language, "create-patch"))
+        .id(egui::Id::new("create-patch-window"))
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            let state = if let Some(state) = state.as_mut() {
+                state
+            } else {
+                return;
+            };
+
+            let roms = roms_scanner.read();
+            let version = semver::Version::parse(state.version.trim()).ok();
+            let is_creating = matches!(*state.status.lock(), Status::Creating);
+
+            ui.add_enabled_ui(!is_creating, |ui| {
+                egui::Grid::new("create-patch-window-grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.strong(i18n::LOCALES.lookup(language, "create-patch-base-game"));
+                        egui::ComboBox::from_id_source("create-patch-window-base-game")
+                            .width(250.0)
+                            .selected_text(state.game.map(|game| game_name(language, game)).unwrap_or_default())
+                            .show_ui(ui, |ui| {
+                                for game in game::sorted_all_games(language) {
+                                    if !roms.by_game.contains_key(&game) {
+                                        continue;
+                                    }
+                                    ui.selectable_value(&mut state.game, Some(game), game_name(language, game));
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.strong(i18n::LOCALES.lookup(language, "create-patch-modified-rom"));
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button(i18n::LOCALES.lookup(language, "create-patch-modified-rom.choose"))
+                                .clicked()
+                            {
+                                if let Some(path) = rfd::FileDialog::new().add_filter("GBA ROM", &["gba"]).pick_file() {
+                                    state.modified_rom_path = Some(path);
+                                }
+                            }
+                            if let Some(path) = state.modified_rom_path.as_ref() {
+                                ui.label(path.display().to_string());
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.strong(i18n::LOCALES.lookup(language, "create-patch-name"));
+                        ui.text_edit_singleline(&mut state.name);
+                        ui.end_row();
+
+                        ui.strong(i18n::LOCALES.lookup(language, "create-patch-version"));
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut state.version);
+                            if version.is_none() {
+                                ui.colored_label(
+                                    egui::Color32::RED,
+                                    i18n::LOCALES.lookup(language, "create-patch-version.invalid"),
+                                );
+                            }
+                        });
+                        ui.end_row();
+                    });
+
+                ui.separator();
+
+                let can_create =
+                    state.game.is_some() && state.modified_rom_path.is_some() && !state.name.trim().is_empty();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            can_create && version.is_some(),
+                            egui::Button::new(i18n::LOCALES.lookup(language, "create-patch-create")),
+                        )
+                        .clicked()
+                    {
+                        let game = state.game.unwrap();
+                        let base_rom = roms.by_game.get(&game).cloned().unwrap_or_default();
+                        let modified_rom_path = state.modified_rom_path.clone().unwrap();
+                        let name = state.name.trim().to_string();
+                        let version = version.clone().unwrap();
+                        let patches_path = patches_path.to_path_buf();
+                        let patches_scanner = patches_scanner.clone();
+                        let status = state.status.clone();
+                        let egui_ctx = ui.ctx().clone();
+                        *status.lock() = Status::Creating;
+                        tokio::task::spawn_blocking(move || {
+                            let result = (|| {
+                                let modified_rom = std::fs::read(&modified_rom_path)?;
+                                patch::create_version_from_rom(
+                                    &patches_path,
+                                    &name,
+                                    &version,
+                                    game,
+                                    &base_rom,
+                                    &modified_rom,
+                                )
+                            })();
+                            if let Err(e) = result.as_ref() {
+                                log::error!("failed to create patch: {:?}", e);
+                            }
+                            patches_scanner.rescan(move || patch::scan(&patches_path).ok());
+                            *status.lock() = Status::Done(result.map(|_| name).map_err(|e| e.to_string()));
+                            egui_ctx.request_repaint();
+                        });
+                    }
+                    if is_creating {
+                        ui.spinner();
+                    }
+                });
+            });
+
+            match &*state.status.lock() {
+                Status::Done(Ok(name)) => {
+                    created = Some(name.clone());
+                }
+                Status::Done(Err(e)) => {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        i18n::LOCALES.lookup_with_args(
+                            language,
+                            "create-patch-error",
+                            &std::collections::HashMap::from([("error", e.clone().into())]),
+                        ),
+                    );
+                }
+                _ => {}
+            }
+        });
+
+    if let Some(name) = created {
+        *patch_selection = Some(name);
+        open = false;
+    }
+
+    if !open {
+        *state = None;
+    }
+}
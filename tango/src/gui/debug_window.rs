@@ -1,19 +1,36 @@
-use fluent_templates::Loader;
+use crate::{gui, i18n, session};
 
-use crate::{i18n, session};
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Tab {
+    Memory,
+    Fonts,
+}
 
 pub struct State {
+    tab: Tab,
     jump_to: String,
 }
 
 impl State {
     pub fn new() -> Self {
         Self {
+            tab: Tab::Memory,
             jump_to: "".to_string(),
         }
     }
 }
 
+fn show_fonts(ui: &mut egui::Ui) {
+    egui::Grid::new("debug-fonts-grid").num_columns(2).show(ui, |ui| {
+        for (script, text) in gui::fonts::TEST_STRINGS {
+            gui::fonts::request(text);
+            ui.strong(*script);
+            ui.label(*text);
+            ui.end_row();
+        }
+    });
+}
+
 pub fn show(
     ctx: &egui::Context,
     language: &unic_langid::LanguageIdentifier,
@@ -21,17 +38,27 @@ pub fn show(
     state: &mut Option<State>,
 ) {
     let mut open = state.is_some();
-    egui::Window::new(format!("🪲 {}", i18n::LOCALES.lookup(language, "debug").unwrap()))
+    egui::Window::new(format!("🪲 {}", i18n::LOCALES.lookup(language, "debug")))
         .id(egui::Id::new("debug"))
         .open(&mut open)
         .show(ctx, |ui| {
+            let state = state.as_mut().unwrap();
+
             ui.horizontal(|ui| {
-                let _ = ui.selectable_label(true, "Memory");
+                ui.selectable_value(&mut state.tab, Tab::Memory, "Memory");
+                ui.selectable_value(&mut state.tab, Tab::Fonts, "Fonts");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    // Anything above one here (plus one per shadow or replay being exported) is a core some ended session didn't let go of.
+                    ui.monospace(format!("Live cores: {}", mgba::core::live_count()));
+                });
             });
 
             ui.separator();
 
-            let state = state.as_mut().unwrap();
+            if state.tab == Tab::Fonts {
+                show_fonts(ui);
+                return;
+            }
 
             let mut jumping = false;
             ui.horizontal(|ui| {
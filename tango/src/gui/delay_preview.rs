@@ -0,0 +1,147 @@
+use crate::{gui, i18n, input, session};
+
+const AREA_SIZE: egui::Vec2 = egui::Vec2::new(240.0, 80.0);
+const CURSOR_SIZE: f32 = 8.0;
+/// How far the cursor moves per frame while a direction is held.
+const CURSOR_SPEED: f32 = 2.0;
+
+/// The buttons shown in the preview, with the labels they're shown with.
+const BUTTONS: &[(u32, &str)] = &[
+    (mgba::input::keys::LEFT, "←"),
+    (mgba::input::keys::UP, "↑"),
+    (mgba::input::keys::DOWN, "↓"),
+    (mgba::input::keys::RIGHT, "→"),
+    (mgba::input::keys::A, "A"),
+    (mgba::input::keys::B, "B"),
+    (mgba::input::keys::L, "L"),
+    (mgba::input::keys::R, "R"),
+];
+
+/// Lets the player feel out an input delay before playing with it.
+///
+/// Inputs go through a queue as long as the delay, ticking at the emulator's frame rate, and whatever comes out the other end moves a cursor around. This is entirely local: nothing here touches the netplay input path.
+pub struct State {
+    queue: std::collections::VecDeque<u32>,
+    delayed_keys: u32,
+    last_tick: std::time::Instant,
+    cursor: egui::Vec2,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            queue: std::collections::VecDeque::new(),
+            delayed_keys: 0,
+            last_tick: std::time::Instant::now(),
+            cursor: AREA_SIZE / 2.0,
+        }
+    }
+
+    fn update(&mut self, keys: u32, delay: u32) {
+        let tick = std::time::Duration::from_secs_f32(1.0 / session::EXPECTED_FPS);
+        let now = std::time::Instant::now();
+
+        // Don't try to catch up on frames that went by while nothing was being drawn.
+        if now.duration_since(self.last_tick) > tick * 10 {
+            self.last_tick = now - tick;
+        }
+
+        while now.duration_since(self.last_tick) >= tick {
+            self.last_tick += tick;
+            self.queue.push_back(keys);
+            while self.queue.len() > delay as usize {
+                self.delayed_keys = self.queue.pop_front().unwrap();
+                self.step();
+            }
+        }
+    }
+
+    fn step(&mut self) {
+        let keys = self.delayed_keys;
+        let mut direction = egui::Vec2::ZERO;
+        if keys & mgba::input::keys::LEFT != 0 {
+            direction.x -= 1.0;
+        }
+        if keys & mgba::input::keys::RIGHT != 0 {
+            direction.x += 1.0;
+        }
+        if keys & mgba::input::keys::UP != 0 {
+            direction.y -= 1.0;
+        }
+        if keys & mgba::input::keys::DOWN != 0 {
+            direction.y += 1.0;
+        }
+        self.cursor = (self.cursor + direction * CURSOR_SPEED).clamp(
+            egui::Vec2::splat(CURSOR_SIZE / 2.0),
+            AREA_SIZE - egui::Vec2::splat(CURSOR_SIZE / 2.0),
+        );
+    }
+}
+
+fn show_buttons(ui: &mut egui::Ui, label: String, keys: u32) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        for (key, text) in BUTTONS {
+            let text = egui::RichText::new(*text).monospace();
+            if keys & key != 0 {
+                ui.label(text.strong().color(ui.visuals().strong_text_color()));
+            } else {
+                ui.label(text.weak());
+            }
+        }
+    });
+}
+
+/// Shows the preview for the given delay, next to the delay the connection to the opponent suggests.
+pub fn show(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    state: &mut State,
+    input_state: &input::State,
+    input_mapping: &input::Mapping,
+    delay: u32,
+    suggested_delay: u32,
+) {
+    let keys = input_mapping.to_mgba_keys(input_state);
+    state.update(keys, delay);
+
+    ui.group(|ui| {
+        ui.strong(i18n::LOCALES.lookup(language, "play-delay-preview"));
+        ui.horizontal(|ui| {
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                "play-delay-preview.configured",
+                &std::collections::HashMap::from([("delay", delay.into())]),
+            ));
+            ui.separator();
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                "play-delay-preview.suggested",
+                &std::collections::HashMap::from([("delay", suggested_delay.into())]),
+            ));
+        });
+        ui.weak(i18n::LOCALES.lookup(language, "play-delay-preview.description"));
+
+        show_buttons(ui, i18n::LOCALES.lookup(language, "play-delay-preview.pressed"), keys);
+        show_buttons(
+            ui,
+            i18n::LOCALES.lookup(language, "play-delay-preview.delayed"),
+            state.delayed_keys,
+        );
+
+        let (rect, _) = ui.allocate_exact_size(AREA_SIZE, egui::Sense::hover());
+        ui.painter().rect_filled(rect, 4.0, ui.visuals().extreme_bg_color);
+        ui.painter().rect_filled(
+            egui::Rect::from_center_size(rect.min + state.cursor, egui::Vec2::splat(CURSOR_SIZE)),
+            1.0,
+            if state.delayed_keys & (mgba::input::keys::A | mgba::input::keys::B) != 0 {
+                gui::theme::colors(ui.ctx()).warning
+            } else {
+                gui::theme::colors(ui.ctx()).ready
+            },
+        );
+    });
+
+    // Inputs have to be sampled every frame for the delay to be accurate.
+    ui.ctx().request_repaint();
+}
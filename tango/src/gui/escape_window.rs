@@ -1,6 +1,4 @@
-use fluent_templates::Loader;
-
-use crate::{gui, i18n, session};
+use crate::{config, gui, i18n, replay, session};
 
 pub struct State {}
 
@@ -17,6 +15,10 @@ pub fn show(
     show_escape_window: &mut Option<State>,
     language: &unic_langid::LanguageIdentifier,
     show_settings: &mut Option<gui::settings_window::State>,
+    save_write_back_policy: config::SaveWriteBackPolicy,
+    show_save_changes: &mut Option<gui::save_changes_window::State>,
+    match_summary: &mut Option<gui::match_summary_window::State>,
+    replay_uploader: Option<replay::upload::Uploader>,
 ) {
     let mut open = show_escape_window.is_some();
     egui::Window::new("")
@@ -28,21 +30,32 @@ pub fn show(
         .show(ctx, |ui| {
             ui.vertical_centered_justified(|ui| {
                 if ui
-                    .button(egui::RichText::new(i18n::LOCALES.lookup(language, "escape-settings").unwrap()).heading())
+                    .button(egui::RichText::new(i18n::LOCALES.lookup(language, "escape-settings")).heading())
                     .clicked()
                 {
                     *show_settings = Some(gui::settings_window::State::new());
                     *show_escape_window = None;
                 }
                 if ui
-                    .button(egui::RichText::new(i18n::LOCALES.lookup(language, "escape-end-game").unwrap()).heading())
+                    .button(egui::RichText::new(i18n::LOCALES.lookup(language, "escape-end-game")).heading())
                     .clicked()
                 {
-                    *session.lock() = None;
-                    // Current save file needs to be reloaded from disk.
-                    // TODO: Maybe we even need to rescan saves if region lock status changed? (e.g. EXE4 -> BN4)
-                    if let Some(selection) = selection.as_mut() {
-                        let _ = selection.reload_save();
+                    let ended_session = session.lock().take();
+                    if let Some(ended_session) = ended_session {
+                        if let Some(summary) =
+                            gui::match_summary_window::State::new(&ended_session, true, replay_uploader.clone())
+                        {
+                            *match_summary = Some(summary);
+                        }
+                        // Current save file may need to be reloaded from disk, depending on what's done with the changes.
+                        // TODO: Maybe we even need to rescan saves if region lock status changed? (e.g. EXE4 -> BN4)
+                        gui::save_changes_window::end_session(
+                            ended_session,
+                            save_write_back_policy,
+                            language,
+                            show_save_changes,
+                            selection,
+                        );
                     }
                     *show_escape_window = None;
                 }
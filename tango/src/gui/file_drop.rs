@@ -0,0 +1,547 @@
+use crate::{audio, config, fs, game, gui, i18n, patch, replay, rom, save, session, stats};
+
+/// A dropped file that's waiting on the player to say what to do with it.
+enum Prompt {
+    /// The save was opened, but isn't in the saves folder.
+    CopySave {
+        path: std::path::PathBuf,
+        game: &'static (dyn game::Game + Send + Sync),
+    },
+    CopyRom {
+        path: std::path::PathBuf,
+        game: &'static (dyn game::Game + Send + Sync),
+    },
+}
+
+/// Where to go to see what was done with a dropped file.
+pub enum Destination {
+    Play,
+    Patches,
+}
+
+/// Files dropped onto the window, gone through one at a time, and what became of them so far.
+pub struct State {
+    queue: std::collections::VecDeque<std::path::PathBuf>,
+    prompt: Option<Prompt>,
+    /// Set while a dropped patch is being installed: nothing else is looked at until that's done.
+    installing_patch: bool,
+    /// Only one replay can be watched at a time, so any others dropped along with it are turned away.
+    started_replay: bool,
+    imported: Vec<String>,
+    rejected: Vec<String>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            queue: std::collections::VecDeque::new(),
+            prompt: None,
+            installing_patch: false,
+            started_replay: false,
+            imported: vec![],
+            rejected: vec![],
+        }
+    }
+
+    pub fn enqueue(&mut self, paths: impl Iterator<Item = std::path::PathBuf>) {
+        self.queue.extend(paths);
+    }
+
+    fn import(&mut self, language: &unic_langid::LanguageIdentifier, path: &std::path::Path, text_id: &str) {
+        self.imported.push(format!(
+            "{}: {}",
+            file_name(path),
+            i18n::LOCALES.lookup(language, text_id)
+        ));
+    }
+
+    fn reject(&mut self, path: &std::path::Path, reason: String) {
+        self.rejected.push(format!("{}: {}", file_name(path), reason));
+    }
+
+    /// Tells the player what happened to everything dropped, once it's all been gone through.
+    fn summarize(&mut self, language: &unic_langid::LanguageIdentifier) -> Option<String> {
+        self.started_replay = false;
+        if self.imported.is_empty() && self.rejected.is_empty() {
+            return None;
+        }
+        let mut lines = vec![];
+        if !self.imported.is_empty() {
+            lines.push(i18n::LOCALES.lookup(language, "file-drop-imported"));
+            lines.extend(self.imported.drain(..));
+        }
+        if !self.rejected.is_empty() {
+            lines.push(i18n::LOCALES.lookup(language, "file-drop-rejected"));
+            lines.extend(self.rejected.drain(..));
+        }
+        Some(lines.join("\n"))
+    }
+}
+
+fn file_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn has_extension(path: &std::path::Path, extension: &str) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case(extension))
+        .unwrap_or(false)
+}
+
+fn game_name(language: &unic_langid::LanguageIdentifier, game: &'static (dyn game::Game + Send + Sync)) -> String {
+    let (family, variant) = game.family_and_variant();
+    i18n::LOCALES.lookup(language, &format!("game-{}.variant-{}", family, variant))
+}
+
+/// Copies a file into a folder, without replacing anything already there.
+fn copy_into(path: &std::path::Path, dir: &std::path::Path) -> Result<std::path::PathBuf, std::io::Error> {
+    let dest = dir.join(path.file_name().unwrap_or_default());
+    if fs::metadata(&dest).is_ok() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists", dest.display()),
+        ));
+    }
+    fs::create_dir_all(dir)?;
+    fs::write(&dest, fs::read(path)?)?;
+    Ok(dest)
+}
+
+fn rescan_roms(egui_ctx: &egui::Context, roms_scanner: &rom::Scanner, roms_path: &std::path::Path) {
+    tokio::task::spawn_blocking({
+        let roms_scanner = roms_scanner.clone();
+        let roms_path = roms_path.to_path_buf();
+        let egui_ctx = egui_ctx.clone();
+        move || {
+            roms_scanner.rescan_progressively(move |progress| Some(game::scan_roms(&roms_path, false, progress)));
+            egui_ctx.request_repaint();
+        }
+    });
+}
+
+fn rescan_saves(egui_ctx: &egui::Context, saves_scanner: &save::Scanner, saves_path: &std::path::Path) {
+    tokio::task::spawn_blocking({
+        let saves_scanner = saves_scanner.clone();
+        let saves_path = saves_path.to_path_buf();
+        let egui_ctx = egui_ctx.clone();
+        move || {
+            saves_scanner.rescan_progressively(move |progress| Some(save::scan_saves(&saves_path, false, progress)));
+            egui_ctx.request_repaint();
+        }
+    });
+}
+
+/// Opens a dropped save in the save viewer, asking to copy it into the saves folder if it isn't already there.
+fn open_save(
+    state: &mut State,
+    language: &unic_langid::LanguageIdentifier,
+    config: &config::Config,
+    roms_scanner: &rom::Scanner,
+    selection: &mut Option<gui::Selection>,
+    path: std::path::PathBuf,
+) -> Option<Destination> {
+    let raw = match fs::read(&path) {
+        Ok(raw) => raw,
+        Err(e) => {
+            state.reject(
+                &path,
+                i18n::LOCALES.lookup_with_args(
+                    language,
+                    "file-drop-rejected-unreadable",
+                    &std::collections::HashMap::from([("error", e.to_string().into())]),
+                ),
+            );
+            return None;
+        }
+    };
+
+    // Like when scanning the saves folder, every game gets a try at parsing it.
+    let (game, save) = if let Some((game, save)) = game::GAMES
+        .iter()
+        .find_map(|game| game.parse_save(&raw).ok().map(|save| (*game, save)))
+    {
+        (game, save)
+    } else {
+        state.reject(&path, i18n::LOCALES.lookup(language, "file-drop-rejected-not-a-save"));
+        return None;
+    };
+
+    let rom = if let Some(rom) = roms_scanner.read().by_game.get(&game) {
+        rom.clone()
+    } else {
+        state.reject(
+            &path,
+            i18n::LOCALES.lookup_with_args(
+                language,
+                "file-drop-rejected-no-rom",
+                &std::collections::HashMap::from([("game", game_name(language, game).into())]),
+            ),
+        );
+        return None;
+    };
+
+    *selection = Some(gui::Selection::new(
+        game,
+        save::ScannedSave {
+            path: path.clone(),
+            save,
+            modified: fs::metadata(&path).and_then(|metadata| metadata.modified()).ok(),
+        },
+        None,
+        rom,
+    ));
+
+    if path.starts_with(config.saves_path()) {
+        state.import(language, &path, "file-drop-imported-save");
+    } else {
+        state.prompt = Some(Prompt::CopySave { path, game });
+    }
+    Some(Destination::Play)
+}
+
+/// Starts watching a dropped replay, with the same ROM and patch it was recorded with.
+fn open_replay(
+    state: &mut State,
+    egui_ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    config: &config::Config,
+    roms_scanner: &rom::Scanner,
+    patched_roms: &patch::cache::PatchedRoms,
+    audio_binder: &audio::LateBinder,
+    emu_tps_counter: &std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
+    session: &std::sync::Arc<parking_lot::Mutex<Option<session::Session>>>,
+    path: std::path::PathBuf,
+) {
+    if state.started_replay || session.lock().is_some() {
+        state.reject(
+            &path,
+            i18n::LOCALES.lookup(language, "file-drop-rejected-already-watching"),
+        );
+        return;
+    }
+
+    let replay = match fs::open(&path).and_then(replay::Replay::decode) {
+        Ok(replay) => replay,
+        Err(e) => {
+            state.reject(
+                &path,
+                i18n::LOCALES.lookup_with_args(
+                    language,
+                    "file-drop-rejected-invalid-replay",
+                    &std::collections::HashMap::from([("error", e.to_string().into())]),
+                ),
+            );
+            return;
+        }
+    };
+
+    let game_info = if let Some(game_info) = replay
+        .metadata
+        .local_side
+        .as_ref()
+        .and_then(|side| side.game_info.as_ref())
+    {
+        game_info
+    } else {
+        state.reject(
+            &path,
+            i18n::LOCALES.lookup_with_args(
+                language,
+                "file-drop-rejected-invalid-replay",
+                &std::collections::HashMap::from([("error", "missing game info".into())]),
+            ),
+        );
+        return;
+    };
+
+    let game = if let Some(game) =
+        game::find_by_family_and_variant(game_info.rom_family.as_str(), game_info.rom_variant as u8)
+    {
+        game
+    } else {
+        state.reject(
+            &path,
+            i18n::LOCALES.lookup(language, "file-drop-rejected-unsupported-game"),
+        );
+        return;
+    };
+
+    let rom = if let Some(rom) = roms_scanner.read().by_game.get(&game) {
+        rom.clone()
+    } else {
+        state.reject(
+            &path,
+            i18n::LOCALES.lookup_with_args(
+                language,
+                "file-drop-rejected-no-rom",
+                &std::collections::HashMap::from([("game", game_name(language, game).into())]),
+            ),
+        );
+        return;
+    };
+
+    let (rom, patch) = if let Some(patch_info) = game_info.patch.as_ref() {
+        let rom = semver::Version::parse(&patch_info.version)
+            .map_err(anyhow::Error::from)
+            .and_then(|version| {
+                patched_roms
+                    .get_or_apply(&rom, game, &config.patches_path(), &patch_info.name, &version)
+                    .map(|rom| (rom, version))
+            });
+        match rom {
+            Ok((rom, version)) => (rom, Some((patch_info.name.clone(), version))),
+            Err(e) => {
+                log::error!("failed to apply patch {}: {:?}", patch_info.name, e);
+                state.reject(
+                    &path,
+                    i18n::LOCALES.lookup_with_args(
+                        language,
+                        "file-drop-rejected-missing-patch",
+                        &std::collections::HashMap::from([
+                            ("patch_name", patch_info.name.clone().into()),
+                            ("patch_version", patch_info.version.clone().into()),
+                        ]),
+                    ),
+                );
+                return;
+            }
+        }
+    } else {
+        (rom, None)
+    };
+
+    state.import(language, &path, "file-drop-imported-replay");
+    state.started_replay = true;
+    tokio::task::spawn_blocking({
+        let egui_ctx = egui_ctx.clone();
+        let audio_binder = audio_binder.clone();
+        let emu_tps_counter = emu_tps_counter.clone();
+        let session = session.clone();
+        let bios_path = config.bios_path.clone();
+        let path = path.to_path_buf();
+        move || {
+            match session::Session::new_replayer(
+                audio_binder,
+                game,
+                patch,
+                &rom,
+                bios_path.as_deref(),
+                emu_tps_counter,
+                &replay,
+                &path,
+            ) {
+                Ok(s) => {
+                    *session.lock() = Some(s);
+                }
+                Err(e) => {
+                    log::error!("failed to start replay: {:?}", e);
+                }
+            }
+            egui_ctx.request_repaint();
+        }
+    });
+}
+
+/// Offers to copy a dropped ROM into the ROMs folder, if it's a game Tango supports.
+fn open_rom(state: &mut State, language: &unic_langid::LanguageIdentifier, path: std::path::PathBuf) {
+    let game = match fs::read(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|rom| game::detect(&rom))
+    {
+        Ok(game) => game,
+        Err(e) => {
+            state.reject(
+                &path,
+                i18n::LOCALES.lookup_with_args(
+                    language,
+                    "file-drop-rejected-not-a-rom",
+                    &std::collections::HashMap::from([("error", e.to_string().into())]),
+                ),
+            );
+            return;
+        }
+    };
+    state.prompt = Some(Prompt::CopyRom { path, game });
+}
+
+fn show_prompt(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    state: &mut State,
+    config: &config::Config,
+    roms_scanner: &rom::Scanner,
+    saves_scanner: &save::Scanner,
+    selection: &mut Option<gui::Selection>,
+) {
+    let (path, game, text_id) = match state.prompt.as_ref() {
+        Some(Prompt::CopySave { path, game }) => (path.clone(), *game, "file-drop-copy-save"),
+        Some(Prompt::CopyRom { path, game }) => (path.clone(), *game, "file-drop-copy-rom"),
+        None => {
+            return;
+        }
+    };
+
+    let mut confirmed = None;
+    egui::Window::new(i18n::LOCALES.lookup(language, text_id))
+        .id(egui::Id::new("file-drop-prompt-window"))
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                &format!("{}.description", text_id),
+                &std::collections::HashMap::from([
+                    ("file_name", file_name(&path).into()),
+                    ("game", game_name(language, game).into()),
+                ]),
+            ));
+            ui.horizontal(|ui| {
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "file-drop-copy.confirm"))
+                    .clicked()
+                {
+                    confirmed = Some(true);
+                }
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "file-drop-copy.cancel"))
+                    .clicked()
+                {
+                    confirmed = Some(false);
+                }
+            });
+        });
+
+    let confirmed = if let Some(confirmed) = confirmed {
+        confirmed
+    } else {
+        return;
+    };
+
+    match state.prompt.take() {
+        Some(Prompt::CopySave { path, .. }) => {
+            if !confirmed {
+                state.import(language, &path, "file-drop-imported-save");
+                return;
+            }
+            match copy_into(&path, &config.saves_path()) {
+                Ok(dest) => {
+                    if let Some(selection) = selection.as_mut().filter(|selection| selection.save.path == path) {
+                        selection.save.path = dest;
+                    }
+                    rescan_saves(ctx, saves_scanner, &config.saves_path());
+                    state.import(language, &path, "file-drop-imported-save-copied");
+                }
+                Err(e) => {
+                    log::error!("failed to copy save {}: {:?}", path.display(), e);
+                    state.reject(
+                        &path,
+                        i18n::LOCALES.lookup_with_args(
+                            language,
+                            "file-drop-rejected-copy-failed",
+                            &std::collections::HashMap::from([("error", e.to_string().into())]),
+                        ),
+                    );
+                }
+            }
+        }
+        Some(Prompt::CopyRom { path, .. }) => {
+            if !confirmed {
+                state.reject(&path, i18n::LOCALES.lookup(language, "file-drop-rejected-declined"));
+                return;
+            }
+            match copy_into(&path, &config.roms_path()) {
+                Ok(_) => {
+                    rescan_roms(ctx, roms_scanner, &config.roms_path());
+                    state.import(language, &path, "file-drop-imported-rom");
+                }
+                Err(e) => {
+                    log::error!("failed to copy rom {}: {:?}", path.display(), e);
+                    state.reject(
+                        &path,
+                        i18n::LOCALES.lookup_with_args(
+                            language,
+                            "file-drop-rejected-copy-failed",
+                            &std::collections::HashMap::from([("error", e.to_string().into())]),
+                        ),
+                    );
+                }
+            }
+        }
+        None => {}
+    }
+}
+
+/// Goes through files dropped onto the main window one at a time, asking about them as needed.
+///
+/// Returns where to go to see the file just opened, if anything was.
+pub fn show(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    state: &mut State,
+    config: &config::Config,
+    roms_scanner: &rom::Scanner,
+    saves_scanner: &save::Scanner,
+    patched_roms: &patch::cache::PatchedRoms,
+    patches_pane: &mut gui::patches_pane::State,
+    audio_binder: &audio::LateBinder,
+    emu_tps_counter: &std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
+    session: &std::sync::Arc<parking_lot::Mutex<Option<session::Session>>>,
+    selection: &mut Option<gui::Selection>,
+    notifications: &mut gui::notifications::State,
+) -> Option<Destination> {
+    show_prompt(ctx, language, state, config, roms_scanner, saves_scanner, selection);
+
+    if state.installing_patch {
+        if patches_pane.is_installing() {
+            return None;
+        }
+        state.installing_patch = false;
+    }
+
+    if state.prompt.is_some() {
+        return None;
+    }
+
+    let path = if let Some(path) = state.queue.pop_front() {
+        path
+    } else {
+        if let Some(summary) = state.summarize(language) {
+            notifications.toast(summary);
+        }
+        return None;
+    };
+    // Keep going through the rest even if nothing else happens to repaint.
+    ctx.request_repaint();
+
+    if has_extension(&path, "sav") {
+        open_save(state, language, config, roms_scanner, selection, path)
+    } else if has_extension(&path, "tangoreplay") {
+        open_replay(
+            state,
+            ctx,
+            language,
+            config,
+            roms_scanner,
+            patched_roms,
+            audio_binder,
+            emu_tps_counter,
+            session,
+            path,
+        );
+        None
+    } else if has_extension(&path, "zip") {
+        patches_pane.open_install_patch_window(Some(path.clone()));
+        state.installing_patch = true;
+        state.import(language, &path, "file-drop-imported-patch");
+        Some(Destination::Patches)
+    } else if has_extension(&path, "gba") {
+        open_rom(state, language, path);
+        None
+    } else {
+        state.reject(&path, i18n::LOCALES.lookup(language, "file-drop-rejected-unsupported"));
+        None
+    }
+}
@@ -0,0 +1,158 @@
+/// Text covering each fallback script, for checking that fallback fonts render correctly.
+pub const TEST_STRINGS: &[(&str, &str)] = &[
+    ("Latn", "The quick brown fox jumps over the lazy dog"),
+    ("Jpan", "ロックマンエグゼ 流星のロックマン 熱斗"),
+    ("Kore", "록맨 에그제 배틀 네트워크 넷토"),
+    ("Hans", "洛克人EXE 光热斗 网络对战"),
+    ("Hant", "洛克人EXE 光熱斗 網路對戰"),
+    ("Emoji", "😀 🎮 ⚡ 🔥 👍"),
+];
+
+/// Scripts that need a font other than the primary Latin font.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Script {
+    Jpan,
+    Hans,
+    Hant,
+    Kore,
+}
+
+impl Script {
+    fn of_char(c: char) -> Option<Self> {
+        match c {
+            '\u{3040}'..='\u{30ff}' | '\u{31f0}'..='\u{31ff}' | '\u{ff66}'..='\u{ff9f}' => Some(Script::Jpan),
+            '\u{1100}'..='\u{11ff}' | '\u{3130}'..='\u{318f}' | '\u{a960}'..='\u{a97f}' | '\u{ac00}'..='\u{d7ff}' => {
+                Some(Script::Kore)
+            }
+            // Ideographs on their own don't say which language they're from, so we pick Simplified Chinese: the Japanese and Traditional Chinese fonts will have been loaded already if there was anything else to go on.
+            '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' | '\u{3000}'..='\u{303f}' => {
+                Some(Script::Hans)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn for_language(lang: &unic_langid::LanguageIdentifier) -> Option<Self> {
+        let mut lang = lang.clone();
+        lang.maximize();
+        match lang.script.as_ref().map(|s| s.as_str()) {
+            Some("Jpan") => Some(Script::Jpan),
+            Some("Hans") => Some(Script::Hans),
+            Some("Hant") => Some(Script::Hant),
+            Some("Kore") => Some(Script::Kore),
+            _ => None,
+        }
+    }
+
+    pub fn font_name(&self) -> &'static str {
+        match self {
+            Script::Jpan => "NotoSansJP-Regular",
+            Script::Hans => "NotoSansSC-Regular",
+            Script::Hant => "NotoSansTC-Regular",
+            Script::Kore => "Kore-Fallback",
+        }
+    }
+}
+
+lazy_static! {
+    static ref REQUESTED: parking_lot::Mutex<std::collections::BTreeSet<Script>> =
+        parking_lot::Mutex::new(std::collections::BTreeSet::new());
+}
+
+/// Notes that some text is going to be displayed, so fallback fonts for any scripts it contains get loaded.
+///
+/// This may be called from any thread: the fonts are loaded at the start of the next frame.
+pub fn request(text: &str) {
+    let scripts = text
+        .chars()
+        .flat_map(Script::of_char)
+        .collect::<std::collections::BTreeSet<_>>();
+    if scripts.is_empty() {
+        return;
+    }
+    REQUESTED.lock().extend(scripts);
+}
+
+pub fn request_language(lang: &unic_langid::LanguageIdentifier) {
+    if let Some(script) = Script::for_language(lang) {
+        REQUESTED.lock().insert(script);
+    }
+}
+
+/// We don't ship a Korean font, so we look for one installed on the system.
+const KORE_FONT_PATHS: &[&str] = &[
+    "C:\\Windows\\Fonts\\malgun.ttf",
+    "/System/Library/Fonts/AppleSDGothicNeo.ttc",
+    "/Library/Fonts/AppleGothic.ttf",
+    "/usr/share/fonts/truetype/nanum/NanumGothic.ttf",
+    "/usr/share/fonts/nanum/NanumGothic.ttf",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+];
+
+fn load_kore_font() -> Option<egui::FontData> {
+    for path in KORE_FONT_PATHS {
+        match std::fs::read(path) {
+            Ok(raw) => {
+                log::info!("loaded korean fallback font from {}", path);
+                return Some(egui::FontData::from_owned(raw));
+            }
+            Err(_) => {
+                continue;
+            }
+        }
+    }
+    log::warn!("no korean fallback font found");
+    None
+}
+
+/// Fallback fonts that have been loaded so far. Fonts are only ever added, never removed.
+pub struct Fallbacks {
+    scripts: std::collections::BTreeSet<Script>,
+    kore: Option<egui::FontData>,
+}
+
+impl Fallbacks {
+    pub fn new() -> Self {
+        Self {
+            scripts: std::collections::BTreeSet::new(),
+            kore: None,
+        }
+    }
+
+    /// Loads fallbacks for any newly requested scripts, returning true if the font definitions need to be rebuilt.
+    pub fn update(&mut self) -> bool {
+        let requested = std::mem::take(&mut *REQUESTED.lock());
+        let mut changed = false;
+        for script in requested {
+            if !self.scripts.insert(script) {
+                continue;
+            }
+            if script == Script::Kore {
+                self.kore = load_kore_font();
+                if self.kore.is_none() {
+                    continue;
+                }
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Adds the loaded fallback font data to the given font data.
+    pub fn add_font_data(&self, font_data: &mut std::collections::BTreeMap<String, egui::FontData>) {
+        if let Some(kore) = self.kore.as_ref() {
+            font_data.insert(Script::Kore.font_name().to_string(), kore.clone());
+        }
+    }
+
+    /// The names of the loaded fallback fonts, in the order they should be tried.
+    pub fn font_names(&self) -> Vec<String> {
+        self.scripts
+            .iter()
+            .filter(|script| **script != Script::Kore || self.kore.is_some())
+            .map(|script| script.font_name().to_string())
+            .collect()
+    }
+}
@@ -0,0 +1,89 @@
+//! Banners and icons for games, so which game is which can be told at a glance.
+
+use crate::{game, gui, i18n};
+
+use gui::save_view::texture_cache;
+
+pub const BANNER_SIZE: egui::Vec2 = egui::Vec2::new(240.0, 48.0);
+
+/// Games without art are told apart by color instead, picked from these by family and variant.
+const PLACEHOLDER_COLORS: &[egui::Color32] = &[
+    egui::Color32::from_rgb(0x2e, 0x5c, 0x9e),
+    egui::Color32::from_rgb(0x9e, 0x3a, 0x2e),
+    egui::Color32::from_rgb(0x3a, 0x7d, 0x44),
+    egui::Color32::from_rgb(0x7a, 0x4a, 0x9e),
+    egui::Color32::from_rgb(0xa8, 0x70, 0x1e),
+    egui::Color32::from_rgb(0x2e, 0x84, 0x8c),
+    egui::Color32::from_rgb(0x8c, 0x2e, 0x66),
+    egui::Color32::from_rgb(0x55, 0x5f, 0x6e),
+];
+
+fn placeholder_color(game: &'static (dyn game::Game + Send + Sync)) -> egui::Color32 {
+    let (family, variant) = game.family_and_variant();
+    let hash = family
+        .bytes()
+        .chain(std::iter::once(variant))
+        .fold(0usize, |hash, b| hash.wrapping_mul(31).wrapping_add(b as usize));
+    PLACEHOLDER_COLORS[hash % PLACEHOLDER_COLORS.len()]
+}
+
+/// Stands in for a game's art if it has none, with the game written on it.
+fn show_placeholder(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    game: &'static (dyn game::Game + Send + Sync),
+    kind: game::ArtKind,
+    size: egui::Vec2,
+) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let (family, variant) = game.family_and_variant();
+    let (text, text_style) = match kind {
+        game::ArtKind::Banner => (
+            i18n::LOCALES.lookup(language, &format!("game-{}.variant-{}", family, variant)),
+            egui::TextStyle::Heading,
+        ),
+        game::ArtKind::Icon => (
+            i18n::LOCALES.lookup(language, &format!("game-{}.short", family)),
+            egui::TextStyle::Small,
+        ),
+    };
+    let painter = ui.painter().with_clip_rect(rect);
+    painter.rect_filled(rect, 2.0, placeholder_color(game));
+    painter.text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        text,
+        text_style.resolve(ui.style()),
+        egui::Color32::WHITE,
+    );
+    response
+}
+
+/// Shows a game's art, or a placeholder if it has none. Art from `override_path` is shown instead if it's there, e.g. a patch's own banner.
+pub fn show(
+    ui: &mut egui::Ui,
+    texture_cache: &mut texture_cache::TextureCache,
+    language: &unic_langid::LanguageIdentifier,
+    game: &'static (dyn game::Game + Send + Sync),
+    kind: game::ArtKind,
+    override_path: Option<&std::path::Path>,
+    size: egui::Vec2,
+) -> egui::Response {
+    let override_path =
+        override_path.filter(|path| !matches!(texture_cache.get_file(ui.ctx(), path), texture_cache::Lookup::Missing));
+    let lookup = if let Some(path) = override_path {
+        texture_cache.get_file(ui.ctx(), path)
+    } else {
+        texture_cache.get_game_art(ui.ctx(), game, kind)
+    };
+    match lookup {
+        texture_cache::Lookup::Ready(texture_handle) => ui.image(texture_handle.id(), size),
+        texture_cache::Lookup::Pending => texture_cache::placeholder(ui, size),
+        texture_cache::Lookup::Missing => show_placeholder(ui, language, game, kind, size),
+    }
+}
+
+/// How big an icon is drawn inline with text of the given height. Icons are wider than they are tall, so the placeholder's text fits.
+pub fn icon_size(row_height: f32) -> egui::Vec2 {
+    egui::Vec2::new(row_height * 2.0, row_height)
+}
@@ -0,0 +1,116 @@
+use crate::{config, i18n, inputlatency};
+
+const FLASH_SIZE: egui::Vec2 = egui::Vec2::new(320.0, 180.0);
+
+fn format_duration(d: std::time::Duration) -> String {
+    format!("{:.1} ms", d.as_secs_f32() * 1000.0)
+}
+
+/// Shows a latency test report's median, min and max.
+pub fn report_summary(language: &unic_langid::LanguageIdentifier, report: &inputlatency::Report) -> String {
+    i18n::LOCALES.lookup_with_args(
+        language,
+        "input-latency-summary",
+        &std::collections::HashMap::from([
+            ("median", format_duration(report.median()).into()),
+            ("min", format_duration(report.min()).into()),
+            ("max", format_duration(report.max()).into()),
+        ]),
+    )
+}
+
+fn show_plot(ui: &mut egui::Ui, samples: &[std::time::Duration]) {
+    egui::plot::Plot::new("input-latency-plot")
+        .height(150.0)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .include_y(0.0)
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(egui::plot::BarChart::new(
+                samples
+                    .iter()
+                    .enumerate()
+                    .map(|(i, d)| egui::plot::Bar::new((i + 1) as f64, d.as_secs_f64() * 1000.0))
+                    .collect(),
+            ));
+        });
+}
+
+pub fn show(ctx: &egui::Context, config: &mut config::Config, input_latency_tester: &mut Option<inputlatency::Tester>) {
+    let tester = if let Some(tester) = input_latency_tester.as_mut() {
+        tester
+    } else {
+        return;
+    };
+
+    if let Some(report) = tester.take_report() {
+        config
+            .input_latency_reports
+            .insert(tester.device_key().to_string(), report);
+    }
+
+    let language = &config.language;
+    let mut open = true;
+    egui::Window::new(i18n::LOCALES.lookup(language, "input-latency-test"))
+        .id(egui::Id::new("input-latency-window"))
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.strong(tester.device_name());
+
+            if let inputlatency::Phase::Done = tester.phase() {
+                if let Some(report) = config.input_latency_reports.get(tester.device_key()) {
+                    ui.label(report_summary(language, report));
+                }
+                show_plot(ui, tester.samples());
+                return;
+            }
+
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                "input-latency-test-progress",
+                &std::collections::HashMap::from([
+                    ("current", (tester.samples().len() + 1).into()),
+                    ("total", inputlatency::NUM_TRIALS.into()),
+                ]),
+            ));
+            if tester.early_presses() > 0 {
+                ui.weak(i18n::LOCALES.lookup_with_args(
+                    language,
+                    "input-latency-test-early-presses",
+                    &std::collections::HashMap::from([("count", tester.early_presses().into())]),
+                ));
+            }
+
+            let flash = tester.update();
+            let (rect, _) = ui.allocate_exact_size(FLASH_SIZE, egui::Sense::hover());
+            ui.painter().rect_filled(
+                rect,
+                4.0,
+                if flash {
+                    egui::Color32::WHITE
+                } else {
+                    egui::Color32::from_gray(0x20)
+                },
+            );
+            if !flash {
+                ui.painter().text(
+                    rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    i18n::LOCALES.lookup(language, "input-latency-test-prompt"),
+                    egui::TextStyle::Body.resolve(ui.style()),
+                    egui::Color32::from_gray(0xc0),
+                );
+            }
+
+            // The flash has to be drawn on the very next frame for the measurement to mean anything.
+            ui.ctx().request_repaint();
+        });
+
+    if !open {
+        *input_latency_tester = None;
+    }
+}
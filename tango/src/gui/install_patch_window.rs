@@ -0,0 +1,123 @@
+use crate::{i18n, patch};
+
+enum Status {
+    Idle,
+    Installing,
+    Done(Result<String, String>),
+}
+
+pub struct State {
+    archive_path: Option<std::path::PathBuf>,
+    status: std::sync::Arc<parking_lot::Mutex<Status>>,
+}
+
+impl State {
+    pub fn new(archive_path: Option<std::path::PathBuf>) -> Self {
+        Self {
+            archive_path,
+            status: std::sync::Arc::new(parking_lot::Mutex::new(Status::Idle)),
+        }
+    }
+}
+
+pub fn show(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    state: &mut Option<State>,
+    patches_path: &std::path::Path,
+    patches_scanner: patch::Scanner,
+    patch_selection: &mut Option<String>,
+) {
+    let mut open = state.is_some();
+    let mut installed = None;
+    egui::Window::new(i18n::LOCALES.lookup(language, "install-patch"))
+        .id(egui::Id::new("install-patch-window"))
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            let state = if let Some(state) = state.as_mut() {
+                state
+            } else {
+                return;
+            };
+
+            let is_installing = matches!(*state.status.lock(), Status::Installing);
+
+            ui.add_enabled_ui(!is_installing, |ui| {
+                ui.label(i18n::LOCALES.lookup(language, "install-patch-description"));
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(i18n::LOCALES.lookup(language, "install-patch-choose"))
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("ZIP", &["zip"]).pick_file() {
+                            state.archive_path = Some(path);
+                            *state.status.lock() = Status::Idle;
+                        }
+                    }
+                    if let Some(path) = state.archive_path.as_ref() {
+                        ui.label(path.display().to_string());
+                    }
+                });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            state.archive_path.is_some(),
+                            egui::Button::new(i18n::LOCALES.lookup(language, "install-patch-install")),
+                        )
+                        .clicked()
+                    {
+                        let archive_path = state.archive_path.clone().unwrap();
+                        let patches_path = patches_path.to_path_buf();
+                        let patches_scanner = patches_scanner.clone();
+                        let status = state.status.clone();
+                        let egui_ctx = ui.ctx().clone();
+                        *status.lock() = Status::Installing;
+                        tokio::task::spawn_blocking(move || {
+                            let result = patch::install_from_zip(&patches_path, &archive_path);
+                            if let Err(e) = result.as_ref() {
+                                log::error!("failed to install patch from {}: {:?}", archive_path.display(), e);
+                            }
+                            patches_scanner.rescan(move || patch::scan(&patches_path).ok());
+                            *status.lock() = Status::Done(result.map_err(|e| e.to_string()));
+                            egui_ctx.request_repaint();
+                        });
+                    }
+                    if is_installing {
+                        ui.spinner();
+                    }
+                });
+            });
+
+            match &*state.status.lock() {
+                Status::Done(Ok(name)) => {
+                    installed = Some(name.clone());
+                }
+                Status::Done(Err(e)) => {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        i18n::LOCALES.lookup_with_args(
+                            language,
+                            "install-patch-error",
+                            &std::collections::HashMap::from([("error", e.clone().into())]),
+                        ),
+                    );
+                }
+                _ => {}
+            }
+        });
+
+    if let Some(name) = installed {
+        *patch_selection = Some(name);
+        open = false;
+    }
+
+    if !open {
+        *state = None;
+    }
+}
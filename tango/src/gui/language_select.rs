@@ -1,6 +1,11 @@
 use crate::gui;
 
-pub fn show(ui: &mut egui::Ui, font_families: &gui::FontFamilies, language: &mut unic_langid::LanguageIdentifier) {
+pub fn show(
+    ui: &mut egui::Ui,
+    id_source: impl std::hash::Hash,
+    font_families: &gui::FontFamilies,
+    language: &mut unic_langid::LanguageIdentifier,
+) {
     let en_us_label = egui::RichText::new("English (United States)").family(font_families.latn.egui.clone());
     let ja_jp_label = egui::RichText::new("日本語").family(font_families.jpan.egui.clone());
     let zh_cn_label = egui::RichText::new("简体中文").family(font_families.hans.egui.clone());
@@ -12,7 +17,7 @@ pub fn show(ui: &mut egui::Ui, font_families: &gui::FontFamilies, language: &mut
     let vi_vn_label = egui::RichText::new("Tiếng Việt").family(font_families.latn.egui.clone());
     let ru_ru_label = egui::RichText::new("Русский (Россия)").family(font_families.latn.egui.clone());
 
-    egui::ComboBox::from_id_source("settings-window-general-language")
+    egui::ComboBox::from_id_source(id_source)
         .width(200.0)
         .selected_text(match &language {
             lang if lang.matches(&unic_langid::langid!("en-US"), false, true) => en_us_label.clone(),
@@ -0,0 +1,455 @@
+use crate::{config, game, gui, i18n, patch, save};
+
+/// A part of a preset that couldn't be restored.
+pub enum Problem {
+    UnknownGame(String, u8),
+    NoROM(&'static (dyn game::Game + Send + Sync)),
+    NoSave(std::path::PathBuf),
+    NoPatch(String),
+    NoPatchVersion(String, semver::Version),
+    PatchDoesNotApply(String, semver::Version),
+    UnsupportedMatchType,
+}
+
+impl Problem {
+    pub fn description(&self, language: &unic_langid::LanguageIdentifier) -> String {
+        match self {
+            Problem::UnknownGame(family, variant) => i18n::LOCALES.lookup_with_args(
+                language,
+                "play-presets-problem-unknown-game",
+                &std::collections::HashMap::from([("family", family.clone().into()), ("variant", (*variant).into())]),
+            ),
+            Problem::NoROM(game) => {
+                let (family, variant) = game.family_and_variant();
+                i18n::LOCALES.lookup_with_args(
+                    language,
+                    "play-presets-problem-no-rom",
+                    &std::collections::HashMap::from([(
+                        "game_name",
+                        i18n::LOCALES
+                            .lookup(language, &format!("game-{}.variant-{}", family, variant))
+                            .into(),
+                    )]),
+                )
+            }
+            Problem::NoSave(path) => i18n::LOCALES.lookup_with_args(
+                language,
+                "play-presets-problem-no-save",
+                &std::collections::HashMap::from([("path", path.display().to_string().into())]),
+            ),
+            Problem::NoPatch(name) => i18n::LOCALES.lookup_with_args(
+                language,
+                "play-presets-problem-no-patch",
+                &std::collections::HashMap::from([("patch_name", name.clone().into())]),
+            ),
+            Problem::NoPatchVersion(name, version) => i18n::LOCALES.lookup_with_args(
+                language,
+                "play-presets-problem-no-patch-version",
+                &std::collections::HashMap::from([
+                    ("patch_name", name.clone().into()),
+                    ("patch_version", version.to_string().into()),
+                ]),
+            ),
+            Problem::PatchDoesNotApply(name, version) => i18n::LOCALES.lookup_with_args(
+                language,
+                "play-presets-problem-patch-does-not-apply",
+                &std::collections::HashMap::from([
+                    ("patch_name", name.clone().into()),
+                    ("patch_version", version.to_string().into()),
+                ]),
+            ),
+            Problem::UnsupportedMatchType => {
+                i18n::LOCALES.lookup(language, "play-presets-problem-unsupported-match-type")
+            }
+        }
+    }
+}
+
+/// The options a preset sets on the lobby, as opposed to on the selection.
+#[derive(Clone, Copy)]
+pub struct LobbyOptions {
+    pub match_type: (u8, u8),
+    pub reveal_setup: bool,
+}
+
+pub struct State {
+    /// The preset that was last saved or restored.
+    selected: Option<String>,
+    /// The name being entered for a new preset, while the save dialog is open.
+    new_name: Option<String>,
+    /// The preset that couldn't be restored, and why.
+    problems: Option<(String, Vec<Problem>)>,
+    import_error: Option<String>,
+    /// Lobby options from a restored preset, waiting for a lobby to be applied to.
+    pending_lobby_options: Option<LobbyOptions>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            selected: None,
+            new_name: None,
+            problems: None,
+            import_error: None,
+            pending_lobby_options: None,
+        }
+    }
+
+    pub fn take_pending_lobby_options(&mut self) -> Option<LobbyOptions> {
+        self.pending_lobby_options.take()
+    }
+}
+
+fn capture(
+    name: String,
+    saves_path: &std::path::Path,
+    selection: &gui::Selection,
+    lobby_options: LobbyOptions,
+) -> config::LobbyPreset {
+    let (family, variant) = selection.game.family_and_variant();
+    config::LobbyPreset {
+        name,
+        game_family: family.to_string(),
+        game_variant: variant,
+        save_path: selection
+            .save
+            .path
+            .strip_prefix(saves_path)
+            .unwrap_or(&selection.save.path)
+            .to_path_buf(),
+        match_type: lobby_options.match_type,
+        reveal_setup: lobby_options.reveal_setup,
+        patch: selection
+            .patch
+            .as_ref()
+            .map(|(name, version, _)| config::LobbyPresetPatch {
+                name: name.clone(),
+                version: version.clone(),
+            }),
+    }
+}
+
+/// Rebuilds the selection a preset was saved with. Nothing is restored unless everything can be, so it's all reported at once instead.
+fn restore(
+    preset: &config::LobbyPreset,
+    saves_path: &std::path::Path,
+    patches_path: &std::path::Path,
+    roms: &std::collections::HashMap<&'static (dyn game::Game + Send + Sync), std::sync::Arc<[u8]>>,
+    saves: &save::ScannedSaves,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+    patched_roms: &patch::cache::PatchedRoms,
+) -> Result<gui::Selection, Vec<Problem>> {
+    let game = if let Some(game) = game::find_by_family_and_variant(&preset.game_family, preset.game_variant) {
+        game
+    } else {
+        return Err(vec![Problem::UnknownGame(
+            preset.game_family.clone(),
+            preset.game_variant,
+        )]);
+    };
+
+    let mut problems = vec![];
+
+    let rom = roms.get(&game);
+    if rom.is_none() {
+        problems.push(Problem::NoROM(game));
+    }
+
+    let save_path = saves_path.join(&preset.save_path);
+    let save = saves
+        .by_game
+        .get(&game)
+        .and_then(|saves| saves.iter().find(|save| save.path == save_path));
+    if save.is_none() {
+        problems.push(Problem::NoSave(preset.save_path.clone()));
+    }
+
+    let patch = if let Some(preset_patch) = preset.patch.as_ref() {
+        if let Some(patch) = patches.get(&preset_patch.name) {
+            let version = patch
+                .versions
+                .get(&preset_patch.version)
+                .filter(|version| version.supported_games.contains(&game) && version.is_usable());
+            if version.is_none() {
+                problems.push(Problem::NoPatchVersion(
+                    preset_patch.name.clone(),
+                    preset_patch.version.clone(),
+                ));
+            }
+            version.map(|version| (preset_patch, version))
+        } else {
+            problems.push(Problem::NoPatch(preset_patch.name.clone()));
+            None
+        }
+    } else {
+        None
+    };
+
+    if !game.has_match_type(preset.match_type) || !game.hooks().capabilities().supports_match_type(preset.match_type) {
+        problems.push(Problem::UnsupportedMatchType);
+    }
+
+    let (rom, save) = match (rom, save) {
+        (Some(rom), Some(save)) if problems.is_empty() => (rom, save),
+        _ => {
+            return Err(problems);
+        }
+    };
+
+    let (rom, patch) = if let Some((preset_patch, version)) = patch {
+        match patched_roms.get_or_apply(rom, game, patches_path, &preset_patch.name, &preset_patch.version) {
+            Ok(rom) => (
+                rom,
+                Some((preset_patch.name.clone(), preset_patch.version.clone(), version.clone())),
+            ),
+            Err(e) => {
+                log::error!(
+                    "failed to apply patch {} v{} for preset: {:?}",
+                    preset_patch.name,
+                    preset_patch.version,
+                    e
+                );
+                return Err(vec![Problem::PatchDoesNotApply(
+                    preset_patch.name.clone(),
+                    preset_patch.version.clone(),
+                )]);
+            }
+        }
+    } else {
+        (rom.clone(), None)
+    };
+
+    Ok(gui::Selection::new(game, save.clone(), patch, rom))
+}
+
+fn show_save_window(
+    ctx: &egui::Context,
+    config: &mut config::Config,
+    selection: &Option<gui::Selection>,
+    lobby_options: LobbyOptions,
+    state: &mut State,
+) {
+    let language = config.language.clone();
+    let language = &language;
+    let new_name = if let Some(new_name) = state.new_name.as_mut() {
+        new_name
+    } else {
+        return;
+    };
+
+    let selection = if let Some(selection) = selection.as_ref() {
+        selection
+    } else {
+        state.new_name = None;
+        return;
+    };
+
+    let mut open = true;
+    let mut saved = false;
+    egui::Window::new(i18n::LOCALES.lookup(language, "play-presets.save"))
+        .id(egui::Id::new("lobby-presets-save-window"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(i18n::LOCALES.lookup(language, "play-presets-save-name"));
+            let resp = ui.text_edit_singleline(new_name);
+            let name = new_name.trim().to_string();
+            let exists = config.lobby_presets.iter().any(|preset| preset.name == name);
+            if exists {
+                ui.weak(i18n::LOCALES.lookup(language, "play-presets-save-name.exists"));
+            }
+            if (ui
+                .add_enabled(
+                    !name.is_empty(),
+                    egui::Button::new(i18n::LOCALES.lookup(language, "play-presets-save-confirm")),
+                )
+                .clicked()
+                || (resp.lost_focus() && ui.input().key_pressed(egui::Key::Enter)))
+                && !name.is_empty()
+            {
+                let preset = capture(name.clone(), &config.saves_path(), selection, lobby_options);
+                if let Some(existing) = config.lobby_presets.iter_mut().find(|preset| preset.name == name) {
+                    *existing = preset;
+                } else {
+                    config.lobby_presets.push(preset);
+                }
+                state.selected = Some(name);
+                saved = true;
+            }
+        });
+
+    if !open || saved {
+        state.new_name = None;
+    }
+}
+
+/// Shows the preset picker, along with buttons to save, share and remove presets.
+pub fn show(
+    ui: &mut egui::Ui,
+    clipboard: &mut arboard::Clipboard,
+    config: &mut config::Config,
+    roms: &std::collections::HashMap<&'static (dyn game::Game + Send + Sync), std::sync::Arc<[u8]>>,
+    saves_scanner: save::Scanner,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+    patched_roms: &patch::cache::PatchedRoms,
+    selection: &mut Option<gui::Selection>,
+    patch_selection: &mut Option<String>,
+    lobby_options: LobbyOptions,
+    state: &mut State,
+) {
+    let language = config.language.clone();
+    let language = &language;
+
+    if state
+        .selected
+        .as_ref()
+        .map(|name| !config.lobby_presets.iter().any(|preset| &preset.name == name))
+        .unwrap_or(false)
+    {
+        state.selected = None;
+    }
+
+    let mut to_restore = None;
+    ui.horizontal(|ui| {
+        ui.label(i18n::LOCALES.lookup(language, "play-presets"));
+        egui::ComboBox::from_id_source("lobby-presets-combobox")
+            .width(200.0)
+            .selected_text(
+                state
+                    .selected
+                    .clone()
+                    .unwrap_or_else(|| i18n::LOCALES.lookup(language, "play-presets.none")),
+            )
+            .show_ui(ui, |ui| {
+                for preset in config.lobby_presets.iter() {
+                    if ui
+                        .selectable_label(state.selected.as_ref() == Some(&preset.name), &preset.name)
+                        .clicked()
+                    {
+                        to_restore = Some(preset.clone());
+                    }
+                }
+            });
+
+        if ui
+            .add_enabled(selection.is_some(), egui::Button::new("💾"))
+            .on_hover_text(i18n::LOCALES.lookup(language, "play-presets.save"))
+            .clicked()
+        {
+            state.new_name = Some(state.selected.clone().unwrap_or_default());
+        }
+
+        let selected_preset = state
+            .selected
+            .as_ref()
+            .and_then(|name| config.lobby_presets.iter().find(|preset| &preset.name == name));
+        if ui
+            .add_enabled(selected_preset.is_some(), egui::Button::new("📋"))
+            .on_hover_text(i18n::LOCALES.lookup(language, "play-presets.copy"))
+            .clicked()
+        {
+            match selected_preset.map(|preset| preset.to_toml()) {
+                Some(Ok(toml)) => {
+                    let _ = clipboard.set_text(toml);
+                }
+                Some(Err(e)) => {
+                    log::error!("failed to export preset: {:?}", e);
+                }
+                None => {}
+            }
+        }
+
+        if ui
+            .button("📥")
+            .on_hover_text(i18n::LOCALES.lookup(language, "play-presets.paste"))
+            .clicked()
+        {
+            match clipboard
+                .get_text()
+                .map_err(|e| e.to_string())
+                .and_then(|text| config::LobbyPreset::from_toml(&text).map_err(|e| e.to_string()))
+            {
+                Ok(preset) => {
+                    state.import_error = None;
+                    state.selected = Some(preset.name.clone());
+                    if let Some(existing) = config.lobby_presets.iter_mut().find(|p| p.name == preset.name) {
+                        *existing = preset;
+                    } else {
+                        config.lobby_presets.push(preset);
+                    }
+                }
+                Err(e) => {
+                    state.import_error = Some(e);
+                }
+            }
+        }
+
+        if ui
+            .add_enabled(state.selected.is_some(), egui::Button::new("🗑"))
+            .on_hover_text(i18n::LOCALES.lookup(language, "play-presets.delete"))
+            .clicked()
+        {
+            if let Some(name) = state.selected.take() {
+                config.lobby_presets.retain(|preset| preset.name != name);
+            }
+        }
+    });
+
+    if let Some(preset) = to_restore {
+        state.selected = Some(preset.name.clone());
+        state.import_error = None;
+        match restore(
+            &preset,
+            &config.saves_path(),
+            &config.patches_path(),
+            roms,
+            &saves_scanner.read(),
+            patches,
+            patched_roms,
+        ) {
+            Ok(restored) => {
+                state.problems = None;
+                *patch_selection = restored.patch.as_ref().map(|(name, _, _)| name.clone());
+                *selection = Some(restored);
+                config.default_match_type = preset.match_type.0;
+                config.default_match_subtype = preset.match_type.1;
+                state.pending_lobby_options = Some(LobbyOptions {
+                    match_type: preset.match_type,
+                    reveal_setup: preset.reveal_setup,
+                });
+            }
+            Err(problems) => {
+                state.problems = Some((preset.name.clone(), problems));
+            }
+        }
+    }
+
+    if let Some((name, problems)) = state.problems.as_ref() {
+        ui.colored_label(
+            egui::Color32::RED,
+            i18n::LOCALES.lookup_with_args(
+                language,
+                "play-presets-restore-error",
+                &std::collections::HashMap::from([("name", name.clone().into())]),
+            ),
+        );
+        for problem in problems {
+            ui.colored_label(egui::Color32::RED, format!("• {}", problem.description(language)));
+        }
+    }
+
+    if let Some(e) = state.import_error.as_ref() {
+        ui.colored_label(
+            egui::Color32::RED,
+            i18n::LOCALES.lookup_with_args(
+                language,
+                "play-presets-import-error",
+                &std::collections::HashMap::from([("error", e.clone().into())]),
+            ),
+        );
+    }
+
+    show_save_window(ui.ctx(), config, selection, lobby_options, state);
+}
@@ -0,0 +1,103 @@
+use crate::{gui, i18n, logging};
+
+pub struct State {
+    /// Only entries from this subsystem are shown, if set.
+    subsystem: Option<&'static str>,
+    /// Only entries at this level or more severe are shown.
+    level: log::LevelFilter,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            subsystem: None,
+            level: log::LevelFilter::Trace,
+        }
+    }
+
+    fn matches(&self, entry: &logging::Entry) -> bool {
+        entry.level <= self.level && (self.subsystem.is_none() || entry.subsystem == self.subsystem)
+    }
+}
+
+fn level_color(ui: &egui::Ui, level: log::Level) -> egui::Color32 {
+    match level {
+        log::Level::Error => ui.visuals().error_fg_color,
+        log::Level::Warn => gui::theme::colors(ui.ctx()).warning,
+        log::Level::Info => ui.visuals().text_color(),
+        log::Level::Debug | log::Level::Trace => ui.visuals().weak_text_color(),
+    }
+}
+
+pub fn show(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    clipboard: &mut arboard::Clipboard,
+    state: &mut Option<State>,
+) {
+    let mut open = state.is_some();
+    egui::Window::new(format!("📜 {}", i18n::LOCALES.lookup(language, "log-viewer")))
+        .id(egui::Id::new("log-viewer-window"))
+        .open(&mut open)
+        .default_size([600.0, 400.0])
+        .show(ctx, |ui| {
+            let state = state.as_mut().unwrap();
+
+            ui.horizontal(|ui| {
+                let all = i18n::LOCALES.lookup(language, "log-viewer-subsystem.all");
+                egui::ComboBox::from_id_source("log-viewer-subsystem")
+                    .selected_text(state.subsystem.unwrap_or(&all))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut state.subsystem, None, &all);
+                        for name in logging::SUBSYSTEMS
+                            .iter()
+                            .map(|subsystem| subsystem.name)
+                            .chain(std::iter::once(logging::OTHER))
+                        {
+                            ui.selectable_value(&mut state.subsystem, Some(name), name);
+                        }
+                    });
+
+                egui::ComboBox::from_id_source("log-viewer-level")
+                    .selected_text(state.level.as_str())
+                    .show_ui(ui, |ui| {
+                        for level in log::LevelFilter::iter().skip(1) {
+                            ui.selectable_value(&mut state.level, level, level.as_str());
+                        }
+                    });
+
+                if ui
+                    .button(format!("📋 {}", i18n::LOCALES.lookup(language, "log-viewer.copy")))
+                    .on_hover_text(i18n::LOCALES.lookup(language, "log-viewer.copy-tooltip"))
+                    .clicked()
+                {
+                    let lines = logging::recent(|entry| state.matches(entry))
+                        .into_iter()
+                        .map(|entry| entry.to_string())
+                        .collect::<Vec<_>>();
+                    let _ = clipboard.set_text(logging::redact_home_dir(&lines.join("\n")));
+                }
+            });
+
+            ui.separator();
+
+            let entries = logging::recent(|entry| state.matches(entry));
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::both()
+                .auto_shrink([false; 2])
+                .stick_to_bottom()
+                .show_rows(ui, row_height, entries.len(), |ui, range| {
+                    for entry in &entries[range] {
+                        ui.label(
+                            egui::RichText::new(entry.to_string())
+                                .monospace()
+                                .color(level_color(ui, entry.level)),
+                        );
+                    }
+                });
+        });
+
+    if !open {
+        *state = None;
+    }
+}
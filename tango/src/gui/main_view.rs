@@ -1,6 +1,7 @@
-use fluent_templates::Loader;
-
-use crate::{audio, config, discord, gui, i18n, patch, rom, save, session, stats, sync, updater};
+use crate::{
+    audio, broadcast, config, discord, gui, i18n, input, linkcode, opponents, patch, rom, save, session, stats, sync,
+    updater,
+};
 
 pub struct State {
     tab: Tab,
@@ -8,20 +9,57 @@ pub struct State {
     play_pane: gui::play_pane::State,
     patches_pane: gui::patches_pane::State,
     replays_pane: gui::replays_pane::State,
+    opponents_pane: gui::opponents_pane::State,
+    opponents: opponents::Registry,
+    save_patch_history: save::migration::History,
+    save_registry: save::registry::Registry,
+    /// Whether the saves were being scanned last frame, so the save registry can catch up once a scan is done.
+    saves_were_scanning: bool,
+    file_drop: gui::file_drop::State,
+    patch_validator: patch::validation::Validator,
+    patched_roms: patch::cache::PatchedRoms,
     show_updater: bool,
 }
 
 impl State {
-    pub fn new() -> Self {
+    pub fn new(
+        invite: Option<linkcode::Invite>,
+        opponents_path: std::path::PathBuf,
+        save_patch_history_path: std::path::PathBuf,
+        save_registry_path: std::path::PathBuf,
+    ) -> Self {
         Self {
             tab: Tab::Play,
             patch_selection: None,
-            play_pane: gui::play_pane::State::new(),
+            play_pane: gui::play_pane::State::new(invite),
             patches_pane: gui::patches_pane::State::new(),
             replays_pane: gui::replays_pane::State::new(),
+            opponents_pane: gui::opponents_pane::State::new(),
+            opponents: opponents::Registry::load(opponents_path),
+            save_patch_history: save::migration::History::load(save_patch_history_path),
+            save_registry: save::registry::Registry::load(save_registry_path),
+            saves_were_scanning: true,
+            file_drop: gui::file_drop::State::new(),
+            patch_validator: patch::validation::Validator::new(),
+            patched_roms: patch::cache::PatchedRoms::new(),
             show_updater: false,
         }
     }
+
+    pub fn opponents(&self) -> &opponents::Registry {
+        &self.opponents
+    }
+
+    /// The link code being connected with, while connecting or in a lobby.
+    pub fn link_code_in_progress(&self) -> Option<String> {
+        self.play_pane.link_code_in_progress()
+    }
+
+    /// Goes back to the play tab to join a link code again.
+    pub fn rematch(&mut self, link_code: String) {
+        self.tab = Tab::Play;
+        self.play_pane.join(link_code);
+    }
 }
 
 #[derive(PartialEq)]
@@ -29,6 +67,7 @@ enum Tab {
     Play,
     Patches,
     Replays,
+    Opponents,
 }
 
 pub fn show(
@@ -40,6 +79,7 @@ pub fn show(
     show_settings: &mut Option<gui::settings_window::State>,
     replay_dump_windows: &mut gui::replay_dump_windows::State,
     clipboard: &mut arboard::Clipboard,
+    input_state: &input::State,
     audio_binder: audio::LateBinder,
     roms_scanner: rom::Scanner,
     saves_scanner: save::Scanner,
@@ -49,6 +89,8 @@ pub fn show(
     selection: &mut Option<gui::Selection>,
     state: &mut State,
     discord_client: &mut discord::Client,
+    broadcast_server: broadcast::Server,
+    notifications: &mut gui::notifications::State,
     updater: &updater::Updater,
 ) {
     egui::TopBottomPanel::top("main-top-panel").show(ctx, |ui| {
@@ -57,7 +99,7 @@ pub fn show(
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
                     if ui
                         .selectable_label(show_settings.is_some(), "⚙️")
-                        .on_hover_text_at_pointer(i18n::LOCALES.lookup(&config.language, "settings").unwrap())
+                        .on_hover_text_at_pointer(i18n::LOCALES.lookup(&config.language, "settings"))
                         .clicked()
                     {
                         *show_settings = if show_settings.is_none() {
@@ -71,27 +113,25 @@ pub fn show(
                         if ui
                             .selectable_label(state.show_updater, "🆕")
                             .on_hover_text_at_pointer(match updater_status {
-                                updater::Status::ReadyToUpdate { .. } => i18n::LOCALES
-                                    .lookup(&config.language, "updater-ready-to-update")
-                                    .unwrap(),
-                                updater::Status::UpdateAvailable { .. } => i18n::LOCALES
-                                    .lookup(&config.language, "updater-update-available")
-                                    .unwrap(),
-                                updater::Status::Downloading { current, total, .. } => i18n::LOCALES
-                                    .lookup_with_args(
-                                        &config.language,
-                                        "updater-downloading",
-                                        &std::collections::HashMap::from([(
-                                            "percent",
-                                            if total > 0 {
-                                                format!("{}", current * 100 / total)
-                                            } else {
-                                                "?".to_string()
-                                            }
-                                            .into(),
-                                        )]),
-                                    )
-                                    .unwrap(),
+                                updater::Status::ReadyToUpdate { .. } => {
+                                    i18n::LOCALES.lookup(&config.language, "updater-ready-to-update")
+                                }
+                                updater::Status::UpdateAvailable { .. } => {
+                                    i18n::LOCALES.lookup(&config.language, "updater-update-available")
+                                }
+                                updater::Status::Downloading { current, total, .. } => i18n::LOCALES.lookup_with_args(
+                                    &config.language,
+                                    "updater-downloading",
+                                    &std::collections::HashMap::from([(
+                                        "percent",
+                                        if total > 0 {
+                                            format!("{}", current * 100 / total)
+                                        } else {
+                                            "?".to_string()
+                                        }
+                                        .into(),
+                                    )]),
+                                ),
                                 updater::Status::UpToDate => unreachable!(),
                             })
                             .clicked()
@@ -104,19 +144,22 @@ pub fn show(
                             ui.set_width(ui.available_width());
 
                             ui.selectable_value(&mut state.tab, Tab::Play, "🎮")
-                                .on_hover_text_at_pointer(i18n::LOCALES.lookup(&config.language, "play").unwrap());
+                                .on_hover_text_at_pointer(i18n::LOCALES.lookup(&config.language, "play"));
 
                             if ui
                                 .selectable_value(&mut state.tab, Tab::Replays, "📽️")
-                                .on_hover_text_at_pointer(i18n::LOCALES.lookup(&config.language, "replays").unwrap())
+                                .on_hover_text_at_pointer(i18n::LOCALES.lookup(&config.language, "replays"))
                                 .clicked()
                             {
                                 state.replays_pane.rescan(ui.ctx(), &config.replays_path());
                             }
 
+                            ui.selectable_value(&mut state.tab, Tab::Opponents, "👥")
+                                .on_hover_text_at_pointer(i18n::LOCALES.lookup(&config.language, "opponents"));
+
                             if ui
                                 .selectable_value(&mut state.tab, Tab::Patches, "🩹")
-                                .on_hover_text_at_pointer(i18n::LOCALES.lookup(&config.language, "patches").unwrap())
+                                .on_hover_text_at_pointer(i18n::LOCALES.lookup(&config.language, "patches"))
                                 .clicked()
                             {
                                 let egui_ctx = ui.ctx().clone();
@@ -141,11 +184,64 @@ pub fn show(
         gui::updater_window::show(ctx, &mut state.show_updater, &config.language, updater);
     }
 
+    let dropped_files = ctx
+        .input()
+        .raw
+        .dropped_files
+        .iter()
+        .filter_map(|f| f.path.clone())
+        .collect::<Vec<_>>();
+    if !dropped_files.is_empty() {
+        // Changing the selection or installing patches would pull the rug out from under the lobby.
+        if state.play_pane.is_connecting() {
+            notifications.toast(i18n::LOCALES.lookup(&config.language, "file-drop-ignored-netplay"));
+        } else {
+            state.file_drop.enqueue(dropped_files.into_iter());
+        }
+    }
+    match gui::file_drop::show(
+        ctx,
+        &config.language,
+        &mut state.file_drop,
+        config,
+        &roms_scanner,
+        &saves_scanner,
+        &state.patched_roms,
+        &mut state.patches_pane,
+        &audio_binder,
+        &emu_tps_counter,
+        &session,
+        selection,
+        notifications,
+    ) {
+        Some(gui::file_drop::Destination::Play) => {
+            state.tab = Tab::Play;
+        }
+        Some(gui::file_drop::Destination::Patches) => {
+            state.tab = Tab::Patches;
+        }
+        None => {}
+    }
+
+    let saves_scanning = saves_scanner.is_scanning();
+    if state.saves_were_scanning && !saves_scanning {
+        // Every save gets hashed, which is too slow to do between frames.
+        tokio::task::spawn_blocking({
+            let save_registry = state.save_registry.clone();
+            let saves_scanner = saves_scanner.clone();
+            move || {
+                save_registry.reconcile(&saves_scanner.read());
+            }
+        });
+    }
+    state.saves_were_scanning = saves_scanning;
+
     // If a join is requested, switch immediately to the play tab.
     if discord_client.has_current_join_secret() {
         state.tab = Tab::Play;
     }
 
+    let mut show_save = None;
     egui::CentralPanel::default()
         .frame(
             egui::Frame::none()
@@ -159,6 +255,7 @@ pub fn show(
                     &font_families,
                     window,
                     clipboard,
+                    input_state,
                     config,
                     config_arc,
                     roms_scanner.clone(),
@@ -169,27 +266,41 @@ pub fn show(
                     selection,
                     &mut state.patch_selection,
                     emu_tps_counter.clone(),
+                    &state.patch_validator,
+                    state.patched_roms.clone(),
+                    state.opponents.clone(),
+                    state.save_patch_history.clone(),
+                    state.save_registry.clone(),
                     &mut state.play_pane,
                     discord_client,
+                    broadcast_server,
+                    notifications,
                 );
             }
             Tab::Replays => {
-                gui::replays_pane::show(
+                show_save = gui::replays_pane::show(
                     ui,
                     clipboard,
                     &font_families,
                     &mut state.replays_pane,
                     replay_dump_windows,
                     &config.language,
+                    config.game_data_language.as_ref(),
                     &config.patches_path(),
                     patches_scanner.clone(),
                     roms_scanner.clone(),
                     &config.replays_path(),
+                    config.bios_path.as_deref(),
                     audio_binder.clone(),
                     emu_tps_counter.clone(),
                     session.clone(),
+                    &state.save_registry,
+                    config.show_debug,
                 );
             }
+            Tab::Opponents => {
+                gui::opponents_pane::show(ui, &mut state.opponents_pane, &config.language, &state.opponents);
+            }
             Tab::Patches => {
                 gui::patches_pane::show(
                     ui,
@@ -200,10 +311,19 @@ pub fn show(
                     } else {
                         config::DEFAULT_PATCH_REPO
                     },
+                    &config.network,
                     &mut state.patch_selection,
                     &config.patches_path(),
                     patches_scanner.clone(),
+                    roms_scanner.clone(),
+                    &state.patch_validator,
+                    config.show_debug,
                 );
             }
         });
+
+    if let Some((game, path)) = show_save {
+        state.tab = Tab::Play;
+        state.play_pane.show_save(game, path);
+    }
 }
@@ -0,0 +1,226 @@
+/// A run of text with the same formatting.
+enum Span {
+    Text {
+        text: String,
+        strong: bool,
+        italics: bool,
+        code: bool,
+    },
+    Link {
+        text: String,
+        url: String,
+    },
+}
+
+/// Parses emphasis, inline code and links out of a line of text. Anything that isn't closed is kept as it was written.
+fn parse_inline(s: &str) -> Vec<Span> {
+    let mut spans = vec![];
+    let mut text = String::new();
+    let mut strong = false;
+    let mut italics = false;
+
+    let flush = |spans: &mut Vec<Span>, text: &mut String, strong: bool, italics: bool| {
+        if !text.is_empty() {
+            spans.push(Span::Text {
+                text: std::mem::take(text),
+                strong,
+                italics,
+                code: false,
+            });
+        }
+    };
+
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            if let Some(end) = rest[1..].find('`') {
+                flush(&mut spans, &mut text, strong, italics);
+                spans.push(Span::Text {
+                    text: rest[1..1 + end].to_string(),
+                    strong: false,
+                    italics: false,
+                    code: true,
+                });
+                rest = &rest[end + 2..];
+                continue;
+            }
+        }
+
+        if c == '[' {
+            if let Some((label, after)) = rest[1..].split_once("](") {
+                if let Some((url, after)) = after.split_once(')') {
+                    if !label.contains(']') {
+                        flush(&mut spans, &mut text, strong, italics);
+                        spans.push(Span::Link {
+                            text: label.to_string(),
+                            url: url.to_string(),
+                        });
+                        rest = after;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        if rest.starts_with("**") || rest.starts_with("__") {
+            flush(&mut spans, &mut text, strong, italics);
+            strong = !strong;
+            rest = &rest[2..];
+            continue;
+        }
+
+        // Underscores inside words, e.g. in identifiers, aren't emphasis.
+        if c == '*'
+            || (c == '_'
+                && (text.chars().last().map(|c| !c.is_alphanumeric()).unwrap_or(true)
+                    || rest[1..].chars().next().map(|c| !c.is_alphanumeric()).unwrap_or(true)))
+        {
+            flush(&mut spans, &mut text, strong, italics);
+            italics = !italics;
+            rest = &rest[1..];
+            continue;
+        }
+
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    flush(&mut spans, &mut text, strong, italics);
+
+    spans
+}
+
+fn show_inline(ui: &mut egui::Ui, s: &str) {
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing.x = 0.0;
+        for span in parse_inline(s) {
+            match span {
+                Span::Text {
+                    text,
+                    strong,
+                    italics,
+                    code,
+                } => {
+                    let mut text = egui::RichText::new(text);
+                    if strong {
+                        text = text.strong();
+                    }
+                    if italics {
+                        text = text.italics();
+                    }
+                    if code {
+                        text = text.code();
+                    }
+                    ui.label(text);
+                }
+                Span::Link { text, url } => {
+                    ui.hyperlink_to(text, url);
+                }
+            }
+        }
+    });
+}
+
+/// Gets the level and text of a heading line, e.g. `## Changes`.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let text = line[level..].strip_prefix(' ')?;
+    Some((level, text.trim_end_matches('#').trim()))
+}
+
+/// Gets the marker and text of a list item line, e.g. `- item` or `1. item`.
+fn list_item(line: &str) -> Option<(String, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(text) = line.strip_prefix(bullet) {
+            return Some(("•".to_string(), text));
+        }
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(text) = line[digits..].strip_prefix(". ") {
+            return Some((line[..digits + 1].to_string(), text));
+        }
+    }
+    None
+}
+
+/// Shows a small subset of Markdown: headings, lists, code blocks, rules, links, inline code and emphasis.
+///
+/// This is meant for patch READMEs, not as a complete renderer: anything it doesn't understand is shown as plain text.
+pub fn show(ui: &mut egui::Ui, markdown: &str) {
+    let mut paragraph = String::new();
+    let mut code_block = None;
+
+    let flush_paragraph = |ui: &mut egui::Ui, paragraph: &mut String| {
+        if !paragraph.is_empty() {
+            show_inline(ui, &std::mem::take(paragraph));
+        }
+    };
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") {
+            flush_paragraph(ui, &mut paragraph);
+            if let Some(code) = code_block.take() {
+                ui.monospace(code);
+            } else {
+                code_block = Some(String::new());
+            }
+            continue;
+        }
+
+        if let Some(code) = code_block.as_mut() {
+            if !code.is_empty() {
+                code.push('\n');
+            }
+            code.push_str(line);
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            flush_paragraph(ui, &mut paragraph);
+            ui.add_space(ui.spacing().item_spacing.y);
+            continue;
+        }
+
+        if let Some((level, text)) = heading(trimmed) {
+            flush_paragraph(ui, &mut paragraph);
+            if level == 1 {
+                ui.heading(text);
+            } else {
+                ui.label(egui::RichText::new(text).strong());
+            }
+            continue;
+        }
+
+        if trimmed.chars().all(|c| c == '-' || c == '*' || c == '_') && trimmed.len() >= 3 {
+            flush_paragraph(ui, &mut paragraph);
+            ui.separator();
+            continue;
+        }
+
+        if let Some((marker, text)) = list_item(trimmed) {
+            flush_paragraph(ui, &mut paragraph);
+            let indent = (line.len() - trimmed.len()) / 2 + 1;
+            ui.horizontal(|ui| {
+                ui.add_space(ui.spacing().indent * indent as f32);
+                ui.label(marker);
+                show_inline(ui, text);
+            });
+            continue;
+        }
+
+        if !paragraph.is_empty() {
+            paragraph.push(' ');
+        }
+        paragraph.push_str(trimmed);
+    }
+
+    flush_paragraph(ui, &mut paragraph);
+    if let Some(code) = code_block {
+        ui.monospace(code);
+    }
+}
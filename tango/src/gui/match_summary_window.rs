@@ -0,0 +1,507 @@
+use crate::{audio, battle, contentpolicy, fairness, game, gui, i18n, net, opponents, replay, session, stats};
+
+/// How long after the summary comes up before key presses close it, so a button still held from the match doesn't close it straight away.
+const DISMISS_GRACE: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How the match came to an end.
+#[derive(Clone, Copy)]
+enum Ending {
+    Finished,
+    /// The local player ended the match themselves.
+    Forfeited,
+    OpponentForfeited,
+    Disconnected,
+    Desynced(battle::DesyncError),
+}
+
+pub struct State {
+    ending: Ending,
+    game: &'static (dyn game::Game + Send + Sync),
+    patch: Option<(String, semver::Version)>,
+    rom: std::sync::Arc<[u8]>,
+    link_code: String,
+    local_nickname: String,
+    remote_nickname: String,
+    remote_installation_id: Option<String>,
+    /// Who the opponent is in the opponents registry, if they're in it.
+    opponent_id: Option<String>,
+    score: battle::Score,
+    record: battle::Record,
+    /// Filled in once the match is over and its fairness report has been written.
+    fairness_report: std::sync::Arc<parking_lot::Mutex<Option<std::path::PathBuf>>>,
+    /// How many rounds were checked if the fairness report checked out, or what was wrong with it.
+    fairness: Option<Result<usize, String>>,
+    shown_at: Option<std::time::Instant>,
+    error: Option<String>,
+    upload: Option<Upload>,
+}
+
+/// The match's replays, queued to be uploaded once the summary goes away unless the player opts out here.
+struct Upload {
+    uploader: replay::upload::Uploader,
+    match_id: String,
+    enabled: bool,
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        if let Some(upload) = self.upload.as_ref() {
+            if upload.enabled {
+                upload.uploader.release_match(&upload.match_id);
+            } else {
+                upload.uploader.discard_match(&upload.match_id);
+            }
+        }
+    }
+}
+
+impl State {
+    /// Sums up a netplay session as it ends. Other sessions have nothing to sum up.
+    ///
+    /// If given an uploader, the match's replays are queued on it straight away, to be uploaded once the summary goes away.
+    pub fn new(
+        session: &session::Session,
+        forfeited: bool,
+        replay_uploader: Option<replay::upload::Uploader>,
+    ) -> Option<Self> {
+        let pvp = if let session::Mode::PvP(pvp) = session.mode() {
+            pvp
+        } else {
+            return None;
+        };
+        let record = pvp.record();
+        let ending = if let Some(desync) = pvp.desync() {
+            Ending::Desynced(desync)
+        } else if record.opponent_forfeited {
+            Ending::OpponentForfeited
+        } else if record.disconnected {
+            Ending::Disconnected
+        } else if forfeited {
+            Ending::Forfeited
+        } else {
+            Ending::Finished
+        };
+        let (local_nickname, remote_nickname) = pvp.nicknames();
+        let game_info = session.game_info();
+        let upload = replay_uploader.and_then(|uploader| {
+            let match_id = format!("{:032x}", rand::random::<u128>());
+            let items = record
+                .rounds
+                .iter()
+                .filter_map(|round| {
+                    let replay_path = round.replay_path.as_ref()?;
+                    Some(replay::upload::Item {
+                        replay_path: replay_path.clone(),
+                        match_id: match_id.clone(),
+                        round_number: round.number,
+                        outcome: match round.result {
+                            _ if round.was_draw => Some(replay::upload::Outcome::Draw),
+                            Some(battle::BattleResult::Win) => Some(replay::upload::Outcome::Win),
+                            Some(battle::BattleResult::Loss) => Some(replay::upload::Outcome::Loss),
+                            None => None,
+                        },
+                        finished_at: std::fs::metadata(replay_path)
+                            .and_then(|metadata| metadata.modified())
+                            .unwrap_or_else(|_| std::time::SystemTime::now()),
+                        attempts: 0,
+                    })
+                })
+                .collect::<Vec<_>>();
+            if items.is_empty() {
+                return None;
+            }
+            uploader.enqueue_match(&match_id, items);
+            Some(Upload {
+                uploader,
+                match_id,
+                enabled: true,
+            })
+        });
+        Some(Self {
+            ending,
+            game: game_info.game,
+            patch: game_info.patch.clone(),
+            rom: pvp.local_rom().clone(),
+            link_code: pvp.link_code().to_string(),
+            local_nickname: local_nickname.to_string(),
+            remote_nickname: remote_nickname.to_string(),
+            remote_installation_id: pvp.remote_installation_id().map(|id| id.to_string()),
+            opponent_id: pvp.opponent_id().map(|id| id.to_string()),
+            score: pvp.score(),
+            record,
+            fairness_report: pvp.fairness_report().clone(),
+            fairness: None,
+            shown_at: None,
+            error: None,
+            upload,
+        })
+    }
+}
+
+fn format_ticks(ticks: u64) -> String {
+    let secs = ticks / session::EXPECTED_FPS as u64;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Lists how each round went, with its replay. Returns an error if a replay couldn't be opened.
+fn show_rounds(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    state: &State,
+    session: &std::sync::Arc<parking_lot::Mutex<Option<session::Session>>>,
+    audio_binder: &audio::LateBinder,
+    emu_tps_counter: &std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
+    bios_path: Option<&std::path::Path>,
+) -> Option<String> {
+    let colors = gui::theme::colors(ui.ctx());
+    let mut error = None;
+    egui::Grid::new("match-summary-rounds").num_columns(4).show(ui, |ui| {
+        for round in state.record.rounds.iter() {
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                "match-summary-round",
+                &std::collections::HashMap::from([("round", round.number.into())]),
+            ));
+            match round.result {
+                _ if round.was_draw => {
+                    ui.label(i18n::LOCALES.lookup(language, "match-summary-result-draw"));
+                }
+                Some(battle::BattleResult::Win) => {
+                    ui.colored_label(colors.ready, i18n::LOCALES.lookup(language, "match-summary-result-win"));
+                }
+                Some(battle::BattleResult::Loss) => {
+                    ui.colored_label(
+                        colors.warning,
+                        i18n::LOCALES.lookup(language, "match-summary-result-loss"),
+                    );
+                }
+                None => {
+                    ui.label("—");
+                }
+            }
+            ui.weak(format_ticks(round.ticks as u64));
+            ui.horizontal(|ui| {
+                let replay_path = if let Some(replay_path) = round.replay_path.as_ref() {
+                    replay_path
+                } else {
+                    ui.weak(i18n::LOCALES.lookup(language, "match-not-recorded"));
+                    return;
+                };
+
+                if ui
+                    .button("▶️")
+                    .on_hover_text(i18n::LOCALES.lookup(language, "match-summary-replay-play"))
+                    .clicked()
+                {
+                    match std::fs::File::open(replay_path).and_then(|f| replay::Replay::decode(f)) {
+                        Ok(replay) => {
+                            tokio::task::spawn_blocking({
+                                let egui_ctx = ui.ctx().clone();
+                                let audio_binder = audio_binder.clone();
+                                let game = state.game;
+                                let patch = state.patch.clone();
+                                let rom = state.rom.clone();
+                                let emu_tps_counter = emu_tps_counter.clone();
+                                let session = session.clone();
+                                let bios_path = bios_path.map(|path| path.to_path_buf());
+                                let replay_path = replay_path.to_path_buf();
+                                move || {
+                                    match session::Session::new_replayer(
+                                        audio_binder,
+                                        game,
+                                        patch,
+                                        &rom,
+                                        bios_path.as_deref(),
+                                        emu_tps_counter,
+                                        &replay,
+                                        &replay_path,
+                                    ) {
+                                        Ok(s) => {
+                                            *session.lock() = Some(s);
+                                        }
+                                        Err(e) => {
+                                            log::error!("failed to start replay: {:?}", e);
+                                        }
+                                    }
+                                    egui_ctx.request_repaint();
+                                }
+                            });
+                        }
+                        Err(e) => {
+                            log::error!("failed to load replay {}: {:?}", replay_path.display(), e);
+                            error = Some(e.to_string());
+                        }
+                    }
+                }
+
+                if ui
+                    .button("📂")
+                    .on_hover_text(i18n::LOCALES.lookup(language, "match-summary-replay-reveal"))
+                    .clicked()
+                {
+                    if let Some(parent) = replay_path.parent() {
+                        let _ = open::that(parent);
+                    }
+                }
+            });
+            ui.end_row();
+        }
+    });
+    error
+}
+
+fn show_stats(ui: &mut egui::Ui, language: &unic_langid::LanguageIdentifier, record: &battle::Record) {
+    egui::Grid::new("match-summary-stats").num_columns(2).show(ui, |ui| {
+        ui.strong(i18n::LOCALES.lookup(language, "match-summary-duration"));
+        ui.label(format_ticks(record.rounds.iter().map(|round| round.ticks as u64).sum()));
+        ui.end_row();
+
+        ui.strong(i18n::LOCALES.lookup(language, "match-summary-latency"));
+        if record.latencies.is_empty() {
+            ui.label("—");
+        } else {
+            let mean = record.latencies.iter().sum::<std::time::Duration>() / record.latencies.len() as u32;
+            let percentiles = stats::Percentiles::of(record.latencies.iter().cloned());
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                "match-summary-latency-value",
+                &std::collections::HashMap::from([
+                    ("mean", (mean.as_millis() as u64).into()),
+                    ("p95", (percentiles.p95.as_millis() as u64).into()),
+                ]),
+            ));
+        }
+        ui.end_row();
+
+        ui.strong(i18n::LOCALES.lookup(language, "match-summary-rollback"));
+        ui.label(
+            i18n::LOCALES.lookup_with_args(
+                language,
+                "match-summary-rollback-value",
+                &std::collections::HashMap::from([(
+                    "ticks",
+                    record
+                        .rounds
+                        .iter()
+                        .map(|round| round.rollback_ticks)
+                        .sum::<u64>()
+                        .into(),
+                )]),
+            ),
+        );
+        ui.end_row();
+    });
+}
+
+/// Offers to check the match's fairness report, once it's been written.
+fn show_fairness(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    state: &mut State,
+    bios_path: Option<&std::path::Path>,
+) {
+    let colors = gui::theme::colors(ui.ctx());
+    let report_path = state.fairness_report.lock().clone();
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(
+                report_path.is_some(),
+                egui::Button::new(i18n::LOCALES.lookup(language, "match-summary-verify-fairness")),
+            )
+            .on_hover_text(i18n::LOCALES.lookup(language, "match-summary-verify-fairness.tooltip"))
+            .clicked()
+        {
+            if let Some(report_path) = report_path.as_ref() {
+                state.fairness = Some(
+                    fairness::verify(report_path, &state.rom, state.game.hooks(), bios_path).map_err(|e| {
+                        log::error!("fairness report {} failed to verify: {:?}", report_path.display(), e);
+                        e.to_string()
+                    }),
+                );
+            }
+        }
+
+        match state.fairness.as_ref() {
+            Some(Ok(rounds)) => {
+                ui.colored_label(
+                    colors.ready,
+                    i18n::LOCALES.lookup_with_args(
+                        language,
+                        "match-summary-fairness-verified",
+                        &std::collections::HashMap::from([("rounds", (*rounds).into())]),
+                    ),
+                );
+            }
+            Some(Err(error)) => {
+                ui.colored_label(
+                    colors.warning,
+                    i18n::LOCALES.lookup_with_args(
+                        language,
+                        "match-summary-fairness-failed",
+                        &std::collections::HashMap::from([("error", error.as_str().into())]),
+                    ),
+                );
+            }
+            None => {}
+        }
+    });
+}
+
+/// Shows how a netplay match went once it's over. Returns the link code to join again if a rematch was asked for.
+pub fn show(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    state: &mut Option<State>,
+    opponents: &opponents::Registry,
+    session: std::sync::Arc<parking_lot::Mutex<Option<session::Session>>>,
+    audio_binder: audio::LateBinder,
+    emu_tps_counter: std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
+    bios_path: Option<&std::path::Path>,
+) -> Option<String> {
+    let summary = state.as_mut()?;
+
+    let shown_at = *summary.shown_at.get_or_insert_with(|| std::time::Instant::now());
+    let mut close = shown_at.elapsed() >= DISMISS_GRACE
+        && ctx
+            .input()
+            .events
+            .iter()
+            .any(|event| matches!(event, egui::Event::Key { pressed: true, .. }));
+    if !close && shown_at.elapsed() < DISMISS_GRACE {
+        ctx.request_repaint_after(DISMISS_GRACE - shown_at.elapsed());
+    }
+
+    let mut rematch = None;
+    let mut open = true;
+    egui::Window::new(i18n::LOCALES.lookup(language, "match-summary"))
+        .id(egui::Id::new("match-summary-window"))
+        .open(&mut open)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let colors = gui::theme::colors(ui.ctx());
+            ui.vertical_centered(|ui| {
+                ui.heading(format!(
+                    "{} {} - {} {}",
+                    summary.local_nickname,
+                    summary.score.wins,
+                    summary.score.losses,
+                    contentpolicy::display_nickname(&summary.remote_nickname)
+                ));
+                if summary.score.draws > 0 {
+                    ui.weak(i18n::LOCALES.lookup_with_args(
+                        language,
+                        "scoreboard-draws",
+                        &std::collections::HashMap::from([("draws", summary.score.draws.into())]),
+                    ));
+                }
+            });
+
+            // Cut short matches only show what was finished, as their stats are for part of a round at best.
+            let cut_short = match summary.ending {
+                Ending::Finished => None,
+                Ending::Forfeited => Some(i18n::LOCALES.lookup(language, "match-summary-ending-forfeited")),
+                Ending::OpponentForfeited => {
+                    Some(i18n::LOCALES.lookup(language, "match-summary-ending-opponent-forfeited"))
+                }
+                Ending::Disconnected => Some(i18n::LOCALES.lookup(language, "match-summary-ending-disconnected")),
+                Ending::Desynced(desync) => Some(i18n::LOCALES.lookup_with_args(
+                    language,
+                    "match-summary-ending-desynced",
+                    &std::collections::HashMap::from([("round", desync.round_number.into())]),
+                )),
+            };
+            if let Some(cut_short) = cut_short.as_ref() {
+                ui.colored_label(colors.warning, cut_short);
+            }
+
+            ui.separator();
+            if let Some(error) = show_rounds(
+                ui,
+                language,
+                summary,
+                &session,
+                &audio_binder,
+                &emu_tps_counter,
+                bios_path,
+            ) {
+                summary.error = Some(error);
+            }
+            if cut_short.is_none() {
+                ui.separator();
+                show_stats(ui, language, &summary.record);
+            }
+            show_fairness(ui, language, summary, bios_path);
+            if let Some(upload) = summary.upload.as_mut() {
+                ui.checkbox(
+                    &mut upload.enabled,
+                    i18n::LOCALES.lookup(language, "match-summary-upload-replays"),
+                )
+                .on_hover_text(i18n::LOCALES.lookup(language, "match-summary-upload-replays.tooltip"));
+            }
+
+            if let Some(error) = summary.error.as_ref() {
+                ui.colored_label(
+                    colors.warning,
+                    i18n::LOCALES.lookup_with_args(
+                        language,
+                        "match-summary-replay-error",
+                        &std::collections::HashMap::from([("error", error.as_str().into())]),
+                    ),
+                );
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                // A LAN match can't be rejoined through the matchmaking server: the host has to host again.
+                if !net::lan::is_lan_link_code(&summary.link_code)
+                    && ui
+                        .button(i18n::LOCALES.lookup(language, "match-summary-rematch"))
+                        .on_hover_text(i18n::LOCALES.lookup(language, "match-summary-rematch.tooltip"))
+                        .clicked()
+                {
+                    rematch = Some(summary.link_code.clone());
+                    close = true;
+                }
+
+                if summary.opponent_id.is_some() {
+                    ui.weak(i18n::LOCALES.lookup(language, "match-summary-opponent-saved"));
+                } else if ui
+                    .button(i18n::LOCALES.lookup(language, "match-summary-save-opponent"))
+                    .clicked()
+                {
+                    // They're saved with the rounds just played, as if they'd been recognized from the start.
+                    let r = opponents
+                        .add(&summary.remote_nickname, summary.remote_installation_id.as_deref())
+                        .and_then(|id| {
+                            for round in summary.record.rounds.iter() {
+                                opponents.record(&id, round.result, round.was_draw)?;
+                            }
+                            Ok(id)
+                        });
+                    match r {
+                        Ok(id) => {
+                            summary.opponent_id = Some(id);
+                        }
+                        Err(e) => {
+                            log::error!("failed to save opponent: {:?}", e);
+                        }
+                    }
+                }
+
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "match-summary-close"))
+                    .clicked()
+                {
+                    close = true;
+                }
+            });
+            ui.weak(i18n::LOCALES.lookup(language, "match-summary-dismiss-hint"));
+        });
+
+    if !open || close {
+        *state = None;
+    }
+    rematch
+}
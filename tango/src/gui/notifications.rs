@@ -0,0 +1,129 @@
+use crate::{audio, config, i18n};
+
+const TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
+
+lazy_static! {
+    // Raw signed 16-bit little-endian mono PCM at 48000 Hz, matching the audio binder's sample rate.
+    static ref NOTIFICATION_SOUND: Vec<i16> = include_bytes!("../sounds/notification.pcm")
+        .chunks_exact(2)
+        .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]))
+        .collect();
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Event {
+    OpponentJoined,
+    OpponentCompatible,
+    OpponentReady,
+}
+
+impl Event {
+    fn is_enabled(&self, config: &config::Config) -> bool {
+        match self {
+            Event::OpponentJoined => config.notify_on_opponent_join,
+            Event::OpponentCompatible => config.notify_on_opponent_compatible,
+            Event::OpponentReady => config.notify_on_opponent_ready,
+        }
+    }
+
+    fn message_id(&self) -> &'static str {
+        match self {
+            Event::OpponentJoined => "notification-opponent-joined",
+            Event::OpponentCompatible => "notification-opponent-compatible",
+            Event::OpponentReady => "notification-opponent-ready",
+        }
+    }
+}
+
+pub struct State {
+    toasts: std::collections::VecDeque<(String, std::time::Instant)>,
+    window_focused: bool,
+    attention_requested: bool,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            toasts: std::collections::VecDeque::new(),
+            window_focused: true,
+            attention_requested: false,
+        }
+    }
+
+    pub fn set_window_focused(&mut self, focused: bool) {
+        self.window_focused = focused;
+        if focused {
+            self.attention_requested = false;
+        }
+    }
+
+    pub fn notify(
+        &mut self,
+        window: &winit::window::Window,
+        config: &config::Config,
+        audio_binder: &audio::LateBinder,
+        nickname: &str,
+        event: Event,
+    ) {
+        if !event.is_enabled(config) {
+            return;
+        }
+
+        // Only ask for attention once per unfocus: some window managers flash again on every request.
+        if !self.window_focused && !self.attention_requested {
+            window.request_user_attention(Some(winit::window::UserAttentionType::Critical));
+            self.attention_requested = true;
+        }
+
+        play_sound(config, audio_binder);
+
+        self.toast(i18n::LOCALES.lookup_with_args(
+            &config.language,
+            event.message_id(),
+            &std::collections::HashMap::from([("nickname", nickname.to_string().into())]),
+        ));
+    }
+
+    /// Shows a message in the corner of the window for a few seconds.
+    pub fn toast(&mut self, message: String) {
+        self.toasts.push_back((message, std::time::Instant::now()));
+    }
+}
+
+/// Plays the notification sound, unless it's muted.
+pub fn play_sound(config: &config::Config, audio_binder: &audio::LateBinder) {
+    if !config.notification_sound_muted {
+        audio_binder.play_sound(&NOTIFICATION_SOUND, config.notification_sound_volume);
+    }
+}
+
+pub fn show(ctx: &egui::Context, state: &mut State) {
+    let now = std::time::Instant::now();
+    while state
+        .toasts
+        .front()
+        .map(|(_, shown_at)| now.duration_since(*shown_at) >= TOAST_DURATION)
+        .unwrap_or(false)
+    {
+        state.toasts.pop_front();
+    }
+
+    let next_expiry = if let Some((_, shown_at)) = state.toasts.front() {
+        TOAST_DURATION - now.duration_since(*shown_at)
+    } else {
+        return;
+    };
+
+    egui::Area::new("notifications-area")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+        .order(egui::Order::Foreground)
+        .interactable(false)
+        .show(ctx, |ui| {
+            for (message, _) in state.toasts.iter() {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(message);
+                });
+            }
+        });
+    ctx.request_repaint_after(next_expiry);
+}
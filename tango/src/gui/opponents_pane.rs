@@ -0,0 +1,310 @@
+use chrono_locale::LocaleDate;
+
+use crate::{contentpolicy, gui, i18n, opponents};
+
+struct Selection {
+    id: String,
+    /// The notes being edited, which are written out when the player is done with them.
+    notes: String,
+    merge_into: Option<String>,
+    confirm_remove: bool,
+}
+
+pub struct State {
+    selection: Option<Selection>,
+    search: String,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            selection: None,
+            search: String::new(),
+        }
+    }
+}
+
+/// Describes an opponent's head-to-head record and when they were last played.
+pub fn record_text(language: &unic_langid::LanguageIdentifier, entry: &opponents::Entry) -> String {
+    format!(
+        "{} · {}",
+        i18n::LOCALES.lookup_with_args(
+            language,
+            "opponents-record",
+            &std::collections::HashMap::from([
+                ("wins", entry.wins.into()),
+                ("losses", entry.losses.into()),
+                ("draws", entry.draws.into()),
+            ]),
+        ),
+        if let Some(last_played_at) = entry.last_played_at {
+            i18n::LOCALES.lookup_with_args(
+                language,
+                "opponents-last-played",
+                &std::collections::HashMap::from([(
+                    "date",
+                    chrono::DateTime::<chrono::Local>::from(last_played_at)
+                        .formatl("%x", &language.to_string())
+                        .to_string()
+                        .into(),
+                )]),
+            )
+        } else {
+            i18n::LOCALES.lookup(language, "opponents-never-played")
+        }
+    )
+}
+
+/// Checks if every word searched for is in the opponent's nicknames or notes.
+fn matches_search(terms: &[String], entry: &opponents::Entry) -> bool {
+    let haystack = entry
+        .nicknames
+        .iter()
+        .chain(std::iter::once(&entry.notes))
+        .map(|s| s.to_lowercase())
+        .collect::<Vec<_>>();
+    terms
+        .iter()
+        .all(|term| haystack.iter().any(|s| s.contains(term.as_str())))
+}
+
+pub fn show(
+    ui: &mut egui::Ui,
+    state: &mut State,
+    language: &unic_langid::LanguageIdentifier,
+    opponents: &opponents::Registry,
+) {
+    let entries = opponents.entries();
+
+    egui::SidePanel::left("opponents-window-left-panel").show_inside(ui, |ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut state.search)
+                .hint_text(i18n::LOCALES.lookup(language, "opponents-search"))
+                .desired_width(f32::INFINITY),
+        );
+        let search_terms = state
+            .search
+            .to_lowercase()
+            .split_whitespace()
+            .map(|term| term.to_string())
+            .collect::<Vec<_>>();
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .id_source("opponents-window-left")
+            .show(ui, |ui| {
+                if entries.is_empty() {
+                    ui.label(i18n::LOCALES.lookup(language, "opponents-empty"));
+                    return;
+                }
+
+                ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
+                    for (id, entry) in entries.iter() {
+                        if !search_terms.is_empty() && !matches_search(&search_terms, entry) {
+                            continue;
+                        }
+
+                        gui::fonts::request(entry.nickname());
+                        let selected = state.selection.as_ref().map(|s| &s.id) == Some(id);
+                        let text_color = if selected {
+                            ui.ctx().style().visuals.selection.stroke.color
+                        } else {
+                            ui.visuals().text_color()
+                        };
+
+                        let mut layout_job = egui::text::LayoutJob::default();
+                        layout_job.append(
+                            &if entry.pinned {
+                                format!("📌 {}", contentpolicy::display_nickname(entry.nickname()))
+                            } else {
+                                contentpolicy::display_nickname(entry.nickname())
+                            },
+                            0.0,
+                            egui::TextFormat::simple(
+                                ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
+                                text_color,
+                            ),
+                        );
+                        layout_job.append(
+                            "\n",
+                            0.0,
+                            egui::TextFormat::simple(
+                                ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
+                                text_color,
+                            ),
+                        );
+                        layout_job.append(
+                            &record_text(language, entry),
+                            0.0,
+                            egui::TextFormat::simple(
+                                ui.style().text_styles.get(&egui::TextStyle::Small).unwrap().clone(),
+                                text_color,
+                            ),
+                        );
+
+                        if ui.selectable_label(selected, layout_job).clicked() {
+                            state.selection = Some(Selection {
+                                id: id.clone(),
+                                notes: entry.notes.clone(),
+                                merge_into: None,
+                                confirm_remove: false,
+                            });
+                        }
+                    }
+                });
+            });
+    });
+
+    let mut remove = false;
+    egui::CentralPanel::default().show_inside(ui, |ui| {
+        let selection = if let Some(selection) = state.selection.as_mut() {
+            selection
+        } else {
+            return;
+        };
+
+        let entry = if let Some((_, entry)) = entries.iter().find(|(id, _)| id == &selection.id) {
+            entry
+        } else {
+            return;
+        };
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .id_source("opponents-window-info")
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading(contentpolicy::display_nickname(entry.nickname()));
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                        if selection.confirm_remove {
+                            if ui
+                                .button(i18n::LOCALES.lookup(language, "opponents-remove.cancel"))
+                                .clicked()
+                            {
+                                selection.confirm_remove = false;
+                            }
+                            if ui
+                                .button(format!(
+                                    "🗑 {}",
+                                    i18n::LOCALES.lookup(language, "opponents-remove.confirm")
+                                ))
+                                .clicked()
+                            {
+                                remove = true;
+                            }
+                        } else if ui
+                            .button(format!("🗑 {}", i18n::LOCALES.lookup(language, "opponents-remove")))
+                            .clicked()
+                        {
+                            selection.confirm_remove = true;
+                        }
+                    });
+                });
+
+                ui.label(record_text(language, entry));
+                if entry.nicknames.len() > 1 {
+                    ui.label(
+                        i18n::LOCALES.lookup_with_args(
+                            language,
+                            "opponents-also-known-as",
+                            &std::collections::HashMap::from([(
+                                "nicknames",
+                                entry.nicknames[..entry.nicknames.len() - 1]
+                                    .iter()
+                                    .map(|nickname| contentpolicy::display_nickname(nickname))
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                                    .into(),
+                            )]),
+                        ),
+                    );
+                }
+
+                let mut pinned = entry.pinned;
+                if ui
+                    .checkbox(&mut pinned, i18n::LOCALES.lookup(language, "opponents-pinned"))
+                    .on_hover_text(i18n::LOCALES.lookup(language, "opponents-pinned.tooltip"))
+                    .changed()
+                {
+                    if let Err(e) = opponents.set_pinned(&selection.id, pinned) {
+                        log::error!("failed to save opponent: {:?}", e);
+                    }
+                }
+
+                ui.strong(i18n::LOCALES.lookup(language, "opponents-notes"));
+                if ui
+                    .add(
+                        egui::TextEdit::multiline(&mut selection.notes)
+                            .hint_text(i18n::LOCALES.lookup(language, "opponents-notes.placeholder"))
+                            .desired_rows(3)
+                            .desired_width(f32::INFINITY),
+                    )
+                    .lost_focus()
+                {
+                    if let Err(e) = opponents.set_notes(&selection.id, &selection.notes) {
+                        log::error!("failed to save opponent notes: {:?}", e);
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label(i18n::LOCALES.lookup(language, "opponents-merge"))
+                        .on_hover_text(i18n::LOCALES.lookup(language, "opponents-merge.tooltip"));
+                    egui::ComboBox::from_id_source("opponents-merge-into")
+                        .selected_text(
+                            selection
+                                .merge_into
+                                .as_ref()
+                                .and_then(|id| entries.iter().find(|(other_id, _)| other_id == id))
+                                .map(|(_, entry)| contentpolicy::display_nickname(entry.nickname()))
+                                .unwrap_or_default(),
+                        )
+                        .show_ui(ui, |ui| {
+                            for (id, other) in entries.iter() {
+                                if id == &selection.id {
+                                    continue;
+                                }
+                                ui.selectable_value(
+                                    &mut selection.merge_into,
+                                    Some(id.clone()),
+                                    format!(
+                                        "{} ({})",
+                                        contentpolicy::display_nickname(other.nickname()),
+                                        record_text(language, other)
+                                    ),
+                                );
+                            }
+                        });
+                    if ui
+                        .add_enabled(
+                            selection.merge_into.is_some(),
+                            egui::Button::new(i18n::LOCALES.lookup(language, "opponents-merge.confirm")),
+                        )
+                        .clicked()
+                    {
+                        let into = selection.merge_into.take().unwrap();
+                        match opponents.merge(&selection.id, &into) {
+                            Ok(()) => {
+                                *selection = Selection {
+                                    notes: opponents.get(&into).map(|entry| entry.notes).unwrap_or_default(),
+                                    id: into,
+                                    merge_into: None,
+                                    confirm_remove: false,
+                                };
+                            }
+                            Err(e) => {
+                                log::error!("failed to merge opponents: {:?}", e);
+                            }
+                        }
+                    }
+                });
+            });
+    });
+
+    if remove {
+        if let Some(selection) = state.selection.take() {
+            if let Err(e) = opponents.remove(&selection.id) {
+                log::error!("failed to remove opponent: {:?}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use itertools::Itertools;
+
+use crate::{game, gui, i18n, patch, rom};
+
+fn show_row_label(ui: &mut egui::Ui, text: String) -> egui::Response {
+    ui.with_layout(
+        egui::Layout::left_to_right(egui::Align::Min).with_cross_justify(true),
+        |ui| ui.strong(text),
+    )
+    .inner
+}
+
+/// Shows everything known about a patch: who made it, its license and website, each of its versions, and its README.
+pub fn show(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    patches_path: &std::path::Path,
+    patch_name: &str,
+    patch: &patch::Patch,
+    roms_scanner: rom::Scanner,
+    patch_validator: &patch::validation::Validator,
+) {
+    let latest_version = patch.versions.keys().max();
+
+    ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+        if ui
+            .button(format!("📂 {}", i18n::LOCALES.lookup(language, "patches-open-folder"),))
+            .clicked()
+        {
+            let _ = open::that(&patch.path);
+        }
+
+        ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
+            ui.horizontal(|ui| {
+                ui.with_layout(
+                    egui::Layout::left_to_right(egui::Align::Max).with_main_wrap(true),
+                    |ui| {
+                        ui.heading(&patch.title);
+                        if let Some(version) = latest_version {
+                            ui.label(version.to_string());
+                        }
+                    },
+                );
+            });
+        });
+    });
+
+    egui::Grid::new(("patch-info-grid", patch_name))
+        .num_columns(2)
+        .show(ui, |ui| {
+            show_row_label(ui, i18n::LOCALES.lookup(language, "patches-details-authors"));
+            ui.vertical(|ui| {
+                for author in patch.authors.iter() {
+                    let name = author.display_name.as_ref().unwrap_or(&author.addr);
+                    if author.addr == "" {
+                        ui.label(name);
+                    } else {
+                        ui.hyperlink_to(name, format!("mailto:{}", author.addr));
+                    }
+                }
+            });
+            ui.end_row();
+
+            show_row_label(ui, i18n::LOCALES.lookup(language, "patches-details-license"));
+            if let Some(license) = patch.license.as_ref() {
+                ui.vertical(|ui| {
+                    ui.label(license);
+                    if !patch::is_recognized_license(license) {
+                        ui.weak(i18n::LOCALES.lookup(language, "patches-details-license.unrecognized"));
+                    }
+                });
+            } else {
+                ui.label(i18n::LOCALES.lookup(language, "patches-details-license.all-rights-reserved"));
+            }
+            ui.end_row();
+
+            if let Some(source) = patch.source.as_ref() {
+                show_row_label(ui, i18n::LOCALES.lookup(language, "patches-details-source"));
+                ui.hyperlink_to(format!("🌐 {}", source), source);
+                ui.end_row();
+            }
+
+            show_row_label(ui, i18n::LOCALES.lookup(language, "patches-details-versions"));
+            ui.vertical(|ui| {
+                for (version, version_info) in patch.versions.iter().sorted_by_key(|(k, _)| *k).rev() {
+                    show_version(
+                        ui,
+                        language,
+                        patch_validator,
+                        patches_path,
+                        patch_name,
+                        version,
+                        version_info,
+                        roms_scanner.clone(),
+                    );
+                }
+            });
+            ui.end_row();
+        });
+    ui.separator();
+
+    egui::ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .id_source(("patch-details-readme", patch_name))
+        .show(ui, |ui| match patch.readme.as_ref() {
+            Some(readme) if readme.is_markdown => {
+                gui::markdown::show(ui, &readme.contents);
+            }
+            Some(readme) => {
+                ui.monospace(&readme.contents);
+            }
+            None => {}
+        });
+}
+
+fn show_version(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    patch_validator: &patch::validation::Validator,
+    patches_path: &std::path::Path,
+    patch_name: &str,
+    version: &semver::Version,
+    version_info: &patch::Version,
+    roms_scanner: rom::Scanner,
+) {
+    let is_running = patch_validator.is_running(patch_name, version);
+    ui.horizontal(|ui| {
+        ui.strong(version.to_string());
+        if ui
+            .add_enabled(
+                !is_running,
+                egui::Button::new(i18n::LOCALES.lookup(language, "patches-validate")).small(),
+            )
+            .on_hover_text(i18n::LOCALES.lookup(language, "patches-validate.description"))
+            .clicked()
+        {
+            let egui_ctx = ui.ctx().clone();
+            patch_validator.validate(
+                patches_path,
+                patch_name,
+                version,
+                version_info.supported_games.iter().cloned().collect(),
+                roms_scanner.clone(),
+                move || egui_ctx.request_repaint(),
+            );
+        }
+        if is_running {
+            ui.spinner();
+        }
+    });
+
+    ui.indent(("patch-details-version", patch_name, version.to_string()), |ui| {
+        ui.label(i18n::LOCALES.lookup_with_args(
+            language,
+            "patches-details-netplay-compatibility",
+            &std::collections::HashMap::from([(
+                "netplay_compatibility",
+                version_info.netplay_compatibility.clone().into(),
+            )]),
+        ));
+
+        for (key, dependencies) in [
+            (
+                "patches-details-requires",
+                version_info.requires.iter().map(|d| d.to_string()).join(", "),
+            ),
+            (
+                "patches-details-conflicts",
+                version_info.conflicts.iter().map(|d| d.to_string()).join(", "),
+            ),
+            (
+                "patches-details-includes",
+                version_info
+                    .includes
+                    .iter()
+                    .map(|i| format!("{} {}", i.name, i.version))
+                    .join(", "),
+            ),
+        ] {
+            if !dependencies.is_empty() {
+                ui.label(i18n::LOCALES.lookup_with_args(
+                    language,
+                    key,
+                    &std::collections::HashMap::from([("patches", dependencies.into())]),
+                ));
+            }
+        }
+
+        let colors = gui::theme::colors(ui.ctx());
+        let roms = roms_scanner.read();
+        let mut games = version_info.supported_games.iter().cloned().collect::<Vec<_>>();
+        game::sort_games(language, &mut games);
+        for game in games {
+            let (family, variant) = game.family_and_variant();
+            ui.horizontal(|ui| {
+                if roms.by_game.contains_key(&game) {
+                    ui.colored_label(colors.ready, "✔")
+                        .on_hover_text(i18n::LOCALES.lookup(language, "patches-details-rom.present"));
+                } else {
+                    ui.weak("✖")
+                        .on_hover_text(i18n::LOCALES.lookup(language, "patches-details-rom.missing"));
+                }
+                ui.label(i18n::LOCALES.lookup(language, &format!("game-{}.variant-{}", family, variant)));
+            });
+        }
+        drop(roms);
+
+        if !is_running {
+            show_validation(ui, language, patch_validator, patch_name, version);
+        }
+    });
+}
+
+fn show_validation(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    patch_validator: &patch::validation::Validator,
+    patch_name: &str,
+    version: &semver::Version,
+) {
+    let mut last_run = if let Some(last_run) = patch_validator.last_run(patch_name, version) {
+        last_run
+    } else {
+        return;
+    };
+    if last_run.is_empty() {
+        ui.weak(i18n::LOCALES.lookup(language, "patches-validation-no-roms"));
+        return;
+    }
+    last_run.sort_by_key(|(game, _)| game.family_and_variant());
+
+    let colors = gui::theme::colors(ui.ctx());
+    for (game, outcome) in last_run {
+        let (family, variant) = game.family_and_variant();
+        let game_name = i18n::LOCALES.lookup(language, &format!("game-{}.variant-{}", family, variant));
+        let (key, error, color) = match outcome.as_ref() {
+            Some(patch::validation::Outcome::Applies) => ("patches-validation-applies", None, colors.ready),
+            Some(patch::validation::Outcome::SourceChecksumMismatch) => {
+                ("patches-validation-source-checksum-mismatch", None, colors.warning)
+            }
+            Some(patch::validation::Outcome::TargetChecksumMismatch) => {
+                ("patches-validation-target-checksum-mismatch", None, colors.warning)
+            }
+            Some(patch::validation::Outcome::Failed(e)) => {
+                ("patches-validation-failed", Some(e.clone()), egui::Color32::RED)
+            }
+            None => ("patches-validation-stale", None, ui.visuals().weak_text_color()),
+        };
+        let mut args = std::collections::HashMap::from([("game_name", game_name.into())]);
+        if let Some(error) = error {
+            args.insert("error", error.into());
+        }
+        ui.colored_label(color, i18n::LOCALES.lookup_with_args(language, key, &args));
+    }
+}
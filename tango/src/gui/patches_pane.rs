@@ -1,42 +1,76 @@
-use fluent_templates::Loader;
+use crate::{gui, i18n, net, patch, rom, sync};
 
-use crate::{game, i18n, patch, sync};
-
-pub struct State {}
+pub struct State {
+    create_patch_window: Option<gui::create_patch_window::State>,
+    install_patch_window: Option<gui::install_patch_window::State>,
+}
 
 impl State {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            create_patch_window: None,
+            install_patch_window: None,
+        }
+    }
+
+    /// Opens the install patch window, optionally with an archive already chosen.
+    pub fn open_install_patch_window(&mut self, archive_path: Option<std::path::PathBuf>) {
+        self.install_patch_window = Some(gui::install_patch_window::State::new(archive_path));
+    }
+
+    pub fn is_installing(&self) -> bool {
+        self.install_patch_window.is_some()
     }
 }
 
 pub fn show(
     ui: &mut egui::Ui,
-    _state: &mut State,
+    state: &mut State,
     language: &unic_langid::LanguageIdentifier,
     repo_url: &str,
+    network: &net::proxy::Settings,
     patch_selection: &mut Option<String>,
     patches_path: &std::path::Path,
     patches_scanner: patch::Scanner,
+    roms_scanner: rom::Scanner,
+    patch_validator: &patch::validation::Validator,
+    show_debug: bool,
 ) {
+    gui::create_patch_window::show(
+        ui.ctx(),
+        language,
+        &mut state.create_patch_window,
+        roms_scanner.clone(),
+        patches_path,
+        patches_scanner.clone(),
+        patch_selection,
+    );
+
+    gui::install_patch_window::show(
+        ui.ctx(),
+        language,
+        &mut state.install_patch_window,
+        patches_path,
+        patches_scanner.clone(),
+        patch_selection,
+    );
+
     egui::TopBottomPanel::top("patches-window-top-panel").show_inside(ui, |ui| {
         ui.horizontal(|ui| {
             ui.add_enabled_ui(!patches_scanner.is_scanning(), |ui| {
                 if ui
-                    .button(format!(
-                        "🔄 {}",
-                        i18n::LOCALES.lookup(language, "patches-update").unwrap()
-                    ))
+                    .button(format!("🔄 {}", i18n::LOCALES.lookup(language, "patches-update")))
                     .clicked()
                 {
                     let egui_ctx = ui.ctx().clone();
                     tokio::task::spawn_blocking({
                         let patches_scanner = patches_scanner.clone();
                         let repo_url = repo_url.to_owned();
+                        let network = network.clone();
                         let patches_path = patches_path.to_path_buf();
                         move || {
                             patches_scanner.rescan(move || {
-                                if let Err(e) = sync::block_on(patch::update(&repo_url, &patches_path)) {
+                                if let Err(e) = sync::block_on(patch::update(&repo_url, &patches_path, &network)) {
                                     log::error!("failed to update patches: {:?}", e);
                                 }
                                 patch::scan(&patches_path).ok()
@@ -50,6 +84,24 @@ pub fn show(
             if patches_scanner.is_scanning() {
                 ui.spinner();
             }
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .button(format!("📦 {}", i18n::LOCALES.lookup(language, "patches-install")))
+                    .clicked()
+                {
+                    state.open_install_patch_window(None);
+                }
+
+                if show_debug {
+                    if ui
+                        .button(format!("🛠 {}", i18n::LOCALES.lookup(language, "patches-create")))
+                        .clicked()
+                    {
+                        state.create_patch_window = Some(gui::create_patch_window::State::new());
+                    }
+                }
+            });
         });
     });
 
@@ -73,8 +125,11 @@ pub fn show(
     });
 
     egui::CentralPanel::default().show_inside(ui, |ui| {
-        let patch = if let Some(patch) = patch_selection.as_ref().and_then(|n| patches.get(n)) {
-            patch
+        let (patch_name, patch) = if let Some((name, patch)) = patch_selection
+            .as_ref()
+            .and_then(|n| patches.get(n).map(|patch| (n, patch)))
+        {
+            (name, patch)
         } else {
             egui::ScrollArea::vertical()
                 .auto_shrink([false, false])
@@ -83,118 +138,14 @@ pub fn show(
             return;
         };
 
-        ui.with_layout(
-            egui::Layout::top_down_justified(egui::Align::Min).with_main_justify(true),
-            |ui| {
-                ui.horizontal_wrapped(|ui| {
-                    let latest_version_and_info = patch.versions.iter().max_by_key(|(k, _)| *k);
-
-                    ui.vertical(|ui| {
-                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
-                            if ui
-                                .button(format!(
-                                    "📂 {}",
-                                    i18n::LOCALES.lookup(language, "patches-open-folder").unwrap(),
-                                ))
-                                .clicked()
-                            {
-                                let _ = open::that(&patch.path);
-                            }
-
-                            ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
-                                ui.horizontal(|ui| {
-                                    ui.with_layout(
-                                        egui::Layout::left_to_right(egui::Align::Max).with_main_wrap(true),
-                                        |ui| {
-                                            ui.heading(&patch.title);
-                                            if let Some((version, _)) = latest_version_and_info.as_ref() {
-                                                ui.label(version.to_string());
-                                            }
-                                        },
-                                    );
-                                });
-                            });
-                        });
-                        egui::Grid::new("patch-info-grid").num_columns(2).show(ui, |ui| {
-                            ui.with_layout(
-                                egui::Layout::left_to_right(egui::Align::Min).with_cross_justify(true),
-                                |ui| {
-                                    ui.strong(i18n::LOCALES.lookup(language, "patches-details-authors").unwrap());
-                                },
-                            );
-                            ui.vertical(|ui| {
-                                for author in patch.authors.iter() {
-                                    let name = author.display_name.as_ref().unwrap_or(&author.addr);
-                                    if author.addr == "" {
-                                        ui.label(name);
-                                    } else {
-                                        ui.hyperlink_to(name, format!("mailto:{}", author.addr));
-                                    }
-                                }
-                            });
-                            ui.end_row();
-
-                            ui.with_layout(
-                                egui::Layout::left_to_right(egui::Align::Min).with_cross_justify(true),
-                                |ui| {
-                                    ui.strong(i18n::LOCALES.lookup(language, "patches-details-license").unwrap());
-                                },
-                            );
-                            if let Some(license) = patch.license.as_ref() {
-                                ui.label(license);
-                            } else {
-                                ui.label(
-                                    i18n::LOCALES
-                                        .lookup(language, "patches-details-license.all-rights-reserved")
-                                        .unwrap(),
-                                );
-                            }
-                            ui.end_row();
-
-                            if let Some(source) = patch.source.as_ref() {
-                                ui.with_layout(
-                                    egui::Layout::left_to_right(egui::Align::Min).with_cross_justify(true),
-                                    |ui| {
-                                        ui.strong(i18n::LOCALES.lookup(language, "patches-details-source").unwrap());
-                                    },
-                                );
-                                ui.hyperlink_to("🌐", source);
-                                ui.end_row();
-                            }
-
-                            if let Some((_, version_info)) = latest_version_and_info.as_ref() {
-                                ui.with_layout(
-                                    egui::Layout::left_to_right(egui::Align::Min).with_cross_justify(true),
-                                    |ui| {
-                                        ui.strong(i18n::LOCALES.lookup(language, "patches-details-games").unwrap());
-                                    },
-                                );
-                                ui.vertical(|ui| {
-                                    let mut games = version_info.supported_games.iter().cloned().collect::<Vec<_>>();
-                                    game::sort_games(language, &mut games);
-                                    for game in games.iter() {
-                                        let (family, variant) = game.family_and_variant();
-                                        ui.label(
-                                            i18n::LOCALES
-                                                .lookup(language, &format!("game-{}.variant-{}", family, variant))
-                                                .unwrap(),
-                                        );
-                                    }
-                                });
-                                ui.end_row();
-                            }
-                        });
-                        ui.separator();
-
-                        egui::ScrollArea::vertical()
-                            .auto_shrink([false, false])
-                            .id_source("patch-window-readme")
-                            .show(ui, |ui| {
-                                ui.monospace(patch.readme.clone().unwrap_or("".to_string()));
-                            });
-                    });
-                });
-            },
+        gui::patch_details::show(
+            ui,
+            language,
+            patches_path,
+            patch_name,
+            patch,
+            roms_scanner.clone(),
+            patch_validator,
         );
     });
 }
@@ -1,12 +1,15 @@
-use fluent_templates::Loader;
+use itertools::Itertools;
 use rand::RngCore;
-use sha3::digest::{ExtendableOutput, Update};
 use subtle::ConstantTimeEq;
 
-use crate::{audio, config, discord, game, gui, i18n, net, patch, randomcode, rom, save, session, stats, sync};
+use crate::{
+    audio, bios, broadcast, config, contentpolicy, desyncrisk, determinism, discord, fairness, game, gui, i18n, input,
+    linkcode, net, opponents, patch, randomcode, rom, save, scanner, session, stats, sync,
+};
 
 pub enum Warning {
     Incompatible,
+    ConfigurationMismatch(Vec<determinism::Difference>),
     UnrecognizedGame,
     NoLocalSelection,
     NoRemoteSelection,
@@ -15,93 +18,395 @@ pub enum Warning {
     NoRemoteROM(&'static (dyn game::Game + Send + Sync)),
     NoRemotePatch(String, semver::Version),
     NoRemotePatches(String),
+    PatchDoesNotApply,
+    UnmetPatchDependencies(Vec<patch::DependencyProblem>),
+    PatchRequirementsMismatch(String),
+    MissingRemoteCapabilities(u32),
 }
 
 impl Warning {
     pub fn description(&self, language: &unic_langid::LanguageIdentifier) -> String {
         match self {
-            Warning::Incompatible => i18n::LOCALES.lookup(language, "lobby-issue-incompatible").unwrap(),
-            Warning::UnrecognizedGame => i18n::LOCALES.lookup(language, "lobby-issue-unrecognized-game").unwrap(),
-            Warning::NoLocalSelection => i18n::LOCALES
-                .lookup(language, "lobby-issue-no-local-selection")
-                .unwrap(),
-            Warning::NoRemoteSelection => i18n::LOCALES
-                .lookup(language, "lobby-issue-no-remote-selection")
-                .unwrap(),
-            Warning::NoLocalROM(game) => i18n::LOCALES
-                .lookup_with_args(
-                    language,
-                    "lobby-issue-no-local-rom",
-                    &std::collections::HashMap::from([(
-                        "game_name",
-                        i18n::LOCALES
-                            .lookup(
-                                language,
-                                &format!(
-                                    "game-{}.variant-{}",
-                                    game.family_and_variant().0,
-                                    game.family_and_variant().1
-                                ),
-                            )
-                            .unwrap()
-                            .into(),
-                    )]),
-                )
-                .unwrap(),
-            Warning::NoLocalPatch(name, version) => i18n::LOCALES
-                .lookup_with_args(
-                    language,
-                    "lobby-issue-no-local-patch",
-                    &std::collections::HashMap::from([
-                        ("patch_name", name.as_str().into()),
-                        ("patch_version", version.to_string().into()),
-                    ]),
-                )
-                .unwrap(),
-            Warning::NoRemoteROM(game) => i18n::LOCALES
-                .lookup_with_args(
-                    language,
-                    "lobby-issue-no-remote-rom",
-                    &std::collections::HashMap::from([(
-                        "game_name",
-                        i18n::LOCALES
-                            .lookup(
-                                language,
-                                &format!(
-                                    "game-{}.variant-{}",
-                                    game.family_and_variant().0,
-                                    game.family_and_variant().1
-                                ),
-                            )
-                            .unwrap()
-                            .into(),
-                    )]),
-                )
-                .unwrap(),
-            Warning::NoRemotePatch(name, version) => i18n::LOCALES
-                .lookup_with_args(
+            Warning::Incompatible => i18n::LOCALES.lookup(language, "lobby-issue-incompatible"),
+            Warning::ConfigurationMismatch(differences) => i18n::LOCALES.lookup_with_args(
+                language,
+                "lobby-issue-configuration-mismatch",
+                &std::collections::HashMap::from([(
+                    "differences",
+                    determinism::describe_differences(differences, language).into(),
+                )]),
+            ),
+            Warning::UnrecognizedGame => i18n::LOCALES.lookup(language, "lobby-issue-unrecognized-game"),
+            Warning::NoLocalSelection => i18n::LOCALES.lookup(language, "lobby-issue-no-local-selection"),
+            Warning::NoRemoteSelection => i18n::LOCALES.lookup(language, "lobby-issue-no-remote-selection"),
+            Warning::NoLocalROM(game) => i18n::LOCALES.lookup_with_args(
+                language,
+                "lobby-issue-no-local-rom",
+                &std::collections::HashMap::from([(
+                    "game_name",
+                    i18n::LOCALES
+                        .lookup(
+                            language,
+                            &format!(
+                                "game-{}.variant-{}",
+                                game.family_and_variant().0,
+                                game.family_and_variant().1
+                            ),
+                        )
+                        .into(),
+                )]),
+            ),
+            Warning::NoLocalPatch(name, version) => i18n::LOCALES.lookup_with_args(
+                language,
+                "lobby-issue-no-local-patch",
+                &std::collections::HashMap::from([
+                    ("patch_name", name.as_str().into()),
+                    ("patch_version", version.to_string().into()),
+                ]),
+            ),
+            Warning::NoRemoteROM(game) => i18n::LOCALES.lookup_with_args(
+                language,
+                "lobby-issue-no-remote-rom",
+                &std::collections::HashMap::from([(
+                    "game_name",
+                    i18n::LOCALES
+                        .lookup(
+                            language,
+                            &format!(
+                                "game-{}.variant-{}",
+                                game.family_and_variant().0,
+                                game.family_and_variant().1
+                            ),
+                        )
+                        .into(),
+                )]),
+            ),
+            Warning::NoRemotePatch(name, version) => i18n::LOCALES.lookup_with_args(
+                language,
+                "lobby-issue-no-remote-patch",
+                &std::collections::HashMap::from([
+                    ("patch_name", name.as_str().into()),
+                    ("patch_version", version.to_string().into()),
+                ]),
+            ),
+            Warning::NoRemotePatches(name) => i18n::LOCALES.lookup_with_args(
+                language,
+                "lobby-issue-no-remote-patches",
+                &std::collections::HashMap::from([("patch_name", name.as_str().into())]),
+            ),
+            Warning::PatchDoesNotApply => i18n::LOCALES.lookup(language, "lobby-issue-patch-does-not-apply"),
+            Warning::UnmetPatchDependencies(problems) => i18n::LOCALES.lookup_with_args(
+                language,
+                "lobby-issue-unmet-patch-dependencies",
+                &std::collections::HashMap::from([(
+                    "problems",
+                    describe_dependency_problems(language, problems).into(),
+                )]),
+            ),
+            Warning::PatchRequirementsMismatch(name) => i18n::LOCALES.lookup_with_args(
+                language,
+                "lobby-issue-patch-requirements-mismatch",
+                &std::collections::HashMap::from([("patch_name", name.as_str().into())]),
+            ),
+            Warning::MissingRemoteCapabilities(bits) => i18n::LOCALES.lookup_with_args(
+                language,
+                "lobby-issue-missing-remote-capabilities",
+                &std::collections::HashMap::from([("capabilities", describe_capabilities(language, *bits).into())]),
+            ),
+        }
+    }
+}
+
+fn describe_dependency_problems(
+    language: &unic_langid::LanguageIdentifier,
+    problems: &[patch::DependencyProblem],
+) -> String {
+    problems
+        .iter()
+        .map(|problem| match problem {
+            patch::DependencyProblem::Missing(dependency) => i18n::LOCALES.lookup_with_args(
+                language,
+                "patch-dependency-missing",
+                &std::collections::HashMap::from([("dependency", dependency.to_string().into())]),
+            ),
+            patch::DependencyProblem::Conflict { patch_name, conflict } => i18n::LOCALES.lookup_with_args(
+                language,
+                "patch-dependency-conflict",
+                &std::collections::HashMap::from([
+                    ("patch_name", patch_name.as_str().into()),
+                    ("dependency", conflict.to_string().into()),
+                ]),
+            ),
+            patch::DependencyProblem::Cycle(patch_name) => i18n::LOCALES.lookup_with_args(
+                language,
+                "patch-dependency-cycle",
+                &std::collections::HashMap::from([("patch_name", patch_name.as_str().into())]),
+            ),
+        })
+        .join("\n")
+}
+
+fn describe_capabilities(language: &unic_langid::LanguageIdentifier, bits: u32) -> String {
+    game::Capabilities::FEATURES
+        .iter()
+        .filter(|(bit, _)| bits & bit != 0)
+        .map(|(_, key)| i18n::LOCALES.lookup(language, key))
+        .join(", ")
+}
+
+/// Shows what netplay supports for the selected game in a single line, with any known issues after it.
+fn show_capabilities_summary(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    game: &'static (dyn game::Game + Send + Sync),
+) {
+    let capabilities = game.hooks().capabilities();
+    let bits = capabilities.bits();
+    ui.horizontal_wrapped(|ui| {
+        ui.weak(i18n::LOCALES.lookup(language, "play-capabilities"));
+        for (bit, key) in game::Capabilities::FEATURES {
+            let text = i18n::LOCALES.lookup(language, key);
+            if bits & bit != 0 {
+                ui.label(egui::RichText::new(format!("✔ {}", text)).color(gui::theme::colors(ui.ctx()).ready));
+            } else {
+                ui.weak(format!("✖ {}", text));
+            }
+        }
+        if !capabilities.known_issues.is_empty() {
+            gui::warning::show(
+                ui,
+                capabilities
+                    .known_issues
+                    .iter()
+                    .map(|key| i18n::LOCALES.lookup(language, key))
+                    .join("\n"),
+            );
+        }
+    });
+}
+
+fn describe_save_requirement(
+    language: &unic_langid::LanguageIdentifier,
+    selection: &gui::Selection,
+    requirement: &patch::SaveRequirement,
+) -> String {
+    if let Some(description) = requirement.description.as_ref() {
+        return description.clone();
+    }
+
+    match &requirement.kind {
+        patch::SaveRequirementKind::Flag { flag } => i18n::LOCALES.lookup_with_args(
+            language,
+            "play-save-requirement-flag",
+            &std::collections::HashMap::from([("flag", format!("0x{:04x}", flag).into())]),
+        ),
+        patch::SaveRequirementKind::LibraryCount { count } => i18n::LOCALES.lookup_with_args(
+            language,
+            "play-save-requirement-library-count",
+            &std::collections::HashMap::from([("count", (*count).into())]),
+        ),
+        patch::SaveRequirementKind::BannedChips { chips } => i18n::LOCALES.lookup_with_args(
+            language,
+            "play-save-requirement-banned-chips",
+            &std::collections::HashMap::from([(
+                "chips",
+                chips
+                    .iter()
+                    .map(|id| {
+                        selection
+                            .assets
+                            .as_ref()
+                            .and_then(|assets| assets.chip(*id))
+                            .map(|chip| chip.name())
+                            .unwrap_or_else(|| format!("#{}", id))
+                    })
+                    .join(", ")
+                    .into(),
+            )]),
+        ),
+    }
+}
+
+fn show_save_requirements_banner(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    selection: &gui::Selection,
+) {
+    if selection.unmet_save_requirements.is_empty() {
+        return;
+    }
+
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.set_width(ui.available_width());
+        let mut layout_job = egui::text::LayoutJob::default();
+        gui::warning::append_to_layout_job(ui, &mut layout_job);
+        layout_job.append(
+            &i18n::LOCALES.lookup(language, "play-save-requirements-unmet"),
+            0.0,
+            egui::TextFormat::simple(
+                ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
+                ui.visuals().strong_text_color(),
+            ),
+        );
+        ui.label(layout_job);
+        for unmet in selection.unmet_save_requirements.iter() {
+            let mut text = format!(
+                "• {}",
+                describe_save_requirement(language, selection, &unmet.requirement)
+            );
+            if !unmet.checked {
+                text.push(' ');
+                text.push_str(&i18n::LOCALES.lookup(language, "play-save-requirement-unchecked"));
+            } else if unmet.requirement.hard {
+                text.push(' ');
+                text.push_str(&i18n::LOCALES.lookup(language, "play-save-requirement-hard"));
+            }
+            ui.label(text);
+        }
+    });
+}
+
+/// Offers to migrate the save if it was last played with an older version of the selected patch that numbered things differently.
+fn show_save_migration_banner(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    selection: &mut gui::Selection,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+    save_patch_history: &save::migration::History,
+    save_backups_path: &std::path::Path,
+) {
+    if let Some(result) = selection.save_migration.as_ref() {
+        let colors = gui::theme::colors(ui.ctx());
+        match result {
+            Ok(backup_path) => {
+                ui.colored_label(
+                    colors.ready,
+                    i18n::LOCALES.lookup_with_args(
+                        language,
+                        "play-save-migration.done",
+                        &std::collections::HashMap::from([("backup", backup_path.display().to_string().into())]),
+                    ),
+                );
+            }
+            Err(e) => {
+                ui.colored_label(
+                    colors.warning,
+                    i18n::LOCALES.lookup_with_args(
+                        language,
+                        "play-save-migration.error",
+                        &std::collections::HashMap::from([("error", e.clone().into())]),
+                    ),
+                );
+            }
+        }
+        return;
+    }
+
+    let (patch_name, version) = if let Some((patch_name, version, _)) = selection.patch.as_ref() {
+        (patch_name.clone(), version.clone())
+    } else {
+        return;
+    };
+    let last_used = if let Some(last_used) = save_patch_history.get(&selection.save.path) {
+        last_used
+    } else {
+        return;
+    };
+    if last_used.patch != patch_name || last_used.version >= version {
+        return;
+    }
+    // Versions that didn't renumber anything don't ship a migration, and saves carry over as they are.
+    let table = if let Some(table) = patches
+        .get(&patch_name)
+        .and_then(|patch| patch.migration(&last_used.version, &version))
+    {
+        table
+    } else {
+        return;
+    };
+
+    let unmapped = save::migration::find_unmapped(selection.save.save.as_ref(), &table);
+
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.set_width(ui.available_width());
+        let mut layout_job = egui::text::LayoutJob::default();
+        gui::warning::append_to_layout_job(ui, &mut layout_job);
+        layout_job.append(
+            &i18n::LOCALES.lookup_with_args(
+                language,
+                "play-save-migration",
+                &std::collections::HashMap::from([("from", last_used.version.to_string().into())]),
+            ),
+            0.0,
+            egui::TextFormat::simple(
+                ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
+                ui.visuals().strong_text_color(),
+            ),
+        );
+        ui.label(layout_job);
+
+        if !unmapped.is_empty() {
+            ui.colored_label(
+                gui::theme::colors(ui.ctx()).warning,
+                i18n::LOCALES.lookup_with_args(
                     language,
-                    "lobby-issue-no-remote-patch",
+                    "play-save-migration.unmapped",
                     &std::collections::HashMap::from([
-                        ("patch_name", name.as_str().into()),
-                        ("patch_version", version.to_string().into()),
+                        ("to", version.to_string().into()),
+                        ("ids", unmapped.iter().join(", ").into()),
                     ]),
-                )
-                .unwrap(),
-            Warning::NoRemotePatches(name) => i18n::LOCALES
-                .lookup_with_args(
-                    language,
-                    "lobby-issue-no-remote-patches",
-                    &std::collections::HashMap::from([("patch_name", name.as_str().into())]),
-                )
-                .unwrap(),
+                ),
+            );
+            return;
         }
-    }
+
+        if !ui
+            .button(i18n::LOCALES.lookup_with_args(
+                language,
+                "play-save-migration.run",
+                &std::collections::HashMap::from([("to", version.to_string().into())]),
+            ))
+            .clicked()
+        {
+            return;
+        }
+
+        let result = save::migration::migrate(selection.save.save.as_ref(), &table).and_then(|migrated| {
+            save::migration::write_with_backup(
+                &selection.save.path,
+                save_backups_path,
+                &last_used.version,
+                &migrated.to_vec(),
+            )
+        });
+        selection.save_migration = Some(match result {
+            Ok(backup_path) => {
+                log::info!(
+                    "migrated {} from v{} to v{}, backed up to {}",
+                    selection.save.path.display(),
+                    last_used.version,
+                    version,
+                    backup_path.display()
+                );
+                if let Err(e) = save_patch_history.record(&selection.save.path, Some((&patch_name, &version))) {
+                    log::error!("failed to record save patch history: {:?}", e);
+                }
+                if let Err(e) = selection.reload_save() {
+                    log::error!("failed to reload migrated save: {:?}", e);
+                }
+                Ok(backup_path)
+            }
+            Err(e) => {
+                log::error!("failed to migrate {}: {:?}", selection.save.path.display(), e);
+                Err(e.to_string())
+            }
+        });
+    });
 }
 
 fn make_warning(
     lobby: &Lobby,
-    roms: &std::collections::HashMap<&'static (dyn game::Game + Send + Sync), Vec<u8>>,
+    roms: &std::collections::HashMap<&'static (dyn game::Game + Send + Sync), std::sync::Arc<[u8]>>,
     patches: &std::collections::BTreeMap<String, patch::Patch>,
 ) -> Option<Warning> {
     let local_selection = if let Some(local_selection) = lobby.local_selection.as_ref() {
@@ -143,6 +448,10 @@ fn make_warning(
         }) {
             return Some(Warning::NoLocalPatch(pi.name.clone(), pi.version.clone()));
         }
+
+        if !has_matching_requirements(remote_game, pi, &patches) {
+            return Some(Warning::PatchRequirementsMismatch(pi.name.clone()));
+        }
     }
 
     if let Some((patch_name, patch_version, _)) = local_selection.patch.as_ref() {
@@ -175,36 +484,133 @@ fn make_warning(
         return Some(Warning::Incompatible);
     }
 
+    // Both sides pick the battle background, so they must be picking from the same pool.
+    if get_battle_backgrounds(
+        local_selection
+            .patch
+            .as_ref()
+            .map(|(name, version, _)| (name.as_str(), version)),
+        &patches,
+    ) != get_battle_backgrounds(
+        remote_gi.patch.as_ref().map(|pi| (pi.name.as_str(), &pi.version)),
+        &patches,
+    ) {
+        return Some(Warning::Incompatible);
+    }
+
+    if let (Some(local_fingerprint), Some(remote_fingerprint)) = (
+        lobby.local_fingerprint.as_ref(),
+        lobby.remote_settings.fingerprint.as_ref(),
+    ) {
+        let differences = local_fingerprint.differences(remote_fingerprint);
+        if !differences.is_empty() {
+            return Some(Warning::ConfigurationMismatch(differences));
+        }
+    }
+
+    let missing_capabilities = local_selection.game.hooks().capabilities().bits() & !lobby.remote_settings.capabilities;
+    if missing_capabilities != 0 {
+        return Some(Warning::MissingRemoteCapabilities(missing_capabilities));
+    }
+
     None
 }
 struct LocalSelection {
     pub game: &'static (dyn game::Game + Send + Sync),
     pub save: Box<dyn save::Save + Send + Sync>,
-    pub rom: Vec<u8>,
+    pub save_path: std::path::PathBuf,
+    /// The save file as it was when it was last read, to tell if it's been changed since.
+    pub save_stamp: Option<scanner::FileStamp>,
+    pub rom: std::sync::Arc<[u8]>,
+    /// The hash of the ROM as patched, which the opponent checks their copy of it against.
+    pub rom_hash: [u8; 16],
     pub patch: Option<(String, semver::Version, patch::Version)>,
 }
 
+impl LocalSelection {
+    /// Checks the save for values the game could never have produced by itself, against the ROM it'll be played with.
+    fn validate_save(&self) -> Result<(), gui::ReloadSaveError> {
+        let assets = self
+            .game
+            .load_rom_assets(
+                &self.rom,
+                self.save.as_raw_wram(),
+                &self
+                    .patch
+                    .as_ref()
+                    .map(|(_, _, metadata)| metadata.rom_overrides.clone())
+                    .unwrap_or_default(),
+            )
+            .map_err(gui::ReloadSaveError::Invalid)?;
+        self.save.validate(assets.as_ref())?;
+        Ok(())
+    }
+}
+
+fn save_file_stamp(path: &std::path::Path) -> Option<scanner::FileStamp> {
+    std::fs::metadata(path)
+        .ok()
+        .and_then(|metadata| scanner::FileStamp::of(&metadata))
+}
+
 struct RemoteSelection {
     pub game: &'static (dyn game::Game + Send + Sync),
-    pub rom: Vec<u8>,
+    pub rom: std::sync::Arc<[u8]>,
+    /// The hash of the ROM as we patched it, to check against the one the opponent sent.
+    pub rom_hash: [u8; 16],
     pub patch: Option<(String, semver::Version, patch::Version)>,
 }
 
 struct Lobby {
-    attention_requested: bool,
+    pending_notifications: Vec<gui::notifications::Event>,
+    /// Whether the opponent's first settings have come in, which is when they're announced as having joined: before then, we don't know their nickname.
+    joined_notified: bool,
+    compatible_notified: bool,
     link_code: String,
-    sender: Option<net::Sender>,
+    /// Packets waiting for the connection task to send them. This is taken once the lobby ends.
+    outgoing: Option<tokio::sync::mpsc::UnboundedSender<net::protocol::Packet>>,
     local_selection: Option<LocalSelection>,
     remote_selection: Option<RemoteSelection>,
     nickname: String,
     match_type: (u8, u8),
+    round_time_limit_secs: Option<u32>,
+    input_delay: u32,
+    /// Extra input delay this side takes on, for when the opponent's connection is much worse.
+    input_delay_handicap: u32,
+    replay_recording: net::protocol::ReplayRecording,
+    /// Set while asking whether the match may be recorded, before readying up.
+    asking_replay_consent: bool,
+    /// Whether the factors behind the desync risk are being shown.
+    showing_desync_risk: bool,
+    /// Set while asking the player to confirm readying up despite a high desync risk.
+    confirming_desync_risk: bool,
+    /// Whether the player has already confirmed readying up despite a high desync risk, so they aren't asked again until it goes down and back up.
+    desync_risk_acknowledged: bool,
     reveal_setup: bool,
+    /// What the opponent has of our settings, which the next change is sent as a delta from if they take deltas.
+    sent_settings: Option<net::protocol::Settings>,
+    settings_seq: u32,
     remote_settings: net::protocol::Settings,
+    remote_client_info: Option<net::protocol::ClientInfo>,
+    remote_installation_id: Option<String>,
     remote_commitment: Option<[u8; 16]>,
+    opponents: opponents::Registry,
+    /// Who the opponent is in the registry, worked out again whenever their nickname changes.
+    opponent: opponents::Recognition,
+    opponent_notes: String,
     latencies: stats::DeltaCounter,
     local_negotiated_state: Option<(net::protocol::NegotiatedState, Vec<u8>)>,
+    local_fingerprint: Option<determinism::Fingerprint>,
+    delay_preview: Option<gui::delay_preview::State>,
+    /// A match type that was picked but is flagged as unsupported, waiting for the player to confirm it.
+    unsupported_match_type: Option<(u8, u8)>,
+    save_changed_on_disk: bool,
+    save_reload_error: Option<gui::ReloadSaveError>,
+    save_checked_at: std::time::Instant,
     roms_scanner: rom::Scanner,
     patches_scanner: patch::Scanner,
+    patched_roms: patch::cache::PatchedRoms,
+    broadcast_server: broadcast::Server,
 }
 
 pub fn get_netplay_compatibility(
@@ -223,6 +629,38 @@ pub fn get_netplay_compatibility(
     }
 }
 
+/// Gets the battle backgrounds a patch version picks from instead of the game's own, if it does.
+pub fn get_battle_backgrounds(
+    patch: Option<(&str, &semver::Version)>,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+) -> Option<Vec<u8>> {
+    let (name, version) = patch?;
+    patches.get(name)?.versions.get(version)?.battle_backgrounds.clone()
+}
+
+/// Makes the patch info sent to the opponent, along with the patches that are applied before it.
+fn make_patch_info(
+    game: &'static (dyn game::Game + Send + Sync),
+    name: &str,
+    version: &semver::Version,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+) -> net::protocol::PatchInfo {
+    net::protocol::PatchInfo {
+        name: name.to_string(),
+        version: version.clone(),
+        requirements: patch::resolve_requirements(patches, name, version, game).unwrap_or_default(),
+    }
+}
+
+/// Whether the patches applied before a patch resolve to the same ones here as they did for whoever sent it.
+fn has_matching_requirements(
+    game: &'static (dyn game::Game + Send + Sync),
+    pi: &net::protocol::PatchInfo,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+) -> bool {
+    patch::resolve_requirements(patches, &pi.name, &pi.version, game).as_ref() == Ok(&pi.requirements)
+}
+
 pub fn get_netplay_compatibility_from_game_info(
     g: &net::protocol::GameInfo,
     patches: &std::collections::BTreeMap<String, patch::Patch>,
@@ -289,10 +727,24 @@ fn are_settings_compatible(
         }
     }
 
+    if [local_game_info, remote_game_info].iter().any(|gi| {
+        match (
+            game::find_by_family_and_variant(&gi.family_and_variant.0, gi.family_and_variant.1),
+            gi.patch.as_ref(),
+        ) {
+            (Some(game), Some(pi)) => !has_matching_requirements(game, pi, patches),
+            _ => false,
+        }
+    }) {
+        return false;
+    }
+
     #[derive(PartialEq)]
     struct SimplifiedSettings {
         netplay_compatibility: Option<String>,
+        battle_backgrounds: Option<Vec<u8>>,
         match_type: (u8, u8),
+        round_time_limit_secs: Option<u32>,
     }
 
     impl SimplifiedSettings {
@@ -302,11 +754,27 @@ fn are_settings_compatible(
                     .game_info
                     .as_ref()
                     .and_then(|gi| get_netplay_compatibility_from_game_info(gi, patches)),
+                battle_backgrounds: settings.game_info.as_ref().and_then(|gi| {
+                    get_battle_backgrounds(gi.patch.as_ref().map(|pi| (pi.name.as_str(), &pi.version)), patches)
+                }),
                 match_type: settings.match_type,
+                round_time_limit_secs: settings.round_time_limit_secs,
             }
         }
     }
 
+    if local_settings.fingerprint.is_none() || local_settings.fingerprint != remote_settings.fingerprint {
+        return false;
+    }
+
+    // Delays past these can't have come from the lobby UI, and may not fit in the other side's input queue.
+    if [local_settings, remote_settings].iter().any(|settings| {
+        settings.input_delay > net::protocol::MAX_INPUT_DELAY
+            || settings.input_delay_handicap > net::protocol::MAX_INPUT_DELAY_HANDICAP
+    }) {
+        return false;
+    }
+
     let local_simplified_settings = SimplifiedSettings::new(&local_settings, patches);
     let remote_simplified_settings = SimplifiedSettings::new(&remote_settings, patches);
 
@@ -315,49 +783,37 @@ fn are_settings_compatible(
         && local_simplified_settings == remote_simplified_settings
 }
 
-fn make_commitment(buf: &[u8]) -> [u8; 16] {
-    let mut shake128 = sha3::Shake128::default();
-    shake128.update(b"tango:lobby:");
-    shake128.update(buf);
-    let mut commitment = [0u8; 16];
-    shake128.finalize_xof_into(&mut commitment);
-    commitment
-}
-
 impl Lobby {
-    async fn uncommit(&mut self) -> Result<(), anyhow::Error> {
-        let sender = if let Some(sender) = self.sender.as_mut() {
-            sender
-        } else {
-            anyhow::bail!("no sender?")
-        };
+    /// Queues a packet for the connection task to send.
+    ///
+    /// The lobby is locked from the GUI thread every frame, so it must never be held while waiting on the network: sending is left to the connection task instead.
+    fn send(&self, packet: net::protocol::Packet) {
+        if let Some(outgoing) = self.outgoing.as_ref() {
+            let _ = outgoing.send(packet);
+        }
+    }
 
-        sender.send_uncommit().await?;
+    fn uncommit(&mut self) {
+        self.send(net::protocol::Packet::Uncommit(net::protocol::Uncommit {}));
         self.local_negotiated_state = None;
-        Ok(())
     }
 
-    async fn commit(&mut self, save_data: &[u8]) -> Result<(), anyhow::Error> {
+    fn commit(&mut self, save_data: &[u8]) -> Result<(), anyhow::Error> {
+        // The preview has to be gone before the match can start, so it can't get between the player and the real inputs.
+        self.delay_preview = None;
+
         let mut nonce = [0u8; 16];
         rand::thread_rng().fill_bytes(&mut nonce);
         let negotiated_state = net::protocol::NegotiatedState {
             nonce: nonce.clone(),
             save_data: save_data.to_vec(),
         };
-        let buf = zstd::stream::encode_all(
-            &net::protocol::NegotiatedState::serialize(&negotiated_state).unwrap()[..],
-            0,
-        )?;
-        let commitment = make_commitment(&buf);
+        let buf = negotiated_state.encode()?;
+        let commitment = fairness::commitment(&buf);
 
         log::info!("nonce = {:02x?}, commitment = {:02x?}", nonce, commitment);
 
-        let sender = if let Some(sender) = self.sender.as_mut() {
-            sender
-        } else {
-            anyhow::bail!("no sender?")
-        };
-        sender.send_commit(commitment).await?;
+        self.send(net::protocol::Packet::Commit(net::protocol::Commit { commitment }));
         self.local_negotiated_state = Some((negotiated_state, buf));
         Ok(())
     }
@@ -376,67 +832,143 @@ impl Lobby {
                     patch: local_selection
                         .patch
                         .as_ref()
-                        .map(|(name, version, _)| net::protocol::PatchInfo {
-                            name: name.clone(),
-                            version: version.clone(),
-                        }),
+                        .map(|(name, version, _)| make_patch_info(local_selection.game, name, version, &patches)),
+                    rom_hash: Some(local_selection.rom_hash),
                 }
             }),
             available_games: roms
+                .by_game
                 .keys()
                 .map(|g| {
                     let (family, variant) = g.family_and_variant();
                     (family.to_string(), variant)
                 })
                 .collect(),
+            // Versions this build is too old for can't be played, so they aren't offered to the opponent either.
             available_patches: patches
                 .iter()
-                .map(|(p, info)| (p.clone(), info.versions.keys().cloned().collect()))
+                .map(|(p, info)| {
+                    (
+                        p.clone(),
+                        info.versions
+                            .iter()
+                            .filter(|(_, v)| v.is_usable())
+                            .map(|(v, _)| v.clone())
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .filter(|(_, versions)| !versions.is_empty())
                 .collect(),
             reveal_setup: self.reveal_setup,
+            fingerprint: self.local_fingerprint.clone(),
+            round_time_limit_secs: self.round_time_limit_secs,
+            capabilities: self
+                .local_selection
+                .as_ref()
+                .map(|local_selection| local_selection.game.hooks().capabilities().bits())
+                .unwrap_or(0),
+            input_delay: self.input_delay,
+            input_delay_handicap: self.input_delay_handicap,
+            replay_recording: self.replay_recording,
+            seq: self.settings_seq,
+            accepts_deltas: true,
         }
     }
 
-    async fn send_settings(&mut self, settings: net::protocol::Settings) -> Result<(), anyhow::Error> {
-        let sender = if let Some(sender) = self.sender.as_mut() {
-            sender
-        } else {
-            anyhow::bail!("no sender?")
+    /// Sends our settings, as only what changed since they were last sent if the opponent takes deltas.
+    fn send_settings(&mut self, mut settings: net::protocol::Settings) {
+        self.settings_seq = self.settings_seq.wrapping_add(1);
+        settings.seq = self.settings_seq;
+        let packet = match self.sent_settings.as_ref() {
+            Some(sent_settings) if self.remote_settings.accepts_deltas => {
+                let delta = net::protocol::SettingsDelta::between(sent_settings, &settings);
+                if log::log_enabled!(log::Level::Debug) {
+                    log::debug!(
+                        "sending settings delta of {} bytes instead of {} bytes",
+                        net::protocol::Packet::SettingsDelta(delta.clone())
+                            .serialize()
+                            .map(|buf| buf.len())
+                            .unwrap_or(0),
+                        net::protocol::Packet::Settings(settings.clone())
+                            .serialize()
+                            .map(|buf| buf.len())
+                            .unwrap_or(0)
+                    );
+                }
+                net::protocol::Packet::SettingsDelta(delta)
+            }
+            _ => net::protocol::Packet::Settings(settings.clone()),
         };
-        sender.send_settings(settings).await?;
-        Ok(())
+        self.send(packet);
+        self.sent_settings = Some(settings.clone());
+        self.broadcast_server.publish(broadcast::Event::SettingsChanged {
+            local: broadcast::Side::from_settings(&settings),
+            remote: broadcast::Side::from_settings(&self.remote_settings),
+            match_type: settings.match_type,
+        });
     }
 
-    async fn set_reveal_setup(&mut self, reveal_setup: bool) -> Result<(), anyhow::Error> {
+    fn set_reveal_setup(&mut self, reveal_setup: bool) {
         if reveal_setup == self.reveal_setup {
-            return Ok(());
+            return;
         }
         self.send_settings(net::protocol::Settings {
             reveal_setup,
             ..self.make_local_settings()
-        })
-        .await?;
+        });
         self.reveal_setup = reveal_setup;
         if !self.reveal_setup {
             self.remote_commitment = None;
         }
-        Ok(())
     }
 
-    async fn set_match_type(&mut self, match_type: (u8, u8)) -> Result<(), anyhow::Error> {
+    fn set_match_type(&mut self, match_type: (u8, u8)) {
         if match_type == self.match_type {
-            return Ok(());
+            return;
         }
         self.send_settings(net::protocol::Settings {
             match_type,
             ..self.make_local_settings()
-        })
-        .await?;
+        });
         self.match_type = match_type;
-        Ok(())
     }
 
-    async fn set_local_selection(&mut self, selection: &Option<gui::Selection>) -> Result<(), anyhow::Error> {
+    fn set_round_time_limit(&mut self, round_time_limit_secs: Option<u32>) {
+        if round_time_limit_secs == self.round_time_limit_secs {
+            return;
+        }
+        self.send_settings(net::protocol::Settings {
+            round_time_limit_secs,
+            ..self.make_local_settings()
+        });
+        self.round_time_limit_secs = round_time_limit_secs;
+    }
+
+    fn set_input_delay(&mut self, input_delay: u32, input_delay_handicap: u32) {
+        if (input_delay, input_delay_handicap) == (self.input_delay, self.input_delay_handicap) {
+            return;
+        }
+        self.send_settings(net::protocol::Settings {
+            input_delay,
+            input_delay_handicap,
+            ..self.make_local_settings()
+        });
+        self.input_delay = input_delay;
+        self.input_delay_handicap = input_delay_handicap;
+    }
+
+    fn set_replay_recording(&mut self, replay_recording: net::protocol::ReplayRecording) {
+        if replay_recording == self.replay_recording {
+            return;
+        }
+        self.send_settings(net::protocol::Settings {
+            replay_recording,
+            ..self.make_local_settings()
+        });
+        self.replay_recording = replay_recording;
+    }
+
+    fn set_local_selection(&mut self, selection: &Option<gui::Selection>) {
         if selection.as_ref().map(|selection| {
             (
                 selection.game,
@@ -445,6 +977,7 @@ impl Lobby {
                     .as_ref()
                     .map(|(name, version, _)| (name.clone(), version.clone())),
                 selection.save.save.as_raw_wram(),
+                selection.save.path.as_path(),
             )
         }) == self.local_selection.as_ref().map(|selection| {
             (
@@ -454,57 +987,110 @@ impl Lobby {
                     .as_ref()
                     .map(|(name, version, _)| (name.clone(), version.clone())),
                 selection.save.as_raw_wram(),
+                selection.save_path.as_path(),
             )
         }) {
-            return Ok(());
+            return;
         }
 
-        let match_type = (
-            if selection
-                .as_ref()
-                .map(|selection| (self.match_type.0 as usize) < selection.game.match_types().len())
-                .unwrap_or(false)
-            {
-                self.match_type.0
-            } else {
-                0
-            },
-            0,
-        );
+        // Whatever of the match type the newly selected game also has is kept.
+        let match_type = match selection.as_ref().map(|selection| selection.game) {
+            Some(game) if game.has_match_type(self.match_type) => self.match_type,
+            Some(game) if game.has_match_type((self.match_type.0, 0)) => (self.match_type.0, 0),
+            _ => (0, 0),
+        };
+
+        let round_time_limit_secs = if selection
+            .as_ref()
+            .map(|selection| selection.game.hooks().supports_round_time_limit())
+            .unwrap_or(false)
+        {
+            self.round_time_limit_secs
+        } else {
+            None
+        };
+
+        let rom_hash = selection
+            .as_ref()
+            .map(|selection| determinism::hash_rom(&selection.rom));
+
+        let game_info = selection.as_ref().zip(rom_hash).map(|(selection, rom_hash)| {
+            let (family, variant) = selection.game.family_and_variant();
+            net::protocol::GameInfo {
+                family_and_variant: (family.to_string(), variant),
+                patch: selection.patch.as_ref().map(|(name, version, _)| {
+                    make_patch_info(selection.game, name, version, &self.patches_scanner.read())
+                }),
+                rom_hash: Some(rom_hash),
+            }
+        });
 
         self.send_settings(net::protocol::Settings {
-            game_info: selection.as_ref().map(|selection| {
-                let (family, variant) = selection.game.family_and_variant();
-                net::protocol::GameInfo {
-                    family_and_variant: (family.to_string(), variant),
-                    patch: selection
-                        .patch
-                        .as_ref()
-                        .map(|(name, version, _)| net::protocol::PatchInfo {
-                            name: name.clone(),
-                            version: version.clone(),
-                        }),
-                }
-            }),
+            game_info,
             match_type,
+            round_time_limit_secs,
+            capabilities: selection
+                .as_ref()
+                .map(|selection| selection.game.hooks().capabilities().bits())
+                .unwrap_or(0),
             ..self.make_local_settings()
-        })
-        .await?;
-        self.local_selection = if let Some(selection) = selection.as_ref() {
+        });
+        self.local_selection = if let Some((selection, rom_hash)) = selection.as_ref().zip(rom_hash) {
             Some(LocalSelection {
                 game: selection.game,
                 save: selection.save.save.clone(),
+                save_path: selection.save.path.clone(),
+                save_stamp: save_file_stamp(&selection.save.path),
                 rom: selection.rom.clone(),
+                rom_hash,
                 patch: selection.patch.clone(),
             })
         } else {
             None
         };
+        self.save_changed_on_disk = false;
+        self.save_reload_error = None;
         self.match_type = match_type;
+        self.round_time_limit_secs = round_time_limit_secs;
         if !self.can_ready() {
             self.remote_commitment = None;
         }
-        Ok(())
+        self.check_compatible();
+    }
+
+    /// Notices if the selected save has been changed on disk since it was read, checking at most once a second.
+    fn check_save_changed_on_disk(&mut self) {
+        if self.save_checked_at.elapsed() < std::time::Duration::from_secs(1) {
+            return;
+        }
+        self.save_checked_at = std::time::Instant::now();
+
+        let local_selection = if let Some(local_selection) = self.local_selection.as_ref() {
+            local_selection
+        } else {
+            return;
+        };
+
+        if save_file_stamp(&local_selection.save_path) != local_selection.save_stamp {
+            self.save_changed_on_disk = true;
+        }
+    }
+
+    /// Records that the selected save has just been read from disk again.
+    fn mark_save_reloaded(&mut self) {
+        if let Some(local_selection) = self.local_selection.as_mut() {
+            local_selection.save_stamp = save_file_stamp(&local_selection.save_path);
+        }
+        self.save_changed_on_disk = false;
+        self.save_reload_error = None;
+    }
+
+    fn check_compatible(&mut self) {
+        if !self.compatible_notified && self.can_ready() {
+            self.compatible_notified = true;
+            self.pending_notifications
+                .push(gui::notifications::Event::OpponentCompatible);
+        }
     }
 
     fn can_ready(&self) -> bool {
@@ -515,22 +1101,64 @@ impl Lobby {
         )
     }
 
-    fn set_remote_settings(&mut self, settings: net::protocol::Settings, patches_path: &std::path::Path) {
-        let roms = self.roms_scanner.read();
+    /// Estimates how likely the match is to desync from everything known so far. This changes as the connection is measured and either side changes what they picked.
+    fn assess_desync_risk(&self) -> desyncrisk::Assessment {
+        let opponent = if let opponents::Recognition::Known(id) = &self.opponent {
+            self.opponents.get(id)
+        } else {
+            None
+        };
+        desyncrisk::Assessment::of(&desyncrisk::Inputs {
+            latency: &self.latencies,
+            local_game: self.local_selection.as_ref().map(|selection| selection.game),
+            remote_game: self.remote_selection.as_ref().map(|selection| selection.game),
+            remote_capabilities: self.remote_settings.capabilities,
+            local_rom_hash: self.remote_selection.as_ref().map(|selection| &selection.rom_hash),
+            remote_rom_hash: self
+                .remote_settings
+                .game_info
+                .as_ref()
+                .and_then(|game_info| game_info.rom_hash.as_ref()),
+            local_fingerprint: self.local_fingerprint.as_ref(),
+            remote_fingerprint: self.remote_settings.fingerprint.as_ref(),
+            opponent: opponent.as_ref(),
+        })
+    }
 
-        let old_reveal_setup = self.remote_settings.reveal_setup;
-        self.remote_selection = settings.game_info.as_ref().and_then(|gi| {
+    /// Sends our full settings again, for when the opponent can't make sense of a delta.
+    fn resend_settings(&mut self) {
+        self.sent_settings = None;
+        let settings = self.make_local_settings();
+        self.send_settings(settings);
+    }
+
+    /// Merges a delta into the opponent's settings. If it doesn't apply, they're asked for their full settings instead.
+    fn apply_remote_settings_delta(&mut self, delta: net::protocol::SettingsDelta, patches_path: &std::path::Path) {
+        let mut settings = self.remote_settings.clone();
+        if let Err(e) = delta.apply(&mut settings) {
+            log::warn!("opponent's settings are out of sync, asking for them again: {}", e);
+            self.send(net::protocol::Packet::ResendSettings(net::protocol::ResendSettings {}));
+            return;
+        }
+        self.set_remote_settings(settings, patches_path);
+    }
+
+    /// Rebuilds the opponent's ROM from what they picked, patching it if they picked a patch.
+    fn make_remote_selection(
+        &self,
+        settings: &net::protocol::Settings,
+        patches_path: &std::path::Path,
+    ) -> Option<RemoteSelection> {
+        let roms = self.roms_scanner.read();
+        let patches = self.patches_scanner.read();
+        settings.game_info.as_ref().and_then(|gi| {
             game::find_by_family_and_variant(&gi.family_and_variant.0, gi.family_and_variant.1).and_then(|game| {
-                roms.get(&game).and_then(|rom| {
+                roms.by_game.get(&game).and_then(|rom| {
                     if let Some(pi) = gi.patch.as_ref() {
                         let (rom_code, revision) = game.rom_code_and_revision();
 
-                        let patch_version_metadata = if let Some(version_meta) = self
-                            .patches_scanner
-                            .read()
-                            .get(&pi.name)
-                            .and_then(|p| p.versions.get(&pi.version))
-                            .cloned()
+                        let patch_version_metadata = if let Some(version_meta) =
+                            patches.get(&pi.name).and_then(|p| p.versions.get(&pi.version)).cloned()
                         {
                             version_meta
                         } else {
@@ -538,7 +1166,10 @@ impl Lobby {
                             return None;
                         };
 
-                        let rom = match patch::apply_patch_from_disk(&rom, game, patches_path, &pi.name, &pi.version) {
+                        let rom = match self
+                            .patched_roms
+                            .get_or_apply(rom, game, patches_path, &pi.name, &pi.version)
+                        {
                             Ok(r) => r,
                             Err(e) => {
                                 log::error!("failed to apply patch {}: {:?}: {:?}", pi.name, (rom_code, revision), e);
@@ -547,6 +1178,7 @@ impl Lobby {
                         };
 
                         Some(RemoteSelection {
+                            rom_hash: determinism::hash_rom(&rom),
                             rom,
                             game,
                             patch: Some((pi.name.clone(), pi.version.clone(), patch_version_metadata)),
@@ -554,41 +1186,149 @@ impl Lobby {
                     } else {
                         Some(RemoteSelection {
                             rom: rom.clone(),
+                            rom_hash: determinism::hash_rom(rom),
                             game,
                             patch: None,
                         })
                     }
                 })
             })
-        });
-
+        })
+    }
+
+    fn set_remote_settings(&mut self, settings: net::protocol::Settings, patches_path: &std::path::Path) {
+        let old_reveal_setup = self.remote_settings.reveal_setup;
+        let old_input_delay = (
+            self.remote_settings.input_delay,
+            self.remote_settings.input_delay_handicap,
+        );
+        let old_replay_recording = self.remote_settings.replay_recording;
+        let nickname_changed = self.remote_settings.nickname != settings.nickname;
+        // Rebuilding the opponent's ROM may mean patching it, so it's only done when they picked something else.
+        if settings.game_info != self.remote_settings.game_info {
+            self.remote_selection = self.make_remote_selection(&settings, patches_path);
+        }
+
         self.remote_settings = settings;
-        if !self.can_ready() || (old_reveal_setup && !self.remote_settings.reveal_setup) {
+        // Both sides' delays and whether they record are shown before committing, so a commitment made with different ones no longer stands.
+        if !self.can_ready()
+            || (old_reveal_setup && !self.remote_settings.reveal_setup)
+            || old_input_delay
+                != (
+                    self.remote_settings.input_delay,
+                    self.remote_settings.input_delay_handicap,
+                )
+            || old_replay_recording != self.remote_settings.replay_recording
+        {
             self.local_negotiated_state = None;
         }
+        if nickname_changed {
+            self.recognize_opponent();
+        }
+        if !self.joined_notified {
+            self.joined_notified = true;
+            self.pending_notifications
+                .push(gui::notifications::Event::OpponentJoined);
+        }
+        self.check_compatible();
+
+        let local_settings = self.make_local_settings();
+        self.broadcast_server.publish(broadcast::Event::SettingsChanged {
+            local: broadcast::Side::from_settings(&local_settings),
+            remote: broadcast::Side::from_settings(&self.remote_settings),
+            match_type: local_settings.match_type,
+        });
     }
 
-    async fn send_pong(&mut self, ts: std::time::SystemTime) -> Result<(), anyhow::Error> {
-        let sender = if let Some(sender) = self.sender.as_mut() {
-            sender
+    fn recognize_opponent(&mut self) {
+        self.opponent = self
+            .opponents
+            .recognize(&self.remote_settings.nickname, self.remote_installation_id.as_deref());
+        self.opponent_notes = if let opponents::Recognition::Known(id) = &self.opponent {
+            self.opponents.get(id).map(|entry| entry.notes).unwrap_or_default()
         } else {
-            anyhow::bail!("no sender?")
+            String::new()
         };
-        sender.send_pong(ts).await?;
-        Ok(())
     }
 
-    async fn send_ping(&mut self) -> Result<(), anyhow::Error> {
-        let sender = if let Some(sender) = self.sender.as_mut() {
-            sender
+    /// Settles who the opponent is, from picking one of the candidates or adding them as someone new.
+    fn set_opponent(&mut self, id: Option<String>) {
+        let nickname = self.remote_settings.nickname.clone();
+        let installation_id = self.remote_installation_id.clone();
+        let r = if let Some(id) = id {
+            self.opponents
+                .confirm(&id, &nickname, installation_id.as_deref())
+                .map(|_| id)
         } else {
-            anyhow::bail!("no sender?")
+            self.opponents.add(&nickname, installation_id.as_deref())
         };
-        sender.send_ping(std::time::SystemTime::now()).await?;
-        Ok(())
+        match r {
+            Ok(id) => {
+                self.opponent_notes = self.opponents.get(&id).map(|entry| entry.notes).unwrap_or_default();
+                self.opponent = opponents::Recognition::Known(id);
+            }
+            Err(e) => {
+                log::error!("failed to save opponent: {:?}", e);
+            }
+        }
+    }
+
+    /// Saves the notes being edited. Writing something about someone new adds them right away, so it isn't lost if the match never happens.
+    fn save_opponent_notes(&mut self) {
+        let opponent_notes = std::mem::take(&mut self.opponent_notes);
+        if self.opponent == opponents::Recognition::Unknown && !opponent_notes.is_empty() {
+            self.set_opponent(None);
+        }
+        if let opponents::Recognition::Known(id) = &self.opponent {
+            if let Err(e) = self.opponents.set_notes(id, &opponent_notes) {
+                log::error!("failed to save opponent notes: {:?}", e);
+            }
+        }
+        self.opponent_notes = opponent_notes;
+    }
+
+    /// Gets where the match's results go. Someone new is added now that they've actually been played, but if it's still unclear who they are, nothing is recorded.
+    fn take_opponent_recorder(&mut self) -> Option<opponents::Recorder> {
+        match &self.opponent {
+            opponents::Recognition::Known(id) => {
+                let id = id.clone();
+                self.set_opponent(Some(id));
+            }
+            opponents::Recognition::Unknown => {
+                self.set_opponent(None);
+            }
+            opponents::Recognition::Candidates(_) => {
+                log::info!("not recording results: opponent wasn't picked from the candidates");
+            }
+        }
+        if let opponents::Recognition::Known(id) = &self.opponent {
+            Some(opponents::Recorder {
+                registry: self.opponents.clone(),
+                id: id.clone(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Whether both sides are ready, i.e. match data may already be on its way.
+    ///
+    /// Latency measured from then on is skewed by the bulk transfer, so it's left out of the suggested input delay.
+    fn is_exchange_imminent(&self) -> bool {
+        self.local_negotiated_state.is_some() && self.remote_commitment.is_some()
     }
 }
 
+/// How to reach the opponent.
+enum ConnectTo {
+    /// Meet up through the matchmaking server by link code.
+    Matchmaking { addr: String, link_code: String },
+    /// Wait on the local network for the opponent to connect to us.
+    LanHost { port: u16 },
+    /// Connect to an opponent hosting on the local network.
+    LanJoin { addr: String },
+}
+
 async fn run_connection_task(
     config: std::sync::Arc<parking_lot::RwLock<config::Config>>,
     egui_ctx: egui::Context,
@@ -597,12 +1337,16 @@ async fn run_connection_task(
     session: std::sync::Arc<parking_lot::Mutex<Option<session::Session>>>,
     roms_scanner: rom::Scanner,
     patches_scanner: patch::Scanner,
-    matchmaking_addr: String,
-    link_code: String,
+    patched_roms: patch::cache::PatchedRoms,
+    opponents: opponents::Registry,
+    save_patch_history: save::migration::History,
+    save_registry: save::registry::Registry,
+    broadcast_server: broadcast::Server,
+    connect_to: ConnectTo,
     nickname: String,
     patches_path: std::path::PathBuf,
     replays_path: std::path::PathBuf,
-    connection_task: std::sync::Arc<tokio::sync::Mutex<Option<ConnectionTask>>>,
+    connection_task: std::sync::Arc<parking_lot::Mutex<Option<ConnectionTask>>>,
     cancellation_token: tokio_util::sync::CancellationToken,
 ) {
     if let Err(e) = {
@@ -612,107 +1356,211 @@ async fn run_connection_task(
             r = {
                 let connection_task = connection_task.clone();
                 let cancellation_token = cancellation_token.clone();
+                let broadcast_server = broadcast_server.clone();
                 (move || async move {
-                    *connection_task.lock().await =
-                        Some(ConnectionTask::InProgress {
-                            state: ConnectionState::Signaling,
-                            cancellation_token:
-                                cancellation_token.clone(),
-                        });
                     const OPEN_TIMEOUT: std::time::Duration =
                         std::time::Duration::from_secs(30);
-                    let pending_conn = tokio::time::timeout(
-                        OPEN_TIMEOUT,
-                        net::signaling::open(
-                            &matchmaking_addr,
-                            &link_code,
-                        ),
-                    )
-                    .await.map_err(|e| std::io::Error::new(std::io::ErrorKind::TimedOut, e))??;
-
-                    *connection_task.lock().await =
-                        Some(ConnectionTask::InProgress {
-                            state: ConnectionState::Waiting,
-                            cancellation_token:
-                                cancellation_token.clone(),
-                        });
+                    let set_state = |state| {
+                        *connection_task.lock() =
+                            Some(ConnectionTask::InProgress {
+                                state,
+                                cancellation_token:
+                                    cancellation_token.clone(),
+                            });
+                    };
 
-                    let (dc, peer_conn) = pending_conn.connect().await?;
-                    let (dc_tx, dc_rx) = dc.split();
-                    let mut sender = net::Sender::new(dc_tx);
-                    let mut receiver = net::Receiver::new(dc_rx);
-                    net::negotiate(&mut sender, &mut receiver).await?;
+                    let installation_id = config.read().installation_id.clone();
+
+                    // Everything from here on goes the same way whichever way the opponent was reached.
+                    let (mut sender, mut receiver, transport, link_code, (remote_client_info, remote_identity)) = match connect_to {
+                        ConnectTo::Matchmaking { addr: matchmaking_addr, link_code } => {
+                            set_state(ConnectionState::Signaling);
+                            let network = config.read().network.clone();
+                            let pending_conn = tokio::time::timeout(
+                                OPEN_TIMEOUT,
+                                net::signaling::open(
+                                    &matchmaking_addr,
+                                    &link_code,
+                                    &network,
+                                ),
+                            )
+                            .await.map_err(|e| std::io::Error::new(std::io::ErrorKind::TimedOut, e))?
+                            .map_err(|e| match e.downcast::<net::proxy::ConnectError>() {
+                                Ok(e) => ConnectionError::Connect(e),
+                                Err(e) => ConnectionError::Other(e),
+                            })?;
+
+                            set_state(ConnectionState::Waiting);
+
+                            let (dc, peer_conn) = pending_conn.connect().await?;
+                            let (dc_tx, dc_rx) = dc.split();
+                            let (mut sender, mut receiver) = (net::Sender::new(dc_tx), net::Receiver::new(dc_rx));
+                            let negotiated = net::negotiate(&mut sender, &mut receiver, &installation_id).await?;
+                            (sender, receiver, net::Transport::DataChannel(peer_conn), link_code, negotiated)
+                        }
+                        ConnectTo::LanHost { port } => {
+                            let listener = net::lan::Listener::bind(port).await?;
+                            set_state(ConnectionState::Listening(net::lan::local_addresses(listener.port()?)));
+                            // Negotiation happens while accepting, so that the host can keep listening past anyone who doesn't get through it.
+                            let (sender, receiver, local_addr, negotiated) = listener.accept(&installation_id).await?;
+                            (sender, receiver, net::Transport::Lan { is_host: true }, net::lan::link_code(&local_addr), negotiated)
+                        }
+                        ConnectTo::LanJoin { addr } => {
+                            set_state(ConnectionState::Dialing(addr.clone()));
+                            let (mut sender, mut receiver, remote_addr) = tokio::time::timeout(OPEN_TIMEOUT, net::lan::connect(&addr))
+                                .await
+                                .map_err(|e| std::io::Error::new(std::io::ErrorKind::TimedOut, e))??;
+                            let negotiated = net::negotiate(&mut sender, &mut receiver, &installation_id).await?;
+                            (sender, receiver, net::Transport::Lan { is_host: false }, net::lan::link_code(&remote_addr), negotiated)
+                        }
+                    };
 
-                    let default_match_type = {
+                    let (default_match_type, default_match_subtype, input_delay, replay_recording) = {
                         let config = config.read();
-                        config.default_match_type
+                        (config.default_match_type, config.default_match_subtype, config.input_delay, config.replay_recording)
+                    };
+
+                    // The BIOS is read once for the whole match, so what the opponent is told about it is what the match runs on.
+                    let bios = bios::load_configured(config.read().bios_path.as_deref());
+                    let local_fingerprint = match tokio::task::spawn_blocking({
+                        let bios = bios.clone();
+                        move || determinism::Fingerprint::local(bios.as_deref())
+                    }).await.map_err(|e| anyhow::anyhow!(e))? {
+                        Ok(fingerprint) => Some(fingerprint),
+                        Err(e) => {
+                            log::error!("failed to compute emulator configuration fingerprint: {:?}", e);
+                            None
+                        }
                     };
 
-                    let lobby = std::sync::Arc::new(tokio::sync::Mutex::new(Lobby{
-                        attention_requested: false,
-                        sender: Some(sender),
+                    // The lobby is locked by the GUI every frame, so changes made there are queued for this task to send rather than sent while it's locked.
+                    let (outgoing_tx, mut outgoing_rx) = tokio::sync::mpsc::unbounded_channel();
+                    let lobby = std::sync::Arc::new(parking_lot::Mutex::new(Lobby{
+                        pending_notifications: vec![],
+                        joined_notified: false,
+                        compatible_notified: false,
+                        outgoing: Some(outgoing_tx),
                         local_selection: None,
                         remote_selection: None,
                         nickname,
                         link_code,
-                        match_type: (default_match_type, 0),
+                        match_type: (default_match_type, default_match_subtype),
+                        round_time_limit_secs: None,
+                        input_delay,
+                        input_delay_handicap: 0,
+                        replay_recording,
+                        asking_replay_consent: false,
+                        showing_desync_risk: false,
+                        confirming_desync_risk: false,
+                        desync_risk_acknowledged: false,
                         reveal_setup: false,
+                        sent_settings: None,
+                        settings_seq: 0,
                         remote_settings: net::protocol::Settings::default(),
+                        remote_client_info: remote_client_info.clone(),
+                        remote_installation_id: remote_identity.map(|identity| identity.installation_id),
                         remote_commitment: None,
-                        latencies: stats::DeltaCounter::new(5),
+                        opponents: opponents.clone(),
+                        opponent: opponents::Recognition::Unknown,
+                        opponent_notes: String::new(),
+                        latencies: stats::DeltaCounter::new(stats::window::LOBBY_LATENCY, net::MAX_LATENCY_SAMPLE),
                         local_negotiated_state: None,
+                        local_fingerprint,
+                        delay_preview: None,
+                        unsupported_match_type: None,
+                        save_changed_on_disk: false,
+                        save_reload_error: None,
+                        save_checked_at: std::time::Instant::now(),
                         roms_scanner: roms_scanner.clone(),
                         patches_scanner: patches_scanner.clone(),
+                        patched_roms: patched_roms.clone(),
+                        broadcast_server: broadcast_server.clone(),
                     }));
                     {
-                        let mut lobby = lobby.lock().await;
+                        let mut lobby = lobby.lock();
                         let settings = lobby.make_local_settings();
-                        lobby.send_settings(settings).await?;
+                        broadcast_server.publish(broadcast::Event::LobbyJoined {
+                            local: broadcast::Side::from_settings(&settings),
+                            remote: broadcast::Side::default(),
+                        });
+                        lobby.send_settings(settings);
                     }
 
-                    *connection_task.lock().await =
+                    *connection_task.lock() =
                         Some(ConnectionTask::InProgress {
                             state: ConnectionState::InLobby(lobby.clone()),
                             cancellation_token:
                                 cancellation_token.clone(),
                         });
 
+                    // Latency is only shown to the user, so there's no need to repaint for every pong.
+                    const LATENCY_REPAINT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+                    let repaint = gui::repaint::Scheduler::new(egui_ctx.clone());
+                    repaint.request_transition();
+
                     let mut remote_chunks = vec![];
                     let mut ping_timer = tokio::time::interval(net::PING_INTERVAL);
                     'l: loop {
                         tokio::select! {
+                            Some(packet) = outgoing_rx.recv() => {
+                                sender.send_packet(&packet).await?;
+                            }
                             _ = ping_timer.tick() => {
-                                lobby.lock().await.send_ping().await?;
+                                let exchange_imminent = lobby.lock().is_exchange_imminent();
+                                if !exchange_imminent {
+                                    sender.send_ping(stats::monotonic_timestamp()).await?;
+                                }
                             }
                             p = receiver.receive() => {
                                 match p? {
                                     net::protocol::Packet::Ping(ping) => {
-                                        lobby.lock().await.send_pong(ping.ts).await?;
+                                        sender.send_pong(ping.ts).await?;
                                     },
                                     net::protocol::Packet::Pong(pong) => {
-                                        let mut lobby = lobby.lock().await;
-                                        if let Ok(d) = std::time::SystemTime::now().duration_since(pong.ts) {
-                                            lobby.latencies.mark(d);
-                                            egui_ctx.request_repaint();
+                                        let mut lobby = lobby.lock();
+                                        // Once the opponent may be sending us their chunks, their pongs queue up behind them and say nothing about the link.
+                                        if !lobby.is_exchange_imminent() {
+                                            if let Some(d) = stats::monotonic_elapsed(pong.ts) {
+                                                lobby.latencies.mark(d);
+                                                repaint.request_within(LATENCY_REPAINT_INTERVAL);
+                                            }
                                         }
                                     },
                                     net::protocol::Packet::Settings(settings) => {
-                                        let mut lobby = lobby.lock().await;
+                                        let mut lobby = lobby.lock();
                                         lobby.set_remote_settings(settings, &patches_path);
-                                        egui_ctx.request_repaint();
+                                        if !lobby.pending_notifications.is_empty() {
+                                            // Notifications are sent from the GUI thread, so they need a repaint even when unfocused.
+                                            repaint.request_transition();
+                                        } else {
+                                            repaint.request_now();
+                                        }
+                                    },
+                                    net::protocol::Packet::SettingsDelta(delta) => {
+                                        let mut lobby = lobby.lock();
+                                        lobby.apply_remote_settings_delta(delta, &patches_path);
+                                        if !lobby.pending_notifications.is_empty() {
+                                            repaint.request_transition();
+                                        } else {
+                                            repaint.request_now();
+                                        }
+                                    },
+                                    net::protocol::Packet::ResendSettings(_) => {
+                                        lobby.lock().resend_settings();
                                     },
                                     net::protocol::Packet::Commit(commit) => {
-                                        let mut lobby = lobby.lock().await;
+                                        let mut lobby = lobby.lock();
                                         lobby.remote_commitment = Some(commit.commitment);
-                                        egui_ctx.request_repaint();
+                                        lobby.pending_notifications.push(gui::notifications::Event::OpponentReady);
+                                        repaint.request_transition();
 
                                         if lobby.local_negotiated_state.is_some() {
                                             break 'l;
                                         }
                                     },
                                     net::protocol::Packet::Uncommit(_) => {
-                                        lobby.lock().await.remote_commitment = None;
-                                        egui_ctx.request_repaint();
+                                        lobby.lock().remote_commitment = None;
+                                        repaint.request_transition();
                                     },
                                     net::protocol::Packet::Chunk(chunk) => {
                                         remote_chunks.push(chunk.chunk);
@@ -728,50 +1576,130 @@ async fn run_connection_task(
 
                     log::info!("ending lobby");
 
-                    let (mut sender, match_type, local_settings, remote_selection, remote_settings, remote_commitment, local_negotiated_state, local_selection, link_code) = {
-                        let mut lobby = lobby.lock().await;
+                    let (match_type, local_settings, remote_selection, remote_settings, remote_installation_id, remote_commitment, local_negotiated_state, local_selection, link_code, opponent) = {
+                        let mut lobby = lobby.lock();
                         let local_settings = lobby.make_local_settings();
-                        let sender = if let Some(sender) = lobby.sender.take() {
-                            sender
-                        } else {
-                            return Err(ConnectionError::Other(anyhow::anyhow!("no sender?")));
-                        };
-                        (sender, lobby.match_type, local_settings, lobby.remote_selection.take(), lobby.remote_settings.clone(), lobby.remote_commitment.clone(), lobby.local_negotiated_state.take(), lobby.local_selection.take(), lobby.link_code.clone())
+                        lobby.outgoing = None;
+                        (lobby.match_type, local_settings, lobby.remote_selection.take(), lobby.remote_settings.clone(), lobby.remote_installation_id.clone(), lobby.remote_commitment.clone(), lobby.local_negotiated_state.take(), lobby.local_selection.take(), lobby.link_code.clone(), lobby.take_opponent_recorder())
                     };
 
+                    // Anything the lobby queued before it ended, including our commitment, has to reach the opponent before our chunks do.
+                    while let Ok(packet) = outgoing_rx.try_recv() {
+                        sender.send_packet(&packet).await?;
+                    }
+
+                    // From here on, the opponent is told if we give up on starting the match, whether it was cancelled or failed.
+                    let mut pending_start = PendingMatchStart::new(sender, transport);
+                    let sender = pending_start.sender();
+
                     let remote_selection = if let Some(remote_selection) = remote_selection {
                         remote_selection
                     } else {
                         return Err(ConnectionError::Other(anyhow::anyhow!("missing shadow rom")));
                     };
 
+                    // The lobby won't let either side ready up with mismatched configurations, but make sure before committing to the match.
+                    match (local_settings.fingerprint.as_ref(), remote_settings.fingerprint.as_ref()) {
+                        (Some(local_fingerprint), Some(remote_fingerprint)) => {
+                            let differences = local_fingerprint.differences(remote_fingerprint);
+                            if !differences.is_empty() {
+                                return Err(ConnectionError::ConfigurationMismatch(differences));
+                            }
+                        }
+                        _ => {
+                            return Err(ConnectionError::Other(anyhow::anyhow!("missing emulator configuration fingerprint")));
+                        }
+                    }
+
                     let remote_patch_overrides = remote_selection.patch.as_ref().map(|(_, _, version_meta)| version_meta.rom_overrides.clone()).unwrap_or_default();
 
+                    // Each side picks battle backgrounds from its own patch's pool, so they have to be the same or the games will diverge.
+                    let battle_backgrounds = local_selection.as_ref().and_then(|local_selection| local_selection.patch.as_ref()).and_then(|(_, _, version_meta)| version_meta.battle_backgrounds.clone());
+                    if battle_backgrounds != remote_selection.patch.as_ref().and_then(|(_, _, version_meta)| version_meta.battle_backgrounds.clone()) {
+                        return Err(ConnectionError::Other(anyhow::anyhow!("battle background pools do not match")));
+                    }
+
                     let (local_negotiated_state, raw_local_state) = if let Some((negotiated_state, raw_local_state)) = local_negotiated_state {
                         (negotiated_state, raw_local_state)
                     } else {
                         return Err(ConnectionError::Other(anyhow::anyhow!("attempted to start match in invalid state")));
                     };
 
-                    const CHUNK_SIZE: usize = 32 * 1024;
-                    const CHUNKS_REQUIRED: usize = 5;
-                    for (_, chunk) in std::iter::zip(
+                    const CHUNK_SIZE: usize = net::protocol::NEGOTIATED_STATE_CHUNK_SIZE;
+                    const CHUNKS_REQUIRED: usize = net::protocol::NEGOTIATED_STATE_CHUNKS;
+
+                    // A lost chunk would otherwise leave us waiting forever, so the whole exchange has to finish in time.
+                    let match_start_deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(config.read().match_start_timeout_secs as u64);
+                    let timed_out = |received: usize| ConnectionError::MatchStartTimedOut { received, total: CHUNKS_REQUIRED };
+                    let report_exchange_progress = |sent: usize, received: usize| {
+                        *connection_task.lock() =
+                            Some(ConnectionTask::InProgress {
+                                state: ConnectionState::Exchanging { sent, received, total: CHUNKS_REQUIRED },
+                                cancellation_token: cancellation_token.clone(),
+                            });
+                        egui_ctx.request_repaint();
+                    };
+                    report_exchange_progress(0, remote_chunks.len());
+
+                    let mut pending_pongs = vec![];
+                    for (i, chunk) in std::iter::zip(
                         0..CHUNKS_REQUIRED,
                         raw_local_state.chunks(CHUNK_SIZE).chain(std::iter::repeat(&[][..]))
                      ) {
-                        sender.send_chunk(chunk.to_vec()).await?;
+                        // Keep handling what the opponent sends while our chunk goes out, so their pings aren't stuck behind it.
+                        {
+                            let send = sender.send_chunk(chunk.to_vec());
+                            tokio::pin!(send);
+                            loop {
+                                tokio::select! {
+                                    biased;
+                                    r = &mut send => {
+                                        r?;
+                                        break;
+                                    }
+                                    p = tokio::time::timeout_at(match_start_deadline, receiver.receive()) => {
+                                        match p.map_err(|_| timed_out(remote_chunks.len()))?? {
+                                            net::protocol::Packet::Ping(ping) => {
+                                                pending_pongs.push(ping.ts);
+                                            },
+                                            net::protocol::Packet::Pong(_) => { },
+                                            net::protocol::Packet::Chunk(chunk) => {
+                                                remote_chunks.push(chunk.chunk);
+                                            },
+                                            net::protocol::Packet::Uncommit(_) => {
+                                                return Err(ConnectionError::RemoteCancelled);
+                                            },
+                                            p => {
+                                                return Err(ConnectionError::Other(anyhow::format_err!("unexpected packet: {:?}", p)));
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+
+                        // Control packets go out before the next chunk does.
+                        for ts in pending_pongs.drain(..) {
+                            sender.send_pong(ts).await?;
+                        }
+                        report_exchange_progress(i + 1, remote_chunks.len());
 
-                        if remote_chunks.len() < CHUNKS_REQUIRED {
+                        // Some of the opponent's chunks may have come in while we were sending ours.
+                        if remote_chunks.len() < i + 1 {
                             loop {
-                                match receiver.receive().await? {
+                                match tokio::time::timeout_at(match_start_deadline, receiver.receive()).await.map_err(|_| timed_out(remote_chunks.len()))?? {
                                     net::protocol::Packet::Ping(ping) => {
                                         sender.send_pong(ping.ts).await?;
                                     },
                                     net::protocol::Packet::Pong(_) => { },
                                     net::protocol::Packet::Chunk(chunk) => {
                                         remote_chunks.push(chunk.chunk);
+                                        report_exchange_progress(i + 1, remote_chunks.len());
                                         break;
                                     },
+                                    net::protocol::Packet::Uncommit(_) => {
+                                        return Err(ConnectionError::RemoteCancelled);
+                                    },
                                     p => {
                                         return Err(ConnectionError::Other(anyhow::format_err!("unexpected packet: {:?}", p)));
                                     }
@@ -780,6 +1708,7 @@ async fn run_connection_task(
                         }
                     }
 
+                    let received_chunks = remote_chunks.len();
                     let raw_remote_negotiated_state = remote_chunks.into_iter().flatten().collect::<Vec<_>>();
 
                     let received_remote_commitment = if let Some(commitment) = remote_commitment {
@@ -790,16 +1719,40 @@ async fn run_connection_task(
 
                     log::info!("remote commitment = {:02x?}", received_remote_commitment);
 
-                    if !bool::from(make_commitment(&raw_remote_negotiated_state).ct_eq(&received_remote_commitment)) {
+                    if !bool::from(fairness::commitment(&raw_remote_negotiated_state).ct_eq(&received_remote_commitment)) {
                         return Err(ConnectionError::Other(anyhow::anyhow!("commitment mismatch?")));
                     }
 
-                    let raw_remote_negotiated_state = zstd::stream::decode_all(&raw_remote_negotiated_state[..])?;
-                    let remote_negotiated_state = net::protocol::NegotiatedState::deserialize(&raw_remote_negotiated_state)
-                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                    let remote_negotiated_state = match net::protocol::NegotiatedState::decode(&raw_remote_negotiated_state) {
+                        Ok(remote_negotiated_state) => remote_negotiated_state,
+                        Err(e) => {
+                            log::warn!("rejected remote negotiated state: {}", e);
+                            return Err(ConnectionError::InvalidRemoteState(e));
+                        }
+                    };
+
+                    let remote_save = remote_selection.game.parse_save(&remote_negotiated_state.save_data)?;
+                    let remote_assets = remote_selection.game.load_rom_assets(&remote_selection.rom, remote_save.as_raw_wram(), &remote_patch_overrides)?;
+                    if let Err(e) = remote_save.validate(remote_assets.as_ref()) {
+                        log::warn!("rejected remote save: {}", e);
+                        return Err(ConnectionError::IllegalRemoteSave(e));
+                    }
 
-                    let rng_seed = std::iter::zip(local_negotiated_state.nonce, remote_negotiated_state.nonce).map(|(x, y)| x ^ y).collect::<Vec<_>>().try_into().unwrap();
+                    let rng_seed = fairness::seed(&local_negotiated_state.nonce, &remote_negotiated_state.nonce);
                     log::info!("session verified! rng seed = {:02x?}", rng_seed);
+                    let reveal = fairness::Reveal {
+                        seed: rng_seed,
+                        local: fairness::Side {
+                            nonce: local_negotiated_state.nonce,
+                            commitment: fairness::commitment(&raw_local_state),
+                            negotiated_state: raw_local_state.clone(),
+                        },
+                        remote: fairness::Side {
+                            nonce: remote_negotiated_state.nonce,
+                            commitment: received_remote_commitment,
+                            negotiated_state: raw_remote_negotiated_state.clone(),
+                        },
+                    };
 
                     let local_selection = if let Some(local_selection) = local_selection {
                         local_selection
@@ -808,15 +1761,38 @@ async fn run_connection_task(
                     };
 
                     sender.send_start_match().await?;
-                    match receiver.receive().await? {
+                    match tokio::time::timeout_at(match_start_deadline, receiver.receive()).await.map_err(|_| timed_out(received_chunks))?? {
                         net::protocol::Packet::StartMatch(_) => {},
+                        net::protocol::Packet::Uncommit(_) => return Err(ConnectionError::RemoteCancelled),
                         p => return Err(ConnectionError::Other(anyhow::anyhow!("unexpected packet when expecting start match: {:?}", p))),
                     }
 
+                    // Setting up the session doesn't yield, so cancelling can't interrupt it: check before and after instead.
+                    if cancellation_token.is_cancelled() {
+                        return Ok(());
+                    }
+
                     log::info!("starting session");
-                    let is_offerer = peer_conn.local_description().unwrap().sdp_type == datachannel_wrapper::SdpType::Offer;
+                    let save_recorder = match save_registry.record_match(
+                        &local_selection.save_path,
+                        &*local_selection.save,
+                        save::registry::MatchRecord {
+                            played_at: std::time::SystemTime::now(),
+                            link_code: link_code.clone(),
+                            opponent_nickname: remote_settings.nickname.clone(),
+                            opponent_id: opponent.as_ref().map(|opponent| opponent.id.clone()),
+                            patch: local_selection.patch.as_ref().map(|(name, version, _)| (name.clone(), version.clone())),
+                        },
+                    ) {
+                        Ok(save_recorder) => Some(save_recorder),
+                        Err(e) => {
+                            log::error!("failed to record match in save registry: {:?}", e);
+                            None
+                        }
+                    };
+                    let (sender, transport) = pending_start.finish();
                     {
-                        *session.lock() = Some(session::Session::new_pvp(
+                        let pvp = session::Session::new_pvp(
                             config.clone(),
                             audio_binder,
                             link_code,
@@ -828,25 +1804,43 @@ async fn run_connection_task(
                             local_selection.patch.as_ref().map(|(name, version, _)| {
                                 (name.clone(), version.clone())
                             }),
-                            &local_selection.rom,
+                            local_selection.rom.clone(),
                             &local_negotiated_state.save_data,
+                            bios,
                             remote_settings,
+                            remote_client_info,
+                            remote_installation_id,
                             remote_selection.game,
                             &remote_patch_overrides,
                             &remote_selection.rom,
                             &remote_negotiated_state.save_data,
                             emu_tps_counter.clone(),
+                            broadcast_server.clone(),
                             sender,
                             receiver,
-                            peer_conn,
-                            is_offerer,
+                            transport,
                             replays_path,
                             match_type,
-                            rng_seed,
-                        )?);
+                            battle_backgrounds,
+                            reveal,
+                            opponent,
+                            save_recorder,
+                        )?;
+                        if cancellation_token.is_cancelled() {
+                            // Dropping it stops its cores and unbinds its audio. The opponent finds out from the connection closing.
+                            drop(pvp);
+                            return Ok(());
+                        }
+                        *session.lock() = Some(pvp);
+                    }
+                    if let Err(e) = save_patch_history.record(
+                        &local_selection.save_path,
+                        local_selection.patch.as_ref().map(|(name, version, _)| (name.as_str(), version)),
+                    ) {
+                        log::error!("failed to record save patch history: {:?}", e);
                     }
                     egui_ctx.request_repaint();
-                    *connection_task.lock().await = None;
+                    *connection_task.lock() = None;
 
                     Ok(())
                 })(
@@ -855,15 +1849,18 @@ async fn run_connection_task(
             => {
                 r
             }
+            // Dropping the other branch cancels whatever it was waiting on, however deep in it that is.
             _ = cancellation_token.cancelled() => {
+                broadcast_server.publish(broadcast::Event::LobbyLeft);
                 Ok(())
             }
         }
     } {
         log::info!("connection task failed: {:?}", e);
-        *connection_task.lock().await = Some(ConnectionTask::Failed(e));
+        broadcast_server.publish(broadcast::Event::LobbyLeft);
+        *connection_task.lock() = Some(ConnectionTask::Failed(e));
     } else {
-        *connection_task.lock().await = None;
+        *connection_task.lock() = None;
     }
 }
 
@@ -875,10 +1872,84 @@ enum ConnectionError {
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
+    #[error(transparent)]
+    Connect(net::proxy::ConnectError),
+
+    #[error("emulator configuration mismatch: {0:?}")]
+    ConfigurationMismatch(Vec<determinism::Difference>),
+
+    #[error("timed out waiting for opponent's data, {received}/{total} chunks received")]
+    MatchStartTimedOut { received: usize, total: usize },
+
+    #[error("opponent's save is not allowed: {0}")]
+    IllegalRemoteSave(save::validation::IllegalValue),
+
+    #[error("opponent sent invalid data: {0}")]
+    InvalidRemoteState(net::protocol::InvalidNegotiatedState),
+
+    #[error("opponent cancelled starting the match")]
+    RemoteCancelled,
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+/// How long to try telling the opponent that starting the match was given up on.
+const ABORT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The connection while the match is being started. If it's dropped before the match starts, the opponent is sent an uncommit so they stop waiting for us.
+struct PendingMatchStart {
+    sender: Option<net::Sender>,
+    transport: Option<net::Transport>,
+}
+
+impl PendingMatchStart {
+    fn new(sender: net::Sender, transport: net::Transport) -> Self {
+        Self {
+            sender: Some(sender),
+            transport: Some(transport),
+        }
+    }
+
+    fn sender(&mut self) -> &mut net::Sender {
+        self.sender.as_mut().unwrap()
+    }
+
+    /// Hands the connection over to the match.
+    fn finish(mut self) -> (net::Sender, net::Transport) {
+        (self.sender.take().unwrap(), self.transport.take().unwrap())
+    }
+}
+
+impl Drop for PendingMatchStart {
+    fn drop(&mut self) {
+        let (mut sender, transport) = match (self.sender.take(), self.transport.take()) {
+            (Some(sender), Some(transport)) => (sender, transport),
+            _ => {
+                return;
+            }
+        };
+        let handle = if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle
+        } else {
+            return;
+        };
+        handle.spawn(async move {
+            match tokio::time::timeout(ABORT_TIMEOUT, sender.send_uncommit()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    log::warn!("failed to tell opponent the match start was aborted: {:?}", e);
+                }
+                Err(_) => {
+                    log::warn!("timed out telling opponent the match start was aborted");
+                }
+            }
+            // The connection has to stay open until the uncommit is out.
+            drop(transport);
+        });
+    }
+}
+
 enum ConnectionTask {
     InProgress {
         state: ConnectionState,
@@ -891,122 +1962,940 @@ enum ConnectionState {
     Starting,
     Signaling,
     Waiting,
-    InLobby(std::sync::Arc<tokio::sync::Mutex<Lobby>>),
+    /// Hosting on the local network, reachable at these addresses.
+    Listening(Vec<std::net::SocketAddr>),
+    /// Connecting to a host on the local network.
+    Dialing(String),
+    InLobby(std::sync::Arc<parking_lot::Mutex<Lobby>>),
+    /// Both sides are ready and are sending each other the data needed to start the match.
+    Exchanging {
+        sent: usize,
+        received: usize,
+        total: usize,
+    },
+}
+
+pub struct Share {
+    uri: String,
+    qr: Option<egui::TextureHandle>,
+}
+
+/// The direct connect controls, for playing over the local network without the matchmaking server.
+pub struct DirectConnect {
+    /// The address of the host to join.
+    address: String,
+    /// The port to host on.
+    port: u16,
+    /// The QR code of the address being hosted at, rendered the first time it's shown.
+    qr: Option<(std::net::SocketAddr, egui::TextureHandle)>,
+}
+
+impl DirectConnect {
+    fn new() -> Self {
+        Self {
+            address: String::new(),
+            port: net::lan::DEFAULT_PORT,
+            qr: None,
+        }
+    }
 }
 
 pub struct State {
     link_code: String,
     show_link_code: bool,
-    connection_task: std::sync::Arc<tokio::sync::Mutex<Option<ConnectionTask>>>,
+    connection_task: std::sync::Arc<parking_lot::Mutex<Option<ConnectionTask>>>,
     show_save_select: Option<gui::save_select_view::State>,
+    pending_invite: Option<linkcode::Invite>,
+    share: Option<Share>,
+    direct_connect: DirectConnect,
+    /// The name of the patch whose details are being shown.
+    show_patch_details: Option<String>,
+    lobby_presets: gui::lobby_presets::State,
+    /// Banners and icons for the games shown.
+    game_art: gui::save_view::texture_cache::TextureCache,
 }
 
 impl State {
-    pub fn new() -> Self {
+    pub fn new(pending_invite: Option<linkcode::Invite>) -> Self {
         Self {
             link_code: String::new(),
             show_link_code: false,
-            connection_task: std::sync::Arc::new(tokio::sync::Mutex::new(None)),
+            connection_task: std::sync::Arc::new(parking_lot::Mutex::new(None)),
             show_save_select: None,
+            pending_invite,
+            share: None,
+            direct_connect: DirectConnect::new(),
+            show_patch_details: None,
+            lobby_presets: gui::lobby_presets::State::new(),
+            game_art: gui::save_view::texture_cache::TextureCache::new(),
         }
     }
+
+    /// Joins a link code as if from an invite, e.g. to play the same opponent again.
+    pub fn join(&mut self, link_code: String) {
+        self.pending_invite = Some(linkcode::Invite {
+            link_code,
+            matchmaking_endpoint: None,
+        });
+    }
+
+    /// Opens the save list with a save highlighted in it, e.g. to find the save a replay was recorded with.
+    pub fn show_save(&mut self, game: &'static (dyn game::Game + Send + Sync), path: std::path::PathBuf) {
+        self.show_save_select = Some(gui::save_select_view::State::new(Some((game, Some(path)))));
+    }
+
+    /// The link code being connected with, while connecting or in a lobby.
+    pub fn link_code_in_progress(&self) -> Option<String> {
+        match &*self.connection_task.try_lock()? {
+            Some(ConnectionTask::InProgress { .. }) => Some(self.link_code.clone()),
+            _ => None,
+        }
+    }
+
+    /// Whether a connection is being made or a lobby is open, i.e. the selection shouldn't change from under it.
+    pub fn is_connecting(&self) -> bool {
+        self.connection_task
+            .try_lock()
+            .map(|connection_task| matches!(*connection_task, Some(ConnectionTask::InProgress { .. })))
+            .unwrap_or(true)
+    }
 }
 
-fn show_lobby_table(
-    ui: &mut egui::Ui,
-    cancellation_token: &tokio_util::sync::CancellationToken,
-    config: &mut config::Config,
-    lobby: &mut Lobby,
-    roms: &std::collections::HashMap<&'static (dyn game::Game + Send + Sync), Vec<u8>>,
-    patches: &std::collections::BTreeMap<String, patch::Patch>,
+fn show_patch_details_window(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    patches_path: &std::path::Path,
+    patches_scanner: patch::Scanner,
+    roms_scanner: rom::Scanner,
+    patch_validator: &patch::validation::Validator,
+    show_patch_details: &mut Option<String>,
 ) {
-    let row_height = ui.text_style_height(&egui::TextStyle::Body);
-    let spacing_x = ui.spacing().item_spacing.x;
-    let spacing_y = ui.spacing().item_spacing.y;
-    egui_extras::StripBuilder::new(ui)
-        .size(egui_extras::Size::exact(row_height + spacing_y))
-        .size(egui_extras::Size::exact(
-            if lobby
-                .local_selection
-                .as_ref()
-                .map(|s| s.patch.is_some())
-                .unwrap_or(false)
-                || lobby
-                    .remote_settings
-                    .game_info
-                    .as_ref()
-                    .map(|gi| gi.patch.is_some())
-                    .unwrap_or(false)
-            {
-                row_height * 2.0 + spacing_y * 0.5
-            } else {
-                row_height
-            },
-        ))
-        .size(egui_extras::Size::exact(row_height + spacing_y))
-        .size(egui_extras::Size::exact(row_height + spacing_y))
-        .size(egui_extras::Size::exact(row_height + spacing_y))
-        .size(egui_extras::Size::exact(row_height + spacing_y))
-        .vertical(|mut outer_strip| {
-            const CELL_WIDTH: f32 = 200.0;
-            outer_strip.strip(|sb| {
-                sb.size(egui_extras::Size::remainder())
-                    .size(egui_extras::Size::exact(CELL_WIDTH))
-                    .size(egui_extras::Size::exact(CELL_WIDTH))
-                    .horizontal(|mut strip| {
-                        strip.cell(|_ui| {});
-                        strip.cell(|ui| {
-                            ui.horizontal(|ui| {
-                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
-                                    if ui
-                                        .button(format!(
-                                            "🚶 {}",
-                                            i18n::LOCALES.lookup(&config.language, "play-leave").unwrap()
-                                        ))
-                                        .clicked()
-                                    {
-                                        cancellation_token.cancel();
-                                    }
+    let patches = patches_scanner.read();
+    let (patch_name, patch) = if let Some((name, patch)) = show_patch_details
+        .as_ref()
+        .and_then(|name| patches.get(name).map(|patch| (name, patch)))
+    {
+        (name, patch)
+    } else {
+        *show_patch_details = None;
+        return;
+    };
 
-                                    ui.horizontal_top(|ui| {
-                                        ui.with_layout(egui::Layout::left_to_right(egui::Align::Min), |ui| {
-                                            ui.set_width(ui.available_width());
-                                            ui.strong(i18n::LOCALES.lookup(&config.language, "play-you").unwrap());
-                                            if lobby.local_negotiated_state.is_some() || lobby.sender.is_none() {
-                                                ui.label(
-                                                    egui::RichText::new("✅")
-                                                        .color(egui::Color32::from_rgb(0x4c, 0xaf, 0x50)),
-                                                );
-                                            }
-                                        });
-                                    });
-                                });
-                            });
-                        });
-                        strip.cell(|ui| {
-                            ui.horizontal(|ui| {
-                                ui.strong(lobby.remote_settings.nickname.clone());
-                                ui.small(format!("{}ms", lobby.latencies.median().as_millis()));
-                                if lobby.remote_commitment.is_some() {
-                                    ui.label(
-                                        egui::RichText::new("✅").color(egui::Color32::from_rgb(0x4c, 0xaf, 0x50)),
-                                    );
-                                }
-                            });
-                        });
-                    });
-            });
+    let mut open = true;
+    egui::Window::new(format!("ℹ {}", patch.title))
+        .id(egui::Id::new("play-patch-details-window"))
+        .open(&mut open)
+        .collapsible(false)
+        .default_size(egui::vec2(480.0, 400.0))
+        .show(ctx, |ui| {
+            gui::patch_details::show(
+                ui,
+                language,
+                patches_path,
+                patch_name,
+                patch,
+                roms_scanner,
+                patch_validator,
+            );
+        });
 
-            outer_strip.strip(|sb| {
-                sb.size(egui_extras::Size::remainder())
-                    .size(egui_extras::Size::exact(CELL_WIDTH))
-                    .size(egui_extras::Size::exact(CELL_WIDTH))
-                    .horizontal(|mut strip| {
-                        strip.cell(|ui| {
-                            ui.horizontal(|ui| {
-                                ui.strong(i18n::LOCALES.lookup(&config.language, "play-details-game").unwrap());
+    if !open {
+        *show_patch_details = None;
+    }
+}
 
-                                if let Some(warning) = make_warning(&lobby, &roms, &patches) {
+fn show_share_window(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    reveal: bool,
+    clipboard: &mut arboard::Clipboard,
+    share: &mut Option<Share>,
+) {
+    let mut open = share.is_some();
+    egui::Window::new(format!("🔗 {}", i18n::LOCALES.lookup(language, "play-share")))
+        .id(egui::Id::new("play-share-window"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            let share = share.as_mut().unwrap();
+            ui.label(i18n::LOCALES.lookup(language, "play-share.description"));
+
+            if !reveal {
+                ui.weak(i18n::LOCALES.lookup(language, "play-share.hidden"));
+                return;
+            }
+
+            if share.qr.is_none() {
+                match linkcode::render_qr(&share.uri) {
+                    Ok(image) => {
+                        share.qr = Some(ui.ctx().load_texture(
+                            "share qr",
+                            egui::ColorImage::from_rgba_unmultiplied(
+                                [image.width() as usize, image.height() as usize],
+                                &image::DynamicImage::ImageLuma8(image).to_rgba8(),
+                            ),
+                            egui::TextureFilter::Nearest,
+                        ));
+                    }
+                    Err(e) => {
+                        log::error!("failed to render qr code: {:?}", e);
+                    }
+                }
+            }
+
+            ui.vertical_centered(|ui| {
+                if let Some(qr) = share.qr.as_ref() {
+                    ui.image(qr, qr.size_vec2() * 4.0);
+                }
+                ui.horizontal(|ui| {
+                    ui.monospace(&share.uri);
+                    if ui.button(i18n::LOCALES.lookup(language, "play-share.copy")).clicked() {
+                        let _ = clipboard.set_text(share.uri.clone());
+                    }
+                });
+            });
+        });
+    if !open {
+        *share = None;
+    }
+}
+
+/// Shows where the opponent can reach us while hosting on the local network, with a QR code of the first address.
+fn show_listening_addresses(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    addrs: &[std::net::SocketAddr],
+    direct_connect: &mut DirectConnect,
+) {
+    let addr = if let Some(addr) = addrs.first() {
+        *addr
+    } else {
+        ui.weak(i18n::LOCALES.lookup(language, "play-direct-connect-no-addresses"));
+        return;
+    };
+
+    ui.label(i18n::LOCALES.lookup(language, "play-direct-connect-addresses"));
+    ui.horizontal_wrapped(|ui| {
+        for addr in addrs {
+            ui.monospace(addr.to_string());
+        }
+    });
+
+    if direct_connect
+        .qr
+        .as_ref()
+        .map(|(qr_addr, _)| *qr_addr != addr)
+        .unwrap_or(true)
+    {
+        direct_connect.qr = match linkcode::render_qr(&addr.to_string()) {
+            Ok(image) => Some((
+                addr,
+                ui.ctx().load_texture(
+                    "direct connect qr",
+                    egui::ColorImage::from_rgba_unmultiplied(
+                        [image.width() as usize, image.height() as usize],
+                        &image::DynamicImage::ImageLuma8(image).to_rgba8(),
+                    ),
+                    egui::TextureFilter::Nearest,
+                ),
+            )),
+            Err(e) => {
+                log::error!("failed to render qr code: {:?}", e);
+                None
+            }
+        };
+    }
+
+    if let Some((_, qr)) = direct_connect.qr.as_ref() {
+        ui.image(qr, qr.size_vec2() * 2.0);
+    }
+}
+
+/// Shows the controls for hosting or joining a match on the local network. Returns how to connect if either was asked for.
+fn show_direct_connect(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    enabled: bool,
+    banned_word: Option<&str>,
+    direct_connect: &mut DirectConnect,
+) -> Option<ConnectTo> {
+    let mut connect_to = None;
+    egui::CollapsingHeader::new(i18n::LOCALES.lookup(language, "play-direct-connect"))
+        .id_source("play-direct-connect")
+        .show(ui, |ui| {
+            ui.weak(i18n::LOCALES.lookup(language, "play-direct-connect.description"));
+
+            let on_banned_word = |resp: egui::Response| {
+                if let Some(word) = banned_word {
+                    resp.on_disabled_hover_text(i18n::LOCALES.lookup_with_args(
+                        language,
+                        "play-fight.banned-nickname",
+                        &std::collections::HashMap::from([("word", word.to_string().into())]),
+                    ))
+                } else {
+                    resp
+                }
+            };
+
+            ui.horizontal(|ui| {
+                ui.label(i18n::LOCALES.lookup(language, "play-direct-connect-port"));
+                ui.add_enabled(
+                    enabled,
+                    egui::DragValue::new(&mut direct_connect.port).clamp_range(1..=u16::MAX),
+                );
+                if on_banned_word(ui.add_enabled(
+                    enabled && banned_word.is_none(),
+                    egui::Button::new(format!(
+                        "📡 {}",
+                        i18n::LOCALES.lookup(language, "play-direct-connect-host")
+                    )),
+                ))
+                .clicked()
+                {
+                    connect_to = Some(ConnectTo::LanHost {
+                        port: direct_connect.port,
+                    });
+                }
+            });
+
+            ui.horizontal(|ui| {
+                let address_resp = ui.add_enabled(
+                    enabled,
+                    egui::TextEdit::singleline(&mut direct_connect.address)
+                        .hint_text(i18n::LOCALES.lookup(language, "play-direct-connect-address")),
+                );
+                let can_join = enabled && banned_word.is_none() && !direct_connect.address.trim().is_empty();
+                let join_clicked = on_banned_word(ui.add_enabled(
+                    can_join,
+                    egui::Button::new(format!(
+                        "🔌 {}",
+                        i18n::LOCALES.lookup(language, "play-direct-connect-join")
+                    )),
+                ))
+                .clicked();
+                if can_join
+                    && (join_clicked || (address_resp.lost_focus() && ui.ctx().input().key_pressed(egui::Key::Enter)))
+                {
+                    connect_to = Some(ConnectTo::LanJoin {
+                        addr: direct_connect.address.trim().to_string(),
+                    });
+                }
+            });
+        });
+    connect_to
+}
+
+/// The round time limits offered in the lobby, in seconds.
+const ROUND_TIME_LIMITS: &[u32] = &[60, 90, 120, 180, 300];
+
+fn requires_tango_text(language: &unic_langid::LanguageIdentifier, minimum_tango_version: &semver::Version) -> String {
+    i18n::LOCALES.lookup_with_args(
+        language,
+        "play-patch-requires-tango",
+        &std::collections::HashMap::from([("version", minimum_tango_version.to_string().into())]),
+    )
+}
+
+/// Shows the version a client reported, with the rest of what it reported in a tooltip.
+fn show_client_info(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    client_info: Option<&net::protocol::ClientInfo>,
+) {
+    let unknown = i18n::LOCALES.lookup(language, "play-remote-client-info.unknown");
+    let version = client_info
+        .map(|client_info| client_info.version.clone())
+        .filter(|version| !version.is_empty())
+        .unwrap_or_else(|| unknown.clone());
+    let platform = client_info
+        .map(|client_info| client_info.platform.clone())
+        .filter(|platform| !platform.is_empty())
+        .unwrap_or_else(|| unknown.clone());
+
+    let mut details = vec![
+        i18n::LOCALES.lookup_with_args(
+            language,
+            "play-remote-client-info",
+            &std::collections::HashMap::from([("version", version.clone().into())]),
+        ),
+        i18n::LOCALES.lookup_with_args(
+            language,
+            "play-remote-client-info.platform",
+            &std::collections::HashMap::from([("platform", platform.into())]),
+        ),
+    ];
+    if let Some(client_info) = client_info {
+        details.push(i18n::LOCALES.lookup(
+            language,
+            if client_info.is_release {
+                "play-remote-client-info.release"
+            } else {
+                "play-remote-client-info.dev"
+            },
+        ));
+    }
+
+    ui.weak(egui::RichText::new(version).small())
+        .on_hover_text(details.join("\n"));
+}
+
+/// Suggests an input delay that covers the median round trip time to the opponent.
+fn suggested_input_delay(latencies: &stats::DeltaCounter) -> u32 {
+    std::cmp::min(
+        10,
+        std::cmp::max(
+            2,
+            ((latencies.median() * 60).as_nanos() / std::time::Duration::from_secs(1).as_nanos()) as i32 + 1 - 2,
+        ),
+    ) as u32
+}
+
+fn round_time_limit_text(language: &unic_langid::LanguageIdentifier, round_time_limit_secs: Option<u32>) -> String {
+    if let Some(secs) = round_time_limit_secs {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        i18n::LOCALES.lookup(language, "play-details-round-time-limit.none")
+    }
+}
+
+/// Switches the selection to another version of the patch it has selected, applying it to the ROM again.
+fn select_patch_version(
+    selection: &mut gui::Selection,
+    roms: &std::collections::HashMap<&'static (dyn game::Game + Send + Sync), std::sync::Arc<[u8]>>,
+    patched_roms: &patch::cache::PatchedRoms,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+    patches_path: &std::path::Path,
+    patch_name: &str,
+    version: &semver::Version,
+) {
+    let rom = roms.get(&selection.game).unwrap();
+    let (rom_code, revision) = selection.game.rom_code_and_revision();
+
+    let version_metadata =
+        if let Some(version_metadata) = patches.get(patch_name).and_then(|p| p.versions.get(version)).cloned() {
+            version_metadata
+        } else {
+            return;
+        };
+
+    let rom = match patched_roms.get_or_apply(rom, selection.game, patches_path, patch_name, version) {
+        Ok(r) => r,
+        Err(e) => {
+            log::error!(
+                "failed to apply patch {}: {:?}: {:?}",
+                patch_name,
+                (rom_code, revision),
+                e
+            );
+            return;
+        }
+    };
+
+    *selection = gui::Selection::new(
+        selection.game.clone(),
+        selection.save.clone(),
+        Some((patch_name.to_string(), version.clone(), version_metadata)),
+        rom,
+    );
+}
+
+/// If the opponent doesn't have the selected patch version, offers to switch to the newest version of the patch both sides have.
+fn show_patch_version_suggestion(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    selection: &mut gui::Selection,
+    lobby: &Lobby,
+    roms: &std::collections::HashMap<&'static (dyn game::Game + Send + Sync), std::sync::Arc<[u8]>>,
+    patched_roms: &patch::cache::PatchedRoms,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+    patches_path: &std::path::Path,
+) {
+    let (patch_name, patch_version) = if let Some((patch_name, patch_version, _)) = selection.patch.as_ref() {
+        (patch_name.clone(), patch_version.clone())
+    } else {
+        return;
+    };
+
+    // Until the opponent has picked something, we may not have heard what patches they have yet.
+    if lobby.remote_settings.game_info.is_none() {
+        return;
+    }
+
+    let remote_versions = lobby
+        .remote_settings
+        .available_patches
+        .iter()
+        .find(|(name, _)| *name == patch_name)
+        .map(|(_, versions)| versions.as_slice())
+        .unwrap_or(&[]);
+    if remote_versions.contains(&patch_version) {
+        return;
+    }
+
+    let common_version = patches.get(&patch_name).and_then(|patch| {
+        patch
+            .versions
+            .iter()
+            .filter(|(version, info)| {
+                info.supported_games.contains(&selection.game) && info.is_usable() && remote_versions.contains(version)
+            })
+            .map(|(version, _)| version.clone())
+            .max()
+    });
+
+    ui.horizontal_wrapped(|ui| {
+        let mut layout_job = egui::text::LayoutJob::default();
+        gui::warning::append_to_layout_job(ui, &mut layout_job);
+        layout_job.append(
+            &if common_version.is_some() {
+                i18n::LOCALES.lookup_with_args(
+                    language,
+                    "play-patch-version-suggestion",
+                    &std::collections::HashMap::from([("version", patch_version.to_string().into())]),
+                )
+            } else {
+                i18n::LOCALES.lookup(language, "play-patch-version-suggestion.none")
+            },
+            0.0,
+            egui::TextFormat::simple(
+                ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
+                ui.visuals().text_color(),
+            ),
+        );
+        ui.label(layout_job);
+
+        if let Some(common_version) = common_version {
+            if ui
+                .button(i18n::LOCALES.lookup_with_args(
+                    language,
+                    "play-patch-version-suggestion.switch",
+                    &std::collections::HashMap::from([("version", common_version.to_string().into())]),
+                ))
+                .clicked()
+            {
+                select_patch_version(
+                    selection,
+                    roms,
+                    patched_roms,
+                    patches,
+                    patches_path,
+                    &patch_name,
+                    &common_version,
+                );
+            }
+        }
+    });
+}
+
+fn show_unsupported_match_type_window(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    lobby: &mut Lobby,
+    game: &'static (dyn game::Game + Send + Sync),
+) {
+    let match_type = if let Some(match_type) = lobby.unsupported_match_type {
+        match_type
+    } else {
+        return;
+    };
+
+    let mut confirmed = None;
+    egui::Window::new(i18n::LOCALES.lookup(language, "play-unsupported-match-type"))
+        .id(egui::Id::new("unsupported-match-type-window"))
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(
+                i18n::LOCALES.lookup_with_args(
+                    language,
+                    "play-unsupported-match-type.description",
+                    &std::collections::HashMap::from([(
+                        "match_type",
+                        game::match_type_name(game.family_and_variant().0, language, match_type).into(),
+                    )]),
+                ),
+            );
+            ui.horizontal(|ui| {
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "play-unsupported-match-type.confirm"))
+                    .clicked()
+                {
+                    confirmed = Some(true);
+                }
+                if ui.button(i18n::LOCALES.lookup(language, "play-cancel")).clicked() {
+                    confirmed = Some(false);
+                }
+            });
+        });
+
+    if let Some(confirmed) = confirmed {
+        lobby.unsupported_match_type = None;
+        if confirmed {
+            lobby.set_match_type(match_type);
+        }
+    }
+}
+
+fn show_replay_consent_window(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    lobby: &mut Lobby,
+) -> Option<bool> {
+    if !lobby.asking_replay_consent {
+        return None;
+    }
+
+    let mut allowed = None;
+    egui::Window::new(i18n::LOCALES.lookup(language, "play-replay-consent"))
+        .id(egui::Id::new("replay-consent-window"))
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(i18n::LOCALES.lookup(language, "play-replay-consent.description"));
+            ui.horizontal(|ui| {
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "play-replay-consent.allow"))
+                    .clicked()
+                {
+                    allowed = Some(true);
+                }
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "play-replay-consent.deny"))
+                    .clicked()
+                {
+                    allowed = Some(false);
+                }
+            });
+        });
+
+    if allowed.is_some() {
+        lobby.asking_replay_consent = false;
+    }
+    allowed
+}
+
+fn desync_risk_color(ui: &egui::Ui, level: desyncrisk::Level) -> egui::Color32 {
+    match level {
+        desyncrisk::Level::Low => gui::theme::colors(ui.ctx()).ready,
+        desyncrisk::Level::Elevated => gui::theme::colors(ui.ctx()).warning,
+        desyncrisk::Level::High => egui::Color32::RED,
+    }
+}
+
+fn show_desync_risk_factors(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    assessment: &desyncrisk::Assessment,
+) {
+    if assessment.contributions.is_empty() {
+        ui.label(i18n::LOCALES.lookup(language, "desync-risk.no-factors"));
+        return;
+    }
+    for contribution in assessment.contributions.iter() {
+        ui.colored_label(
+            desync_risk_color(ui, contribution.level),
+            format!("⬤ {}", contribution.factor.description(language)),
+        );
+        ui.weak(contribution.factor.advice(language));
+    }
+}
+
+/// Shows the estimated desync risk next to the ready checkbox, which can be clicked for what went into it.
+fn show_desync_risk(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    lobby: &mut Lobby,
+    assessment: &desyncrisk::Assessment,
+) {
+    let level = assessment.level();
+    if ui
+        .selectable_label(
+            lobby.showing_desync_risk,
+            egui::RichText::new(format!("⬤ {}", level.description(language))).color(desync_risk_color(ui, level)),
+        )
+        .on_hover_text(i18n::LOCALES.lookup(language, "desync-risk.description"))
+        .clicked()
+    {
+        lobby.showing_desync_risk = !lobby.showing_desync_risk;
+    }
+
+    egui::Window::new(i18n::LOCALES.lookup(language, "desync-risk"))
+        .id(egui::Id::new("desync-risk-window"))
+        .open(&mut lobby.showing_desync_risk)
+        .resizable(false)
+        .collapsible(false)
+        .show(ui.ctx(), |ui| {
+            ui.label(i18n::LOCALES.lookup(language, "desync-risk.description"));
+            ui.separator();
+            show_desync_risk_factors(ui, language, assessment);
+        });
+}
+
+fn show_desync_risk_confirmation_window(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    lobby: &mut Lobby,
+    assessment: &desyncrisk::Assessment,
+) -> Option<bool> {
+    if !lobby.confirming_desync_risk {
+        return None;
+    }
+
+    let mut confirmed = None;
+    egui::Window::new(i18n::LOCALES.lookup(language, "desync-risk-confirm"))
+        .id(egui::Id::new("desync-risk-confirm-window"))
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(i18n::LOCALES.lookup(language, "desync-risk-confirm.description"));
+            ui.separator();
+            show_desync_risk_factors(ui, language, assessment);
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "desync-risk-confirm.ready-anyway"))
+                    .clicked()
+                {
+                    confirmed = Some(true);
+                }
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "desync-risk-confirm.cancel"))
+                    .clicked()
+                {
+                    confirmed = Some(false);
+                }
+            });
+        });
+
+    if confirmed.is_some() {
+        lobby.confirming_desync_risk = false;
+    }
+    confirmed
+}
+
+fn replay_recording_text(
+    language: &unic_langid::LanguageIdentifier,
+    replay_recording: net::protocol::ReplayRecording,
+) -> String {
+    i18n::LOCALES.lookup(
+        language,
+        match replay_recording {
+            net::protocol::ReplayRecording::Always => "play-details-replay-recording.always",
+            net::protocol::ReplayRecording::Ask => "play-details-replay-recording.ask",
+            net::protocol::ReplayRecording::Never => "play-details-replay-recording.never",
+        },
+    )
+}
+
+fn input_delay_text(language: &unic_langid::LanguageIdentifier, input_delay: u32, input_delay_handicap: u32) -> String {
+    i18n::LOCALES.lookup_with_args(
+        language,
+        "play-details-input-delay-handicap.total",
+        &std::collections::HashMap::from([
+            ("input_delay", input_delay.into()),
+            ("input_delay_handicap", input_delay_handicap.into()),
+            ("total", (input_delay + input_delay_handicap).into()),
+        ]),
+    )
+}
+
+/// Shows what's known about the opponent from playing them before, or asks who they are if their nickname isn't enough to tell.
+fn show_opponent(ui: &mut egui::Ui, language: &unic_langid::LanguageIdentifier, lobby: &mut Lobby) {
+    if lobby.remote_settings.nickname.is_empty() {
+        return;
+    }
+
+    let nickname_args = std::collections::HashMap::from([(
+        "nickname",
+        contentpolicy::display_nickname(&lobby.remote_settings.nickname).into(),
+    )]);
+    match lobby.opponent.clone() {
+        opponents::Recognition::Candidates(ids) => {
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                if ids.len() == 1 {
+                    "play-opponent-candidates.pinned"
+                } else {
+                    "play-opponent-candidates"
+                },
+                &nickname_args,
+            ));
+            ui.horizontal_wrapped(|ui| {
+                for id in ids {
+                    let entry = if let Some(entry) = lobby.opponents.get(&id) {
+                        entry
+                    } else {
+                        continue;
+                    };
+                    let button = ui.button(format!(
+                        "{} ({})",
+                        contentpolicy::display_nickname(entry.nickname()),
+                        gui::opponents_pane::record_text(language, &entry)
+                    ));
+                    let button = if !entry.notes.is_empty() {
+                        button.on_hover_text(entry.notes.clone())
+                    } else {
+                        button
+                    };
+                    if button.clicked() {
+                        lobby.set_opponent(Some(id));
+                    }
+                }
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "play-opponent-candidates.someone-new"))
+                    .clicked()
+                {
+                    lobby.set_opponent(None);
+                }
+            });
+            return;
+        }
+        opponents::Recognition::Known(id) => {
+            if let Some(entry) = lobby.opponents.get(&id) {
+                ui.horizontal(|ui| {
+                    if entry.pinned {
+                        ui.label("📌");
+                    }
+                    ui.label(gui::opponents_pane::record_text(language, &entry));
+                });
+            }
+        }
+        opponents::Recognition::Unknown => {
+            ui.label(i18n::LOCALES.lookup_with_args(language, "play-opponent-unknown", &nickname_args));
+        }
+    }
+
+    if ui
+        .add(
+            egui::TextEdit::singleline(&mut lobby.opponent_notes)
+                .hint_text(i18n::LOCALES.lookup(language, "opponents-notes.placeholder"))
+                .desired_width(f32::INFINITY),
+        )
+        .lost_focus()
+    {
+        lobby.save_opponent_notes();
+    }
+}
+
+fn show_lobby_table(
+    ui: &mut egui::Ui,
+    cancellation_token: &tokio_util::sync::CancellationToken,
+    config: &mut config::Config,
+    lobby: &mut Lobby,
+    roms: &std::collections::HashMap<&'static (dyn game::Game + Send + Sync), std::sync::Arc<[u8]>>,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+    game_art: &mut gui::save_view::texture_cache::TextureCache,
+) {
+    let row_height = ui.text_style_height(&egui::TextStyle::Body);
+    let spacing_x = ui.spacing().item_spacing.x;
+    let spacing_y = ui.spacing().item_spacing.y;
+    egui_extras::StripBuilder::new(ui)
+        .size(egui_extras::Size::exact(row_height + spacing_y))
+        .size(egui_extras::Size::exact(
+            if lobby
+                .local_selection
+                .as_ref()
+                .map(|s| s.patch.is_some())
+                .unwrap_or(false)
+                || lobby
+                    .remote_settings
+                    .game_info
+                    .as_ref()
+                    .map(|gi| gi.patch.is_some())
+                    .unwrap_or(false)
+            {
+                row_height * 2.0 + spacing_y * 0.5
+            } else {
+                row_height
+            },
+        ))
+        .size(egui_extras::Size::exact(row_height + spacing_y))
+        .size(egui_extras::Size::exact(row_height + spacing_y))
+        .size(egui_extras::Size::exact(row_height + spacing_y))
+        .size(egui_extras::Size::exact(row_height + spacing_y))
+        .size(egui_extras::Size::exact(row_height + spacing_y))
+        .size(egui_extras::Size::exact(row_height + spacing_y))
+        .size(egui_extras::Size::exact(row_height + spacing_y))
+        .vertical(|mut outer_strip| {
+            const CELL_WIDTH: f32 = 200.0;
+            outer_strip.strip(|sb| {
+                sb.size(egui_extras::Size::remainder())
+                    .size(egui_extras::Size::exact(CELL_WIDTH))
+                    .size(egui_extras::Size::exact(CELL_WIDTH))
+                    .horizontal(|mut strip| {
+                        strip.cell(|_ui| {});
+                        strip.cell(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                                    if ui
+                                        .add_enabled(
+                                            !cancellation_token.is_cancelled(),
+                                            egui::Button::new(format!(
+                                                "🚶 {}",
+                                                i18n::LOCALES.lookup(&config.language, "play-leave")
+                                            )),
+                                        )
+                                        .clicked()
+                                    {
+                                        cancellation_token.cancel();
+                                    }
+
+                                    ui.horizontal_top(|ui| {
+                                        ui.with_layout(egui::Layout::left_to_right(egui::Align::Min), |ui| {
+                                            ui.set_width(ui.available_width());
+                                            ui.strong(i18n::LOCALES.lookup(&config.language, "play-you"));
+                                            if lobby.local_negotiated_state.is_some() || lobby.outgoing.is_none() {
+                                                ui.label(
+                                                    egui::RichText::new("✅").color(gui::theme::colors(ui.ctx()).ready),
+                                                );
+                                            }
+                                        });
+                                    });
+                                });
+                            });
+                        });
+                        strip.cell(|ui| {
+                            ui.horizontal(|ui| {
+                                gui::fonts::request(&lobby.remote_settings.nickname);
+                                ui.strong(contentpolicy::display_nickname(&lobby.remote_settings.nickname));
+                                // Spikes are what cause rollbacks, so the 95th percentile says more about the connection than the median.
+                                let latency = lobby.latencies.percentiles();
+                                ui.small(format!("{}ms", latency.p95.as_millis())).on_hover_text(
+                                    i18n::LOCALES.lookup_with_args(
+                                        &config.language,
+                                        "play-latency-percentiles",
+                                        &std::collections::HashMap::from([
+                                            ("window", stats::window::LOBBY_LATENCY.into()),
+                                            ("p50", (latency.p50.as_millis() as u64).into()),
+                                            ("p95", (latency.p95.as_millis() as u64).into()),
+                                            ("p99", (latency.p99.as_millis() as u64).into()),
+                                        ]),
+                                    ),
+                                );
+                                show_client_info(ui, &config.language, lobby.remote_client_info.as_ref());
+                                if lobby.remote_commitment.is_some() {
+                                    ui.label(egui::RichText::new("✅").color(gui::theme::colors(ui.ctx()).ready));
+                                }
+                            });
+                        });
+                    });
+            });
+
+            outer_strip.strip(|sb| {
+                sb.size(egui_extras::Size::remainder())
+                    .size(egui_extras::Size::exact(CELL_WIDTH))
+                    .size(egui_extras::Size::exact(CELL_WIDTH))
+                    .horizontal(|mut strip| {
+                        strip.cell(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.strong(i18n::LOCALES.lookup(&config.language, "play-details-game"));
+
+                                if let Some(warning) = make_warning(&lobby, &roms, &patches) {
                                     gui::warning::show(ui, warning.description(&config.language));
                                 }
                             });
@@ -1015,20 +2904,27 @@ fn show_lobby_table(
                             ui.vertical(|ui| {
                                 if let Some(local_selection) = lobby.local_selection.as_ref() {
                                     let (family, variant) = local_selection.game.family_and_variant();
-                                    ui.label(if game::find_by_family_and_variant(family, variant).is_some() {
-                                        i18n::LOCALES
-                                            .lookup(&config.language, &format!("game-{}", family))
-                                            .unwrap()
-                                    } else {
-                                        i18n::LOCALES
-                                            .lookup(&config.language, "play-details-game.unknown")
-                                            .unwrap()
+                                    ui.horizontal(|ui| {
+                                        gui::game_art::show(
+                                            ui,
+                                            game_art,
+                                            &config.language,
+                                            local_selection.game,
+                                            game::ArtKind::Icon,
+                                            None,
+                                            gui::game_art::icon_size(row_height),
+                                        );
+                                        ui.label(if game::find_by_family_and_variant(family, variant).is_some() {
+                                            i18n::LOCALES.lookup(&config.language, &format!("game-{}", family))
+                                        } else {
+                                            i18n::LOCALES.lookup(&config.language, "play-details-game.unknown")
+                                        });
                                     });
                                     if let Some((patch_name, version, _)) = local_selection.patch.as_ref() {
                                         ui.label(format!("{} v{}", patch_name, version));
                                     }
                                 } else {
-                                    ui.label(i18n::LOCALES.lookup(&config.language, "play-no-game").unwrap());
+                                    ui.label(i18n::LOCALES.lookup(&config.language, "play-no-game"));
                                 }
                             });
                         });
@@ -1038,19 +2934,26 @@ fn show_lobby_table(
                                     let (family, variant) = &game_info.family_and_variant;
                                     if let Some(game) = game::find_by_family_and_variant(&family, *variant) {
                                         let (family, _) = game.family_and_variant();
-                                        ui.label(
-                                            i18n::LOCALES
-                                                .lookup(&config.language, &format!("game-{}", family))
-                                                .unwrap(),
-                                        );
+                                        ui.horizontal(|ui| {
+                                            gui::game_art::show(
+                                                ui,
+                                                game_art,
+                                                &config.language,
+                                                game,
+                                                game::ArtKind::Icon,
+                                                None,
+                                                gui::game_art::icon_size(row_height),
+                                            );
+                                            ui.label(i18n::LOCALES.lookup(&config.language, &format!("game-{}", family)));
+                                        });
                                         if let Some(pi) = game_info.patch.as_ref() {
                                             ui.label(format!("{} v{}", pi.name, pi.version));
                                         }
                                     } else {
-                                        ui.label(i18n::LOCALES.lookup(&config.language, "play-no-game").unwrap());
+                                        ui.label(i18n::LOCALES.lookup(&config.language, "play-no-game"));
                                     }
                                 } else {
-                                    ui.label(i18n::LOCALES.lookup(&config.language, "play-no-game").unwrap());
+                                    ui.label(i18n::LOCALES.lookup(&config.language, "play-no-game"));
                                 }
                             });
                         });
@@ -1064,20 +2967,14 @@ fn show_lobby_table(
                     .horizontal(|mut strip| {
                         strip.cell(|ui| {
                             ui.horizontal(|ui| {
-                                ui.strong(
-                                    i18n::LOCALES
-                                        .lookup(&config.language, "play-details-match-type")
-                                        .unwrap(),
-                                );
+                                ui.strong(i18n::LOCALES.lookup(&config.language, "play-details-match-type"));
                                 if lobby.local_selection.is_some()
                                     && lobby.remote_settings.game_info.is_some()
                                     && lobby.match_type != lobby.remote_settings.match_type
                                 {
                                     gui::warning::show(
                                         ui,
-                                        i18n::LOCALES
-                                            .lookup(&config.language, "lobby-issue-match-type-mismatch")
-                                            .unwrap(),
+                                        i18n::LOCALES.lookup(&config.language, "lobby-issue-match-type-mismatch"),
                                     );
                                 }
                             });
@@ -1088,66 +2985,81 @@ fn show_lobby_table(
                                 .as_ref()
                                 .map(|local_selection| local_selection.game);
                             ui.add_enabled_ui(game.is_some(), |ui| {
-                                egui::ComboBox::new("start-match-type-combobox", "")
-                                    .width(150.0)
-                                    .selected_text(if let Some(game) = game.as_ref() {
-                                        i18n::LOCALES
-                                            .lookup(
-                                                &config.language,
-                                                &format!(
-                                                    "game-{}.match-type-{}-{}",
-                                                    game.family_and_variant().0,
-                                                    lobby.match_type.0,
-                                                    lobby.match_type.1
-                                                ),
-                                            )
-                                            .unwrap()
-                                    } else {
-                                        "".to_string()
-                                    })
-                                    .show_ui(ui, |ui| {
-                                        if let Some(game) = game {
-                                            let mut match_type = lobby.match_type;
-                                            for (typ, subtype_count) in game.match_types().iter().enumerate() {
-                                                for subtype in 0..*subtype_count {
+                                let mut match_type = lobby.match_type;
+                                let family = game.map(|game| game.family_and_variant().0).unwrap_or("");
+                                // The subtype only gets a combobox of its own if there's more than one to pick from.
+                                let has_subtypes = game
+                                    .map(|game| game.match_subtypes(match_type.0).len() > 1)
+                                    .unwrap_or(false);
+                                ui.horizontal(|ui| {
+                                    egui::ComboBox::new("start-match-type-combobox", "")
+                                        .width(if has_subtypes { 90.0 } else { 150.0 })
+                                        .selected_text(if game.is_some() {
+                                            game::match_type_name(family, &config.language, (match_type.0, 0))
+                                        } else {
+                                            "".to_string()
+                                        })
+                                        .show_ui(ui, |ui| {
+                                            if let Some(game) = game {
+                                                for typ in 0..game.match_types().len() as u8 {
+                                                    // A type keeps its subtype when switched to, if it has the same one.
+                                                    let subtype = if game.has_match_type((typ, match_type.1)) {
+                                                        match_type.1
+                                                    } else {
+                                                        0
+                                                    };
+                                                    if ui
+                                                        .selectable_label(
+                                                            match_type.0 == typ,
+                                                            game::match_type_name(family, &config.language, (typ, 0)),
+                                                        )
+                                                        .clicked()
+                                                    {
+                                                        match_type = (typ, subtype);
+                                                    }
+                                                }
+                                            }
+                                        });
+                                    if let Some(game) = game.filter(|_| has_subtypes) {
+                                        egui::ComboBox::new("start-match-subtype-combobox", "")
+                                            .width(90.0)
+                                            .selected_text(game::match_type_name(family, &config.language, match_type))
+                                            .show_ui(ui, |ui| {
+                                                for subtype in game.match_subtypes(match_type.0) {
                                                     ui.selectable_value(
-                                                        &mut match_type,
-                                                        (typ as u8, subtype as u8),
-                                                        i18n::LOCALES
-                                                            .lookup(
-                                                                &config.language,
-                                                                &format!(
-                                                                    "game-{}.match-type-{}-{}",
-                                                                    game.family_and_variant().0,
-                                                                    typ,
-                                                                    subtype
-                                                                ),
-                                                            )
-                                                            .unwrap(),
+                                                        &mut match_type.1,
+                                                        subtype,
+                                                        game::match_type_name(
+                                                            family,
+                                                            &config.language,
+                                                            (match_type.0, subtype),
+                                                        ),
                                                     );
                                                 }
-                                                config.default_match_type = match_type.0;
-                                            }
-                                            if match_type != lobby.match_type {
-                                                let _ = sync::block_on(lobby.set_match_type(match_type));
-                                            }
+                                            });
+                                    }
+                                });
+                                if let Some(game) = game {
+                                    if match_type != lobby.match_type {
+                                        config.default_match_type = match_type.0;
+                                        config.default_match_subtype = match_type.1;
+                                        if game.hooks().capabilities().supports_match_type(match_type) {
+                                            lobby.set_match_type(match_type);
+                                        } else {
+                                            lobby.unsupported_match_type = Some(match_type);
                                         }
-                                    });
+                                    }
+                                    show_unsupported_match_type_window(ui.ctx(), &config.language, lobby, game);
+                                }
                             });
                         });
                         strip.cell(|ui| {
                             ui.label(if let Some(game_info) = lobby.remote_settings.game_info.as_ref() {
-                                i18n::LOCALES
-                                    .lookup(
-                                        &config.language,
-                                        &format!(
-                                            "game-{}.match-type-{}-{}",
-                                            game_info.family_and_variant.0,
-                                            lobby.remote_settings.match_type.0,
-                                            lobby.remote_settings.match_type.1,
-                                        ),
-                                    )
-                                    .unwrap()
+                                game::match_type_name(
+                                    &game_info.family_and_variant.0,
+                                    &config.language,
+                                    lobby.remote_settings.match_type,
+                                )
                             } else {
                                 "".to_string()
                             });
@@ -1161,16 +3073,75 @@ fn show_lobby_table(
                     .size(egui_extras::Size::exact(CELL_WIDTH))
                     .horizontal(|mut strip| {
                         strip.cell(|ui| {
-                            ui.strong(
-                                i18n::LOCALES
-                                    .lookup(&config.language, "play-details-reveal-setup")
-                                    .unwrap(),
-                            );
+                            ui.horizontal(|ui| {
+                                ui.strong(i18n::LOCALES.lookup(&config.language, "play-details-round-time-limit"))
+                                    .on_hover_text(
+                                        i18n::LOCALES
+                                            .lookup(&config.language, "play-details-round-time-limit.description"),
+                                    );
+                                if lobby.local_selection.is_some()
+                                    && lobby.remote_settings.game_info.is_some()
+                                    && lobby.round_time_limit_secs != lobby.remote_settings.round_time_limit_secs
+                                {
+                                    gui::warning::show(
+                                        ui,
+                                        i18n::LOCALES.lookup(&config.language, "lobby-issue-round-time-limit-mismatch"),
+                                    );
+                                }
+                            });
+                        });
+                        strip.cell(|ui| {
+                            let supported = lobby
+                                .local_selection
+                                .as_ref()
+                                .map(|local_selection| local_selection.game.hooks().supports_round_time_limit())
+                                .unwrap_or(false);
+                            ui.add_enabled_ui(supported, |ui| {
+                                let mut round_time_limit_secs = lobby.round_time_limit_secs;
+                                egui::ComboBox::new("start-round-time-limit-combobox", "")
+                                    .width(150.0)
+                                    .selected_text(round_time_limit_text(&config.language, round_time_limit_secs))
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(
+                                            &mut round_time_limit_secs,
+                                            None,
+                                            round_time_limit_text(&config.language, None),
+                                        );
+                                        for secs in ROUND_TIME_LIMITS {
+                                            ui.selectable_value(
+                                                &mut round_time_limit_secs,
+                                                Some(*secs),
+                                                round_time_limit_text(&config.language, Some(*secs)),
+                                            );
+                                        }
+                                    });
+                                if round_time_limit_secs != lobby.round_time_limit_secs {
+                                    lobby.set_round_time_limit(round_time_limit_secs);
+                                }
+                            });
+                        });
+                        strip.cell(|ui| {
+                            ui.label(if lobby.remote_settings.game_info.is_some() {
+                                round_time_limit_text(&config.language, lobby.remote_settings.round_time_limit_secs)
+                            } else {
+                                "".to_string()
+                            });
+                        });
+                    });
+            });
+
+            outer_strip.strip(|sb| {
+                sb.size(egui_extras::Size::remainder())
+                    .size(egui_extras::Size::exact(CELL_WIDTH))
+                    .size(egui_extras::Size::exact(CELL_WIDTH))
+                    .horizontal(|mut strip| {
+                        strip.cell(|ui| {
+                            ui.strong(i18n::LOCALES.lookup(&config.language, "play-details-reveal-setup"));
                         });
                         strip.cell(|ui| {
                             let mut checked = lobby.reveal_setup;
                             ui.checkbox(&mut checked, "");
-                            let _ = sync::block_on(lobby.set_reveal_setup(checked));
+                            lobby.set_reveal_setup(checked);
                         });
                         strip.cell(|ui| {
                             ui.checkbox(&mut lobby.remote_settings.reveal_setup.clone(), "");
@@ -1178,39 +3149,95 @@ fn show_lobby_table(
                     });
             });
 
+            outer_strip.strip(|sb| {
+                sb.size(egui_extras::Size::remainder())
+                    .size(egui_extras::Size::exact(CELL_WIDTH))
+                    .size(egui_extras::Size::exact(CELL_WIDTH))
+                    .horizontal(|mut strip| {
+                        strip.cell(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.strong(i18n::LOCALES.lookup(&config.language, "play-details-replay-recording"))
+                                    .on_hover_text(
+                                        i18n::LOCALES
+                                            .lookup(&config.language, "play-details-replay-recording.description"),
+                                    );
+                                if lobby.replay_recording == net::protocol::ReplayRecording::Never
+                                    || (lobby.remote_settings.game_info.is_some()
+                                        && lobby.remote_settings.replay_recording
+                                            == net::protocol::ReplayRecording::Never)
+                                {
+                                    gui::warning::show(
+                                        ui,
+                                        i18n::LOCALES
+                                            .lookup(&config.language, "play-details-replay-recording.not-recorded"),
+                                    );
+                                }
+                            });
+                        });
+                        strip.cell(|ui| {
+                            let mut replay_recording = lobby.replay_recording;
+                            egui::ComboBox::new("start-replay-recording-combobox", "")
+                                .width(150.0)
+                                .selected_text(replay_recording_text(&config.language, replay_recording))
+                                .show_ui(ui, |ui| {
+                                    for option in [
+                                        net::protocol::ReplayRecording::Always,
+                                        net::protocol::ReplayRecording::Ask,
+                                        net::protocol::ReplayRecording::Never,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut replay_recording,
+                                            option,
+                                            replay_recording_text(&config.language, option),
+                                        );
+                                    }
+                                });
+                            if replay_recording != lobby.replay_recording {
+                                lobby.set_replay_recording(replay_recording);
+                            }
+                        });
+                        strip.cell(|ui| {
+                            ui.label(if lobby.remote_settings.game_info.is_some() {
+                                replay_recording_text(&config.language, lobby.remote_settings.replay_recording)
+                            } else {
+                                "".to_string()
+                            });
+                        });
+                    });
+            });
+
             outer_strip.strip(|sb| {
                 sb.size(egui_extras::Size::remainder())
                     .size(egui_extras::Size::exact(CELL_WIDTH * 2.0 + spacing_x))
                     .horizontal(|mut strip| {
                         strip.cell(|ui| {
-                            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-input-delay").unwrap());
+                            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-input-delay"));
                         });
                         strip.cell(|ui| {
                             ui.horizontal(|ui| {
                                 ui.add(
                                     egui::DragValue::new(&mut config.input_delay)
                                         .speed(1)
-                                        .clamp_range(2..=10),
+                                        .clamp_range(2..=net::protocol::MAX_INPUT_DELAY),
                                 );
                                 if ui
-                                    .button(
-                                        i18n::LOCALES
-                                            .lookup(&config.language, "play-details-input-delay.suggest")
-                                            .unwrap(),
+                                    .button(i18n::LOCALES.lookup(&config.language, "play-details-input-delay.suggest"))
+                                    .clicked()
+                                {
+                                    config.input_delay = suggested_input_delay(&lobby.latencies);
+                                }
+                                if ui
+                                    .selectable_label(
+                                        lobby.delay_preview.is_some(),
+                                        i18n::LOCALES.lookup(&config.language, "play-details-input-delay.preview"),
                                     )
                                     .clicked()
                                 {
-                                    config.input_delay = std::cmp::min(
-                                        10,
-                                        std::cmp::max(
-                                            2,
-                                            ((lobby.latencies.median() * 60).as_nanos()
-                                                / std::time::Duration::from_secs(1).as_nanos())
-                                                as i32
-                                                + 1
-                                                - 2,
-                                        ),
-                                    ) as u32;
+                                    lobby.delay_preview = if lobby.delay_preview.is_none() {
+                                        Some(gui::delay_preview::State::new())
+                                    } else {
+                                        None
+                                    };
                                 }
                             });
                         });
@@ -1219,14 +3246,58 @@ fn show_lobby_table(
 
             outer_strip.strip(|sb| {
                 sb.size(egui_extras::Size::remainder())
-                    .size(egui_extras::Size::exact(CELL_WIDTH * 2.0 + spacing_x))
+                    .size(egui_extras::Size::exact(CELL_WIDTH))
+                    .size(egui_extras::Size::exact(CELL_WIDTH))
                     .horizontal(|mut strip| {
                         strip.cell(|ui| {
-                            ui.strong(
-                                i18n::LOCALES
-                                    .lookup(&config.language, "settings-show-own-setup")
-                                    .unwrap(),
+                            ui.strong(i18n::LOCALES.lookup(&config.language, "play-details-input-delay-handicap"))
+                                .on_hover_text(
+                                    i18n::LOCALES
+                                        .lookup(&config.language, "play-details-input-delay-handicap.description"),
+                                );
+                        });
+                        strip.cell(|ui| {
+                            // The whole delay is filled into the input queue up front, so it has to leave room for at least one more input.
+                            let max_input_delay_handicap = std::cmp::min(
+                                net::protocol::MAX_INPUT_DELAY_HANDICAP,
+                                config.max_queue_length.saturating_sub(config.input_delay + 1),
                             );
+                            let mut input_delay_handicap =
+                                std::cmp::min(lobby.input_delay_handicap, max_input_delay_handicap);
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::DragValue::new(&mut input_delay_handicap)
+                                        .speed(1)
+                                        .clamp_range(0..=max_input_delay_handicap),
+                                );
+                                ui.label(input_delay_text(
+                                    &config.language,
+                                    config.input_delay,
+                                    input_delay_handicap,
+                                ));
+                            });
+                            lobby.set_input_delay(config.input_delay, input_delay_handicap);
+                        });
+                        strip.cell(|ui| {
+                            ui.label(if lobby.remote_settings.game_info.is_some() {
+                                input_delay_text(
+                                    &config.language,
+                                    lobby.remote_settings.input_delay,
+                                    lobby.remote_settings.input_delay_handicap,
+                                )
+                            } else {
+                                "".to_string()
+                            });
+                        });
+                    });
+            });
+
+            outer_strip.strip(|sb| {
+                sb.size(egui_extras::Size::remainder())
+                    .size(egui_extras::Size::exact(CELL_WIDTH * 2.0 + spacing_x))
+                    .horizontal(|mut strip| {
+                        strip.cell(|ui| {
+                            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-show-own-setup"));
                         });
                         strip.cell(|ui| {
                             ui.checkbox(&mut config.show_own_setup, "");
@@ -1240,20 +3311,31 @@ fn show_bottom_pane(
     ui: &mut egui::Ui,
     window: &winit::window::Window,
     clipboard: &mut arboard::Clipboard,
+    input_state: &input::State,
     config: &mut config::Config,
     config_arc: std::sync::Arc<parking_lot::RwLock<config::Config>>,
     roms_scanner: rom::Scanner,
     patches_scanner: patch::Scanner,
+    patched_roms: patch::cache::PatchedRoms,
+    opponents: opponents::Registry,
+    save_patch_history: save::migration::History,
+    save_registry: save::registry::Registry,
     audio_binder: audio::LateBinder,
     session: std::sync::Arc<parking_lot::Mutex<Option<session::Session>>>,
     selection: &mut Option<gui::Selection>,
     emu_tps_counter: std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
     discord_client: &mut discord::Client,
+    broadcast_server: broadcast::Server,
+    notifications: &mut gui::notifications::State,
     connection_task: &mut Option<ConnectionTask>,
-    connection_task_arc: std::sync::Arc<tokio::sync::Mutex<Option<ConnectionTask>>>,
+    connection_task_arc: std::sync::Arc<parking_lot::Mutex<Option<ConnectionTask>>>,
     link_code: &mut String,
     show_link_code: &mut bool,
     show_save_select: &mut Option<gui::save_select_view::State>,
+    pending_invite: &mut Option<linkcode::Invite>,
+    share: &mut Option<Share>,
+    direct_connect: &mut DirectConnect,
+    game_art: &mut gui::save_view::texture_cache::TextureCache,
 ) {
     let error_window_open = {
         if let Some(ConnectionTask::Failed(err)) = connection_task.as_ref() {
@@ -1261,36 +3343,89 @@ fn show_bottom_pane(
             let mut open2 = true;
             egui::Window::new(format!(
                 "🔌 {}",
-                i18n::LOCALES.lookup(&config.language, "connection-error").unwrap()
+                i18n::LOCALES.lookup(&config.language, "connection-error")
             ))
             .id(egui::Id::new("connection-failed-window"))
             .open(&mut open)
             .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
             .show(ui.ctx(), |ui| {
                 ui.label(match err {
-                    ConnectionError::Negotiation(net::NegotiationError::RemoteProtocolVersionTooOld) => i18n::LOCALES
-                        .lookup(&config.language, "connection-error-remote-protocol-version-too-old")
-                        .unwrap(),
-                    ConnectionError::Negotiation(net::NegotiationError::RemoteProtocolVersionTooNew) => i18n::LOCALES
-                        .lookup(&config.language, "connection-error-remote-protocol-version-too-new")
-                        .unwrap(),
+                    ConnectionError::Negotiation(net::NegotiationError::RemoteProtocolVersionTooOld) => {
+                        i18n::LOCALES.lookup(&config.language, "connection-error-remote-protocol-version-too-old")
+                    }
+                    ConnectionError::Negotiation(net::NegotiationError::RemoteProtocolVersionTooNew) => {
+                        i18n::LOCALES.lookup(&config.language, "connection-error-remote-protocol-version-too-new")
+                    }
+                    ConnectionError::Negotiation(net::NegotiationError::ConnectedToSelf) => {
+                        i18n::LOCALES.lookup(&config.language, "connection-error-connected-to-self")
+                    }
                     ConnectionError::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                        i18n::LOCALES.lookup(&config.language, "connection-error-eof").unwrap()
+                        i18n::LOCALES.lookup(&config.language, "connection-error-eof")
+                    }
+                    ConnectionError::RemoteCancelled => {
+                        i18n::LOCALES.lookup(&config.language, "connection-error-remote-cancelled")
+                    }
+                    ConnectionError::Connect(net::proxy::ConnectError::ProxyUnreachable { proxy, source }) => {
+                        i18n::LOCALES.lookup_with_args(
+                            &config.language,
+                            "connection-error-proxy-unreachable",
+                            &std::collections::HashMap::from([
+                                ("proxy", proxy.clone().into()),
+                                ("error", source.to_string().into()),
+                            ]),
+                        )
+                    }
+                    ConnectionError::Connect(net::proxy::ConnectError::ProxyFailed { proxy, reason, .. }) => {
+                        i18n::LOCALES.lookup_with_args(
+                            &config.language,
+                            "connection-error-proxy-failed",
+                            &std::collections::HashMap::from([
+                                ("proxy", proxy.clone().into()),
+                                ("error", reason.clone().into()),
+                            ]),
+                        )
                     }
-                    e => i18n::LOCALES
-                        .lookup_with_args(
+                    ConnectionError::Connect(net::proxy::ConnectError::ServerUnreachable { source, .. }) => {
+                        i18n::LOCALES.lookup_with_args(
                             &config.language,
-                            "connection-error-other",
-                            &std::collections::HashMap::from([("error", format!("{:?}", e).into())]),
+                            "connection-error-server-unreachable",
+                            &std::collections::HashMap::from([("error", source.to_string().into())]),
                         )
-                        .unwrap(),
+                    }
+                    ConnectionError::MatchStartTimedOut { received, total } => i18n::LOCALES.lookup_with_args(
+                        &config.language,
+                        "connection-error-match-start-timed-out",
+                        &std::collections::HashMap::from([
+                            ("received", (*received).into()),
+                            ("total", (*total).into()),
+                        ]),
+                    ),
+                    ConnectionError::IllegalRemoteSave(e) => i18n::LOCALES.lookup_with_args(
+                        &config.language,
+                        "connection-error-illegal-remote-save",
+                        &std::collections::HashMap::from([("error", e.to_string().into())]),
+                    ),
+                    ConnectionError::InvalidRemoteState(e) => i18n::LOCALES.lookup_with_args(
+                        &config.language,
+                        "connection-error-invalid-remote-state",
+                        &std::collections::HashMap::from([("error", e.to_string().into())]),
+                    ),
+                    ConnectionError::ConfigurationMismatch(differences) => i18n::LOCALES.lookup_with_args(
+                        &config.language,
+                        "lobby-issue-configuration-mismatch",
+                        &std::collections::HashMap::from([(
+                            "differences",
+                            determinism::describe_differences(differences, &config.language).into(),
+                        )]),
+                    ),
+                    e => i18n::LOCALES.lookup_with_args(
+                        &config.language,
+                        "connection-error-other",
+                        &std::collections::HashMap::from([("error", format!("{:?}", e).into())]),
+                    ),
                 });
                 if ui
-                    .button(
-                        i18n::LOCALES
-                            .lookup(&config.language, "connection-error-confirm")
-                            .unwrap(),
-                    )
+                    .button(i18n::LOCALES.lookup(&config.language, "connection-error-confirm"))
                     .clicked()
                 {
                     open2 = false;
@@ -1324,10 +3459,13 @@ fn show_bottom_pane(
                             ui.horizontal(|ui| {
                                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
                                     if ui
-                                        .button(format!(
-                                            "❎ {}",
-                                            i18n::LOCALES.lookup(&config.language, "play-cancel").unwrap()
-                                        ))
+                                        .add_enabled(
+                                            !cancellation_token.is_cancelled(),
+                                            egui::Button::new(format!(
+                                                "❎ {}",
+                                                i18n::LOCALES.lookup(&config.language, "play-cancel")
+                                            )),
+                                        )
                                         .clicked()
                                     {
                                         cancellation_token.cancel();
@@ -1337,15 +3475,14 @@ fn show_bottom_pane(
                                         ui.with_layout(egui::Layout::left_to_right(egui::Align::Min), |ui| {
                                             ui.spinner();
                                             ui.label(match connection_state {
+                                                _ if cancellation_token.is_cancelled() => i18n::LOCALES
+                                                    .lookup(&config.language, "play-connection-task-cancelling"),
                                                 ConnectionState::Starting => i18n::LOCALES
-                                                    .lookup(&config.language, "play-connection-task-starting")
-                                                    .unwrap(),
+                                                    .lookup(&config.language, "play-connection-task-starting"),
                                                 ConnectionState::Signaling => i18n::LOCALES
-                                                    .lookup(&config.language, "play-connection-task-signaling")
-                                                    .unwrap(),
+                                                    .lookup(&config.language, "play-connection-task-signaling"),
                                                 ConnectionState::Waiting => i18n::LOCALES
-                                                    .lookup(&config.language, "play-connection-task-waiting")
-                                                    .unwrap(),
+                                                    .lookup(&config.language, "play-connection-task-waiting"),
                                                 _ => unreachable!(),
                                             });
                                         });
@@ -1367,11 +3504,84 @@ fn show_bottom_pane(
                                 }),
                             )));
                         }
+                        ConnectionState::Listening(_) | ConnectionState::Dialing(_) => {
+                            // Nothing is advertised on Discord: nobody off the local network could join anyway.
+                            ui.horizontal(|ui| {
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                                    if ui
+                                        .add_enabled(
+                                            !cancellation_token.is_cancelled(),
+                                            egui::Button::new(format!(
+                                                "❎ {}",
+                                                i18n::LOCALES.lookup(&config.language, "play-cancel")
+                                            )),
+                                        )
+                                        .clicked()
+                                    {
+                                        cancellation_token.cancel();
+                                    }
+
+                                    ui.horizontal_top(|ui| {
+                                        ui.with_layout(egui::Layout::left_to_right(egui::Align::Min), |ui| {
+                                            ui.spinner();
+                                            ui.label(match connection_state {
+                                                _ if cancellation_token.is_cancelled() => i18n::LOCALES
+                                                    .lookup(&config.language, "play-connection-task-cancelling"),
+                                                ConnectionState::Listening(_) => i18n::LOCALES
+                                                    .lookup(&config.language, "play-connection-task-listening"),
+                                                ConnectionState::Dialing(addr) => i18n::LOCALES.lookup_with_args(
+                                                    &config.language,
+                                                    "play-connection-task-dialing",
+                                                    &std::collections::HashMap::from([("addr", addr.clone().into())]),
+                                                ),
+                                                _ => unreachable!(),
+                                            });
+                                        });
+                                    });
+                                });
+                            });
+
+                            if let ConnectionState::Listening(addrs) = connection_state {
+                                show_listening_addresses(ui, &config.language, addrs, direct_connect);
+                            }
+                        }
+                        ConnectionState::Exchanging { sent, received, total } => {
+                            ui.horizontal(|ui| {
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                                    if ui
+                                        .add_enabled(
+                                            !cancellation_token.is_cancelled(),
+                                            egui::Button::new(format!(
+                                                "❎ {}",
+                                                i18n::LOCALES.lookup(&config.language, "play-cancel")
+                                            )),
+                                        )
+                                        .clicked()
+                                    {
+                                        cancellation_token.cancel();
+                                    }
+
+                                    ui.add(
+                                        egui::ProgressBar::new((sent + received) as f32 / (total * 2) as f32).text(
+                                            i18n::LOCALES.lookup_with_args(
+                                                &config.language,
+                                                "play-connection-task-exchanging",
+                                                &std::collections::HashMap::from([
+                                                    ("sent", (*sent).into()),
+                                                    ("received", (*received).into()),
+                                                    ("total", (*total).into()),
+                                                ]),
+                                            ),
+                                        ),
+                                    );
+                                });
+                            });
+                        }
                         ConnectionState::InLobby(lobby) => {
-                            let mut lobby = lobby.blocking_lock();
-                            if !lobby.attention_requested {
-                                window.request_user_attention(Some(winit::window::UserAttentionType::Critical));
-                                lobby.attention_requested = true;
+                            let mut lobby = lobby.lock();
+                            let remote_nickname = contentpolicy::display_nickname(&lobby.remote_settings.nickname);
+                            for event in lobby.pending_notifications.drain(..) {
+                                notifications.notify(window, config, &audio_binder, &remote_nickname, event);
                             }
 
                             discord_client.set_current_activity(Some(discord::make_in_lobby_activity(
@@ -1389,9 +3599,36 @@ fn show_bottom_pane(
                                 }),
                             )));
 
-                            ui.add_enabled_ui(lobby.local_negotiated_state.is_none() && lobby.sender.is_some(), |ui| {
-                                show_lobby_table(ui, &cancellation_token, config, &mut lobby, &roms, &patches);
+                            let is_editable = lobby.local_negotiated_state.is_none() && lobby.outgoing.is_some();
+                            ui.add_enabled_ui(is_editable, |ui| {
+                                show_lobby_table(
+                                    ui,
+                                    &cancellation_token,
+                                    config,
+                                    &mut lobby,
+                                    &roms.by_game,
+                                    &patches,
+                                    game_art,
+                                );
                             });
+
+                            show_opponent(ui, &config.language, &mut lobby);
+
+                            if !is_editable {
+                                lobby.delay_preview = None;
+                            }
+                            let suggested_input_delay = suggested_input_delay(&lobby.latencies);
+                            if let Some(delay_preview) = lobby.delay_preview.as_mut() {
+                                gui::delay_preview::show(
+                                    ui,
+                                    &config.language,
+                                    delay_preview,
+                                    input_state,
+                                    &config.input_mapping,
+                                    config.input_delay + lobby.input_delay_handicap,
+                                    suggested_input_delay,
+                                );
+                            }
                         }
                     }
                 } else {
@@ -1399,6 +3636,19 @@ fn show_bottom_pane(
                 }
             }
 
+            let direct_connect_to = if connection_task.is_none() {
+                let banned_word = config.nickname.as_deref().and_then(contentpolicy::banned_word_in);
+                show_direct_connect(
+                    ui,
+                    &config.language,
+                    !error_window_open,
+                    banned_word.as_deref(),
+                    direct_connect,
+                )
+            } else {
+                None
+            };
+
             ui.horizontal(|ui| {
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     let (lobby, cancellation_token) = if let Some(connection_task) = connection_task.as_ref() {
@@ -1420,35 +3670,82 @@ fn show_bottom_pane(
                         (None, None)
                     };
 
+                    let link_code_validation = linkcode::validate(link_code);
+
+                    // Playing alone, nobody sees our nickname, so it only has to pass the content policy to fight.
+                    let banned_word = if link_code.is_empty() {
+                        None
+                    } else {
+                        config.nickname.as_deref().and_then(contentpolicy::banned_word_in)
+                    };
+
                     let mut submitted = false;
                     if cancellation_token.is_none() {
-                        if ui
-                            .add_enabled(
-                                !error_window_open && (!link_code.is_empty() || selection.is_some()),
-                                egui::Button::new(egui::RichText::new(if link_code.is_empty() {
-                                    format!("▶️ {}", i18n::LOCALES.lookup(&config.language, "play-play").unwrap())
-                                } else {
-                                    format!("🥊 {}", i18n::LOCALES.lookup(&config.language, "play-fight").unwrap())
-                                })),
-                            )
-                            .clicked()
-                        {
+                        let play_resp = ui.add_enabled(
+                            !error_window_open
+                                && link_code_validation.is_ok()
+                                && banned_word.is_none()
+                                && (!link_code.is_empty() || selection.is_some()),
+                            egui::Button::new(egui::RichText::new(if link_code.is_empty() {
+                                format!("▶️ {}", i18n::LOCALES.lookup(&config.language, "play-play"))
+                            } else {
+                                format!("🥊 {}", i18n::LOCALES.lookup(&config.language, "play-fight"))
+                            })),
+                        );
+                        let play_resp = if let Some(word) = banned_word.as_ref() {
+                            play_resp.on_disabled_hover_text(i18n::LOCALES.lookup_with_args(
+                                &config.language,
+                                "play-fight.banned-nickname",
+                                &std::collections::HashMap::from([("word", word.clone().into())]),
+                            ))
+                        } else {
+                            play_resp
+                        };
+                        if play_resp.clicked() {
                             submitted = true;
                         }
 
                         if ui
                             .add_enabled(!error_window_open, egui::Button::new(egui::RichText::new("🎲")))
-                            .on_hover_text(i18n::LOCALES.lookup(&config.language, "play-random").unwrap())
+                            .on_hover_text(i18n::LOCALES.lookup(&config.language, "play-random"))
                             .clicked()
                         {
                             *link_code = randomcode::generate(&config.language);
+                            if let Some(prefix) = contentpolicy::link_code_prefix() {
+                                *link_code = linkcode::with_prefix(link_code, &prefix);
+                            }
                             let _ = clipboard.set_text(link_code.clone());
                         }
 
+                        if ui
+                            .add_enabled(
+                                !error_window_open
+                                    && link_code_validation.is_ok()
+                                    && !linkcode::finish(link_code).is_empty(),
+                                egui::Button::new(egui::RichText::new("🔗")),
+                            )
+                            .on_hover_text(i18n::LOCALES.lookup(&config.language, "play-share"))
+                            .clicked()
+                        {
+                            let uri = linkcode::Invite {
+                                link_code: linkcode::finish(link_code).to_string(),
+                                matchmaking_endpoint: if !config.matchmaking_endpoint.is_empty()
+                                    && config.matchmaking_endpoint != config::DEFAULT_MATCHMAKING_ENDPOINT
+                                {
+                                    Some(config.matchmaking_endpoint.clone())
+                                } else {
+                                    None
+                                },
+                            }
+                            .to_uri();
+                            let _ = clipboard.set_text(uri.clone());
+                            *share = Some(Share { uri, qr: None });
+                        }
+
                         if config.streamer_mode {
                             if ui
                                 .selectable_label(*show_link_code, "👁️")
-                                .on_hover_text(i18n::LOCALES.lookup(&config.language, "play-show-link-code").unwrap())
+                                .on_hover_text(i18n::LOCALES.lookup(&config.language, "play-show-link-code"))
                                 .clicked()
                             {
                                 *show_link_code = !*show_link_code;
@@ -1457,84 +3754,210 @@ fn show_bottom_pane(
                     }
 
                     if let Some(lobby) = lobby {
-                        let mut lobby = lobby.blocking_lock();
-                        let mut ready = lobby.local_negotiated_state.is_some() || lobby.sender.is_none();
+                        let mut lobby = lobby.lock();
+                        lobby.check_save_changed_on_disk();
+                        let mut ready = lobby.local_negotiated_state.is_some() || lobby.outgoing.is_none();
                         let was_ready = ready;
-                        ui.add_enabled(
+                        let save_requirements_blocking = selection
+                            .as_ref()
+                            .map(|selection| selection.has_blocking_save_requirements())
+                            .unwrap_or(false);
+                        let resp = ui.add_enabled(
                             selection.is_some()
+                                && !save_requirements_blocking
                                 && are_settings_compatible(
                                     &lobby.make_local_settings(),
                                     &lobby.remote_settings,
                                     &patches,
                                 )
-                                && lobby.sender.is_some(),
-                            egui::Checkbox::new(
-                                &mut ready,
-                                i18n::LOCALES.lookup(&config.language, "play-ready").unwrap(),
-                            ),
+                                && lobby.outgoing.is_some(),
+                            egui::Checkbox::new(&mut ready, i18n::LOCALES.lookup(&config.language, "play-ready")),
                         );
+                        if save_requirements_blocking {
+                            resp.on_disabled_hover_text(
+                                i18n::LOCALES.lookup(&config.language, "play-save-requirements-blocking"),
+                            );
+                        }
                         if error_window_open {
                             ready = was_ready;
                         }
-                        if lobby.sender.is_some() {
+
+                        let desync_risk = lobby.assess_desync_risk();
+                        if desync_risk.level() != desyncrisk::Level::High {
+                            lobby.desync_risk_acknowledged = false;
+                        }
+                        show_desync_risk(ui, &config.language, &mut lobby, &desync_risk);
+                        if !was_ready
+                            && ready
+                            && desync_risk.level() == desyncrisk::Level::High
+                            && !lobby.desync_risk_acknowledged
+                        {
+                            // This is only advice, but readying up into a likely desync takes a second click.
+                            lobby.confirming_desync_risk = true;
+                            ready = false;
+                        }
+                        if let Some(confirmed) =
+                            show_desync_risk_confirmation_window(ui.ctx(), &config.language, &mut lobby, &desync_risk)
+                        {
+                            if confirmed {
+                                lobby.desync_risk_acknowledged = true;
+                                ready = true;
+                            }
+                        }
+
+                        if !was_ready
+                            && ready
+                            && lobby.replay_recording == net::protocol::ReplayRecording::Ask
+                            && lobby.remote_settings.replay_recording != net::protocol::ReplayRecording::Never
+                        {
+                            // Readying up waits until the player has said whether this match may be recorded.
+                            lobby.asking_replay_consent = true;
+                            ready = false;
+                        }
+                        if let Some(allowed) = show_replay_consent_window(ui.ctx(), &config.language, &mut lobby) {
+                            lobby.set_replay_recording(if allowed {
+                                net::protocol::ReplayRecording::Always
+                            } else {
+                                net::protocol::ReplayRecording::Never
+                            });
+                            ready = true;
+                        }
+                        if lobby.outgoing.is_some() {
                             if !was_ready && ready {
                                 *show_save_select = None;
-                                let save_data = lobby.local_selection.as_ref().map(|selection| selection.save.to_vec());
-                                if let Some(save_data) = save_data {
-                                    let _ = sync::block_on(lobby.commit(&save_data));
+                                // The save may have been edited since it was selected: make sure what gets played with is what's on disk now.
+                                match selection.as_mut().map(|selection| selection.reload_save()) {
+                                    Some(Ok(())) => {
+                                        lobby.set_local_selection(selection);
+                                        lobby.mark_save_reloaded();
+                                        let save_data = lobby
+                                            .local_selection
+                                            .as_ref()
+                                            .map(|local_selection| local_selection.save.to_vec());
+                                        let validation = lobby
+                                            .local_selection
+                                            .as_ref()
+                                            .map(|local_selection| local_selection.validate_save())
+                                            .unwrap_or(Ok(()));
+                                        if let Err(e) = validation {
+                                            log::warn!("rejected local save: {}", e);
+                                            lobby.save_reload_error = Some(e);
+                                        } else if let Some(save_data) = save_data.filter(|_| {
+                                            !selection
+                                                .as_ref()
+                                                .map(|selection| selection.has_blocking_save_requirements())
+                                                .unwrap_or(false)
+                                        }) {
+                                            let _ = lobby.commit(&save_data);
+                                        }
+                                    }
+                                    Some(Err(e)) => {
+                                        log::error!("failed to reload save before committing: {:?}", e);
+                                        lobby.save_reload_error = Some(e);
+                                    }
+                                    None => {}
                                 }
                             } else if was_ready && !ready {
-                                let _ = sync::block_on(lobby.uncommit());
+                                lobby.uncommit();
                             }
                         }
+
+                        if let Some(e) = lobby.save_reload_error.as_ref() {
+                            ui.colored_label(egui::Color32::RED, e.description(&config.language));
+                        } else if lobby.save_changed_on_disk && lobby.local_negotiated_state.is_none() {
+                            ui.weak(i18n::LOCALES.lookup(&config.language, "play-save-changed-on-disk"))
+                                .on_hover_text(
+                                    i18n::LOCALES.lookup(&config.language, "play-save-changed-on-disk.description"),
+                                );
+                        }
                     }
 
                     let input_resp = ui.add_enabled(
                         cancellation_token.is_none() && !error_window_open,
                         egui::TextEdit::singleline(link_code)
                             .password(config.streamer_mode && !*show_link_code)
-                            .hint_text(i18n::LOCALES.lookup(&config.language, "play-link-code").unwrap())
+                            .hint_text(i18n::LOCALES.lookup(&config.language, "play-link-code"))
                             .desired_width(f32::INFINITY),
                     );
-                    *link_code = link_code
-                        .to_lowercase()
-                        .chars()
-                        .filter(|c| "abcdefghijklmnopqrstuvwxyz0123456789-".chars().any(|c2| c2 == *c))
-                        .take(40)
-                        .collect::<String>()
-                        .trim_start_matches("-")
-                        .to_string();
-
-                    if let Some(last) = link_code.chars().last() {
-                        if last == '-' {
-                            *link_code = link_code
-                                .chars()
-                                .rev()
-                                .skip_while(|c| *c == '-')
-                                .collect::<Vec<_>>()
-                                .into_iter()
-                                .rev()
-                                .collect::<String>()
-                                + "-";
-                        }
+                    *link_code = linkcode::normalize(link_code);
+                    // Organizers can lock link codes to their event's prefix, so nobody ends up in someone else's bracket.
+                    let link_code_prefix = contentpolicy::link_code_prefix();
+                    if let Some(prefix) = link_code_prefix.as_ref() {
+                        *link_code = linkcode::with_prefix(link_code, prefix);
                     }
 
-                    if input_resp.lost_focus() && ui.ctx().input().key_pressed(egui::Key::Enter) {
+                    if let Err(e) = link_code_validation.as_ref() {
+                        input_resp.on_hover_text(match e {
+                            linkcode::ValidationError::TooLong => i18n::LOCALES.lookup_with_args(
+                                &config.language,
+                                "play-link-code.too-long",
+                                &std::collections::HashMap::from([("max", linkcode::MAX_LENGTH.into())]),
+                            ),
+                            linkcode::ValidationError::InvalidCharacter(c) => i18n::LOCALES.lookup_with_args(
+                                &config.language,
+                                "play-link-code.invalid-character",
+                                &std::collections::HashMap::from([("char", c.to_string().into())]),
+                            ),
+                        });
+                    } else if input_resp.lost_focus() && ui.ctx().input().key_pressed(egui::Key::Enter) {
                         submitted = true;
                     }
 
-                    if let Some(join_secret) = discord_client.take_current_join_secret() {
-                        *link_code = join_secret.to_string();
-                        submitted = true;
+                    let mut matchmaking_endpoint_override = None;
+
+                    if cancellation_token.is_none() && !error_window_open {
+                        if let Some(join_secret) = discord_client.take_current_join_secret() {
+                            *link_code = join_secret.to_string();
+                            submitted = true;
+                        }
+
+                        if let Some(invite) = pending_invite.take() {
+                            log::info!("joining from invite: {}", invite.to_uri());
+                            *link_code = invite.link_code;
+                            matchmaking_endpoint_override = invite.matchmaking_endpoint;
+                            submitted = true;
+                        }
+
+                        if let Some(prefix) = link_code_prefix.as_ref() {
+                            *link_code = linkcode::with_prefix(link_code, prefix);
+                        }
+                    }
+
+                    let submitted_direct = direct_connect_to.is_some();
+
+                    // Invites and Discord joins don't go through the button, so the nickname is checked again here.
+                    if submitted && !link_code.is_empty() {
+                        if let Some(word) = config.nickname.as_deref().and_then(contentpolicy::banned_word_in) {
+                            log::warn!("not connecting, as our nickname contains the banned word {:?}", word);
+                            submitted = false;
+                        }
                     }
 
-                    if submitted {
+                    // There's already a connection, or one is still being torn down: starting another now would clobber it.
+                    if (submitted || submitted_direct) && cancellation_token.is_none() {
                         let audio_binder = audio_binder.clone();
                         let egui_ctx = ui.ctx().clone();
                         let session = session.clone();
                         let emu_tps_counter = emu_tps_counter.clone();
 
-                        if !link_code.is_empty() {
+                        let connect_to = if let Some(connect_to) = direct_connect_to {
+                            Some(connect_to)
+                        } else if !link_code.is_empty() {
+                            Some(ConnectTo::Matchmaking {
+                                addr: if let Some(matchmaking_endpoint) = matchmaking_endpoint_override {
+                                    matchmaking_endpoint
+                                } else if !config.matchmaking_endpoint.is_empty() {
+                                    config.matchmaking_endpoint.clone()
+                                } else {
+                                    config::DEFAULT_MATCHMAKING_ENDPOINT.to_string()
+                                },
+                                link_code: linkcode::finish(link_code).to_owned(),
+                            })
+                        } else {
+                            None
+                        };
+
+                        if let Some(connect_to) = connect_to {
                             let cancellation_token = tokio_util::sync::CancellationToken::new();
                             *connection_task = Some(ConnectionTask::InProgress {
                                 state: ConnectionState::Starting,
@@ -1542,12 +3965,6 @@ fn show_bottom_pane(
                             });
 
                             tokio::task::spawn({
-                                let matchmaking_endpoint = if !config.matchmaking_endpoint.is_empty() {
-                                    config.matchmaking_endpoint.clone()
-                                } else {
-                                    config::DEFAULT_MATCHMAKING_ENDPOINT.to_string()
-                                };
-                                let link_code = link_code.to_owned();
                                 let nickname = config.nickname.clone().unwrap_or_else(|| "".to_string());
                                 let patches_path = config.patches_path();
                                 let replays_path = config.replays_path();
@@ -1555,6 +3972,11 @@ fn show_bottom_pane(
                                 let connection_task_arc = connection_task_arc.clone();
                                 let roms_scanner = roms_scanner.clone();
                                 let patches_scanner = patches_scanner.clone();
+                                let patched_roms = patched_roms.clone();
+                                let opponents = opponents.clone();
+                                let save_patch_history = save_patch_history.clone();
+                                let save_registry = save_registry.clone();
+                                let broadcast_server = broadcast_server.clone();
                                 async move {
                                     run_connection_task(
                                         config_arc,
@@ -1564,8 +3986,12 @@ fn show_bottom_pane(
                                         session,
                                         roms_scanner,
                                         patches_scanner,
-                                        matchmaking_endpoint,
-                                        link_code,
+                                        patched_roms,
+                                        opponents,
+                                        save_patch_history,
+                                        save_registry,
+                                        broadcast_server,
+                                        connect_to,
                                         nickname,
                                         patches_path,
                                         replays_path,
@@ -1584,17 +4010,31 @@ fn show_bottom_pane(
                                 .patch
                                 .as_ref()
                                 .map(|(name, version, _)| (name.clone(), version.clone()));
+                            let states_path = config.states_path();
+                            let assist_settings = config.assist.clone();
+                            let bios_path = config.bios_path.clone();
 
                             // We have to run this in a thread in order to lock main_view safely. Furthermore, we have to use a real thread because of parking_lot::Mutex.
+                            if let Err(e) = save_patch_history.record(
+                                &save_path,
+                                patch.as_ref().map(|(name, version)| (name.as_str(), version)),
+                            ) {
+                                log::error!("failed to record save patch history: {:?}", e);
+                            }
+
                             tokio::task::spawn_blocking(move || {
+                                let bios = bios::load_configured(bios_path.as_deref());
                                 *session.lock() = Some(
                                     session::Session::new_singleplayer(
                                         audio_binder,
                                         game,
                                         patch,
                                         &rom,
+                                        bios.as_deref(),
                                         &save_path,
+                                        &states_path,
                                         emu_tps_counter,
+                                        assist_settings,
                                     )
                                     .unwrap(),
                                 ); // TODO: Don't unwrap maybe
@@ -1613,6 +4053,7 @@ pub fn show(
     font_families: &gui::FontFamilies,
     window: &winit::window::Window,
     clipboard: &mut arboard::Clipboard,
+    input_state: &input::State,
     config: &mut config::Config,
     config_arc: std::sync::Arc<parking_lot::RwLock<config::Config>>,
     roms_scanner: rom::Scanner,
@@ -1623,35 +4064,75 @@ pub fn show(
     selection: &mut Option<gui::Selection>,
     patch_selection: &mut Option<String>,
     emu_tps_counter: std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
+    patch_validator: &patch::validation::Validator,
+    patched_roms: patch::cache::PatchedRoms,
+    opponents: opponents::Registry,
+    save_patch_history: save::migration::History,
+    save_registry: save::registry::Registry,
     state: &mut State,
     discord_client: &mut discord::Client,
+    broadcast_server: broadcast::Server,
+    notifications: &mut gui::notifications::State,
 ) {
     let connection_task_arc = state.connection_task.clone();
-    let mut connection_task = state.connection_task.blocking_lock();
+    let mut connection_task = state.connection_task.lock();
+
+    state.game_art.upload_decoded(ui.ctx());
 
     if state.show_save_select.is_none() {
         show_bottom_pane(
             ui,
             window,
             clipboard,
+            input_state,
             config,
             config_arc.clone(),
             roms_scanner.clone(),
             patches_scanner.clone(),
+            patched_roms.clone(),
+            opponents,
+            save_patch_history.clone(),
+            save_registry.clone(),
             audio_binder.clone(),
             session,
             selection,
             emu_tps_counter,
             discord_client,
+            broadcast_server,
+            notifications,
             &mut *connection_task,
             connection_task_arc,
             &mut state.link_code,
             &mut state.show_link_code,
             &mut state.show_save_select,
+            &mut state.pending_invite,
+            &mut state.share,
+            &mut state.direct_connect,
+            &mut state.game_art,
         );
     }
 
+    show_share_window(
+        ui.ctx(),
+        &config.language,
+        !config.streamer_mode || state.show_link_code,
+        clipboard,
+        &mut state.share,
+    );
+
+    show_patch_details_window(
+        ui.ctx(),
+        &config.language,
+        &config.patches_path(),
+        patches_scanner.clone(),
+        roms_scanner.clone(),
+        patch_validator,
+        &mut state.show_patch_details,
+    );
+
+    // Snapshots, so a rescan that finishes partway through the frame can't leave the pane looking at two different scans.
     let roms = roms_scanner.read();
+    let patches = patches_scanner.read();
     egui::CentralPanel::default()
         .frame(
             egui::Frame::none()
@@ -1666,7 +4147,7 @@ pub fn show(
         .show_inside(ui, |ui| {
             let lobby = connection_task.as_ref().and_then(|task| match task {
                 ConnectionTask::InProgress { state, .. } => match state {
-                    ConnectionState::InLobby(lobby) => Some(lobby.blocking_lock()),
+                    ConnectionState::InLobby(lobby) => Some(lobby.lock()),
                     _ => None,
                 },
                 _ => None,
@@ -1677,6 +4158,50 @@ pub fn show(
                 .map(|lobby| lobby.local_negotiated_state.is_some())
                 .unwrap_or(false);
 
+            if state.show_save_select.is_none() {
+                ui.add_enabled_ui(!is_ready, |ui| {
+                    let lobby_options = lobby
+                        .as_ref()
+                        .map(|lobby| gui::lobby_presets::LobbyOptions {
+                            match_type: lobby.match_type,
+                            reveal_setup: lobby.reveal_setup,
+                        })
+                        .unwrap_or(gui::lobby_presets::LobbyOptions {
+                            match_type: (config.default_match_type, config.default_match_subtype),
+                            reveal_setup: false,
+                        });
+                    gui::lobby_presets::show(
+                        ui,
+                        clipboard,
+                        config,
+                        &roms.by_game,
+                        saves_scanner.clone(),
+                        &patches,
+                        &patched_roms,
+                        selection,
+                        patch_selection,
+                        lobby_options,
+                        &mut state.lobby_presets,
+                    );
+                });
+            }
+
+            if let Some(selection) = selection.as_ref() {
+                let banner_path = selection
+                    .patch
+                    .as_ref()
+                    .and_then(|(patch_name, _, _)| patches.get(patch_name).and_then(|patch| patch.banner_path.clone()));
+                gui::game_art::show(
+                    ui,
+                    &mut state.game_art,
+                    &config.language,
+                    selection.game,
+                    game::ArtKind::Banner,
+                    banner_path.as_deref(),
+                    gui::game_art::BANNER_SIZE,
+                );
+            }
+
             ui.add_enabled_ui(!is_ready, |ui| {
                 if ui
                     .horizontal(|ui| {
@@ -1685,7 +4210,7 @@ pub fn show(
                             |ui| {
                                 ui.add({
                                     let text = egui::RichText::new(
-                                        i18n::LOCALES.lookup(&config.language, "select-save.select").unwrap(),
+                                        i18n::LOCALES.lookup(&config.language, "select-save.select"),
                                     );
 
                                     if state.show_save_select.is_some() {
@@ -1696,9 +4221,8 @@ pub fn show(
                                     }
                                 }) | ui
                                     .vertical_centered_justified(|ui| {
-                                        let patches = patches_scanner.read();
                                         let warning = if let Some(lobby) = lobby.as_ref() {
-                                            make_warning(&lobby, &roms, &patches)
+                                            make_warning(&lobby, &roms.by_game, &patches)
                                         } else {
                                             None
                                         };
@@ -1735,12 +4259,10 @@ pub fn show(
                                                 layout_job.append(
                                                     &format!(
                                                         "{}",
-                                                        &i18n::LOCALES
-                                                            .lookup(
-                                                                &config.language,
-                                                                &format!("game-{}.variant-{}", family, variant),
-                                                            )
-                                                            .unwrap(),
+                                                        &i18n::LOCALES.lookup(
+                                                            &config.language,
+                                                            &format!("game-{}.variant-{}", family, variant),
+                                                        ),
                                                     ),
                                                     0.0,
                                                     egui::TextFormat::simple(
@@ -1755,8 +4277,7 @@ pub fn show(
                                             } else {
                                                 layout_job.append(
                                                     &i18n::LOCALES
-                                                        .lookup(&config.language, "select-save.no-save-selected")
-                                                        .unwrap(),
+                                                        .lookup(&config.language, "select-save.no-save-selected"),
                                                     0.0,
                                                     egui::TextFormat::simple(
                                                         ui.style()
@@ -1794,8 +4315,12 @@ pub fn show(
                             let saves_path = config.saves_path();
                             let egui_ctx = ui.ctx().clone();
                             move || {
-                                roms_scanner.rescan(move || Some(game::scan_roms(&roms_path)));
-                                saves_scanner.rescan(move || Some(save::scan_saves(&saves_path)));
+                                roms_scanner.rescan_progressively(move |progress| {
+                                    Some(game::scan_roms(&roms_path, false, progress))
+                                });
+                                saves_scanner.rescan_progressively(move |progress| {
+                                    Some(save::scan_saves(&saves_path, false, progress))
+                                });
                                 egui_ctx.request_repaint();
                             }
                         });
@@ -1809,12 +4334,18 @@ pub fn show(
             });
 
             if state.show_save_select.is_some() {
+                // Once the save has been committed to, it has to stay where it is until the match.
+                let locked_save = selection
+                    .as_ref()
+                    .filter(|_| is_ready)
+                    .map(|selection| selection.save.path.clone());
                 gui::save_select_view::show(
                     ui,
                     &mut state.show_save_select,
                     &mut *selection,
                     &config.language,
                     &config.saves_path(),
+                    &config.roms_path(),
                     roms_scanner.clone(),
                     saves_scanner.clone(),
                     patches_scanner.clone(),
@@ -1823,10 +4354,27 @@ pub fn show(
                     } else {
                         None
                     },
+                    locked_save.as_deref(),
+                    &mut state.game_art,
+                    &save_registry,
                 );
             } else {
                 ui.horizontal_top(|ui| {
-                    let patches = patches_scanner.read();
+                    // Checksumming the ROM is only worth it if there are validation results to look up with it.
+                    let rom_crc32 = if !patch_validator.is_empty() {
+                        selection
+                            .as_ref()
+                            .and_then(|selection| roms.by_game.get(&selection.game))
+                            .map(|rom| crc32fast::hash(rom))
+                    } else {
+                        None
+                    };
+                    let known_not_to_apply = |name: &str, version: &semver::Version| {
+                        rom_crc32
+                            .and_then(|rom_crc32| patch_validator.outcome(name, version, rom_crc32))
+                            .map(|outcome| !outcome.applies())
+                            .unwrap_or(false)
+                    };
 
                     let mut supported_patches = std::collections::BTreeMap::new();
                     if let Some(selection) = selection.as_mut() {
@@ -1849,6 +4397,7 @@ pub fn show(
                     }
 
                     const PATCH_VERSION_COMBOBOX_WIDTH: f32 = 100.0;
+                    const PATCH_DETAILS_BUTTON_WIDTH: f32 = 24.0;
                     ui.add_enabled_ui(!is_ready && selection.is_some(), |ui| {
                         let warning = (|| {
                             let selection = if let Some(selection) = selection.as_ref() {
@@ -1923,7 +4472,7 @@ pub fn show(
                                 selection
                                     .as_ref()
                                     .and_then(|s| s.patch.as_ref().map(|(name, _, _)| name.as_str()))
-                                    .unwrap_or(&i18n::LOCALES.lookup(&config.language, "play-no-patch").unwrap())
+                                    .unwrap_or(&i18n::LOCALES.lookup(&config.language, "play-no-patch"))
                             ),
                             0.0,
                             egui::TextFormat::simple(
@@ -1948,7 +4497,12 @@ pub fn show(
 
                         let resp = egui::ComboBox::from_id_source("patch-select-combobox")
                             .selected_text(layout_job)
-                            .width(ui.available_width() - ui.spacing().item_spacing.x - PATCH_VERSION_COMBOBOX_WIDTH)
+                            .width(
+                                ui.available_width()
+                                    - ui.spacing().item_spacing.x * 2.0
+                                    - PATCH_VERSION_COMBOBOX_WIDTH
+                                    - PATCH_DETAILS_BUTTON_WIDTH,
+                            )
                             .show_ui(ui, |ui| {
                                 let selection = if let Some(selection) = selection.as_mut() {
                                     selection
@@ -1998,7 +4552,7 @@ pub fn show(
                                         gui::warning::append_to_layout_job(ui, &mut layout_job);
                                     }
                                     layout_job.append(
-                                        &i18n::LOCALES.lookup(&config.language, "play-no-patch").unwrap(),
+                                        &i18n::LOCALES.lookup(&config.language, "play-no-patch"),
                                         0.0,
                                         egui::TextFormat::simple(
                                             ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
@@ -2018,13 +4572,29 @@ pub fn show(
                                             selection.game.clone(),
                                             selection.save.clone(),
                                             None,
-                                            roms.get(&selection.game).unwrap().clone(),
+                                            roms.by_game.get(&selection.game).unwrap().clone(),
                                         );
                                     }
                                 }
 
                                 for (name, (meta, supported_versions)) in supported_patches.iter() {
+                                    let usable_version = supported_versions
+                                        .iter()
+                                        .find(|v| meta.versions.get(**v).map(|v| v.is_usable()).unwrap_or(false));
+
+                                    let dependency_problems = usable_version.and_then(|v| {
+                                        patch::resolve_requirements(&patches, name, v, selection.game).err()
+                                    });
+
                                     let warning = (|| {
+                                        if usable_version.map(|v| known_not_to_apply(name, v)).unwrap_or(false) {
+                                            return Some(Warning::PatchDoesNotApply);
+                                        }
+
+                                        if let Some(problems) = dependency_problems.as_ref() {
+                                            return Some(Warning::UnmetPatchDependencies(problems.clone()));
+                                        }
+
                                         let lobby = if let Some(lobby) = lobby.as_ref() {
                                             lobby
                                         } else {
@@ -2075,6 +4645,12 @@ pub fn show(
                                         None
                                     })();
 
+                                    let minimum_tango_version = supported_versions
+                                        .iter()
+                                        .flat_map(|v| meta.versions.get(*v))
+                                        .flat_map(|v| v.minimum_tango_version.as_ref())
+                                        .min();
+
                                     let checked = selection.patch.as_ref().map(|(name, _, _)| name) == Some(*name);
                                     let mut layout_job = egui::text::LayoutJob::default();
                                     if warning.is_some() {
@@ -2104,16 +4680,75 @@ pub fn show(
                                             },
                                         ),
                                     );
-                                    let mut resp = ui.selectable_label(checked, layout_job);
+                                    let mut resp = ui.add_enabled(
+                                        usable_version.is_some(),
+                                        egui::SelectableLabel::new(checked, layout_job),
+                                    );
+                                    if let Some(minimum_tango_version) = minimum_tango_version {
+                                        resp = resp.on_disabled_hover_text(requires_tango_text(
+                                            &config.language,
+                                            minimum_tango_version,
+                                        ));
+                                    }
                                     if let Some(warning) = warning {
                                         resp = resp.on_hover_text(warning.description(&config.language));
                                     }
                                     if resp.clicked() {
+                                        if let Some(problems) = dependency_problems.as_ref() {
+                                            notifications.toast(i18n::LOCALES.lookup_with_args(
+                                                &config.language,
+                                                "play-patch-unmet-dependencies",
+                                                &std::collections::HashMap::from([
+                                                    ("patch_name", name.to_string().into()),
+                                                    (
+                                                        "problems",
+                                                        describe_dependency_problems(&config.language, problems).into(),
+                                                    ),
+                                                ]),
+                                            ));
+
+                                            // The patch repository might have what's missing.
+                                            if config.enable_patch_autoupdate
+                                                && !patches_scanner.is_scanning()
+                                                && problems
+                                                    .iter()
+                                                    .any(|p| matches!(p, patch::DependencyProblem::Missing(_)))
+                                            {
+                                                notifications.toast(
+                                                    i18n::LOCALES
+                                                        .lookup(&config.language, "play-patch-fetching-dependencies"),
+                                                );
+                                                let egui_ctx = ui.ctx().clone();
+                                                let patches_scanner = patches_scanner.clone();
+                                                let repo_url = if !config.patch_repo.is_empty() {
+                                                    config.patch_repo.clone()
+                                                } else {
+                                                    config::DEFAULT_PATCH_REPO.to_owned()
+                                                };
+                                                let patches_path = config.patches_path();
+                                                let network = config.network.clone();
+                                                tokio::task::spawn_blocking(move || {
+                                                    patches_scanner.rescan(move || {
+                                                        if let Err(e) = sync::block_on(patch::update(
+                                                            &repo_url,
+                                                            &patches_path,
+                                                            &network,
+                                                        )) {
+                                                            log::error!("failed to update patches: {:?}", e);
+                                                        }
+                                                        patch::scan(&patches_path).ok()
+                                                    });
+                                                    egui_ctx.request_repaint();
+                                                });
+                                            }
+                                            return;
+                                        }
+
                                         *patch_selection = Some(name.to_string());
 
-                                        let rom = roms.get(&selection.game).unwrap().clone();
+                                        let rom = roms.by_game.get(&selection.game).unwrap();
                                         let (rom_code, revision) = selection.game.rom_code_and_revision();
-                                        let version = *supported_versions.first().unwrap();
+                                        let version = *usable_version.unwrap();
 
                                         let version_metadata = if let Some(version_metadata) =
                                             patches.get(*name).and_then(|p| p.versions.get(version)).cloned()
@@ -2123,8 +4758,8 @@ pub fn show(
                                             return;
                                         };
 
-                                        let rom = match patch::apply_patch_from_disk(
-                                            &rom,
+                                        let rom = match patched_roms.get_or_apply(
+                                            rom,
                                             selection.game,
                                             &config.patches_path(),
                                             &name,
@@ -2138,6 +4773,14 @@ pub fn show(
                                                     (rom_code, revision),
                                                     e
                                                 );
+                                                notifications.toast(i18n::LOCALES.lookup_with_args(
+                                                    &config.language,
+                                                    "play-patch-apply-error",
+                                                    &std::collections::HashMap::from([
+                                                        ("patch_name", name.to_string().into()),
+                                                        ("error", e.to_string().into()),
+                                                    ]),
+                                                ));
                                                 return;
                                             }
                                         };
@@ -2155,6 +4798,21 @@ pub fn show(
                             resp.response.on_hover_text(warning.description(&config.language));
                         }
 
+                        let selected_patch_name = selection
+                            .as_ref()
+                            .and_then(|selection| selection.patch.as_ref())
+                            .map(|(name, _, _)| name.clone());
+                        if ui
+                            .add_enabled(
+                                selected_patch_name.is_some(),
+                                egui::Button::new("ℹ").min_size(egui::vec2(PATCH_DETAILS_BUTTON_WIDTH, 0.0)),
+                            )
+                            .on_hover_text(i18n::LOCALES.lookup(&config.language, "play-patch-details"))
+                            .clicked()
+                        {
+                            state.show_patch_details = selected_patch_name;
+                        }
+
                         ui.add_enabled_ui(
                             !is_ready
                                 && selection
@@ -2267,6 +4925,10 @@ pub fn show(
 
                                         for version in supported_versions.iter() {
                                             let warning = (|| {
+                                                if known_not_to_apply(&patch_name, version) {
+                                                    return Some(Warning::PatchDoesNotApply);
+                                                }
+
                                                 let lobby = if let Some(lobby) = lobby.as_ref() {
                                                     lobby
                                                 } else {
@@ -2320,6 +4982,10 @@ pub fn show(
                                                 None
                                             })();
 
+                                            let version_info =
+                                                patches.get(&patch_name).and_then(|p| p.versions.get(*version));
+                                            let is_usable = version_info.map(|v| v.is_usable()).unwrap_or(false);
+
                                             let checked = &patch_version == *version;
                                             let mut layout_job = egui::text::LayoutJob::default();
                                             if warning.is_some() {
@@ -2338,48 +5004,30 @@ pub fn show(
                                                 ),
                                             );
 
-                                            let mut resp = ui.selectable_label(checked, layout_job);
+                                            let mut resp = ui.add_enabled(
+                                                is_usable,
+                                                egui::SelectableLabel::new(checked, layout_job),
+                                            );
+                                            if let Some(minimum_tango_version) =
+                                                version_info.and_then(|v| v.minimum_tango_version.as_ref())
+                                            {
+                                                resp = resp.on_disabled_hover_text(requires_tango_text(
+                                                    &config.language,
+                                                    minimum_tango_version,
+                                                ));
+                                            }
                                             if let Some(warning) = warning {
                                                 resp = resp.on_hover_text(warning.description(&config.language));
                                             }
                                             if resp.clicked() {
-                                                let rom = roms.get(&selection.game).unwrap().clone();
-                                                let (rom_code, revision) = selection.game.rom_code_and_revision();
-
-                                                let version_metadata = if let Some(version_metadata) = patches
-                                                    .get(&patch_name)
-                                                    .and_then(|p| p.versions.get(version))
-                                                    .cloned()
-                                                {
-                                                    version_metadata
-                                                } else {
-                                                    return;
-                                                };
-
-                                                let rom = match patch::apply_patch_from_disk(
-                                                    &rom,
-                                                    selection.game,
+                                                select_patch_version(
+                                                    selection,
+                                                    &roms.by_game,
+                                                    &patched_roms,
+                                                    &patches,
                                                     &config.patches_path(),
                                                     &patch_name,
-                                                    &version,
-                                                ) {
-                                                    Ok(r) => r,
-                                                    Err(e) => {
-                                                        log::error!(
-                                                            "failed to apply patch {}: {:?}: {:?}",
-                                                            patch_name,
-                                                            (rom_code, revision),
-                                                            e
-                                                        );
-                                                        return;
-                                                    }
-                                                };
-
-                                                *selection = gui::Selection::new(
-                                                    selection.game.clone(),
-                                                    selection.save.clone(),
-                                                    Some((patch_name.clone(), (*version).clone(), version_metadata)),
-                                                    rom,
+                                                    version,
                                                 );
                                             }
                                         }
@@ -2392,26 +5040,46 @@ pub fn show(
                     });
                 });
 
+                if let (Some(selection), Some(lobby)) = (selection.as_mut(), lobby.as_deref()) {
+                    ui.add_enabled_ui(!is_ready, |ui| {
+                        show_patch_version_suggestion(
+                            ui,
+                            &config.language,
+                            selection,
+                            lobby,
+                            &roms.by_game,
+                            &patched_roms,
+                            &patches,
+                            &config.patches_path(),
+                        );
+                    });
+                }
+
                 ui.separator();
 
                 if let Some(selection) = selection.as_mut() {
+                    show_save_requirements_banner(ui, &config.language, selection);
+                    ui.add_enabled_ui(!is_ready, |ui| {
+                        show_save_migration_banner(
+                            ui,
+                            &config.language,
+                            selection,
+                            &patches,
+                            &save_patch_history,
+                            &config.save_backups_path(),
+                        );
+                    });
+                    show_capabilities_summary(ui, &config.language, selection.game);
+                    selection.update_game_data_language(config.game_data_language.as_ref());
+
                     if let Some(assets) = selection.assets.as_ref() {
-                        let game_language = selection.game.language();
                         gui::save_view::show(
                             ui,
                             config.streamer_mode,
                             clipboard,
                             font_families,
                             &config.language,
-                            if let Some((_, _, metadata)) = selection.patch.as_ref() {
-                                if let Some(language) = metadata.rom_overrides.language.as_ref() {
-                                    language
-                                } else {
-                                    &game_language
-                                }
-                            } else {
-                                &game_language
-                            },
+                            &selection.game_data_language,
                             &selection.save.save,
                             assets,
                             &mut selection.save_view_state,
@@ -2427,7 +5095,132 @@ pub fn show(
         ..
     }) = connection_task.as_ref()
     {
-        let mut lobby = lobby.blocking_lock();
-        let _ = sync::block_on(lobby.set_local_selection(&selection));
+        let mut lobby = lobby.lock();
+        lobby.set_local_selection(&selection);
+        if let Some(lobby_options) = state.lobby_presets.take_pending_lobby_options() {
+            lobby.set_match_type(lobby_options.match_type);
+            lobby.set_reveal_setup(lobby_options.reveal_setup);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Waits for the connection task to get to a state.
+    async fn wait_for_state(
+        connection_task: &std::sync::Arc<parking_lot::Mutex<Option<ConnectionTask>>>,
+        f: impl Fn(&ConnectionState) -> bool,
+    ) {
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                if let Some(ConnectionTask::InProgress { state, .. }) = connection_task.lock().as_ref() {
+                    if f(state) {
+                        return;
+                    }
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("connection task should get there");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_while_starting_match() {
+        let _cores = crate::session::tests::CORES_LOCK.lock();
+        let dir = tempfile::tempdir().unwrap();
+
+        let config = std::sync::Arc::new(parking_lot::RwLock::new(config::Config::default()));
+        let game = game::find_by_family_and_variant("bn6", 0).unwrap();
+        // Nothing gets far enough to run the ROM, so it only has to be there for the opponent's pick to be found.
+        let roms_scanner = rom::Scanner::new();
+        roms_scanner.update(|roms| {
+            roms.by_game.insert(game, vec![0; 0x100].into());
+        });
+        let connection_task = std::sync::Arc::new(parking_lot::Mutex::new(None));
+        let cancellation_token = tokio_util::sync::CancellationToken::new();
+
+        // We play the opponent, hosting for the task to join.
+        let listener = net::lan::Listener::bind(0).await.unwrap();
+        let addr = format!("127.0.0.1:{}", listener.port().unwrap());
+        let task = tokio::task::spawn(run_connection_task(
+            config.clone(),
+            egui::Context::default(),
+            audio::LateBinder::new(48000),
+            std::sync::Arc::new(parking_lot::Mutex::new(stats::Counter::new(stats::window::EMU_TPS))),
+            std::sync::Arc::new(parking_lot::Mutex::new(None)),
+            roms_scanner,
+            patch::Scanner::new(),
+            patch::cache::PatchedRoms::new(),
+            opponents::Registry::load(dir.path().join("opponents.json")),
+            save::migration::History::load(dir.path().join("save_patches.json")),
+            save::registry::Registry::load(dir.path().join("save_registry.json")),
+            broadcast::Server::new(config.clone()),
+            ConnectTo::LanJoin { addr },
+            "us".to_string(),
+            dir.path().join("patches"),
+            dir.path().join("replays"),
+            connection_task.clone(),
+            cancellation_token.clone(),
+        ));
+        let (mut sender, mut receiver, _, _) = listener.accept("opponent").await.unwrap();
+
+        // Ready up on both sides, and then go quiet instead of sending our half of the exchange.
+        wait_for_state(&connection_task, |state| matches!(state, ConnectionState::InLobby(_))).await;
+        let lobby = if let Some(ConnectionTask::InProgress {
+            state: ConnectionState::InLobby(lobby),
+            ..
+        }) = connection_task.lock().as_ref()
+        {
+            lobby.clone()
+        } else {
+            unreachable!()
+        };
+        sender
+            .send_packet(&net::protocol::Packet::Settings(net::protocol::Settings {
+                nickname: "opponent".to_string(),
+                game_info: Some(net::protocol::GameInfo {
+                    family_and_variant: ("bn6".to_string(), 0),
+                    patch: None,
+                    rom_hash: None,
+                }),
+                fingerprint: Some(determinism::Fingerprint::local(None).unwrap()),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        // Settings that can't be readied up with take back a commitment made before them.
+        wait_for_state(&connection_task, |_| lobby.lock().remote_selection.is_some()).await;
+        lobby.lock().commit(b"save").unwrap();
+        sender
+            .send_packet(&net::protocol::Packet::Commit(net::protocol::Commit {
+                commitment: [0; 16],
+            }))
+            .await
+            .unwrap();
+        wait_for_state(&connection_task, |state| {
+            matches!(state, ConnectionState::Exchanging { .. })
+        })
+        .await;
+
+        cancellation_token.cancel();
+        tokio::time::timeout(std::time::Duration::from_secs(1), task)
+            .await
+            .expect("cancellation should not wait on the opponent")
+            .unwrap();
+        assert!(connection_task.lock().is_none());
+
+        // We're told, so we don't wait on them until we time out either.
+        tokio::time::timeout(ABORT_TIMEOUT, async {
+            loop {
+                if let net::protocol::Packet::Uncommit(_) = receiver.receive().await.unwrap() {
+                    return;
+                }
+            }
+        })
+        .await
+        .expect("opponent should be sent an uncommit");
     }
 }
@@ -0,0 +1,116 @@
+static WINDOW_FOCUSED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(true);
+
+/// Records whether the window is focused. Unfocused (including minimized) windows only get repaints for state transitions.
+pub fn set_window_focused(focused: bool) {
+    WINDOW_FOCUSED.store(focused, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn is_window_focused() -> bool {
+    WINDOW_FOCUSED.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+struct Inner {
+    egui_ctx: egui::Context,
+    deadline: parking_lot::Mutex<Option<std::time::Instant>>,
+    notify: tokio::sync::Notify,
+    requested: std::sync::atomic::AtomicUsize,
+    issued: std::sync::atomic::AtomicUsize,
+}
+
+impl Inner {
+    fn repaint(&self) {
+        self.issued.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.egui_ctx.request_repaint();
+    }
+}
+
+/// Coalesces repaint requests from background tasks, so that chatty sources (e.g. pings) don't wake the GUI thread for every update.
+pub struct Scheduler {
+    inner: std::sync::Arc<Inner>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Scheduler {
+    pub fn new(egui_ctx: egui::Context) -> Self {
+        let inner = std::sync::Arc::new(Inner {
+            egui_ctx,
+            deadline: parking_lot::Mutex::new(None),
+            notify: tokio::sync::Notify::new(),
+            requested: std::sync::atomic::AtomicUsize::new(0),
+            issued: std::sync::atomic::AtomicUsize::new(0),
+        });
+
+        let task = tokio::task::spawn({
+            let inner = inner.clone();
+            async move {
+                loop {
+                    let deadline = *inner.deadline.lock();
+                    if let Some(deadline) = deadline {
+                        tokio::select! {
+                            _ = tokio::time::sleep_until(deadline.into()) => {
+                                let mut deadline = inner.deadline.lock();
+                                if deadline.map(|d| d <= std::time::Instant::now()).unwrap_or(false) {
+                                    *deadline = None;
+                                    if is_window_focused() {
+                                        inner.repaint();
+                                    }
+                                }
+                            }
+                            _ = inner.notify.notified() => { }
+                        }
+                    } else {
+                        inner.notify.notified().await;
+                    }
+                }
+            }
+        });
+
+        Self { inner, task }
+    }
+
+    /// Requests a repaint no later than max_latency from now, merging it with any other pending request.
+    pub fn request_within(&self, max_latency: std::time::Duration) {
+        self.inner.requested.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if !is_window_focused() {
+            return;
+        }
+
+        let deadline = std::time::Instant::now() + max_latency;
+        let mut pending = self.inner.deadline.lock();
+        if pending.map(|pending| pending <= deadline).unwrap_or(false) {
+            return;
+        }
+        *pending = Some(deadline);
+        self.inner.notify.notify_one();
+    }
+
+    /// Requests a repaint right away, e.g. for changes the user is waiting to see.
+    pub fn request_now(&self) {
+        self.inner.requested.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if !is_window_focused() {
+            return;
+        }
+
+        // This repaint covers any pending one too.
+        *self.inner.deadline.lock() = None;
+        self.inner.repaint();
+    }
+
+    /// Requests a repaint right away even if the window isn't focused, for state transitions that notifications or the window title depend on.
+    pub fn request_transition(&self) {
+        self.inner.requested.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        *self.inner.deadline.lock() = None;
+        self.inner.repaint();
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.task.abort();
+        log::info!(
+            "repaint scheduler: coalesced {} repaint requests into {} repaints",
+            self.inner.requested.load(std::sync::atomic::Ordering::Relaxed),
+            self.inner.issued.load(std::sync::atomic::Ordering::Relaxed)
+        );
+    }
+}
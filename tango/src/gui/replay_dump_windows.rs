@@ -1,5 +1,3 @@
-use fluent_templates::Loader;
-
 use crate::{i18n, replay};
 
 pub struct State {
@@ -15,7 +13,7 @@ impl State {
         }
     }
 
-    pub fn add_child(&mut self, rom: Vec<u8>, replay: replay::Replay, path: std::path::PathBuf) {
+    pub fn add_child(&mut self, rom: std::sync::Arc<[u8]>, replay: replay::Replay, path: std::path::PathBuf) {
         let id = self.next_id;
         self.next_id += 1;
         let mut output_path = path.clone();
@@ -35,12 +33,26 @@ impl State {
             },
         );
     }
+
+    /// How far along the running exports are altogether, if any are running.
+    pub fn progress(&self) -> Option<f32> {
+        let (current, total) = self
+            .children
+            .values()
+            .filter(|child| child.cancellation_token.is_some() && child.result.lock().is_none())
+            .map(|child| *child.progress.lock())
+            .fold((0, 0), |(current, total), (c, t)| (current + c, total + t));
+        if total == 0 {
+            return None;
+        }
+        Some(current as f32 / total as f32)
+    }
 }
 
 pub struct ChildState {
     cancellation_token: Option<tokio_util::sync::CancellationToken>,
     output_path: std::path::PathBuf,
-    rom: Vec<u8>,
+    rom: std::sync::Arc<[u8]>,
     replay: replay::Replay,
     path: std::path::PathBuf,
     scale: usize,
@@ -62,6 +74,7 @@ pub fn show(
     state: &mut State,
     language: &unic_langid::LanguageIdentifier,
     replays_path: &std::path::Path,
+    bios_path: Option<&std::path::Path>,
 ) {
     state.children.retain(|id, state| {
         let mut open = true;
@@ -76,7 +89,7 @@ pub fn show(
                     egui::Grid::new(format!("replay-dump-window-{}-grid", id))
                         .num_columns(2)
                         .show(ui, |ui| {
-                            ui.strong(i18n::LOCALES.lookup(language, "replays-export-path").unwrap());
+                            ui.strong(i18n::LOCALES.lookup(language, "replays-export-path"));
                             ui.horizontal(|ui| {
                                 ui.add(
                                     egui::TextEdit::singleline(&mut format!("{}", state.output_path.display()))
@@ -85,7 +98,7 @@ pub fn show(
                                 );
 
                                 if ui
-                                    .button(i18n::LOCALES.lookup(language, "replays-export-path.change").unwrap())
+                                    .button(i18n::LOCALES.lookup(language, "replays-export-path.change"))
                                     .clicked()
                                 {
                                     if let Some(path) = rfd::FileDialog::new()
@@ -106,11 +119,11 @@ pub fn show(
                             });
                             ui.end_row();
 
-                            ui.strong(i18n::LOCALES.lookup(language, "replays-export-scale-factor").unwrap());
+                            ui.strong(i18n::LOCALES.lookup(language, "replays-export-scale-factor"));
                             ui.add(egui::DragValue::new(&mut state.scale).speed(1).clamp_range(1..=10));
                             ui.end_row();
 
-                            ui.strong(i18n::LOCALES.lookup(language, "replays-export-disable-bgm").unwrap());
+                            ui.strong(i18n::LOCALES.lookup(language, "replays-export-disable-bgm"));
                             ui.add(egui::Checkbox::new(&mut state.disable_bgm, ""));
                             ui.end_row();
                         });
@@ -121,14 +134,12 @@ pub fn show(
                         Ok(()) => {
                             ui.add(
                                 egui::widgets::ProgressBar::new(1.0)
-                                    .text(i18n::LOCALES.lookup(language, "replays-export-success").unwrap()),
+                                    .text(i18n::LOCALES.lookup(language, "replays-export-success")),
                             );
                             if ui
                                 .button(format!(
                                     "{}",
-                                    i18n::LOCALES
-                                        .lookup(language, "replays-export-confirm-success")
-                                        .unwrap()
+                                    i18n::LOCALES.lookup(language, "replays-export-confirm-success")
                                 ))
                                 .clicked()
                             {
@@ -136,19 +147,15 @@ pub fn show(
                             }
                         }
                         Err(e) => {
-                            ui.label(
-                                i18n::LOCALES
-                                    .lookup_with_args(
-                                        language,
-                                        "replays-export-error",
-                                        &std::collections::HashMap::from([("error", format!("{:?}", e).into())]),
-                                    )
-                                    .unwrap(),
-                            );
+                            ui.label(i18n::LOCALES.lookup_with_args(
+                                language,
+                                "replays-export-error",
+                                &std::collections::HashMap::from([("error", format!("{:?}", e).into())]),
+                            ));
                             if ui
                                 .button(format!(
                                     "{}",
-                                    i18n::LOCALES.lookup(language, "replays-export-confirm-error").unwrap()
+                                    i18n::LOCALES.lookup(language, "replays-export-confirm-error")
                                 ))
                                 .clicked()
                             {
@@ -171,7 +178,7 @@ pub fn show(
                     if ui
                         .button(format!(
                             "❎ {}",
-                            i18n::LOCALES.lookup(language, "replays-export-cancel").unwrap(),
+                            i18n::LOCALES.lookup(language, "replays-export-cancel"),
                         ))
                         .clicked()
                     {
@@ -179,10 +186,7 @@ pub fn show(
                     }
                 } else {
                     if ui
-                        .button(format!(
-                            "💾 {}",
-                            i18n::LOCALES.lookup(language, "replays-export").unwrap()
-                        ))
+                        .button(format!("💾 {}", i18n::LOCALES.lookup(language, "replays-export")))
                         .clicked()
                     {
                         let egui_ctx = ui.ctx().clone();
@@ -193,6 +197,7 @@ pub fn show(
                         let result = state.result.clone();
                         let mut settings = replay::export::Settings::default_with_scale(state.scale);
                         settings.disable_bgm = state.disable_bgm;
+                        settings.bios = bios_path.map(|path| path.to_path_buf());
                         let cancellation_token = tokio_util::sync::CancellationToken::new();
                         state.cancellation_token = Some(cancellation_token.clone());
                         tokio::task::spawn(async move {
@@ -1,22 +1,49 @@
 use chrono_locale::LocaleDate;
-use fluent_templates::Loader;
 
-use crate::{audio, game, gui, i18n, patch, replay, rom, save, scanner, session, stats};
+use crate::{audio, contentpolicy, fs, game, gui, i18n, patch, replay, rom, save, scanner, session, stats};
 
 struct Selection {
     path: std::path::PathBuf,
     game: &'static (dyn game::Game + Send + Sync),
     replay: replay::Replay,
     save: Box<dyn save::Save + Send + Sync>,
-    rom: Vec<u8>,
+    rom: std::sync::Arc<[u8]>,
     patch: Option<(String, semver::Version, patch::Version)>,
-    assets: Option<Box<dyn rom::Assets + Send + Sync>>,
+    assets: Option<std::sync::Arc<dyn rom::Assets + Send + Sync>>,
+    game_data_language: unic_langid::LanguageIdentifier,
     save_view: gui::save_view::State,
+    notes_key: String,
+    /// The notes being edited, which are written out when the player is done with them.
+    notes: replay::notes::Entry,
+    new_tag: String,
+    confirm_delete: bool,
+    /// The tick to dump the replay's state at, for patch authors tracking down desyncs.
+    dump_tick: u32,
+    /// Set when a state dump is started, and filled in with where it was written once it's done.
+    dump_result: Option<std::sync::Arc<parking_lot::Mutex<Option<anyhow::Result<std::path::PathBuf>>>>>,
+    /// Both sides' saves going into the match, ours first, if they're being viewed.
+    setups: Option<[Result<Setup, String>; 2]>,
+}
+
+/// One side's save as it was going into the match, taken from the state the replay starts from.
+///
+/// It's only ever shown: it's not in the saves folder, and exporting it always writes a new file.
+struct Setup {
+    nickname: String,
+    save: Box<dyn save::Save + Send + Sync>,
+    assets: std::sync::Arc<dyn rom::Assets + Send + Sync>,
+    game_data_language: unic_langid::LanguageIdentifier,
+    /// The name and version of the patch the side played with, if it isn't installed and the unpatched game's assets are being used instead.
+    missing_patch: Option<(String, String)>,
+    save_view: gui::save_view::State,
+    export_result: Option<Result<std::path::PathBuf, String>>,
 }
 
 pub struct State {
     replays_scanner: scanner::Scanner<std::collections::BTreeMap<std::path::PathBuf, (bool, replay::Metadata)>>,
     selection: Option<Selection>,
+    notes: Option<replay::notes::Index>,
+    search: String,
 }
 
 impl State {
@@ -24,6 +51,8 @@ impl State {
         Self {
             selection: None,
             replays_scanner: scanner::Scanner::new(),
+            notes: None,
+            search: String::new(),
         }
     }
 
@@ -72,6 +101,320 @@ impl State {
     }
 }
 
+/// Checks if every word searched for is in the replay's notes, tags, nicknames, link code or game.
+fn matches_search(
+    language: &unic_langid::LanguageIdentifier,
+    terms: &[String],
+    game: &'static (dyn game::Game + Send + Sync),
+    metadata: &replay::Metadata,
+    notes: Option<&replay::notes::Entry>,
+) -> bool {
+    let (family, variant) = game.family_and_variant();
+    let mut haystack = vec![
+        metadata.link_code.clone(),
+        i18n::LOCALES.lookup(language, &format!("game-{}.short", family)),
+        i18n::LOCALES.lookup(language, &format!("game-{}.variant-{}", family, variant)),
+    ];
+    for side in [metadata.local_side.as_ref(), metadata.remote_side.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        haystack.push(side.nickname.clone());
+    }
+    if let Some(notes) = notes {
+        haystack.push(notes.notes.clone());
+        haystack.extend(notes.tags.iter().cloned());
+    }
+    let haystack = haystack.into_iter().map(|s| s.to_lowercase()).collect::<Vec<_>>();
+    terms
+        .iter()
+        .all(|term| haystack.iter().any(|s| s.contains(term.as_str())))
+}
+
+fn save_notes(notes: &mut replay::notes::Index, selection: &Selection) {
+    // Only what's edited here is written, so loop markers set while playing the replay back are kept.
+    if let Err(e) = notes.update(&selection.notes_key, |entry| {
+        entry.notes = selection.notes.notes.clone();
+        entry.tags = selection.notes.tags.clone();
+    }) {
+        log::error!("failed to save replay notes: {:?}", e);
+    }
+}
+
+/// Extracts one side's save from the replay, along with the assets to show it with.
+fn load_setup(
+    side: Option<&replay::metadata::Side>,
+    state: Option<&mgba::state::State>,
+    roms: &rom::ScannedRoms,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+    patches_path: &std::path::Path,
+    game_data_language: Option<&unic_langid::LanguageIdentifier>,
+) -> Result<Setup, anyhow::Error> {
+    let side = side.ok_or_else(|| anyhow::anyhow!("the replay doesn't say who played"))?;
+    let state = state.ok_or_else(|| anyhow::anyhow!("the replay doesn't have this side's state"))?;
+    let game_info = side
+        .game_info
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("the replay doesn't say which game was played"))?;
+    let game = game::find_by_family_and_variant(&game_info.rom_family, game_info.rom_variant as u8)
+        .ok_or_else(|| anyhow::anyhow!("unknown game: {} {}", game_info.rom_family, game_info.rom_variant))?;
+    let save = game.save_from_wram(state.wram())?;
+    let rom = roms
+        .by_game
+        .get(&game)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("no ROM for {} {}", game_info.rom_family, game_info.rom_variant))?;
+
+    // If the patch is gone, the save is still worth seeing: the unpatched game's names and icons are mostly right.
+    let mut missing_patch = None;
+    let (rom, patch) = if let Some(patch_info) = game_info.patch.as_ref() {
+        let installed = patches.get(&patch_info.name).and_then(|patch| {
+            let version = semver::Version::parse(&patch_info.version).ok()?;
+            let version_meta = patch.versions.get(&version)?;
+            Some((version, version_meta))
+        });
+        match installed.map(|(version, version_meta)| {
+            patch::apply_patch_from_disk(&rom, game, patches_path, &patch_info.name, &version)
+                .map(|rom| (rom, version_meta))
+        }) {
+            Some(Ok((patched_rom, version_meta))) => (patched_rom.into(), Some(version_meta)),
+            Some(Err(e)) => {
+                log::error!("failed to apply patch {}: {:?}", patch_info.name, e);
+                missing_patch = Some((patch_info.name.clone(), patch_info.version.clone()));
+                (rom, None)
+            }
+            None => {
+                missing_patch = Some((patch_info.name.clone(), patch_info.version.clone()));
+                (rom, None)
+            }
+        }
+    } else {
+        (rom, None)
+    };
+
+    let game_data_language = gui::game_data_language(game_data_language, game, patch);
+    let assets = gui::load_assets(game, &rom, state.wram(), patch, &game_data_language)?;
+
+    Ok(Setup {
+        nickname: side.nickname.clone(),
+        save,
+        assets,
+        game_data_language,
+        missing_patch,
+        save_view: gui::save_view::State::new(),
+        export_result: None,
+    })
+}
+
+/// Asks where to write an extracted save, then writes it there. The suggested name comes from the replay and the player, so it's not mistaken for one of the user's own saves, and a file that's already there is never overwritten.
+fn export_setup(replay_path: &std::path::Path, setup: &Setup) -> Option<Result<std::path::PathBuf, String>> {
+    let file_name = format!(
+        "{}-{}.sav",
+        replay_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        contentpolicy::display_nickname(&setup.nickname)
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect::<String>()
+    );
+    let path = rfd::FileDialog::new()
+        .set_file_name(&file_name)
+        .add_filter("SAV", &["sav"])
+        .save_file()?;
+    Some(
+        fs::create_new(&path)
+            .and_then(|mut f| std::io::Write::write_all(&mut f, &setup.save.to_vec()))
+            .map(|()| path)
+            .map_err(|e| e.to_string()),
+    )
+}
+
+/// Shows both sides' saves next to each other.
+fn show_setups(
+    ui: &mut egui::Ui,
+    clipboard: &mut arboard::Clipboard,
+    font_families: &gui::FontFamilies,
+    language: &unic_langid::LanguageIdentifier,
+    replay_path: &std::path::Path,
+    setups: &mut [Result<Setup, String>; 2],
+) {
+    ui.columns(2, |columns| {
+        for (ui, setup) in columns.iter_mut().zip(setups.iter_mut()) {
+            let setup = match setup {
+                Ok(setup) => setup,
+                Err(e) => {
+                    ui.label(
+                        egui::RichText::new(i18n::LOCALES.lookup_with_args(
+                            language,
+                            "replays-setups.error",
+                            &std::collections::HashMap::from([("error", e.clone().into())]),
+                        ))
+                        .color(egui::Color32::RED),
+                    );
+                    continue;
+                }
+            };
+
+            ui.horizontal(|ui| {
+                ui.heading(contentpolicy::display_nickname(&setup.nickname));
+                if ui
+                    .button(format!(
+                        "💾 {}",
+                        i18n::LOCALES.lookup(language, "replays-setups.export")
+                    ))
+                    .on_hover_text(i18n::LOCALES.lookup(language, "replays-setups.export-description"))
+                    .clicked()
+                {
+                    if let Some(result) = export_setup(replay_path, setup) {
+                        setup.export_result = Some(result);
+                    }
+                }
+            });
+
+            match setup.export_result.as_ref() {
+                Some(Ok(path)) => {
+                    ui.label(i18n::LOCALES.lookup_with_args(
+                        language,
+                        "replays-setups.export-done",
+                        &std::collections::HashMap::from([("path", path.display().to_string().into())]),
+                    ));
+                }
+                Some(Err(e)) => {
+                    ui.label(
+                        egui::RichText::new(i18n::LOCALES.lookup_with_args(
+                            language,
+                            "replays-setups.export-error",
+                            &std::collections::HashMap::from([("error", e.clone().into())]),
+                        ))
+                        .color(egui::Color32::RED),
+                    );
+                }
+                None => {}
+            }
+
+            if let Some((name, version)) = setup.missing_patch.as_ref() {
+                ui.colored_label(
+                    egui::Color32::from_rgb(0xff, 0xa5, 0x00),
+                    i18n::LOCALES.lookup_with_args(
+                        language,
+                        "replays-setups.missing-patch",
+                        &std::collections::HashMap::from([
+                            ("name", name.clone().into()),
+                            ("version", version.clone().into()),
+                        ]),
+                    ),
+                );
+            }
+
+            gui::save_view::show(
+                ui,
+                false,
+                clipboard,
+                font_families,
+                language,
+                &setup.game_data_language,
+                &setup.save,
+                &setup.assets,
+                &mut setup.save_view,
+                true,
+            );
+        }
+    });
+}
+
+/// Lets patch authors dump the machine state at a tick of the selected replay, e.g. the one a desync report points at.
+fn show_dump_state(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    selection: &mut Selection,
+    bios_path: Option<&std::path::Path>,
+) {
+    let final_tick = if let Some(final_tick) = replay::dump::final_tick(&selection.replay) {
+        final_tick
+    } else {
+        return;
+    };
+
+    let running = selection
+        .dump_result
+        .as_ref()
+        .map(|result| result.lock().is_none())
+        .unwrap_or(false);
+
+    ui.horizontal(|ui| {
+        ui.strong(i18n::LOCALES.lookup(language, "replays-dump-state"));
+        ui.add(egui::DragValue::new(&mut selection.dump_tick).clamp_range(0..=final_tick));
+        ui.label(i18n::LOCALES.lookup_with_args(
+            language,
+            "replays-dump-state.final-tick",
+            &std::collections::HashMap::from([("tick", final_tick.into())]),
+        ));
+        if ui
+            .add_enabled(
+                !running,
+                egui::Button::new(i18n::LOCALES.lookup(language, "replays-dump-state.run")),
+            )
+            .clicked()
+        {
+            if let Some(output_path) = rfd::FileDialog::new().pick_folder() {
+                let output_path = output_path.join(format!(
+                    "{}-tick{}",
+                    selection
+                        .path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    selection.dump_tick
+                ));
+                let result = std::sync::Arc::new(parking_lot::Mutex::new(None));
+                selection.dump_result = Some(result.clone());
+                tokio::task::spawn_blocking({
+                    let egui_ctx = ui.ctx().clone();
+                    let rom = selection.rom.clone();
+                    let game = selection.game;
+                    let replay = selection.replay.clone();
+                    let tick = selection.dump_tick;
+                    let bios_path = bios_path.map(|path| path.to_path_buf());
+                    move || {
+                        *result.lock() = Some(
+                            replay::dump::dump_state(&rom, bios_path.as_deref(), game, &replay, tick, &output_path)
+                                .map(|()| output_path),
+                        );
+                        egui_ctx.request_repaint();
+                    }
+                });
+            }
+        }
+    });
+
+    if let Some(result) = selection.dump_result.as_ref() {
+        match result.lock().as_ref() {
+            None => {
+                ui.spinner();
+            }
+            Some(Ok(output_path)) => {
+                ui.label(i18n::LOCALES.lookup_with_args(
+                    language,
+                    "replays-dump-state.done",
+                    &std::collections::HashMap::from([("path", output_path.display().to_string().into())]),
+                ));
+            }
+            Some(Err(e)) => {
+                ui.label(
+                    egui::RichText::new(i18n::LOCALES.lookup_with_args(
+                        language,
+                        "replays-dump-state.error",
+                        &std::collections::HashMap::from([("error", format!("{}", e).into())]),
+                    ))
+                    .color(egui::Color32::RED),
+                );
+            }
+        }
+    }
+}
+
 pub fn show(
     ui: &mut egui::Ui,
     clipboard: &mut arboard::Clipboard,
@@ -79,18 +422,38 @@ pub fn show(
     state: &mut State,
     replay_dump_windows: &mut gui::replay_dump_windows::State,
     language: &unic_langid::LanguageIdentifier,
+    game_data_language: Option<&unic_langid::LanguageIdentifier>,
     patches_path: &std::path::Path,
     patches_scanner: patch::Scanner,
     roms_scanner: rom::Scanner,
     replays_path: &std::path::Path,
+    bios_path: Option<&std::path::Path>,
     audio_binder: audio::LateBinder,
     emu_tps_counter: std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
     session: std::sync::Arc<parking_lot::Mutex<Option<session::Session>>>,
-) {
+    save_registry: &save::registry::Registry,
+    show_debug: bool,
+) -> Option<(&'static (dyn game::Game + Send + Sync), std::path::PathBuf)> {
+    let mut show_save = None;
     let roms = roms_scanner.read();
     let patches = patches_scanner.read();
+    let notes = state
+        .notes
+        .get_or_insert_with(|| replay::notes::Index::load(replay::notes::index_path(replays_path)));
 
     egui::SidePanel::left("replays-window-left-panel").show_inside(ui, |ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut state.search)
+                .hint_text(i18n::LOCALES.lookup(language, "replays-search"))
+                .desired_width(f32::INFINITY),
+        );
+        let search_terms = state
+            .search
+            .to_lowercase()
+            .split_whitespace()
+            .map(|term| term.to_string())
+            .collect::<Vec<_>>();
+
         egui::ScrollArea::vertical()
             .auto_shrink([false, false])
             .id_source("replays-window-left")
@@ -98,7 +461,7 @@ pub fn show(
                 if state.replays_scanner.is_scanning() {
                     ui.horizontal(|ui| {
                         ui.spinner();
-                        ui.label(i18n::LOCALES.lookup(language, "replays-scanning").unwrap());
+                        ui.label(i18n::LOCALES.lookup(language, "replays-scanning"));
                     });
                     return;
                 }
@@ -140,6 +503,20 @@ pub fn show(
                             continue;
                         };
 
+                        if !search_terms.is_empty()
+                            && !matches_search(
+                                language,
+                                &search_terms,
+                                game,
+                                metadata,
+                                notes.get(&replay::notes::key(metadata)),
+                            )
+                        {
+                            continue;
+                        }
+
+                        gui::fonts::request(&remote_side.nickname);
+
                         let selected = state.selection.as_ref().map(|s| &s.path) == Some(path);
                         let text_color = if selected {
                             ui.ctx().style().visuals.selection.stroke.color
@@ -167,26 +544,20 @@ pub fn show(
                             ),
                         );
                         layout_job.append(
-                            &i18n::LOCALES
-                                .lookup_with_args(
-                                    language,
-                                    "replay-subtitle",
-                                    &std::collections::HashMap::from([
-                                        (
-                                            "game_family",
-                                            i18n::LOCALES
-                                                .lookup(
-                                                    language,
-                                                    &format!("game-{}.short", game.family_and_variant().0),
-                                                )
-                                                .unwrap()
-                                                .into(),
-                                        ),
-                                        ("link_code", metadata.link_code.clone().into()),
-                                        ("nickname", remote_side.nickname.clone().into()),
-                                    ]),
-                                )
-                                .unwrap(),
+                            &i18n::LOCALES.lookup_with_args(
+                                language,
+                                "replay-subtitle",
+                                &std::collections::HashMap::from([
+                                    (
+                                        "game_family",
+                                        i18n::LOCALES
+                                            .lookup(language, &format!("game-{}.short", game.family_and_variant().0))
+                                            .into(),
+                                    ),
+                                    ("link_code", metadata.link_code.clone().into()),
+                                    ("nickname", contentpolicy::display_nickname(&remote_side.nickname).into()),
+                                ]),
+                            ),
                             0.0,
                             egui::TextFormat::simple(
                                 ui.style().text_styles.get(&egui::TextStyle::Small).unwrap().clone(),
@@ -225,7 +596,7 @@ pub fn show(
                                 }
                             };
 
-                            let mut rom = if let Some(rom) = roms.get(&game) {
+                            let mut rom = if let Some(rom) = roms.by_game.get(&game) {
                                 rom.clone()
                             } else {
                                 continue;
@@ -259,7 +630,7 @@ pub fn show(
                                     &patch_info.name,
                                     &version,
                                 ) {
-                                    Ok(r) => r,
+                                    Ok(r) => r.into(),
                                     Err(e) => {
                                         log::error!(
                                             "failed to apply patch {}: {:?}: {:?}",
@@ -276,13 +647,14 @@ pub fn show(
                                 None
                             };
 
-                            let assets = match game.load_rom_assets(
+                            let metadata = patch.as_ref().map(|(_, _, metadata)| metadata);
+                            let game_data_language = gui::game_data_language(game_data_language, game, metadata);
+                            let assets = match gui::load_assets(
+                                game,
                                 &rom,
                                 save_state.wram(),
-                                &patch
-                                    .as_ref()
-                                    .map(|(_, _, metadata)| metadata.rom_overrides.clone())
-                                    .unwrap_or_default(),
+                                metadata,
+                                &game_data_language,
                             ) {
                                 Ok(assets) => Some(assets),
                                 Err(e) => {
@@ -291,6 +663,7 @@ pub fn show(
                                 }
                             };
 
+                            let notes_key = replay::notes::key(metadata);
                             state.selection = Some(Selection {
                                 path: path.clone(),
                                 game,
@@ -299,7 +672,15 @@ pub fn show(
                                 rom,
                                 patch,
                                 assets,
+                                game_data_language,
                                 save_view: gui::save_view::State::new(),
+                                notes: notes.get(&notes_key).cloned().unwrap_or_default(),
+                                notes_key,
+                                new_tag: String::new(),
+                                confirm_delete: false,
+                                dump_tick: 0,
+                                dump_result: None,
+                                setups: None,
                             });
                         }
                     }
@@ -307,6 +688,7 @@ pub fn show(
             });
     });
 
+    let mut delete = false;
     egui::CentralPanel::default().show_inside(ui, |ui| {
         egui::ScrollArea::vertical()
             .auto_shrink([false, false])
@@ -321,8 +703,31 @@ pub fn show(
 
                 ui.vertical(|ui| {
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                        if selection.confirm_delete {
+                            if ui
+                                .button(i18n::LOCALES.lookup(language, "replays-delete.cancel"))
+                                .clicked()
+                            {
+                                selection.confirm_delete = false;
+                            }
+                            if ui
+                                .button(format!(
+                                    "🗑 {}",
+                                    i18n::LOCALES.lookup(language, "replays-delete.confirm")
+                                ))
+                                .clicked()
+                            {
+                                delete = true;
+                            }
+                        } else if ui
+                            .button(format!("🗑 {}", i18n::LOCALES.lookup(language, "replays-delete")))
+                            .clicked()
+                        {
+                            selection.confirm_delete = true;
+                        }
+
                         if ui
-                            .button(format!("▶️ {}", i18n::LOCALES.lookup(language, "replays-play").unwrap()))
+                            .button(format!("▶️ {}", i18n::LOCALES.lookup(language, "replays-play")))
                             .clicked()
                         {
                             tokio::task::spawn_blocking({
@@ -336,29 +741,65 @@ pub fn show(
                                 let rom = selection.rom.clone();
                                 let emu_tps_counter = emu_tps_counter.clone();
                                 let replay = selection.replay.clone();
+                                let replay_path = selection.path.clone();
+                                let bios_path = bios_path.map(|path| path.to_path_buf());
 
                                 move || {
-                                    *session.lock() = Some(
-                                        session::Session::new_replayer(
-                                            audio_binder,
-                                            game,
-                                            patch,
-                                            &rom,
-                                            emu_tps_counter,
-                                            &replay,
-                                        )
-                                        .unwrap(),
-                                    ); // TODO: Don't unwrap maybe
+                                    match session::Session::new_replayer(
+                                        audio_binder,
+                                        game,
+                                        patch,
+                                        &rom,
+                                        bios_path.as_deref(),
+                                        emu_tps_counter,
+                                        &replay,
+                                        &replay_path,
+                                    ) {
+                                        Ok(s) => {
+                                            *session.lock() = Some(s);
+                                        }
+                                        Err(e) => {
+                                            log::error!("failed to start replay: {:?}", e);
+                                        }
+                                    }
                                     egui_ctx.request_repaint();
                                 }
                             });
                         }
 
                         if ui
-                            .button(format!(
-                                "💾 {}",
-                                i18n::LOCALES.lookup(language, "replays-export").unwrap()
-                            ))
+                            .selectable_label(
+                                selection.setups.is_some(),
+                                format!("👥 {}", i18n::LOCALES.lookup(language, "replays-view-setups")),
+                            )
+                            .clicked()
+                        {
+                            selection.setups = if selection.setups.is_some() {
+                                None
+                            } else {
+                                let metadata = &selection.replay.metadata;
+                                Some(
+                                    [
+                                        (metadata.local_side.as_ref(), selection.replay.local_state.as_ref()),
+                                        (metadata.remote_side.as_ref(), selection.replay.remote_state.as_ref()),
+                                    ]
+                                    .map(|(side, state)| {
+                                        load_setup(side, state, &roms, &patches, patches_path, game_data_language)
+                                            .map_err(|e| {
+                                                log::error!(
+                                                    "failed to load setup from replay {}: {:?}",
+                                                    selection.path.display(),
+                                                    e
+                                                );
+                                                e.to_string()
+                                            })
+                                    }),
+                                )
+                            };
+                        }
+
+                        if ui
+                            .button(format!("💾 {}", i18n::LOCALES.lookup(language, "replays-export")))
                             .clicked()
                         {
                             replay_dump_windows.add_child(
@@ -386,23 +827,109 @@ pub fn show(
                             });
                         });
                     });
-                    if let Some(assets) = selection.assets.as_ref() {
-                        let game_language = selection.game.language();
+
+                    ui.horizontal_wrapped(|ui| {
+                        ui.strong(i18n::LOCALES.lookup(language, "replays-save"));
+                        match save_registry.find_by_replay(&selection.path) {
+                            Some(entry) if fs::metadata(&entry.path).is_ok() => {
+                                if ui
+                                    .link(format!("{}", entry.path.display()))
+                                    .on_hover_text(i18n::LOCALES.lookup(language, "replays-save.show"))
+                                    .clicked()
+                                {
+                                    show_save = Some((selection.game, entry.path.clone()));
+                                }
+                            }
+                            Some(entry) => {
+                                ui.weak(i18n::LOCALES.lookup_with_args(
+                                    language,
+                                    "replays-save.missing",
+                                    &std::collections::HashMap::from([(
+                                        "path",
+                                        format!("{}", entry.path.display()).into(),
+                                    )]),
+                                ));
+                            }
+                            None => {
+                                ui.weak(i18n::LOCALES.lookup(language, "replays-save.unknown"));
+                            }
+                        }
+                    });
+
+                    ui.horizontal_wrapped(|ui| {
+                        ui.strong(i18n::LOCALES.lookup(language, "replays-tags"));
+                        let mut removed_tag = None;
+                        for (i, tag) in selection.notes.tags.iter().enumerate() {
+                            if ui.small_button(format!("{} ✖", tag)).clicked() {
+                                removed_tag = Some(i);
+                            }
+                        }
+                        if let Some(i) = removed_tag {
+                            selection.notes.tags.remove(i);
+                            save_notes(notes, selection);
+                        }
+
+                        let resp = ui.add(
+                            egui::TextEdit::singleline(&mut selection.new_tag)
+                                .hint_text(i18n::LOCALES.lookup(language, "replays-tags.add"))
+                                .desired_width(150.0),
+                        );
+                        let mut added_tag = None;
+                        if resp.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                            added_tag = Some(selection.new_tag.trim().to_string());
+                        }
+
+                        // Suggest tags used before, so the same tag isn't spelled several different ways.
+                        let new_tag = selection.new_tag.trim().to_lowercase();
+                        if !new_tag.is_empty() {
+                            for tag in notes.all_tags() {
+                                if !tag.to_lowercase().starts_with(&new_tag)
+                                    || selection.notes.tags.iter().any(|t| t == tag)
+                                {
+                                    continue;
+                                }
+                                if ui.small_button(tag).clicked() {
+                                    added_tag = Some(tag.to_string());
+                                }
+                            }
+                        }
+
+                        if let Some(tag) = added_tag {
+                            if !tag.is_empty() && !selection.notes.tags.contains(&tag) {
+                                selection.notes.tags.push(tag);
+                                save_notes(notes, selection);
+                            }
+                            selection.new_tag.clear();
+                        }
+                    });
+
+                    ui.strong(i18n::LOCALES.lookup(language, "replays-notes"));
+                    if ui
+                        .add(
+                            egui::TextEdit::multiline(&mut selection.notes.notes)
+                                .hint_text(i18n::LOCALES.lookup(language, "replays-notes.placeholder"))
+                                .desired_rows(3)
+                                .desired_width(f32::INFINITY),
+                        )
+                        .lost_focus()
+                    {
+                        save_notes(notes, selection);
+                    }
+
+                    if show_debug {
+                        show_dump_state(ui, language, selection, bios_path);
+                    }
+
+                    if let Some(setups) = selection.setups.as_mut() {
+                        show_setups(ui, clipboard, font_families, language, &selection.path, setups);
+                    } else if let Some(assets) = selection.assets.as_ref() {
                         gui::save_view::show(
                             ui,
                             false,
                             clipboard,
                             font_families,
                             language,
-                            if let Some((_, _, metadata)) = selection.patch.as_ref() {
-                                if let Some(language) = metadata.rom_overrides.language.as_ref() {
-                                    language
-                                } else {
-                                    &game_language
-                                }
-                            } else {
-                                &game_language
-                            },
+                            &selection.game_data_language,
                             &selection.save,
                             &assets,
                             &mut selection.save_view,
@@ -412,4 +939,32 @@ pub fn show(
                 });
             });
     });
+
+    if delete {
+        if let Some(selection) = state.selection.take() {
+            match fs::remove_file(&selection.path) {
+                Ok(()) => {
+                    // Copies of the replay share its notes, so they're only cleaned up with the last one.
+                    let has_copies = state.replays_scanner.read().iter().any(|(path, (_, metadata))| {
+                        path != &selection.path && replay::notes::key(metadata) == selection.notes_key
+                    });
+                    if !has_copies {
+                        if let Err(e) = notes.remove(&selection.notes_key) {
+                            log::error!("failed to remove replay notes: {:?}", e);
+                        }
+                    }
+                    state.rescan(ui.ctx(), replays_path);
+                }
+                Err(e) => {
+                    log::error!("failed to delete replay {}: {:?}", selection.path.display(), e);
+                    state.selection = Some(Selection {
+                        confirm_delete: false,
+                        ..selection
+                    });
+                }
+            }
+        }
+    }
+
+    show_save
 }
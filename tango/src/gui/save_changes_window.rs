@@ -0,0 +1,255 @@
+use crate::{config, game, gui, i18n, save, session};
+
+pub struct State {
+    game: &'static (dyn game::Game + Send + Sync),
+    path: std::path::PathBuf,
+    modified: Vec<u8>,
+    summary: Vec<String>,
+    error: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    Discard,
+    Overwrite,
+    SaveAsNew,
+}
+
+fn summarize_changes(
+    language: &unic_langid::LanguageIdentifier,
+    original: &(dyn save::Save + Send + Sync),
+    modified: &(dyn save::Save + Send + Sync),
+) -> Vec<String> {
+    let mut summary = vec![];
+
+    if let (Some(original_chips), Some(modified_chips)) = (original.view_chips(), modified.view_chips()) {
+        if original_chips.equipped_folder_index() != modified_chips.equipped_folder_index() {
+            summary.push(i18n::LOCALES.lookup_with_args(
+                language,
+                "save-changes-summary-equipped-folder",
+                &std::collections::HashMap::from([
+                    ("from", (original_chips.equipped_folder_index() + 1).to_string().into()),
+                    ("to", (modified_chips.equipped_folder_index() + 1).to_string().into()),
+                ]),
+            ));
+        }
+    }
+
+    let diff = save::diff::diff(original, modified);
+
+    for (folder_index, changes) in diff.folders.iter().flatten().enumerate() {
+        if changes.is_empty() {
+            continue;
+        }
+
+        summary.push(i18n::LOCALES.lookup_with_args(
+            language,
+            "save-changes-summary-folder",
+            &std::collections::HashMap::from([
+                ("folder", (folder_index + 1).to_string().into()),
+                ("added", changes.added.len().to_string().into()),
+                ("removed", changes.removed.len().to_string().into()),
+            ]),
+        ));
+    }
+
+    for (key, change) in [
+        ("save-changes-summary-zenny", diff.zenny.as_ref()),
+        ("save-changes-summary-bugfrags", diff.bugfrags.as_ref()),
+    ] {
+        let change = if let Some(change) = change.filter(|change| !change.is_empty()) {
+            change
+        } else {
+            continue;
+        };
+        let delta = change.after as i64 - change.before as i64;
+        summary.push(i18n::LOCALES.lookup_with_args(
+            language,
+            key,
+            &std::collections::HashMap::from([
+                ("from", change.before.to_string().into()),
+                ("to", change.after.to_string().into()),
+                ("delta", format!("{:+}", delta).into()),
+            ]),
+        ));
+    }
+
+    if diff.navi.map(|navi| !navi.is_empty()).unwrap_or(false) {
+        summary.push(i18n::LOCALES.lookup(language, "save-changes-summary-navi"));
+    }
+
+    if summary.is_empty() {
+        summary.push(i18n::LOCALES.lookup(language, "save-changes-summary-other"));
+    }
+
+    summary
+}
+
+fn apply(action: Action, path: &std::path::Path, modified: &[u8]) -> std::io::Result<()> {
+    match action {
+        Action::Discard => {
+            log::info!("discarding changes to {}", path.display());
+        }
+        Action::Overwrite => {
+            log::info!("writing changes back to {}", path.display());
+            save::write_atomically(path, modified)?;
+        }
+        Action::SaveAsNew => {
+            let new_path = save::new_save_path(path);
+            log::info!("writing changes to {}", new_path.display());
+            save::write_atomically(&new_path, modified)?;
+        }
+    }
+    Ok(())
+}
+
+/// Ends a session, applying the write-back policy to any changes it made to its save.
+///
+/// If the policy is to ask, the window is opened and the changes are held until the user decides. If the emulator has stopped responding, the session is force-terminated and the save is taken as it was when it was last checked for changes.
+pub fn end_session(
+    session: session::Session,
+    policy: config::SaveWriteBackPolicy,
+    language: &unic_langid::LanguageIdentifier,
+    show_save_changes: &mut Option<State>,
+    selection: &mut Option<gui::Selection>,
+) {
+    let source_save = if let Some(source_save) = session.source_save() {
+        source_save
+    } else {
+        if session.hang().is_some() {
+            session.force_terminate();
+        } else {
+            session.shutdown();
+        }
+        return;
+    };
+
+    let game = session.game_info().game;
+    let path = source_save.path.clone();
+    let original = source_save.original.clone();
+    let modified = if session.hang().is_some() {
+        let modified = source_save.latest();
+        session.force_terminate();
+        modified
+    } else {
+        let modified = session.current_save();
+        session.shutdown();
+        modified
+    };
+
+    if modified == original {
+        return;
+    }
+
+    let action = match policy {
+        config::SaveWriteBackPolicy::Ask => {
+            let summary = match (game.parse_save(&original), game.parse_save(&modified)) {
+                (Ok(original), Ok(modified)) => summarize_changes(language, &*original, &*modified),
+                _ => vec![i18n::LOCALES.lookup(language, "save-changes-summary-other")],
+            };
+            *show_save_changes = Some(State {
+                game,
+                path,
+                modified,
+                summary,
+                error: None,
+            });
+            return;
+        }
+        config::SaveWriteBackPolicy::Discard => Action::Discard,
+        config::SaveWriteBackPolicy::Overwrite => Action::Overwrite,
+        config::SaveWriteBackPolicy::SaveAsNew => Action::SaveAsNew,
+    };
+
+    if let Err(e) = apply(action, &path, &modified) {
+        log::error!("failed to write back save: {:?}", e);
+        return;
+    }
+
+    if let Some(selection) = selection.as_mut() {
+        if selection.save.path == path {
+            let _ = selection.reload_save();
+        }
+    }
+}
+
+pub fn show(
+    ctx: &egui::Context,
+    show_save_changes: &mut Option<State>,
+    language: &unic_langid::LanguageIdentifier,
+    selection: &mut Option<gui::Selection>,
+) {
+    let state = if let Some(state) = show_save_changes.as_mut() {
+        state
+    } else {
+        return;
+    };
+
+    let mut action = None;
+    egui::Window::new(i18n::LOCALES.lookup(language, "save-changes"))
+        .id(egui::Id::new("save-changes-window"))
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                "save-changes-description",
+                &std::collections::HashMap::from([("path", state.path.display().to_string().into())]),
+            ));
+            for line in state.summary.iter() {
+                ui.label(format!("• {}", line));
+            }
+
+            if let Some(error) = state.error.as_ref() {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    i18n::LOCALES.lookup_with_args(
+                        language,
+                        "save-changes-error",
+                        &std::collections::HashMap::from([("error", error.clone().into())]),
+                    ),
+                );
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "save-changes.overwrite"))
+                    .clicked()
+                {
+                    action = Some(Action::Overwrite);
+                }
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "save-changes.save-as-new"))
+                    .clicked()
+                {
+                    action = Some(Action::SaveAsNew);
+                }
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "save-changes.discard"))
+                    .clicked()
+                {
+                    action = Some(Action::Discard);
+                }
+            });
+        });
+
+    let action = if let Some(action) = action {
+        action
+    } else {
+        return;
+    };
+
+    if let Err(e) = apply(action, &state.path, &state.modified) {
+        log::error!("failed to write back save: {:?}", e);
+        state.error = Some(e.to_string());
+        return;
+    }
+
+    if let Some(selection) = selection.as_mut() {
+        if selection.game == state.game && selection.save.path == state.path {
+            let _ = selection.reload_save();
+        }
+    }
+    *show_save_changes = None;
+}
@@ -0,0 +1,176 @@
+use crate::{game, gui, i18n, rom, save};
+
+/// Two saves of the same game being compared.
+pub struct State {
+    game: &'static (dyn game::Game + Send + Sync),
+    before_path: std::path::PathBuf,
+    after_path: std::path::PathBuf,
+    chip_codes: Option<&'static [u8]>,
+    diff: save::diff::SaveDiff,
+}
+
+impl State {
+    pub fn new(
+        game: &'static (dyn game::Game + Send + Sync),
+        before: &save::ScannedSave,
+        after: &save::ScannedSave,
+    ) -> Self {
+        Self {
+            game,
+            before_path: before.path.clone(),
+            after_path: after.path.clone(),
+            chip_codes: before.save.view_chips().map(|chips_view| chips_view.chip_codes()),
+            diff: save::diff::diff(before.save.as_ref(), after.save.as_ref()),
+        }
+    }
+
+    pub fn game(&self) -> &'static (dyn game::Game + Send + Sync) {
+        self.game
+    }
+}
+
+fn chip_name(
+    language: &unic_langid::LanguageIdentifier,
+    assets: Option<&(dyn rom::Assets + Send + Sync)>,
+    id: usize,
+) -> String {
+    assets
+        .and_then(|assets| assets.chip(id))
+        .map(|chip| chip.name())
+        .unwrap_or_else(|| {
+            i18n::LOCALES.lookup_with_args(
+                language,
+                "save-diff-unknown-chip",
+                &std::collections::HashMap::from([("id", id.into())]),
+            )
+        })
+}
+
+fn show_changes<T>(ui: &mut egui::Ui, changes: &save::diff::Changes<T>, describe: impl Fn(&T) -> String) {
+    let colors = gui::theme::colors(ui.ctx());
+    for item in changes.added.iter() {
+        ui.colored_label(colors.ready, format!("+ {}", describe(item)));
+    }
+    for item in changes.removed.iter() {
+        ui.colored_label(colors.warning, format!("− {}", describe(item)));
+    }
+}
+
+fn show_change<T>(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    key: &str,
+    change: &save::diff::Change<T>,
+    describe: impl Fn(&T) -> String,
+) where
+    T: PartialEq,
+{
+    if change.is_empty() {
+        return;
+    }
+    ui.label(i18n::LOCALES.lookup_with_args(
+        language,
+        key,
+        &std::collections::HashMap::from([
+            ("before", describe(&change.before).into()),
+            ("after", describe(&change.after).into()),
+        ]),
+    ));
+}
+
+pub fn show(
+    ctx: &egui::Context,
+    show_save_diff: &mut Option<State>,
+    language: &unic_langid::LanguageIdentifier,
+    saves_path: &std::path::Path,
+    assets: Option<&(dyn rom::Assets + Send + Sync)>,
+) {
+    let state = if let Some(state) = show_save_diff.as_ref() {
+        state
+    } else {
+        return;
+    };
+
+    let mut open = true;
+    egui::Window::new(i18n::LOCALES.lookup(language, "save-diff"))
+        .id(egui::Id::new("save-diff-window"))
+        .open(&mut open)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} → {}",
+                state
+                    .before_path
+                    .strip_prefix(saves_path)
+                    .unwrap_or(&state.before_path)
+                    .display(),
+                state
+                    .after_path
+                    .strip_prefix(saves_path)
+                    .unwrap_or(&state.after_path)
+                    .display()
+            ));
+            ui.separator();
+
+            if state.diff.is_empty() {
+                ui.weak(i18n::LOCALES.lookup(language, "save-diff.no-differences"));
+                return;
+            }
+
+            egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                let diff = &state.diff;
+
+                if let Some(navi) = diff.navi.as_ref() {
+                    show_change(ui, language, "save-diff-navi", navi, |id| {
+                        assets
+                            .and_then(|assets| assets.navi(*id))
+                            .map(|navi| navi.name())
+                            .unwrap_or_else(|| format!("#{}", id))
+                    });
+                }
+                if let Some(zenny) = diff.zenny.as_ref() {
+                    show_change(ui, language, "save-diff-zenny", zenny, |v| v.to_string());
+                }
+                if let Some(bugfrags) = diff.bugfrags.as_ref() {
+                    show_change(ui, language, "save-diff-bugfrags", bugfrags, |v| v.to_string());
+                }
+
+                for (folder_index, changes) in diff.folders.iter().flatten().enumerate() {
+                    if changes.is_empty() {
+                        continue;
+                    }
+                    ui.strong(i18n::LOCALES.lookup_with_args(
+                        language,
+                        "save-diff-folder",
+                        &std::collections::HashMap::from([("folder", (folder_index + 1).into())]),
+                    ));
+                    show_changes(ui, changes, |chip| {
+                        let name = chip_name(language, assets, chip.id);
+                        match state.chip_codes.and_then(|codes| codes.get(chip.code)) {
+                            Some(code) => format!("{} {}", name, *code as char),
+                            None => name,
+                        }
+                    });
+                }
+
+                if let Some(changes) = diff.navicust_parts.as_ref().filter(|changes| !changes.is_empty()) {
+                    ui.strong(i18n::LOCALES.lookup(language, "save-diff-navicust"));
+                    show_changes(ui, changes, |part| {
+                        assets
+                            .and_then(|assets| assets.navicust_part(part.id, part.variant))
+                            .map(|info| info.name())
+                            .unwrap_or_else(|| format!("#{}:{}", part.id, part.variant))
+                    });
+                }
+
+                if let Some(changes) = diff.library.as_ref().filter(|changes| !changes.is_empty()) {
+                    ui.strong(i18n::LOCALES.lookup(language, "save-diff-library"));
+                    show_changes(ui, changes, |id| chip_name(language, assets, *id));
+                }
+            });
+        });
+
+    if !open {
+        *show_save_diff = None;
+    }
+}
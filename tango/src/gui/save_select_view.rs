@@ -1,15 +1,544 @@
-use fluent_templates::Loader;
+use chrono_locale::LocaleDate;
 
-use crate::{game, gui, i18n, net, patch, rom, save};
+use crate::{fs, game, gui, i18n, net, patch, rom, save, scanner};
+
+/// How long a save moved to the trash can still be put back from here.
+const UNDO_TRASH_DURATION: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Characters that can't be in file names on at least one platform.
+const ILLEGAL_NAME_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// A save being renamed in place in the list.
+struct Renaming {
+    path: std::path::PathBuf,
+    /// The new name, without the extension, which is kept as it is.
+    name: String,
+    request_focus: bool,
+}
+
+/// A save that was moved to the trash, kept so it can be put back where it was.
+struct Trashed {
+    path: std::path::PathBuf,
+    contents: Vec<u8>,
+    saves: Vec<(&'static (dyn game::Game + Send + Sync), save::ScannedSave)>,
+    trashed_at: std::time::Instant,
+}
+
+enum FileAction {
+    Duplicate(&'static (dyn game::Game + Send + Sync), std::path::PathBuf),
+    StartRename(std::path::PathBuf),
+    Rename {
+        from: std::path::PathBuf,
+        to: std::path::PathBuf,
+    },
+    Trash(std::path::PathBuf),
+    Reveal(std::path::PathBuf),
+    Undo,
+}
+
+#[derive(Debug)]
+enum NameError {
+    Empty,
+    IllegalChar(char),
+    Exists,
+}
+
+impl NameError {
+    fn description(&self, language: &unic_langid::LanguageIdentifier) -> String {
+        match self {
+            NameError::Empty => i18n::LOCALES.lookup(language, "select-save-rename-error-empty"),
+            NameError::IllegalChar(c) => i18n::LOCALES.lookup_with_args(
+                language,
+                "select-save-rename-error-illegal-char",
+                &std::collections::HashMap::from([("char", c.to_string().into())]),
+            ),
+            NameError::Exists => i18n::LOCALES.lookup(language, "select-save-rename-error-exists"),
+        }
+    }
+}
+
+fn file_stem(path: &std::path::Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Swaps out a path's file name, keeping its extension.
+fn with_stem(path: &std::path::Path, stem: &str) -> std::path::PathBuf {
+    let mut file_name = std::ffi::OsString::from(stem);
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    path.with_file_name(file_name)
+}
+
+/// Checks a new name for a save, giving where the save would be moved to.
+fn validate_new_name(path: &std::path::Path, name: &str) -> Result<std::path::PathBuf, NameError> {
+    if name.trim().is_empty() || name == "." || name == ".." {
+        return Err(NameError::Empty);
+    }
+    if let Some(c) = name.chars().find(|c| ILLEGAL_NAME_CHARS.contains(c) || c.is_control()) {
+        return Err(NameError::IllegalChar(c));
+    }
+    // Windows quietly drops these from the end of file names.
+    if let Some(c) = name.chars().last().filter(|c| *c == '.' || *c == ' ') {
+        return Err(NameError::IllegalChar(c));
+    }
+    let new_path = with_stem(path, name);
+    // Changing only the case is fine, even where the file system would say the new name already exists.
+    if new_path.to_string_lossy().to_lowercase() != path.to_string_lossy().to_lowercase()
+        && fs::metadata(&new_path).is_ok()
+    {
+        return Err(NameError::Exists);
+    }
+    Ok(new_path)
+}
+
+/// Finds a name for a copy of a save that isn't taken yet.
+fn duplicate_path(path: &std::path::Path) -> std::path::PathBuf {
+    let stem = file_stem(path);
+    (1..)
+        .map(|n| {
+            with_stem(
+                path,
+                &if n == 1 {
+                    format!("{} (copy)", stem)
+                } else {
+                    format!("{} (copy {})", stem, n)
+                },
+            )
+        })
+        .find(|path| fs::metadata(path).is_err())
+        .unwrap()
+}
+
+/// How many of a save's most recent matches are listed in its history.
+const MAX_HISTORY_MATCHES: usize = 10;
+
+fn format_time(language: &unic_langid::LanguageIdentifier, time: std::time::SystemTime) -> String {
+    chrono::DateTime::<chrono::Local>::from(time)
+        .formatl("%c", &language.to_string())
+        .to_string()
+}
+
+/// Shows which matches and replays a save has been used in.
+fn show_history(ui: &mut egui::Ui, language: &unic_langid::LanguageIdentifier, entry: &save::registry::Entry) {
+    if let Some(last_used_at) = entry.last_used_at {
+        ui.label(i18n::LOCALES.lookup_with_args(
+            language,
+            "select-save-history.last-used",
+            &std::collections::HashMap::from([("time", format_time(language, last_used_at).into())]),
+        ));
+    }
+    if !entry.patches.is_empty() {
+        ui.strong(i18n::LOCALES.lookup(language, "select-save-history.patches"));
+        for (name, version) in entry.patches.iter() {
+            ui.label(format!("{} v{}", name, version));
+        }
+    }
+    if !entry.matches.is_empty() {
+        ui.strong(i18n::LOCALES.lookup(language, "select-save-history.matches"));
+        for record in entry.matches.iter().rev().take(MAX_HISTORY_MATCHES) {
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                "select-save-history.match",
+                &std::collections::HashMap::from([
+                    ("time", format_time(language, record.played_at).into()),
+                    ("nickname", record.opponent_nickname.clone().into()),
+                    ("link_code", record.link_code.clone().into()),
+                ]),
+            ));
+        }
+        if entry.matches.len() > MAX_HISTORY_MATCHES {
+            ui.weak(i18n::LOCALES.lookup_with_args(
+                language,
+                "select-save-history.more-matches",
+                &std::collections::HashMap::from([("count", (entry.matches.len() - MAX_HISTORY_MATCHES).into())]),
+            ));
+        }
+    }
+    if !entry.replays.is_empty() {
+        ui.label(i18n::LOCALES.lookup_with_args(
+            language,
+            "select-save-history.replays",
+            &std::collections::HashMap::from([("count", entry.replays.len().into())]),
+        ));
+    }
+}
+
+/// Shows what can be done with a save file. Returns what was picked, if anything.
+fn show_file_actions(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    game: &'static (dyn game::Game + Send + Sync),
+    path: &std::path::Path,
+    enabled: bool,
+) -> Option<FileAction> {
+    let mut action = None;
+    if ui
+        .add_enabled(
+            enabled,
+            egui::Button::new(i18n::LOCALES.lookup(language, "select-save.duplicate")),
+        )
+        .clicked()
+    {
+        action = Some(FileAction::Duplicate(game, path.to_path_buf()));
+    }
+    if ui
+        .add_enabled(
+            enabled,
+            egui::Button::new(i18n::LOCALES.lookup(language, "select-save.rename")),
+        )
+        .clicked()
+    {
+        action = Some(FileAction::StartRename(path.to_path_buf()));
+    }
+    if ui
+        .add_enabled(
+            enabled,
+            egui::Button::new(format!("🗑 {}", i18n::LOCALES.lookup(language, "select-save.trash"))),
+        )
+        .clicked()
+    {
+        action = Some(FileAction::Trash(path.to_path_buf()));
+    }
+    if ui
+        .button(format!("📂 {}", i18n::LOCALES.lookup(language, "select-save.reveal")))
+        .clicked()
+    {
+        action = Some(FileAction::Reveal(path.to_path_buf()));
+    }
+    action
+}
+
+/// Carries out a change to the save files, updating the scanned saves to match rather than scanning them all again.
+fn apply_file_action(
+    action: FileAction,
+    state: &mut State,
+    selection: &mut Option<gui::Selection>,
+    saves_scanner: &save::Scanner,
+) -> std::io::Result<()> {
+    match action {
+        FileAction::Duplicate(game, path) => {
+            let new_path = duplicate_path(&path);
+            fs::copy(&path, &new_path)?;
+            let copies = saves_scanner
+                .read()
+                .find(&path)
+                .into_iter()
+                .map(|(game, save)| {
+                    (
+                        game,
+                        save::ScannedSave {
+                            path: new_path.clone(),
+                            modified: Some(std::time::SystemTime::now()),
+                            ..save
+                        },
+                    )
+                })
+                .collect();
+            saves_scanner.update(|saves| saves.insert(copies));
+            state.highlighted = Some((game, new_path.clone()));
+            state.scroll_to_highlighted = true;
+            state.renaming = Some(Renaming {
+                name: file_stem(&new_path),
+                path: new_path,
+                request_focus: true,
+            });
+        }
+        FileAction::StartRename(path) => {
+            state.renaming = Some(Renaming {
+                name: file_stem(&path),
+                path,
+                request_focus: true,
+            });
+        }
+        FileAction::Rename { from, to } => {
+            fs::rename(&from, &to)?;
+            saves_scanner.update(|saves| saves.rename(&from, &to));
+            if let Some(selection) = selection.as_mut().filter(|selection| selection.save.path == from) {
+                selection.save.path = to.clone();
+            }
+            for (_, path) in state.highlighted.iter_mut().chain(state.compare_from.iter_mut()) {
+                if *path == from {
+                    *path = to.clone();
+                }
+            }
+        }
+        FileAction::Trash(path) => {
+            let contents = fs::read(&path)?;
+            trash::delete(&path)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{}: {}", path.display(), e)))?;
+            let mut saves = vec![];
+            saves_scanner.update(|scanned| saves = scanned.remove(&path));
+            if selection
+                .as_ref()
+                .map(|selection| selection.save.path == path)
+                .unwrap_or(false)
+            {
+                *selection = None;
+            }
+            if state.highlighted.as_ref().map(|(_, p)| *p == path).unwrap_or(false) {
+                state.highlighted = None;
+            }
+            if state.compare_from.as_ref().map(|(_, p)| *p == path).unwrap_or(false) {
+                state.compare_from = None;
+            }
+            state.trashed = Some(Trashed {
+                path,
+                contents,
+                saves,
+                trashed_at: std::time::Instant::now(),
+            });
+        }
+        FileAction::Reveal(path) => {
+            let _ = open::that(path.parent().unwrap_or(&path));
+        }
+        FileAction::Undo => {
+            if let Some(trashed) = state.trashed.take() {
+                // The trashed copy is left where it is: putting it back is just writing it out again.
+                if fs::metadata(&trashed.path).is_ok() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        format!("{}: already exists", trashed.path.display()),
+                    ));
+                }
+                save::write_atomically(&trashed.path, &trashed.contents)?;
+                saves_scanner.update(|saves| saves.insert(trashed.saves));
+            }
+        }
+    }
+    Ok(())
+}
 
 pub struct State {
-    selection: Option<(&'static (dyn game::Game + Send + Sync), Option<std::path::PathBuf>)>,
+    selected_game: Option<&'static (dyn game::Game + Send + Sync)>,
+    highlighted: Option<(&'static (dyn game::Game + Send + Sync), std::path::PathBuf)>,
+    scroll_to_highlighted: bool,
+    search: String,
+    assets:
+        std::collections::HashMap<&'static (dyn game::Game + Send + Sync), Option<Box<dyn rom::Assets + Send + Sync>>>,
+    /// The save picked to compare from, while waiting for the save to compare it with.
+    compare_from: Option<(&'static (dyn game::Game + Send + Sync), std::path::PathBuf)>,
+    save_diff: Option<gui::save_diff_window::State>,
+    renaming: Option<Renaming>,
+    trashed: Option<Trashed>,
+    file_error: Option<String>,
 }
 
 impl State {
     pub fn new(selection: Option<(&'static (dyn game::Game + Send + Sync), Option<std::path::PathBuf>)>) -> Self {
-        Self { selection }
+        Self {
+            selected_game: selection.as_ref().map(|(game, _)| *game),
+            highlighted: selection.and_then(|(game, path)| path.map(|path| (game, path))),
+            scroll_to_highlighted: true,
+            search: "".to_string(),
+            assets: std::collections::HashMap::new(),
+            compare_from: None,
+            save_diff: None,
+            renaming: None,
+            trashed: None,
+            file_error: None,
+        }
+    }
+
+    /// Gets the assets for a game.
+    ///
+    /// Assets are loaded once per game, from whichever save asks first: nothing they're used for here depends on save data.
+    fn assets(
+        &mut self,
+        game: &'static (dyn game::Game + Send + Sync),
+        save: &save::ScannedSave,
+        rom: Option<&std::sync::Arc<[u8]>>,
+    ) -> Option<&(dyn rom::Assets + Send + Sync)> {
+        self.assets
+            .entry(game)
+            .or_insert_with(|| {
+                rom.and_then(|rom| {
+                    game.load_rom_assets(rom, save.save.as_raw_wram(), &Default::default())
+                        .ok()
+                })
+            })
+            .as_deref()
+    }
+
+    /// Looks up the name of the navi in a save, if the game tracks one.
+    fn navi_name(
+        &mut self,
+        game: &'static (dyn game::Game + Send + Sync),
+        save: &save::ScannedSave,
+        rom: Option<&std::sync::Arc<[u8]>>,
+    ) -> Option<String> {
+        let navi = save.save.view_navi()?.navi();
+        Some(self.assets(game, save, rom)?.navi(navi)?.name())
+    }
+}
+
+fn get_warning(
+    game: &'static (dyn game::Game + Send + Sync),
+    remote_settings: Option<&net::protocol::Settings>,
+    patches: &std::collections::BTreeMap<String, patch::Patch>,
+) -> Option<gui::play_pane::Warning> {
+    let remote_settings = remote_settings?;
+
+    if !remote_settings
+        .available_games
+        .iter()
+        .any(|(family, variant)| game.family_and_variant() == (family, *variant))
+    {
+        return Some(gui::play_pane::Warning::NoRemoteROM(game));
+    }
+
+    let remote_gi = remote_settings.game_info.as_ref()?;
+
+    if let Some(netplay_compatibility) = gui::play_pane::get_netplay_compatibility_from_game_info(remote_gi, patches) {
+        if netplay_compatibility != game.family_and_variant().0
+            && !patches.values().any(|metadata| {
+                metadata.versions.values().any(|version| {
+                    version.supported_games.contains(&game) && version.netplay_compatibility == netplay_compatibility
+                })
+            })
+        {
+            return Some(gui::play_pane::Warning::Incompatible);
+        }
     }
+    None
+}
+
+fn display_path<'a>(path: &'a std::path::Path, saves_path: &std::path::Path) -> std::path::Display<'a> {
+    path.strip_prefix(saves_path).unwrap_or(path).display()
+}
+
+/// Which scanned directory duplicates were found in.
+#[derive(Clone, Copy)]
+enum DuplicatesKind {
+    Roms,
+    Saves,
+}
+
+/// Lists files that are copies of one another, returning whether moving the extra copies away was asked for.
+fn show_duplicates(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    kind: DuplicatesKind,
+    duplicates: &[scanner::Duplicates],
+    root: &std::path::Path,
+    can_move: bool,
+) -> bool {
+    if duplicates.is_empty() {
+        return false;
+    }
+
+    let (label, id) = match kind {
+        DuplicatesKind::Roms => ("select-save.duplicate-roms", "select-save-duplicate-roms"),
+        DuplicatesKind::Saves => ("select-save.duplicate-saves", "select-save-duplicate-saves"),
+    };
+    let mut clicked = false;
+    egui::CollapsingHeader::new(
+        egui::RichText::new(format!(
+            "⚠️ {} ({})",
+            i18n::LOCALES.lookup(language, label),
+            duplicates
+                .iter()
+                .map(|duplicates| duplicates.extras.len())
+                .sum::<usize>()
+        ))
+        .color(gui::theme::colors(ui.ctx()).warning),
+    )
+    .id_source(id)
+    .default_open(false)
+    .show(ui, |ui| {
+        for duplicates in duplicates {
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                "select-save.duplicates-canonical",
+                &std::collections::HashMap::from([(
+                    "path",
+                    display_path(&duplicates.canonical, root).to_string().into(),
+                )]),
+            ));
+            for extra in duplicates.extras.iter() {
+                ui.label(
+                    egui::RichText::new(display_path(extra, root).to_string())
+                        .small()
+                        .weak(),
+                );
+            }
+        }
+        if ui
+            .add_enabled(
+                can_move,
+                egui::Button::new(i18n::LOCALES.lookup_with_args(
+                    language,
+                    "select-save.duplicates-move",
+                    &std::collections::HashMap::from([("dir", scanner::DUPLICATES_DIR.into())]),
+                )),
+            )
+            .clicked()
+        {
+            clicked = true;
+        }
+    });
+    clicked
+}
+
+/// Moves the extra copies of duplicated files away, then rescans so they stop being found.
+fn move_duplicates(
+    ctx: &egui::Context,
+    kind: DuplicatesKind,
+    duplicates: &[scanner::Duplicates],
+    state: &mut State,
+    selection: &mut Option<gui::Selection>,
+    roms_path: &std::path::Path,
+    saves_path: &std::path::Path,
+    roms_scanner: &rom::Scanner,
+    saves_scanner: &save::Scanner,
+) -> std::io::Result<()> {
+    let r = match kind {
+        DuplicatesKind::Roms => scanner::move_duplicates(roms_path, duplicates),
+        DuplicatesKind::Saves => {
+            let r = scanner::move_duplicates(saves_path, duplicates);
+            // A moved save has the same contents as the one that was kept, so anything pointing at it can point at that instead.
+            for duplicates in duplicates {
+                if let Some(selection) = selection
+                    .as_mut()
+                    .filter(|selection| duplicates.extras.contains(&selection.save.path))
+                {
+                    selection.save.path = duplicates.canonical.clone();
+                }
+                for (_, path) in state.highlighted.iter_mut().chain(state.compare_from.iter_mut()) {
+                    if duplicates.extras.contains(path) {
+                        *path = duplicates.canonical.clone();
+                    }
+                }
+            }
+            r
+        }
+    };
+
+    // Some files may have been moved even if others couldn't be, so rescan either way.
+    tokio::task::spawn_blocking({
+        let roms_scanner = roms_scanner.clone();
+        let saves_scanner = saves_scanner.clone();
+        let roms_path = roms_path.to_path_buf();
+        let saves_path = saves_path.to_path_buf();
+        let egui_ctx = ctx.clone();
+        move || {
+            match kind {
+                DuplicatesKind::Roms => {
+                    roms_scanner
+                        .rescan_progressively(move |progress| Some(game::scan_roms(&roms_path, false, progress)));
+                }
+                DuplicatesKind::Saves => {
+                    saves_scanner
+                        .rescan_progressively(move |progress| Some(save::scan_saves(&saves_path, false, progress)));
+                }
+            }
+            egui_ctx.request_repaint();
+        }
+    });
+    r
 }
 
 pub fn show(
@@ -18,197 +547,585 @@ pub fn show(
     selection: &mut Option<gui::Selection>,
     language: &unic_langid::LanguageIdentifier,
     saves_path: &std::path::Path,
+    roms_path: &std::path::Path,
     roms_scanner: rom::Scanner,
     saves_scanner: save::Scanner,
     patches_scanner: patch::Scanner,
     remote_settings: Option<&net::protocol::Settings>,
+    locked_save: Option<&std::path::Path>,
+    game_art: &mut gui::save_view::texture_cache::TextureCache,
+    save_registry: &save::registry::Registry,
 ) {
+    let is_scanning = roms_scanner.is_scanning() || saves_scanner.is_scanning();
+    if is_scanning {
+        // Scans publish partial results as they go, so keep repainting until they're done.
+        ui.ctx().request_repaint_after(std::time::Duration::from_millis(100));
+    }
+
     let roms = roms_scanner.read();
     let saves = saves_scanner.read();
     let patches = patches_scanner.read();
 
+    let state = show.as_mut().unwrap();
+    let games = game::sorted_all_games(language);
+
+    let mut chosen = None;
+    let mut file_action = None;
+    let mut duplicates_action = None;
+    // Files aren't touched mid-scan, as the scan's results would put back what was changed.
+    let can_change = |path: &std::path::Path| !is_scanning && locked_save != Some(path);
+
     ui.vertical(|ui| {
-        let games = game::sorted_all_games(language);
-        ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
-            if ui
-                .button(format!(
-                    "📂 {}",
-                    i18n::LOCALES.lookup(language, "select-save.open-folder").unwrap(),
-                ))
-                .clicked()
-            {
-                let _ = open::that(
-                    if let Some(path) = selection.as_ref().and_then(|selection| selection.save.path.parent()) {
-                        path
-                    } else {
-                        saves_path
-                    },
-                );
-            }
+        let search_resp = ui
+            .with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                if ui
+                    .button(format!(
+                        "📂 {}",
+                        i18n::LOCALES.lookup(language, "select-save.open-folder"),
+                    ))
+                    .clicked()
+                {
+                    let _ = open::that(
+                        if let Some(path) = selection.as_ref().and_then(|selection| selection.save.path.parent()) {
+                            path
+                        } else {
+                            saves_path
+                        },
+                    );
+                }
 
-            if let Some((game, _)) = show.as_mut().unwrap().selection {
-                let (family, variant) = game.family_and_variant();
-                ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
-                    ui.horizontal(|ui| {
-                        ui.with_layout(
-                            egui::Layout::left_to_right(egui::Align::Max).with_main_wrap(true),
-                            |ui| {
-                                ui.label(
-                                    i18n::LOCALES
-                                        .lookup(language, &format!("game-{}.variant-{}", family, variant))
-                                        .unwrap(),
-                                );
-                            },
-                        );
+                if ui
+                    .add_enabled(
+                        !is_scanning,
+                        egui::Button::new(format!("🔄 {}", i18n::LOCALES.lookup(language, "select-save.rescan"),)),
+                    )
+                    .clicked()
+                {
+                    tokio::task::spawn_blocking({
+                        let roms_scanner = roms_scanner.clone();
+                        let saves_scanner = saves_scanner.clone();
+                        let roms_path = roms_path.to_path_buf();
+                        let saves_path = saves_path.to_path_buf();
+                        let egui_ctx = ui.ctx().clone();
+                        move || {
+                            roms_scanner.rescan_progressively(move |progress| {
+                                Some(game::scan_roms(&roms_path, true, progress))
+                            });
+                            saves_scanner.rescan_progressively(move |progress| {
+                                Some(save::scan_saves(&saves_path, true, progress))
+                            });
+                            egui_ctx.request_repaint();
+                        }
                     });
+                }
+
+                if is_scanning {
+                    ui.spinner();
+                }
+
+                ui.add(
+                    egui::TextEdit::singleline(&mut state.search)
+                        .hint_text(format!("🔍 {}", i18n::LOCALES.lookup(language, "select-save.search")))
+                        .desired_width(f32::INFINITY),
+                )
+            })
+            .inner;
+
+        if let Some((_, compare_from_path)) = state.compare_from.clone() {
+            ui.horizontal(|ui| {
+                ui.label(i18n::LOCALES.lookup_with_args(
+                    language,
+                    "save-diff.pick",
+                    &std::collections::HashMap::from([(
+                        "path",
+                        display_path(&compare_from_path, saves_path).to_string().into(),
+                    )]),
+                ));
+                if ui.button(i18n::LOCALES.lookup(language, "save-diff.cancel")).clicked() {
+                    state.compare_from = None;
+                }
+            });
+        }
+
+        if let Some(trashed) = state.trashed.as_ref() {
+            let elapsed = trashed.trashed_at.elapsed();
+            if elapsed < UNDO_TRASH_DURATION {
+                ui.horizontal(|ui| {
+                    ui.label(i18n::LOCALES.lookup_with_args(
+                        language,
+                        "select-save.trashed",
+                        &std::collections::HashMap::from([(
+                            "path",
+                            display_path(&trashed.path, saves_path).to_string().into(),
+                        )]),
+                    ));
+                    if ui.button(i18n::LOCALES.lookup(language, "select-save.undo")).clicked() {
+                        file_action = Some(FileAction::Undo);
+                    }
                 });
+                ui.ctx().request_repaint_after(UNDO_TRASH_DURATION - elapsed);
+            } else {
+                state.trashed = None;
             }
-        });
+        }
 
-        ui.group(|ui| {
-            egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
-                ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
-                    if let Some((game, _)) = show.as_ref().unwrap().selection.clone() {
-                        if ui
-                            .selectable_label(
-                                false,
-                                format!(
-                                    "⬅️ {}",
-                                    i18n::LOCALES
-                                        .lookup(language, "select-save.return-to-games-list")
-                                        .unwrap()
-                                ),
-                            )
-                            .clicked()
-                        {
-                            show.as_mut().unwrap().selection = None;
-                        }
+        if let Some(error) = state.file_error.as_ref() {
+            ui.colored_label(
+                gui::theme::colors(ui.ctx()).warning,
+                i18n::LOCALES.lookup_with_args(
+                    language,
+                    "select-save.error",
+                    &std::collections::HashMap::from([("error", error.as_str().into())]),
+                ),
+            );
+        }
 
-                        if let Some(saves) = saves.get(&game) {
-                            for save in saves {
-                                let selected = selection
-                                    .as_ref()
-                                    .map(|selection| selection.save.path.as_path() == save.path.as_path())
-                                    .unwrap_or(false);
-                                let mut layout_job = egui::text::LayoutJob::default();
-                                layout_job.append(
-                                    &format!(
-                                        "{}",
-                                        save.path
-                                            .as_path()
-                                            .strip_prefix(saves_path)
-                                            .unwrap_or(save.path.as_path())
-                                            .display()
-                                    ),
-                                    0.0,
-                                    egui::TextFormat::simple(
-                                        ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
-                                        if selected {
+        if let Some((game, path)) = state.highlighted.clone() {
+            ui.horizontal(|ui| {
+                if let Some(action) = show_file_actions(ui, language, game, &path, can_change(&path)) {
+                    file_action = Some(action);
+                }
+                if locked_save == Some(path.as_path()) {
+                    ui.weak(i18n::LOCALES.lookup(language, "select-save.in-use"));
+                }
+            });
+        }
+
+        let search = state.search.to_lowercase();
+        let entries = if search.is_empty() {
+            state
+                .selected_game
+                .and_then(|game| saves.by_game.get(&game).map(|saves| (game, saves)))
+                .map(|(game, saves)| saves.iter().map(|save| (game, save)).collect::<Vec<_>>())
+                .unwrap_or_default()
+        } else {
+            games
+                .iter()
+                .flat_map(|game| {
+                    saves
+                        .by_game
+                        .get(game)
+                        .into_iter()
+                        .flatten()
+                        .map(move |save| (*game, save))
+                })
+                .filter(|(_, save)| {
+                    display_path(&save.path, saves_path)
+                        .to_string()
+                        .to_lowercase()
+                        .contains(&search)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Arrow keys and enter move through and pick from the saves list, unless something other than the search box is being typed in.
+        let keyboard_enabled = ui.memory().focus().map(|id| id == search_resp.id).unwrap_or(true);
+        if keyboard_enabled && !entries.is_empty() {
+            let (up, down, enter) = {
+                let input = ui.input();
+                (
+                    input.key_pressed(egui::Key::ArrowUp),
+                    input.key_pressed(egui::Key::ArrowDown),
+                    input.key_pressed(egui::Key::Enter),
+                )
+            };
+
+            let position = state
+                .highlighted
+                .as_ref()
+                .and_then(|(highlighted_game, highlighted_path)| {
+                    entries
+                        .iter()
+                        .position(|(game, save)| game == highlighted_game && &save.path == highlighted_path)
+                });
+
+            let new_position = if down {
+                Some(position.map(|p| (p + 1).min(entries.len() - 1)).unwrap_or(0))
+            } else if up {
+                Some(position.map(|p| p.saturating_sub(1)).unwrap_or(0))
+            } else {
+                None
+            };
+
+            if let Some(new_position) = new_position {
+                let (game, save) = entries[new_position];
+                state.highlighted = Some((game, save.path.clone()));
+                state.scroll_to_highlighted = true;
+            } else if enter {
+                if let Some((game, save)) = position.map(|p| entries[p]) {
+                    if roms.by_game.contains_key(&game) {
+                        chosen = Some((game, save.clone()));
+                    }
+                }
+            }
+        }
+
+        egui_extras::StripBuilder::new(ui)
+            .size(egui_extras::Size::exact(200.0))
+            .size(egui_extras::Size::remainder())
+            .horizontal(|mut strip| {
+                strip.cell(|ui| {
+                    ui.group(|ui| {
+                        egui::ScrollArea::vertical()
+                            .id_source("select-save-games")
+                            .auto_shrink([false, false])
+                            .show(ui, |ui| {
+                                ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
+                                    for (available, game) in games
+                                        .iter()
+                                        .filter(|g| roms.by_game.contains_key(*g))
+                                        .map(|g| (true, g))
+                                        .chain(
+                                            games
+                                                .iter()
+                                                .filter(|g| !roms.by_game.contains_key(*g))
+                                                .map(|g| (false, g)),
+                                        )
+                                    {
+                                        let (family, variant) = game.family_and_variant();
+
+                                        let selected = search.is_empty() && state.selected_game == Some(*game);
+                                        let warning = get_warning(*game, remote_settings, &patches);
+
+                                        let mut layout_job = egui::text::LayoutJob::default();
+                                        if warning.is_some() {
+                                            gui::warning::append_to_layout_job(ui, &mut layout_job);
+                                        }
+                                        let text_color = if selected {
                                             ui.visuals().selection.stroke.color
                                         } else {
                                             ui.visuals().text_color()
-                                        },
-                                    ),
-                                );
-                                if ui.selectable_label(selected, layout_job).clicked() {
-                                    let (game, rom, patch) = if let Some(selection) = selection.take() {
-                                        if selection.game == game {
-                                            (selection.game, selection.rom, selection.patch)
-                                        } else {
-                                            (game, roms.get(&game).unwrap().clone(), None)
+                                        };
+                                        layout_job.append(
+                                            &i18n::LOCALES
+                                                .lookup(language, &format!("game-{}.variant-{}", family, variant)),
+                                            0.0,
+                                            egui::TextFormat::simple(
+                                                ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
+                                                text_color,
+                                            ),
+                                        );
+                                        layout_job.append(
+                                            &format!(
+                                                " ({})",
+                                                saves.by_game.get(game).map(|saves| saves.len()).unwrap_or(0)
+                                            ),
+                                            0.0,
+                                            egui::TextFormat::simple(
+                                                ui.style().text_styles.get(&egui::TextStyle::Small).unwrap().clone(),
+                                                text_color,
+                                            ),
+                                        );
+
+                                        let mut resp = ui
+                                            .horizontal(|ui| {
+                                                gui::game_art::show(
+                                                    ui,
+                                                    game_art,
+                                                    language,
+                                                    *game,
+                                                    game::ArtKind::Icon,
+                                                    None,
+                                                    gui::game_art::icon_size(
+                                                        ui.text_style_height(&egui::TextStyle::Body),
+                                                    ),
+                                                );
+                                                ui.with_layout(
+                                                    egui::Layout::top_down_justified(egui::Align::LEFT),
+                                                    |ui| {
+                                                        ui.add_enabled(
+                                                            available,
+                                                            egui::SelectableLabel::new(selected, layout_job),
+                                                        )
+                                                    },
+                                                )
+                                                .inner
+                                            })
+                                            .inner;
+                                        if let Some(warning) = warning {
+                                            resp = resp.on_hover_text(warning.description(language));
                                         }
-                                    } else {
-                                        (game, roms.get(&game).unwrap().clone(), None)
-                                    };
-
-                                    *show = None;
-                                    *selection = Some(gui::Selection::new(game, save.clone(), patch, rom));
-                                }
-                            }
-                        }
-                    } else {
-                        for (available, game) in games
-                            .iter()
-                            .filter(|g| roms.contains_key(*g))
-                            .map(|g| (true, g))
-                            .chain(games.iter().filter(|g| !roms.contains_key(*g)).map(|g| (false, g)))
-                        {
-                            let (family, variant) = game.family_and_variant();
-
-                            let selected = selection
-                                .as_ref()
-                                .map(|selection| selection.game == *game)
-                                .unwrap_or(false);
-
-                            let warning = (|| {
-                                let remote_settings = if let Some(remote_settings) = remote_settings.as_ref() {
-                                    remote_settings
-                                } else {
-                                    return None;
-                                };
-
-                                if !remote_settings
-                                    .available_games
-                                    .iter()
-                                    .any(|(family, variant)| game.family_and_variant() == (family, *variant))
-                                {
-                                    return Some(gui::play_pane::Warning::NoRemoteROM(*game));
-                                }
-
-                                let remote_gi = if let Some(remote_gi) = remote_settings.game_info.as_ref() {
-                                    remote_gi
-                                } else {
-                                    return None;
-                                };
-
-                                if let Some(netplay_compatibility) =
-                                    gui::play_pane::get_netplay_compatibility_from_game_info(remote_gi, &patches)
-                                {
-                                    if &netplay_compatibility != family
-                                        && !patches.values().any(|metadata| {
-                                            metadata.versions.values().any(|version| {
-                                                version.supported_games.contains(game)
-                                                    && version.netplay_compatibility == netplay_compatibility
+
+                                        if resp.clicked() {
+                                            state.selected_game = Some(*game);
+                                            state.search.clear();
+                                        }
+                                    }
+                                });
+                            });
+                    });
+                });
+
+                strip.cell(|ui| {
+                    ui.group(|ui| {
+                        egui::ScrollArea::vertical()
+                            .id_source("select-save-saves")
+                            .auto_shrink([false, false])
+                            .show(ui, |ui| {
+                                ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
+                                    if entries.is_empty() && (!search.is_empty() || state.selected_game.is_some()) {
+                                        ui.weak(i18n::LOCALES.lookup(language, "select-save.no-saves"));
+                                    }
+
+                                    let mut found_highlighted = false;
+                                    for (game, save) in entries.iter() {
+                                        if let Some(renaming) =
+                                            state.renaming.as_mut().filter(|renaming| renaming.path == save.path)
+                                        {
+                                            let new_path = validate_new_name(&save.path, &renaming.name);
+                                            let resp = ui.add(
+                                                egui::TextEdit::singleline(&mut renaming.name)
+                                                    .desired_width(f32::INFINITY),
+                                            );
+                                            if renaming.request_focus {
+                                                resp.request_focus();
+                                                renaming.request_focus = false;
+                                            }
+                                            if let Err(e) = new_path.as_ref() {
+                                                ui.colored_label(
+                                                    gui::theme::colors(ui.ctx()).warning,
+                                                    e.description(language),
+                                                );
+                                            }
+                                            if resp.lost_focus() {
+                                                if !ui.input().key_pressed(egui::Key::Enter) {
+                                                    // Anything but enter, e.g. escape or clicking elsewhere, leaves the name as it was.
+                                                    state.renaming = None;
+                                                } else if let Ok(new_path) = new_path {
+                                                    if new_path != save.path {
+                                                        file_action = Some(FileAction::Rename {
+                                                            from: save.path.clone(),
+                                                            to: new_path,
+                                                        });
+                                                    }
+                                                    state.renaming = None;
+                                                } else {
+                                                    renaming.request_focus = true;
+                                                }
+                                            }
+                                            continue;
+                                        }
+
+                                        let (family, variant) = game.family_and_variant();
+                                        let highlighted = state
+                                            .highlighted
+                                            .as_ref()
+                                            .map(|(highlighted_game, highlighted_path)| {
+                                                highlighted_game == game && highlighted_path == &save.path
                                             })
+                                            .unwrap_or(false);
+
+                                        let (text_color, weak_text_color) = if highlighted {
+                                            (ui.visuals().selection.stroke.color, ui.visuals().selection.stroke.color)
+                                        } else {
+                                            (ui.visuals().text_color(), ui.visuals().weak_text_color())
+                                        };
+
+                                        let mut details = vec![i18n::LOCALES
+                                            .lookup(language, &format!("game-{}.variant-{}", family, variant))];
+                                        if let Some(navi_name) = state.navi_name(*game, save, roms.by_game.get(game)) {
+                                            details.push(navi_name);
+                                        }
+                                        if let Some(modified) = save.modified {
+                                            details.push(format_time(language, modified));
+                                        }
+                                        let history = save_registry.get(&save.path);
+                                        if let Some(history) =
+                                            history.as_ref().filter(|history| !history.matches.is_empty())
+                                        {
+                                            details.push(i18n::LOCALES.lookup_with_args(
+                                                language,
+                                                "select-save-history",
+                                                &std::collections::HashMap::from([(
+                                                    "matches",
+                                                    history.matches.len().into(),
+                                                )]),
+                                            ));
+                                        }
+
+                                        let mut layout_job = egui::text::LayoutJob::default();
+                                        layout_job.append(
+                                            &format!("{}\n", display_path(&save.path, saves_path)),
+                                            0.0,
+                                            egui::TextFormat::simple(
+                                                ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
+                                                text_color,
+                                            ),
+                                        );
+                                        layout_job.append(
+                                            &details.join(" · "),
+                                            0.0,
+                                            egui::TextFormat::simple(
+                                                ui.style().text_styles.get(&egui::TextStyle::Small).unwrap().clone(),
+                                                weak_text_color,
+                                            ),
+                                        );
+
+                                        let enabled = if let Some((compare_from_game, _)) = state.compare_from.as_ref()
+                                        {
+                                            compare_from_game == game
+                                        } else {
+                                            roms.by_game.contains_key(game)
+                                        };
+                                        let resp = ui
+                                            .add_enabled(enabled, egui::SelectableLabel::new(highlighted, layout_job))
+                                            .context_menu(|ui| {
+                                                if ui
+                                                    .button(i18n::LOCALES.lookup(language, "save-diff.compare-with"))
+                                                    .clicked()
+                                                {
+                                                    state.compare_from = Some((*game, save.path.clone()));
+                                                    ui.close_menu();
+                                                }
+                                                ui.separator();
+                                                if let Some(action) = show_file_actions(
+                                                    ui,
+                                                    language,
+                                                    *game,
+                                                    &save.path,
+                                                    can_change(&save.path),
+                                                ) {
+                                                    file_action = Some(action);
+                                                    ui.close_menu();
+                                                }
+                                            });
+                                        let resp = if let Some(history) = history.as_ref() {
+                                            resp.on_hover_ui(|ui| show_history(ui, language, history))
+                                        } else {
+                                            resp
+                                        };
+                                        if highlighted {
+                                            found_highlighted = true;
+                                            if state.scroll_to_highlighted {
+                                                resp.scroll_to_me(Some(egui::Align::Center));
+                                            }
+                                        }
+                                        if resp.clicked() {
+                                            if let Some((compare_from_game, compare_from_path)) =
+                                                state.compare_from.take()
+                                            {
+                                                if let Some(before) =
+                                                    saves.by_game.get(&compare_from_game).and_then(|saves| {
+                                                        saves.iter().find(|s| s.path == compare_from_path)
+                                                    })
+                                                {
+                                                    state.assets(*game, save, roms.by_game.get(game));
+                                                    state.save_diff =
+                                                        Some(gui::save_diff_window::State::new(*game, before, save));
+                                                }
+                                            } else {
+                                                chosen = Some((*game, (*save).clone()));
+                                            }
+                                        }
+                                    }
+
+                                    // The previous selection might not have been scanned yet, so keep trying to scroll to it until the scan is done.
+                                    if found_highlighted || !is_scanning {
+                                        state.scroll_to_highlighted = false;
+                                    }
+
+                                    let unrecognized = saves
+                                        .unrecognized
+                                        .iter()
+                                        .filter(|unrecognized| {
+                                            display_path(&unrecognized.path, saves_path)
+                                                .to_string()
+                                                .to_lowercase()
+                                                .contains(&search)
                                         })
-                                    {
-                                        return Some(gui::play_pane::Warning::Incompatible);
+                                        .collect::<Vec<_>>();
+                                    if !unrecognized.is_empty() {
+                                        egui::CollapsingHeader::new(format!(
+                                            "{} ({})",
+                                            i18n::LOCALES.lookup(language, "select-save.unrecognized"),
+                                            unrecognized.len()
+                                        ))
+                                        .id_source("select-save-unrecognized")
+                                        .default_open(false)
+                                        .show(ui, |ui| {
+                                            for unrecognized in unrecognized {
+                                                ui.label(display_path(&unrecognized.path, saves_path).to_string());
+                                                ui.label(egui::RichText::new(&unrecognized.error).small().weak());
+                                            }
+                                        });
                                     }
-                                }
-                                None
-                            })();
-
-                            let mut layout_job = egui::text::LayoutJob::default();
-                            if warning.is_some() {
-                                gui::warning::append_to_layout_job(ui, &mut layout_job);
-                            }
-                            layout_job.append(
-                                &i18n::LOCALES
-                                    .lookup(language, &format!("game-{}.variant-{}", family, variant))
-                                    .unwrap(),
-                                0.0,
-                                egui::TextFormat::simple(
-                                    ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
-                                    if selected {
-                                        ui.visuals().selection.stroke.color
-                                    } else {
-                                        ui.visuals().text_color()
-                                    },
-                                ),
-                            );
-
-                            let mut resp = ui.add_enabled(available, egui::SelectableLabel::new(selected, layout_job));
-                            if let Some(warning) = warning {
-                                resp = resp.on_hover_text(warning.description(language));
-                            }
-
-                            if resp.clicked() {
-                                show.as_mut().unwrap().selection = Some((*game, None));
-                            }
-                        }
-                    }
+
+                                    if show_duplicates(
+                                        ui,
+                                        language,
+                                        DuplicatesKind::Roms,
+                                        &roms.duplicates,
+                                        roms_path,
+                                        !is_scanning,
+                                    ) {
+                                        duplicates_action = Some((DuplicatesKind::Roms, roms.duplicates.clone()));
+                                    }
+                                    if show_duplicates(
+                                        ui,
+                                        language,
+                                        DuplicatesKind::Saves,
+                                        &saves.duplicates,
+                                        saves_path,
+                                        !is_scanning
+                                            && saves.duplicates.iter().all(|duplicates| {
+                                                duplicates.extras.iter().all(|path| can_change(path))
+                                            }),
+                                    ) {
+                                        duplicates_action = Some((DuplicatesKind::Saves, saves.duplicates.clone()));
+                                    }
+                                });
+                            });
+                    });
                 });
             });
-        });
     });
+
+    let save_diff_assets = state
+        .save_diff
+        .as_ref()
+        .and_then(|save_diff| state.assets.get(&save_diff.game()))
+        .and_then(|assets| assets.as_deref());
+    gui::save_diff_window::show(ui.ctx(), &mut state.save_diff, language, saves_path, save_diff_assets);
+
+    drop(saves);
+    if let Some(action) = file_action {
+        let r = apply_file_action(action, state, selection, &saves_scanner);
+        state.file_error = r.err().map(|e| {
+            log::error!("failed to change save files: {:?}", e);
+            e.to_string()
+        });
+    }
+
+    if let Some((kind, duplicates)) = duplicates_action {
+        let r = move_duplicates(
+            ui.ctx(),
+            kind,
+            &duplicates,
+            state,
+            selection,
+            roms_path,
+            saves_path,
+            &roms_scanner,
+            &saves_scanner,
+        );
+        state.file_error = r.err().map(|e| {
+            log::error!("failed to move duplicates: {:?}", e);
+            e.to_string()
+        });
+    }
+
+    if let Some((game, save)) = chosen {
+        let (game, rom, patch) = if let Some(selection) = selection.take() {
+            if selection.game == game {
+                (selection.game, selection.rom, selection.patch)
+            } else {
+                (game, roms.by_game.get(&game).unwrap().clone(), None)
+            }
+        } else {
+            (game, roms.by_game.get(&game).unwrap().clone(), None)
+        };
+
+        *show = None;
+        *selection = Some(gui::Selection::new(game, save, patch, rom));
+    }
 }
@@ -3,10 +3,9 @@ mod folder_view;
 mod modcards_view;
 mod navi_view;
 mod navicust_view;
+pub mod texture_cache;
 
-use fluent_templates::Loader;
-
-use crate::{gui, i18n, rom, save};
+use crate::{gui, i18n, rom, save, sharecode};
 
 #[derive(PartialEq, Clone)]
 enum Tab {
@@ -24,6 +23,8 @@ pub struct State {
     folder_view: folder_view::State,
     modcards_view: modcards_view::State,
     dark_ai_view: dark_ai_view::State,
+    /// Textures for chips and elements, shared between the views that show them.
+    texture_cache: texture_cache::TextureCache,
 }
 
 impl State {
@@ -35,9 +36,74 @@ impl State {
             folder_view: folder_view::State::new(),
             modcards_view: modcards_view::State::new(),
             dark_ai_view: dark_ai_view::State::new(),
+            texture_cache: texture_cache::TextureCache::new(),
         }
     }
+
+    /// Drops the textures made from the assets being shown, for when the assets have been reloaded and may look different.
+    pub fn clear_texture_cache(&mut self) {
+        self.texture_cache.clear();
+    }
 }
+/// A folder or NaviCust pasted from a share code, shown in place of the one in the save.
+struct Imported<T> {
+    view: T,
+    unmatched: Vec<sharecode::Unmatched>,
+}
+
+/// Shows the result of pasting a share code, returning true if the user wants to go back to the save.
+fn show_import_status<T>(
+    ui: &mut egui::Ui,
+    lang: &unic_langid::LanguageIdentifier,
+    import: &Result<Imported<T>, String>,
+) -> bool {
+    let mut dismiss = false;
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.horizontal(|ui| {
+            match import {
+                Ok(_) => {
+                    ui.label(i18n::LOCALES.lookup(lang, "share-code-imported"));
+                }
+                Err(e) => {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        i18n::LOCALES.lookup_with_args(
+                            lang,
+                            "share-code-import-error",
+                            &std::collections::HashMap::from([("error", e.clone().into())]),
+                        ),
+                    );
+                }
+            }
+            if ui.button(i18n::LOCALES.lookup(lang, "share-code-dismiss")).clicked() {
+                dismiss = true;
+            }
+        });
+
+        if let Ok(imported) = import {
+            for unmatched in imported.unmatched.iter() {
+                let mut args = std::collections::HashMap::from([
+                    ("line", unmatched.line.to_string().into()),
+                    ("name", unmatched.name.clone().into()),
+                ]);
+                let key = match unmatched.reason {
+                    sharecode::UnmatchedReason::Unknown => "share-code-unmatched-unknown",
+                    sharecode::UnmatchedReason::InvalidCode(code) => {
+                        args.insert("code", code.to_string().into());
+                        "share-code-unmatched-invalid-code"
+                    }
+                    sharecode::UnmatchedReason::OutOfBounds => "share-code-unmatched-out-of-bounds",
+                };
+                ui.colored_label(
+                    egui::Color32::from_rgb(0xff, 0xa5, 0x00),
+                    i18n::LOCALES.lookup_with_args(lang, key, &args),
+                );
+            }
+        }
+    });
+    dismiss
+}
+
 pub fn show(
     ui: &mut egui::Ui,
     streamer_mode: bool,
@@ -46,10 +112,12 @@ pub fn show(
     lang: &unic_langid::LanguageIdentifier,
     game_lang: &unic_langid::LanguageIdentifier,
     save: &Box<dyn save::Save + Send + Sync>,
-    assets: &Box<dyn rom::Assets + Send + Sync>,
+    assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
     state: &mut State,
     prefer_vertical: bool,
 ) {
+    gui::fonts::request_language(game_lang);
+    state.texture_cache.upload_decoded(ui.ctx());
     ui.vertical(|ui| {
         let navi_view = save.view_navi();
         let navicust_view = save.view_navicust();
@@ -77,7 +145,7 @@ pub fn show(
         ui.horizontal(|ui| {
             if streamer_mode
                 && ui
-                    .selectable_label(state.tab == None, i18n::LOCALES.lookup(lang, "save-tab-cover").unwrap())
+                    .selectable_label(state.tab == None, i18n::LOCALES.lookup(lang, "save-tab-cover"))
                     .clicked()
             {
                 state.tab = None;
@@ -87,18 +155,16 @@ pub fn show(
                 if ui
                     .selectable_label(
                         state.tab.as_ref() == Some(tab),
-                        i18n::LOCALES
-                            .lookup(
-                                lang,
-                                match tab {
-                                    Tab::Navi => "save-tab-navi",
-                                    Tab::Navicust => "save-tab-navicust",
-                                    Tab::Folder => "save-tab-folder",
-                                    Tab::Modcards => "save-tab-modcards",
-                                    Tab::DarkAI => "save-tab-dark-ai",
-                                },
-                            )
-                            .unwrap(),
+                        i18n::LOCALES.lookup(
+                            lang,
+                            match tab {
+                                Tab::Navi => "save-tab-navi",
+                                Tab::Navicust => "save-tab-navicust",
+                                Tab::Folder => "save-tab-folder",
+                                Tab::Modcards => "save-tab-modcards",
+                                Tab::DarkAI => "save-tab-dark-ai",
+                            },
+                        ),
                     )
                     .clicked()
                 {
@@ -151,6 +217,7 @@ pub fn show(
                         game_lang,
                         &chips_view,
                         assets,
+                        &mut state.texture_cache,
                         &mut state.folder_view,
                     );
                 }
@@ -179,6 +246,7 @@ pub fn show(
                         game_lang,
                         &dark_ai_view,
                         assets,
+                        &mut state.texture_cache,
                         &mut state.dark_ai_view,
                     );
                 }
@@ -188,7 +256,7 @@ pub fn show(
                     egui::Layout::centered_and_justified(egui::Direction::LeftToRight)
                         .with_main_align(egui::Align::Center),
                     |ui| {
-                        ui.label(i18n::LOCALES.lookup(lang, "save-cover-description").unwrap());
+                        ui.label(i18n::LOCALES.lookup(lang, "save-cover-description"));
                     },
                 );
             }
@@ -1,21 +1,14 @@
-use fluent_templates::Loader;
 use itertools::Itertools;
 
 use crate::{gui, i18n, rom, save};
 
 pub struct State {
-    chip_icon_texture_cache: std::collections::HashMap<usize, egui::TextureHandle>,
-    element_icon_texture_cache: std::collections::HashMap<usize, egui::TextureHandle>,
     materialized: Option<MaterializedDarkAI>,
 }
 
 impl State {
     pub fn new() -> Self {
-        Self {
-            chip_icon_texture_cache: std::collections::HashMap::new(),
-            element_icon_texture_cache: std::collections::HashMap::new(),
-            materialized: None,
-        }
+        Self { materialized: None }
     }
 }
 
@@ -37,7 +30,10 @@ struct MaterializedDarkAI {
 }
 
 impl MaterializedDarkAI {
-    fn new(dark_ai_view: &Box<dyn save::DarkAIView + '_>, assets: &Box<dyn rom::Assets + Send + Sync>) -> Self {
+    fn new(
+        dark_ai_view: &Box<dyn save::DarkAIView + '_>,
+        assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
+    ) -> Self {
         let mut use_counts = vec![];
         loop {
             if let Some(count) = dark_ai_view.chip_use_count(use_counts.len()) {
@@ -141,12 +137,11 @@ fn show_table<const N: usize>(
     ui: &mut egui::Ui,
     chips: &[Option<usize>; N],
     counts: &[usize; N],
-    assets: &Box<dyn rom::Assets + Send + Sync>,
+    assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
     font_families: &gui::FontFamilies,
     lang: &unic_langid::LanguageIdentifier,
     game_lang: &unic_langid::LanguageIdentifier,
-    chip_icon_texture_cache: &mut std::collections::HashMap<usize, egui::TextureHandle>,
-    element_icon_texture_cache: &mut std::collections::HashMap<usize, egui::TextureHandle>,
+    texture_cache: &mut super::texture_cache::TextureCache,
 ) {
     egui_extras::StripBuilder::new(ui)
         .sizes(egui_extras::Size::exact(28.0), chips.len())
@@ -157,24 +152,21 @@ fn show_table<const N: usize>(
 
                     let (bg_color, fg_color) = if let Some(info) = info.as_ref() {
                         let bg_color = if info.dark() {
-                            Some(if ui.visuals().dark_mode {
-                                egui::Color32::from_rgb(0x31, 0x39, 0x5a)
-                            } else {
-                                egui::Color32::from_rgb(0xb5, 0x8c, 0xd6)
-                            })
+                            Some(gui::theme::highlight_fill(
+                                ui.visuals(),
+                                egui::Color32::from_rgb(0xb5, 0x8c, 0xd6),
+                            ))
                         } else {
                             match info.class() {
                                 rom::ChipClass::Standard => None,
-                                rom::ChipClass::Mega => Some(if ui.visuals().dark_mode {
-                                    egui::Color32::from_rgb(0x52, 0x84, 0x9c)
-                                } else {
-                                    egui::Color32::from_rgb(0xad, 0xef, 0xef)
-                                }),
-                                rom::ChipClass::Giga => Some(if ui.visuals().dark_mode {
-                                    egui::Color32::from_rgb(0x8c, 0x31, 0x52)
-                                } else {
-                                    egui::Color32::from_rgb(0xf7, 0xce, 0xe7)
-                                }),
+                                rom::ChipClass::Mega => Some(gui::theme::highlight_fill(
+                                    ui.visuals(),
+                                    egui::Color32::from_rgb(0xad, 0xef, 0xef),
+                                )),
+                                rom::ChipClass::Giga => Some(gui::theme::highlight_fill(
+                                    ui.visuals(),
+                                    egui::Color32::from_rgb(0xf7, 0xce, 0xe7),
+                                )),
                                 rom::ChipClass::None => None,
                                 rom::ChipClass::ProgramAdvance => None,
                             }
@@ -211,24 +203,19 @@ fn show_table<const N: usize>(
                             });
                             if let Some(id) = id {
                                 strip.cell(|ui| {
-                                    match chip_icon_texture_cache.entry(*id) {
-                                        std::collections::hash_map::Entry::Occupied(_) => {}
-                                        std::collections::hash_map::Entry::Vacant(e) => {
-                                            if let Some(image) = info.as_ref().map(|info| info.icon()) {
-                                                e.insert(ui.ctx().load_texture(
-                                                    format!("chip icon {}", id),
-                                                    egui::ColorImage::from_rgba_unmultiplied(
-                                                        [14, 14],
-                                                        &image::imageops::crop_imm(&image, 1, 1, 14, 14).to_image(),
-                                                    ),
-                                                    egui::TextureFilter::Nearest,
-                                                ));
-                                            }
+                                    match texture_cache.get(
+                                        ui.ctx(),
+                                        assets,
+                                        super::texture_cache::CachedAssetType::ChipIcon,
+                                        *id,
+                                    ) {
+                                        super::texture_cache::Lookup::Ready(texture_handle) => {
+                                            ui.image(texture_handle.id(), egui::Vec2::new(28.0, 28.0));
                                         }
-                                    }
-
-                                    if let Some(texture_handle) = chip_icon_texture_cache.get(&id) {
-                                        ui.image(texture_handle.id(), egui::Vec2::new(28.0, 28.0));
+                                        super::texture_cache::Lookup::Pending => {
+                                            super::texture_cache::placeholder(ui, egui::Vec2::new(28.0, 28.0));
+                                        }
+                                        super::texture_cache::Lookup::Missing => {}
                                     }
                                 });
                                 strip.cell(|ui| {
@@ -253,24 +240,19 @@ fn show_table<const N: usize>(
                                         return;
                                     };
 
-                                    match element_icon_texture_cache.entry(element) {
-                                        std::collections::hash_map::Entry::Occupied(_) => {}
-                                        std::collections::hash_map::Entry::Vacant(e) => {
-                                            if let Some(image) = assets.element_icon(element) {
-                                                e.insert(ui.ctx().load_texture(
-                                                    format!("element {}", element),
-                                                    egui::ColorImage::from_rgba_unmultiplied(
-                                                        [14, 14],
-                                                        &image::imageops::crop_imm(&image, 1, 1, 14, 14).to_image(),
-                                                    ),
-                                                    egui::TextureFilter::Nearest,
-                                                ));
-                                            }
+                                    match texture_cache.get(
+                                        ui.ctx(),
+                                        assets,
+                                        super::texture_cache::CachedAssetType::ElementIcon,
+                                        element,
+                                    ) {
+                                        super::texture_cache::Lookup::Ready(texture_handle) => {
+                                            ui.image(texture_handle.id(), egui::Vec2::new(28.0, 28.0));
                                         }
-                                    }
-
-                                    if let Some(texture_handle) = element_icon_texture_cache.get(&element) {
-                                        ui.image(texture_handle.id(), egui::Vec2::new(28.0, 28.0));
+                                        super::texture_cache::Lookup::Pending => {
+                                            super::texture_cache::placeholder(ui, egui::Vec2::new(28.0, 28.0));
+                                        }
+                                        super::texture_cache::Lookup::Missing => {}
                                     }
                                 });
                                 strip.cell(|ui| {
@@ -284,7 +266,7 @@ fn show_table<const N: usize>(
                             } else {
                                 strip.cell(|_ui| {});
                                 strip.cell(|ui| {
-                                    ui.weak(i18n::LOCALES.lookup(lang, "dark-ai-unset").unwrap());
+                                    ui.weak(i18n::LOCALES.lookup(lang, "dark-ai-unset"));
                                 });
                                 strip.cell(|_ui| {});
                                 strip.cell(|_ui| {});
@@ -298,7 +280,7 @@ fn show_table<const N: usize>(
 fn make_string<'a, const N: usize>(
     chips: &'a [Option<usize>; N],
     counts: &'a [usize; N],
-    assets: &'a Box<dyn rom::Assets + Send + Sync>,
+    assets: &'a std::sync::Arc<dyn rom::Assets + Send + Sync>,
 ) -> impl std::iter::Iterator<Item = String> + 'a {
     std::iter::zip(chips, counts).map(|(id, count)| {
         let name = if let Some(id) = id {
@@ -321,7 +303,8 @@ pub fn show<'a>(
     lang: &unic_langid::LanguageIdentifier,
     game_lang: &unic_langid::LanguageIdentifier,
     dark_ai_view: &Box<dyn save::DarkAIView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync>,
+    assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
+    texture_cache: &mut super::texture_cache::TextureCache,
     state: &mut State,
 ) {
     let materialized = state
@@ -330,10 +313,7 @@ pub fn show<'a>(
 
     ui.horizontal(|ui| {
         if ui
-            .button(format!(
-                "📋 {}",
-                i18n::LOCALES.lookup(lang, "copy-to-clipboard").unwrap(),
-            ))
+            .button(format!("📋 {}", i18n::LOCALES.lookup(lang, "copy-to-clipboard"),))
             .clicked()
         {
             let _ = clipboard.set_text(
@@ -362,7 +342,7 @@ pub fn show<'a>(
         .auto_shrink([false, false])
         .show(ui, |ui| {
             ui.push_id(egui::Id::new("dark-ai-view-secondary-standard-chips"), |ui| {
-                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-secondary-standard-chips").unwrap());
+                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-secondary-standard-chips"));
                 show_table(
                     ui,
                     &materialized.secondary_standard_chips,
@@ -371,13 +351,12 @@ pub fn show<'a>(
                     font_families,
                     lang,
                     game_lang,
-                    &mut state.chip_icon_texture_cache,
-                    &mut state.element_icon_texture_cache,
+                    texture_cache,
                 );
             });
 
             ui.push_id(egui::Id::new("dark-ai-view-standard-chips"), |ui| {
-                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-standard-chips").unwrap());
+                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-standard-chips"));
                 show_table(
                     ui,
                     &materialized.standard_chips,
@@ -386,13 +365,12 @@ pub fn show<'a>(
                     font_families,
                     lang,
                     game_lang,
-                    &mut state.chip_icon_texture_cache,
-                    &mut state.element_icon_texture_cache,
+                    texture_cache,
                 );
             });
 
             ui.push_id(egui::Id::new("dark-ai-view-mega-chips"), |ui| {
-                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-mega-chips").unwrap());
+                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-mega-chips"));
                 show_table(
                     ui,
                     &materialized.mega_chips,
@@ -401,13 +379,12 @@ pub fn show<'a>(
                     font_families,
                     lang,
                     game_lang,
-                    &mut state.chip_icon_texture_cache,
-                    &mut state.element_icon_texture_cache,
+                    texture_cache,
                 );
             });
 
             ui.push_id(egui::Id::new("dark-ai-view-giga-chip"), |ui| {
-                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-giga-chip").unwrap());
+                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-giga-chip"));
                 show_table(
                     ui,
                     &[materialized.giga_chip],
@@ -416,13 +393,12 @@ pub fn show<'a>(
                     font_families,
                     lang,
                     game_lang,
-                    &mut state.chip_icon_texture_cache,
-                    &mut state.element_icon_texture_cache,
+                    texture_cache,
                 );
             });
 
             ui.push_id(egui::Id::new("dark-ai-view-combos"), |ui| {
-                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-combos").unwrap());
+                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-combos"));
                 show_table(
                     ui,
                     &[None; 8],
@@ -431,13 +407,12 @@ pub fn show<'a>(
                     font_families,
                     lang,
                     game_lang,
-                    &mut state.chip_icon_texture_cache,
-                    &mut state.element_icon_texture_cache,
+                    texture_cache,
                 );
             });
 
             ui.push_id(egui::Id::new("dark-ai-view-program-advance"), |ui| {
-                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-program-advance").unwrap());
+                ui.strong(i18n::LOCALES.lookup(lang, "dark-ai-program-advance"));
                 show_table(
                     ui,
                     &[materialized.program_advance],
@@ -446,8 +421,7 @@ pub fn show<'a>(
                     font_families,
                     lang,
                     game_lang,
-                    &mut state.chip_icon_texture_cache,
-                    &mut state.element_icon_texture_cache,
+                    texture_cache,
                 );
             });
         });
@@ -1,21 +1,15 @@
-use fluent_templates::Loader;
-
-use crate::{gui, i18n, rom, save};
+use crate::{gui, i18n, rom, save, sharecode};
 
 pub struct State {
     grouped: bool,
-    chip_icon_texture_cache: std::collections::HashMap<usize, egui::TextureHandle>,
-    chip_image_texture_cache: std::collections::HashMap<usize, (egui::TextureHandle, [u32; 2])>,
-    element_icon_texture_cache: std::collections::HashMap<usize, egui::TextureHandle>,
+    import: Option<Result<super::Imported<sharecode::Folder>, String>>,
 }
 
 impl State {
     pub fn new() -> Self {
         Self {
             grouped: true,
-            chip_icon_texture_cache: std::collections::HashMap::new(),
-            chip_image_texture_cache: std::collections::HashMap::new(),
-            element_icon_texture_cache: std::collections::HashMap::new(),
+            import: None,
         }
     }
 }
@@ -27,7 +21,8 @@ pub fn show<'a>(
     lang: &unic_langid::LanguageIdentifier,
     game_lang: &unic_langid::LanguageIdentifier,
     chips_view: &Box<dyn save::ChipsView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync>,
+    assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
+    texture_cache: &mut super::texture_cache::TextureCache,
     state: &mut State,
 ) {
     struct GroupedChip {
@@ -36,6 +31,12 @@ pub fn show<'a>(
         tag_count: usize,
     }
 
+    let chips_view: &(dyn save::ChipsView<'a> + 'a) = match state.import.as_ref() {
+        Some(Ok(imported)) => &imported.view,
+        _ => chips_view.as_ref(),
+    };
+    let mut import = None;
+
     let mut chips = (0..30)
         .map(|i| chips_view.chip(chips_view.equipped_folder_index(), i))
         .collect::<Vec<_>>();
@@ -94,10 +95,7 @@ pub fn show<'a>(
 
     ui.horizontal(|ui| {
         if ui
-            .button(format!(
-                "📋 {}",
-                i18n::LOCALES.lookup(lang, "copy-to-clipboard").unwrap(),
-            ))
+            .button(format!("📋 {}", i18n::LOCALES.lookup(lang, "copy-to-clipboard"),))
             .clicked()
         {
             let _ = clipboard.set_text(
@@ -131,9 +129,36 @@ pub fn show<'a>(
                     .join("\n"),
             );
         }
-        ui.checkbox(&mut state.grouped, i18n::LOCALES.lookup(lang, "save-group").unwrap());
+        if ui
+            .button(format!("📋 {}", i18n::LOCALES.lookup(lang, "copy-share-code")))
+            .clicked()
+        {
+            let _ = clipboard.set_text(sharecode::FolderCode::export(chips_view, assets.as_ref()));
+        }
+        if ui
+            .button(format!("📥 {}", i18n::LOCALES.lookup(lang, "paste-share-code")))
+            .clicked()
+        {
+            import = Some(
+                clipboard
+                    .get_text()
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| sharecode::FolderCode::parse(&text).map_err(|e| e.to_string()))
+                    .map(|code| {
+                        let (view, unmatched) =
+                            code.resolve(chips_view.chip_codes(), chips_view.chips_have_mb(), assets.as_ref());
+                        super::Imported { view, unmatched }
+                    }),
+            );
+        }
+        ui.checkbox(&mut state.grouped, i18n::LOCALES.lookup(lang, "save-group"));
     });
 
+    let dismiss_import = state
+        .import
+        .as_ref()
+        .map_or(false, |status| super::show_import_status(ui, lang, status));
+
     egui::ScrollArea::vertical()
         .id_source("folder-view")
         .auto_shrink([false, false])
@@ -147,24 +172,21 @@ pub fn show<'a>(
 
                             let (bg_color, fg_color) = if let Some(info) = info.as_ref() {
                                 let bg_color = if info.dark() {
-                                    Some(if ui.visuals().dark_mode {
-                                        egui::Color32::from_rgb(0x31, 0x39, 0x5a)
-                                    } else {
-                                        egui::Color32::from_rgb(0xb5, 0x8c, 0xd6)
-                                    })
+                                    Some(gui::theme::highlight_fill(
+                                        ui.visuals(),
+                                        egui::Color32::from_rgb(0xb5, 0x8c, 0xd6),
+                                    ))
                                 } else {
                                     match info.class() {
                                         rom::ChipClass::Standard => None,
-                                        rom::ChipClass::Mega => Some(if ui.visuals().dark_mode {
-                                            egui::Color32::from_rgb(0x52, 0x84, 0x9c)
-                                        } else {
-                                            egui::Color32::from_rgb(0xad, 0xef, 0xef)
-                                        }),
-                                        rom::ChipClass::Giga => Some(if ui.visuals().dark_mode {
-                                            egui::Color32::from_rgb(0x8c, 0x31, 0x52)
-                                        } else {
-                                            egui::Color32::from_rgb(0xf7, 0xce, 0xe7)
-                                        }),
+                                        rom::ChipClass::Mega => Some(gui::theme::highlight_fill(
+                                            ui.visuals(),
+                                            egui::Color32::from_rgb(0xad, 0xef, 0xef),
+                                        )),
+                                        rom::ChipClass::Giga => Some(gui::theme::highlight_fill(
+                                            ui.visuals(),
+                                            egui::Color32::from_rgb(0xf7, 0xce, 0xe7),
+                                        )),
                                         rom::ChipClass::None => None,
                                         rom::ChipClass::ProgramAdvance => None,
                                     }
@@ -217,57 +239,38 @@ pub fn show<'a>(
                                         return;
                                     };
 
-                                    match state.chip_icon_texture_cache.entry(chip.id) {
-                                        std::collections::hash_map::Entry::Occupied(_) => {}
-                                        std::collections::hash_map::Entry::Vacant(e) => {
-                                            if let Some(image) = info.as_ref().map(|info| info.icon()) {
-                                                e.insert(ui.ctx().load_texture(
-                                                    format!("chip icon {}", chip.id),
-                                                    egui::ColorImage::from_rgba_unmultiplied(
-                                                        [14, 14],
-                                                        &image::imageops::crop_imm(&image, 1, 1, 14, 14).to_image(),
-                                                    ),
-                                                    egui::TextureFilter::Nearest,
-                                                ));
+                                    let icon = match texture_cache.get(
+                                        ui.ctx(),
+                                        assets,
+                                        super::texture_cache::CachedAssetType::ChipIcon,
+                                        chip.id,
+                                    ) {
+                                        super::texture_cache::Lookup::Ready(texture_handle) => {
+                                            ui.image(texture_handle.id(), egui::Vec2::new(28.0, 28.0))
+                                        }
+                                        super::texture_cache::Lookup::Pending => {
+                                            super::texture_cache::placeholder(ui, egui::Vec2::new(28.0, 28.0))
+                                        }
+                                        super::texture_cache::Lookup::Missing => {
+                                            return;
+                                        }
+                                    };
+                                    icon.on_hover_ui(|ui| {
+                                        match texture_cache.get(
+                                            ui.ctx(),
+                                            assets,
+                                            super::texture_cache::CachedAssetType::ChipImage,
+                                            chip.id,
+                                        ) {
+                                            super::texture_cache::Lookup::Ready(texture_handle) => {
+                                                ui.image(texture_handle.id(), texture_handle.size_vec2() * 2.0);
+                                            }
+                                            super::texture_cache::Lookup::Pending => {
+                                                ui.spinner();
                                             }
+                                            super::texture_cache::Lookup::Missing => {}
                                         }
-                                    }
-
-                                    if let Some(texture_handle) = state.chip_icon_texture_cache.get(&chip.id) {
-                                        ui.image(texture_handle.id(), egui::Vec2::new(28.0, 28.0))
-                                            .on_hover_ui(|ui| {
-                                                match state.chip_image_texture_cache.entry(chip.id) {
-                                                    std::collections::hash_map::Entry::Occupied(_) => {}
-                                                    std::collections::hash_map::Entry::Vacant(e) => {
-                                                        if let Some(image) = info.as_ref().map(|info| info.image()) {
-                                                            e.insert((
-                                                                ui.ctx().load_texture(
-                                                                    format!("chip image {}", chip.id),
-                                                                    egui::ColorImage::from_rgba_unmultiplied(
-                                                                        [
-                                                                            image.width() as usize,
-                                                                            image.height() as usize,
-                                                                        ],
-                                                                        &image,
-                                                                    ),
-                                                                    egui::TextureFilter::Nearest,
-                                                                ),
-                                                                [image.width(), image.height()],
-                                                            ));
-                                                        }
-                                                    }
-                                                }
-
-                                                if let Some((texture_handle, [width, height])) =
-                                                    state.chip_image_texture_cache.get(&chip.id)
-                                                {
-                                                    ui.image(
-                                                        texture_handle.id(),
-                                                        egui::Vec2::new(*width as f32 * 2.0, *height as f32 * 2.0),
-                                                    );
-                                                }
-                                            });
-                                    }
+                                    });
                                 });
                                 strip.cell(|ui| {
                                     ui.horizontal(|ui| {
@@ -341,24 +344,19 @@ pub fn show<'a>(
                                         return;
                                     };
 
-                                    match state.element_icon_texture_cache.entry(element) {
-                                        std::collections::hash_map::Entry::Occupied(_) => {}
-                                        std::collections::hash_map::Entry::Vacant(e) => {
-                                            if let Some(image) = assets.element_icon(element) {
-                                                e.insert(ui.ctx().load_texture(
-                                                    format!("element {}", element),
-                                                    egui::ColorImage::from_rgba_unmultiplied(
-                                                        [14, 14],
-                                                        &image::imageops::crop_imm(&image, 1, 1, 14, 14).to_image(),
-                                                    ),
-                                                    egui::TextureFilter::Nearest,
-                                                ));
-                                            }
+                                    match texture_cache.get(
+                                        ui.ctx(),
+                                        assets,
+                                        super::texture_cache::CachedAssetType::ElementIcon,
+                                        element,
+                                    ) {
+                                        super::texture_cache::Lookup::Ready(texture_handle) => {
+                                            ui.image(texture_handle.id(), egui::Vec2::new(28.0, 28.0));
                                         }
-                                    }
-
-                                    if let Some(texture_handle) = state.element_icon_texture_cache.get(&element) {
-                                        ui.image(texture_handle.id(), egui::Vec2::new(28.0, 28.0));
+                                        super::texture_cache::Lookup::Pending => {
+                                            super::texture_cache::placeholder(ui, egui::Vec2::new(28.0, 28.0));
+                                        }
+                                        super::texture_cache::Lookup::Missing => {}
                                     }
                                 });
                                 strip.cell(|ui| {
@@ -390,4 +388,11 @@ pub fn show<'a>(
                     }
                 });
         });
+
+    if dismiss_import {
+        state.import = None;
+    }
+    if let Some(import) = import {
+        state.import = Some(import);
+    }
 }
@@ -1,5 +1,3 @@
-use fluent_templates::Loader;
-
 use crate::{gui, i18n, rom, save};
 
 pub struct State {}
@@ -11,20 +9,21 @@ impl State {
 }
 
 fn show_effect(ui: &mut egui::Ui, name: egui::RichText, is_enabled: bool, is_debuff: bool) {
+    let fill = if is_enabled {
+        if is_debuff {
+            egui::Color32::from_rgb(0xb5, 0x5a, 0xde)
+        } else {
+            egui::Color32::from_rgb(0xff, 0xbd, 0x18)
+        }
+    } else {
+        egui::Color32::from_rgb(0xbd, 0xbd, 0xbd)
+    };
     egui::Frame::none()
         .inner_margin(egui::style::Margin::symmetric(4.0, 0.0))
         .rounding(egui::Rounding::same(2.0))
-        .fill(if is_enabled {
-            if is_debuff {
-                egui::Color32::from_rgb(0xb5, 0x5a, 0xde)
-            } else {
-                egui::Color32::from_rgb(0xff, 0xbd, 0x18)
-            }
-        } else {
-            egui::Color32::from_rgb(0xbd, 0xbd, 0xbd)
-        })
+        .fill(fill)
         .show(ui, |ui| {
-            ui.label(name.color(egui::Color32::BLACK));
+            ui.label(name.color(gui::theme::text_color_on(fill)));
         });
 }
 
@@ -35,15 +34,12 @@ pub fn show_modcard4s<'a>(
     lang: &unic_langid::LanguageIdentifier,
     game_lang: &unic_langid::LanguageIdentifier,
     modcard4s_view: &Box<dyn save::Modcard4sView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync>,
+    assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
     _state: &mut State,
 ) {
     ui.horizontal(|ui| {
         if ui
-            .button(format!(
-                "📋 {}",
-                i18n::LOCALES.lookup(lang, "copy-to-clipboard").unwrap(),
-            ))
+            .button(format!("📋 {}", i18n::LOCALES.lookup(lang, "copy-to-clipboard"),))
             .clicked()
         {
             let _ = clipboard.set_text(
@@ -143,7 +139,7 @@ pub fn show_modcard56s<'a>(
     lang: &unic_langid::LanguageIdentifier,
     game_lang: &unic_langid::LanguageIdentifier,
     modcard56s_view: &Box<dyn save::Modcard56sView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync>,
+    assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
     _state: &mut State,
 ) {
     let items = (0..modcard56s_view.count())
@@ -160,10 +156,7 @@ pub fn show_modcard56s<'a>(
 
     ui.horizontal(|ui| {
         if ui
-            .button(format!(
-                "📋 {}",
-                i18n::LOCALES.lookup(lang, "copy-to-clipboard").unwrap(),
-            ))
+            .button(format!("📋 {}", i18n::LOCALES.lookup(lang, "copy-to-clipboard"),))
             .clicked()
         {
             let _ = clipboard.set_text(
@@ -228,6 +221,18 @@ pub fn show_modcard56s<'a>(
                                 ui.label(text);
                                 ui.small(format!("{}MB", modcard.mb()));
                             });
+                        } else if let Some(modcard) = modcard.as_ref() {
+                            // The ROM doesn't have this modcard, e.g. because the save was made with a patch that adds more.
+                            let mut text = egui::RichText::new(i18n::LOCALES.lookup_with_args(
+                                lang,
+                                "save-modcard-unknown",
+                                &std::collections::HashMap::from([("id", modcard.id.into())]),
+                            ))
+                            .weak();
+                            if !modcard.enabled {
+                                text = text.strikethrough();
+                            }
+                            ui.label(text);
                         }
                     });
 
@@ -279,7 +284,7 @@ pub fn show<'a>(
     lang: &unic_langid::LanguageIdentifier,
     game_lang: &unic_langid::LanguageIdentifier,
     modcards_view: &save::ModcardsView,
-    assets: &Box<dyn rom::Assets + Send + Sync>,
+    assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
     state: &mut State,
 ) {
     match modcards_view {
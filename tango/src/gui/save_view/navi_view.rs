@@ -1,6 +1,4 @@
 #[allow(unused_imports)]
-use fluent_templates::Loader;
-
 #[allow(unused_imports)]
 use crate::{gui, i18n, rom, save};
 
@@ -23,7 +21,7 @@ pub fn show<'a>(
     _lang: &unic_langid::LanguageIdentifier,
     game_lang: &unic_langid::LanguageIdentifier,
     navi_view: &Box<dyn save::NaviView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync>,
+    assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
     state: &mut State,
 ) {
     let navi_id = navi_view.navi();
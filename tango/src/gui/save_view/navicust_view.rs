@@ -1,20 +1,86 @@
-use fluent_templates::Loader;
 use itertools::Itertools;
 
-use crate::{gui, i18n, rom, save};
+use crate::{gui, i18n, rom, save, sharecode};
 
 pub struct State {
     rendered_navicust_cache: Option<(image::RgbaImage, ComposedNavicust, egui::TextureHandle)>,
+    /// The NaviCust being rendered in the background, if it's not done yet.
+    rendering: Option<std::sync::mpsc::Receiver<(image::RgbaImage, ComposedNavicust)>>,
+    import: Option<Result<super::Imported<sharecode::Navicust>, String>>,
 }
 
 impl State {
     pub fn new() -> Self {
         Self {
             rendered_navicust_cache: None,
+            rendering: None,
+            import: None,
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.rendered_navicust_cache = None;
+        self.rendering = None;
+    }
+}
+
+/// A copy of a NaviCust, so it can be rendered away from the save it was read from.
+struct Snapshot {
+    count: usize,
+    style: Option<usize>,
+    width: usize,
+    height: usize,
+    command_line: usize,
+    has_out_of_bounds: bool,
+    parts: Vec<Option<save::NavicustPart>>,
+}
+
+impl Snapshot {
+    fn new<'a>(navicust_view: &(dyn save::NavicustView<'a> + 'a)) -> Self {
+        Self {
+            count: navicust_view.count(),
+            style: navicust_view.style(),
+            width: navicust_view.width(),
+            height: navicust_view.height(),
+            command_line: navicust_view.command_line(),
+            has_out_of_bounds: navicust_view.has_out_of_bounds(),
+            parts: (0..navicust_view.count())
+                .map(|i| navicust_view.navicust_part(i))
+                .collect(),
         }
     }
 }
 
+impl<'a> save::NavicustView<'a> for Snapshot {
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    fn style(&self) -> Option<usize> {
+        self.style
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn command_line(&self) -> usize {
+        self.command_line
+    }
+
+    fn has_out_of_bounds(&self) -> bool {
+        self.has_out_of_bounds
+    }
+
+    fn navicust_part(&self, i: usize) -> Option<save::NavicustPart> {
+        self.parts.get(i).cloned().flatten()
+    }
+}
+
 fn navicust_part_colors(color: &rom::NavicustPartColor) -> (image::Rgba<u8>, image::Rgba<u8>) {
     match color {
         rom::NavicustPartColor::Red => (
@@ -63,17 +129,18 @@ fn show_part_name(
     is_enabled: bool,
     color: &rom::NavicustPartColor,
 ) {
+    let fill = if is_enabled {
+        let (color, _) = navicust_part_colors(color);
+        egui::Color32::from_rgb(color.0[0], color.0[1], color.0[2])
+    } else {
+        egui::Color32::from_rgb(0xbd, 0xbd, 0xbd)
+    };
     egui::Frame::none()
         .inner_margin(egui::style::Margin::symmetric(4.0, 0.0))
         .rounding(egui::Rounding::same(2.0))
-        .fill(if is_enabled {
-            let (color, _) = navicust_part_colors(color);
-            egui::Color32::from_rgb(color.0[0], color.0[1], color.0[2])
-        } else {
-            egui::Color32::from_rgb(0xbd, 0xbd, 0xbd)
-        })
+        .fill(fill)
         .show(ui, |ui| {
-            ui.label(name.color(egui::Color32::BLACK));
+            ui.label(name.color(gui::theme::text_color_on(fill)));
         })
         .response
         .on_hover_text(description);
@@ -105,8 +172,8 @@ fn ncp_bitmap<'a>(info: &'a Box<dyn rom::NavicustPart + 'a>, compressed: bool, r
 type ComposedNavicust = image::ImageBuffer<image::LumaA<u8>, Vec<u8>>;
 
 fn compose_navicust<'a>(
-    navicust_view: &Box<dyn save::NavicustView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync + 'a>,
+    navicust_view: &(dyn save::NavicustView<'a> + 'a),
+    assets: &(dyn rom::Assets + Send + Sync),
 ) -> ComposedNavicust {
     let mut composed = image::ImageBuffer::new(navicust_view.width() as u32, navicust_view.height() as u32);
     for i in 0..navicust_view.count() {
@@ -156,8 +223,8 @@ const BORDER_STROKE_COLOR: image::Rgba<u8> = image::Rgba([0x00, 0x00, 0x00, 0xff
 
 fn render_navicust<'a>(
     composed: &ComposedNavicust,
-    navicust_view: &Box<dyn save::NavicustView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync + 'a>,
+    navicust_view: &(dyn save::NavicustView<'a> + 'a),
+    assets: &(dyn rom::Assets + Send + Sync),
     raw_font: &[u8],
 ) -> image::RgbaImage {
     let body = render_navicust_body(composed, navicust_view, assets);
@@ -218,8 +285,8 @@ fn render_navicust<'a>(
 }
 
 fn gather_ncp_colors<'a>(
-    navicust_view: &Box<dyn save::NavicustView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync + 'a>,
+    navicust_view: &(dyn save::NavicustView<'a> + 'a),
+    assets: &(dyn rom::Assets + Send + Sync),
 ) -> Vec<rom::NavicustPartColor> {
     (0..navicust_view.count())
         .flat_map(|i| {
@@ -315,8 +382,8 @@ fn render_navicust_color_bar3<'a>(extra_color: Option<rom::NavicustPartColor>) -
 }
 
 fn render_navicust_color_bar456<'a>(
-    navicust_view: &Box<dyn save::NavicustView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync + 'a>,
+    navicust_view: &(dyn save::NavicustView<'a> + 'a),
+    assets: &(dyn rom::Assets + Send + Sync),
 ) -> image::RgbaImage {
     const TILE_WIDTH: f32 = SQUARE_SIZE * 3.0 / 4.0;
 
@@ -409,8 +476,8 @@ fn render_navicust_color_bar456<'a>(
 
 fn render_navicust_body<'a>(
     composed: &ComposedNavicust,
-    navicust_view: &Box<dyn save::NavicustView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync + 'a>,
+    navicust_view: &(dyn save::NavicustView<'a> + 'a),
+    assets: &(dyn rom::Assets + Send + Sync),
 ) -> image::RgbaImage {
     let mut pixmap = tiny_skia::Pixmap::new(
         (composed.width() as f32 * SQUARE_SIZE + BORDER_WIDTH) as u32,
@@ -673,12 +740,18 @@ pub fn show<'a>(
     lang: &unic_langid::LanguageIdentifier,
     game_lang: &unic_langid::LanguageIdentifier,
     navicust_view: &Box<dyn save::NavicustView<'a> + 'a>,
-    assets: &Box<dyn rom::Assets + Send + Sync>,
+    assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
     state: &mut State,
     prefer_vertical: bool,
 ) {
     const NCP_CHIP_WIDTH: f32 = 150.0;
 
+    let navicust_view: &(dyn save::NavicustView<'a> + 'a) = match state.import.as_ref() {
+        Some(Ok(imported)) => &imported.view,
+        _ => navicust_view.as_ref(),
+    };
+    let mut import = None;
+
     let items = (0..navicust_view.count())
         .flat_map(|i| {
             navicust_view.navicust_part(i).and_then(|ncp| {
@@ -691,10 +764,7 @@ pub fn show<'a>(
 
     ui.horizontal(|ui| {
         if ui
-            .button(format!(
-                "📋 {}",
-                i18n::LOCALES.lookup(lang, "copy-to-clipboard").unwrap(),
-            ))
+            .button(format!("📋 {}", i18n::LOCALES.lookup(lang, "copy-to-clipboard"),))
             .clicked()
         {
             let mut buf = vec![];
@@ -730,7 +800,7 @@ pub fn show<'a>(
         if ui
             .button(format!(
                 "📋 {}",
-                i18n::LOCALES.lookup(lang, "copy-navicust-image-to-clipboard").unwrap(),
+                i18n::LOCALES.lookup(lang, "copy-navicust-image-to-clipboard"),
             ))
             .clicked()
         {
@@ -748,8 +818,35 @@ pub fn show<'a>(
                 });
             })()
         }
+
+        if ui
+            .button(format!("📋 {}", i18n::LOCALES.lookup(lang, "copy-share-code")))
+            .clicked()
+        {
+            let _ = clipboard.set_text(sharecode::NavicustCode::export(navicust_view, assets.as_ref()));
+        }
+        if ui
+            .button(format!("📥 {}", i18n::LOCALES.lookup(lang, "paste-share-code")))
+            .clicked()
+        {
+            import = Some(
+                clipboard
+                    .get_text()
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| sharecode::NavicustCode::parse(&text).map_err(|e| e.to_string()))
+                    .map(|code| {
+                        let (view, unmatched) = code.resolve(navicust_view, assets.as_ref());
+                        super::Imported { view, unmatched }
+                    }),
+            );
+        }
     });
 
+    let dismiss_import = state
+        .import
+        .as_ref()
+        .map_or(false, |status| super::show_import_status(ui, lang, status));
+
     egui::ScrollArea::vertical()
         .id_source("navicust-view")
         .auto_shrink([false, false])
@@ -761,23 +858,44 @@ pub fn show<'a>(
                     egui::Layout::left_to_right(egui::Align::Min)
                 },
                 |ui| {
-                    if !state.rendered_navicust_cache.is_some() {
-                        let composed = compose_navicust(navicust_view, assets);
-                        let image = render_navicust(
-                            &composed,
-                            navicust_view,
-                            assets,
-                            font_families.raw_for_language(game_lang),
-                        );
-                        let texture = ui.ctx().load_texture(
-                            "navicust",
-                            egui::ColorImage::from_rgba_unmultiplied(
-                                [image.width() as usize, image.height() as usize],
-                                &image,
-                            ),
-                            egui::TextureFilter::Nearest,
-                        );
-                        state.rendered_navicust_cache = Some((image, composed, texture));
+                    if state.rendered_navicust_cache.is_none() && state.rendering.is_none() {
+                        // Rendering takes long enough to stall a frame, so it's done in the background.
+                        let (tx, rx) = std::sync::mpsc::channel();
+                        let egui_ctx = ui.ctx().clone();
+                        tokio::task::spawn_blocking({
+                            let snapshot = Snapshot::new(navicust_view);
+                            let assets = assets.clone();
+                            let raw_font = font_families.raw_for_language(game_lang);
+                            move || {
+                                let composed = compose_navicust(&snapshot, assets.as_ref());
+                                let image = render_navicust(&composed, &snapshot, assets.as_ref(), raw_font);
+                                let _ = tx.send((image, composed));
+                                egui_ctx.request_repaint();
+                            }
+                        });
+                        state.rendering = Some(rx);
+                    }
+
+                    if let Some(rendering) = state.rendering.as_ref() {
+                        match rendering.try_recv() {
+                            Ok((image, composed)) => {
+                                let texture = ui.ctx().load_texture(
+                                    "navicust",
+                                    egui::ColorImage::from_rgba_unmultiplied(
+                                        [image.width() as usize, image.height() as usize],
+                                        &image,
+                                    ),
+                                    egui::TextureFilter::Nearest,
+                                );
+                                state.rendered_navicust_cache = Some((image, composed, texture));
+                                state.rendering = None;
+                            }
+                            Err(std::sync::mpsc::TryRecvError::Empty) => {
+                                ui.spinner();
+                            }
+                            // Rendering failed, which has already been reported: leave the space empty rather than trying again every frame.
+                            Err(std::sync::mpsc::TryRecvError::Disconnected) => {}
+                        }
                     }
 
                     if let Some((image, composed, texture_handle)) = state.rendered_navicust_cache.as_ref() {
@@ -853,4 +971,13 @@ pub fn show<'a>(
                 },
             );
         });
+
+    if dismiss_import {
+        state.import = None;
+        state.invalidate();
+    }
+    if let Some(import) = import {
+        state.import = Some(import);
+        state.invalidate();
+    }
 }
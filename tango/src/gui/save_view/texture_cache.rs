@@ -0,0 +1,383 @@
+use crate::{game, rom};
+
+/// How many textures each save view keeps loaded, unless configured otherwise.
+pub const DEFAULT_CAPACITY: usize = 300;
+
+/// How many decoded images are uploaded as textures each frame, so a screenful of new icons is spread over a few frames rather than holding one up.
+const UPLOADS_PER_FRAME: usize = 4;
+
+static CAPACITY: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(DEFAULT_CAPACITY);
+static LIVE_ENTRIES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+static LIVE_BYTES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+static LOADS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+static EVICTIONS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Sets how many textures each cache may hold. Caches over it shrink the next time they load something.
+pub fn set_capacity(capacity: usize) {
+    CAPACITY.store(capacity.max(1), std::sync::atomic::Ordering::Relaxed);
+}
+
+/// What all caches are holding and have done so far, for checking that they don't grow when they shouldn't.
+#[derive(Clone, Copy, Debug)]
+pub struct Metrics {
+    pub entries: usize,
+    /// Roughly how much video memory the textures take.
+    pub bytes: usize,
+    pub loads: usize,
+    pub evictions: usize,
+}
+
+pub fn metrics() -> Metrics {
+    Metrics {
+        entries: LIVE_ENTRIES.load(std::sync::atomic::Ordering::Relaxed),
+        bytes: LIVE_BYTES.load(std::sync::atomic::Ordering::Relaxed),
+        loads: LOADS.load(std::sync::atomic::Ordering::Relaxed),
+        evictions: EVICTIONS.load(std::sync::atomic::Ordering::Relaxed),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CachedAssetType {
+    ChipIcon,
+    ChipImage,
+    ElementIcon,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Key {
+    Asset(CachedAssetType, usize),
+    /// A game's own art, with the game going by its ROM code and revision.
+    GameArt(game::ArtKind, [u8; 4], u8),
+    File(std::path::PathBuf),
+}
+
+/// Where an image is decoded from.
+enum Source {
+    Asset {
+        assets: std::sync::Arc<dyn rom::Assets + Send + Sync>,
+        asset_type: CachedAssetType,
+        id: usize,
+    },
+    Png(&'static [u8]),
+    File(std::path::PathBuf),
+}
+
+fn decode_png(buf: &[u8]) -> Option<egui::ColorImage> {
+    match image::load_from_memory(buf) {
+        Ok(image) => {
+            let image = image.to_rgba8();
+            Some(egui::ColorImage::from_rgba_unmultiplied(
+                [image.width() as usize, image.height() as usize],
+                &image,
+            ))
+        }
+        Err(e) => {
+            log::error!("failed to decode image: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Decodes an image, e.g. an asset's out of the ROM. This is the slow part of showing one, so it's done off the UI thread.
+fn decode(source: &Source) -> Option<egui::ColorImage> {
+    match source {
+        Source::Asset { assets, asset_type, id } => decode_asset(assets.as_ref(), *asset_type, *id),
+        Source::Png(buf) => decode_png(buf),
+        Source::File(path) => match std::fs::read(path) {
+            Ok(buf) => decode_png(&buf),
+            Err(e) => {
+                log::error!("failed to read image {}: {:?}", path.display(), e);
+                None
+            }
+        },
+    }
+}
+
+fn decode_asset(
+    assets: &(dyn rom::Assets + Send + Sync),
+    asset_type: CachedAssetType,
+    id: usize,
+) -> Option<egui::ColorImage> {
+    match asset_type {
+        CachedAssetType::ChipIcon => assets.chip(id).map(|info| {
+            egui::ColorImage::from_rgba_unmultiplied(
+                [14, 14],
+                &image::imageops::crop_imm(&info.icon(), 1, 1, 14, 14).to_image(),
+            )
+        }),
+        CachedAssetType::ChipImage => assets.chip(id).map(|info| {
+            let image = info.image();
+            egui::ColorImage::from_rgba_unmultiplied([image.width() as usize, image.height() as usize], &image)
+        }),
+        CachedAssetType::ElementIcon => assets.element_icon(id).map(|image| {
+            egui::ColorImage::from_rgba_unmultiplied(
+                [14, 14],
+                &image::imageops::crop_imm(&image, 1, 1, 14, 14).to_image(),
+            )
+        }),
+    }
+}
+
+struct Request {
+    key: Key,
+    generation: u64,
+    source: Source,
+}
+
+struct Decoded {
+    key: Key,
+    generation: u64,
+    image: Option<egui::ColorImage>,
+}
+
+/// A thread decoding images for a cache. It stops once the cache is dropped.
+struct Decoder {
+    requests: std::sync::mpsc::Sender<Request>,
+    results: std::sync::mpsc::Receiver<Decoded>,
+}
+
+impl Decoder {
+    fn spawn(ctx: &egui::Context) -> std::io::Result<Self> {
+        let (requests_tx, requests_rx) = std::sync::mpsc::channel::<Request>();
+        let (results_tx, results_rx) = std::sync::mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::Builder::new()
+            .name("texture decoder".to_string())
+            .spawn(move || {
+                for request in requests_rx {
+                    let decoded = Decoded {
+                        image: decode(&request.source),
+                        key: request.key,
+                        generation: request.generation,
+                    };
+                    if results_tx.send(decoded).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            })?;
+        Ok(Self {
+            requests: requests_tx,
+            results: results_rx,
+        })
+    }
+}
+
+/// What the cache has for an asset.
+pub enum Lookup<'a> {
+    Ready(&'a egui::TextureHandle),
+    /// Still being decoded: show a placeholder for now.
+    Pending,
+    /// There's no image for it.
+    Missing,
+}
+
+/// Takes up the space of an image that's still being decoded.
+pub fn placeholder(ui: &mut egui::Ui, size: egui::Vec2) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    ui.painter().rect_filled(rect, 2.0, ui.visuals().faint_bg_color);
+    response
+}
+
+struct Entry {
+    texture_handle: egui::TextureHandle,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// Textures for game assets and art, keeping only the most recently used ones so scrolling through folders and switching saves doesn't keep piling them up.
+///
+/// Images are decoded on a background thread and uploaded a few per frame, so opening a view full of icons for the first time doesn't stall. Textures are freed as soon as they're evicted, since the cache holds the only handle to them.
+pub struct TextureCache {
+    entries: std::collections::HashMap<Key, Entry>,
+    clock: u64,
+    decoder: Option<Decoder>,
+    pending: std::collections::HashSet<Key>,
+    missing: std::collections::HashSet<Key>,
+    /// Bumped whenever the cache is cleared, so images decoded from the old assets are thrown away when they arrive.
+    generation: u64,
+}
+
+impl TextureCache {
+    pub fn new() -> Self {
+        Self {
+            entries: std::collections::HashMap::new(),
+            clock: 0,
+            decoder: None,
+            pending: std::collections::HashSet::new(),
+            missing: std::collections::HashSet::new(),
+            generation: 0,
+        }
+    }
+
+    /// Gets the texture for an asset, asking for it to be decoded if it isn't loaded yet.
+    pub fn get(
+        &mut self,
+        ctx: &egui::Context,
+        assets: &std::sync::Arc<dyn rom::Assets + Send + Sync>,
+        asset_type: CachedAssetType,
+        id: usize,
+    ) -> Lookup<'_> {
+        self.lookup(ctx, Key::Asset(asset_type, id), || Source::Asset {
+            assets: assets.clone(),
+            asset_type,
+            id,
+        })
+    }
+
+    /// Gets the texture for the art a game ships with. Games without that kind of art are always missing it.
+    pub fn get_game_art(
+        &mut self,
+        ctx: &egui::Context,
+        game: &'static (dyn game::Game + Send + Sync),
+        kind: game::ArtKind,
+    ) -> Lookup<'_> {
+        let buf = if let Some(buf) = game.art(kind) {
+            buf
+        } else {
+            return Lookup::Missing;
+        };
+        let (rom_code, revision) = game.rom_code_and_revision();
+        self.lookup(ctx, Key::GameArt(kind, *rom_code, revision), || Source::Png(buf))
+    }
+
+    /// Gets the texture for a PNG file, e.g. a patch's banner.
+    pub fn get_file(&mut self, ctx: &egui::Context, path: &std::path::Path) -> Lookup<'_> {
+        self.lookup(ctx, Key::File(path.to_path_buf()), || Source::File(path.to_path_buf()))
+    }
+
+    fn lookup(&mut self, ctx: &egui::Context, key: Key, source: impl FnOnce() -> Source) -> Lookup<'_> {
+        self.clock += 1;
+
+        if self.entries.contains_key(&key) {
+            let entry = self.entries.get_mut(&key).unwrap();
+            entry.last_used = self.clock;
+            return Lookup::Ready(&entry.texture_handle);
+        }
+        if self.missing.contains(&key) {
+            return Lookup::Missing;
+        }
+        if self.pending.contains(&key) {
+            return Lookup::Pending;
+        }
+
+        if self.decoder.is_none() {
+            match Decoder::spawn(ctx) {
+                Ok(decoder) => {
+                    self.decoder = Some(decoder);
+                }
+                Err(e) => {
+                    log::error!("failed to start texture decoder, decoding on the ui thread: {:?}", e);
+                }
+            }
+        }
+        let request = Request {
+            key: key.clone(),
+            generation: self.generation,
+            source: source(),
+        };
+        let request = match self.decoder.as_ref() {
+            Some(decoder) => match decoder.requests.send(request) {
+                Ok(()) => {
+                    self.pending.insert(key);
+                    return Lookup::Pending;
+                }
+                Err(std::sync::mpsc::SendError(request)) => request,
+            },
+            None => request,
+        };
+        if let Some(image) = decode(&request.source) {
+            self.insert(ctx, key.clone(), image);
+            self.entries
+                .get(&key)
+                .map(|entry| Lookup::Ready(&entry.texture_handle))
+                .unwrap_or(Lookup::Missing)
+        } else {
+            self.missing.insert(key);
+            Lookup::Missing
+        }
+    }
+
+    /// Uploads images decoded since the last frame, a few at a time. This should be called once a frame, before anything is drawn from the cache.
+    pub fn upload_decoded(&mut self, ctx: &egui::Context) {
+        let mut uploaded = 0;
+        while uploaded < UPLOADS_PER_FRAME {
+            let decoded = if let Some(decoded) = self
+                .decoder
+                .as_ref()
+                .and_then(|decoder| decoder.results.try_recv().ok())
+            {
+                decoded
+            } else {
+                return;
+            };
+            if decoded.generation != self.generation {
+                continue;
+            }
+            self.pending.remove(&decoded.key);
+            if let Some(image) = decoded.image {
+                self.insert(ctx, decoded.key, image);
+                uploaded += 1;
+            } else {
+                self.missing.insert(decoded.key);
+            }
+        }
+        // There may be more waiting, which the decoder won't ask to repaint for again.
+        ctx.request_repaint();
+    }
+
+    fn insert(&mut self, ctx: &egui::Context, key: Key, image: egui::ColorImage) {
+        self.evict_to(CAPACITY.load(std::sync::atomic::Ordering::Relaxed) - 1);
+        // Textures are uploaded as RGBA8, whatever the image looked like.
+        let bytes = image.pixels.len() * 4;
+        let texture_handle = ctx.load_texture(format!("{:?}", key), image, egui::TextureFilter::Nearest);
+        self.entries.insert(
+            key,
+            Entry {
+                texture_handle,
+                bytes,
+                last_used: self.clock,
+            },
+        );
+        LOADS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        LIVE_ENTRIES.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        LIVE_BYTES.fetch_add(bytes, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Frees textures, least recently used first, until there are at most `capacity` left.
+    fn evict_to(&mut self, capacity: usize) {
+        while self.entries.len() > capacity {
+            let key = if let Some((key, _)) = self.entries.iter().min_by_key(|(_, entry)| entry.last_used) {
+                key.clone()
+            } else {
+                break;
+            };
+            self.remove(&key);
+            EVICTIONS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            LIVE_ENTRIES.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+            LIVE_BYTES.fetch_sub(entry.bytes, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    /// Frees every texture, e.g. because the assets they were made from changed.
+    pub fn clear(&mut self) {
+        let keys = self.entries.keys().cloned().collect::<Vec<_>>();
+        for key in keys {
+            self.remove(&key);
+        }
+        self.pending.clear();
+        self.missing.clear();
+        self.generation += 1;
+    }
+}
+
+impl Drop for TextureCache {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -1,23 +1,41 @@
-use fluent_templates::Loader;
-
-use crate::{discord, gui, i18n, input, session, stats, sync, video};
+use crate::{assist, crashreport, discord, gui, i18n, input, session, stats, sync, video};
 
+mod focus_pause;
+mod match_timer;
+mod practice_overlay;
+mod practice_panel;
 mod replay_controls_window;
+mod save_states_window;
+mod scoreboard;
+mod shadow_view;
 
 pub struct State {
     vbuf: Option<VBuf>,
+    /// Marked whenever a new emulator frame is presented, as opposed to every time the UI is drawn.
+    presentation_counter: stats::Counter,
     opponent_save_view: gui::save_view::State,
     own_save_view: gui::save_view::State,
     debug_window: Option<gui::debug_window::State>,
+    save_states_window: save_states_window::State,
+    shadow_view: shadow_view::State,
+    focus_pause: focus_pause::State,
+    practice_panel: practice_panel::State,
+    replay_controls_window: replay_controls_window::State,
 }
 
 impl State {
     pub fn new() -> State {
         Self {
             vbuf: None,
+            presentation_counter: stats::Counter::new(stats::window::PRESENTATION),
             opponent_save_view: gui::save_view::State::new(),
             own_save_view: gui::save_view::State::new(),
             debug_window: None,
+            save_states_window: save_states_window::State::new(),
+            shadow_view: shadow_view::State::new(),
+            focus_pause: focus_pause::State::new(),
+            practice_panel: practice_panel::State::new(),
+            replay_controls_window: replay_controls_window::State::new(),
         }
     }
 }
@@ -25,6 +43,12 @@ impl State {
 struct VBuf {
     image: egui::ColorImage,
     texture: egui::TextureHandle,
+    /// Which emulator frame was taken last, so the texture is only updated when there's something new to show.
+    frame: Option<u64>,
+    frame_taken_at: std::time::Instant,
+    /// The last two frames taken, after filtering, for interpolating between.
+    previous: Vec<u8>,
+    current: Vec<u8>,
 }
 
 impl VBuf {
@@ -36,6 +60,10 @@ impl VBuf {
                 egui::ColorImage::new([width, height], egui::Color32::BLACK),
                 egui::TextureFilter::Nearest,
             ),
+            frame: None,
+            frame_taken_at: std::time::Instant::now(),
+            previous: vec![0u8; width * height * 4],
+            current: vec![0u8; width * height * 4],
         }
     }
 }
@@ -46,7 +74,9 @@ fn show_emulator(
     video_filter: &str,
     max_scale: u32,
     integer_scaling: bool,
+    frame_interpolation: bool,
     vbuf: &mut Option<VBuf>,
+    presentation_counter: &mut stats::Counter,
 ) {
     let video_filter = video::filter_by_name(video_filter).unwrap_or(Box::new(video::NullFilter));
 
@@ -65,13 +95,35 @@ fn show_emulator(
         vbuf.as_mut().unwrap()
     };
 
-    video_filter.apply(
-        &session.lock_vbuf(),
-        bytemuck::cast_slice_mut(&mut vbuf.image.pixels[..]),
-        (mgba::gba::SCREEN_WIDTH as usize, mgba::gba::SCREEN_HEIGHT as usize),
-    );
-
-    vbuf.texture.set(vbuf.image.clone(), egui::TextureFilter::Nearest);
+    // The emulator runs on its own clock: each time we present, we show whichever frame it completed last.
+    let (frame, emu_vbuf) = session.latest_frame();
+    let is_new_frame = vbuf.frame != Some(frame);
+    if is_new_frame {
+        std::mem::swap(&mut vbuf.previous, &mut vbuf.current);
+        video_filter.apply(
+            &emu_vbuf,
+            &mut vbuf.current,
+            (mgba::gba::SCREEN_WIDTH as usize, mgba::gba::SCREEN_HEIGHT as usize),
+        );
+        vbuf.frame = Some(frame);
+        vbuf.frame_taken_at = std::time::Instant::now();
+        presentation_counter.mark();
+    }
+    drop(emu_vbuf);
+
+    if frame_interpolation {
+        // This shows everything one frame late, fading into each frame over the time the emulator takes to produce one.
+        video::pacing::interpolate(
+            &vbuf.previous,
+            &vbuf.current,
+            vbuf.frame_taken_at.elapsed().as_secs_f32() * session.fps_target(),
+            bytemuck::cast_slice_mut(&mut vbuf.image.pixels[..]),
+        );
+        vbuf.texture.set(vbuf.image.clone(), egui::TextureFilter::Nearest);
+    } else if is_new_frame {
+        bytemuck::cast_slice_mut(&mut vbuf.image.pixels[..]).copy_from_slice(&vbuf.current);
+        vbuf.texture.set(vbuf.image.clone(), egui::TextureFilter::Nearest);
+    }
 
     let mut scaling_factor = std::cmp::min_by(
         ui.available_width() * ui.ctx().pixels_per_point() / mgba::gba::SCREEN_WIDTH as f32,
@@ -94,23 +146,78 @@ fn show_emulator(
             mgba::gba::SCREEN_HEIGHT as f32 * scaling_factor as f32 / ui.ctx().pixels_per_point(),
         ),
     );
-    ui.ctx().request_repaint();
+    if session.is_idle() {
+        // Nothing's moving fast enough to need presenting more often than frames come in.
+        ui.ctx()
+            .request_repaint_after(std::time::Duration::from_secs_f32(1.0 / session.fps_target().max(1.0)));
+    } else {
+        ui.ctx().request_repaint();
+    }
 }
 
+/// Offers to end a session whose emulator stopped responding, returning true if the user chose to.
+fn show_hang_window(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    session: &session::Session,
+    hang: &stats::Hang,
+) -> bool {
+    let mut force_terminate = false;
+    egui::Window::new(i18n::LOCALES.lookup(language, "session-hung"))
+        .id(egui::Id::new("session-hung-window"))
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(i18n::LOCALES.lookup_with_args(
+                language,
+                if let session::Mode::PvP(_) = session.mode() {
+                    "session-hung-description-pvp"
+                } else {
+                    "session-hung-description"
+                },
+                &std::collections::HashMap::from([("seconds", hang.stalled_for.as_secs().into())]),
+            ));
+            ui.horizontal(|ui| {
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "session-hung-keep-waiting"))
+                    .clicked()
+                {
+                    session.keep_waiting();
+                }
+                if ui.button(i18n::LOCALES.lookup(language, "session-hung-end")).clicked() {
+                    force_terminate = true;
+                }
+            });
+        });
+    force_terminate
+}
+
+/// Shows a session, returning true if it should be force-terminated because its emulator stopped responding.
 pub fn show(
     ctx: &egui::Context,
     language: &unic_langid::LanguageIdentifier,
+    game_data_language: Option<&unic_langid::LanguageIdentifier>,
     clipboard: &mut arboard::Clipboard,
     font_families: &gui::FontFamilies,
     input_state: &input::State,
     input_mapping: &input::Mapping,
+    assist_settings: &assist::Settings,
     session: &session::Session,
     video_filter: &str,
     integer_scaling: bool,
+    frame_interpolation: bool,
     volume: i32,
     max_scale: u32,
     show_own_setup: bool,
+    show_practice_overlay: bool,
+    window_focused: bool,
+    pause_on_focus_loss: bool,
     crashstates_path: &std::path::Path,
+    crash_reports_path: &std::path::Path,
+    hang_timeout: std::time::Duration,
+    logs_path: &std::path::Path,
+    replays_path: &std::path::Path,
     last_mouse_motion_time: &Option<std::time::Instant>,
     show_escape_window: &mut Option<gui::escape_window::State>,
     fps_counter: std::sync::Arc<parking_lot::Mutex<stats::Counter>>,
@@ -118,11 +225,55 @@ pub fn show(
     show_debug: bool,
     state: &mut State,
     discord_client: &mut discord::Client,
-) {
+) -> bool {
+    if let Some(hang) = session.check_watchdog(hang_timeout) {
+        let description = session.describe_hang(&hang);
+        log::error!("emulator stopped responding: {}", description);
+        let crash_reports_path = crash_reports_path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            match crashreport::write_report_for_hang(&crash_reports_path, &description) {
+                Ok(path) => {
+                    log::error!("wrote hang report to {}", path.display());
+                }
+                Err(e) => {
+                    log::error!("failed to write hang report: {:?}", e);
+                }
+            }
+        });
+    }
+
+    // Most of what's below waits on the emulator one way or another, so while it's hung, all that's shown is the last frame it finished and what can be done about it.
+    if let Some(hang) = session.hang() {
+        *show_escape_window = None;
+        egui::CentralPanel::default()
+            .frame(egui::Frame::none().fill(egui::Color32::BLACK))
+            .show(ctx, |ui| {
+                ui.with_layout(
+                    egui::Layout::centered_and_justified(egui::Direction::LeftToRight),
+                    |ui| {
+                        show_emulator(
+                            ui,
+                            session,
+                            video_filter,
+                            max_scale,
+                            integer_scaling,
+                            false,
+                            &mut state.vbuf,
+                            &mut state.presentation_counter,
+                        );
+                    },
+                );
+            });
+        return show_hang_window(ctx, language, session, &hang);
+    }
+
     session.set_master_volume(volume);
     session.set_joyflags(input_mapping.to_mgba_keys(input_state));
 
-    if input_mapping.menu.iter().any(|c| c.is_pressed(input_state)) {
+    // Keys pressed in other windows aren't meant for us.
+    let hotkeys_enabled = window_focused;
+
+    if hotkeys_enabled && input::is_any_pressed(&input_mapping.menu, input_state) {
         *show_escape_window = if show_escape_window.is_some() {
             None
         } else {
@@ -159,25 +310,81 @@ pub fn show(
                 )),
             )));
         }
-        session::Mode::Replayer => {
+        session::Mode::Replayer(_) => {
             discord_client.set_current_activity(Some(discord::make_base_activity(None)));
         }
     }
 
     match session.mode() {
         session::Mode::SinglePlayer(_) => {
-            session.set_fps_target(if input_mapping.speed_up.iter().any(|c| c.is_active(&input_state)) {
+            let speed_up = hotkeys_enabled && input_mapping.speed_up.iter().any(|c| c.is_active(&input_state));
+            let slow_motion =
+                hotkeys_enabled && !speed_up && input_mapping.slow_motion.iter().any(|c| c.is_active(&input_state));
+            session.set_assist(assist_settings, slow_motion);
+            session.set_fps_target(if speed_up {
                 session::EXPECTED_FPS * 3.0
+            } else if slow_motion {
+                session::EXPECTED_FPS * assist_settings.slow_motion_percent as f32 / 100.0
             } else {
                 session::EXPECTED_FPS
             });
+
+            if hotkeys_enabled {
+                if input::is_any_pressed(&input_mapping.save_states, input_state) {
+                    state.save_states_window.toggle();
+                }
+                if input::is_any_pressed(&input_mapping.save_state, input_state) {
+                    state.save_states_window.save(session);
+                }
+                if input::is_any_pressed(&input_mapping.load_state, input_state) {
+                    state.save_states_window.load(session);
+                }
+            }
+            save_states_window::show(ctx, language, session, &mut state.save_states_window);
         }
-        session::Mode::Replayer => {
-            replay_controls_window::show(ctx, session, language, last_mouse_motion_time);
+        session::Mode::Replayer(replayer) => {
+            replay_controls_window::show(
+                ctx,
+                session,
+                replayer,
+                language,
+                input_state,
+                input_mapping,
+                hotkeys_enabled,
+                replays_path,
+                last_mouse_motion_time,
+                &mut state.replay_controls_window,
+            );
         }
         _ => {}
     }
 
+    focus_pause::show(
+        ctx,
+        language,
+        session,
+        window_focused,
+        pause_on_focus_loss,
+        &mut state.focus_pause,
+    );
+
+    if show_practice_overlay {
+        if let Some(recorder) = session.practice_recorder() {
+            practice_overlay::show(ctx, recorder, language, logs_path);
+        }
+    }
+
+    if let session::Mode::SinglePlayer(single_player) = session.mode() {
+        if let Some(manipulator) = single_player.practice_manipulator() {
+            if show_practice_overlay {
+                practice_panel::show(ctx, manipulator, language, &mut state.practice_panel);
+            }
+            if manipulator.is_modified() {
+                practice_panel::show_modified_watermark(ctx, language);
+            }
+        }
+    }
+
     // If we've crashed, log the error and panic.
     if let Some(thread_handle) = session.has_crashed() {
         // HACK: No better way to lock the core.
@@ -229,14 +436,14 @@ cpsr = {:08x}"#,
                 egui::ScrollArea::horizontal()
                     .auto_shrink([false, false])
                     .show(ui, |ui| {
-                        ui.heading(i18n::LOCALES.lookup(language, "own-setup").unwrap());
+                        ui.heading(i18n::LOCALES.lookup(language, "own-setup"));
                         gui::save_view::show(
                             ui,
                             false,
                             clipboard,
                             font_families,
                             language,
-                            &own_setup.game_lang,
+                            game_data_language.unwrap_or(&own_setup.game_lang),
                             &own_setup.save,
                             &own_setup.assets,
                             &mut state.own_save_view,
@@ -252,14 +459,14 @@ cpsr = {:08x}"#,
             egui::ScrollArea::horizontal()
                 .auto_shrink([false, false])
                 .show(ui, |ui| {
-                    ui.heading(i18n::LOCALES.lookup(language, "opponent-setup").unwrap());
+                    ui.heading(i18n::LOCALES.lookup(language, "opponent-setup"));
                     gui::save_view::show(
                         ui,
                         false,
                         clipboard,
                         font_families,
                         language,
-                        &opponent_setup.game_lang,
+                        game_data_language.unwrap_or(&opponent_setup.game_lang),
                         &opponent_setup.save,
                         &opponent_setup.assets,
                         &mut state.opponent_save_view,
@@ -275,13 +482,76 @@ cpsr = {:08x}"#,
             ui.with_layout(
                 egui::Layout::centered_and_justified(egui::Direction::LeftToRight),
                 |ui| {
-                    show_emulator(ui, session, video_filter, max_scale, integer_scaling, &mut state.vbuf);
+                    show_emulator(
+                        ui,
+                        session,
+                        video_filter,
+                        max_scale,
+                        integer_scaling,
+                        frame_interpolation,
+                        &mut state.vbuf,
+                        &mut state.presentation_counter,
+                    );
                 },
             );
         });
 
     gui::debug_window::show(ctx, language, session, &mut state.debug_window);
 
+    if let session::Mode::PvP(pvp) = session.mode() {
+        if let Some(match_) = &*sync::block_on(pvp.match_.lock()) {
+            match_timer::show(ctx, language, match_);
+            if show_debug {
+                if let Some(view) = match_.shadow_view() {
+                    shadow_view::show(ctx, language, match_, view, &mut state.shadow_view);
+                }
+            }
+        }
+        scoreboard::show(ctx, language, pvp);
+
+        if let Some(desync) = pvp.desync() {
+            egui::Window::new(i18n::LOCALES.lookup(language, "desync-detected"))
+                .id(egui::Id::new("desync-detected-window"))
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .resizable(false)
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    ui.label(i18n::LOCALES.lookup_with_args(
+                        language,
+                        "desync-detected-description",
+                        &std::collections::HashMap::from([
+                            ("round", desync.round_number.to_string().into()),
+                            ("tick", desync.tick.to_string().into()),
+                        ]),
+                    ));
+                });
+        }
+    }
+
+    if session.was_suspended() {
+        egui::Window::new(i18n::LOCALES.lookup(language, "system-suspended"))
+            .id(egui::Id::new("system-suspended-window"))
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(i18n::LOCALES.lookup(
+                    language,
+                    if let session::Mode::PvP(_) = session.mode() {
+                        "system-suspended-description-pvp"
+                    } else {
+                        "system-suspended-description"
+                    },
+                ));
+                if ui
+                    .button(i18n::LOCALES.lookup(language, "system-suspended-continue"))
+                    .clicked()
+                {
+                    session.resume_after_suspend();
+                }
+            });
+    }
+
     const HIDE_AFTER: std::time::Duration = std::time::Duration::from_secs(3);
     if last_mouse_motion_time
         .map(|t| std::time::Instant::now() - t < HIDE_AFTER)
@@ -322,6 +592,8 @@ cpsr = {:08x}"#,
                                 round.local_delay(),
                                 round.current_tick(),
                                 round.local_player_index(),
+                                round.fastforward_cost(),
+                                round.prediction_stats(),
                             )),
                         )
                     })();
@@ -330,7 +602,7 @@ cpsr = {:08x}"#,
                         let debug_window_open = state.debug_window.is_some();
                         if ui
                             .selectable_label(debug_window_open, "🪲")
-                            .on_hover_text(i18n::LOCALES.lookup(language, "debug").unwrap())
+                            .on_hover_text(i18n::LOCALES.lookup(language, "debug"))
                             .clicked()
                         {
                             state.debug_window = if state.debug_window.is_some() {
@@ -342,24 +614,47 @@ cpsr = {:08x}"#,
                         ui.add(egui::Separator::default().vertical());
                     }
 
-                    ui.monospace(format!(
-                        "fps {:7.2}",
-                        1.0 / fps_counter.lock().mean_duration().as_secs_f32()
-                    ));
+                    {
+                        let fps_counter = fps_counter.lock();
+                        ui.monospace(format!(
+                            "fps {:7.2} (p95 {:5.2}ms)",
+                            1.0 / fps_counter.mean_duration().as_secs_f32(),
+                            fps_counter.percentiles().p95.as_secs_f32() * 1000.0
+                        ));
+                    }
 
                     ui.add(egui::Separator::default().vertical());
                     ui.monospace(format!(
-                        "tps {:7.2} ({:+5.2})",
-                        1.0 / emu_tps_counter.lock().mean_duration().as_secs_f32(),
-                        tps_adjustment
+                        "present {:7.2}",
+                        1.0 / state.presentation_counter.mean_duration().as_secs_f32()
                     ));
 
+                    ui.add(egui::Separator::default().vertical());
+                    {
+                        let emu_tps_counter = emu_tps_counter.lock();
+                        ui.monospace(format!(
+                            "tps {:7.2} ({:+5.2}, p95 {:5.2}ms)",
+                            1.0 / emu_tps_counter.mean_duration().as_secs_f32(),
+                            tps_adjustment,
+                            emu_tps_counter.percentiles().p95.as_secs_f32() * 1000.0
+                        ));
+                    }
+
                     if let Some(latency) = latency {
                         ui.add(egui::Separator::default().vertical());
                         ui.monospace(format!("ping {:4}ms", latency.as_millis()));
                     }
 
-                    if let Some((local_qlen, remote_qlen, local_delay, current_tick, local_player_index)) = round_info {
+                    if let Some((
+                        local_qlen,
+                        remote_qlen,
+                        local_delay,
+                        current_tick,
+                        local_player_index,
+                        (fastforward_ticks, fastforward_tick_cost),
+                        prediction_stats,
+                    )) = round_info
+                    {
                         if show_debug {
                             ui.add(egui::Separator::default().vertical());
                             ui.monospace(format!(
@@ -369,6 +664,20 @@ cpsr = {:08x}"#,
 
                             ui.add(egui::Separator::default().vertical());
                             ui.monospace(format!("tick {:5}", current_tick));
+
+                            ui.add(egui::Separator::default().vertical());
+                            ui.monospace(format!(
+                                "ff {:2} × {:4}µs",
+                                fastforward_ticks,
+                                fastforward_tick_cost.as_micros()
+                            ));
+
+                            ui.add(egui::Separator::default().vertical());
+                            ui.monospace(format!(
+                                "mispredict {:5.1}% ({:5}B)",
+                                prediction_stats.misprediction_rate() * 100.0,
+                                prediction_stats.mispredicted_bytes
+                            ));
                         }
 
                         ui.add(egui::Separator::default().vertical());
@@ -380,4 +689,6 @@ cpsr = {:08x}"#,
             });
         });
     }
+
+    false
 }
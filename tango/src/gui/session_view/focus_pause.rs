@@ -0,0 +1,111 @@
+use crate::{gui, i18n, session};
+
+/// How long to count down before resuming, so the player has their hands back on the controls first.
+const COUNTDOWN: std::time::Duration = std::time::Duration::from_secs(3);
+
+enum Phase {
+    Paused,
+    Resuming(std::time::Instant),
+}
+
+pub struct State {
+    /// Set while the session is paused because the window lost focus. Sessions the player paused themselves are left alone.
+    phase: Option<Phase>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self { phase: None }
+    }
+}
+
+/// Pauses local sessions while the window isn't focused, counting down before resuming once it is again.
+///
+/// Netplay sessions are never paused, as that would stall the opponent: instead, the screen is outlined as a reminder that the game still takes input.
+pub fn show(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    session: &session::Session,
+    window_focused: bool,
+    pause_on_focus_loss: bool,
+    state: &mut State,
+) {
+    if let session::Mode::PvP(_) = session.mode() {
+        if !window_focused {
+            show_unfocused_indicator(ctx, language);
+        }
+        return;
+    }
+
+    if state.phase.is_some() && !session.is_paused() {
+        // The player unpaused it themselves.
+        state.phase = None;
+    }
+
+    if !window_focused {
+        match state.phase {
+            None if pause_on_focus_loss && !session.is_paused() => {
+                session.set_paused(true);
+                state.phase = Some(Phase::Paused);
+            }
+            Some(Phase::Resuming(_)) => {
+                state.phase = Some(Phase::Paused);
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    let started_at = match state.phase {
+        None => {
+            return;
+        }
+        Some(Phase::Paused) => {
+            let now = std::time::Instant::now();
+            state.phase = Some(Phase::Resuming(now));
+            now
+        }
+        Some(Phase::Resuming(started_at)) => started_at,
+    };
+
+    let elapsed = started_at.elapsed();
+    if elapsed >= COUNTDOWN {
+        state.phase = None;
+        // If the system was suspended meanwhile, the player has to confirm they're ready to continue.
+        if !session.was_suspended() {
+            session.set_paused(false);
+        }
+        return;
+    }
+
+    ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("focus-pause-countdown"),
+    ))
+    .text(
+        ctx.screen_rect().center(),
+        egui::Align2::CENTER_CENTER,
+        (COUNTDOWN - elapsed).as_secs_f32().ceil().to_string(),
+        egui::FontId::proportional(96.0),
+        egui::Color32::WHITE,
+    );
+    ctx.request_repaint();
+}
+
+fn show_unfocused_indicator(ctx: &egui::Context, language: &unic_langid::LanguageIdentifier) {
+    let color = gui::theme::colors(ctx).warning;
+    ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("unfocused-indicator"),
+    ))
+    .rect_stroke(ctx.screen_rect().shrink(2.0), 0.0, egui::Stroke::new(4.0, color));
+
+    egui::Area::new("unfocused-indicator-label")
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -8.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.colored_label(color, i18n::LOCALES.lookup(language, "session-unfocused-netplay"));
+            });
+        });
+}
@@ -0,0 +1,82 @@
+use crate::{battle, i18n, session, sync};
+
+/// How much time has to be left in a time limited round for the timer to turn red.
+const LOW_TIME_TICKS: u32 = 10 * session::EXPECTED_FPS as u32;
+
+fn format_ticks(ticks: u32) -> String {
+    let secs = ticks / session::EXPECTED_FPS as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Shows how long the match and the current round have lasted.
+///
+/// This counts committed ticks rather than wall time, so it agrees with the opponent's and doesn't jump around when rolling back or fastforwarding.
+pub fn show(ctx: &egui::Context, language: &unic_langid::LanguageIdentifier, match_: &battle::Match) {
+    let (round_number, match_elapsed_ticks, round_ticks) = {
+        let round_state = sync::block_on(match_.lock_round_state());
+        (
+            round_state.number,
+            round_state.match_elapsed_ticks(),
+            round_state
+                .round
+                .as_ref()
+                .map(|round| (round.committed_tick(), round.round_time_limit_tick())),
+        )
+    };
+
+    egui::Area::new("match-timer")
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 4.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.monospace(i18n::LOCALES.lookup_with_args(
+                        language,
+                        "match-timer-match",
+                        &std::collections::HashMap::from([("time", format_ticks(match_elapsed_ticks).into())]),
+                    ));
+
+                    if !match_.records_replays() {
+                        ui.separator();
+                        ui.weak(i18n::LOCALES.lookup(language, "match-not-recorded"));
+                    }
+
+                    let (round_elapsed_ticks, round_time_limit_tick) = if let Some(round_ticks) = round_ticks {
+                        round_ticks
+                    } else {
+                        return;
+                    };
+
+                    ui.separator();
+                    let text = if let Some(round_time_limit_tick) = round_time_limit_tick {
+                        i18n::LOCALES.lookup_with_args(
+                            language,
+                            "match-timer-round-limited",
+                            &std::collections::HashMap::from([
+                                ("round", round_number.into()),
+                                ("time", format_ticks(round_elapsed_ticks).into()),
+                                ("limit", format_ticks(round_time_limit_tick).into()),
+                            ]),
+                        )
+                    } else {
+                        i18n::LOCALES.lookup_with_args(
+                            language,
+                            "match-timer-round",
+                            &std::collections::HashMap::from([
+                                ("round", round_number.into()),
+                                ("time", format_ticks(round_elapsed_ticks).into()),
+                            ]),
+                        )
+                    };
+                    if round_time_limit_tick
+                        .map(|limit| limit.saturating_sub(round_elapsed_ticks) <= LOW_TIME_TICKS)
+                        .unwrap_or(false)
+                    {
+                        ui.label(egui::RichText::new(text).monospace().color(egui::Color32::RED));
+                    } else {
+                        ui.monospace(text);
+                    }
+                });
+            });
+        });
+}
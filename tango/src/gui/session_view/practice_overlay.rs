@@ -0,0 +1,97 @@
+use crate::{i18n, practice};
+
+fn show_player(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    label_id: &str,
+    player: &practice::PlayerSample,
+) {
+    ui.strong(i18n::LOCALES.lookup(language, label_id));
+    ui.end_row();
+
+    if let Some(hp) = player.hp {
+        ui.label(i18n::LOCALES.lookup(language, "practice-overlay-hp"));
+        ui.monospace(hp.to_string());
+        ui.end_row();
+    }
+
+    if let Some(invulnerability_frames) = player.invulnerability_frames {
+        ui.label(i18n::LOCALES.lookup(language, "practice-overlay-invulnerability-frames"));
+        ui.monospace(invulnerability_frames.to_string());
+        ui.end_row();
+    }
+
+    if let Some(chip_in_hand) = player.chip_in_hand {
+        ui.label(i18n::LOCALES.lookup(language, "practice-overlay-chip-in-hand"));
+        ui.monospace(if let Some(chip_id) = chip_in_hand {
+            format!("#{:03}", chip_id)
+        } else {
+            i18n::LOCALES.lookup(language, "practice-overlay-chip-in-hand.none")
+        });
+        ui.end_row();
+    }
+}
+
+pub fn show(
+    ctx: &egui::Context,
+    recorder: &practice::Recorder,
+    language: &unic_langid::LanguageIdentifier,
+    logs_path: &std::path::Path,
+) {
+    let sample = recorder.latest();
+    let log_path = recorder.log_path();
+
+    egui::Window::new(i18n::LOCALES.lookup(language, "practice-overlay"))
+        .id(egui::Id::new("practice-overlay"))
+        .resizable(false)
+        .default_pos(egui::pos2(8.0, 8.0))
+        .show(ctx, |ui| {
+            if let Some(sample) = sample.as_ref() {
+                egui::Grid::new("practice-overlay-grid").num_columns(2).show(ui, |ui| {
+                    if let Some(custom_gauge_frames_remaining) = sample.custom_gauge_frames_remaining {
+                        ui.label(
+                            i18n::LOCALES
+                                .lookup(language, "practice-overlay-custom-gauge-frames-remaining"),
+                        );
+                        ui.monospace(custom_gauge_frames_remaining.to_string());
+                        ui.end_row();
+                    }
+
+                    show_player(ui, language, "practice-overlay-player", &sample.players[0]);
+                    show_player(ui, language, "practice-overlay-opponent", &sample.players[1]);
+                });
+            } else {
+                ui.label(i18n::LOCALES.lookup(language, "practice-overlay-not-in-battle"));
+            }
+
+            ui.separator();
+
+            let mut logging = log_path.is_some();
+            if ui
+                .checkbox(
+                    &mut logging,
+                    i18n::LOCALES.lookup(language, "practice-overlay-log-to-csv"),
+                )
+                .changed()
+            {
+                if logging {
+                    let path = logs_path.join(format!(
+                        "practice-{}.csv",
+                        time::OffsetDateTime::from(std::time::SystemTime::now())
+                            .format(time::macros::format_description!(
+                                "[year padding:zero][month padding:zero repr:numerical][day padding:zero][hour padding:zero][minute padding:zero][second padding:zero]"
+                            ))
+                            .expect("format time")
+                    ));
+                    if let Err(e) = recorder.start_log(&path) {
+                        log::error!("failed to start practice log: {:?}", e);
+                    }
+                } else {
+                    recorder.stop_log();
+                }
+            }
+            if let Some(log_path) = log_path.as_ref() {
+                ui.small(log_path.display().to_string());
+            }
+        });
+}
@@ -0,0 +1,109 @@
+use crate::{gui, i18n, practice};
+
+pub struct State {
+    hp: [u16; 2],
+    chip_id: [u16; 2],
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            hp: [1000, 1000],
+            chip_id: [1, 1],
+        }
+    }
+}
+
+fn show_player(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    label_id: &str,
+    manipulator: &practice::Manipulator,
+    player_index: usize,
+    state: &mut State,
+) {
+    ui.strong(i18n::LOCALES.lookup(language, label_id));
+    ui.end_row();
+
+    ui.add(egui::DragValue::new(&mut state.hp[player_index]).clamp_range(0..=9999));
+    if ui
+        .button(i18n::LOCALES.lookup(language, "practice-panel-set-hp"))
+        .clicked()
+    {
+        manipulator.queue(practice::Manipulation::SetHp {
+            player_index,
+            hp: state.hp[player_index],
+        });
+    }
+    ui.end_row();
+
+    ui.add(
+        egui::DragValue::new(&mut state.chip_id[player_index])
+            .clamp_range(1..=999)
+            .custom_formatter(|n, _| format!("#{:03}", n as u16)),
+    );
+    if ui
+        .button(i18n::LOCALES.lookup(language, "practice-panel-give-chip"))
+        .clicked()
+    {
+        manipulator.queue(practice::Manipulation::SetChipInHand {
+            player_index,
+            chip_id: state.chip_id[player_index],
+        });
+    }
+    ui.end_row();
+}
+
+/// Lets the player force game state for labbing specific situations.
+pub fn show(
+    ctx: &egui::Context,
+    manipulator: &practice::Manipulator,
+    language: &unic_langid::LanguageIdentifier,
+    state: &mut State,
+) {
+    egui::Window::new(i18n::LOCALES.lookup(language, "practice-panel"))
+        .id(egui::Id::new("practice-panel"))
+        .resizable(false)
+        .default_pos(egui::pos2(8.0, 240.0))
+        .show(ctx, |ui| {
+            if ui
+                .button(i18n::LOCALES.lookup(language, "practice-panel-fill-custom-gauge"))
+                .clicked()
+            {
+                manipulator.queue(practice::Manipulation::FillCustomGauge);
+            }
+
+            egui::Grid::new("practice-panel-grid").num_columns(2).show(ui, |ui| {
+                show_player(ui, language, "practice-overlay-player", manipulator, 0, state);
+                show_player(ui, language, "practice-overlay-opponent", manipulator, 1, state);
+            });
+
+            let mut rng_frozen = manipulator.is_rng_frozen();
+            if ui
+                .checkbox(
+                    &mut rng_frozen,
+                    i18n::LOCALES.lookup(language, "practice-panel-freeze-rng"),
+                )
+                .on_hover_text(i18n::LOCALES.lookup(language, "practice-panel-freeze-rng.tooltip"))
+                .changed()
+            {
+                manipulator.queue(practice::Manipulation::FreezeRng(rng_frozen));
+            }
+
+            ui.weak(i18n::LOCALES.lookup(language, "practice-panel-description"));
+        });
+}
+
+/// Marks the session as modified, so nothing that comes out of it is mistaken for unassisted play.
+pub fn show_modified_watermark(ctx: &egui::Context, language: &unic_langid::LanguageIdentifier) {
+    egui::Area::new("practice-modified-watermark")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -8.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.label(
+                egui::RichText::new(i18n::LOCALES.lookup(language, "practice-modified"))
+                    .strong()
+                    .color(gui::theme::colors(ui.ctx()).warning),
+            );
+        });
+}
@@ -1,15 +1,221 @@
-use fluent_templates::Loader;
-
-use crate::{i18n, session};
+use crate::{gui, i18n, input, replay, session};
 
 const HIDE_AFTER: std::time::Duration = std::time::Duration::from_secs(5);
 
+const PROGRESS_BAR_HEIGHT: f32 = 6.0;
+
+/// Rollbacks at least this deep are shown as a warning, and at least `HEAVY_ROLLBACK_DEPTH` deep as an error.
+const MODERATE_ROLLBACK_DEPTH: u8 = 3;
+const HEAVY_ROLLBACK_DEPTH: u8 = 7;
+
+const ROLLBACK_STRIP_HEIGHT: f32 = 6.0;
+
+/// How long the step back hotkey has to be held before it starts repeating, and how often it steps back after that.
+const STEP_BACK_REPEAT_DELAY: std::time::Duration = std::time::Duration::from_millis(400);
+const STEP_BACK_REPEAT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+fn rollback_depth_color(ctx: &egui::Context, depth: u8) -> egui::Color32 {
+    let colors = gui::theme::colors(ctx);
+    if depth >= HEAVY_ROLLBACK_DEPTH {
+        egui::Color32::RED
+    } else if depth >= MODERATE_ROLLBACK_DEPTH {
+        colors.warning
+    } else {
+        colors.ready
+    }
+}
+
+/// Shows how far the recording player rolled back over the whole replay, with the current position marked.
+fn show_rollback_strip(
+    ui: &mut egui::Ui,
+    language: &unic_langid::LanguageIdentifier,
+    width: f32,
+    rollback_depths: &[u8],
+    position: usize,
+) {
+    if rollback_depths.is_empty() {
+        return;
+    }
+
+    let (rect, resp) = ui.allocate_exact_size(egui::vec2(width, ROLLBACK_STRIP_HEIGHT), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+    // Each column shows the deepest rollback in the ticks it covers, so short spikes don't get lost.
+    let columns = rect.width().floor().max(1.0) as usize;
+    for column in 0..columns {
+        let start = column * rollback_depths.len() / columns;
+        let end = ((column + 1) * rollback_depths.len() / columns).max(start + 1);
+        let depth = rollback_depths[start..end.min(rollback_depths.len())]
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0);
+        let x = rect.left() + column as f32;
+        painter.rect_filled(
+            egui::Rect::from_min_max(egui::pos2(x, rect.top()), egui::pos2(x + 1.0, rect.bottom())),
+            0.0,
+            rollback_depth_color(ui.ctx(), depth),
+        );
+    }
+
+    let x = rect.left() + rect.width() * position as f32 / rollback_depths.len() as f32;
+    painter.vline(x, rect.y_range(), ui.visuals().widgets.active.fg_stroke);
+
+    let depth = rollback_depths[position.min(rollback_depths.len() - 1)];
+    ui.colored_label(
+        rollback_depth_color(ui.ctx(), depth),
+        i18n::LOCALES.lookup_with_args(
+            language,
+            "replay-viewer-rollback-depth",
+            &std::collections::HashMap::from([("depth", depth.into())]),
+        ),
+    );
+
+    if let Some(pos) = resp.hover_pos() {
+        let i = (((pos.x - rect.left()) / rect.width()) * rollback_depths.len() as f32) as usize;
+        let depth = rollback_depths[i.min(rollback_depths.len() - 1)];
+        resp.on_hover_text(i18n::LOCALES.lookup_with_args(
+            language,
+            "replay-viewer-rollback-depth",
+            &std::collections::HashMap::from([("depth", depth.into())]),
+        ));
+    }
+}
+
+pub struct State {
+    loop_markers_loaded: bool,
+    /// When to step back again while the step back hotkey is held.
+    next_step_back: Option<std::time::Instant>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            loop_markers_loaded: false,
+            next_step_back: None,
+        }
+    }
+}
+
+/// Shows how far into the replay playback is, with the A/B loop highlighted.
+fn show_progress_bar(ui: &mut egui::Ui, width: f32, replayer: &session::Replayer) {
+    let (position, total) = replayer.progress();
+    if total == 0 {
+        return;
+    }
+    let markers = replayer.loop_markers();
+
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(width, PROGRESS_BAR_HEIGHT), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let x = |position: usize| rect.left() + rect.width() * position as f32 / total as f32;
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+    painter.rect_filled(
+        egui::Rect::from_min_max(rect.left_top(), egui::pos2(x(position), rect.bottom())),
+        0.0,
+        ui.visuals().widgets.inactive.bg_fill,
+    );
+
+    let selection = ui.visuals().selection;
+    if let (Some(a), Some(b)) = (markers.a, markers.b) {
+        painter.rect_filled(
+            egui::Rect::from_min_max(egui::pos2(x(a), rect.top()), egui::pos2(x(b), rect.bottom())),
+            0.0,
+            selection.bg_fill.linear_multiply(0.5),
+        );
+    }
+    for marker in [markers.a, markers.b].into_iter().flatten() {
+        painter.vline(x(marker), rect.y_range(), selection.stroke);
+    }
+    painter.vline(x(position), rect.y_range(), ui.visuals().widgets.active.fg_stroke);
+}
+
+/// Writes the loop markers to the replay's notes, so they're still there the next time it's played back.
+fn save_loop_markers(replays_path: &std::path::Path, replayer: &session::Replayer) {
+    let markers = replayer.loop_markers();
+    if let Err(e) =
+        replay::notes::Index::load(replay::notes::index_path(replays_path)).update(replayer.notes_key(), |entry| {
+            entry.loop_markers = markers;
+        })
+    {
+        log::error!("failed to save replay loop markers: {:?}", e);
+    }
+}
+
+fn set_loop_start(replays_path: &std::path::Path, session: &session::Session, replayer: &session::Replayer) {
+    if let Err(e) = session.set_replay_loop_start() {
+        log::error!("failed to set replay loop start: {:?}", e);
+        return;
+    }
+    save_loop_markers(replays_path, replayer);
+}
+
+fn step_back(session: &session::Session, replayer: &session::Replayer) {
+    if !replayer.can_step_back() {
+        return;
+    }
+    if let Err(e) = session.step_replay_back() {
+        log::error!("failed to step replay back: {:?}", e);
+    }
+}
+
+fn set_loop_end(replays_path: &std::path::Path, replayer: &session::Replayer) {
+    if !replayer.can_set_loop_end() {
+        return;
+    }
+    replayer.set_loop_end();
+    save_loop_markers(replays_path, replayer);
+}
+
 pub fn show(
     ctx: &egui::Context,
     session: &session::Session,
+    replayer: &session::Replayer,
     language: &unic_langid::LanguageIdentifier,
+    input_state: &input::State,
+    input_mapping: &input::Mapping,
+    hotkeys_enabled: bool,
+    replays_path: &std::path::Path,
     last_mouse_motion_time: &Option<std::time::Instant>,
+    state: &mut State,
 ) {
+    if !state.loop_markers_loaded {
+        if let Some(entry) =
+            replay::notes::Index::load(replay::notes::index_path(replays_path)).get(replayer.notes_key())
+        {
+            replayer.restore_loop_markers(entry.loop_markers);
+        }
+        state.loop_markers_loaded = true;
+    }
+
+    if hotkeys_enabled {
+        if input::is_any_pressed(&input_mapping.replay_loop_start, input_state) {
+            set_loop_start(replays_path, session, replayer);
+        }
+        if input::is_any_pressed(&input_mapping.replay_loop_end, input_state) {
+            set_loop_end(replays_path, replayer);
+        }
+
+        let now = std::time::Instant::now();
+        if input::is_any_pressed(&input_mapping.replay_step_back, input_state) {
+            step_back(session, replayer);
+            state.next_step_back = Some(now + STEP_BACK_REPEAT_DELAY);
+        } else if input::is_any_held(&input_mapping.replay_step_back, input_state) {
+            if state.next_step_back.map(|t| now >= t).unwrap_or(false) {
+                step_back(session, replayer);
+                state.next_step_back = Some(now + STEP_BACK_REPEAT_INTERVAL);
+            }
+        } else {
+            state.next_step_back = None;
+        }
+        // Playback is paused, so nothing else would get the next repeat shown.
+        if state.next_step_back.is_some() {
+            ctx.request_repaint();
+        }
+    } else {
+        state.next_step_back = None;
+    }
+
     let paused = session.is_paused();
     egui::Window::new("")
         .id(egui::Id::new("replay-controls-window"))
@@ -23,28 +229,81 @@ pub fn show(
         })
         .anchor(egui::Align2::CENTER_BOTTOM, egui::Vec2::new(0.0, -50.0))
         .show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                if ui
-                    .selectable_label(paused, "⏸️")
-                    .on_hover_text(i18n::LOCALES.lookup(language, "replay-viewer-pause").unwrap())
-                    .clicked()
-                {
-                    session.set_paused(!paused);
-                }
-                if ui
-                    .button("⏯️")
-                    .on_hover_text(i18n::LOCALES.lookup(language, "replay-viewer-step").unwrap())
-                    .clicked()
-                {
-                    session.frame_step();
-                }
-                let mut speed = session.fps_target() / session::EXPECTED_FPS;
-                ui.add(egui::Separator::default().vertical());
-                ui.label("🐢");
-                ui.add(egui::Slider::new(&mut speed, 0.25..=10.0).step_by(0.25))
-                    .on_hover_text(i18n::LOCALES.lookup(language, "replay-viewer-speed").unwrap());
-                ui.label("🐇");
-                session.set_fps_target(speed * session::EXPECTED_FPS);
-            });
+            let controls_width = ui
+                .horizontal(|ui| {
+                    if ui
+                        .selectable_label(paused, "⏸️")
+                        .on_hover_text(i18n::LOCALES.lookup(language, "replay-viewer-pause"))
+                        .clicked()
+                    {
+                        session.set_paused(!paused);
+                    }
+                    if ui
+                        .add_enabled(replayer.can_step_back(), egui::Button::new("⏪"))
+                        .on_hover_text(i18n::LOCALES.lookup(language, "replay-viewer-step-back"))
+                        .clicked()
+                    {
+                        step_back(session, replayer);
+                    }
+                    if ui
+                        .button("⏯️")
+                        .on_hover_text(i18n::LOCALES.lookup(language, "replay-viewer-step"))
+                        .clicked()
+                    {
+                        session.frame_step();
+                    }
+                    let mut speed = session.fps_target() / session::EXPECTED_FPS;
+                    ui.add(egui::Separator::default().vertical());
+                    ui.label("🐢");
+                    ui.add(egui::Slider::new(&mut speed, 0.25..=10.0).step_by(0.25))
+                        .on_hover_text(i18n::LOCALES.lookup(language, "replay-viewer-speed"));
+                    ui.label("🐇");
+                    session.set_fps_target(speed * session::EXPECTED_FPS);
+
+                    let markers = replayer.loop_markers();
+                    ui.add(egui::Separator::default().vertical());
+                    if ui
+                        .selectable_label(markers.a.is_some(), "A")
+                        .on_hover_text(i18n::LOCALES.lookup(language, "replay-viewer-loop-start"))
+                        .clicked()
+                    {
+                        set_loop_start(replays_path, session, replayer);
+                    }
+                    if ui
+                        .add_enabled(
+                            replayer.can_set_loop_end(),
+                            egui::SelectableLabel::new(markers.b.is_some(), "B"),
+                        )
+                        .on_hover_text(i18n::LOCALES.lookup(language, "replay-viewer-loop-end"))
+                        .clicked()
+                    {
+                        set_loop_end(replays_path, replayer);
+                    }
+                    if ui
+                        .add_enabled(!markers.is_empty(), egui::Button::new("✖"))
+                        .on_hover_text(i18n::LOCALES.lookup(language, "replay-viewer-loop-clear"))
+                        .clicked()
+                    {
+                        replayer.clear_loop();
+                        save_loop_markers(replays_path, replayer);
+                    }
+                })
+                .response
+                .rect
+                .width();
+
+            show_progress_bar(ui, controls_width, replayer);
+
+            let (position, total) = replayer.progress();
+            ui.weak(i18n::LOCALES.lookup_with_args(
+                language,
+                "replay-viewer-frame",
+                &std::collections::HashMap::from([("frame", position.into()), ("total", total.into())]),
+            ));
+
+            if let Some(rollback_depths) = replayer.rollback_depths() {
+                let (position, _) = replayer.progress();
+                show_rollback_strip(ui, language, controls_width, rollback_depths, position);
+            }
         });
 }
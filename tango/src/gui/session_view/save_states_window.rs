@@ -0,0 +1,242 @@
+use chrono_locale::LocaleDate;
+
+use crate::{i18n, savestates, session};
+
+struct Slot {
+    saved_at: std::time::SystemTime,
+    patch_version: Option<semver::Version>,
+    thumbnail: Option<egui::TextureHandle>,
+}
+
+pub struct State {
+    open: bool,
+    selected_slot: usize,
+    slots: Option<Vec<Option<Slot>>>,
+    /// A slot that was saved with a different patch version, waiting for confirmation to load, along with that version.
+    pending_load: Option<(usize, Option<semver::Version>)>,
+    error: Option<String>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            selected_slot: 0,
+            slots: None,
+            pending_load: None,
+            error: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        // Slots may have been changed from the hotkeys while the window was closed.
+        self.slots = None;
+    }
+
+    /// Saves to the selected slot.
+    pub fn save(&mut self, session: &session::Session) {
+        self.save_to(session, self.selected_slot);
+    }
+
+    /// Loads from the selected slot.
+    pub fn load(&mut self, session: &session::Session) {
+        self.load_from(session, self.selected_slot, false);
+    }
+
+    fn save_to(&mut self, session: &session::Session, slot: usize) {
+        self.error = session.save_state_to_slot(slot).err().map(|e| {
+            log::error!("failed to save state to slot {}: {:?}", slot, e);
+            e.to_string()
+        });
+        self.slots = None;
+    }
+
+    fn load_from(&mut self, session: &session::Session, slot: usize, confirmed: bool) {
+        let slots = if let Some(slots) = session.save_state_slots() {
+            slots
+        } else {
+            return;
+        };
+
+        let info = if let Some(info) = slots.info(slot) {
+            info
+        } else {
+            return;
+        };
+
+        if !confirmed && info.patch_version.as_ref() != slots.patch_version() {
+            self.pending_load = Some((slot, info.patch_version));
+            self.open = true;
+            return;
+        }
+
+        self.pending_load = None;
+        self.error = session.load_state_from_slot(slot).err().map(|e| {
+            log::error!("failed to load state from slot {}: {:?}", slot, e);
+            e.to_string()
+        });
+    }
+}
+
+fn load_slots(ctx: &egui::Context, slots: &savestates::Slots) -> Vec<Option<Slot>> {
+    (0..savestates::NUM_SLOTS)
+        .map(|i| {
+            let info = slots.info(i)?;
+            Some(Slot {
+                saved_at: info.saved_at,
+                patch_version: info.patch_version,
+                thumbnail: info.thumbnail.map(|thumbnail| {
+                    ctx.load_texture(
+                        format!("save-state-thumbnail-{}", i),
+                        egui::ColorImage::from_rgba_unmultiplied(
+                            [thumbnail.width() as usize, thumbnail.height() as usize],
+                            &thumbnail,
+                        ),
+                        egui::TextureFilter::Linear,
+                    )
+                }),
+            })
+        })
+        .collect()
+}
+
+fn format_version(version: Option<&semver::Version>) -> String {
+    version.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+pub fn show(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    session: &session::Session,
+    state: &mut State,
+) {
+    let save_state_slots = if let Some(save_state_slots) = session.save_state_slots() {
+        save_state_slots
+    } else {
+        return;
+    };
+
+    if !state.open {
+        return;
+    }
+
+    let mut open = true;
+    egui::Window::new(i18n::LOCALES.lookup(language, "save-states"))
+        .id(egui::Id::new("save-states-window"))
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            if let Some((slot, saved_version)) = state.pending_load.clone() {
+                ui.label(i18n::LOCALES.lookup_with_args(
+                    language,
+                    "save-states-patch-version-mismatch",
+                    &std::collections::HashMap::from([
+                        ("slot", (slot + 1).to_string().into()),
+                        ("saved", format_version(saved_version.as_ref()).into()),
+                        ("current", format_version(save_state_slots.patch_version()).into()),
+                    ]),
+                ));
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(i18n::LOCALES.lookup(language, "save-states-patch-version-mismatch.load-anyway"))
+                        .clicked()
+                    {
+                        state.load_from(session, slot, true);
+                    }
+                    if ui
+                        .button(i18n::LOCALES.lookup(language, "save-states-patch-version-mismatch.cancel"))
+                        .clicked()
+                    {
+                        state.pending_load = None;
+                    }
+                });
+                ui.separator();
+            }
+
+            if let Some(error) = state.error.as_ref() {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    i18n::LOCALES.lookup_with_args(
+                        language,
+                        "save-states-error",
+                        &std::collections::HashMap::from([("error", error.clone().into())]),
+                    ),
+                );
+            }
+
+            let slots = state
+                .slots
+                .get_or_insert_with(|| load_slots(ui.ctx(), save_state_slots));
+            let mut save_slot = None;
+            let mut load_slot = None;
+            egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                egui::Grid::new("save-states-grid").num_columns(3).show(ui, |ui| {
+                    for (i, slot) in slots.iter().enumerate() {
+                        ui.selectable_value(
+                            &mut state.selected_slot,
+                            i,
+                            i18n::LOCALES.lookup_with_args(
+                                language,
+                                "save-states-slot",
+                                &std::collections::HashMap::from([("slot", (i + 1).to_string().into())]),
+                            ),
+                        );
+
+                        let thumbnail_size =
+                            egui::Vec2::new(savestates::THUMBNAIL_WIDTH as f32, savestates::THUMBNAIL_HEIGHT as f32);
+                        if let Some(thumbnail) = slot.as_ref().and_then(|slot| slot.thumbnail.as_ref()) {
+                            ui.image(thumbnail, thumbnail_size);
+                        } else {
+                            ui.allocate_space(thumbnail_size);
+                        }
+
+                        ui.vertical(|ui| {
+                            if let Some(slot) = slot.as_ref() {
+                                ui.label(
+                                    chrono::DateTime::<chrono::Local>::from(slot.saved_at)
+                                        .formatl("%c", &language.to_string())
+                                        .to_string(),
+                                );
+                                if slot.patch_version.as_ref() != save_state_slots.patch_version() {
+                                    ui.weak(format!("⚠️ v{}", format_version(slot.patch_version.as_ref())));
+                                }
+                            } else {
+                                ui.weak(i18n::LOCALES.lookup(language, "save-states-empty"));
+                            }
+                            ui.horizontal(|ui| {
+                                if ui.button(i18n::LOCALES.lookup(language, "save-states-save")).clicked() {
+                                    save_slot = Some(i);
+                                }
+                                if ui
+                                    .add_enabled(
+                                        slot.is_some(),
+                                        egui::Button::new(i18n::LOCALES.lookup(language, "save-states-load")),
+                                    )
+                                    .clicked()
+                                {
+                                    load_slot = Some(i);
+                                }
+                            });
+                        });
+                        ui.end_row();
+                    }
+                });
+            });
+
+            if let Some(slot) = save_slot {
+                state.selected_slot = slot;
+                state.save_to(session, slot);
+            }
+            if let Some(slot) = load_slot {
+                state.selected_slot = slot;
+                state.load_from(session, slot, false);
+            }
+        });
+
+    if !open {
+        state.open = false;
+        state.pending_load = None;
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{contentpolicy, i18n, session, sync};
+
+/// Shows both players' nicknames, the score so far and the current round.
+///
+/// The score is only ever what the local side saw happen, never what the opponent claims.
+pub fn show(ctx: &egui::Context, language: &unic_langid::LanguageIdentifier, pvp: &session::PvP) {
+    let round_number = if let Some(match_) = &*sync::block_on(pvp.match_.lock()) {
+        sync::block_on(match_.lock_round_state()).number
+    } else {
+        return;
+    };
+    let score = pvp.score();
+    let (local_nickname, remote_nickname) = pvp.nicknames();
+
+    egui::Area::new("scoreboard")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-4.0, 4.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(local_nickname);
+                    ui.strong(format!("{} - {}", score.wins, score.losses));
+                    ui.label(contentpolicy::display_nickname(remote_nickname));
+                    if score.draws > 0 {
+                        ui.separator();
+                        ui.weak(i18n::LOCALES.lookup_with_args(
+                            language,
+                            "scoreboard-draws",
+                            &std::collections::HashMap::from([("draws", score.draws.into())]),
+                        ));
+                    }
+                    if round_number > 0 {
+                        ui.separator();
+                        ui.weak(i18n::LOCALES.lookup_with_args(
+                            language,
+                            "scoreboard-round",
+                            &std::collections::HashMap::from([("round", round_number.into())]),
+                        ));
+                    }
+                });
+            });
+        });
+}
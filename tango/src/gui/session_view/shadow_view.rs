@@ -0,0 +1,111 @@
+use crate::{battle, i18n, shadow, sync};
+
+pub struct State {
+    texture: Option<egui::TextureHandle>,
+    tick: u32,
+    hash: u32,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            texture: None,
+            tick: 0,
+            hash: 0,
+        }
+    }
+}
+
+/// Shows the shadow core's last frame in a corner, for investigating desyncs.
+///
+/// The shadow plays the opponent's side, so its frames are from their point of view and never the same as ours: whether the two cores have diverged is told from the battle state checksums desync detection uses instead. It also runs behind the primary, so its frame is labelled with the tick it's actually from.
+pub fn show(
+    ctx: &egui::Context,
+    language: &unic_langid::LanguageIdentifier,
+    match_: &battle::Match,
+    view: &shadow::View,
+    state: &mut State,
+) {
+    if let Some(frame) = view.take_frame() {
+        let mut image = egui::ColorImage::new(
+            [mgba::gba::SCREEN_WIDTH as usize, mgba::gba::SCREEN_HEIGHT as usize],
+            egui::Color32::BLACK,
+        );
+        bytemuck::cast_slice_mut(&mut image.pixels[..]).copy_from_slice(&frame.pixels);
+        if let Some(texture) = state.texture.as_mut() {
+            texture.set(image, egui::TextureFilter::Nearest);
+        } else {
+            state.texture = Some(ctx.load_texture("shadow-view", image, egui::TextureFilter::Nearest));
+        }
+        state.tick = frame.tick;
+        state.hash = frame.hash;
+    }
+
+    let texture = if let Some(texture) = state.texture.as_ref() {
+        texture
+    } else {
+        return;
+    };
+
+    let primary_tick = sync::block_on(match_.lock_round_state())
+        .round
+        .as_ref()
+        .map(|round| round.current_tick());
+
+    egui::Area::new("shadow-view")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-4.0, 4.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.image(
+                    texture,
+                    egui::vec2(
+                        mgba::gba::SCREEN_WIDTH as f32 / 2.0,
+                        mgba::gba::SCREEN_HEIGHT as f32 / 2.0,
+                    ),
+                );
+                ui.monospace(
+                    i18n::LOCALES.lookup_with_args(
+                        language,
+                        "shadow-view",
+                        &std::collections::HashMap::from([
+                            ("tick", state.tick.into()),
+                            (
+                                "behind",
+                                primary_tick
+                                    .map(|primary_tick| primary_tick.saturating_sub(state.tick))
+                                    .unwrap_or(0)
+                                    .into(),
+                            ),
+                        ]),
+                    ),
+                );
+                ui.monospace(i18n::LOCALES.lookup_with_args(
+                    language,
+                    "shadow-view.frame-hash",
+                    &std::collections::HashMap::from([("hash", format!("{:08x}", state.hash).into())]),
+                ));
+                match view.last_comparison() {
+                    Some(comparison) => {
+                        let text = i18n::LOCALES.lookup_with_args(
+                            language,
+                            if comparison.matches {
+                                "shadow-view.state-matches"
+                            } else {
+                                "shadow-view.state-diverged"
+                            },
+                            &std::collections::HashMap::from([("tick", comparison.tick.into())]),
+                        );
+                        if comparison.matches {
+                            ui.colored_label(egui::Color32::GREEN, text);
+                        } else {
+                            ui.colored_label(egui::Color32::RED, text);
+                        }
+                    }
+                    None => {
+                        ui.weak(i18n::LOCALES.lookup(language, "shadow-view.state-unknown"));
+                    }
+                }
+            });
+        });
+}
@@ -1,6 +1,6 @@
-use fluent_templates::Loader;
-
-use crate::{config, game, gui, i18n, input, patch, rom, save, version};
+use crate::{
+    assist, bios, config, contentpolicy, gui, i18n, input, inputlatency, logging, net, patch, rom, save, version,
+};
 
 #[derive(PartialEq, Eq)]
 enum Tab {
@@ -8,15 +8,28 @@ enum Tab {
     Input,
     Graphics,
     Audio,
+    Accessibility,
     Netplay,
+    Network,
     Patches,
     Advanced,
     About,
 }
 
+/// How the last network settings test went.
+enum NetworkTest {
+    InProgress,
+    Succeeded { proxy: Option<String> },
+    Failed(String),
+}
+
 pub struct State {
     tab: Tab,
     emblem: egui_extras::RetainedImage,
+    profile_window: Option<gui::config_profile_window::State>,
+    /// Why the last BIOS picked couldn't be used.
+    bios_error: Option<String>,
+    network_test: std::sync::Arc<parking_lot::Mutex<Option<NetworkTest>>>,
 }
 
 impl State {
@@ -24,6 +37,9 @@ impl State {
         Self {
             tab: Tab::General,
             emblem: egui_extras::RetainedImage::from_image_bytes("emblem", include_bytes!("../emblem.png")).unwrap(),
+            profile_window: None,
+            bios_error: None,
+            network_test: std::sync::Arc::new(parking_lot::Mutex::new(None)),
         }
     }
 }
@@ -32,173 +48,433 @@ pub fn show(
     ctx: &egui::Context,
     state: &mut Option<State>,
     font_families: &gui::FontFamilies,
+    themes: &mut gui::theme::Themes,
     config: &mut config::Config,
     roms_scanner: rom::Scanner,
     saves_scanner: save::Scanner,
     patches_scanner: patch::Scanner,
     window: &winit::window::Window,
     steal_input: &mut Option<gui::steal_input_window::State>,
+    input_latency_monitor: &parking_lot::Mutex<inputlatency::Monitor>,
+    input_latency_tester: &mut Option<inputlatency::Tester>,
+    audio_device_names: &[String],
+    welcome: &mut Option<gui::welcome::State>,
+    log_viewer: &mut Option<gui::log_viewer_window::State>,
 ) {
     let mut open = state.is_some();
-    egui::Window::new(format!(
-        "⚙️ {}",
-        i18n::LOCALES.lookup(&config.language, "settings").unwrap()
-    ))
-    .open(&mut open)
-    .id(egui::Id::new("settings-window"))
-    .show(ctx, |ui| {
-        let state = state.as_mut().unwrap();
-        ui.vertical(|ui| {
-            ui.horizontal(|ui| {
-                ui.selectable_value(
-                    &mut state.tab,
-                    Tab::General,
-                    i18n::LOCALES.lookup(&config.language, "settings-tab-general").unwrap(),
-                );
-                ui.selectable_value(
-                    &mut state.tab,
-                    Tab::Input,
-                    i18n::LOCALES.lookup(&config.language, "settings-tab-input").unwrap(),
-                );
-                ui.selectable_value(
-                    &mut state.tab,
-                    Tab::Graphics,
-                    i18n::LOCALES.lookup(&config.language, "settings-tab-graphics").unwrap(),
-                );
-                ui.selectable_value(
-                    &mut state.tab,
-                    Tab::Audio,
-                    i18n::LOCALES.lookup(&config.language, "settings-tab-audio").unwrap(),
-                );
-                ui.selectable_value(
-                    &mut state.tab,
-                    Tab::Netplay,
-                    i18n::LOCALES.lookup(&config.language, "settings-tab-netplay").unwrap(),
-                );
-                ui.selectable_value(
-                    &mut state.tab,
-                    Tab::Patches,
-                    i18n::LOCALES.lookup(&config.language, "settings-tab-patches").unwrap(),
-                );
-                ui.selectable_value(
-                    &mut state.tab,
-                    Tab::Advanced,
-                    i18n::LOCALES.lookup(&config.language, "settings-tab-advanced").unwrap(),
-                );
-                ui.selectable_value(
-                    &mut state.tab,
-                    Tab::About,
-                    i18n::LOCALES.lookup(&config.language, "settings-tab-about").unwrap(),
-                );
-            });
+    egui::Window::new(format!("⚙️ {}", i18n::LOCALES.lookup(&config.language, "settings")))
+        .open(&mut open)
+        .id(egui::Id::new("settings-window"))
+        .show(ctx, |ui| {
+            let state = state.as_mut().unwrap();
+            ui.vertical(|ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(
+                        &mut state.tab,
+                        Tab::General,
+                        i18n::LOCALES.lookup(&config.language, "settings-tab-general"),
+                    );
+                    ui.selectable_value(
+                        &mut state.tab,
+                        Tab::Input,
+                        i18n::LOCALES.lookup(&config.language, "settings-tab-input"),
+                    );
+                    ui.selectable_value(
+                        &mut state.tab,
+                        Tab::Graphics,
+                        i18n::LOCALES.lookup(&config.language, "settings-tab-graphics"),
+                    );
+                    ui.selectable_value(
+                        &mut state.tab,
+                        Tab::Audio,
+                        i18n::LOCALES.lookup(&config.language, "settings-tab-audio"),
+                    );
+                    ui.selectable_value(
+                        &mut state.tab,
+                        Tab::Accessibility,
+                        i18n::LOCALES.lookup(&config.language, "settings-tab-accessibility"),
+                    );
+                    ui.selectable_value(
+                        &mut state.tab,
+                        Tab::Netplay,
+                        i18n::LOCALES.lookup(&config.language, "settings-tab-netplay"),
+                    );
+                    ui.selectable_value(
+                        &mut state.tab,
+                        Tab::Network,
+                        i18n::LOCALES.lookup(&config.language, "settings-tab-network"),
+                    );
+                    ui.selectable_value(
+                        &mut state.tab,
+                        Tab::Patches,
+                        i18n::LOCALES.lookup(&config.language, "settings-tab-patches"),
+                    );
+                    ui.selectable_value(
+                        &mut state.tab,
+                        Tab::Advanced,
+                        i18n::LOCALES.lookup(&config.language, "settings-tab-advanced"),
+                    );
+                    ui.selectable_value(
+                        &mut state.tab,
+                        Tab::About,
+                        i18n::LOCALES.lookup(&config.language, "settings-tab-about"),
+                    );
+                });
 
-            ui.separator();
-
-            egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
-                ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
-                    match state.tab {
-                        Tab::General => show_general_tab(ui, config, font_families),
-                        Tab::Input => show_input_tab(ui, &config.language, &mut config.input_mapping, steal_input),
-                        Tab::Graphics => show_graphics_tab(ui, config, window),
-                        Tab::Audio => show_audio_tab(ui, config),
-                        Tab::Netplay => show_netplay_tab(ui, config),
-                        Tab::Patches => show_patches_tab(ui, config),
-                        Tab::Advanced => show_advanced_tab(
-                            ui,
-                            config,
-                            roms_scanner.clone(),
-                            saves_scanner.clone(),
-                            patches_scanner.clone(),
-                        ),
-                        Tab::About => show_about_tab(ui, &state.emblem),
-                    };
+                ui.separator();
+
+                egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+                    ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
+                        match state.tab {
+                            Tab::General => show_general_tab(ui, config, font_families, themes),
+                            Tab::Input => show_input_tab(
+                                ui,
+                                &config.language,
+                                &mut config.input_mapping,
+                                &config.input_latency_reports,
+                                steal_input,
+                                input_latency_monitor,
+                                input_latency_tester,
+                            ),
+                            Tab::Graphics => show_graphics_tab(ui, config, window),
+                            Tab::Audio => show_audio_tab(ui, config, audio_device_names),
+                            Tab::Accessibility => show_accessibility_tab(ui, config),
+                            Tab::Netplay => show_netplay_tab(ui, config),
+                            Tab::Network => show_network_tab(ui, config, state.network_test.clone()),
+                            Tab::Patches => show_patches_tab(ui, config),
+                            Tab::Advanced => show_advanced_tab(
+                                ui,
+                                config,
+                                roms_scanner.clone(),
+                                saves_scanner.clone(),
+                                patches_scanner.clone(),
+                                welcome,
+                                log_viewer,
+                                &mut state.profile_window,
+                                &mut state.bios_error,
+                            ),
+                            Tab::About => show_about_tab(ui, &state.emblem),
+                        };
+                    });
                 });
             });
         });
-    });
+    if let Some(state) = state.as_mut() {
+        gui::config_profile_window::show(
+            ctx,
+            &mut state.profile_window,
+            config,
+            roms_scanner,
+            saves_scanner,
+            patches_scanner,
+        );
+    }
+
     if !open {
         *state = None;
     }
 }
 
-fn show_general_tab(ui: &mut egui::Ui, config: &mut config::Config, font_families: &gui::FontFamilies) {
+fn show_general_tab(
+    ui: &mut egui::Ui,
+    config: &mut config::Config,
+    font_families: &gui::FontFamilies,
+    themes: &mut gui::theme::Themes,
+) {
     egui::Grid::new("settings-window-general-grid")
         .num_columns(2)
         .show(ui, |ui| {
             {
                 let mut nickname = config.nickname.clone().unwrap_or_else(|| "".to_string());
-                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-nickname").unwrap());
-                ui.add(egui::TextEdit::singleline(&mut nickname).desired_width(100.0));
-                config.nickname = Some(nickname.chars().take(20).collect());
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-nickname"));
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut nickname).desired_width(100.0));
+                    if let Some(word) = contentpolicy::banned_word_in(&nickname) {
+                        ui.colored_label(
+                            gui::theme::colors(ui.ctx()).warning,
+                            i18n::LOCALES.lookup_with_args(
+                                &config.language,
+                                "settings-nickname.banned-word",
+                                &std::collections::HashMap::from([("word", word.into())]),
+                            ),
+                        );
+                    }
+                });
+                config.nickname = Some(nickname.chars().take(contentpolicy::max_nickname_length()).collect());
                 ui.end_row();
             }
 
             {
-                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-theme").unwrap());
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-theme"));
 
-                let system_label = i18n::LOCALES.lookup(&config.language, "settings-theme.system").unwrap();
-                let light_label = i18n::LOCALES.lookup(&config.language, "settings-theme.light").unwrap();
-                let dark_label = i18n::LOCALES.lookup(&config.language, "settings-theme.dark").unwrap();
+                let system_label = i18n::LOCALES.lookup(&config.language, "settings-theme.system");
+                let light_label = i18n::LOCALES.lookup(&config.language, "settings-theme.light");
+                let dark_label = i18n::LOCALES.lookup(&config.language, "settings-theme.dark");
 
-                egui::ComboBox::from_id_source("settings-window-general-theme")
-                    .selected_text(match config.theme {
-                        config::Theme::System => &system_label,
-                        config::Theme::Light => &light_label,
-                        config::Theme::Dark => &dark_label,
-                    })
-                    .width(200.0)
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut config.theme, config::Theme::System, &system_label);
-                        ui.selectable_value(&mut config.theme, config::Theme::Light, &light_label);
-                        ui.selectable_value(&mut config.theme, config::Theme::Dark, &dark_label);
-                    });
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("settings-window-general-theme")
+                        .selected_text(match &config.theme {
+                            config::Theme::System => &system_label,
+                            config::Theme::Light => &light_label,
+                            config::Theme::Dark => &dark_label,
+                            config::Theme::User(name) => name,
+                        })
+                        .width(200.0)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut config.theme, config::Theme::System, &system_label);
+                            ui.selectable_value(&mut config.theme, config::Theme::Light, &light_label);
+                            ui.selectable_value(&mut config.theme, config::Theme::Dark, &dark_label);
+                            for name in themes.user_theme_names() {
+                                ui.selectable_value(&mut config.theme, config::Theme::User(name.to_string()), name);
+                            }
+                        });
+
+                    if ui
+                        .button("🔄")
+                        .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-theme.reload"))
+                        .clicked()
+                    {
+                        themes.load_user_themes(&config.themes_path());
+                    }
+
+                    if ui
+                        .button("📂")
+                        .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-theme.open-folder"))
+                        .clicked()
+                    {
+                        let _ = open::that(&config.themes_path());
+                    }
+                });
                 ui.end_row();
             }
 
             {
-                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-language").unwrap());
-                gui::language_select::show(ui, font_families, &mut config.language);
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-language"));
+                gui::language_select::show(
+                    ui,
+                    "settings-window-general-language",
+                    font_families,
+                    &mut config.language,
+                );
                 ui.end_row();
             }
 
             {
-                ui.strong(
-                    i18n::LOCALES
-                        .lookup(&config.language, "settings-streamer-mode")
-                        .unwrap(),
-                );
-                ui.checkbox(&mut config.streamer_mode, "").on_hover_text(
-                    i18n::LOCALES
-                        .lookup(&config.language, "settings-streamer-mode.tooltip")
-                        .unwrap(),
-                );
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-game-data-language"));
+                ui.vertical(|ui| {
+                    let mut override_language = config.game_data_language.is_some();
+                    if ui
+                        .checkbox(
+                            &mut override_language,
+                            i18n::LOCALES.lookup(&config.language, "settings-game-data-language.override"),
+                        )
+                        .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-game-data-language.tooltip"))
+                        .changed()
+                    {
+                        config.game_data_language = if override_language {
+                            Some(config.language.clone())
+                        } else {
+                            None
+                        };
+                    }
+
+                    if let Some(game_data_language) = config.game_data_language.as_mut() {
+                        gui::language_select::show(
+                            ui,
+                            "settings-window-general-game-data-language",
+                            font_families,
+                            game_data_language,
+                        );
+                        gui::fonts::request_language(game_data_language);
+                        ui.label(
+                            egui::RichText::new(game_data_language_preview(game_data_language))
+                                .family(font_families.for_language(game_data_language)),
+                        );
+                    }
+                });
                 ui.end_row();
             }
 
             {
-                ui.strong(
-                    i18n::LOCALES
-                        .lookup(&config.language, "settings-show-own-setup")
-                        .unwrap(),
-                );
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-streamer-mode"));
+                ui.checkbox(&mut config.streamer_mode, "")
+                    .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-streamer-mode.tooltip"));
+                ui.end_row();
+            }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-show-own-setup"));
                 ui.checkbox(&mut config.show_own_setup, "");
                 ui.end_row();
             }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-show-practice-overlay"));
+                ui.checkbox(&mut config.show_practice_overlay, "")
+                    .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-show-practice-overlay.tooltip"));
+                ui.end_row();
+            }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-pause-on-focus-loss"));
+                ui.checkbox(&mut config.pause_on_focus_loss, "")
+                    .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-pause-on-focus-loss.tooltip"));
+                ui.end_row();
+            }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-save-write-back-policy"));
+
+                let ask_label = i18n::LOCALES.lookup(&config.language, "settings-save-write-back-policy.ask");
+                let discard_label = i18n::LOCALES.lookup(&config.language, "settings-save-write-back-policy.discard");
+                let overwrite_label =
+                    i18n::LOCALES.lookup(&config.language, "settings-save-write-back-policy.overwrite");
+                let save_as_new_label =
+                    i18n::LOCALES.lookup(&config.language, "settings-save-write-back-policy.save-as-new");
+
+                egui::ComboBox::from_id_source("settings-window-general-save-write-back-policy")
+                    .width(200.0)
+                    .selected_text(match config.save_write_back_policy {
+                        config::SaveWriteBackPolicy::Ask => &ask_label,
+                        config::SaveWriteBackPolicy::Discard => &discard_label,
+                        config::SaveWriteBackPolicy::Overwrite => &overwrite_label,
+                        config::SaveWriteBackPolicy::SaveAsNew => &save_as_new_label,
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut config.save_write_back_policy,
+                            config::SaveWriteBackPolicy::Ask,
+                            &ask_label,
+                        );
+                        ui.selectable_value(
+                            &mut config.save_write_back_policy,
+                            config::SaveWriteBackPolicy::Discard,
+                            &discard_label,
+                        );
+                        ui.selectable_value(
+                            &mut config.save_write_back_policy,
+                            config::SaveWriteBackPolicy::Overwrite,
+                            &overwrite_label,
+                        );
+                        ui.selectable_value(
+                            &mut config.save_write_back_policy,
+                            config::SaveWriteBackPolicy::SaveAsNew,
+                            &save_as_new_label,
+                        );
+                    });
+                ui.end_row();
+            }
         });
 }
 
-fn show_input_tab(
+/// Gets some sample text in the script the given language is written in, to show what game data will look like in it.
+fn game_data_language_preview(language: &unic_langid::LanguageIdentifier) -> &'static str {
+    let mut language = language.clone();
+    language.maximize();
+    let script = language.script.as_ref().map(|s| s.as_str()).unwrap_or("Latn");
+    gui::fonts::TEST_STRINGS
+        .iter()
+        .find(|(s, _)| *s == script)
+        .or_else(|| gui::fonts::TEST_STRINGS.first())
+        .map(|(_, text)| *text)
+        .unwrap()
+}
+
+fn show_input_devices(
+    ui: &mut egui::Ui,
+    lang: &unic_langid::LanguageIdentifier,
+    input_latency_reports: &std::collections::BTreeMap<String, inputlatency::Report>,
+    input_latency_monitor: &parking_lot::Mutex<inputlatency::Monitor>,
+    input_latency_tester: &mut Option<inputlatency::Tester>,
+) {
+    let input_latency_monitor = input_latency_monitor.lock();
+    let active_guid = input_latency_monitor
+        .active_controller()
+        .map(|controller| controller.guid.clone());
+
+    let mut devices = vec![(
+        inputlatency::KEYBOARD_DEVICE_KEY.to_string(),
+        format!("⌨️ {}", i18n::LOCALES.lookup(lang, "input-device-keyboard")),
+        None,
+    )];
+    for controller in input_latency_monitor.controllers() {
+        devices.push((
+            controller.guid.clone(),
+            format!("🎮 {}", controller.name),
+            Some(controller.polling_interval()),
+        ));
+    }
+
+    egui::Grid::new("settings-window-input-devices-grid")
+        .num_columns(4)
+        .show(ui, |ui| {
+            for (device_key, device_name, polling_interval) in devices {
+                if active_guid.as_ref() == Some(&device_key) {
+                    ui.strong(&device_name);
+                } else {
+                    ui.label(&device_name);
+                }
+
+                match polling_interval {
+                    Some(Some(polling_interval)) => {
+                        ui.label(i18n::LOCALES.lookup_with_args(
+                            lang,
+                            "input-device-polling-interval",
+                            &std::collections::HashMap::from([(
+                                "interval",
+                                format!("{:.0}", polling_interval.as_secs_f32() * 1000.0).into(),
+                            )]),
+                        ));
+                    }
+                    Some(None) => {
+                        ui.weak(i18n::LOCALES.lookup(lang, "input-device-polling-interval.unknown"));
+                    }
+                    None => {
+                        ui.label("");
+                    }
+                }
+
+                if let Some(report) = input_latency_reports.get(&device_key) {
+                    ui.label(gui::input_latency_window::report_summary(lang, report));
+                } else {
+                    ui.weak(i18n::LOCALES.lookup(lang, "input-device-not-tested"));
+                }
+
+                if ui
+                    .button(i18n::LOCALES.lookup(lang, "input-device-test-latency"))
+                    .clicked()
+                {
+                    *input_latency_tester = Some(inputlatency::Tester::new(device_key, device_name));
+                }
+                ui.end_row();
+            }
+        });
+}
+
+pub fn show_input_tab(
     ui: &mut egui::Ui,
     lang: &unic_langid::LanguageIdentifier,
     input_mapping: &mut input::Mapping,
+    input_latency_reports: &std::collections::BTreeMap<String, inputlatency::Report>,
     steal_input: &mut Option<gui::steal_input_window::State>,
+    input_latency_monitor: &parking_lot::Mutex<inputlatency::Monitor>,
+    input_latency_tester: &mut Option<inputlatency::Tester>,
 ) {
+    show_input_devices(
+        ui,
+        lang,
+        input_latency_reports,
+        input_latency_monitor,
+        input_latency_tester,
+    );
+    ui.separator();
+
     egui::Grid::new("settings-window-input-mapping-grid")
         .num_columns(2)
         .show(ui, |ui| {
             let mut add_row =
                 |label_text_id, get_mapping: fn(&mut input::Mapping) -> &mut Vec<input::PhysicalInput>| {
-                    ui.strong(i18n::LOCALES.lookup(lang, label_text_id).unwrap());
+                    ui.strong(i18n::LOCALES.lookup(lang, label_text_id));
                     ui.horizontal_wrapped(|ui| {
                         let mapping = get_mapping(input_mapping);
                         for (i, c) in mapping.clone().iter().enumerate() {
@@ -212,13 +488,13 @@ fn show_input_tab(
                                         input::PhysicalInput::Key(key) => {
                                             let raw = serde_plain::to_string(key).unwrap();
                                             i18n::LOCALES
-                                                .lookup(lang, &format!("physical-input-key-{}", raw))
+                                                .try_lookup(lang, &format!("physical-input-key-{}", raw))
                                                 .unwrap_or(raw)
                                         }
                                         input::PhysicalInput::Button(button) => {
                                             let raw = button.string();
                                             i18n::LOCALES
-                                                .lookup(lang, &format!("physical-input-button-{}", raw))
+                                                .try_lookup(lang, &format!("physical-input-button-{}", raw))
                                                 .unwrap_or(raw)
                                         }
                                         input::PhysicalInput::Axis { axis, direction } => {
@@ -231,7 +507,7 @@ fn show_input_tab(
                                                 }
                                             );
                                             i18n::LOCALES
-                                                .lookup(lang, &format!("physical-input-axis-motion-{}", raw))
+                                                .try_lookup(lang, &format!("physical-input-axis-motion-{}", raw))
                                                 .unwrap_or(raw)
                                         }
                                     });
@@ -276,7 +552,27 @@ fn show_input_tab(
             add_row("input-button-start", |input_mapping| &mut input_mapping.start);
             add_row("input-button-select", |input_mapping| &mut input_mapping.select);
             add_row("input-button-speed-up", |input_mapping| &mut input_mapping.speed_up);
+            add_row("input-button-slow-motion", |input_mapping| {
+                &mut input_mapping.slow_motion
+            });
             add_row("input-button-menu", |input_mapping| &mut input_mapping.menu);
+            add_row("input-button-save-state", |input_mapping| &mut input_mapping.save_state);
+            add_row("input-button-load-state", |input_mapping| &mut input_mapping.load_state);
+            add_row("input-button-save-states", |input_mapping| {
+                &mut input_mapping.save_states
+            });
+            add_row("input-button-replay-loop-start", |input_mapping| {
+                &mut input_mapping.replay_loop_start
+            });
+            add_row("input-button-replay-loop-end", |input_mapping| {
+                &mut input_mapping.replay_loop_end
+            });
+            add_row("input-button-replay-step-back", |input_mapping| {
+                &mut input_mapping.replay_step_back
+            });
+            add_row("input-button-toggle-streamer-mode", |input_mapping| {
+                &mut input_mapping.toggle_streamer_mode
+            });
         });
 }
 
@@ -284,16 +580,14 @@ fn show_graphics_tab(ui: &mut egui::Ui, config: &mut config::Config, window: &wi
     egui::Grid::new("settings-window-graphics-grid")
         .num_columns(2)
         .show(ui, |ui| {
-            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-max-scale").unwrap());
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-max-scale"));
             ui.add(
                 egui::DragValue::new(&mut config.max_scale)
                     .custom_formatter(|n, _| {
                         if n > 0.0 {
                             format!("{}", n)
                         } else {
-                            i18n::LOCALES
-                                .lookup(&config.language, "settings-max-scale.unset")
-                                .unwrap()
+                            i18n::LOCALES.lookup(&config.language, "settings-max-scale.unset")
                         }
                     })
                     .speed(1)
@@ -301,15 +595,21 @@ fn show_graphics_tab(ui: &mut egui::Ui, config: &mut config::Config, window: &wi
             );
             ui.end_row();
 
-            ui.strong(
-                i18n::LOCALES
-                    .lookup(&config.language, "settings-integer-scaling")
-                    .unwrap(),
-            );
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-integer-scaling"));
             ui.checkbox(&mut config.integer_scaling, "");
             ui.end_row();
 
-            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-ui-scale").unwrap());
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-vsync"))
+                .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-vsync.tooltip"));
+            ui.checkbox(&mut config.vsync, "");
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-frame-interpolation"))
+                .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-frame-interpolation.tooltip"));
+            ui.checkbox(&mut config.frame_interpolation, "");
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-ui-scale"));
             egui::ComboBox::from_id_source("settings-ui-scale")
                 .selected_text(format!("{}%", config.ui_scale_percent))
                 .show_ui(ui, |ui| {
@@ -323,7 +623,7 @@ fn show_graphics_tab(ui: &mut egui::Ui, config: &mut config::Config, window: &wi
                 });
             ui.end_row();
 
-            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-full-screen").unwrap());
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-full-screen"));
             ui.add(egui::Checkbox::new(&mut config.full_screen, ""));
             if config.full_screen && window.fullscreen().is_none() {
                 window.set_fullscreen(Some(winit::window::Fullscreen::Borderless(None)));
@@ -334,23 +634,13 @@ fn show_graphics_tab(ui: &mut egui::Ui, config: &mut config::Config, window: &wi
             ui.end_row();
 
             {
-                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-video-filter").unwrap());
-
-                let null_label = i18n::LOCALES
-                    .lookup(&config.language, "settings-video-filter.null")
-                    .unwrap();
-                let hq2x_label = i18n::LOCALES
-                    .lookup(&config.language, "settings-video-filter.hq2x")
-                    .unwrap();
-                let hq3x_label = i18n::LOCALES
-                    .lookup(&config.language, "settings-video-filter.hq3x")
-                    .unwrap();
-                let hq4x_label = i18n::LOCALES
-                    .lookup(&config.language, "settings-video-filter.hq4x")
-                    .unwrap();
-                let mmpx_label = i18n::LOCALES
-                    .lookup(&config.language, "settings-video-filter.mmpx")
-                    .unwrap();
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-video-filter"));
+
+                let null_label = i18n::LOCALES.lookup(&config.language, "settings-video-filter.null");
+                let hq2x_label = i18n::LOCALES.lookup(&config.language, "settings-video-filter.hq2x");
+                let hq3x_label = i18n::LOCALES.lookup(&config.language, "settings-video-filter.hq3x");
+                let hq4x_label = i18n::LOCALES.lookup(&config.language, "settings-video-filter.hq4x");
+                let mmpx_label = i18n::LOCALES.lookup(&config.language, "settings-video-filter.mmpx");
 
                 egui::ComboBox::from_id_source("settings-window-general-video-filter")
                     .width(200.0)
@@ -373,20 +663,12 @@ fn show_graphics_tab(ui: &mut egui::Ui, config: &mut config::Config, window: &wi
             }
 
             {
-                ui.strong(
-                    i18n::LOCALES
-                        .lookup(&config.language, "settings-graphics-backend")
-                        .unwrap(),
-                );
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-graphics-backend"));
 
                 #[cfg(feature = "glutin")]
-                let glutin_label = i18n::LOCALES
-                    .lookup(&config.language, "settings-graphics-backend.glutin")
-                    .unwrap();
+                let glutin_label = i18n::LOCALES.lookup(&config.language, "settings-graphics-backend.glutin");
                 #[cfg(feature = "wgpu")]
-                let wgpu_label = i18n::LOCALES
-                    .lookup(&config.language, "settings-graphics-backend.wgpu")
-                    .unwrap();
+                let wgpu_label = i18n::LOCALES.lookup(&config.language, "settings-graphics-backend.wgpu");
 
                 egui::ComboBox::from_id_source("settings-window-general-graphics-backend")
                     .width(200.0)
@@ -411,31 +693,67 @@ fn show_graphics_tab(ui: &mut egui::Ui, config: &mut config::Config, window: &wi
         });
 }
 
-fn show_audio_tab(ui: &mut egui::Ui, config: &mut config::Config) {
+pub fn show_audio_tab(ui: &mut egui::Ui, config: &mut config::Config, audio_device_names: &[String]) {
     egui::Grid::new("settings-window-audio-grid")
         .num_columns(2)
         .show(ui, |ui| {
             let mut volume = (config.volume as f32 * 100.0 / 256.0).round() as i32;
-            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-volume").unwrap());
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-volume"));
             ui.add(egui::Slider::new(&mut volume, 0..=100).suffix("%"));
             config.volume = volume * 0x100 / 100;
             ui.end_row();
 
             {
-                ui.strong(
-                    i18n::LOCALES
-                        .lookup(&config.language, "settings-audio-backend")
-                        .unwrap(),
-                );
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-audio-device"));
+
+                let default_label = i18n::LOCALES.lookup(&config.language, "settings-audio-device.default");
+                let selected_text = match config.audio_device.as_ref() {
+                    Some(name) if audio_device_names.contains(name) => name.clone(),
+                    Some(name) => i18n::LOCALES.lookup_with_args(
+                        &config.language,
+                        "settings-audio-device.missing",
+                        &std::collections::HashMap::from([("device", name.clone().into())]),
+                    ),
+                    None => default_label.clone(),
+                };
+
+                egui::ComboBox::from_id_source("settings-window-audio-device")
+                    .width(200.0)
+                    .selected_text(selected_text)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut config.audio_device, None, &default_label);
+                        for name in audio_device_names {
+                            ui.selectable_value(&mut config.audio_device, Some(name.clone()), name);
+                        }
+                    });
+                ui.end_row();
+            }
+
+            {
+                let mut notification_sound_volume =
+                    (config.notification_sound_volume as f32 * 100.0 / 256.0).round() as i32;
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-notification-sound-volume"));
+                ui.horizontal(|ui| {
+                    ui.add_enabled(
+                        !config.notification_sound_muted,
+                        egui::Slider::new(&mut notification_sound_volume, 0..=100).suffix("%"),
+                    );
+                    ui.checkbox(
+                        &mut config.notification_sound_muted,
+                        i18n::LOCALES.lookup(&config.language, "settings-notification-sound-volume.mute"),
+                    );
+                });
+                config.notification_sound_volume = notification_sound_volume * 0x100 / 100;
+                ui.end_row();
+            }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-audio-backend"));
 
                 #[cfg(feature = "sdl2-audio")]
-                let sdl2_label = i18n::LOCALES
-                    .lookup(&config.language, "settings-audio-backend.sdl2")
-                    .unwrap();
+                let sdl2_label = i18n::LOCALES.lookup(&config.language, "settings-audio-backend.sdl2");
                 #[cfg(feature = "cpal")]
-                let cpal_label = i18n::LOCALES
-                    .lookup(&config.language, "settings-audio-backend.cpal")
-                    .unwrap();
+                let cpal_label = i18n::LOCALES.lookup(&config.language, "settings-audio-backend.cpal");
 
                 egui::ComboBox::from_id_source("settings-window-general-audio-backend")
                     .width(200.0)
@@ -456,27 +774,141 @@ fn show_audio_tab(ui: &mut egui::Ui, config: &mut config::Config) {
         });
 }
 
+fn show_accessibility_tab(ui: &mut egui::Ui, config: &mut config::Config) {
+    ui.label(i18n::LOCALES.lookup(&config.language, "settings-assist.description"));
+
+    egui::Grid::new("settings-window-accessibility-grid")
+        .num_columns(2)
+        .show(ui, |ui| {
+            let keys = [
+                ("input-button-left", mgba::input::keys::LEFT),
+                ("input-button-right", mgba::input::keys::RIGHT),
+                ("input-button-up", mgba::input::keys::UP),
+                ("input-button-down", mgba::input::keys::DOWN),
+                ("input-button-a", mgba::input::keys::A),
+                ("input-button-b", mgba::input::keys::B),
+                ("input-button-l", mgba::input::keys::L),
+                ("input-button-r", mgba::input::keys::R),
+                ("input-button-start", mgba::input::keys::START),
+                ("input-button-select", mgba::input::keys::SELECT),
+            ];
+
+            let language = &config.language;
+            let key_checkboxes = |ui: &mut egui::Ui, mask: &mut u32| {
+                ui.horizontal_wrapped(|ui| {
+                    for (label_text_id, key) in keys {
+                        let mut checked = *mask & key != 0;
+                        if ui
+                            .checkbox(&mut checked, i18n::LOCALES.lookup(language, label_text_id))
+                            .changed()
+                        {
+                            *mask ^= key;
+                        }
+                    }
+                });
+            };
+
+            ui.strong(i18n::LOCALES.lookup(language, "settings-assist-toggle-hold"))
+                .on_hover_text(i18n::LOCALES.lookup(language, "settings-assist-toggle-hold.tooltip"));
+            key_checkboxes(ui, &mut config.assist.toggle_hold_keys);
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(language, "settings-assist-turbo"))
+                .on_hover_text(i18n::LOCALES.lookup(language, "settings-assist-turbo.tooltip"));
+            key_checkboxes(ui, &mut config.assist.turbo_keys);
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(language, "settings-assist-turbo-interval"));
+            ui.add(
+                egui::Slider::new(
+                    &mut config.assist.turbo_interval_frames,
+                    1..=assist::MAX_TURBO_INTERVAL_FRAMES,
+                )
+                .suffix(i18n::LOCALES.lookup(language, "settings-assist-turbo-interval.suffix")),
+            );
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(language, "settings-assist-slow-motion"))
+                .on_hover_text(i18n::LOCALES.lookup(language, "settings-assist-slow-motion.tooltip"));
+            ui.add(
+                egui::Slider::new(
+                    &mut config.assist.slow_motion_percent,
+                    assist::MIN_SLOW_MOTION_PERCENT..=assist::MAX_SLOW_MOTION_PERCENT,
+                )
+                .suffix("%"),
+            );
+            ui.end_row();
+        });
+}
+
 fn show_netplay_tab(ui: &mut egui::Ui, config: &mut config::Config) {
     egui::Grid::new("settings-window-netplay-grid")
         .num_columns(2)
         .show(ui, |ui| {
-            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-input-delay").unwrap());
-            ui.add(egui::Slider::new(&mut config.input_delay, 2..=10));
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-input-delay"));
+            ui.add(egui::Slider::new(
+                &mut config.input_delay,
+                2..=net::protocol::MAX_INPUT_DELAY,
+            ));
             ui.end_row();
 
-            ui.strong(
-                i18n::LOCALES
-                    .lookup(&config.language, "settings-max-queue-length")
-                    .unwrap(),
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-max-queue-length"));
+            // The queue has to fit the most delay either side could play with, and then some.
+            ui.add(
+                egui::DragValue::new(&mut config.max_queue_length).speed(1).clamp_range(
+                    net::protocol::MAX_INPUT_DELAY + net::protocol::MAX_INPUT_DELAY_HANDICAP + 1..=u32::MAX,
+                ),
             );
-            ui.add(egui::DragValue::new(&mut config.max_queue_length).speed(1));
             ui.end_row();
 
-            ui.strong(
-                i18n::LOCALES
-                    .lookup(&config.language, "settings-matchmaking-endpoint")
-                    .unwrap(),
-            );
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-match-start-timeout"));
+            ui.add(
+                egui::DragValue::new(&mut config.match_start_timeout_secs)
+                    .speed(1)
+                    .clamp_range(5..=300)
+                    .suffix("s"),
+            )
+            .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-match-start-timeout.tooltip"));
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-replay-recording"))
+                .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-replay-recording.tooltip"));
+            egui::ComboBox::from_id_source("settings-window-netplay-replay-recording")
+                .selected_text(i18n::LOCALES.lookup(
+                    &config.language,
+                    match config.replay_recording {
+                        net::protocol::ReplayRecording::Always => "play-details-replay-recording.always",
+                        net::protocol::ReplayRecording::Ask => "play-details-replay-recording.ask",
+                        net::protocol::ReplayRecording::Never => "play-details-replay-recording.never",
+                    },
+                ))
+                .show_ui(ui, |ui| {
+                    for (replay_recording, key) in [
+                        (
+                            net::protocol::ReplayRecording::Always,
+                            "play-details-replay-recording.always",
+                        ),
+                        (net::protocol::ReplayRecording::Ask, "play-details-replay-recording.ask"),
+                        (
+                            net::protocol::ReplayRecording::Never,
+                            "play-details-replay-recording.never",
+                        ),
+                    ] {
+                        ui.selectable_value(
+                            &mut config.replay_recording,
+                            replay_recording,
+                            i18n::LOCALES.lookup(&config.language, key),
+                        );
+                    }
+                });
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-desync-detection"));
+            ui.checkbox(&mut config.desync_detection, "")
+                .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-desync-detection.tooltip"));
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-matchmaking-endpoint"));
             let matchmaking_endpoint_is_empty = config.matchmaking_endpoint.is_empty();
             ui.add(
                 egui::TextEdit::singleline(&mut config.matchmaking_endpoint)
@@ -489,21 +921,255 @@ fn show_netplay_tab(ui: &mut egui::Ui, config: &mut config::Config) {
             );
             ui.end_row();
 
-            ui.strong(
-                i18n::LOCALES
-                    .lookup(&config.language, "settings-replaycollector-endpoint")
-                    .unwrap(),
-            );
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-replaycollector-endpoint"));
             ui.add(egui::TextEdit::singleline(&mut config.replaycollector_endpoint).desired_width(200.0));
             ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-replay-upload-endpoint"))
+                .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-replay-upload-endpoint.tooltip"));
+            ui.vertical(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut config.replay_upload.endpoint)
+                        .desired_width(200.0)
+                        .hint_text("https://"),
+                );
+                if !config.replay_upload.endpoint.is_empty() && !config.replay_upload.is_enabled() {
+                    ui.colored_label(
+                        gui::theme::colors(ui.ctx()).warning,
+                        i18n::LOCALES.lookup(&config.language, "settings-replay-upload-endpoint.invalid"),
+                    );
+                }
+            });
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-replay-upload-token"));
+            ui.add(
+                egui::TextEdit::singleline(&mut config.replay_upload.token)
+                    .password(true)
+                    .desired_width(200.0),
+            );
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-replay-upload-include-notes"));
+            ui.checkbox(&mut config.replay_upload.include_notes, "")
+                .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-replay-upload-include-notes.tooltip"));
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-notify-on-opponent-join"));
+            ui.checkbox(&mut config.notify_on_opponent_join, "");
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-notify-on-opponent-compatible"));
+            ui.checkbox(&mut config.notify_on_opponent_compatible, "");
+            ui.end_row();
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-notify-on-opponent-ready"));
+            ui.checkbox(&mut config.notify_on_opponent_ready, "");
+            ui.end_row();
         });
 }
 
+fn show_network_tab(
+    ui: &mut egui::Ui,
+    config: &mut config::Config,
+    network_test: std::sync::Arc<parking_lot::Mutex<Option<NetworkTest>>>,
+) {
+    egui::Grid::new("settings-window-network-grid")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-proxy-mode"));
+            egui::ComboBox::from_id_source("settings-window-network-proxy-mode")
+                .selected_text(i18n::LOCALES.lookup(
+                    &config.language,
+                    match config.network.mode {
+                        net::proxy::Mode::Direct => "settings-proxy-mode.direct",
+                        net::proxy::Mode::System => "settings-proxy-mode.system",
+                        net::proxy::Mode::Manual => "settings-proxy-mode.manual",
+                    },
+                ))
+                .show_ui(ui, |ui| {
+                    for (mode, key) in [
+                        (net::proxy::Mode::Direct, "settings-proxy-mode.direct"),
+                        (net::proxy::Mode::System, "settings-proxy-mode.system"),
+                        (net::proxy::Mode::Manual, "settings-proxy-mode.manual"),
+                    ] {
+                        ui.selectable_value(
+                            &mut config.network.mode,
+                            mode,
+                            i18n::LOCALES.lookup(&config.language, key),
+                        );
+                    }
+                });
+            ui.end_row();
+
+            if config.network.mode == net::proxy::Mode::Manual {
+                let proxy = &mut config.network.proxy;
+
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-proxy-scheme"));
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut proxy.scheme, net::proxy::Scheme::Http, "HTTP");
+                    ui.selectable_value(&mut proxy.scheme, net::proxy::Scheme::Socks5, "SOCKS5");
+                });
+                ui.end_row();
+
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-proxy-address"));
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut proxy.host).desired_width(150.0));
+                    ui.add(egui::DragValue::new(&mut proxy.port).speed(1).prefix(":"));
+                });
+                ui.end_row();
+
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-proxy-username"));
+                ui.add(
+                    egui::TextEdit::singleline(&mut proxy.username)
+                        .desired_width(200.0)
+                        .hint_text(i18n::LOCALES.lookup(&config.language, "settings-proxy-username.none")),
+                );
+                ui.end_row();
+
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-proxy-password"));
+                ui.add(
+                    egui::TextEdit::singleline(&mut proxy.password)
+                        .password(true)
+                        .desired_width(200.0),
+                );
+                ui.end_row();
+            }
+
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-network-test"));
+            ui.horizontal(|ui| {
+                let in_progress = matches!(*network_test.lock(), Some(NetworkTest::InProgress));
+                if ui
+                    .add_enabled(
+                        !in_progress,
+                        egui::Button::new(i18n::LOCALES.lookup(&config.language, "settings-network-test.run")),
+                    )
+                    .clicked()
+                {
+                    *network_test.lock() = Some(NetworkTest::InProgress);
+                    let matchmaking_endpoint = if !config.matchmaking_endpoint.is_empty() {
+                        config.matchmaking_endpoint.clone()
+                    } else {
+                        config::DEFAULT_MATCHMAKING_ENDPOINT.to_string()
+                    };
+                    let network = config.network.clone();
+                    let network_test = network_test.clone();
+                    let egui_ctx = ui.ctx().clone();
+                    tokio::task::spawn(async move {
+                        *network_test.lock() = Some(test_network(&matchmaking_endpoint, &network).await);
+                        egui_ctx.request_repaint();
+                    });
+                }
+
+                match &*network_test.lock() {
+                    Some(NetworkTest::InProgress) => {
+                        ui.spinner();
+                    }
+                    Some(NetworkTest::Succeeded { proxy: Some(proxy) }) => {
+                        ui.label(i18n::LOCALES.lookup_with_args(
+                            &config.language,
+                            "settings-network-test.succeeded-through-proxy",
+                            &std::collections::HashMap::from([("proxy", proxy.as_str().into())]),
+                        ));
+                    }
+                    Some(NetworkTest::Succeeded { proxy: None }) => {
+                        ui.label(i18n::LOCALES.lookup(&config.language, "settings-network-test.succeeded"));
+                    }
+                    Some(NetworkTest::Failed(error)) => {
+                        ui.colored_label(
+                            gui::theme::colors(ui.ctx()).warning,
+                            i18n::LOCALES.lookup_with_args(
+                                &config.language,
+                                "settings-network-test.failed",
+                                &std::collections::HashMap::from([("error", error.as_str().into())]),
+                            ),
+                        );
+                    }
+                    None => {}
+                }
+            });
+            ui.end_row();
+        });
+
+    ui.separator();
+
+    ui.strong(i18n::LOCALES.lookup(&config.language, "settings-turn-servers"))
+        .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-turn-servers.tooltip"));
+    let mut remove = None;
+    egui::Grid::new("settings-window-network-turn-servers-grid")
+        .num_columns(4)
+        .show(ui, |ui| {
+            for (i, turn_server) in config.network.turn_servers.iter_mut().enumerate() {
+                ui.add(
+                    egui::TextEdit::singleline(&mut turn_server.url)
+                        .desired_width(200.0)
+                        .hint_text("turn:turn.example.com:3478"),
+                );
+                ui.add(
+                    egui::TextEdit::singleline(&mut turn_server.username)
+                        .desired_width(100.0)
+                        .hint_text(i18n::LOCALES.lookup(&config.language, "settings-turn-servers.username")),
+                );
+                ui.add(
+                    egui::TextEdit::singleline(&mut turn_server.credential)
+                        .password(true)
+                        .desired_width(100.0)
+                        .hint_text(i18n::LOCALES.lookup(&config.language, "settings-turn-servers.credential")),
+                );
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+    if let Some(i) = remove {
+        config.network.turn_servers.remove(i);
+    }
+    if ui
+        .button(format!(
+            "➕ {}",
+            i18n::LOCALES.lookup(&config.language, "settings-turn-servers.add")
+        ))
+        .clicked()
+    {
+        config.network.turn_servers.push(Default::default());
+    }
+}
+
+/// Checks that the matchmaking server can be reached with the given network settings, through the proxy if there is one. Only the connection is made: nothing is sent, so no session is started on the server.
+async fn test_network(matchmaking_endpoint: &str, network: &net::proxy::Settings) -> NetworkTest {
+    const TEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+    let url = match url::Url::parse(matchmaking_endpoint) {
+        Ok(url) => url,
+        Err(e) => {
+            return NetworkTest::Failed(e.to_string());
+        }
+    };
+    let (host, port) = match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) => (host, port),
+        _ => {
+            return NetworkTest::Failed(format!("invalid matchmaking endpoint: {}", matchmaking_endpoint));
+        }
+    };
+    let proxy = network.proxy_for(&url);
+    match tokio::time::timeout(TEST_TIMEOUT, net::proxy::connect(proxy.as_ref(), host, port)).await {
+        Ok(Ok(_)) => NetworkTest::Succeeded {
+            proxy: proxy.map(|proxy| proxy.to_string()),
+        },
+        Ok(Err(e)) => {
+            log::warn!("network test failed: {}", e);
+            NetworkTest::Failed(e.to_string())
+        }
+        Err(_) => NetworkTest::Failed(format!("timed out after {}s", TEST_TIMEOUT.as_secs())),
+    }
+}
+
 fn show_patches_tab(ui: &mut egui::Ui, config: &mut config::Config) {
     egui::Grid::new("settings-window-patches-grid")
         .num_columns(2)
         .show(ui, |ui| {
-            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-patch-repo").unwrap());
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-patch-repo"));
             let patch_repo = config.patch_repo.is_empty();
             ui.add(
                 egui::TextEdit::singleline(&mut config.patch_repo)
@@ -512,11 +1178,7 @@ fn show_patches_tab(ui: &mut egui::Ui, config: &mut config::Config) {
             );
             ui.end_row();
 
-            ui.strong(
-                i18n::LOCALES
-                    .lookup(&config.language, "settings-enable-patch-autoupdate")
-                    .unwrap(),
-            );
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-enable-patch-autoupdate"));
             ui.checkbox(&mut config.enable_patch_autoupdate, "");
             ui.end_row();
         });
@@ -528,75 +1190,150 @@ fn show_advanced_tab(
     roms_scanner: rom::Scanner,
     saves_scanner: save::Scanner,
     patches_scanner: patch::Scanner,
+    welcome: &mut Option<gui::welcome::State>,
+    log_viewer: &mut Option<gui::log_viewer_window::State>,
+    profile_window: &mut Option<gui::config_profile_window::State>,
+    bios_error: &mut Option<String>,
 ) {
     egui::Grid::new("settings-window-general-grid")
         .num_columns(2)
         .show(ui, |ui| {
             {
-                ui.strong(
-                    i18n::LOCALES
-                        .lookup(&config.language, "settings-enable-updater")
-                        .unwrap(),
-                );
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-enable-updater"));
                 ui.checkbox(&mut config.enable_updater, "");
                 ui.end_row();
             }
 
             {
-                ui.strong(
-                    i18n::LOCALES
-                        .lookup(&config.language, "settings-allow-prerelease-upgrades")
-                        .unwrap(),
-                );
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-allow-prerelease-upgrades"));
                 ui.checkbox(&mut config.allow_prerelease_upgrades, "");
                 ui.end_row();
             }
 
             {
-                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-data-path").unwrap());
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-data-path"));
                 ui.horizontal(|ui| {
                     ui.add(
                         egui::TextEdit::singleline(&mut format!("{}", config.data_path.display())).interactive(false),
                     );
 
                     if ui
-                        .button(
-                            i18n::LOCALES
-                                .lookup(&config.language, "settings-data-path.open")
-                                .unwrap(),
-                        )
+                        .button(i18n::LOCALES.lookup(&config.language, "settings-data-path.open"))
                         .clicked()
                     {
                         let _ = open::that(&config.data_path);
                     }
 
                     if ui
-                        .button(
-                            i18n::LOCALES
-                                .lookup(&config.language, "settings-data-path.change")
-                                .unwrap(),
-                        )
+                        .button(i18n::LOCALES.lookup(&config.language, "settings-data-path.change"))
                         .clicked()
                     {
                         if let Some(data_path) = rfd::FileDialog::new().set_directory(&config.data_path).pick_folder() {
-                            config.data_path = data_path;
-                            let _ = config.ensure_dirs();
-                            tokio::task::spawn_blocking({
-                                let egui_ctx = ui.ctx().clone();
-                                let roms_scanner = roms_scanner.clone();
-                                let saves_scanner = saves_scanner.clone();
-                                let patches_scanner = patches_scanner.clone();
-                                let roms_path = config.roms_path();
-                                let saves_path = config.saves_path();
-                                let patches_path = config.patches_path();
-                                move || {
-                                    roms_scanner.rescan(move || Some(game::scan_roms(&roms_path)));
-                                    saves_scanner.rescan(move || Some(save::scan_saves(&saves_path)));
-                                    patches_scanner
-                                        .rescan(move || Some(patch::scan(&patches_path).unwrap_or_default()));
-                                    egui_ctx.request_repaint();
+                            match config.set_data_path(data_path) {
+                                Ok(()) => {
+                                    gui::rescan_all(
+                                        ui.ctx(),
+                                        config,
+                                        roms_scanner.clone(),
+                                        saves_scanner.clone(),
+                                        patches_scanner.clone(),
+                                    );
                                 }
-                            });
+                                Err(e) => {
+                                    log::error!("failed to change data path: {:?}", e);
+                                }
+                            }
+                        }
+                    }
+                });
+                ui.end_row();
+            }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-bios"))
+                    .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-bios.tooltip"));
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut config.bios_path.as_ref().map_or_else(
+                                || i18n::LOCALES.lookup(&config.language, "settings-bios.emulated"),
+                                |path| format!("{}", path.display()),
+                            ))
+                            .interactive(false),
+                        );
+
+                        if ui
+                            .button(i18n::LOCALES.lookup(&config.language, "settings-bios.change"))
+                            .clicked()
+                        {
+                            if let Some(path) = rfd::FileDialog::new().add_filter("GBA BIOS", &["bin"]).pick_file() {
+                                // Only BIOSes that will actually be used are taken, so a bad one is caught here rather than when a match starts.
+                                match bios::load(&path) {
+                                    Ok(_) => {
+                                        config.bios_path = Some(path);
+                                        *bios_error = None;
+                                    }
+                                    Err(e) => {
+                                        log::error!("rejected bios {}: {:?}", path.display(), e);
+                                        *bios_error = Some(e.to_string());
+                                    }
+                                }
+                            }
+                        }
+
+                        if ui
+                            .add_enabled(
+                                config.bios_path.is_some(),
+                                egui::Button::new(i18n::LOCALES.lookup(&config.language, "settings-bios.clear")),
+                            )
+                            .clicked()
+                        {
+                            config.bios_path = None;
+                            *bios_error = None;
+                        }
+                    });
+
+                    if let Some(error) = bios_error.as_ref() {
+                        ui.colored_label(
+                            gui::theme::colors(ui.ctx()).warning,
+                            i18n::LOCALES.lookup_with_args(
+                                &config.language,
+                                "settings-bios.error",
+                                &std::collections::HashMap::from([("error", error.as_str().into())]),
+                            ),
+                        );
+                    }
+                });
+                ui.end_row();
+            }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-setup"));
+                if ui
+                    .button(i18n::LOCALES.lookup(&config.language, "settings-setup.run"))
+                    .clicked()
+                {
+                    *welcome = Some(gui::welcome::State::new(config));
+                }
+                ui.end_row();
+            }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-profile"));
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(i18n::LOCALES.lookup(&config.language, "settings-profile.export"))
+                        .clicked()
+                    {
+                        *profile_window = Some(gui::config_profile_window::State::export());
+                    }
+
+                    if ui
+                        .button(i18n::LOCALES.lookup(&config.language, "settings-profile.import"))
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("TOML", &["toml"]).pick_file() {
+                            *profile_window = Some(gui::config_profile_window::State::import(config, &path));
                         }
                     }
                 });
@@ -604,10 +1341,106 @@ fn show_advanced_tab(
             }
 
             {
-                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-debug").unwrap());
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-enable-broadcast-api"));
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut config.enable_broadcast_api, "")
+                        .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-enable-broadcast-api.tooltip"));
+                    ui.add_enabled(
+                        config.enable_broadcast_api,
+                        egui::DragValue::new(&mut config.broadcast_api_port)
+                            .prefix("127.0.0.1:")
+                            .speed(1)
+                            .clamp_range(1024..=65535),
+                    );
+                });
+                ui.end_row();
+            }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-debug"));
                 ui.checkbox(&mut config.show_debug, "");
                 ui.end_row();
             }
+
+            if config.show_debug {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-shadow-view"));
+                ui.checkbox(&mut config.show_shadow_view, "")
+                    .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-shadow-view.tooltip"));
+                ui.end_row();
+
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-log-levels"))
+                    .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-log-levels.tooltip"));
+                ui.vertical(|ui| {
+                    egui::Grid::new("settings-window-log-levels-grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            for name in logging::SUBSYSTEMS
+                                .iter()
+                                .map(|subsystem| subsystem.name)
+                                .chain(std::iter::once(logging::OTHER))
+                            {
+                                ui.label(name);
+                                let mut level = logging::level(name);
+                                egui::ComboBox::from_id_source(("settings-log-level", name))
+                                    .selected_text(level.as_str())
+                                    .show_ui(ui, |ui| {
+                                        for option in log::LevelFilter::iter() {
+                                            ui.selectable_value(&mut level, option, option.as_str());
+                                        }
+                                    });
+                                if level != logging::level(name) {
+                                    logging::set_level(name, level);
+                                    config.log_levels.insert(name.to_string(), level.as_str().to_string());
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    if ui
+                        .button(i18n::LOCALES.lookup(&config.language, "settings-log-levels.open-viewer"))
+                        .clicked()
+                    {
+                        *log_viewer = Some(gui::log_viewer_window::State::new());
+                    }
+                });
+                ui.end_row();
+            }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-texture-cache-capacity"));
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut config.texture_cache_capacity)
+                            .speed(1)
+                            .clamp_range(1..=usize::MAX),
+                    );
+                    if config.show_debug {
+                        let metrics = gui::save_view::texture_cache::metrics();
+                        ui.weak(i18n::LOCALES.lookup_with_args(
+                            &config.language,
+                            "settings-texture-cache-capacity.usage",
+                            &std::collections::HashMap::from([
+                                ("entries", metrics.entries.into()),
+                                ("kib", (metrics.bytes / 1024).into()),
+                                ("loads", metrics.loads.into()),
+                                ("evictions", metrics.evictions.into()),
+                            ]),
+                        ));
+                    }
+                });
+                ui.end_row();
+            }
+
+            {
+                ui.strong(i18n::LOCALES.lookup(&config.language, "settings-hang-timeout"));
+                ui.add(
+                    egui::DragValue::new(&mut config.hang_timeout_secs)
+                        .speed(1)
+                        .clamp_range(3..=300)
+                        .suffix("s"),
+                )
+                .on_hover_text(i18n::LOCALES.lookup(&config.language, "settings-hang-timeout.tooltip"));
+                ui.end_row();
+            }
         });
 }
 
@@ -1,5 +1,3 @@
-use fluent_templates::Loader;
-
 use crate::{i18n, input};
 
 pub struct State {
@@ -41,19 +39,16 @@ pub fn show(ctx: &egui::Context, language: &unic_langid::LanguageIdentifier, ste
 
                         ui.label(
                             egui::RichText::new(
-                                i18n::LOCALES
-                                    .lookup_with_args(
-                                        &language,
-                                        "input-mapping-prompt",
-                                        &std::collections::HashMap::from([(
-                                            "key",
-                                            i18n::LOCALES
-                                                .lookup(&language, userdata.downcast_ref::<&str>().unwrap())
-                                                .unwrap()
-                                                .into(),
-                                        )]),
-                                    )
-                                    .unwrap(),
+                                i18n::LOCALES.lookup_with_args(
+                                    &language,
+                                    "input-mapping-prompt",
+                                    &std::collections::HashMap::from([(
+                                        "key",
+                                        i18n::LOCALES
+                                            .lookup(&language, userdata.downcast_ref::<&str>().unwrap())
+                                            .into(),
+                                    )]),
+                                ),
                             )
                             .size(32.0),
                         );
@@ -0,0 +1,290 @@
+use serde::Deserialize;
+
+use crate::config;
+
+/// Text on highlighted backgrounds must have at least this contrast ratio with them (WCAG AA for normal text).
+const MIN_CONTRAST: f32 = 4.5;
+
+/// Colors Tango uses for its own purposes, on top of egui's visuals.
+#[derive(Clone, Copy, Debug)]
+pub struct Colors {
+    /// Shown when a player is ready, or something has been set up.
+    pub ready: egui::Color32,
+    /// Shown next to things that need attention, e.g. settings incompatible with the opponent's.
+    pub warning: egui::Color32,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            ready: egui::Color32::from_rgb(0x4c, 0xaf, 0x50),
+            warning: egui::Color32::from_rgb(0xf4, 0xba, 0x51),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Theme {
+    pub visuals: egui::style::Visuals,
+    pub colors: Colors,
+}
+
+impl Theme {
+    pub fn light() -> Self {
+        let colors = Colors::default();
+        let mut visuals = egui::style::Visuals::light();
+        visuals.selection.bg_fill = colors.ready;
+        visuals.selection.stroke.color = egui::Color32::BLACK;
+        Self { visuals, colors }
+    }
+
+    pub fn dark() -> Self {
+        let colors = Colors::default();
+        let mut visuals = egui::style::Visuals::dark();
+        visuals.selection.bg_fill = colors.ready;
+        visuals.selection.stroke.color = egui::Color32::WHITE;
+        Self { visuals, colors }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct HexColor(egui::Color32);
+
+impl<'de> serde::Deserialize<'de> for HexColor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let hex = s.strip_prefix('#').unwrap_or(&s);
+        if hex.len() != 6 {
+            return Err(serde::de::Error::custom(format!(
+                "expected a color like #rrggbb, got {:?}",
+                s
+            )));
+        }
+        let v = u32::from_str_radix(hex, 16).map_err(serde::de::Error::custom)?;
+        Ok(Self(egui::Color32::from_rgb((v >> 16) as u8, (v >> 8) as u8, v as u8)))
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum Base {
+    Light,
+    Dark,
+}
+
+impl Default for Base {
+    fn default() -> Self {
+        Self::Dark
+    }
+}
+
+/// A user theme, as written in a TOML file in the themes directory. Anything left out is taken from the base theme.
+#[derive(serde::Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+struct ThemeFile {
+    base: Base,
+    accent: Option<HexColor>,
+    window_fill: Option<HexColor>,
+    selection: Option<HexColor>,
+    text: Option<HexColor>,
+    strong_text: Option<HexColor>,
+    ready: Option<HexColor>,
+    warning: Option<HexColor>,
+}
+
+impl ThemeFile {
+    fn into_theme(self) -> Theme {
+        let mut theme = match self.base {
+            Base::Light => Theme::light(),
+            Base::Dark => Theme::dark(),
+        };
+        let visuals = &mut theme.visuals;
+
+        if let Some(HexColor(accent)) = self.accent {
+            visuals.hyperlink_color = accent;
+            visuals.selection.bg_fill = accent;
+            visuals.widgets.hovered.bg_stroke.color = accent;
+        }
+
+        if let Some(HexColor(window_fill)) = self.window_fill {
+            visuals.widgets.noninteractive.bg_fill = window_fill;
+        }
+
+        if let Some(HexColor(selection)) = self.selection {
+            visuals.selection.bg_fill = selection;
+        }
+        visuals.selection.stroke.color = text_color_on(visuals.selection.bg_fill);
+
+        if let Some(HexColor(text)) = self.text {
+            visuals.widgets.noninteractive.fg_stroke.color = text;
+            visuals.widgets.inactive.fg_stroke.color = text;
+        }
+
+        if let Some(HexColor(strong_text)) = self.strong_text {
+            visuals.widgets.hovered.fg_stroke.color = strong_text;
+            visuals.widgets.active.fg_stroke.color = strong_text;
+        }
+
+        if let Some(HexColor(ready)) = self.ready {
+            theme.colors.ready = ready;
+        }
+
+        if let Some(HexColor(warning)) = self.warning {
+            theme.colors.warning = warning;
+        }
+
+        theme
+    }
+}
+
+/// The built-in themes and any user themes found in the themes directory.
+pub struct Themes {
+    light: Theme,
+    dark: Theme,
+    user: std::collections::BTreeMap<String, Theme>,
+}
+
+impl Themes {
+    pub fn new() -> Self {
+        Self {
+            light: Theme::light(),
+            dark: Theme::dark(),
+            user: std::collections::BTreeMap::new(),
+        }
+    }
+
+    /// Loads user themes from the TOML files in the given directory, replacing any previously loaded. Files that fail to load are skipped.
+    pub fn load_user_themes(&mut self, path: &std::path::Path) {
+        self.user.clear();
+
+        let read_dir = match std::fs::read_dir(path) {
+            Ok(read_dir) => read_dir,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("failed to read themes directory {}: {}", path.display(), e);
+                }
+                return;
+            }
+        };
+
+        for entry in read_dir {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log::warn!("failed to read themes directory entry: {}", e);
+                    continue;
+                }
+            };
+
+            let path = entry.path();
+            if path.extension() != Some(std::ffi::OsStr::new("toml")) {
+                continue;
+            }
+
+            let name = if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                name.to_string()
+            } else {
+                continue;
+            };
+
+            let theme_file = match std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|contents| Ok(toml::from_str::<ThemeFile>(&contents)?))
+            {
+                Ok(theme_file) => theme_file,
+                Err(e) => {
+                    log::warn!("failed to load theme {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            self.user.insert(name, theme_file.into_theme());
+        }
+    }
+
+    pub fn user_theme_names(&self) -> impl Iterator<Item = &str> {
+        self.user.keys().map(|name| name.as_str())
+    }
+
+    /// Gets the theme to use for the configured theme. User themes that no longer exist fall back to following the system.
+    pub fn resolve(&self, theme: &config::Theme) -> &Theme {
+        match theme {
+            config::Theme::Light => &self.light,
+            config::Theme::Dark => &self.dark,
+            config::Theme::User(name) if self.user.contains_key(name) => &self.user[name],
+            config::Theme::System | config::Theme::User(_) => match dark_light::detect() {
+                dark_light::Mode::Light => &self.light,
+                dark_light::Mode::Dark => &self.dark,
+            },
+        }
+    }
+}
+
+fn colors_id() -> egui::Id {
+    egui::Id::new("tango-theme-colors")
+}
+
+/// Sets the visuals for a theme, and makes its colors available to everything drawn with the context.
+pub fn apply(ctx: &egui::Context, theme: &Theme) {
+    ctx.set_visuals(theme.visuals.clone());
+    ctx.data().insert_temp(colors_id(), theme.colors);
+}
+
+/// Gets the current theme's colors.
+pub fn colors(ctx: &egui::Context) -> Colors {
+    ctx.data().get_temp(colors_id()).unwrap_or_default()
+}
+
+fn relative_luminance(color: egui::Color32) -> f32 {
+    let channel = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color.r()) + 0.7152 * channel(color.g()) + 0.0722 * channel(color.b())
+}
+
+fn contrast_ratio(a: egui::Color32, b: egui::Color32) -> f32 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+fn lerp(from: egui::Color32, to: egui::Color32, t: f32) -> egui::Color32 {
+    let channel = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+    egui::Color32::from_rgb(
+        channel(from.r(), to.r()),
+        channel(from.g(), to.g()),
+        channel(from.b(), to.b()),
+    )
+}
+
+/// Gets black or white, whichever is more readable on the given fill.
+pub fn text_color_on(fill: egui::Color32) -> egui::Color32 {
+    if contrast_ratio(fill, egui::Color32::BLACK) >= contrast_ratio(fill, egui::Color32::WHITE) {
+        egui::Color32::BLACK
+    } else {
+        egui::Color32::WHITE
+    }
+}
+
+/// Blends a highlight color into the window fill until the theme's text is readable on it.
+///
+/// Highlights are given as they should look on a light background: on dark themes this darkens them towards the background instead of washing out the text.
+pub fn highlight_fill(visuals: &egui::style::Visuals, color: egui::Color32) -> egui::Color32 {
+    const STEPS: usize = 10;
+    let bg = visuals.window_fill();
+    let text = visuals.text_color();
+    for i in 0..STEPS {
+        let fill = lerp(bg, color, 1.0 - i as f32 / STEPS as f32);
+        if contrast_ratio(fill, text) >= MIN_CONTRAST {
+            return fill;
+        }
+    }
+    bg
+}
@@ -1,5 +1,3 @@
-use fluent_templates::Loader;
-
 use crate::{i18n, sync, updater};
 
 pub fn show(
@@ -8,17 +6,17 @@ pub fn show(
     language: &unic_langid::LanguageIdentifier,
     updater: &updater::Updater,
 ) {
-    egui::Window::new(format!("🆕 {}", i18n::LOCALES.lookup(language, "updater").unwrap()))
+    egui::Window::new(format!("🆕 {}", i18n::LOCALES.lookup(language, "updater")))
         .id(egui::Id::new("updater-window"))
         .open(open)
         .show(ctx, |ui| {
             let status = sync::block_on(updater.status());
             egui::Grid::new("updater-window-grid").num_columns(2).show(ui, |ui| {
-                ui.strong(i18n::LOCALES.lookup(language, "updater-current-version").unwrap());
+                ui.strong(i18n::LOCALES.lookup(language, "updater-current-version"));
                 ui.label(format!("v{}", updater.current_version()));
                 ui.end_row();
 
-                ui.strong(i18n::LOCALES.lookup(language, "updater-latest-version").unwrap());
+                ui.strong(i18n::LOCALES.lookup(language, "updater-latest-version"));
                 ui.label(format!(
                     "v{}",
                     match &status {
@@ -55,10 +53,10 @@ pub fn show(
                 updater::Status::ReadyToUpdate { .. } => {
                     ui.add(
                         egui::widgets::ProgressBar::new(1.0)
-                            .text(i18n::LOCALES.lookup(language, "updater-ready-to-update").unwrap()),
+                            .text(i18n::LOCALES.lookup(language, "updater-ready-to-update")),
                     );
                     if ui
-                        .button(i18n::LOCALES.lookup(language, "updater-update-now").unwrap())
+                        .button(i18n::LOCALES.lookup(language, "updater-update-now"))
                         .clicked()
                     {
                         updater.finish_update();
@@ -1,8 +1,10 @@
-const COLOR: egui::Color32 = egui::Color32::from_rgb(0xf4, 0xba, 0x51);
+use crate::gui;
+
 const TEXT: &str = "⚠️";
 
 pub fn show(ui: &mut egui::Ui, text: impl Into<egui::WidgetText>) -> egui::Response {
-    ui.colored_label(COLOR, TEXT).on_hover_text(text)
+    ui.colored_label(gui::theme::colors(ui.ctx()).warning, TEXT)
+        .on_hover_text(text)
 }
 
 pub fn append_to_layout_job(ui: &egui::Ui, layout_job: &mut egui::text::LayoutJob) {
@@ -11,7 +13,7 @@ pub fn append_to_layout_job(ui: &egui::Ui, layout_job: &mut egui::text::LayoutJo
         0.0,
         egui::TextFormat::simple(
             ui.style().text_styles.get(&egui::TextStyle::Body).unwrap().clone(),
-            COLOR,
+            gui::theme::colors(ui.ctx()).warning,
         ),
     );
 }
@@ -1,29 +1,278 @@
-use fluent_templates::Loader;
+use crate::{audio, config, contentpolicy, game, gui, i18n, inputlatency, patch, rom, save};
 
-use crate::{config, game, gui, i18n, rom, save};
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Directories,
+    Scan,
+    Profile,
+    Check,
+}
+
+const STEPS: &[Step] = &[Step::Directories, Step::Scan, Step::Profile, Step::Check];
+
+impl Step {
+    fn index(&self) -> usize {
+        STEPS.iter().position(|step| step == self).unwrap()
+    }
+
+    fn heading_id(&self) -> &'static str {
+        match self {
+            Step::Directories => "welcome-directories",
+            Step::Scan => "welcome-scan",
+            Step::Profile => "welcome-profile",
+            Step::Check => "welcome-check",
+        }
+    }
+}
 
 pub struct State {
+    step: Step,
     nickname: String,
+    /// Why the current step couldn't be continued from.
+    error: Option<String>,
     emblem: egui_extras::RetainedImage,
 }
 
 impl State {
-    pub fn new() -> Self {
+    pub fn new(config: &config::Config) -> Self {
         Self {
-            nickname: "".to_string(),
+            step: Step::Directories,
+            nickname: config.nickname.clone().unwrap_or_default(),
+            error: None,
             emblem: egui_extras::RetainedImage::from_image_bytes("emblem", include_bytes!("../emblem.png")).unwrap(),
         }
     }
 }
 
+/// Whether there's nothing to play with yet: either setup was never finished, or no ROMs can be found.
+pub fn is_needed(config: &config::Config, roms_scanner: &rom::Scanner) -> bool {
+    config.nickname.is_none() || (!roms_scanner.is_scanning() && roms_scanner.read().by_game.is_empty())
+}
+
+fn show_check(ui: &mut egui::Ui, ok: bool) {
+    if ok {
+        ui.label(egui::RichText::new("✅").color(gui::theme::colors(ui.ctx()).ready));
+    } else {
+        ui.label(egui::RichText::new("⌛").color(gui::theme::colors(ui.ctx()).warning));
+    }
+}
+
+fn show_directories_step(
+    ui: &mut egui::Ui,
+    config: &mut config::Config,
+    roms_scanner: rom::Scanner,
+    saves_scanner: save::Scanner,
+    patches_scanner: patch::Scanner,
+    state: &mut State,
+) {
+    ui.label(i18n::LOCALES.lookup(&config.language, "welcome-directories-description"));
+    ui.horizontal(|ui| {
+        ui.monospace(format!("{}", config.data_path.display()));
+        if ui
+            .button(i18n::LOCALES.lookup(&config.language, "settings-data-path.change"))
+            .clicked()
+        {
+            if let Some(data_path) = rfd::FileDialog::new().set_directory(&config.data_path).pick_folder() {
+                match config.set_data_path(data_path) {
+                    Ok(()) => {
+                        state.error = None;
+                        gui::rescan_all(ui.ctx(), config, roms_scanner, saves_scanner, patches_scanner);
+                    }
+                    Err(e) => {
+                        state.error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+    });
+
+    ui.add_space(8.0);
+    egui::Grid::new("welcome-directories-grid")
+        .num_columns(3)
+        .show(ui, |ui| {
+            for (id, path) in [
+                ("welcome-directories-roms", config.roms_path()),
+                ("welcome-directories-saves", config.saves_path()),
+                ("welcome-directories-patches", config.patches_path()),
+                ("welcome-directories-replays", config.replays_path()),
+            ] {
+                show_check(ui, path.is_dir());
+                ui.strong(i18n::LOCALES.lookup(&config.language, id));
+                ui.monospace(format!("{}", path.display()));
+                ui.end_row();
+            }
+        });
+}
+
+fn show_scan_step(
+    ui: &mut egui::Ui,
+    config: &config::Config,
+    roms_scanner: rom::Scanner,
+    saves_scanner: save::Scanner,
+    patches_scanner: patch::Scanner,
+) {
+    let roms = roms_scanner.read();
+    let saves = saves_scanner.read();
+    let patches = patches_scanner.read();
+
+    ui.horizontal(|ui| {
+        show_check(ui, !roms.by_game.is_empty());
+        ui.strong(i18n::LOCALES.lookup(&config.language, "welcome-scan-roms"));
+    });
+    ui.indent("welcome-scan-roms", |ui| {
+        if roms.by_game.is_empty() {
+            ui.label(i18n::LOCALES.lookup(&config.language, "welcome-scan-roms.missing"));
+            show_folder(ui, &config.language, &config.roms_path());
+        } else {
+            let mut games = roms.by_game.keys().cloned().collect::<Vec<_>>();
+            game::sort_games(&config.language, &mut games);
+            for game in games {
+                let (family, variant) = game.family_and_variant();
+                ui.label(
+                    i18n::LOCALES.lookup_with_args(
+                        &config.language,
+                        "welcome-scan-roms.game",
+                        &std::collections::HashMap::from([
+                            (
+                                "game_name",
+                                i18n::LOCALES
+                                    .lookup(&config.language, &format!("game-{}.variant-{}", family, variant))
+                                    .into(),
+                            ),
+                            (
+                                "saves",
+                                saves.by_game.get(&game).map(|saves| saves.len()).unwrap_or(0).into(),
+                            ),
+                        ]),
+                    ),
+                );
+            }
+        }
+    });
+
+    let has_saves = roms.by_game.keys().any(|game| saves.by_game.contains_key(game));
+    ui.add_space(8.0);
+    ui.horizontal(|ui| {
+        show_check(ui, has_saves);
+        ui.strong(i18n::LOCALES.lookup(&config.language, "welcome-scan-saves"));
+    });
+    ui.indent("welcome-scan-saves", |ui| {
+        if !has_saves {
+            ui.label(i18n::LOCALES.lookup(&config.language, "welcome-scan-saves.missing"));
+            show_folder(ui, &config.language, &config.saves_path());
+        }
+    });
+
+    ui.add_space(8.0);
+    ui.horizontal(|ui| {
+        show_check(ui, true);
+        ui.strong(i18n::LOCALES.lookup(&config.language, "welcome-scan-patches"));
+    });
+    ui.indent("welcome-scan-patches", |ui| {
+        ui.label(i18n::LOCALES.lookup_with_args(
+            &config.language,
+            "welcome-scan-patches.description",
+            &std::collections::HashMap::from([("count", patches.len().into())]),
+        ));
+        show_folder(ui, &config.language, &config.patches_path());
+    });
+}
+
+fn show_folder(ui: &mut egui::Ui, language: &unic_langid::LanguageIdentifier, path: &std::path::Path) {
+    ui.horizontal(|ui| {
+        ui.monospace(format!("{}", path.display()));
+        if ui
+            .button(i18n::LOCALES.lookup(language, "welcome-open-folder"))
+            .clicked()
+        {
+            let _ = open::that(path);
+        }
+    });
+}
+
+fn show_profile_step(
+    ui: &mut egui::Ui,
+    font_families: &gui::FontFamilies,
+    config: &mut config::Config,
+    state: &mut State,
+) {
+    egui::Grid::new("welcome-profile-grid").num_columns(2).show(ui, |ui| {
+        ui.strong(i18n::LOCALES.lookup(&config.language, "settings-nickname"));
+        ui.vertical(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.nickname).desired_width(200.0));
+            state.nickname = state
+                .nickname
+                .chars()
+                .take(contentpolicy::max_nickname_length())
+                .collect::<String>()
+                .trim()
+                .to_string();
+            ui.weak(i18n::LOCALES.lookup(&config.language, "welcome-profile-nickname.description"));
+        });
+        ui.end_row();
+
+        ui.strong(i18n::LOCALES.lookup(&config.language, "settings-language"));
+        gui::language_select::show(ui, "welcome-profile-language", font_families, &mut config.language);
+        ui.end_row();
+    });
+}
+
+fn show_check_step(
+    ui: &mut egui::Ui,
+    config: &mut config::Config,
+    audio_binder: &audio::LateBinder,
+    audio_device_names: &[String],
+    steal_input: &mut Option<gui::steal_input_window::State>,
+    input_latency_monitor: &parking_lot::Mutex<inputlatency::Monitor>,
+    input_latency_tester: &mut Option<inputlatency::Tester>,
+) {
+    ui.label(i18n::LOCALES.lookup(&config.language, "welcome-check-description"));
+    ui.add_space(8.0);
+
+    egui::ScrollArea::vertical()
+        .auto_shrink([false, true])
+        .max_height(ui.available_height() - 48.0)
+        .show(ui, |ui| {
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-tab-audio"));
+            gui::settings_window::show_audio_tab(ui, config, audio_device_names);
+            if ui
+                .button(i18n::LOCALES.lookup(&config.language, "welcome-check-play-sound"))
+                .clicked()
+            {
+                gui::notifications::play_sound(config, audio_binder);
+            }
+
+            ui.add_space(8.0);
+            ui.strong(i18n::LOCALES.lookup(&config.language, "settings-tab-input"));
+            gui::settings_window::show_input_tab(
+                ui,
+                &config.language,
+                &mut config.input_mapping,
+                &config.input_latency_reports,
+                steal_input,
+                input_latency_monitor,
+                input_latency_tester,
+            );
+        });
+}
+
+/// Shows the first-run setup. Returns whether it was finished.
 pub fn show(
     ctx: &egui::Context,
     font_families: &gui::FontFamilies,
     config: &mut config::Config,
     roms_scanner: rom::Scanner,
     saves_scanner: save::Scanner,
+    patches_scanner: patch::Scanner,
+    audio_binder: &audio::LateBinder,
+    audio_device_names: &[String],
+    steal_input: &mut Option<gui::steal_input_window::State>,
+    input_latency_monitor: &parking_lot::Mutex<inputlatency::Monitor>,
+    input_latency_tester: &mut Option<inputlatency::Tester>,
     state: &mut State,
-) {
+) -> bool {
+    let mut finished = false;
+
     egui::CentralPanel::default().show(ctx, |ui| {
         ui.horizontal_centered(|ui| {
             ui.add_space(8.0);
@@ -33,143 +282,173 @@ pub fn show(
             ui.add(egui::Separator::default().vertical());
             ui.add_space(8.0);
 
-            let has_roms = !roms_scanner.read().is_empty();
-            let has_saves = !saves_scanner.read().is_empty();
-
             ui.vertical(|ui| {
                 ui.horizontal(|ui| {
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
-                        gui::language_select::show(ui, font_families, &mut config.language);
+                        gui::language_select::show(ui, "welcome-language", font_families, &mut config.language);
                     });
                 });
 
+                ui.heading(i18n::LOCALES.lookup(&config.language, "welcome-heading"));
+                ui.weak(i18n::LOCALES.lookup_with_args(
+                    &config.language,
+                    "welcome-step",
+                    &std::collections::HashMap::from([
+                        ("step", (state.step.index() + 1).into()),
+                        ("steps", STEPS.len().into()),
+                    ]),
+                ));
+
                 ui.add_space(16.0);
-                ui.vertical(|ui| {
-                    ui.heading(i18n::LOCALES.lookup(&config.language, "welcome-heading").unwrap());
-                    ui.label(i18n::LOCALES.lookup(&config.language, "welcome-description").unwrap());
-
-                    ui.add_space(16.0);
-                    ui.horizontal(|ui| {
-                        if has_roms {
-                            ui.label(egui::RichText::new("✅").color(egui::Color32::from_rgb(0x4c, 0xaf, 0x50)));
+                ui.strong(i18n::LOCALES.lookup(&config.language, state.step.heading_id()));
+                ui.add_space(8.0);
+
+                let is_scanning =
+                    roms_scanner.is_scanning() || saves_scanner.is_scanning() || patches_scanner.is_scanning();
+
+                match state.step {
+                    Step::Directories => show_directories_step(
+                        ui,
+                        config,
+                        roms_scanner.clone(),
+                        saves_scanner.clone(),
+                        patches_scanner.clone(),
+                        state,
+                    ),
+                    Step::Scan => {
+                        if is_scanning {
+                            ui.spinner();
                         } else {
-                            ui.label(egui::RichText::new("⌛").color(egui::Color32::from_rgb(0xf4, 0xba, 0x51)));
+                            show_scan_step(
+                                ui,
+                                config,
+                                roms_scanner.clone(),
+                                saves_scanner.clone(),
+                                patches_scanner.clone(),
+                            );
                         }
-                        ui.strong(i18n::LOCALES.lookup(&config.language, "welcome-step-1").unwrap());
-                    });
-                    if !has_roms {
-                        ui.label(
-                            i18n::LOCALES
-                                .lookup(&config.language, "welcome-step-1-description")
-                                .unwrap(),
-                        );
-                        ui.monospace(format!("{}", config.roms_path().display()));
-                        ui.horizontal(|ui| {
-                            if ui
-                                .button(i18n::LOCALES.lookup(&config.language, "welcome-open-folder").unwrap())
-                                .clicked()
-                            {
-                                let _ = open::that(&config.roms_path());
-                            }
-                            ui.add_enabled_ui(!roms_scanner.is_scanning(), |ui| {
-                                if ui
-                                    .button(i18n::LOCALES.lookup(&config.language, "welcome-continue").unwrap())
-                                    .clicked()
-                                {
-                                    let roms_path = config.roms_path();
-                                    let roms_scanner = roms_scanner.clone();
-                                    let egui_ctx = ui.ctx().clone();
-                                    tokio::task::spawn_blocking(move || {
-                                        roms_scanner.rescan(|| Some(game::scan_roms(&roms_path)));
-                                        egui_ctx.request_repaint();
-                                    });
-                                }
-                            });
-                        });
                     }
-                });
+                    Step::Profile => show_profile_step(ui, font_families, config, state),
+                    Step::Check => show_check_step(
+                        ui,
+                        config,
+                        audio_binder,
+                        audio_device_names,
+                        steal_input,
+                        input_latency_monitor,
+                        input_latency_tester,
+                    ),
+                }
+
+                if let Some(error) = state.error.as_ref() {
+                    ui.add_space(8.0);
+                    ui.colored_label(egui::Color32::RED, error);
+                }
 
                 ui.add_space(16.0);
-                ui.vertical(|ui| {
-                    ui.horizontal(|ui| {
-                        if has_saves {
-                            ui.label(egui::RichText::new("✅").color(egui::Color32::from_rgb(0x4c, 0xaf, 0x50)));
-                        } else {
-                            ui.label(egui::RichText::new("⌛").color(egui::Color32::from_rgb(0xf4, 0xba, 0x51)));
-                        }
-                        ui.strong(i18n::LOCALES.lookup(&config.language, "welcome-step-2").unwrap());
-                    });
-                    if has_roms && !has_saves {
-                        ui.label(
-                            i18n::LOCALES
-                                .lookup(&config.language, "welcome-step-2-description")
-                                .unwrap(),
+                ui.horizontal(|ui| {
+                    if state.step != Step::Directories
+                        && ui
+                            .button(i18n::LOCALES.lookup(&config.language, "welcome-back"))
+                            .clicked()
+                    {
+                        state.step = STEPS[state.step.index() - 1];
+                        state.error = None;
+                    }
+
+                    if state.step == Step::Scan
+                        && ui
+                            .add_enabled(
+                                !is_scanning,
+                                egui::Button::new(i18n::LOCALES.lookup(&config.language, "welcome-rescan")),
+                            )
+                            .clicked()
+                    {
+                        gui::rescan_all(
+                            ui.ctx(),
+                            config,
+                            roms_scanner.clone(),
+                            saves_scanner.clone(),
+                            patches_scanner.clone(),
                         );
-                        ui.monospace(format!("{}", config.saves_path().display()));
-                        ui.horizontal(|ui| {
-                            if ui
-                                .button(i18n::LOCALES.lookup(&config.language, "welcome-open-folder").unwrap())
-                                .clicked()
-                            {
-                                let _ = open::that(&config.saves_path());
-                            }
+                    }
 
-                            ui.add_enabled_ui(!saves_scanner.is_scanning(), |ui| {
-                                if ui
-                                    .button(i18n::LOCALES.lookup(&config.language, "welcome-continue").unwrap())
-                                    .clicked()
-                                {
-                                    let saves_path = config.saves_path();
-                                    let saves_scanner = saves_scanner.clone();
-                                    let egui_ctx = ui.ctx().clone();
-                                    tokio::task::spawn_blocking(move || {
-                                        saves_scanner.rescan(|| Some(save::scan_saves(&saves_path)));
-                                        egui_ctx.request_repaint();
-                                    });
-                                }
-                            });
-                        });
+                    let is_last = state.step == Step::Check;
+                    if !ui
+                        .add_enabled(
+                            !is_scanning,
+                            egui::Button::new(i18n::LOCALES.lookup(
+                                &config.language,
+                                if is_last { "welcome-finish" } else { "welcome-continue" },
+                            )),
+                        )
+                        .clicked()
+                    {
+                        return;
                     }
-                });
 
-                ui.add_space(16.0);
-                ui.vertical(|ui| {
-                    ui.horizontal(|ui| {
-                        ui.label(egui::RichText::new("⌛").color(egui::Color32::from_rgb(0xf4, 0xba, 0x51)));
-                        ui.strong(i18n::LOCALES.lookup(&config.language, "welcome-step-3").unwrap());
-                    });
-                    if has_roms && has_saves {
-                        ui.label(
-                            i18n::LOCALES
-                                .lookup(&config.language, "welcome-step-3-description")
-                                .unwrap(),
-                        );
-                        ui.horizontal(|ui| {
-                            let mut submitted = false;
-                            let input_resp = ui.add(
-                                egui::TextEdit::singleline(&mut state.nickname)
-                                    .hint_text(i18n::LOCALES.lookup(&config.language, "settings-nickname").unwrap())
-                                    .desired_width(200.0),
-                            );
-                            if input_resp.lost_focus() && ui.ctx().input().key_pressed(egui::Key::Enter) {
-                                submitted = true;
+                    // Each step has to be valid before moving on from it.
+                    let error = match state.step {
+                        Step::Directories => config.ensure_dirs().err().map(|e| e.to_string()),
+                        Step::Scan => {
+                            let roms = roms_scanner.read();
+                            let saves = saves_scanner.read();
+                            if roms.by_game.is_empty() {
+                                Some(i18n::LOCALES.lookup(&config.language, "welcome-scan-roms.missing"))
+                            } else if !roms.by_game.keys().any(|game| saves.by_game.contains_key(game)) {
+                                Some(i18n::LOCALES.lookup(&config.language, "welcome-scan-saves.missing"))
+                            } else {
+                                None
                             }
-                            state.nickname = state.nickname.chars().take(20).collect::<String>().trim().to_string();
-
-                            if ui
-                                .button(i18n::LOCALES.lookup(&config.language, "welcome-continue").unwrap())
-                                .clicked()
-                            {
-                                submitted = true;
+                        }
+                        Step::Profile => {
+                            if state.nickname.is_empty() {
+                                Some(i18n::LOCALES.lookup(&config.language, "welcome-profile-nickname.missing"))
+                            } else if let Some(word) = contentpolicy::banned_word_in(&state.nickname) {
+                                Some(i18n::LOCALES.lookup_with_args(
+                                    &config.language,
+                                    "welcome-profile-nickname.banned-word",
+                                    &std::collections::HashMap::from([("word", word.into())]),
+                                ))
+                            } else {
+                                None
                             }
+                        }
+                        Step::Check => None,
+                    };
+                    if error.is_some() {
+                        state.error = error;
+                        return;
+                    }
+                    state.error = None;
 
-                            if submitted && !state.nickname.is_empty() {
-                                config.nickname = Some(state.nickname.clone());
-                            }
-                        });
+                    if is_last {
+                        config.nickname = Some(state.nickname.clone());
+                        gui::rescan_all(
+                            ui.ctx(),
+                            config,
+                            roms_scanner.clone(),
+                            saves_scanner.clone(),
+                            patches_scanner.clone(),
+                        );
+                        finished = true;
+                        return;
+                    }
+
+                    state.step = STEPS[state.step.index() + 1];
+                    if state.step == Step::Scan {
+                        gui::rescan_all(
+                            ui.ctx(),
+                            config,
+                            roms_scanner.clone(),
+                            saves_scanner.clone(),
+                            patches_scanner.clone(),
+                        );
                     }
                 });
             });
         });
     });
+
+    finished
 }
@@ -0,0 +1,157 @@
+//! The window title, which says what this instance of Tango is up to so that several of them can be told apart in the taskbar.
+//!
+//! The title is worked out from scratch every frame from the session, the lobby and any running jobs, and only set on the window when it changes.
+
+use crate::{contentpolicy, game, i18n, session};
+
+/// What Tango is doing, as far as the window title is concerned.
+pub enum Activity {
+    Idle,
+    /// Connecting to or waiting in a lobby. The link code is left out in streamer mode.
+    Lobby {
+        link_code: Option<String>,
+    },
+    SinglePlayer {
+        game: &'static (dyn game::Game + Send + Sync),
+    },
+    Match {
+        local_nickname: String,
+        remote_nickname: String,
+        game: &'static (dyn game::Game + Send + Sync),
+    },
+    Replay {
+        name: String,
+    },
+}
+
+impl Activity {
+    pub fn for_session(session: &session::Session) -> Self {
+        let game = session.game_info().game;
+        match session.mode() {
+            session::Mode::SinglePlayer(_) => Activity::SinglePlayer { game },
+            session::Mode::PvP(pvp) => {
+                let (local_nickname, remote_nickname) = pvp.nicknames();
+                Activity::Match {
+                    local_nickname: local_nickname.to_string(),
+                    remote_nickname: contentpolicy::display_nickname(remote_nickname),
+                    game,
+                }
+            }
+            session::Mode::Replayer(replayer) => Activity::Replay {
+                name: replayer
+                    .path()
+                    .file_stem()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            },
+        }
+    }
+}
+
+pub enum Progress {
+    /// Something is running, but how far along it is isn't known.
+    Indeterminate,
+    /// How far along a job is, from 0 to 1.
+    Fraction(f32),
+}
+
+pub struct Title {
+    pub activity: Activity,
+    pub paused: bool,
+    pub progress: Option<Progress>,
+}
+
+fn game_name(language: &unic_langid::LanguageIdentifier, game: &'static (dyn game::Game + Send + Sync)) -> String {
+    let (family, variant) = game.family_and_variant();
+    i18n::LOCALES.lookup(language, &format!("game-{}.variant-{}", family, variant))
+}
+
+impl Title {
+    fn render(&self, language: &unic_langid::LanguageIdentifier) -> String {
+        let mut title = match &self.activity {
+            Activity::Idle => i18n::LOCALES.lookup(language, "window-title"),
+            Activity::Lobby {
+                link_code: Some(link_code),
+            } => i18n::LOCALES.lookup_with_args(
+                language,
+                "window-title.lobby",
+                &std::collections::HashMap::from([("link_code", link_code.as_str().into())]),
+            ),
+            Activity::Lobby { link_code: None } => i18n::LOCALES.lookup(language, "window-title.lobby-hidden"),
+            Activity::SinglePlayer { game } => i18n::LOCALES.lookup_with_args(
+                language,
+                "window-title.single-player",
+                &std::collections::HashMap::from([("game", game_name(language, *game).into())]),
+            ),
+            Activity::Match {
+                local_nickname,
+                remote_nickname,
+                game,
+            } => i18n::LOCALES.lookup_with_args(
+                language,
+                "window-title.match",
+                &std::collections::HashMap::from([
+                    ("local", local_nickname.as_str().into()),
+                    ("remote", remote_nickname.as_str().into()),
+                    ("game", game_name(language, *game).into()),
+                ]),
+            ),
+            Activity::Replay { name } => i18n::LOCALES.lookup_with_args(
+                language,
+                "window-title.replay",
+                &std::collections::HashMap::from([("name", name.as_str().into())]),
+            ),
+        };
+
+        if self.paused {
+            title = i18n::LOCALES.lookup_with_args(
+                language,
+                "window-title.paused",
+                &std::collections::HashMap::from([("title", title.into())]),
+            );
+        }
+
+        // winit has no way to show progress on the taskbar entry itself, so it goes in the title instead.
+        match self.progress {
+            Some(Progress::Fraction(fraction)) => i18n::LOCALES.lookup_with_args(
+                language,
+                "window-title.progress",
+                &std::collections::HashMap::from([
+                    ("percent", ((fraction * 100.0).floor() as u32).into()),
+                    ("title", title.into()),
+                ]),
+            ),
+            Some(Progress::Indeterminate) => i18n::LOCALES.lookup_with_args(
+                language,
+                "window-title.progress-indeterminate",
+                &std::collections::HashMap::from([("title", title.into())]),
+            ),
+            None => title,
+        }
+    }
+}
+
+pub struct State {
+    current: Option<String>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self { current: None }
+    }
+}
+
+/// Sets the window title, if it's changed since it was last set.
+pub fn apply(
+    window: &winit::window::Window,
+    language: &unic_langid::LanguageIdentifier,
+    state: &mut State,
+    title: Title,
+) {
+    let title = title.render(language);
+    if state.current.as_ref() == Some(&title) {
+        return;
+    }
+    window.set_title(&title);
+    state.current = Some(title);
+}
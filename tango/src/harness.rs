@@ -0,0 +1,455 @@
+//! Plays both sides of a match in one process, on real cores and with scripted input instead of players, so game hooks can be tested from start to end of a round. The sides can also be linked over a simulated network, to check that a round goes the same way however its packets get across.
+//!
+//! The ROMs and saves to play with are read from the directory in `TANGO_TEST_ROMS`, named after each game's family and variant, e.g. `bn6-0.gba` and `bn6-0.sav`. Games that aren't there are skipped. How each game's round is expected to end is kept next to them, e.g. in `bn6-0.hash`, as it depends on the save played with: set `TANGO_TEST_BLESS` to write it from a run.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::{audio, broadcast, config, determinism, fairness, game, net, patch, replay, session, stats};
+
+/// The input a side plays on each tick of a round.
+pub type Script = fn(u32) -> u16;
+
+/// How far ahead in the script the remote side plays, so the sides don't mirror each other move for move.
+const REMOTE_SCRIPT_OFFSET: u32 = 17;
+
+/// How long a round may take before giving up on it. Rounds are played in real time.
+const ROUND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// How long the cores of a match may take to go away once its sessions are dropped.
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// A game to play a round of.
+pub struct Case {
+    pub family_and_variant: (&'static str, u8),
+    pub script: Script,
+    /// Ends the round on HP once it's been going this long, for games that support it, so the script doesn't have to win it.
+    pub round_time_limit_secs: Option<u32>,
+}
+
+/// How a round ended, which both sides have to agree on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    /// The tick the round was decided on.
+    pub end_tick: u32,
+    /// The hash of the last state both sides committed to before then.
+    pub final_hash: u32,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:08x}", self.end_tick, self.final_hash)
+    }
+}
+
+/// A round both sides played. Their replays and fairness reports are deleted once this is dropped.
+pub struct Played {
+    pub outcome: Outcome,
+    /// Where each side's replays and fairness report were written.
+    pub replays_dirs: Vec<tempfile::TempDir>,
+}
+
+/// Passes packets on to the other side, keeping the state hashes sent along the way.
+struct Tap {
+    inner: Box<dyn net::RawSender>,
+    state_hashes: Arc<Mutex<std::collections::BTreeMap<u32, u32>>>,
+}
+
+impl net::RawSender for Tap {
+    fn send<'a>(&'a mut self, data: &'a [u8]) -> futures::future::BoxFuture<'a, std::io::Result<()>> {
+        if let Ok(net::protocol::Packet::StateHash(state_hash)) = net::protocol::Packet::deserialize(data) {
+            self.state_hashes.lock().insert(state_hash.tick, state_hash.hash);
+        }
+        self.inner.send(data)
+    }
+}
+
+/// Reads the ROM and save to play a game with, or None if they aren't there to be read.
+pub fn load(case: &Case) -> Option<(std::path::PathBuf, Vec<u8>, Vec<u8>)> {
+    let dir = if let Some(dir) = std::env::var_os("TANGO_TEST_ROMS") {
+        std::path::PathBuf::from(dir)
+    } else {
+        eprintln!("skipping: TANGO_TEST_ROMS must be set");
+        return None;
+    };
+    let (family, variant) = case.family_and_variant;
+    let (rom_path, save_path) = (
+        dir.join(format!("{}-{}.gba", family, variant)),
+        dir.join(format!("{}-{}.sav", family, variant)),
+    );
+    match (std::fs::read(&rom_path), std::fs::read(&save_path)) {
+        (Ok(rom), Ok(save)) => Some((dir, rom, save)),
+        _ => {
+            eprintln!(
+                "skipping: {} and {} must both exist",
+                rom_path.display(),
+                save_path.display()
+            );
+            None
+        }
+    }
+}
+
+/// Checks an outcome against the one kept for the game, or keeps it if `TANGO_TEST_BLESS` is set.
+pub fn check_outcome(case: &Case, dir: &std::path::Path, outcome: Outcome) {
+    let (family, variant) = case.family_and_variant;
+    let path = dir.join(format!("{}-{}.hash", family, variant));
+    if std::env::var_os("TANGO_TEST_BLESS").is_some() {
+        std::fs::write(&path, format!("{}\n", outcome)).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "no outcome kept at {}: this run's was {}, set TANGO_TEST_BLESS to keep it",
+            path.display(),
+            outcome
+        )
+    });
+    assert_eq!(expected.trim(), outcome.to_string());
+}
+
+fn settings(case: &Case, rom: &[u8], nickname: &str) -> net::protocol::Settings {
+    let (family, variant) = case.family_and_variant;
+    net::protocol::Settings {
+        nickname: nickname.to_string(),
+        match_type: (0, 0),
+        game_info: Some(net::protocol::GameInfo {
+            family_and_variant: (family.to_string(), variant),
+            patch: None,
+            rom_hash: Some(determinism::hash_rom(rom)),
+        }),
+        round_time_limit_secs: case.round_time_limit_secs,
+        ..Default::default()
+    }
+}
+
+fn pvp(session: &session::Session) -> &session::PvP {
+    match session.mode() {
+        session::Mode::PvP(pvp) => pvp,
+        _ => unreachable!(),
+    }
+}
+
+/// Plays the first round of a match between two sides on the same ROM and save, with packets between them carried over links from `make_link`.
+///
+/// The round has to be played out without errors or desyncs, and both sides have to agree on how it ended. Holds the cores lock throughout, and only returns once the match's cores are gone, by which time its fairness reports have been written.
+pub async fn play(
+    case: &Case,
+    rom: &[u8],
+    save: &[u8],
+    mut make_link: impl FnMut() -> (net::loopback::LoopbackSender, net::loopback::LoopbackReceiver),
+) -> anyhow::Result<Played> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let (family, variant) = case.family_and_variant;
+    let game = game::find_by_family_and_variant(family, variant)
+        .ok_or_else(|| anyhow::anyhow!("no such game: {} {}", family, variant))?;
+    if game::detect(rom)? != game {
+        anyhow::bail!("ROM is not of {:?}", game);
+    }
+    let rom: Arc<[u8]> = rom.into();
+
+    // Fixed nonces make for the same seed, and so the same rounds, every run.
+    let states = [[0x11; 16], [0x22; 16]].map(|nonce| net::protocol::NegotiatedState {
+        nonce,
+        save_data: save.to_vec(),
+    });
+    let mut sides = vec![];
+    for state in states.iter() {
+        let negotiated_state = state.encode()?;
+        sides.push(fairness::Side {
+            nonce: state.nonce,
+            commitment: fairness::commitment(&negotiated_state),
+            negotiated_state,
+        });
+    }
+    let seed = fairness::seed(&states[0].nonce, &states[1].nonce);
+
+    let config = Arc::new(parking_lot::RwLock::new(config::Config {
+        replaycollector_endpoint: "".to_string(),
+        ..Default::default()
+    }));
+    let nicknames = ["host", "guest"];
+    let (host_sender, guest_receiver) = make_link();
+    let (guest_sender, host_receiver) = make_link();
+    let links = [(host_sender, host_receiver), (guest_sender, guest_receiver)];
+
+    let _cores = session::tests::CORES_LOCK.lock();
+    let baseline = mgba::core::live_count();
+
+    let mut sessions = vec![];
+    let mut state_hashes = vec![];
+    let mut replays_dirs = vec![];
+    for (i, (sender, receiver)) in links.into_iter().enumerate() {
+        let replays_dir = tempfile::tempdir()?;
+        let side_state_hashes = Arc::new(Mutex::new(std::collections::BTreeMap::new()));
+        let session = session::Session::new_pvp(
+            config.clone(),
+            audio::LateBinder::new(48000),
+            "harness".to_string(),
+            family.to_string(),
+            settings(case, &rom, nicknames[i]),
+            game,
+            None,
+            rom.clone(),
+            save,
+            None,
+            settings(case, &rom, nicknames[1 - i]),
+            None,
+            None,
+            game,
+            &patch::ROMOverrides::default(),
+            &rom,
+            save,
+            Arc::new(Mutex::new(stats::Counter::new(stats::window::EMU_TPS))),
+            broadcast::Server::new(config.clone()),
+            net::Sender::new(Tap {
+                inner: Box::new(sender),
+                state_hashes: side_state_hashes.clone(),
+            }),
+            net::Receiver::new(receiver),
+            net::Transport::Lan { is_host: i == 0 },
+            replays_dir.path().to_path_buf(),
+            (0, 0),
+            None,
+            fairness::Reveal {
+                seed,
+                local: sides[i].clone(),
+                remote: sides[1 - i].clone(),
+            },
+            None,
+            None,
+        )?;
+
+        // The game takes a while to get from booting to the first round, which is plenty of time to get the script in.
+        let script = case.script;
+        let offset = if i == 0 { 0 } else { REMOTE_SCRIPT_OFFSET };
+        let match_ = pvp(&session).match_.lock().await.clone();
+        match_
+            .ok_or_else(|| anyhow::anyhow!("match ended before it started"))?
+            .set_input_script(Arc::new(move |tick: u32| script(tick + offset)));
+
+        sessions.push(session);
+        state_hashes.push(side_state_hashes);
+        replays_dirs.push(replays_dir);
+    }
+
+    let result = play_round(&sessions, &state_hashes).await;
+
+    drop(sessions);
+    let deadline = tokio::time::Instant::now() + SHUTDOWN_TIMEOUT;
+    while mgba::core::live_count() > baseline {
+        if tokio::time::Instant::now() > deadline {
+            anyhow::bail!("match left cores behind");
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    Ok(Played {
+        outcome: result?,
+        replays_dirs,
+    })
+}
+
+/// Checks each side's fairness report against its replay of the round, as `tango fairness-verify` would.
+pub fn verify_fairness(case: &Case, rom: &[u8], played: &Played) -> anyhow::Result<()> {
+    let (family, variant) = case.family_and_variant;
+    let game = game::find_by_family_and_variant(family, variant)
+        .ok_or_else(|| anyhow::anyhow!("no such game: {} {}", family, variant))?;
+    for replays_dir in played.replays_dirs.iter() {
+        let report_path = std::fs::read_dir(replays_dir.path())?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .find(|path| path.to_string_lossy().ends_with(&format!(".{}", fairness::EXTENSION)))
+            .ok_or_else(|| anyhow::anyhow!("no fairness report in {}", replays_dir.path().display()))?;
+        let rounds_checked = fairness::verify(&report_path, rom, game.hooks(), None)?;
+        if rounds_checked != 1 {
+            anyhow::bail!("{} rounds checked in {}", rounds_checked, report_path.display());
+        }
+    }
+    Ok(())
+}
+
+async fn play_round(
+    sessions: &[session::Session],
+    state_hashes: &[Arc<Mutex<std::collections::BTreeMap<u32, u32>>>],
+) -> anyhow::Result<Outcome> {
+    let deadline = tokio::time::Instant::now() + ROUND_TIMEOUT;
+    let rounds = loop {
+        let mut rounds = vec![];
+        for session in sessions {
+            let pvp = pvp(session);
+            if let Some(desync) = pvp.desync() {
+                return Err(desync.into());
+            }
+            if let Some(round) = pvp.record().rounds.first() {
+                rounds.push(round.clone());
+            } else if pvp.match_.lock().await.is_none() {
+                // Whatever went wrong was logged when the match was cancelled over it.
+                anyhow::bail!("match ended before its first round did");
+            }
+        }
+        if rounds.len() == sessions.len() {
+            break rounds;
+        }
+        if tokio::time::Instant::now() > deadline {
+            anyhow::bail!("round didn't end within {:?}", ROUND_TIMEOUT);
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    };
+
+    // Replays stop at the tick the round was decided on, while a side may have committed past it by the time it noticed.
+    let mut end_ticks = vec![];
+    for round in rounds.iter() {
+        let replay_path = round
+            .replay_path
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("round wasn't recorded"))?;
+        let replay = replay::Replay::decode(std::fs::File::open(&replay_path)?)?;
+        end_ticks.push(replay.input_pairs.len() as u32);
+    }
+    if end_ticks[0] != end_ticks[1] {
+        anyhow::bail!("sides ended the round on different ticks: {:?}", end_ticks);
+    }
+    let end_tick = end_ticks[0];
+
+    let state_hashes = state_hashes
+        .iter()
+        .map(|state_hashes| {
+            state_hashes
+                .lock()
+                .range(..end_tick)
+                .map(|(tick, hash)| (*tick, *hash))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    if state_hashes[0] != state_hashes[1] {
+        anyhow::bail!("sides committed to different states: {:x?}", state_hashes);
+    }
+    let (_, final_hash) = state_hashes[0]
+        .last()
+        .copied()
+        .ok_or_else(|| anyhow::anyhow!("no state hashes were sent"))?;
+
+    Ok(Outcome { end_tick, final_hash })
+}
+
+/// Holds a key for a few ticks and then lets go of it for as long, so it reads as a press every time.
+fn tap(tick: u32, key: u32) -> u16 {
+    if tick % 8 < 4 {
+        key as u16
+    } else {
+        0
+    }
+}
+
+/// Picks whatever chips the custom screen puts under the cursor, skips to OK, and then fights with them and the buster until the custom screen comes up again.
+fn pick_chips_and_fight(tick: u32) -> u16 {
+    match tick % 240 {
+        0..=39 => tap(tick, mgba::input::keys::A),
+        40..=47 => tap(tick, mgba::input::keys::START),
+        48..=63 => tap(tick, mgba::input::keys::A),
+        64..=199 if tick % 32 < 8 => tap(tick, mgba::input::keys::A),
+        64..=199 => tap(tick, mgba::input::keys::B),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BN6: Case = Case {
+        family_and_variant: ("bn6", 0),
+        script: pick_chips_and_fight,
+        round_time_limit_secs: Some(30),
+    };
+
+    /// BN3 has no round time limit, so its round goes on until someone wins.
+    const BN3: Case = Case {
+        family_and_variant: ("bn3", 0),
+        script: pick_chips_and_fight,
+        round_time_limit_secs: None,
+    };
+
+    /// What simulated links are seeded from, so a failure over one can be played again the same way.
+    const LINK_SEED: u64 = 618661;
+
+    async fn run(case: &Case) {
+        let (dir, rom, save) = if let Some(loaded) = load(case) {
+            loaded
+        } else {
+            return;
+        };
+        let played = play(case, &rom, &save, net::loopback::pipe).await.unwrap();
+        check_outcome(case, &dir, played.outcome);
+    }
+
+    async fn run_over(case: &Case, conditions: net::loopback::Conditions) {
+        let (dir, rom, save) = if let Some(loaded) = load(case) {
+            loaded
+        } else {
+            return;
+        };
+        let mut seed = LINK_SEED;
+        let played = play(case, &rom, &save, || {
+            seed += 1;
+            net::loopback::lossy_pipe(conditions, seed)
+        })
+        .await
+        .unwrap();
+        verify_fairness(case, &rom, &played).unwrap();
+        // However late the packets got there, the round has to go the same way.
+        check_outcome(case, &dir, played.outcome);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bn6() {
+        run(&BN6).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bn3() {
+        run(&BN3).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bn6_over_clean_network() {
+        run_over(
+            &BN6,
+            net::loopback::Conditions {
+                delay: std::time::Duration::ZERO,
+                jitter: std::time::Duration::ZERO,
+                loss: 0.0,
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bn6_over_100ms_with_1_percent_loss() {
+        run_over(
+            &BN6,
+            net::loopback::Conditions {
+                delay: std::time::Duration::from_millis(100),
+                jitter: std::time::Duration::from_millis(20),
+                loss: 0.01,
+            },
+        )
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bn6_over_250ms_with_5_percent_loss() {
+        run_over(
+            &BN6,
+            net::loopback::Conditions {
+                delay: std::time::Duration::from_millis(250),
+                jitter: std::time::Duration::from_millis(50),
+                loss: 0.05,
+            },
+        )
+        .await;
+    }
+}
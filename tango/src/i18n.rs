@@ -1,7 +1,131 @@
+use fluent_templates::Loader;
+
 pub const FALLBACK_LANG: &str = "en-US";
 fluent_templates::static_loader! {
-    pub static LOCALES = {
+    static LOADER = {
         locales: "./locales",
         fallback_language: "en-US",
     };
 }
+
+lazy_static! {
+    static ref REPORTED_MISSING: parking_lot::Mutex<std::collections::HashSet<String>> =
+        parking_lot::Mutex::new(std::collections::HashSet::new());
+}
+
+/// The translations.
+///
+/// Anything missing from a language falls back to the fallback language, and anything missing from that too comes out as the key in brackets, so a missing translation is never more than a cosmetic problem.
+pub struct Locales;
+
+pub static LOCALES: Locales = Locales;
+
+impl Locales {
+    pub fn lookup(&self, lang: &unic_langid::LanguageIdentifier, text_id: &str) -> String {
+        self.lookup_with_args(
+            lang,
+            text_id,
+            &std::collections::HashMap::<&str, fluent_templates::fluent_bundle::FluentValue>::new(),
+        )
+    }
+
+    pub fn lookup_with_args<T: AsRef<str>>(
+        &self,
+        lang: &unic_langid::LanguageIdentifier,
+        text_id: &str,
+        args: &std::collections::HashMap<T, fluent_templates::fluent_bundle::FluentValue>,
+    ) -> String {
+        LOADER
+            .lookup_with_args(lang, text_id, args)
+            .unwrap_or_else(|| missing(text_id))
+    }
+
+    /// Looks up a key that might legitimately not exist, e.g. one built from the name of a key on a controller.
+    pub fn try_lookup(&self, lang: &unic_langid::LanguageIdentifier, text_id: &str) -> Option<String> {
+        LOADER.lookup(lang, text_id)
+    }
+
+    pub fn locales(&self) -> impl Iterator<Item = &unic_langid::LanguageIdentifier> {
+        LOADER.locales()
+    }
+}
+
+/// What's shown in place of a key that isn't in any language, which is logged the first time it's looked up.
+fn missing(text_id: &str) -> String {
+    if REPORTED_MISSING.lock().insert(text_id.to_string()) {
+        log::error!("missing translation for {}, even in {}", text_id, FALLBACK_LANG);
+    }
+    format!("[{}]", text_id)
+}
+
+/// Picks the language we have translations for that's closest to the given one, e.g. ja-JP for ja or zh-TW for zh-HK.
+///
+/// Languages we have nothing for at all get the fallback language.
+pub fn closest_supported(lang: &unic_langid::LanguageIdentifier) -> unic_langid::LanguageIdentifier {
+    let maximize = |lang: &unic_langid::LanguageIdentifier| {
+        let mut lang = lang.clone();
+        lang.maximize();
+        lang
+    };
+
+    let wanted = maximize(lang);
+    let supported = LOCALES.locales().map(|l| (l, maximize(l))).collect::<Vec<_>>();
+
+    supported
+        .iter()
+        .find(|(_, l)| *l == wanted)
+        .or_else(|| {
+            supported
+                .iter()
+                .find(|(_, l)| l.language == wanted.language && l.script == wanted.script)
+        })
+        .or_else(|| supported.iter().find(|(_, l)| l.language == wanted.language))
+        .map(|(l, _)| (*l).clone())
+        .unwrap_or_else(|| FALLBACK_LANG.parse().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_key_in_every_locale() {
+        let locales = LOCALES.locales().collect::<Vec<_>>();
+        assert!(locales.len() > 1);
+        for lang in locales {
+            assert_eq!(LOCALES.lookup(lang, "no-such-key"), "[no-such-key]", "{}", lang);
+            assert_eq!(
+                LOCALES.lookup_with_args(
+                    lang,
+                    "no-such-key.attribute",
+                    &std::collections::HashMap::from([("arg", "value".into())]),
+                ),
+                "[no-such-key.attribute]",
+                "{}",
+                lang
+            );
+            assert_eq!(LOCALES.try_lookup(lang, "no-such-key"), None, "{}", lang);
+        }
+    }
+
+    #[test]
+    fn test_every_locale_falls_back() {
+        for lang in LOCALES.locales() {
+            for key in ["save-changes", "save-changes.overwrite"] {
+                assert!(LOCALES.try_lookup(lang, key).is_some(), "{} in {}", key, lang);
+            }
+        }
+    }
+
+    #[test]
+    fn test_closest_supported() {
+        let closest = |lang: &str| closest_supported(&lang.parse().unwrap()).to_string();
+        assert_eq!(closest("en-US"), "en-US");
+        assert_eq!(closest("en-GB"), "en-US");
+        assert_eq!(closest("ja"), "ja-JP");
+        assert_eq!(closest("zh-HK"), "zh-TW");
+        assert_eq!(closest("zh"), "zh-CN");
+        assert_eq!(closest("es-MX"), "es-419");
+        assert_eq!(closest("tlh"), FALLBACK_LANG);
+    }
+}
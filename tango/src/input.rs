@@ -97,6 +97,18 @@ impl PhysicalInput {
     }
 }
 
+/// Whether any of the physical inputs bound to a button is held down.
+pub fn is_any_held(bindings: &[PhysicalInput], input: &State) -> bool {
+    bindings.iter().any(|c| c.is_active(input))
+}
+
+/// Whether a button bound to several physical inputs was just pressed.
+///
+/// The bound inputs act as a single button: pressing another one while one is already held down isn't a new press.
+pub fn is_any_pressed(bindings: &[PhysicalInput], input: &State) -> bool {
+    bindings.iter().any(|c| c.is_pressed(input)) && !bindings.iter().any(|c| c.is_active(input) && !c.is_pressed(input))
+}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Mapping {
@@ -111,7 +123,15 @@ pub struct Mapping {
     pub select: Vec<PhysicalInput>,
     pub start: Vec<PhysicalInput>,
     pub speed_up: Vec<PhysicalInput>,
+    pub slow_motion: Vec<PhysicalInput>,
     pub menu: Vec<PhysicalInput>,
+    pub save_state: Vec<PhysicalInput>,
+    pub load_state: Vec<PhysicalInput>,
+    pub save_states: Vec<PhysicalInput>,
+    pub replay_loop_start: Vec<PhysicalInput>,
+    pub replay_loop_end: Vec<PhysicalInput>,
+    pub replay_step_back: Vec<PhysicalInput>,
+    pub toggle_streamer_mode: Vec<PhysicalInput>,
 }
 
 impl Default for Mapping {
@@ -182,7 +202,15 @@ impl Default for Mapping {
                 PhysicalInput::Button(sdl2::controller::Button::Start),
             ],
             speed_up: vec![PhysicalInput::Key(winit::event::VirtualKeyCode::LShift)],
+            slow_motion: vec![],
             menu: vec![PhysicalInput::Key(winit::event::VirtualKeyCode::Escape)],
+            save_state: vec![PhysicalInput::Key(winit::event::VirtualKeyCode::F5)],
+            load_state: vec![PhysicalInput::Key(winit::event::VirtualKeyCode::F8)],
+            save_states: vec![PhysicalInput::Key(winit::event::VirtualKeyCode::F7)],
+            replay_loop_start: vec![PhysicalInput::Key(winit::event::VirtualKeyCode::LBracket)],
+            replay_loop_end: vec![PhysicalInput::Key(winit::event::VirtualKeyCode::RBracket)],
+            replay_step_back: vec![PhysicalInput::Key(winit::event::VirtualKeyCode::Comma)],
+            toggle_streamer_mode: vec![],
         }
     }
 }
@@ -0,0 +1,264 @@
+use rand::Rng;
+
+use crate::stats;
+
+/// The number of presses a latency test measures.
+pub const NUM_TRIALS: usize = 10;
+
+/// The device key results for the keyboard are stored under. Controllers are stored under their GUIDs.
+pub const KEYBOARD_DEVICE_KEY: &str = "keyboard";
+
+/// How long the tester waits before flashing, chosen at random in this range so the flash can't be anticipated.
+const MIN_FLASH_DELAY: std::time::Duration = std::time::Duration::from_millis(1000);
+const MAX_FLASH_DELAY: std::time::Duration = std::time::Duration::from_millis(3000);
+
+/// Gaps between controller events longer than this are the controller being idle, not its polling interval.
+const MAX_POLLING_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// The results of a latency test for a device, stored in the config.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub device_name: String,
+    pub samples_us: Vec<u64>,
+    pub tested_at: std::time::SystemTime,
+}
+
+impl Report {
+    pub fn samples(&self) -> impl Iterator<Item = std::time::Duration> + '_ {
+        self.samples_us.iter().map(|us| std::time::Duration::from_micros(*us))
+    }
+
+    pub fn median(&self) -> std::time::Duration {
+        let mut samples = self.samples_us.clone();
+        if samples.is_empty() {
+            return std::time::Duration::ZERO;
+        }
+        let (_, v, _) = samples.select_nth_unstable(self.samples_us.len() / 2);
+        std::time::Duration::from_micros(*v)
+    }
+
+    pub fn min(&self) -> std::time::Duration {
+        self.samples().min().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> std::time::Duration {
+        self.samples().max().unwrap_or_default()
+    }
+}
+
+pub struct Controller {
+    pub guid: String,
+    pub name: String,
+    polling_intervals: stats::DeltaCounter,
+    last_event_at: Option<std::time::Instant>,
+}
+
+impl Controller {
+    /// The interval the OS is delivering events from this controller at, if it has been moved enough to tell.
+    pub fn polling_interval(&self) -> Option<std::time::Duration> {
+        let median = self.polling_intervals.median();
+        if median.is_zero() {
+            None
+        } else {
+            Some(median)
+        }
+    }
+}
+
+/// Tracks connected controllers and the timing of their events, shared between the event loop and the GUI.
+pub struct Monitor {
+    sdl_epoch: (std::time::Instant, u32),
+    controllers: std::collections::BTreeMap<u32, Controller>,
+    active_controller: Option<u32>,
+}
+
+impl Monitor {
+    /// Creates a monitor, given the current SDL tick count so SDL event timestamps can be converted to instants.
+    pub fn new(sdl_ticks: u32) -> Self {
+        Self {
+            sdl_epoch: (std::time::Instant::now(), sdl_ticks),
+            controllers: std::collections::BTreeMap::new(),
+            active_controller: None,
+        }
+    }
+
+    /// Converts an SDL event timestamp, in milliseconds since SDL was initialized, to an instant.
+    pub fn instant_from_sdl_timestamp(&self, timestamp: u32) -> std::time::Instant {
+        let (epoch, epoch_ticks) = self.sdl_epoch;
+        epoch + std::time::Duration::from_millis(timestamp.wrapping_sub(epoch_ticks) as u64)
+    }
+
+    pub fn handle_controller_connected(&mut self, which: u32, guid: String, name: String) {
+        self.controllers.insert(
+            which,
+            Controller {
+                guid,
+                name,
+                polling_intervals: stats::DeltaCounter::new(
+                    stats::window::CONTROLLER_POLLING_INTERVAL,
+                    MAX_POLLING_INTERVAL,
+                ),
+                last_event_at: None,
+            },
+        );
+    }
+
+    pub fn handle_controller_disconnected(&mut self, which: u32) {
+        self.controllers.remove(&which);
+        if self.active_controller == Some(which) {
+            self.active_controller = None;
+        }
+    }
+
+    /// Records an event from a controller, timestamped at the source.
+    pub fn handle_controller_event(&mut self, which: u32, at: std::time::Instant) {
+        let controller = if let Some(controller) = self.controllers.get_mut(&which) {
+            controller
+        } else {
+            return;
+        };
+        self.active_controller = Some(which);
+
+        if let Some(last_event_at) = controller.last_event_at {
+            let d = at.saturating_duration_since(last_event_at);
+            // Several axes from the same poll arrive with the same timestamp.
+            if !d.is_zero() {
+                controller.polling_intervals.mark(d);
+            }
+        }
+        controller.last_event_at = Some(at);
+    }
+
+    pub fn controllers(&self) -> impl Iterator<Item = &Controller> {
+        self.controllers.values()
+    }
+
+    pub fn controller(&self, which: u32) -> Option<&Controller> {
+        self.controllers.get(&which)
+    }
+
+    /// The controller the most recent event came from.
+    pub fn active_controller(&self) -> Option<&Controller> {
+        self.active_controller.and_then(|which| self.controllers.get(&which))
+    }
+}
+
+pub enum Phase {
+    /// Waiting to flash at the given time. Presses now are too early.
+    Waiting {
+        flash_at: std::time::Instant,
+    },
+    /// Flashing, since the flash was first drawn.
+    Flashing {
+        shown_at: std::time::Instant,
+    },
+    Done,
+}
+
+/// Measures the time from a flash being drawn to a press from a device arriving, over a number of trials.
+///
+/// This includes the player's reaction time and the display's latency, so it's only meaningful for comparing devices against each other on the same setup.
+pub struct Tester {
+    device_key: String,
+    device_name: String,
+    phase: Phase,
+    samples: Vec<std::time::Duration>,
+    early_presses: usize,
+    reported: bool,
+}
+
+fn random_flash_delay() -> std::time::Duration {
+    rand::thread_rng().gen_range(MIN_FLASH_DELAY..MAX_FLASH_DELAY)
+}
+
+impl Tester {
+    pub fn new(device_key: String, device_name: String) -> Self {
+        Self {
+            device_key,
+            device_name,
+            phase: Phase::Waiting {
+                flash_at: std::time::Instant::now() + random_flash_delay(),
+            },
+            samples: vec![],
+            early_presses: 0,
+            reported: false,
+        }
+    }
+
+    pub fn device_key(&self) -> &str {
+        &self.device_key
+    }
+
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+
+    pub fn phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    pub fn samples(&self) -> &[std::time::Duration] {
+        &self.samples
+    }
+
+    pub fn early_presses(&self) -> usize {
+        self.early_presses
+    }
+
+    /// Advances the test. Returns true if the flash should be drawn this frame.
+    pub fn update(&mut self) -> bool {
+        match self.phase {
+            Phase::Waiting { flash_at } => {
+                if std::time::Instant::now() < flash_at {
+                    return false;
+                }
+                self.phase = Phase::Flashing {
+                    shown_at: std::time::Instant::now(),
+                };
+                true
+            }
+            Phase::Flashing { .. } => true,
+            Phase::Done => false,
+        }
+    }
+
+    /// Handles a press from a device, timestamped as close to the source as possible.
+    pub fn handle_press(&mut self, device_key: &str, at: std::time::Instant) {
+        if device_key != self.device_key {
+            return;
+        }
+
+        match self.phase {
+            Phase::Waiting { .. } => {
+                self.early_presses += 1;
+                self.phase = Phase::Waiting {
+                    flash_at: std::time::Instant::now() + random_flash_delay(),
+                };
+            }
+            Phase::Flashing { shown_at } => {
+                self.samples.push(at.saturating_duration_since(shown_at));
+                self.phase = if self.samples.len() >= NUM_TRIALS {
+                    Phase::Done
+                } else {
+                    Phase::Waiting {
+                        flash_at: std::time::Instant::now() + random_flash_delay(),
+                    }
+                };
+            }
+            Phase::Done => {}
+        }
+    }
+
+    /// Takes the report for a finished test. This only returns it once, so it's only stored once.
+    pub fn take_report(&mut self) -> Option<Report> {
+        if !matches!(self.phase, Phase::Done) || self.reported {
+            return None;
+        }
+        self.reported = true;
+        Some(Report {
+            device_name: self.device_name.clone(),
+            samples_us: self.samples.iter().map(|d| d.as_micros() as u64).collect(),
+            tested_at: std::time::SystemTime::now(),
+        })
+    }
+}
@@ -0,0 +1,329 @@
+/// Characters that may appear in a link code.
+pub const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz0123456789-";
+
+pub const MAX_LENGTH: usize = 40;
+
+pub const URI_SCHEME: &str = "tango";
+
+/// The command line flag organizers can lock link codes to a prefix with, e.g. `--link-code-prefix=weekly42-`.
+const PREFIX_FLAG: &str = "--link-code-prefix";
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("link code is too long")]
+    TooLong,
+
+    #[error("link code contains invalid character {0:?}")]
+    InvalidCharacter(char),
+}
+
+/// Maps characters that are commonly mistaken for characters in the alphabet to them.
+fn fold_lookalike(c: char) -> char {
+    match c {
+        // Fullwidth forms, e.g. from a Japanese IME.
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xff01 + 0x21).unwrap_or(c),
+        // Dashes and hyphens that aren't the ASCII hyphen-minus, e.g. from autocorrect.
+        '\u{2010}'..='\u{2015}' | '\u{2212}' | '\u{30fc}' | '_' => '-',
+        c => c,
+    }
+}
+
+/// Normalizes a link code as it's typed or pasted: surrounding whitespace is removed, lookalike characters are replaced, letters are lowercased and runs of dashes are collapsed.
+///
+/// A single trailing dash is kept so that codes can still be typed out, but should be dropped with `finish` before use. Characters that aren't in the alphabet are never removed, so they can be pointed out by `validate` instead of silently producing a different code.
+pub fn normalize(link_code: &str) -> String {
+    let mut normalized = String::new();
+    for c in link_code
+        .trim()
+        .chars()
+        .map(fold_lookalike)
+        .flat_map(|c| c.to_lowercase())
+    {
+        if c == '-' && (normalized.is_empty() || normalized.ends_with('-')) {
+            continue;
+        }
+        normalized.push(c);
+    }
+    normalized
+}
+
+/// Turns a normalized link code as typed into the link code to connect with.
+pub fn finish(link_code: &str) -> &str {
+    link_code.trim_end_matches('-')
+}
+
+/// Locks a normalized link code as typed to a prefix. Codes that don't start with it have it added, and deleting into the prefix clears the whole code, so the prefix is never left half edited. The empty link code still means to play alone.
+pub fn with_prefix(link_code: &str, prefix: &str) -> String {
+    if link_code.is_empty() || link_code.starts_with(prefix) {
+        link_code.to_string()
+    } else if prefix.starts_with(link_code) {
+        "".to_string()
+    } else {
+        format!("{}{}", prefix, link_code)
+    }
+}
+
+/// Finds a prefix to lock link codes to among command line arguments, given as either `--link-code-prefix=<prefix>` or `--link-code-prefix <prefix>`.
+pub fn prefix_from_args(mut args: impl Iterator<Item = std::ffi::OsString>) -> Option<String> {
+    while let Some(arg) = args.next() {
+        let arg = if let Some(arg) = arg.to_str() {
+            arg
+        } else {
+            continue;
+        };
+        if arg == PREFIX_FLAG {
+            return args
+                .next()
+                .and_then(|prefix| prefix.to_str().map(|prefix| prefix.to_string()));
+        }
+        if let Some(prefix) = arg.strip_prefix(PREFIX_FLAG).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(prefix.to_string());
+        }
+    }
+    None
+}
+
+/// Checks that a normalized link code is valid. The empty link code is valid: it means to play alone.
+pub fn validate(link_code: &str) -> Result<(), ValidationError> {
+    if let Some(c) = link_code.chars().find(|c| !ALPHABET.contains(*c)) {
+        return Err(ValidationError::InvalidCharacter(c));
+    }
+    if link_code.len() > MAX_LENGTH {
+        return Err(ValidationError::TooLong);
+    }
+    Ok(())
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum InviteParseError {
+    #[error("invalid uri: {0}")]
+    Url(#[from] url::ParseError),
+
+    #[error("not a {}:// uri", URI_SCHEME)]
+    WrongScheme,
+
+    #[error("unknown invite action: {0}")]
+    UnknownAction(String),
+
+    #[error("missing link code")]
+    MissingLinkCode,
+
+    #[error(transparent)]
+    InvalidLinkCode(#[from] ValidationError),
+}
+
+/// An invitation to a match, shareable as a `tango://join/<link code>?server=<matchmaking endpoint>` URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invite {
+    pub link_code: String,
+    /// None if the default matchmaking endpoint should be used.
+    pub matchmaking_endpoint: Option<String>,
+}
+
+impl Invite {
+    pub fn to_uri(&self) -> String {
+        let mut url = url::Url::parse(&format!("{}://join/", URI_SCHEME)).unwrap();
+        url.path_segments_mut().unwrap().pop_if_empty().push(&self.link_code);
+        if let Some(matchmaking_endpoint) = self.matchmaking_endpoint.as_ref() {
+            url.query_pairs_mut().append_pair("server", matchmaking_endpoint);
+        }
+        url.to_string()
+    }
+
+    pub fn parse(uri: &str) -> Result<Self, InviteParseError> {
+        let url = url::Url::parse(uri.trim())?;
+        if url.scheme() != URI_SCHEME {
+            return Err(InviteParseError::WrongScheme);
+        }
+
+        let action = url.host_str().unwrap_or("");
+        if action != "join" {
+            return Err(InviteParseError::UnknownAction(action.to_string()));
+        }
+
+        let link_code = url
+            .path_segments()
+            .and_then(|mut segments| segments.find(|segment| !segment.is_empty()))
+            .ok_or(InviteParseError::MissingLinkCode)?;
+        let link_code = finish(&normalize(
+            &urlencoding::decode(link_code).map_err(|_| InviteParseError::MissingLinkCode)?,
+        ))
+        .to_string();
+        if link_code.is_empty() {
+            return Err(InviteParseError::MissingLinkCode);
+        }
+        validate(&link_code)?;
+
+        let matchmaking_endpoint = url
+            .query_pairs()
+            .find(|(k, _)| k == "server")
+            .map(|(_, v)| v.to_string())
+            .filter(|v| !v.is_empty());
+
+        Ok(Self {
+            link_code,
+            matchmaking_endpoint,
+        })
+    }
+
+    /// Finds an invite among command line arguments, which is how the OS hands us URIs we're registered for.
+    pub fn from_args(args: impl Iterator<Item = std::ffi::OsString>) -> Option<Self> {
+        let prefix = format!("{}:", URI_SCHEME);
+        for arg in args {
+            let arg = if let Some(arg) = arg.to_str() {
+                arg
+            } else {
+                continue;
+            };
+            if !arg.starts_with(&prefix) {
+                continue;
+            }
+            match Self::parse(arg) {
+                Ok(invite) => {
+                    return Some(invite);
+                }
+                Err(e) => {
+                    log::warn!("ignoring invalid invite {}: {}", arg, e);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Renders a QR code for some text, as an image with one pixel per module.
+pub fn render_qr(text: &str) -> Result<image::GrayImage, qrcode::types::QrError> {
+    // The spec calls for a 4 module wide light border around the code.
+    const QUIET_ZONE: u32 = 4;
+
+    let code = qrcode::QrCode::new(text.as_bytes())?;
+    let width = code.width() as u32;
+    let colors = code.to_colors();
+    Ok(image::GrayImage::from_fn(
+        width + QUIET_ZONE * 2,
+        width + QUIET_ZONE * 2,
+        |x, y| {
+            let dark = x >= QUIET_ZONE
+                && y >= QUIET_ZONE
+                && x < width + QUIET_ZONE
+                && y < width + QUIET_ZONE
+                && colors[((y - QUIET_ZONE) * width + (x - QUIET_ZONE)) as usize] == qrcode::Color::Dark;
+            image::Luma([if dark { 0x00 } else { 0xff }])
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("  Hello--World_ "), "hello-world-");
+        assert_eq!(normalize("ＡＢＣ－１２３"), "abc-123");
+        assert_eq!(normalize("--abc"), "abc");
+        assert_eq!(normalize("abc-"), "abc-");
+        assert_eq!(normalize("abc\u{2014}def"), "abc-def");
+        // Characters outside the alphabet are kept so that they can be pointed out.
+        assert_eq!(normalize("abc!"), "abc!");
+    }
+
+    #[test]
+    fn test_validate_alphabet_edges() {
+        assert_eq!(validate(""), Ok(()));
+        assert_eq!(validate("a"), Ok(()));
+        assert_eq!(validate("z"), Ok(()));
+        assert_eq!(validate("0"), Ok(()));
+        assert_eq!(validate("9"), Ok(()));
+        assert_eq!(validate("-"), Ok(()));
+        // Just outside the alphabet's ranges.
+        for c in ['`', '{', '/', ':', '.', '_', 'A', 'é', ' '] {
+            assert_eq!(
+                validate(&format!("abc{}", c)),
+                Err(ValidationError::InvalidCharacter(c)),
+                "{:?}",
+                c
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_length() {
+        assert_eq!(validate(&"a".repeat(MAX_LENGTH)), Ok(()));
+        assert_eq!(validate(&"a".repeat(MAX_LENGTH + 1)), Err(ValidationError::TooLong));
+    }
+
+    #[test]
+    fn test_invite_round_trip() {
+        for invite in [
+            Invite {
+                link_code: "abc-123".to_string(),
+                matchmaking_endpoint: None,
+            },
+            Invite {
+                link_code: "abc-123".to_string(),
+                matchmaking_endpoint: Some("wss://example.com/signaling?a=b&c=d".to_string()),
+            },
+        ] {
+            assert_eq!(Invite::parse(&invite.to_uri()).unwrap(), invite);
+        }
+    }
+
+    #[test]
+    fn test_invite_parse_normalizes() {
+        let invite = Invite::parse(" tango://join/ABC--123-?server= ").unwrap();
+        assert_eq!(invite.link_code, "abc-123");
+        assert_eq!(invite.matchmaking_endpoint, None);
+        assert_eq!(Invite::parse("tango://join//abc").unwrap().link_code, "abc");
+        assert_eq!(Invite::parse("tango://join/%EF%BC%A1bc").unwrap().link_code, "abc");
+    }
+
+    #[test]
+    fn test_invite_parse_malformed() {
+        assert!(matches!(Invite::parse(""), Err(InviteParseError::Url(_))));
+        assert!(matches!(Invite::parse("join/abc"), Err(InviteParseError::Url(_))));
+        assert!(matches!(
+            Invite::parse("https://join/abc"),
+            Err(InviteParseError::WrongScheme)
+        ));
+        assert!(matches!(
+            Invite::parse("tango://watch/abc"),
+            Err(InviteParseError::UnknownAction(action)) if action == "watch"
+        ));
+        assert!(matches!(
+            Invite::parse("tango:join/abc"),
+            Err(InviteParseError::UnknownAction(action)) if action.is_empty()
+        ));
+        assert!(matches!(
+            Invite::parse("tango://join"),
+            Err(InviteParseError::MissingLinkCode)
+        ));
+        assert!(matches!(
+            Invite::parse("tango://join/---"),
+            Err(InviteParseError::MissingLinkCode)
+        ));
+        assert!(matches!(
+            Invite::parse("tango://join/%FF"),
+            Err(InviteParseError::MissingLinkCode)
+        ));
+        assert!(matches!(
+            Invite::parse("tango://join/abc%21"),
+            Err(InviteParseError::InvalidLinkCode(ValidationError::InvalidCharacter(
+                '!'
+            )))
+        ));
+        assert!(matches!(
+            Invite::parse(&format!("tango://join/{}", "a".repeat(MAX_LENGTH + 1))),
+            Err(InviteParseError::InvalidLinkCode(ValidationError::TooLong))
+        ));
+    }
+
+    #[test]
+    fn test_invite_from_args() {
+        let args = ["tango", "--foo", "tango://join/bad!", "tango://join/abc"]
+            .iter()
+            .map(std::ffi::OsString::from);
+        assert_eq!(Invite::from_args(args).unwrap().link_code, "abc");
+        assert_eq!(Invite::from_args(std::iter::empty()), None);
+    }
+}
@@ -0,0 +1,317 @@
+//! Logging, split up by subsystem so that each one can be turned up on its own while debugging.
+//!
+//! Records go to stderr, which the parent process writes out to rotating log files, and the most recent ones are also kept in memory for the log viewer and crash reports.
+
+/// How many of the most recent entries are kept in memory.
+const MAX_ENTRIES: usize = 2000;
+
+/// Log files are rolled over once they get this big.
+const MAX_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Once the log files add up to more than this, the oldest ones are deleted.
+const MAX_TOTAL_SIZE: u64 = 64 * 1024 * 1024;
+
+pub struct Subsystem {
+    pub name: &'static str,
+    /// The module paths logged from, which are what records are targeted at.
+    targets: &'static [&'static str],
+}
+
+pub const SUBSYSTEMS: &[Subsystem] = &[
+    Subsystem {
+        name: "net",
+        targets: &["tango::net", "tango::battle", "tango::lockstep", "tango::shadow"],
+    },
+    Subsystem {
+        name: "lobby",
+        targets: &["tango::gui::play_pane"],
+    },
+    Subsystem {
+        name: "hooks::bn1",
+        targets: &["tango::game::bn1::hooks"],
+    },
+    Subsystem {
+        name: "hooks::bn2",
+        targets: &["tango::game::bn2::hooks"],
+    },
+    Subsystem {
+        name: "hooks::bn3",
+        targets: &["tango::game::bn3::hooks"],
+    },
+    Subsystem {
+        name: "hooks::bn4",
+        targets: &["tango::game::bn4::hooks"],
+    },
+    Subsystem {
+        name: "hooks::exe45",
+        targets: &["tango::game::exe45::hooks"],
+    },
+    Subsystem {
+        name: "hooks::bn5",
+        targets: &["tango::game::bn5::hooks"],
+    },
+    Subsystem {
+        name: "hooks::bn6",
+        targets: &["tango::game::bn6::hooks"],
+    },
+    Subsystem {
+        name: "session",
+        targets: &["tango::session", "tango::replayer"],
+    },
+    Subsystem {
+        name: "scanner",
+        targets: &["tango::scanner"],
+    },
+    Subsystem {
+        name: "patch",
+        targets: &["tango::patch"],
+    },
+];
+
+/// The subsystem everything else in Tango is logged under.
+pub const OTHER: &str = "other";
+
+const DEFAULT_LEVEL: log::LevelFilter = log::LevelFilter::Info;
+
+fn matches_target(target: &str, prefix: &str) -> bool {
+    target
+        .strip_prefix(prefix)
+        .map(|rest| rest.is_empty() || rest.starts_with("::"))
+        .unwrap_or(false)
+}
+
+/// The subsystem a record's target belongs to. Records from outside Tango, e.g. from mGBA, don't belong to any.
+pub fn subsystem(target: &str) -> Option<&'static str> {
+    if let Some(subsystem) = SUBSYSTEMS
+        .iter()
+        .find(|subsystem| subsystem.targets.iter().any(|prefix| matches_target(target, prefix)))
+    {
+        return Some(subsystem.name);
+    }
+    if matches_target(target, "tango") {
+        return Some(OTHER);
+    }
+    None
+}
+
+/// A single logged record.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub time: time::OffsetDateTime,
+    pub level: log::Level,
+    pub subsystem: Option<&'static str>,
+    pub target: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{} {} {}] {}",
+            self.time
+                .format(time::macros::format_description!(
+                    "[hour padding:zero]:[minute padding:zero]:[second padding:zero].[subsecond digits:3]"
+                ))
+                .unwrap_or_default(),
+            self.level,
+            self.target,
+            self.message
+        )
+    }
+}
+
+lazy_static! {
+    static ref ENTRIES: parking_lot::Mutex<std::collections::VecDeque<Entry>> =
+        parking_lot::Mutex::new(std::collections::VecDeque::with_capacity(MAX_ENTRIES));
+    static ref LEVELS: parking_lot::RwLock<std::collections::HashMap<&'static str, log::LevelFilter>> =
+        parking_lot::RwLock::new(std::collections::HashMap::new());
+}
+
+/// The level a subsystem is logged at.
+pub fn level(subsystem: &str) -> log::LevelFilter {
+    LEVELS.read().get(subsystem).cloned().unwrap_or(DEFAULT_LEVEL)
+}
+
+/// Changes the level a subsystem is logged at. This takes effect immediately.
+pub fn set_level(subsystem: &'static str, level: log::LevelFilter) {
+    LEVELS.write().insert(subsystem, level);
+}
+
+/// Sets every subsystem's level from the config, where they're stored by name. Subsystems left out go back to the default level.
+pub fn set_levels(levels: &std::collections::BTreeMap<String, String>) {
+    let mut current = LEVELS.write();
+    current.clear();
+    for name in SUBSYSTEMS
+        .iter()
+        .map(|subsystem| subsystem.name)
+        .chain(std::iter::once(OTHER))
+    {
+        let level = if let Some(level) = levels.get(name) {
+            level
+        } else {
+            continue;
+        };
+        match level.parse() {
+            Ok(level) => {
+                current.insert(name, level);
+            }
+            Err(e) => {
+                log::warn!("invalid log level {:?} for {}: {}", level, name, e);
+            }
+        }
+    }
+}
+
+/// The most recent entries that pass the filter, oldest first.
+pub fn recent(filter: impl Fn(&Entry) -> bool) -> Vec<Entry> {
+    ENTRIES.lock().iter().filter(|entry| filter(entry)).cloned().collect()
+}
+
+/// The most recent entries, formatted as lines, for when the log can't be waited on forever (e.g. from a panic hook, which may have been hit while it was held).
+pub fn try_recent_lines(timeout: std::time::Duration) -> Option<Vec<String>> {
+    ENTRIES
+        .try_lock_for(timeout)
+        .map(|entries| entries.iter().map(|entry| entry.to_string()).collect())
+}
+
+/// Replaces the user's home directory with `~` wherever it appears, so logs can be shared without giving away the user's name.
+pub fn redact_home_dir(s: &str) -> String {
+    let home_dir = if let Some(base_dirs) = directories_next::BaseDirs::new() {
+        base_dirs.home_dir().to_string_lossy().to_string()
+    } else {
+        return s.to_string();
+    };
+    if home_dir.is_empty() {
+        return s.to_string();
+    }
+    s.replace(&home_dir, "~")
+}
+
+/// A logger that filters Tango's records by subsystem and keeps the most recent ones in memory.
+pub struct Logger {
+    inner: env_logger::Logger,
+}
+
+impl Logger {
+    pub fn init(inner: env_logger::Logger) -> Result<(), log::SetLoggerError> {
+        log::set_boxed_logger(Box::new(Self { inner }))?;
+        // Levels can be turned up at any time, so everything has to at least make it as far as the logger.
+        log::set_max_level(log::LevelFilter::Trace);
+        Ok(())
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        if let Some(subsystem) = subsystem(metadata.target()) {
+            if metadata.level() > level(subsystem) {
+                return false;
+            }
+        }
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) || !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+
+        let entry = Entry {
+            time: time::OffsetDateTime::from(std::time::SystemTime::now()),
+            level: record.level(),
+            subsystem: subsystem(record.target()),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        let mut entries = ENTRIES.lock();
+        if entries.len() >= MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Log files for one run of Tango. A new file is started whenever the current one gets too big, and the oldest logs are deleted so they don't take up more than a few dozen MB altogether.
+pub struct RotatingFile {
+    dir: std::path::PathBuf,
+    stem: String,
+    index: usize,
+    path: std::path::PathBuf,
+    file: std::fs::File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn create(dir: &std::path::Path, stem: &str) -> std::io::Result<Self> {
+        let path = dir.join(format!("{}.log", stem));
+        let file = std::fs::File::create(&path)?;
+        let rotating_file = Self {
+            dir: dir.to_path_buf(),
+            stem: stem.to_string(),
+            index: 0,
+            path,
+            file,
+            size: 0,
+        };
+        rotating_file.prune();
+        Ok(rotating_file)
+    }
+
+    /// The file currently being written to.
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// Writes a line, moving on to a new file first if the current one is full. Lines are never split across files.
+    pub fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > MAX_FILE_SIZE {
+            self.index += 1;
+            self.path = self.dir.join(format!("{}.{}.log", self.stem, self.index));
+            self.file = std::fs::File::create(&self.path)?;
+            self.size = 0;
+            self.prune();
+        }
+        std::io::Write::write_all(&mut self.file, line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Deletes the oldest logs, other than the current one, until they all fit.
+    fn prune(&self) {
+        let mut logs = match std::fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    let path = entry.path();
+                    if path.extension() != Some(std::ffi::OsStr::new("log")) || path == self.path {
+                        return None;
+                    }
+                    let metadata = entry.metadata().ok()?;
+                    Some((metadata.modified().ok()?, metadata.len(), path))
+                })
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                log::error!("failed to list logs in {}: {:?}", self.dir.display(), e);
+                return;
+            }
+        };
+        logs.sort_by(|(a, _, _), (b, _, _)| b.cmp(a));
+
+        let mut total_size = self.size;
+        for (_, size, path) in logs {
+            total_size += size;
+            if total_size <= MAX_TOTAL_SIZE {
+                continue;
+            }
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::error!("failed to delete old log {}: {:?}", path.display(), e);
+            }
+        }
+    }
+}
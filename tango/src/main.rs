@@ -3,35 +3,53 @@
 #[macro_use]
 extern crate lazy_static;
 
+mod assist;
 mod audio;
 mod battle;
+mod bios;
+mod broadcast;
+mod cli;
 mod config;
+mod contentpolicy;
+mod crashreport;
+mod desyncrisk;
+mod determinism;
 mod discord;
+mod fairness;
 mod filesync;
+mod fs;
 mod game;
 mod graphics;
 mod gui;
+#[cfg(all(test, feature = "core-tests"))]
+mod harness;
 mod i18n;
 mod input;
+mod inputlatency;
+mod linkcode;
 mod lockstep;
+mod logging;
 mod net;
+mod opponents;
 mod patch;
+mod practice;
 mod randomcode;
 mod replay;
 mod replayer;
 mod rom;
 mod save;
+mod savestates;
 mod scanner;
 mod session;
 mod shadow;
+mod sharecode;
 mod stats;
 mod sync;
+mod throttle;
 mod updater;
 mod version;
 mod video;
 
-use fluent_templates::Loader;
-
 const TANGO_CHILD_ENV_VAR: &str = "TANGO_CHILD";
 
 enum UserEvent {
@@ -41,14 +59,21 @@ enum UserEvent {
 fn main() -> Result<(), anyhow::Error> {
     std::env::set_var("RUST_BACKTRACE", "1");
 
-    env_logger::Builder::from_default_env()
-        .filter(Some("tango"), log::LevelFilter::Info)
-        .filter(Some("datachannel"), log::LevelFilter::Info)
-        .filter(Some("mgba"), log::LevelFilter::Info)
-        .init();
+    logging::Logger::init(
+        env_logger::Builder::from_default_env()
+            // Tango's own records are filtered by subsystem instead, at levels that can be changed while running.
+            .filter(Some("tango"), log::LevelFilter::Trace)
+            .filter(Some("datachannel"), log::LevelFilter::Info)
+            .filter(Some("mgba"), log::LevelFilter::Info)
+            .build(),
+    )?;
 
     log::info!("welcome to tango {}!", version::VERSION);
 
+    if let Some(result) = cli::run() {
+        return result;
+    }
+
     let config = config::Config::load_or_create()?;
     config.ensure_dirs()?;
 
@@ -56,57 +81,83 @@ fn main() -> Result<(), anyhow::Error> {
         return child_main(config);
     }
 
-    let log_filename = format!(
-        "{}.log",
-        time::OffsetDateTime::from(std::time::SystemTime::now())
-            .format(time::macros::format_description!(
-                "[year padding:zero][month padding:zero repr:numerical][day padding:zero][hour padding:zero][minute padding:zero][second padding:zero]"
-            ))
-            .expect("format time"),
-    );
-
-    let log_path = config.logs_path().join(log_filename);
-    log::info!("logging to: {}", log_path.display());
+    let log_stem = time::OffsetDateTime::from(std::time::SystemTime::now())
+        .format(time::macros::format_description!(
+            "[year padding:zero][month padding:zero repr:numerical][day padding:zero][hour padding:zero][minute padding:zero][second padding:zero]"
+        ))
+        .expect("format time");
 
-    let log_file = match std::fs::File::create(&log_path) {
+    let mut log_file = match logging::RotatingFile::create(&config.logs_path(), &log_stem) {
         Ok(f) => f,
         Err(e) => {
             rfd::MessageDialog::new()
-                .set_title(&i18n::LOCALES.lookup(&config.language, "window-title").unwrap())
-                .set_description(
-                    &i18n::LOCALES
-                        .lookup_with_args(
-                            &config.language,
-                            "crash-no-log",
-                            &std::collections::HashMap::from([("error", format!("{:?}", e).into())]),
-                        )
-                        .unwrap(),
-                )
+                .set_title(&i18n::LOCALES.lookup(&config.language, "window-title"))
+                .set_description(&i18n::LOCALES.lookup_with_args(
+                    &config.language,
+                    "crash-no-log",
+                    &std::collections::HashMap::from([("error", format!("{:?}", e).into())]),
+                ))
                 .set_level(rfd::MessageLevel::Error)
                 .show();
             return Err(e.into());
         }
     };
+    log::info!("logging to: {}", log_file.path().display());
 
-    let status = std::process::Command::new(std::env::current_exe()?)
+    let child_start_time = std::time::SystemTime::now();
+    let mut child = std::process::Command::new(std::env::current_exe()?)
         .args(std::env::args_os().skip(1).collect::<Vec<std::ffi::OsString>>())
         .env(TANGO_CHILD_ENV_VAR, "1")
-        .stderr(log_file)
-        .spawn()?
-        .wait()?;
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+
+    // Keep reading even if the log can't be written to, or the child will block once the pipe fills up.
+    let mut child_stderr = std::io::BufReader::new(child.stderr.take().unwrap());
+    let mut line = vec![];
+    loop {
+        line.clear();
+        match std::io::BufRead::read_until(&mut child_stderr, b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                if let Err(e) = log_file.write_line(&line) {
+                    log::error!("failed to write to log {}: {:?}", log_file.path().display(), e);
+                }
+            }
+            Err(e) => {
+                log::error!("failed to read log from child: {:?}", e);
+                break;
+            }
+        }
+    }
+    let status = child.wait()?;
+    let log_path = log_file.path().to_path_buf();
 
     if !status.success() {
+        // If the child panicked, it will have written a report itself. Otherwise, it died in a way it couldn't catch (e.g. a segfault in the core), so write what we can.
+        let report_path = if let Some(report_path) =
+            crashreport::latest_report_since(&config.crash_reports_path(), child_start_time)
+        {
+            Some(report_path)
+        } else {
+            match crashreport::write_report_for_child(&config.crash_reports_path(), &status, &log_path) {
+                Ok(report_path) => Some(report_path),
+                Err(e) => {
+                    log::error!("failed to write crash report: {:?}", e);
+                    None
+                }
+            }
+        };
+
         rfd::MessageDialog::new()
-            .set_title(&i18n::LOCALES.lookup(&config.language, "window-title").unwrap())
-            .set_description(
-                &i18n::LOCALES
-                    .lookup_with_args(
-                        &config.language,
-                        "crash",
-                        &std::collections::HashMap::from([("path", format!("{}", log_path.display()).into())]),
-                    )
-                    .unwrap(),
-            )
+            .set_title(&i18n::LOCALES.lookup(&config.language, "window-title"))
+            .set_description(&i18n::LOCALES.lookup_with_args(
+                &config.language,
+                "crash",
+                &std::collections::HashMap::from([(
+                    "path",
+                    format!("{}", report_path.as_ref().unwrap_or(&log_path).display()).into(),
+                )]),
+            ))
             .set_level(rfd::MessageLevel::Error)
             .show();
     }
@@ -122,10 +173,20 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
     let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
     let _enter_guard = rt.enter();
 
+    logging::set_levels(&config.log_levels);
+
+    contentpolicy::set(&config.content_policy);
+    if let Some(prefix) = linkcode::prefix_from_args(std::env::args_os().skip(1)) {
+        log::info!("link codes are locked to the prefix {:?}", prefix);
+        contentpolicy::override_link_code_prefix(prefix);
+    }
+
     let config = std::sync::Arc::new(parking_lot::RwLock::new(config));
 
     mgba::log::init();
 
+    crashreport::install_panic_hook(config.read().crash_reports_path());
+
     let updater_path = config::get_updater_path().unwrap();
     let _ = std::fs::create_dir_all(&updater_path);
     let mut updater = updater::Updater::new(&updater_path, config.clone());
@@ -134,6 +195,8 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
     let sdl = sdl2::init().unwrap();
     let audio = sdl.audio().unwrap();
     let game_controller = sdl.game_controller().unwrap();
+    let joystick = sdl.joystick().unwrap();
+    let sdl_timer = sdl.timer().unwrap();
 
     let event_loop = winit::event_loop::EventLoopBuilder::with_user_event().build();
     let mut sdl_event_loop = sdl.event_pump().unwrap();
@@ -143,7 +206,7 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
     let icon_height = icon.height();
 
     let wb = winit::window::WindowBuilder::new()
-        .with_title(&i18n::LOCALES.lookup(&config.read().language, "window-title").unwrap())
+        .with_title(&i18n::LOCALES.lookup(&config.read().language, "window-title"))
         .with_window_icon(Some(winit::window::Icon::from_rgba(
             icon.into_bytes(),
             icon_width,
@@ -169,7 +232,7 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
             glutin::ContextBuilder::new()
                 .with_depth_buffer(0)
                 .with_stencil_buffer(0)
-                .with_vsync(true)
+                .with_vsync(config.read().vsync)
                 .build_windowed(wb, &event_loop)
                 .unwrap(),
             &event_loop,
@@ -186,7 +249,11 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
                     features: wgpu::Features::default(),
                     limits: wgpu::Limits::downlevel_webgl2_defaults(),
                 },
-                wgpu::PresentMode::Fifo,
+                if config.read().vsync {
+                    wgpu::PresentMode::Fifo
+                } else {
+                    wgpu::PresentMode::AutoNoVsync
+                },
                 1,
             ),
             &event_loop,
@@ -210,17 +277,20 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
 
     let audio_binder = audio::LateBinder::new(48000);
 
-    let _audio_backend: Box<dyn audio::Backend> = match config.read().audio_backend {
+    let audio_backend: Box<dyn audio::Backend> = match config.read().audio_backend {
         #[cfg(feature = "cpal")]
-        config::AudioBackend::Cpal => Box::new(audio::cpal::Backend::new(audio_binder.clone())?),
+        config::AudioBackend::Cpal => Box::new(audio::cpal::Backend::new()),
         #[cfg(feature = "sdl2-audio")]
-        config::AudioBackend::Sdl2 => Box::new(audio::sdl2::Backend::new(&audio, audio_binder.clone())?),
+        config::AudioBackend::Sdl2 => Box::new(audio::sdl2::Backend::new(&audio)),
     };
+    let mut audio_output = audio::Output::new(audio_backend, audio_binder.clone(), config.read().audio_device.clone());
 
-    let fps_counter = std::sync::Arc::new(parking_lot::Mutex::new(stats::Counter::new(30)));
-    let emu_tps_counter = std::sync::Arc::new(parking_lot::Mutex::new(stats::Counter::new(10)));
+    let fps_counter = std::sync::Arc::new(parking_lot::Mutex::new(stats::Counter::new(stats::window::FPS)));
+    let emu_tps_counter = std::sync::Arc::new(parking_lot::Mutex::new(stats::Counter::new(stats::window::EMU_TPS)));
 
     let mut input_state = input::State::new();
+    let input_latency_monitor =
+        std::sync::Arc::new(parking_lot::Mutex::new(inputlatency::Monitor::new(sdl_timer.ticks())));
 
     let mut controllers: std::collections::HashMap<u32, sdl2::controller::GameController> =
         std::collections::HashMap::new();
@@ -231,11 +301,22 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
         }
         let controller = game_controller.open(which).unwrap();
         log::info!("controller added: {}", controller.name());
+        input_latency_monitor.lock().handle_controller_connected(
+            controller.instance_id(),
+            joystick
+                .device_guid(which)
+                .map(|guid| guid.to_string())
+                .unwrap_or_default(),
+            controller.name(),
+        );
         controllers.insert(which, controller);
     }
 
     let discord_client = discord::Client::new();
 
+    let broadcast_server = broadcast::Server::new(config.clone());
+    broadcast_server.set_enabled(config.read().enable_broadcast_api);
+
     let roms_scanner = scanner::Scanner::new();
     let saves_scanner = scanner::Scanner::new();
     let patches_scanner = scanner::Scanner::new();
@@ -243,8 +324,8 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
         let roms_path = config.read().roms_path();
         let saves_path = config.read().saves_path();
         let patches_path = config.read().patches_path();
-        roms_scanner.rescan(move || Some(game::scan_roms(&roms_path)));
-        saves_scanner.rescan(move || Some(save::scan_saves(&saves_path)));
+        roms_scanner.rescan_progressively(move |progress| Some(game::scan_roms(&roms_path, false, progress)));
+        saves_scanner.rescan_progressively(move |progress| Some(save::scan_saves(&saves_path, false, progress)));
         patches_scanner.rescan(move || Some(patch::scan(&patches_path).unwrap_or_default()));
     }
 
@@ -252,12 +333,16 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
         egui_ctx,
         config.clone(),
         discord_client,
+        broadcast_server.clone(),
         audio_binder.clone(),
         fps_counter.clone(),
         emu_tps_counter.clone(),
         roms_scanner.clone(),
         saves_scanner.clone(),
         patches_scanner.clone(),
+        input_latency_monitor.clone(),
+        audio_output.device_names(),
+        linkcode::Invite::from_args(std::env::args_os().skip(1)),
     );
 
     let mut patch_autoupdater = patch::Autoupdater::new(config.clone(), patches_scanner.clone());
@@ -283,6 +368,10 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
 
             gfx_backend.paint();
             fps_counter.lock().mark();
+
+            if state.ready_to_exit() {
+                control_flow.set_exit();
+            }
         };
 
         match event {
@@ -306,7 +395,15 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
                         ..
                     } => match element_state {
                         winit::event::ElementState::Pressed => {
-                            if let Some(steal_input) = state.steal_input.take() {
+                            // Keyboard events carry no timestamp, so this is as close to the source as we can get.
+                            let pressed_at = std::time::Instant::now();
+                            if let Some(tester) = state
+                                .input_latency_tester
+                                .as_mut()
+                                .filter(|tester| tester.device_key() == inputlatency::KEYBOARD_DEVICE_KEY)
+                            {
+                                tester.handle_press(inputlatency::KEYBOARD_DEVICE_KEY, pressed_at);
+                            } else if let Some(steal_input) = state.steal_input.take() {
                                 steal_input.run_callback(
                                     input::PhysicalInput::Key(virutal_keycode),
                                     &mut next_config.input_mapping,
@@ -330,8 +427,11 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
                     window_event => {
                         gfx_backend.on_window_event(&window_event);
                         match window_event {
-                            winit::event::WindowEvent::Focused(false) => {
-                                input_state.clear_keys();
+                            winit::event::WindowEvent::Focused(focused) => {
+                                state.set_window_focused(focused);
+                                if !focused {
+                                    input_state.clear_keys();
+                                }
                             }
                             winit::event::WindowEvent::Occluded(false) => {
                                 next_config.full_screen = gfx_backend.window().fullscreen().is_some();
@@ -343,7 +443,9 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
                                 state.last_mouse_motion_time = None;
                             }
                             winit::event::WindowEvent::CloseRequested => {
-                                control_flow.set_exit();
+                                if state.request_exit(&next_config) {
+                                    control_flow.set_exit();
+                                }
                             }
                             _ => {}
                         }
@@ -368,6 +470,14 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
                             if game_controller.is_game_controller(which) {
                                 let controller = game_controller.open(which).unwrap();
                                 log::info!("controller added: {}", controller.name());
+                                input_latency_monitor.lock().handle_controller_connected(
+                                    controller.instance_id(),
+                                    joystick
+                                        .device_guid(which)
+                                        .map(|guid| guid.to_string())
+                                        .unwrap_or_default(),
+                                    controller.name(),
+                                );
                                 controllers.insert(which, controller);
                                 input_state.handle_controller_connected(
                                     which,
@@ -380,8 +490,19 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
                                 log::info!("controller removed: {}", controller.name());
                                 input_state.handle_controller_disconnected(which);
                             }
+                            input_latency_monitor.lock().handle_controller_disconnected(which);
                         }
-                        sdl2::event::Event::ControllerAxisMotion { axis, value, which, .. } => {
+                        sdl2::event::Event::ControllerAxisMotion {
+                            axis,
+                            value,
+                            which,
+                            timestamp,
+                        } => {
+                            {
+                                let mut input_latency_monitor = input_latency_monitor.lock();
+                                let at = input_latency_monitor.instant_from_sdl_timestamp(timestamp);
+                                input_latency_monitor.handle_controller_event(which, at);
+                            }
                             if value > input::AXIS_THRESHOLD || value < -input::AXIS_THRESHOLD {
                                 if let Some(steal_input) = state.steal_input.take() {
                                     steal_input.run_callback(
@@ -402,8 +523,30 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
                             input_state.handle_controller_axis_motion(which, axis as usize, value);
                             gfx_backend.window().request_redraw();
                         }
-                        sdl2::event::Event::ControllerButtonDown { button, which, .. } => {
-                            if let Some(steal_input) = state.steal_input.take() {
+                        sdl2::event::Event::ControllerButtonDown {
+                            button,
+                            which,
+                            timestamp,
+                        } => {
+                            let (pressed_at, device_key) = {
+                                let mut input_latency_monitor = input_latency_monitor.lock();
+                                let at = input_latency_monitor.instant_from_sdl_timestamp(timestamp);
+                                input_latency_monitor.handle_controller_event(which, at);
+                                (
+                                    at,
+                                    input_latency_monitor
+                                        .controller(which)
+                                        .map(|controller| controller.guid.clone()),
+                                )
+                            };
+                            if let Some((tester, device_key)) = state
+                                .input_latency_tester
+                                .as_mut()
+                                .zip(device_key)
+                                .filter(|(tester, device_key)| tester.device_key() == device_key.as_str())
+                            {
+                                tester.handle_press(&device_key, pressed_at);
+                            } else if let Some(steal_input) = state.steal_input.take() {
                                 steal_input
                                     .run_callback(input::PhysicalInput::Button(button), &mut next_config.input_mapping);
                             } else {
@@ -418,6 +561,22 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
                         _ => {}
                     })();
                 }
+
+                if let Some(event) = audio_output.update(next_config.audio_device.as_deref()) {
+                    state.handle_audio_output_event(&next_config.language, event);
+                    gfx_backend.window().request_redraw();
+                }
+                if audio_output.is_lost() {
+                    // Make sure we come back around to try reconnecting even if nothing else happens.
+                    let retry_at = std::time::Instant::now() + std::time::Duration::from_secs(1);
+                    match *control_flow {
+                        winit::event_loop::ControlFlow::Wait => control_flow.set_wait_until(retry_at),
+                        winit::event_loop::ControlFlow::WaitUntil(until) if until > retry_at => {
+                            control_flow.set_wait_until(retry_at)
+                        }
+                        _ => {}
+                    }
+                }
             }
 
             winit::event::Event::RedrawEventsCleared if cfg!(windows) => redraw(),
@@ -427,6 +586,7 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
         }
 
         if next_config != old_config {
+            contentpolicy::set(&next_config.content_policy);
             *config.write() = next_config.clone();
             let r = next_config.save();
             log::info!("config save: {:?}", r);
@@ -434,5 +594,6 @@ fn child_main(config: config::Config) -> Result<(), anyhow::Error> {
         gfx_backend.set_ui_scale(next_config.ui_scale_percent as f32 / 100.0);
         patch_autoupdater.set_enabled(next_config.enable_patch_autoupdate);
         updater.set_enabled(next_config.enable_updater);
+        broadcast_server.set_enabled(next_config.enable_broadcast_api);
     });
 }
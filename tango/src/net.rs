@@ -1,8 +1,15 @@
+pub mod lan;
+#[cfg(test)]
+pub mod loopback;
 pub mod protocol;
+pub mod proxy;
 pub mod signaling;
 
 pub const PING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
+/// Round trips longer than this are never real latency, e.g. a pong that was held up by the system being suspended.
+pub const MAX_LATENCY_SAMPLE: std::time::Duration = std::time::Duration::from_secs(10);
+
 #[derive(Debug, thiserror::Error)]
 pub enum NegotiationError {
     #[error("expected hello")]
@@ -14,13 +21,24 @@ pub enum NegotiationError {
     #[error("remote protocol version too new")]
     RemoteProtocolVersionTooNew,
 
+    #[error("connected to self")]
+    ConnectedToSelf,
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
-pub async fn negotiate(sender: &mut Sender, receiver: &mut Receiver) -> Result<(), NegotiationError> {
+/// Exchanges hellos with the remote and checks that their protocol version matches ours.
+///
+/// Returns the client info and identity the remote reported, if it sent them.
+pub async fn negotiate(
+    sender: &mut Sender,
+    receiver: &mut Receiver,
+    installation_id: &str,
+) -> Result<(Option<protocol::ClientInfo>, Option<protocol::Identity>), NegotiationError> {
+    let local_identity = protocol::Identity::local(installation_id);
     sender
-        .send_hello()
+        .send_hello(local_identity.clone())
         .await
         .map_err(|e| NegotiationError::Other(e.into()))?;
 
@@ -39,54 +57,114 @@ pub async fn negotiate(sender: &mut Sender, receiver: &mut Receiver) -> Result<(
         return Err(NegotiationError::RemoteProtocolVersionTooNew);
     }
 
-    Ok(())
+    // Two instances started with the same config share an installation ID, which is enough to know it's the same person on both ends.
+    if let Some(remote_identity) = hello.identity.as_ref() {
+        if remote_identity.installation_id == local_identity.installation_id {
+            if remote_identity.instance_id == local_identity.instance_id {
+                log::warn!("remote is this instance of tango");
+            } else {
+                log::warn!("remote is another instance of tango with the same installation");
+            }
+            return Err(NegotiationError::ConnectedToSelf);
+        }
+    }
+
+    let client_info = hello.client_info.map(|client_info| client_info.sanitize());
+    if let Some(client_info) = client_info.as_ref() {
+        log::info!(
+            "remote is tango {} on {} ({})",
+            client_info.version,
+            client_info.platform,
+            if client_info.is_release { "release" } else { "dev" }
+        );
+    } else {
+        log::info!("remote didn't report its client info");
+    }
+
+    Ok((client_info, hello.identity))
+}
+
+/// Sends whole packets, already serialized, over whatever carries them to the remote. Packets must arrive reliably and in order.
+pub trait RawSender: Send {
+    fn send<'a>(&'a mut self, data: &'a [u8]) -> futures::future::BoxFuture<'a, std::io::Result<()>>;
+}
+
+/// Receives whole packets sent by a `RawSender` on the other end. Returns `None` once the connection is closed.
+pub trait RawReceiver: Send {
+    fn receive(&mut self) -> futures::future::BoxFuture<'_, Option<Vec<u8>>>;
+}
+
+impl RawSender for datachannel_wrapper::DataChannelSender {
+    fn send<'a>(&'a mut self, data: &'a [u8]) -> futures::future::BoxFuture<'a, std::io::Result<()>> {
+        Box::pin(async move {
+            match datachannel_wrapper::DataChannelSender::send(self, data).await {
+                Ok(()) => Ok(()),
+                Err(datachannel_wrapper::Error::Closed) => {
+                    Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "unexpected eof"))
+                }
+                Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e)),
+            }
+        })
+    }
+}
+
+impl RawReceiver for datachannel_wrapper::DataChannelReceiver {
+    fn receive(&mut self) -> futures::future::BoxFuture<'_, Option<Vec<u8>>> {
+        Box::pin(datachannel_wrapper::DataChannelReceiver::receive(self))
+    }
+}
+
+/// What keeps a connection to the remote open. Everything past setting it up only needs it kept alive for as long as the match goes on.
+pub enum Transport {
+    /// A WebRTC data channel, set up through the matchmaking server.
+    DataChannel(datachannel_wrapper::PeerConnection),
+    /// A TCP connection on the local network, made without the matchmaking server. The socket itself is owned by the sender and receiver.
+    Lan { is_host: bool },
+}
+
+impl Transport {
+    /// Whether we're the side that set up the connection. Exactly one side is, which is what decides e.g. who's player 1.
+    pub fn is_offerer(&self) -> bool {
+        match self {
+            Transport::DataChannel(peer_conn) => peer_conn
+                .local_description()
+                .map(|description| description.sdp_type == datachannel_wrapper::SdpType::Offer)
+                .unwrap_or(false),
+            Transport::Lan { is_host } => *is_host,
+        }
+    }
 }
 
 pub struct Sender {
-    dc_tx: datachannel_wrapper::DataChannelSender,
+    raw: Box<dyn RawSender>,
 }
 
 impl Sender {
-    pub fn new(dc_tx: datachannel_wrapper::DataChannelSender) -> Self {
-        Self { dc_tx }
+    pub fn new(raw: impl RawSender + 'static) -> Self {
+        Self { raw: Box::new(raw) }
     }
 
-    async fn send_packet(&mut self, p: &protocol::Packet) -> std::io::Result<()> {
-        match self.dc_tx.send(p.serialize().unwrap().as_slice()).await {
-            Ok(()) => Ok(()),
-            Err(datachannel_wrapper::Error::Closed) => {
-                return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "unexpected eof"));
-            }
-            Err(e) => {
-                return Err(std::io::Error::new(std::io::ErrorKind::Other, e));
-            }
-        }
+    pub async fn send_packet(&mut self, p: &protocol::Packet) -> std::io::Result<()> {
+        self.raw.send(p.serialize().unwrap().as_slice()).await
     }
 
-    pub async fn send_hello(&mut self) -> std::io::Result<()> {
+    pub async fn send_hello(&mut self, identity: protocol::Identity) -> std::io::Result<()> {
         self.send_packet(&protocol::Packet::Hello(protocol::Hello {
             protocol_version: protocol::VERSION,
+            client_info: Some(protocol::ClientInfo::local()),
+            identity: Some(identity),
         }))
         .await
     }
 
-    pub async fn send_ping(&mut self, ts: std::time::SystemTime) -> std::io::Result<()> {
+    pub async fn send_ping(&mut self, ts: u64) -> std::io::Result<()> {
         self.send_packet(&protocol::Packet::Ping(protocol::Ping { ts })).await
     }
 
-    pub async fn send_pong(&mut self, ts: std::time::SystemTime) -> std::io::Result<()> {
+    pub async fn send_pong(&mut self, ts: u64) -> std::io::Result<()> {
         self.send_packet(&protocol::Packet::Pong(protocol::Pong { ts })).await
     }
 
-    pub async fn send_settings(&mut self, settings: protocol::Settings) -> std::io::Result<()> {
-        self.send_packet(&protocol::Packet::Settings(settings)).await
-    }
-
-    pub async fn send_commit(&mut self, commitment: [u8; 16]) -> std::io::Result<()> {
-        self.send_packet(&protocol::Packet::Commit(protocol::Commit { commitment }))
-            .await
-    }
-
     pub async fn send_uncommit(&mut self) -> std::io::Result<()> {
         self.send_packet(&protocol::Packet::Uncommit(protocol::Uncommit {}))
             .await
@@ -117,20 +195,34 @@ impl Sender {
         }))
         .await
     }
+
+    pub async fn send_forfeit(&mut self) -> std::io::Result<()> {
+        self.send_packet(&protocol::Packet::Forfeit(protocol::Forfeit {}))
+            .await
+    }
+
+    pub async fn send_state_hash(&mut self, round_number: u8, tick: u32, hash: u32) -> std::io::Result<()> {
+        self.send_packet(&protocol::Packet::StateHash(protocol::StateHash {
+            round_number,
+            tick,
+            hash,
+        }))
+        .await
+    }
 }
 
 pub struct Receiver {
-    dc_rx: datachannel_wrapper::DataChannelReceiver,
+    raw: Box<dyn RawReceiver>,
 }
 
 impl Receiver {
-    pub fn new(dc_rx: datachannel_wrapper::DataChannelReceiver) -> Self {
-        Self { dc_rx }
+    pub fn new(raw: impl RawReceiver + 'static) -> Self {
+        Self { raw: Box::new(raw) }
     }
 
     pub async fn receive(&mut self) -> std::io::Result<protocol::Packet> {
         match protocol::Packet::deserialize(
-            match self.dc_rx.receive().await {
+            match self.raw.receive().await {
                 Some(d) => d,
                 None => {
                     return Err(std::io::Error::new(
@@ -141,6 +233,14 @@ impl Receiver {
             }
             .as_slice(),
         ) {
+            Ok(protocol::Packet::Settings(mut settings)) => {
+                settings.nickname = protocol::sanitize_nickname(&settings.nickname);
+                Ok(protocol::Packet::Settings(settings))
+            }
+            Ok(protocol::Packet::SettingsDelta(mut delta)) => {
+                delta.nickname = delta.nickname.map(|nickname| protocol::sanitize_nickname(&nickname));
+                Ok(protocol::Packet::SettingsDelta(delta))
+            }
             Ok(p) => Ok(p),
             Err(e) => {
                 return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e));
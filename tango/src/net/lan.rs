@@ -0,0 +1,216 @@
+//! Direct connections on the local network, for when the matchmaking server can't be reached, e.g. at an offline event.
+//!
+//! One side listens on a TCP port and the other connects to it by address. No signaling or ICE is involved: packets are framed over the socket as they come, each prefixed with its length.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::net;
+
+pub const DEFAULT_PORT: u16 = 12639;
+
+/// The largest packet we'll accept. Nothing we send comes close, so anything larger means the other end isn't tango.
+const MAX_PACKET_LENGTH: usize = 1024 * 1024;
+
+/// How long someone connecting to us has to say hello before we go back to waiting for someone else.
+const HELLO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Stands in for the link code of a LAN match, e.g. in replays, so they can be told apart from matchmaking link codes.
+const LINK_CODE_PREFIX: &str = "lan@";
+
+/// What's recorded as the link code of a match hosted at the given address.
+pub fn link_code(host_addr: &std::net::SocketAddr) -> String {
+    format!("{}{}", LINK_CODE_PREFIX, host_addr)
+}
+
+/// Whether a link code is one of a LAN match, which can't be used to reach the opponent through the matchmaking server.
+pub fn is_lan_link_code(link_code: &str) -> bool {
+    link_code.starts_with(LINK_CODE_PREFIX)
+}
+
+struct FramedSender(tokio::net::tcp::OwnedWriteHalf);
+
+impl net::RawSender for FramedSender {
+    fn send<'a>(&'a mut self, data: &'a [u8]) -> futures::future::BoxFuture<'a, std::io::Result<()>> {
+        Box::pin(async move {
+            self.0.write_u32_le(data.len() as u32).await?;
+            self.0.write_all(data).await?;
+            Ok(())
+        })
+    }
+}
+
+struct FramedReceiver(tokio::net::tcp::OwnedReadHalf);
+
+impl FramedReceiver {
+    async fn receive_frame(&mut self) -> std::io::Result<Vec<u8>> {
+        let len = self.0.read_u32_le().await? as usize;
+        if len > MAX_PACKET_LENGTH {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("packet too long: {} bytes", len),
+            ));
+        }
+        let mut buf = vec![0; len];
+        self.0.read_exact(&mut buf).await?;
+        Ok(buf)
+    }
+}
+
+impl net::RawReceiver for FramedReceiver {
+    fn receive(&mut self) -> futures::future::BoxFuture<'_, Option<Vec<u8>>> {
+        Box::pin(async move {
+            match self.receive_frame().await {
+                Ok(buf) => Some(buf),
+                Err(e) => {
+                    if e.kind() != std::io::ErrorKind::UnexpectedEof {
+                        log::warn!("lan connection closed: {:?}", e);
+                    }
+                    None
+                }
+            }
+        })
+    }
+}
+
+fn split(stream: tokio::net::TcpStream) -> std::io::Result<(net::Sender, net::Receiver)> {
+    // Inputs are tiny and go out every frame: waiting to batch them up only adds latency.
+    stream.set_nodelay(true)?;
+    let (rx, tx) = stream.into_split();
+    Ok((
+        net::Sender::new(FramedSender(tx)),
+        net::Receiver::new(FramedReceiver(rx)),
+    ))
+}
+
+/// Finds the addresses others on the local network can likely reach us at.
+///
+/// Nothing is sent: connecting a UDP socket only picks the interface that would be used. Private ranges are tried too, as a network with no way out to the internet has no default route.
+pub fn local_addresses(port: u16) -> Vec<std::net::SocketAddr> {
+    let mut addrs = vec![];
+    for target in [
+        "8.8.8.8:80",
+        "10.255.255.255:1",
+        "172.31.255.255:1",
+        "192.168.255.255:1",
+    ] {
+        let ip = match std::net::UdpSocket::bind("0.0.0.0:0")
+            .and_then(|socket| socket.connect(target).map(|()| socket))
+            .and_then(|socket| socket.local_addr())
+        {
+            Ok(addr) => addr.ip(),
+            Err(_) => {
+                continue;
+            }
+        };
+        if ip.is_loopback() || ip.is_unspecified() {
+            continue;
+        }
+        let addr = std::net::SocketAddr::new(ip, port);
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    addrs
+}
+
+/// A connection that got through negotiation: the connection, the address the opponent reached us at, and the client info and identity they sent.
+pub type Accepted = (
+    net::Sender,
+    net::Receiver,
+    std::net::SocketAddr,
+    (Option<net::protocol::ClientInfo>, Option<net::protocol::Identity>),
+);
+
+/// Waits for an opponent to connect to us.
+pub struct Listener {
+    listener: tokio::net::TcpListener,
+}
+
+impl Listener {
+    pub async fn bind(port: u16) -> std::io::Result<Self> {
+        Ok(Self {
+            listener: tokio::net::TcpListener::bind(("0.0.0.0", port)).await?,
+        })
+    }
+
+    /// The port actually listened on, which is only different from the one asked for if that was 0.
+    pub fn port(&self) -> std::io::Result<u16> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    /// Accepts connections until one gets through negotiation, so that anything else on the network connecting to the port, or an opponent on an incompatible version, doesn't stop us hosting. The one connecting finds out why it was turned away from its own end of the negotiation.
+    pub async fn accept(self, installation_id: &str) -> std::io::Result<Accepted> {
+        loop {
+            let (stream, remote_addr) = self.listener.accept().await?;
+            log::info!("accepted lan connection from {}", remote_addr);
+            match Self::negotiate(stream, installation_id).await {
+                Ok(accepted) => {
+                    return Ok(accepted);
+                }
+                Err(e) => {
+                    log::warn!("lan connection from {} failed, still listening: {:?}", remote_addr, e);
+                }
+            }
+        }
+    }
+
+    async fn negotiate(stream: tokio::net::TcpStream, installation_id: &str) -> anyhow::Result<Accepted> {
+        let local_addr = stream.local_addr()?;
+        let (mut sender, mut receiver) = split(stream)?;
+        let negotiated = tokio::time::timeout(
+            HELLO_TIMEOUT,
+            net::negotiate(&mut sender, &mut receiver, installation_id),
+        )
+        .await??;
+        Ok((sender, receiver, local_addr, negotiated))
+    }
+}
+
+/// Connects to an opponent hosting at the given address, e.g. `192.168.1.23:12639`. The default port is used if none is given.
+pub async fn connect(addr: &str) -> std::io::Result<(net::Sender, net::Receiver, std::net::SocketAddr)> {
+    let addr = addr.trim();
+    // A bare IPv6 address has colons in it too, but no port.
+    let stream = if addr.parse::<std::net::IpAddr>().is_ok() || !addr.contains(':') {
+        tokio::net::TcpStream::connect((addr, DEFAULT_PORT)).await?
+    } else {
+        tokio::net::TcpStream::connect(addr).await?
+    };
+    let remote_addr = stream.peer_addr()?;
+    log::info!("connected to lan host at {}", remote_addr);
+    let (sender, receiver) = split(stream)?;
+    Ok((sender, receiver, remote_addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_accept_keeps_listening_past_failed_connections() {
+        let listener = Listener::bind(0).await.unwrap();
+        let port = listener.port().unwrap();
+        let host = tokio::task::spawn(async move { listener.accept("host").await });
+
+        // Something that isn't tango.
+        let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream.write_u32_le(4).await.unwrap();
+        stream.write_all(b"GET ").await.unwrap();
+        drop(stream);
+
+        // Something with our installation ID, which negotiation turns away as being ourselves.
+        let (mut sender, mut receiver, _) = connect(&format!("127.0.0.1:{}", port)).await.unwrap();
+        assert!(matches!(
+            net::negotiate(&mut sender, &mut receiver, "host").await,
+            Err(net::NegotiationError::ConnectedToSelf)
+        ));
+        drop((sender, receiver));
+
+        let (mut sender, mut receiver, _) = connect(&format!("127.0.0.1:{}", port)).await.unwrap();
+        let (_, identity) = net::negotiate(&mut sender, &mut receiver, "opponent").await.unwrap();
+        assert_eq!(identity.unwrap().installation_id, "host");
+
+        let (_, _, local_addr, (_, identity)) = host.await.unwrap().unwrap();
+        assert_eq!(local_addr.port(), port);
+        assert_eq!(identity.unwrap().installation_id, "opponent");
+    }
+}
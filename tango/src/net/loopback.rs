@@ -0,0 +1,177 @@
+//! Links within the same process, for running both sides of a match without a network in between.
+
+use rand::{Rng, SeedableRng};
+
+use crate::net;
+
+/// The sending end of a link made with `pipe` or `lossy_pipe`.
+pub struct LoopbackSender(tokio::sync::mpsc::UnboundedSender<Vec<u8>>);
+
+impl net::RawSender for LoopbackSender {
+    fn send<'a>(&'a mut self, data: &'a [u8]) -> futures::future::BoxFuture<'a, std::io::Result<()>> {
+        Box::pin(async move {
+            self.0
+                .send(data.to_vec())
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "unexpected eof"))
+        })
+    }
+}
+
+/// The receiving end of a link made with `pipe` or `lossy_pipe`.
+pub struct LoopbackReceiver(tokio::sync::mpsc::UnboundedReceiver<Vec<u8>>);
+
+impl net::RawReceiver for LoopbackReceiver {
+    fn receive(&mut self) -> futures::future::BoxFuture<'_, Option<Vec<u8>>> {
+        Box::pin(self.0.recv())
+    }
+}
+
+/// Makes a link that carries packets one way, delivering each as soon as it's sent.
+pub fn pipe() -> (LoopbackSender, LoopbackReceiver) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    (LoopbackSender(tx), LoopbackReceiver(rx))
+}
+
+/// What the network between the ends of a link is like.
+#[derive(Clone, Copy, Debug)]
+pub struct Conditions {
+    /// How long a packet takes to get across, at best.
+    pub delay: std::time::Duration,
+    /// How much longer than that it may take, picked at random for each packet. Packets that take longer than the ones sent after them arrive out of order.
+    pub jitter: std::time::Duration,
+    /// The chance of a packet being lost, each time it's sent.
+    pub loss: f64,
+}
+
+impl Conditions {
+    /// How long a packet takes to be delivered, including resending it for as many times as it's lost. Each loss is noticed a round trip after sending.
+    fn delivery_time(&self, rng: &mut impl Rng) -> std::time::Duration {
+        let mut delivery_time = std::time::Duration::ZERO;
+        while rng.gen_bool(self.loss) {
+            delivery_time += self.delay * 2;
+        }
+        delivery_time + self.delay + self.jitter.mul_f64(rng.gen::<f64>())
+    }
+}
+
+/// Makes a link that carries packets one way over a simulated network, picking how each packet fares from `seed`.
+///
+/// Like a data channel, the link itself is reliable and ordered: lost packets are resent, and packets that arrive early are held back until the ones before them are in. So loss and reordering show up as packets taking longer, and holding up the ones behind them.
+pub fn lossy_pipe(conditions: Conditions, seed: u64) -> (LoopbackSender, LoopbackReceiver) {
+    let (tx, mut network_rx) = tokio::sync::mpsc::unbounded_channel::<Vec<u8>>();
+    let (network_tx, mut delivery_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (delivery_tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+    tokio::task::spawn(async move {
+        let mut rng = rand_pcg::Mcg128Xsl64::seed_from_u64(seed);
+        let mut last_delivery = tokio::time::Instant::now();
+        while let Some(packet) = network_rx.recv().await {
+            let delivery = std::cmp::max(
+                tokio::time::Instant::now() + conditions.delivery_time(&mut rng),
+                last_delivery,
+            );
+            last_delivery = delivery;
+            if network_tx.send((delivery, packet)).is_err() {
+                break;
+            }
+        }
+    });
+
+    tokio::task::spawn(async move {
+        while let Some((delivery, packet)) = delivery_rx.recv().await {
+            tokio::time::sleep_until(delivery).await;
+            if delivery_tx.send(packet).is_err() {
+                break;
+            }
+        }
+    });
+
+    (LoopbackSender(tx), LoopbackReceiver(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use net::{RawReceiver, RawSender};
+
+    const CONDITIONS: Conditions = Conditions {
+        delay: std::time::Duration::from_millis(1),
+        jitter: std::time::Duration::from_millis(5),
+        loss: 0.3,
+    };
+
+    fn delivery_times(conditions: Conditions, seed: u64) -> Vec<std::time::Duration> {
+        let mut rng = rand_pcg::Mcg128Xsl64::seed_from_u64(seed);
+        (0..100).map(|_| conditions.delivery_time(&mut rng)).collect()
+    }
+
+    #[test]
+    fn test_delivery_time_repeats_for_a_seed() {
+        assert_eq!(delivery_times(CONDITIONS, 0), delivery_times(CONDITIONS, 0));
+        assert_ne!(delivery_times(CONDITIONS, 0), delivery_times(CONDITIONS, 1));
+    }
+
+    #[test]
+    fn test_delivery_time_only_goes_past_jitter_when_lost() {
+        let lossless = Conditions {
+            loss: 0.0,
+            ..CONDITIONS
+        };
+        for delivery_time in delivery_times(lossless, 0) {
+            assert!(delivery_time >= lossless.delay && delivery_time <= lossless.delay + lossless.jitter);
+        }
+
+        let lossy = delivery_times(CONDITIONS, 0);
+        assert!(lossy.iter().all(|t| *t >= CONDITIONS.delay));
+        assert!(lossy.iter().any(|t| *t > CONDITIONS.delay + CONDITIONS.jitter));
+    }
+
+    #[tokio::test]
+    async fn test_pipe_ends_when_sender_is_dropped() {
+        let (mut tx, mut rx) = pipe();
+        tx.send(b"hello").await.unwrap();
+        drop(tx);
+        assert_eq!(rx.receive().await.unwrap(), b"hello");
+        assert_eq!(rx.receive().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_over_lossy_pipe() {
+        let (host_tx, opponent_rx) = lossy_pipe(CONDITIONS, 0);
+        let (opponent_tx, host_rx) = lossy_pipe(CONDITIONS, 1);
+        let mut host = (net::Sender::new(host_tx), net::Receiver::new(host_rx));
+        let mut opponent = (net::Sender::new(opponent_tx), net::Receiver::new(opponent_rx));
+
+        let (host_negotiated, opponent_negotiated) = tokio::join!(
+            net::negotiate(&mut host.0, &mut host.1, "host"),
+            net::negotiate(&mut opponent.0, &mut opponent.1, "opponent"),
+        );
+        assert_eq!(host_negotiated.unwrap().1.unwrap().installation_id, "opponent");
+        assert_eq!(opponent_negotiated.unwrap().1.unwrap().installation_id, "host");
+
+        for tick in 0..100 {
+            host.0.send_input(1, tick, 0, tick as u16).await.unwrap();
+        }
+        for tick in 0..100 {
+            match opponent.1.receive().await.unwrap() {
+                net::protocol::Packet::Input(input) => {
+                    assert_eq!((input.local_tick, input.joyflags), (tick, tick as u16));
+                }
+                p => panic!("expected input, got {:?}", p),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lossy_pipe_delivers_in_order() {
+        let (mut tx, mut rx) = lossy_pipe(CONDITIONS, 0);
+        for i in 0..200u32 {
+            tx.send(&i.to_le_bytes()).await.unwrap();
+        }
+        drop(tx);
+        for i in 0..200u32 {
+            assert_eq!(rx.receive().await.unwrap(), i.to_le_bytes());
+        }
+        assert_eq!(rx.receive().await, None);
+    }
+}
@@ -1,6 +1,11 @@
+use std::io::{Read, Write};
+
 use bincode::Options;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{contentpolicy, determinism, version};
 
-pub const VERSION: u8 = 0x30;
+pub const VERSION: u8 = 0x3d;
 
 lazy_static! {
     static ref BINCODE_OPTIONS: bincode::config::WithOtherLimit<
@@ -9,8 +14,12 @@ lazy_static! {
     > = bincode::DefaultOptions::new()
         .with_varint_encoding()
         .with_limit(64 * 1024);
-    static ref STATE_BINCODE_OPTIONS: bincode::config::WithOtherIntEncoding<bincode::config::DefaultOptions, bincode::config::VarintEncoding> =
-        bincode::DefaultOptions::new().with_varint_encoding();
+    static ref STATE_BINCODE_OPTIONS: bincode::config::WithOtherLimit<
+        bincode::config::WithOtherIntEncoding<bincode::config::DefaultOptions, bincode::config::VarintEncoding>,
+        bincode::config::Bounded,
+    > = bincode::DefaultOptions::new()
+        .with_varint_encoding()
+        .with_limit(MAX_NEGOTIATED_STATE_SIZE);
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -24,6 +33,8 @@ pub enum Packet {
 
     // Lobby.
     Settings(Settings),
+    SettingsDelta(SettingsDelta),
+    ResendSettings(ResendSettings),
     Commit(Commit),
     Uncommit(Uncommit),
     Chunk(Chunk),
@@ -31,6 +42,8 @@ pub enum Packet {
 
     // In match.
     Input(Input),
+    StateHash(StateHash),
+    Forfeit(Forfeit),
 }
 
 impl Packet {
@@ -39,13 +52,120 @@ impl Packet {
     }
 
     pub fn deserialize(d: &[u8]) -> bincode::Result<Self> {
-        BINCODE_OPTIONS.deserialize(d)
+        match BINCODE_OPTIONS.deserialize(d) {
+            Ok(p) => Ok(p),
+            Err(e) => {
+                // Hellos from older clients don't carry everything ours do, but we still want to tell them their protocol version is wrong.
+                if let Ok(IdentitylessPacket::Hello(hello)) = BINCODE_OPTIONS.deserialize(d) {
+                    return Ok(Packet::Hello(Hello {
+                        protocol_version: hello.protocol_version,
+                        client_info: hello.client_info,
+                        identity: None,
+                    }));
+                }
+                if let Ok(LegacyPacket::Hello(hello)) = BINCODE_OPTIONS.deserialize(d) {
+                    return Ok(Packet::Hello(Hello {
+                        protocol_version: hello.protocol_version,
+                        client_info: None,
+                        identity: None,
+                    }));
+                }
+                Err(e)
+            }
+        }
     }
 }
 
+/// The packets older clients may send before the protocol version is checked.
+#[derive(serde::Deserialize)]
+enum LegacyPacket {
+    Hello(LegacyHello),
+}
+
+#[derive(serde::Deserialize)]
+struct LegacyHello {
+    protocol_version: u8,
+}
+
+/// The packets clients that report client info but not their identity may send before the protocol version is checked.
+#[derive(serde::Deserialize)]
+enum IdentitylessPacket {
+    Hello(IdentitylessHello),
+}
+
+#[derive(serde::Deserialize)]
+struct IdentitylessHello {
+    protocol_version: u8,
+    client_info: Option<ClientInfo>,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct Hello {
     pub protocol_version: u8,
+    /// Only set by clients new enough to send it.
+    pub client_info: Option<ClientInfo>,
+    /// Only set by clients new enough to send it.
+    pub identity: Option<Identity>,
+}
+
+/// Identifies the Tango a hello came from, so we can tell if we've connected to ourselves and recognize opponents we've played before.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+    /// Stays the same for as long as the config does, so it's shared by every instance started with it.
+    pub installation_id: String,
+    /// Picked at random every time Tango starts.
+    pub instance_id: u64,
+}
+
+lazy_static! {
+    static ref INSTANCE_ID: u64 = rand::random();
+}
+
+impl Identity {
+    pub fn local(installation_id: &str) -> Self {
+        Self {
+            installation_id: installation_id.to_string(),
+            instance_id: *INSTANCE_ID,
+        }
+    }
+}
+
+/// What a client reports about itself, to help with troubleshooting. This is purely informational and never used to decide compatibility.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub version: String,
+    pub platform: String,
+    pub is_release: bool,
+}
+
+/// The longest client info field we accept, in characters.
+const MAX_CLIENT_INFO_FIELD_LENGTH: usize = 64;
+
+impl ClientInfo {
+    pub fn local() -> Self {
+        Self {
+            version: version::VERSION.to_string(),
+            platform: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+            is_release: !cfg!(debug_assertions),
+        }
+    }
+
+    /// Cleans up client info received from a peer, the same way as nicknames.
+    pub fn sanitize(&self) -> Self {
+        let sanitize_field = |s: &str| {
+            s.chars()
+                .filter(|c| !c.is_control() && !is_bidi_control(*c))
+                .take(MAX_CLIENT_INFO_FIELD_LENGTH)
+                .collect::<String>()
+                .trim()
+                .to_string()
+        };
+        Self {
+            version: sanitize_field(&self.version),
+            platform: sanitize_field(&self.platform),
+            is_release: self.is_release,
+        }
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -63,24 +183,30 @@ pub struct Chunk {
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct Ping {
-    pub ts: std::time::SystemTime,
+    /// An opaque timestamp from the sender's monotonic clock, to be echoed back in the pong.
+    pub ts: u64,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct Pong {
-    pub ts: std::time::SystemTime,
+    /// The timestamp from the ping being answered.
+    pub ts: u64,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
 pub struct PatchInfo {
     pub name: String,
     pub version: semver::Version,
+    /// The patches applied before this one, in order, as the sender resolved them. Both sides must apply the same ones.
+    pub requirements: Vec<(String, semver::Version)>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct GameInfo {
     pub family_and_variant: (String, u8),
     pub patch: Option<PatchInfo>,
+    /// The hash of the ROM as the sender patched it, so the other side can check theirs comes out the same.
+    pub rom_hash: Option<[u8; 16]>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
@@ -91,6 +217,181 @@ pub struct Settings {
     pub available_games: Vec<(String, u8)>,
     pub available_patches: Vec<(String, Vec<semver::Version>)>,
     pub reveal_setup: bool,
+    pub fingerprint: Option<determinism::Fingerprint>,
+    /// How long each round may last before it's ended on HP, if it's limited.
+    pub round_time_limit_secs: Option<u32>,
+    /// The `game::Capabilities` bits this build supports for the selected game.
+    pub capabilities: u32,
+    /// The input delay this side plays with, before any handicap.
+    pub input_delay: u32,
+    /// Extra input delay this side takes on top of `input_delay`, so the other side doesn't have to.
+    pub input_delay_handicap: u32,
+    pub replay_recording: ReplayRecording,
+    /// Bumped every time this side's settings change, so a delta can be checked against the settings it was made from.
+    pub seq: u32,
+    /// Whether this side can take settings deltas instead of full settings.
+    pub accepts_deltas: bool,
+}
+
+/// Whether a side is willing to have the match recorded as replays. If either side says never, neither side records.
+///
+/// This can't be enforced: it only tells an honest client what the other side wants.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayRecording {
+    Always,
+    /// Asked before every match, which turns it into one of the others before committing.
+    Ask,
+    Never,
+}
+
+impl Default for ReplayRecording {
+    fn default() -> Self {
+        Self::Always
+    }
+}
+
+impl Settings {
+    /// The input delay this side actually plays with.
+    pub fn total_input_delay(&self) -> u32 {
+        self.input_delay + self.input_delay_handicap
+    }
+}
+
+/// The fields of a side's settings that changed since the ones numbered `base_seq`, so that small changes in the lobby don't resend e.g. every available patch. Fields left as None didn't change.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct SettingsDelta {
+    pub base_seq: u32,
+    pub seq: u32,
+    pub nickname: Option<String>,
+    pub match_type: Option<(u8, u8)>,
+    pub game_info: Option<Option<GameInfo>>,
+    pub available_games: Option<Vec<(String, u8)>>,
+    pub available_patches: Option<Vec<(String, Vec<semver::Version>)>>,
+    pub reveal_setup: Option<bool>,
+    pub fingerprint: Option<Option<determinism::Fingerprint>>,
+    pub round_time_limit_secs: Option<Option<u32>>,
+    pub capabilities: Option<u32>,
+    pub input_delay: Option<u32>,
+    pub input_delay_handicap: Option<u32>,
+    pub replay_recording: Option<ReplayRecording>,
+}
+
+/// A delta was made from settings other than the ones we have, so whatever we have can't be trusted.
+#[derive(Debug, thiserror::Error)]
+#[error("settings delta is based on settings {base_seq}, but we have settings {seq}")]
+pub struct SettingsOutOfSync {
+    pub base_seq: u32,
+    pub seq: u32,
+}
+
+impl SettingsDelta {
+    /// The delta that turns `old` into `new`.
+    pub fn between(old: &Settings, new: &Settings) -> Self {
+        fn changed<T: Clone + PartialEq>(old: &T, new: &T) -> Option<T> {
+            if old != new {
+                Some(new.clone())
+            } else {
+                None
+            }
+        }
+        Self {
+            base_seq: old.seq,
+            seq: new.seq,
+            nickname: changed(&old.nickname, &new.nickname),
+            match_type: changed(&old.match_type, &new.match_type),
+            game_info: changed(&old.game_info, &new.game_info),
+            available_games: changed(&old.available_games, &new.available_games),
+            available_patches: changed(&old.available_patches, &new.available_patches),
+            reveal_setup: changed(&old.reveal_setup, &new.reveal_setup),
+            fingerprint: changed(&old.fingerprint, &new.fingerprint),
+            round_time_limit_secs: changed(&old.round_time_limit_secs, &new.round_time_limit_secs),
+            capabilities: changed(&old.capabilities, &new.capabilities),
+            input_delay: changed(&old.input_delay, &new.input_delay),
+            input_delay_handicap: changed(&old.input_delay_handicap, &new.input_delay_handicap),
+            replay_recording: changed(&old.replay_recording, &new.replay_recording),
+        }
+    }
+
+    /// Merges the delta into the settings it was made from. Nothing is changed if they aren't the ones it was made from.
+    pub fn apply(self, settings: &mut Settings) -> Result<(), SettingsOutOfSync> {
+        if self.base_seq != settings.seq {
+            return Err(SettingsOutOfSync {
+                base_seq: self.base_seq,
+                seq: settings.seq,
+            });
+        }
+        settings.seq = self.seq;
+        if let Some(nickname) = self.nickname {
+            settings.nickname = nickname;
+        }
+        if let Some(match_type) = self.match_type {
+            settings.match_type = match_type;
+        }
+        if let Some(game_info) = self.game_info {
+            settings.game_info = game_info;
+        }
+        if let Some(available_games) = self.available_games {
+            settings.available_games = available_games;
+        }
+        if let Some(available_patches) = self.available_patches {
+            settings.available_patches = available_patches;
+        }
+        if let Some(reveal_setup) = self.reveal_setup {
+            settings.reveal_setup = reveal_setup;
+        }
+        if let Some(fingerprint) = self.fingerprint {
+            settings.fingerprint = fingerprint;
+        }
+        if let Some(round_time_limit_secs) = self.round_time_limit_secs {
+            settings.round_time_limit_secs = round_time_limit_secs;
+        }
+        if let Some(capabilities) = self.capabilities {
+            settings.capabilities = capabilities;
+        }
+        if let Some(input_delay) = self.input_delay {
+            settings.input_delay = input_delay;
+        }
+        if let Some(input_delay_handicap) = self.input_delay_handicap {
+            settings.input_delay_handicap = input_delay_handicap;
+        }
+        if let Some(replay_recording) = self.replay_recording {
+            settings.replay_recording = replay_recording;
+        }
+        Ok(())
+    }
+}
+
+/// Asks the other side to send its full settings again, after a delta didn't apply.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct ResendSettings {}
+
+/// The most input delay either side may play with before any handicap.
+pub const MAX_INPUT_DELAY: u32 = 10;
+
+/// The most extra input delay either side may take on as a handicap.
+pub const MAX_INPUT_DELAY_HANDICAP: u32 = 10;
+
+/// The longest nickname we accept, in characters.
+pub const MAX_NICKNAME_LENGTH: usize = 20;
+
+fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+/// Cleans up a nickname received from a peer, so it can't spoof other UI by e.g. reordering text or spanning multiple lines. Nicknames longer than the content policy allows are cut short, counting what reads as one character as one even if it's made of several, e.g. an emoji with a skin tone.
+pub fn sanitize_nickname(nickname: &str) -> String {
+    nickname
+        .chars()
+        .filter(|c| !c.is_control() && !is_bidi_control(*c))
+        .collect::<String>()
+        .graphemes(true)
+        .take(contentpolicy::max_nickname_length())
+        .collect::<String>()
+        .trim()
+        .to_string()
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -101,21 +402,298 @@ pub struct Input {
     pub joyflags: u16,
 }
 
+/// A checksum of the battle state at a committed tick, used to detect desyncs.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct StateHash {
+    pub round_number: u8,
+    pub tick: u32,
+    pub hash: u32,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct StartMatch {}
 
+/// Sent when giving up on a match partway through, so the opponent can tell it apart from the connection dropping.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Forfeit {}
+
+/// The negotiated state is sent in this many chunks, padded out with empty ones, so how large it is doesn't show.
+pub const NEGOTIATED_STATE_CHUNKS: usize = 5;
+pub const NEGOTIATED_STATE_CHUNK_SIZE: usize = 32 * 1024;
+
+/// The largest save any GBA game can have, which is 1 Mbit of flash.
+pub const MAX_SAVE_SIZE: usize = 128 * 1024;
+
+/// How large a negotiated state may be once decompressed: a save of the largest size, and some room for the rest.
+const MAX_NEGOTIATED_STATE_SIZE: u64 = MAX_SAVE_SIZE as u64 + 1024;
+
+/// How large a window zstd may need to decompress the negotiated state, as a power of two. The whole state fits in it, so a compressor never needs more.
+const NEGOTIATED_STATE_WINDOW_LOG_MAX: u32 = 18;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidNegotiatedState {
+    #[error("compressed state is {0} bytes, more than can be sent")]
+    CompressedTooLarge(usize),
+
+    #[error("state decompresses to more than {} bytes", MAX_NEGOTIATED_STATE_SIZE)]
+    TooLarge,
+
+    #[error("failed to decompress state: {0}")]
+    Decompress(std::io::Error),
+
+    #[error("failed to deserialize state: {0}")]
+    Deserialize(bincode::Error),
+
+    #[error("save is {0} bytes, more than any game's")]
+    SaveTooLarge(usize),
+}
+
+/// Compresses a serialized negotiated state.
+///
+/// The stream doesn't say how large it is up front, so the window has to be limited here: otherwise it's the compression level's default, more than the opponent will decompress with.
+fn compress_negotiated_state(raw: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = zstd::stream::write::Encoder::new(vec![], 0)?;
+    encoder.window_log(NEGOTIATED_STATE_WINDOW_LOG_MAX)?;
+    encoder.write_all(raw)?;
+    encoder.finish()
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct NegotiatedState {
+    /// Being an array, this is always exactly this long: anything else fails to deserialize.
     pub nonce: [u8; 16],
     pub save_data: Vec<u8>,
 }
 
 impl NegotiatedState {
-    pub fn serialize(&self) -> bincode::Result<Vec<u8>> {
-        STATE_BINCODE_OPTIONS.serialize(self)
+    /// Serializes and compresses the state, ready to be committed to and sent in chunks.
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(compress_negotiated_state(&STATE_BINCODE_OPTIONS.serialize(self)?)?)
     }
 
-    pub fn deserialize(d: &[u8]) -> bincode::Result<Self> {
-        STATE_BINCODE_OPTIONS.deserialize(d)
+    /// Decompresses and deserializes the state the opponent sent.
+    ///
+    /// The commitment only proves the opponent didn't change this after committing to it, not that it's safe to decode: nothing in here can be trusted to be reasonably sized until it's been checked.
+    pub fn decode(compressed: &[u8]) -> Result<Self, InvalidNegotiatedState> {
+        if compressed.len() > NEGOTIATED_STATE_CHUNKS * NEGOTIATED_STATE_CHUNK_SIZE {
+            return Err(InvalidNegotiatedState::CompressedTooLarge(compressed.len()));
+        }
+
+        let mut decoder = zstd::stream::read::Decoder::new(compressed).map_err(InvalidNegotiatedState::Decompress)?;
+        decoder
+            .window_log_max(NEGOTIATED_STATE_WINDOW_LOG_MAX)
+            .map_err(InvalidNegotiatedState::Decompress)?;
+
+        // Read one byte past the limit, so a state that's too large can be told apart from one that's exactly at it.
+        let mut raw = vec![];
+        decoder
+            .take(MAX_NEGOTIATED_STATE_SIZE + 1)
+            .read_to_end(&mut raw)
+            .map_err(InvalidNegotiatedState::Decompress)?;
+        if raw.len() as u64 > MAX_NEGOTIATED_STATE_SIZE {
+            return Err(InvalidNegotiatedState::TooLarge);
+        }
+
+        let state: Self = STATE_BINCODE_OPTIONS
+            .deserialize(&raw)
+            .map_err(InvalidNegotiatedState::Deserialize)?;
+        if state.save_data.len() > MAX_SAVE_SIZE {
+            return Err(InvalidNegotiatedState::SaveTooLarge(state.save_data.len()));
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes that don't compress, so sizes before and after compression are about the same.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545f491u32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn compress(raw: &[u8]) -> Vec<u8> {
+        compress_negotiated_state(raw).unwrap()
+    }
+
+    /// Settings as a player with a sizable patch library sends them.
+    fn lobby_settings() -> Settings {
+        Settings {
+            nickname: "player".to_string(),
+            match_type: (1, 0),
+            game_info: Some(GameInfo {
+                family_and_variant: ("bn6".to_string(), 0),
+                patch: None,
+                rom_hash: Some([0xab; 16]),
+            }),
+            available_games: ["bn1", "bn2", "bn3", "bn4", "bn5", "bn6"]
+                .iter()
+                .flat_map(|family| (0..2).map(move |variant| (family.to_string(), variant)))
+                .collect(),
+            available_patches: (0..100)
+                .map(|i| {
+                    (
+                        format!("some-patch-{}", i),
+                        (0..3).map(|minor| semver::Version::new(1, minor, 0)).collect(),
+                    )
+                })
+                .collect(),
+            accepts_deltas: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_settings_delta_size() {
+        let old = lobby_settings();
+        let mut new = old.clone();
+        new.reveal_setup = !new.reveal_setup;
+        new.seq += 1;
+
+        let full = Packet::Settings(new.clone()).serialize().unwrap();
+        let delta = Packet::SettingsDelta(SettingsDelta::between(&old, &new))
+            .serialize()
+            .unwrap();
+        eprintln!(
+            "toggling reveal setup: {} bytes as full settings, {} bytes as a delta",
+            full.len(),
+            delta.len()
+        );
+        assert!(delta.len() * 50 < full.len());
+
+        let mut applied = old;
+        match Packet::deserialize(&delta).unwrap() {
+            Packet::SettingsDelta(delta) => delta.apply(&mut applied).unwrap(),
+            p => panic!("unexpected packet: {:?}", p),
+        }
+        assert_eq!(applied.reveal_setup, new.reveal_setup);
+        assert_eq!(applied.seq, new.seq);
+        assert_eq!(applied.available_patches, new.available_patches);
+    }
+
+    #[test]
+    fn test_sanitize_nickname() {
+        assert_eq!(sanitize_nickname(" a\u{202e}b\nc "), "abc");
+        let max = contentpolicy::max_nickname_length();
+        assert_eq!(sanitize_nickname(&"a".repeat(max + 1)), "a".repeat(max));
+        // A thumbs up with a skin tone is two code points, but one character.
+        let thumbs_up = "\u{1f44d}\u{1f3fd}";
+        assert_eq!(sanitize_nickname(&thumbs_up.repeat(max + 1)), thumbs_up.repeat(max));
+        assert_eq!(
+            sanitize_nickname(&format!("{}{}", "a".repeat(max - 1), thumbs_up)),
+            format!("{}{}", "a".repeat(max - 1), thumbs_up)
+        );
+    }
+
+    #[test]
+    fn test_negotiated_state_round_trip() {
+        for save_data in [vec![], noise(1024), noise(MAX_SAVE_SIZE)] {
+            let state = NegotiatedState {
+                nonce: [0xab; 16],
+                save_data,
+            };
+            let decoded = NegotiatedState::decode(&state.encode().unwrap()).unwrap();
+            assert_eq!(decoded.nonce, state.nonce);
+            assert_eq!(decoded.save_data, state.save_data);
+        }
+    }
+
+    #[test]
+    fn test_negotiated_state_zstd_bomb() {
+        // Zeros compress down to almost nothing, so this fits in a single chunk.
+        let compressed = compress(&vec![0; 64 * 1024 * 1024]);
+        assert!(compressed.len() < NEGOTIATED_STATE_CHUNK_SIZE);
+        assert!(matches!(
+            NegotiatedState::decode(&compressed),
+            Err(InvalidNegotiatedState::TooLarge)
+        ));
+
+        // Just past the limit is still too large.
+        let compressed = compress(&vec![0; MAX_NEGOTIATED_STATE_SIZE as usize + 1]);
+        assert!(matches!(
+            NegotiatedState::decode(&compressed),
+            Err(InvalidNegotiatedState::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_negotiated_state_large_window() {
+        let mut encoder = zstd::stream::write::Encoder::new(vec![], 0).unwrap();
+        encoder.window_log(NEGOTIATED_STATE_WINDOW_LOG_MAX + 4).unwrap();
+        encoder.write_all(&noise(1024)).unwrap();
+        assert!(matches!(
+            NegotiatedState::decode(&encoder.finish().unwrap()),
+            Err(InvalidNegotiatedState::Decompress(_))
+        ));
+    }
+
+    #[test]
+    fn test_negotiated_state_compressed_too_large() {
+        let compressed = noise(NEGOTIATED_STATE_CHUNKS * NEGOTIATED_STATE_CHUNK_SIZE + 1);
+        assert!(matches!(
+            NegotiatedState::decode(&compressed),
+            Err(InvalidNegotiatedState::CompressedTooLarge(len)) if len == compressed.len()
+        ));
+    }
+
+    #[test]
+    fn test_negotiated_state_oversized_save() {
+        let state = NegotiatedState {
+            nonce: [0; 16],
+            save_data: noise(MAX_SAVE_SIZE + 1),
+        };
+        assert!(matches!(
+            NegotiatedState::decode(&state.encode().unwrap()),
+            Err(InvalidNegotiatedState::SaveTooLarge(len)) if len == MAX_SAVE_SIZE + 1
+        ));
+    }
+
+    #[test]
+    fn test_negotiated_state_save_length_lies() {
+        // A save length far longer than what follows it, which mustn't be allocated up front.
+        let mut raw = vec![0; 16];
+        raw.push(0xfd);
+        raw.extend_from_slice(&u64::MAX.to_le_bytes());
+        raw.extend_from_slice(&noise(16));
+        assert!(matches!(
+            NegotiatedState::decode(&compress(&raw)),
+            Err(InvalidNegotiatedState::Deserialize(_))
+        ));
+    }
+
+    #[test]
+    fn test_negotiated_state_truncated() {
+        let state = NegotiatedState {
+            nonce: [0xab; 16],
+            save_data: noise(256),
+        };
+        let compressed = state.encode().unwrap();
+        for len in 0..compressed.len() {
+            assert!(NegotiatedState::decode(&compressed[..len]).is_err(), "{}", len);
+        }
+
+        let raw = STATE_BINCODE_OPTIONS.serialize(&state).unwrap();
+        for len in 0..raw.len() {
+            assert!(matches!(
+                NegotiatedState::decode(&compress(&raw[..len])),
+                Err(InvalidNegotiatedState::Deserialize(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_negotiated_state_garbage() {
+        for len in [0, 1, 16, 1024] {
+            assert!(NegotiatedState::decode(&noise(len)).is_err());
+        }
     }
 }
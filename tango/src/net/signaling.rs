@@ -3,7 +3,7 @@ use futures_util::TryStreamExt;
 use prost::Message;
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 
-use crate::version;
+use crate::{net, version};
 
 async fn create_data_channel(
     ice_servers: &[String],
@@ -51,7 +51,47 @@ pub struct PendingConnection {
     peer_conn: datachannel_wrapper::PeerConnection,
 }
 
-pub async fn open(addr: &str, session_id: &str) -> Result<PendingConnection, anyhow::Error> {
+/// Formats an ICE server's URLs for libdatachannel, which wants the credentials inline.
+fn ice_server_urls(urls: &[String], username: Option<&str>, credential: Option<&str>) -> Vec<String> {
+    urls.iter()
+        .flat_map(|url| {
+            let colon_idx = if let Some(colon_idx) = url.chars().position(|c| c == ':') {
+                colon_idx
+            } else {
+                return vec![];
+            };
+
+            let proto = &url[..colon_idx];
+            let rest = &url[colon_idx + 1..];
+
+            // libdatachannel doesn't support TURN over TCP: in fact, it explodes!
+            if url.chars().skip_while(|c| *c != '?').collect::<String>() == "?transport=tcp" {
+                return vec![];
+            }
+
+            if let (Some(username), Some(credential)) = (username, credential) {
+                vec![format!(
+                    "{}:{}:{}@{}",
+                    proto,
+                    urlencoding::encode(username),
+                    urlencoding::encode(credential),
+                    rest
+                )]
+            } else {
+                vec![format!("{}:{}", proto, rest)]
+            }
+        })
+        .collect()
+}
+
+/// Connects to the signaling server, through a proxy if the network settings say to use one.
+///
+/// If the connection itself couldn't be made, the error is a [`net::proxy::ConnectError`] saying whether it was the proxy or the server that couldn't be reached.
+pub async fn open(
+    addr: &str,
+    session_id: &str,
+    network: &net::proxy::Settings,
+) -> Result<PendingConnection, anyhow::Error> {
     let mut url = url::Url::parse(addr)?;
     url.set_query(Some(
         &url::form_urlencoded::Serializer::new(String::new())
@@ -64,7 +104,18 @@ pub async fn open(addr: &str, session_id: &str) -> Result<PendingConnection, any
         "User-Agent",
         tokio_tungstenite::tungstenite::http::HeaderValue::from_str(&format!("tango/{}", version::VERSION))?,
     );
-    let (mut signaling_stream, _) = tokio_tungstenite::connect_async(req).await?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("no host in signaling address"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow::anyhow!("no port in signaling address"))?;
+    let proxy = network.proxy_for(&url);
+    if let Some(proxy) = proxy.as_ref() {
+        log::info!("connecting to signaling server through proxy {}", proxy);
+    }
+    let stream = net::proxy::connect(proxy.as_ref(), host, port).await?;
+    let (mut signaling_stream, _) = tokio_tungstenite::client_async_tls(req, stream).await?;
 
     let raw = if let Some(raw) = signaling_stream.try_next().await? {
         raw
@@ -89,40 +140,21 @@ pub async fn open(addr: &str, session_id: &str) -> Result<PendingConnection, any
     let (dc, event_rx, peer_conn) = create_data_channel(
         &hello
             .ice_servers
-            .into_iter()
+            .iter()
             .flat_map(|ice_server| {
-                ice_server
-                    .urls
-                    .into_iter()
-                    .flat_map(|url| {
-                        let colon_idx = if let Some(colon_idx) = url.chars().position(|c| c == ':') {
-                            colon_idx
-                        } else {
-                            return vec![];
-                        };
-
-                        let proto = &url[..colon_idx];
-                        let rest = &url[colon_idx + 1..];
-
-                        // libdatachannel doesn't support TURN over TCP: in fact, it explodes!
-                        if url.chars().skip_while(|c| *c != '?').collect::<String>() == "?transport=tcp" {
-                            return vec![];
-                        }
-
-                        if let (Some(username), Some(credential)) = (&ice_server.username, &ice_server.credential) {
-                            vec![format!(
-                                "{}:{}:{}@{}",
-                                proto,
-                                urlencoding::encode(username),
-                                urlencoding::encode(credential),
-                                rest
-                            )]
-                        } else {
-                            vec![format!("{}:{}", proto, rest)]
-                        }
-                    })
-                    .collect::<Vec<_>>()
+                ice_server_urls(
+                    &ice_server.urls,
+                    ice_server.username.as_deref(),
+                    ice_server.credential.as_deref(),
+                )
             })
+            .chain(network.turn_servers.iter().flat_map(|turn_server| {
+                ice_server_urls(
+                    &[turn_server.url.trim().to_string()],
+                    Some(turn_server.username.as_str()).filter(|username| !username.is_empty()),
+                    Some(turn_server.credential.as_str()).filter(|credential| !credential.is_empty()),
+                )
+            }))
             .collect::<Vec<_>>(),
     )
     .await?;
@@ -0,0 +1,280 @@
+use crate::{battle, fs, save};
+
+/// What's known about someone who's been played against.
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, PartialEq)]
+pub struct Entry {
+    /// Every nickname they've played under, oldest first. The last one is what they're shown as.
+    pub nicknames: Vec<String>,
+    /// The installations they've played from, if their Tango told us.
+    pub installation_ids: Vec<String>,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// Rounds against them that desynced.
+    #[serde(default)]
+    pub desyncs: u32,
+    pub last_played_at: Option<std::time::SystemTime>,
+    pub notes: String,
+    /// If set, a nickname alone isn't enough to record results here: it has to come from an installation already seen for this entry, or be confirmed.
+    pub pinned: bool,
+}
+
+impl Entry {
+    pub fn nickname(&self) -> &str {
+        self.nicknames.last().map(|nickname| nickname.as_str()).unwrap_or("")
+    }
+
+    /// How many rounds have been played against them, desynced ones included.
+    pub fn rounds(&self) -> u32 {
+        self.wins + self.losses + self.draws + self.desyncs
+    }
+
+    fn add_identity(&mut self, nickname: &str, installation_id: Option<&str>) {
+        if !nickname.is_empty() && self.nickname() != nickname {
+            self.nicknames.retain(|n| n != nickname);
+            self.nicknames.push(nickname.to_string());
+        }
+        if let Some(installation_id) = installation_id {
+            if !self.installation_ids.iter().any(|id| id == installation_id) {
+                self.installation_ids.push(installation_id.to_string());
+            }
+        }
+    }
+}
+
+/// Who an opponent in the lobby turned out to be.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Recognition {
+    /// Nobody with their nickname or installation has been played before.
+    Unknown,
+    /// There's exactly one entry they can be, and it's fine to record results to it.
+    Known(String),
+    /// They may be any of these entries, or someone else entirely: the player has to pick.
+    ///
+    /// This is also what a single pinned entry gives if they aren't playing from an installation seen for it.
+    Candidates(Vec<String>),
+}
+
+struct Inner {
+    path: std::path::PathBuf,
+    entries: std::collections::BTreeMap<String, Entry>,
+}
+
+impl Inner {
+    fn save(&self) -> std::io::Result<()> {
+        save::write_atomically(&self.path, &serde_json::to_vec_pretty(&self.entries)?)
+    }
+}
+
+/// Everyone who's been played against, stored as a single JSON file in the data directory.
+///
+/// Entries are keyed by an ID of their own rather than by nickname, so one can be kept when someone renames and two can be merged if they turn out to be the same person.
+#[derive(Clone)]
+pub struct Registry(std::sync::Arc<parking_lot::Mutex<Inner>>);
+
+impl Registry {
+    /// Loads the registry. If it can't be read, it starts out empty: it's nice to have, but not worth keeping anyone from playing.
+    pub fn load(path: std::path::PathBuf) -> Self {
+        let entries = match fs::read(&path) {
+            Ok(raw) => match serde_json::from_slice(&raw) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    log::warn!("failed to parse opponents: {}", e);
+                    Default::default()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => {
+                log::warn!("failed to read opponents: {}", e);
+                Default::default()
+            }
+        };
+        Self(std::sync::Arc::new(parking_lot::Mutex::new(Inner { path, entries })))
+    }
+
+    pub fn get(&self, id: &str) -> Option<Entry> {
+        self.0.lock().entries.get(id).cloned()
+    }
+
+    /// Every entry, most recently played first.
+    pub fn entries(&self) -> Vec<(String, Entry)> {
+        let mut entries = self
+            .0
+            .lock()
+            .entries
+            .iter()
+            .map(|(id, entry)| (id.clone(), entry.clone()))
+            .collect::<Vec<_>>();
+        entries.sort_by(|(_, a), (_, b)| b.last_played_at.cmp(&a.last_played_at));
+        entries
+    }
+
+    /// Works out who an opponent is from their nickname and, if they sent it, their installation ID.
+    ///
+    /// An installation ID seen for exactly one entry settles it even if they've renamed, since nicknames are easy to share and IDs aren't.
+    pub fn recognize(&self, nickname: &str, installation_id: Option<&str>) -> Recognition {
+        let inner = self.0.lock();
+
+        if let Some(installation_id) = installation_id {
+            let by_installation = inner
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.installation_ids.iter().any(|id| id == installation_id))
+                .map(|(id, _)| id.clone())
+                .collect::<Vec<_>>();
+            if by_installation.len() == 1 {
+                return Recognition::Known(by_installation.into_iter().next().unwrap());
+            }
+        }
+
+        let candidates = inner
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.nicknames.iter().any(|n| n == nickname))
+            .collect::<Vec<_>>();
+        match candidates.as_slice() {
+            [] => Recognition::Unknown,
+            [(id, entry)] if !entry.pinned => Recognition::Known(id.to_string()),
+            _ => Recognition::Candidates(candidates.into_iter().map(|(id, _)| id.clone()).collect()),
+        }
+    }
+
+    /// Adds someone who hasn't been played before, returning their new entry's ID.
+    pub fn add(&self, nickname: &str, installation_id: Option<&str>) -> std::io::Result<String> {
+        let mut inner = self.0.lock();
+        let id = loop {
+            let id = format!("{:016x}", rand::random::<u64>());
+            if !inner.entries.contains_key(&id) {
+                break id;
+            }
+        };
+        let mut entry = Entry::default();
+        entry.add_identity(nickname, installation_id);
+        inner.entries.insert(id.clone(), entry);
+        inner.save()?;
+        Ok(id)
+    }
+
+    /// Confirms that an opponent is who an entry is for, so they're recognized from then on even if it's pinned.
+    pub fn confirm(&self, id: &str, nickname: &str, installation_id: Option<&str>) -> std::io::Result<()> {
+        self.update(id, |entry| entry.add_identity(nickname, installation_id))
+    }
+
+    pub fn set_notes(&self, id: &str, notes: &str) -> std::io::Result<()> {
+        self.update(id, |entry| entry.notes = notes.to_string())
+    }
+
+    pub fn set_pinned(&self, id: &str, pinned: bool) -> std::io::Result<()> {
+        self.update(id, |entry| entry.pinned = pinned)
+    }
+
+    /// Folds one entry into another, e.g. because someone renamed and was added again. The record and notes are combined.
+    pub fn merge(&self, from: &str, into: &str) -> std::io::Result<()> {
+        if from == into {
+            return Ok(());
+        }
+        let mut inner = self.0.lock();
+        if !inner.entries.contains_key(into) {
+            return Ok(());
+        }
+        let from = if let Some(from) = inner.entries.remove(from) {
+            from
+        } else {
+            return Ok(());
+        };
+        let into = inner.entries.get_mut(into).unwrap();
+        // Keep the nickname the entry being merged into is shown as.
+        let nickname = into.nickname().to_string();
+        for nickname in from.nicknames.iter() {
+            into.add_identity(nickname, None);
+        }
+        into.add_identity(&nickname, None);
+        for installation_id in from.installation_ids.iter() {
+            into.add_identity("", Some(installation_id));
+        }
+        into.wins += from.wins;
+        into.losses += from.losses;
+        into.draws += from.draws;
+        into.desyncs += from.desyncs;
+        into.last_played_at = into.last_played_at.max(from.last_played_at);
+        if !from.notes.is_empty() {
+            if !into.notes.is_empty() {
+                into.notes.push('\n');
+            }
+            into.notes.push_str(&from.notes);
+        }
+        into.pinned |= from.pinned;
+        inner.save()
+    }
+
+    pub fn remove(&self, id: &str) -> std::io::Result<()> {
+        let mut inner = self.0.lock();
+        if inner.entries.remove(id).is_none() {
+            return Ok(());
+        }
+        inner.save()
+    }
+
+    /// Counts a round's result towards an entry's head-to-head record.
+    pub fn record(&self, id: &str, result: Option<battle::BattleResult>, was_draw: bool) -> std::io::Result<()> {
+        self.update(id, |entry| {
+            match result {
+                _ if was_draw => {
+                    entry.draws += 1;
+                }
+                Some(battle::BattleResult::Win) => {
+                    entry.wins += 1;
+                }
+                Some(battle::BattleResult::Loss) => {
+                    entry.losses += 1;
+                }
+                None => {}
+            }
+            entry.last_played_at = Some(std::time::SystemTime::now());
+        })
+    }
+
+    /// Counts a desynced round against an entry, for judging how risky matches against them are.
+    pub fn record_desync(&self, id: &str) -> std::io::Result<()> {
+        self.update(id, |entry| {
+            entry.desyncs += 1;
+            entry.last_played_at = Some(std::time::SystemTime::now());
+        })
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut Entry)) -> std::io::Result<()> {
+        let mut inner = self.0.lock();
+        let entry = if let Some(entry) = inner.entries.get_mut(id) {
+            entry
+        } else {
+            return Ok(());
+        };
+        let old_entry = entry.clone();
+        f(entry);
+        if *entry == old_entry {
+            return Ok(());
+        }
+        inner.save()
+    }
+}
+
+/// Where the results of a match go: the entry the opponent was recognized as.
+#[derive(Clone)]
+pub struct Recorder {
+    pub registry: Registry,
+    pub id: String,
+}
+
+impl Recorder {
+    pub fn record(&self, result: Option<battle::BattleResult>, was_draw: bool) {
+        if let Err(e) = self.registry.record(&self.id, result, was_draw) {
+            log::error!("failed to record result against opponent: {:?}", e);
+        }
+    }
+
+    pub fn record_desync(&self) {
+        if let Err(e) = self.registry.record_desync(&self.id) {
+            log::error!("failed to record desync against opponent: {:?}", e);
+        }
+    }
+}
@@ -1,11 +1,14 @@
 pub mod bps;
+pub mod cache;
+pub mod strings;
+pub mod validation;
 
 use futures::StreamExt;
 use itertools::Itertools;
 use serde::Deserialize;
 use tokio::io::AsyncWriteExt;
 
-use crate::{config, filesync, game, rom, scanner, sync};
+use crate::{config, filesync, fs, game, net, rom, save, scanner, sync};
 
 #[derive(serde::Deserialize, Debug)]
 struct Metadata {
@@ -19,6 +22,8 @@ struct PatchMetadata {
     pub authors: Vec<String>,
     pub license: Option<String>,
     pub source: Option<String>,
+    /// The oldest Tango that can use any version of the patch.
+    pub minimum_tango_version: Option<semver::Version>,
 }
 
 fn deserialize_option_language_identifier<'de, D>(
@@ -84,6 +89,20 @@ pub struct Modcard56EffectOverride {
     pub name_template: Option<rom::Modcard56EffectTemplate>,
 }
 
+/// Where a patched ROM keeps its modcard tables, for patches that move or extend them. Anything left out is where the unpatched ROM has it.
+#[derive(serde::Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Modcard56TableOverride {
+    /// How many modcards there are, including the empty modcard 0.
+    pub count: Option<usize>,
+    /// The address of the modcard data table.
+    pub data: Option<u32>,
+    /// The address of the pointer to the modcard names text archive.
+    pub names_pointer: Option<u32>,
+    /// The address of the pointer to the modcard effect names text archive.
+    pub details_names_pointer: Option<u32>,
+}
+
 #[derive(serde::Deserialize, Default, Debug, Clone)]
 #[serde(default)]
 pub struct ROMOverrides {
@@ -94,6 +113,86 @@ pub struct ROMOverrides {
     pub navicust_parts: Option<Vec<NavicustPartOverride>>,
     pub modcard56s: Option<Vec<Modcard56Override>>,
     pub modcard56_effects: Option<Vec<Modcard56EffectOverride>>,
+    pub modcard56_table: Modcard56TableOverride,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SaveRequirementKind {
+    /// A story progress flag that must be set.
+    Flag { flag: usize },
+    /// A minimum number of chips that must be in the library.
+    LibraryCount { count: usize },
+    /// Chips that may not be in the equipped folder.
+    BannedChips { chips: Vec<usize> },
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct SaveRequirement {
+    #[serde(flatten)]
+    pub kind: SaveRequirementKind,
+
+    /// If set, the requirement must be met before readying up instead of just being recommended.
+    #[serde(default)]
+    pub hard: bool,
+
+    /// What the requirement means to players, e.g. the story event a flag is set by.
+    pub description: Option<String>,
+}
+
+impl SaveRequirement {
+    /// Checks the requirement against a save, or returns None if the save doesn't expose what's needed to check it.
+    pub fn is_met(&self, save: &(dyn save::Save + Send + Sync)) -> Option<bool> {
+        match &self.kind {
+            SaveRequirementKind::Flag { flag } => Some(save.view_progress()?.flag(*flag)),
+            SaveRequirementKind::LibraryCount { count } => Some(save.view_progress()?.library_count() >= *count),
+            SaveRequirementKind::BannedChips { chips } => {
+                let chips_view = save.view_chips()?;
+                let folder_index = chips_view.equipped_folder_index();
+                Some(
+                    !(0..30)
+                        .flat_map(|i| chips_view.chip(folder_index, i))
+                        .any(|chip| chips.contains(&chip.id)),
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnmetSaveRequirement {
+    pub requirement: SaveRequirement,
+
+    /// If false, the save couldn't be checked against the requirement at all, so it may actually be met.
+    pub checked: bool,
+}
+
+impl UnmetSaveRequirement {
+    /// Whether this should stop the player from readying up. Requirements that couldn't be checked never do.
+    pub fn is_blocking(&self) -> bool {
+        self.requirement.hard && self.checked
+    }
+}
+
+/// Finds the requirements a save doesn't meet, including the ones that couldn't be checked.
+pub fn unmet_save_requirements(
+    requirements: &[SaveRequirement],
+    save: &(dyn save::Save + Send + Sync),
+) -> Vec<UnmetSaveRequirement> {
+    requirements
+        .iter()
+        .flat_map(|requirement| match requirement.is_met(save) {
+            Some(true) => None,
+            Some(false) => Some(UnmetSaveRequirement {
+                requirement: requirement.clone(),
+                checked: true,
+            }),
+            None => Some(UnmetSaveRequirement {
+                requirement: requirement.clone(),
+                checked: false,
+            }),
+        })
+        .collect()
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -101,34 +200,386 @@ struct VersionMetadata {
     #[serde(default)]
     pub rom_overrides: ROMOverrides,
     pub netplay_compatibility: String,
+    #[serde(default)]
+    pub save_requirements: Vec<SaveRequirement>,
+    /// The oldest Tango that can use this version, on top of the patch's own requirement.
+    pub minimum_tango_version: Option<semver::Version>,
+    /// Replaces the pool of battle backgrounds picked from at random, for games that pick one.
+    pub battle_backgrounds: Option<Vec<u8>>,
+    /// Other patches that must be applied first, e.g. `OtherPatch >= 1.1`.
+    #[serde(default)]
+    pub requires: Vec<String>,
+    /// Other patches that can't be applied along with this one.
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+    /// Patch versions this one was built on top of, e.g. `OtherPatch 1.1.0`, which then count as applied.
+    #[serde(default)]
+    pub includes: Vec<String>,
+    pub migration: Option<Migration>,
+}
+
+/// How to bring a save over from the previous version of a patch, if that version numbered things differently.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct Migration {
+    /// The version saves are migrated from.
+    pub from: semver::Version,
+    #[serde(flatten)]
+    pub table: save::migration::Table,
+}
+
+lazy_static! {
+    /// The version of Tango this was built as, which patches' minimum versions are checked against.
+    static ref TANGO_VERSION: semver::Version = env!("CARGO_PKG_VERSION").parse().unwrap();
 }
 
 #[derive(Debug, Clone)]
 pub struct Version {
+    pub path: std::path::PathBuf,
     pub rom_overrides: ROMOverrides,
     pub netplay_compatibility: String,
+    pub save_requirements: Vec<SaveRequirement>,
     pub supported_games: std::collections::HashSet<&'static (dyn game::Game + Send + Sync)>,
+    /// The oldest Tango that can use this version, if it needs a newer one than usual.
+    pub minimum_tango_version: Option<semver::Version>,
+    /// The battle backgrounds to pick from instead of the game's usual ones. Both sides of a match must agree on this.
+    pub battle_backgrounds: Option<Vec<u8>>,
+    pub requires: Vec<Dependency>,
+    pub conflicts: Vec<Dependency>,
+    pub includes: Vec<Inclusion>,
+    pub migration: Option<Migration>,
+}
+
+impl Version {
+    /// Whether this build of Tango is new enough to use this version.
+    pub fn is_usable(&self) -> bool {
+        self.minimum_tango_version
+            .as_ref()
+            .map(|minimum_tango_version| *TANGO_VERSION >= *minimum_tango_version)
+            .unwrap_or(true)
+    }
+}
+
+/// Another patch that a patch version requires or conflicts with, e.g. `OtherPatch >= 1.1`. Leaving the version out matches any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    pub version_req: semver::VersionReq,
+}
+
+impl Dependency {
+    pub fn matches(&self, name: &str, version: &semver::Version) -> bool {
+        self.name == name && self.version_req.matches(version)
+    }
+}
+
+impl std::str::FromStr for Dependency {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, version_req) = s.split_at(
+            s.find(|c: char| c.is_whitespace() || "<>=~^*".contains(c))
+                .unwrap_or(s.len()),
+        );
+        if name.is_empty() {
+            anyhow::bail!("missing patch name in {:?}", s);
+        }
+        let version_req = version_req.trim();
+        Ok(Self {
+            name: name.to_string(),
+            version_req: if version_req.is_empty() {
+                semver::VersionReq::STAR
+            } else {
+                semver::VersionReq::parse(version_req)?
+            },
+        })
+    }
+}
+
+impl std::fmt::Display for Dependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.version_req == semver::VersionReq::STAR {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} {}", self.name, self.version_req)
+        }
+    }
+}
+
+/// A patch version that a patch version was built on top of, e.g. `OtherPatch 1.1.0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inclusion {
+    pub name: String,
+    pub version: semver::Version,
+}
+
+impl std::str::FromStr for Inclusion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, version) = s
+            .trim()
+            .split_once(|c: char| c.is_whitespace() || c == '=')
+            .ok_or_else(|| anyhow::anyhow!("missing version in {:?}", s))?;
+        Ok(Self {
+            name: name.to_string(),
+            version: semver::Version::parse(version.trim().trim_start_matches('=').trim())?,
+        })
+    }
+}
+
+/// Why a patch version can't be applied with the patches that are installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyProblem {
+    /// No installed version of the required patch matches, or none of them support the game.
+    Missing(Dependency),
+    /// A patch that would be applied conflicts with another one that would be.
+    Conflict { patch_name: String, conflict: Dependency },
+    /// Patches require each other in a loop.
+    Cycle(String),
+}
+
+impl std::fmt::Display for DependencyProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyProblem::Missing(dependency) => write!(f, "missing {}", dependency),
+            DependencyProblem::Conflict { patch_name, conflict } => {
+                write!(f, "{} conflicts with {}", patch_name, conflict)
+            }
+            DependencyProblem::Cycle(patch_name) => write!(f, "{} requires itself", patch_name),
+        }
+    }
+}
+
+fn resolve_requirements_into<'a>(
+    patches: &'a std::collections::BTreeMap<String, Patch>,
+    game: &'static (dyn game::Game + Send + Sync),
+    patch_name: &'a str,
+    version_info: &'a Version,
+    path: &mut Vec<(&'a str, &'a Version)>,
+    stack: &mut Vec<(String, semver::Version)>,
+    problems: &mut Vec<DependencyProblem>,
+) {
+    path.push((patch_name, version_info));
+    for requirement in version_info.requires.iter() {
+        // Patches built on top of a requirement already have it applied.
+        if path.iter().any(|(_, version_info)| {
+            version_info
+                .includes
+                .iter()
+                .any(|inclusion| requirement.matches(&inclusion.name, &inclusion.version))
+        }) || stack.iter().any(|(name, version)| requirement.matches(name, version))
+        {
+            continue;
+        }
+
+        if path.iter().any(|(name, _)| *name == requirement.name) {
+            problems.push(DependencyProblem::Cycle(requirement.name.clone()));
+            continue;
+        }
+
+        let (name, patch) = if let Some(found) = patches.get_key_value(&requirement.name) {
+            found
+        } else {
+            problems.push(DependencyProblem::Missing(requirement.clone()));
+            continue;
+        };
+
+        let (version, required_version_info) = if let Some(found) = patch
+            .versions
+            .iter()
+            .filter(|(version, version_info)| {
+                requirement.version_req.matches(version)
+                    && version_info.supported_games.contains(&game)
+                    && version_info.is_usable()
+            })
+            .max_by_key(|(version, _)| *version)
+        {
+            found
+        } else {
+            problems.push(DependencyProblem::Missing(requirement.clone()));
+            continue;
+        };
+
+        resolve_requirements_into(patches, game, name, required_version_info, path, stack, problems);
+        stack.push((name.clone(), version.clone()));
+    }
+    path.pop();
+}
+
+/// Works out which patches need applying before a patch version for a game, in the order they're applied.
+///
+/// The newest installed version matching each requirement is picked. Requirements that a patch was built on top of are left out, as they're already part of it.
+pub fn resolve_requirements(
+    patches: &std::collections::BTreeMap<String, Patch>,
+    patch_name: &str,
+    version: &semver::Version,
+    game: &'static (dyn game::Game + Send + Sync),
+) -> Result<Vec<(String, semver::Version)>, Vec<DependencyProblem>> {
+    let version_info = if let Some(version_info) = patches.get(patch_name).and_then(|p| p.versions.get(version)) {
+        version_info
+    } else {
+        return Err(vec![DependencyProblem::Missing(Dependency {
+            name: patch_name.to_string(),
+            version_req: semver::VersionReq::parse(&format!("={}", version)).unwrap(),
+        })]);
+    };
+
+    let mut stack = vec![];
+    let mut problems = vec![];
+    resolve_requirements_into(
+        patches,
+        game,
+        patch_name,
+        version_info,
+        &mut vec![],
+        &mut stack,
+        &mut problems,
+    );
+
+    let applied = stack
+        .iter()
+        .flat_map(|(name, version)| {
+            patches
+                .get(name)
+                .and_then(|p| p.versions.get(version))
+                .map(|version_info| (name.as_str(), version, version_info))
+        })
+        .chain(std::iter::once((patch_name, version, version_info)))
+        .collect::<Vec<_>>();
+    for (name, _, version_info) in applied.iter() {
+        for conflict in version_info.conflicts.iter() {
+            if applied.iter().any(|(other_name, other_version, other_version_info)| {
+                other_name != name
+                    && (conflict.matches(other_name, other_version)
+                        || other_version_info
+                            .includes
+                            .iter()
+                            .any(|inclusion| conflict.matches(&inclusion.name, &inclusion.version)))
+            }) {
+                problems.push(DependencyProblem::Conflict {
+                    patch_name: name.to_string(),
+                    conflict: conflict.clone(),
+                });
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    Ok(stack)
 }
 
+#[derive(Debug)]
+pub struct Readme {
+    pub contents: String,
+    /// Whether the README is written in Markdown, going by its extension. Otherwise it's plain text.
+    pub is_markdown: bool,
+}
+
+/// SPDX identifiers of licenses patches are commonly released under.
+const KNOWN_LICENSES: &[&str] = &[
+    "0BSD",
+    "AGPL-3.0-only",
+    "AGPL-3.0-or-later",
+    "Apache-2.0",
+    "BSD-2-Clause",
+    "BSD-3-Clause",
+    "CC-BY-4.0",
+    "CC-BY-NC-4.0",
+    "CC-BY-NC-ND-4.0",
+    "CC-BY-NC-SA-4.0",
+    "CC-BY-ND-4.0",
+    "CC-BY-SA-4.0",
+    "CC0-1.0",
+    "GPL-2.0-only",
+    "GPL-2.0-or-later",
+    "GPL-3.0-only",
+    "GPL-3.0-or-later",
+    "ISC",
+    "LGPL-2.1-only",
+    "LGPL-2.1-or-later",
+    "LGPL-3.0-only",
+    "LGPL-3.0-or-later",
+    "MIT",
+    "MPL-2.0",
+    "Unlicense",
+    "WTFPL",
+    "Zlib",
+];
+
+/// Whether a license is an SPDX expression made up only of licenses we know, e.g. `MIT OR Apache-2.0`.
+pub fn is_recognized_license(license: &str) -> bool {
+    let mut tokens = license
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|token| !token.is_empty());
+    let mut any = false;
+    while let Some(token) = tokens.next() {
+        match token {
+            "AND" | "OR" => continue,
+            "WITH" => {
+                // Exceptions only loosen the license they're attached to.
+                tokens.next();
+                continue;
+            }
+            _ => {}
+        }
+        if !KNOWN_LICENSES.contains(&token.trim_end_matches('+')) {
+            return false;
+        }
+        any = true;
+    }
+    any
+}
+
+/// A patch can show its own banner in place of the game's by putting one in its directory under this name.
+pub const BANNER_FILENAME: &str = "banner.png";
+
 #[derive(Debug)]
 pub struct Patch {
     pub path: std::path::PathBuf,
+    /// The patch's own banner, if it has one.
+    pub banner_path: Option<std::path::PathBuf>,
     pub title: String,
     pub authors: Vec<mailparse::SingleInfo>,
     pub license: Option<String>,
     pub source: Option<String>,
-    pub readme: Option<String>,
+    pub readme: Option<Readme>,
     pub versions: std::collections::HashMap<semver::Version, Version>,
 }
 
+impl Patch {
+    /// Works out how to migrate saves between two versions, by following each version's migration back to the one before it. Returns None if there's no chain of migrations from one to the other.
+    pub fn migration(&self, from: &semver::Version, to: &semver::Version) -> Option<save::migration::Table> {
+        let mut tables = vec![];
+        let mut version = to;
+        while version != from {
+            // A chain can't be longer than there are versions, so anything longer is going round in circles.
+            if tables.len() >= self.versions.len() {
+                return None;
+            }
+            let migration = self.versions.get(version)?.migration.as_ref()?;
+            tables.push(&migration.table);
+            version = &migration.from;
+        }
+        Some(
+            tables
+                .into_iter()
+                .rev()
+                .fold(save::migration::Table::default(), |table, next| table.then(next)),
+        )
+    }
+}
+
 lazy_static! {
     static ref PATCH_FILENAME_REGEX: regex::Regex = regex::Regex::new(r"^(\S{4})_(\d{2}).bps$").unwrap();
 }
 
-pub async fn update(url: &String, root: &std::path::Path) -> Result<(), anyhow::Error> {
-    std::fs::create_dir_all(root)?;
+pub async fn update(url: &String, root: &std::path::Path, network: &net::proxy::Settings) -> Result<(), anyhow::Error> {
+    fs::create_dir_all(root)?;
 
-    let client = reqwest::Client::new();
+    let client = network.http_client();
     let entries = tokio::time::timeout(
         // 30 second timeout to fetch JSON.
         std::time::Duration::from_secs(30),
@@ -156,9 +607,9 @@ pub async fn update(url: &String, root: &std::path::Path) -> Result<(), anyhow::
             move |path| {
                 let url = url.clone();
                 let root = root.clone();
+                let client = client.clone();
                 Box::pin(async move {
-                    let mut output_file = tokio::fs::File::create(&root.join(path)).await?;
-                    let client = reqwest::Client::new();
+                    let mut output_file = tokio::fs::File::create(fs::extended(&root.join(path))).await?;
                     let mut stream = tokio::time::timeout(
                         // 30 second timeout to initiate connection.
                         std::time::Duration::from_secs(30),
@@ -197,7 +648,7 @@ pub async fn update(url: &String, root: &std::path::Path) -> Result<(), anyhow::
 
 pub fn scan(path: &std::path::Path) -> Result<std::collections::BTreeMap<String, Patch>, std::io::Error> {
     let mut patches = std::collections::BTreeMap::new();
-    for entry in std::fs::read_dir(path)? {
+    for entry in fs::read_dir(path)? {
         let entry = match entry {
             Ok(entry) => entry,
             Err(e) => {
@@ -216,136 +667,194 @@ pub fn scan(path: &std::path::Path) -> Result<std::collections::BTreeMap<String,
             continue;
         }
 
-        let raw_info = match std::fs::read(entry.path().join("info.toml")) {
-            Ok(buf) => buf,
-            Err(_) => {
+        if let Some(patch) = read_patch(&path.join(&name)) {
+            patches.insert(name, patch);
+        }
+    }
+    Ok(patches)
+}
+
+/// Reads a patch from its directory, or returns None if it isn't one.
+fn read_patch(patch_path: &std::path::Path) -> Option<Patch> {
+    let raw_info = match fs::read(patch_path.join("info.toml")) {
+        Ok(buf) => buf,
+        Err(_) => {
+            return None;
+        }
+    };
+
+    let info = match toml::from_slice::<Metadata>(&raw_info) {
+        Ok(info) => info,
+        Err(e) => {
+            log::warn!("{}: {}", patch_path.display(), e);
+            return None;
+        }
+    };
+
+    // README, README.md, README.txt, etc. are all READMEs, but Markdown ones are preferred if there's more than one.
+    let readme = fs::read_dir(patch_path)
+        .ok()
+        .into_iter()
+        .flatten()
+        .flat_map(|entry| entry.ok())
+        .map(|entry| patch_path.join(entry.file_name()))
+        .filter(|path| {
+            path.file_stem()
+                .map(|stem| stem.to_ascii_lowercase() == "readme")
+                .unwrap_or(false)
+        })
+        .map(|path| {
+            let is_markdown = path
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+                .unwrap_or(false);
+            (path, is_markdown)
+        })
+        .max_by_key(|(_, is_markdown)| *is_markdown)
+        .and_then(|(path, is_markdown)| {
+            Some(Readme {
+                contents: String::from_utf8_lossy(&fs::read(path).ok()?).to_string(),
+                is_markdown,
+            })
+        });
+
+    let mut versions = std::collections::HashMap::new();
+    for (v, version) in info.versions.into_iter() {
+        let sv = match semver::Version::parse(&v) {
+            Ok(sv) => sv,
+            Err(e) => {
+                log::warn!("{}: {}", patch_path.display(), e);
                 continue;
             }
         };
 
-        let info = match toml::from_slice::<Metadata>(&raw_info) {
-            Ok(info) => info,
+        if sv.to_string() != v {
+            log::warn!("{}: semver did not round trip", patch_path.display());
+            continue;
+        }
+
+        let version_path = patch_path.join(format!("v{}", sv.to_string()));
+        let read_version_dir = match fs::read_dir(&version_path) {
+            Ok(read_version_dir) => read_version_dir,
             Err(e) => {
-                log::warn!("{}: {}", entry.path().display(), e);
+                log::warn!("{}", e);
                 continue;
             }
         };
 
-        let readme = std::fs::read_dir(entry.path())
-            .ok()
-            .and_then(|mut it| {
-                it.find(|p| {
-                    p.as_ref()
-                        .map(|entry| entry.file_name().to_ascii_lowercase() == "readme")
-                        .unwrap_or(false)
-                })
-                .and_then(|r| r.ok())
-            })
-            .and_then(|entry| std::fs::read(entry.path()).ok())
-            .map(|buf| String::from_utf8_lossy(&buf).to_string());
+        let dependencies = (|| {
+            Ok::<_, anyhow::Error>((
+                version
+                    .requires
+                    .iter()
+                    .map(|s| s.parse())
+                    .collect::<Result<Vec<Dependency>, _>>()?,
+                version
+                    .conflicts
+                    .iter()
+                    .map(|s| s.parse())
+                    .collect::<Result<Vec<Dependency>, _>>()?,
+                version
+                    .includes
+                    .iter()
+                    .map(|s| s.parse())
+                    .collect::<Result<Vec<Inclusion>, _>>()?,
+            ))
+        })();
+        let (requires, conflicts, includes) = match dependencies {
+            Ok(dependencies) => dependencies,
+            Err(e) => {
+                log::warn!("{}: v{}: {}", patch_path.display(), sv, e);
+                continue;
+            }
+        };
+
+        let mut supported_games = std::collections::HashSet::new();
 
-        let mut versions = std::collections::HashMap::new();
-        for (v, version) in info.versions.into_iter() {
-            let sv = match semver::Version::parse(&v) {
-                Ok(sv) => sv,
+        for entry in read_version_dir {
+            let entry = match entry {
+                Ok(entry) => entry,
                 Err(e) => {
-                    log::warn!("{}: {}", entry.path().display(), e);
+                    log::error!("failed to read dir: {:?}", e);
                     continue;
                 }
             };
 
-            if sv.to_string() != v {
-                log::warn!("{}: semver did not round trip", entry.path().display());
-                continue;
-            }
-
-            let read_version_dir = match std::fs::read_dir(entry.path().join(format!("v{}", sv.to_string()))) {
-                Ok(read_version_dir) => read_version_dir,
+            // Try parse file name.
+            let filename = match entry.file_name().into_string() {
+                Ok(filename) => filename,
                 Err(e) => {
-                    log::warn!("{}: {}", entry.path().display(), e);
+                    log::error!("failed to read dir: {:?}", e);
                     continue;
                 }
             };
+            let captures = if let Some(captures) = PATCH_FILENAME_REGEX.captures(&filename) {
+                captures
+            } else {
+                continue;
+            };
 
-            let mut supported_games = std::collections::HashSet::new();
-
-            for entry in read_version_dir {
-                let entry = match entry {
-                    Ok(entry) => entry,
-                    Err(e) => {
-                        log::error!("failed to read dir: {:?}", e);
-                        continue;
-                    }
-                };
-
-                // Try parse file name.
-                let filename = match entry.file_name().into_string() {
-                    Ok(filename) => filename,
-                    Err(e) => {
-                        log::error!("failed to read dir: {:?}", e);
-                        continue;
-                    }
-                };
-                let captures = if let Some(captures) = PATCH_FILENAME_REGEX.captures(&filename) {
-                    captures
-                } else {
-                    continue;
-                };
-
-                let rom_id = captures.get(1).unwrap().as_str().to_string();
-                let revision = captures.get(2).unwrap().as_str().parse::<u8>().unwrap();
-
-                let game = if let Some(game) = game::find_by_rom_info(rom_id.as_bytes().try_into().unwrap(), revision) {
-                    game
-                } else {
-                    continue;
-                };
+            let rom_id = captures.get(1).unwrap().as_str().to_string();
+            let revision = captures.get(2).unwrap().as_str().parse::<u8>().unwrap();
 
-                supported_games.insert(game);
-            }
+            let game = if let Some(game) = game::find_by_rom_info(rom_id.as_bytes().try_into().unwrap(), revision) {
+                game
+            } else {
+                continue;
+            };
 
-            versions.insert(
-                sv,
-                Version {
-                    rom_overrides: version.rom_overrides,
-                    netplay_compatibility: version.netplay_compatibility,
-                    supported_games,
-                },
-            );
+            supported_games.insert(game);
         }
 
-        patches.insert(
-            name.to_string(),
-            Patch {
-                path: entry.path(),
-                title: info.patch.title,
-                authors: info
-                    .patch
-                    .authors
-                    .into_iter()
-                    .flat_map(|author| match mailparse::addrparse(&author) {
-                        Ok(addrs) => addrs
-                            .into_inner()
-                            .into_iter()
-                            .flat_map(|addr| match addr {
-                                mailparse::MailAddr::Group(group) => group.addrs,
-                                mailparse::MailAddr::Single(single) => vec![single],
-                            })
-                            .collect(),
-                        Err(_) => vec![mailparse::SingleInfo {
-                            display_name: Some(author),
-                            addr: "".to_string(),
-                        }],
-                    })
-                    .collect(),
-                license: info.patch.license,
-                readme,
-                source: info.patch.source,
-                versions,
+        versions.insert(
+            sv,
+            Version {
+                path: version_path,
+                rom_overrides: version.rom_overrides,
+                netplay_compatibility: version.netplay_compatibility,
+                save_requirements: version.save_requirements,
+                supported_games,
+                minimum_tango_version: std::cmp::max(
+                    info.patch.minimum_tango_version.clone(),
+                    version.minimum_tango_version,
+                ),
+                battle_backgrounds: version.battle_backgrounds.filter(|backgrounds| !backgrounds.is_empty()),
+                requires,
+                conflicts,
+                includes,
+                migration: version.migration,
             },
         );
     }
-    Ok(patches)
+
+    Some(Patch {
+        path: patch_path.to_path_buf(),
+        banner_path: Some(patch_path.join(BANNER_FILENAME)).filter(|path| path.is_file()),
+        title: info.patch.title,
+        authors: info
+            .patch
+            .authors
+            .into_iter()
+            .flat_map(|author| match mailparse::addrparse(&author) {
+                Ok(addrs) => addrs
+                    .into_inner()
+                    .into_iter()
+                    .flat_map(|addr| match addr {
+                        mailparse::MailAddr::Group(group) => group.addrs,
+                        mailparse::MailAddr::Single(single) => vec![single],
+                    })
+                    .collect(),
+                Err(_) => vec![mailparse::SingleInfo {
+                    display_name: Some(author),
+                    addr: "".to_string(),
+                }],
+            })
+            .collect(),
+        license: info.patch.license,
+        readme,
+        source: info.patch.source,
+        versions,
+    })
 }
 
 pub type Scanner = scanner::Scanner<std::collections::BTreeMap<String, Patch>>;
@@ -378,7 +887,7 @@ impl Autoupdater {
             let patches_scanner = self.patches_scanner.clone();
             async move {
                 'l: loop {
-                    let (repo_url, patches_path) = {
+                    let (repo_url, patches_path, network) = {
                         let config = config.read();
                         (
                             if !config.patch_repo.is_empty() {
@@ -387,13 +896,14 @@ impl Autoupdater {
                                 config::DEFAULT_PATCH_REPO.to_owned()
                             },
                             config.patches_path().to_path_buf(),
+                            config.network.clone(),
                         )
                     };
 
                     let patches_scanner = patches_scanner.clone();
                     let _ = tokio::task::spawn_blocking(move || {
                         patches_scanner.rescan(move || {
-                            if let Err(e) = sync::block_on(update(&repo_url, &patches_path)) {
+                            if let Err(e) = sync::block_on(update(&repo_url, &patches_path, &network)) {
                                 log::error!("failed to update patches: {:?}", e);
                             }
                             scan(&patches_path).ok()
@@ -427,28 +937,309 @@ impl Autoupdater {
     }
 }
 
-pub fn apply_patch_from_disk(
-    rom: &[u8],
-    game: &'static (dyn game::Game + Send + Sync),
+/// Gets the path to the BPS file a patch version uses for a game.
+pub fn bps_path(
     patches_path: &std::path::Path,
     patch_name: &str,
     patch_version: &semver::Version,
-) -> Result<Vec<u8>, anyhow::Error> {
+    game: &'static (dyn game::Game + Send + Sync),
+) -> Result<std::path::PathBuf, anyhow::Error> {
     let patch_name = std::path::Path::new(patch_name);
     if patch_name.components().count() > 1 {
         anyhow::bail!("attempted path traversal in patch name");
     }
 
+    Ok(patches_path
+        .join(&patch_name)
+        .join(format!("v{}", patch_version))
+        .join(bps_filename(game)))
+}
+
+/// Gets the name of the BPS file for a game, e.g. `BR5E_00.bps`.
+fn bps_filename(game: &'static (dyn game::Game + Send + Sync)) -> String {
     let (rom_code, revision) = game.rom_code_and_revision();
-    let raw = std::fs::read(
-        patches_path
-            .join(&patch_name)
-            .join(format!("v{}", patch_version))
-            .join(format!(
-                "{}_{:02}.bps",
-                std::str::from_utf8(rom_code).unwrap(),
-                revision
-            )),
+    format!("{}_{:02}.bps", rom_code.escape_ascii(), revision)
+}
+
+pub fn apply_patch_from_disk(
+    rom: &[u8],
+    game: &'static (dyn game::Game + Send + Sync),
+    patches_path: &std::path::Path,
+    patch_name: &str,
+    patch_version: &semver::Version,
+) -> Result<Vec<u8>, anyhow::Error> {
+    let path = bps_path(patches_path, patch_name, patch_version, game)?;
+
+    // Most patches don't need any others, so only scan the rest of them if this one does.
+    let requirements = if read_patch(&patches_path.join(patch_name))
+        .and_then(|patch| patch.versions.get(patch_version).map(|v| !v.requires.is_empty()))
+        .unwrap_or(false)
+    {
+        resolve_requirements(&scan(patches_path)?, patch_name, patch_version, game)
+            .map_err(|problems| anyhow::anyhow!("unmet patch dependencies: {}", problems.iter().join(", ")))?
+    } else {
+        vec![]
+    };
+
+    let mut rom = std::borrow::Cow::Borrowed(rom);
+    for (name, version) in requirements.iter() {
+        let raw = fs::read(bps_path(patches_path, name, version, game)?)?;
+        rom = std::borrow::Cow::Owned(bps::apply(&rom, &raw)?);
+    }
+
+    let raw = fs::read(path)?;
+    Ok(bps::apply(&rom, &raw)?)
+}
+
+/// Creates a version of a patch for a game from a modified ROM, so patch authors can try out their changes without external tools.
+///
+/// If the patch doesn't exist yet, it's created with a template info.toml that should be filled in before publishing.
+pub fn create_version_from_rom(
+    patches_path: &std::path::Path,
+    patch_name: &str,
+    patch_version: &semver::Version,
+    game: &'static (dyn game::Game + Send + Sync),
+    base_rom: &[u8],
+    modified_rom: &[u8],
+) -> Result<(), anyhow::Error> {
+    // Only a plain directory name will do: anything else, e.g. `..` or an absolute path, would end up outside the patches directory.
+    let patch_name_path = std::path::Path::new(patch_name);
+    if !matches!(
+        patch_name_path.components().collect::<Vec<_>>().as_slice(),
+        [std::path::Component::Normal(_)]
+    ) {
+        anyhow::bail!("invalid patch name");
+    }
+
+    let crc32 = crc32fast::hash(base_rom);
+    if crc32 != game.expected_crc32() {
+        anyhow::bail!(
+            "mismatched crc32 for base rom: expected {:08x}, got {:08x}",
+            game.expected_crc32(),
+            crc32
+        );
+    }
+
+    let patch_path = patches_path.join(patch_name_path);
+    let version_path = patch_path.join(format!("v{}", patch_version));
+    fs::create_dir_all(&version_path)?;
+    fs::write(
+        version_path.join(bps_filename(game)),
+        bps::create(base_rom, modified_rom),
     )?;
-    Ok(bps::apply(rom, &raw)?)
+
+    let quoted_name = toml::Value::String(patch_name.to_string()).to_string();
+    let version_section = format!(
+        "[versions.\"{}\"]\nnetplay_compatibility = {}\n",
+        patch_version, quoted_name
+    );
+    let info_path = patch_path.join("info.toml");
+    match fs::read(&info_path) {
+        Ok(raw) => {
+            let info = toml::from_slice::<Metadata>(&raw)?;
+            if !info.versions.contains_key(&patch_version.to_string()) {
+                let mut raw = String::from_utf8(raw)?;
+                if !raw.ends_with('\n') {
+                    raw.push('\n');
+                }
+                raw.push('\n');
+                raw.push_str(&version_section);
+                fs::write(&info_path, raw)?;
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            fs::write(
+                &info_path,
+                format!("[patch]\ntitle = {}\nauthors = []\n\n{}", quoted_name, version_section),
+            )?;
+        }
+        Err(e) => {
+            return Err(e.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Why a patch archive couldn't be installed.
+#[derive(thiserror::Error, Debug)]
+pub enum InstallError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("not a valid zip archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("{0} would be extracted outside of the patch's folder")]
+    UnsafePath(String),
+
+    #[error("no info.toml found: it must be at the top of the archive, or in a single folder named after the patch")]
+    MissingInfo,
+
+    #[error("\"{0}\" can't be used as a patch name")]
+    InvalidName(String),
+
+    #[error("more than one patch found, in: {}", .0.join(", "))]
+    MultiplePatches(Vec<String>),
+
+    #[error("info.toml is invalid: {0}")]
+    InvalidInfo(toml::de::Error),
+
+    #[error("version \"{0}\" in info.toml is not a valid version")]
+    InvalidVersion(String),
+
+    #[error("version {0} in info.toml has no patches: they must be in a v{0} folder")]
+    MissingVersion(String),
+
+    #[error("{0} is not named like a patch: expected the ROM code and revision, e.g. BR6E_00.bps")]
+    InvalidPatchFilename(String),
+
+    #[error("{0} is not in a folder for any version in info.toml")]
+    UnexpectedPatch(String),
+}
+
+/// Installs a patch from a zip archive into the patches folder, replacing any existing patch with the same name.
+///
+/// The archive must either have info.toml at the top, in which case the patch is named after the archive, or in a single folder that the patch is named after. Everything is checked before anything is extracted. Returns the name of the installed patch.
+pub fn install_from_zip(
+    patches_path: &std::path::Path,
+    archive_path: &std::path::Path,
+) -> Result<String, InstallError> {
+    let mut archive = zip::ZipArchive::new(std::io::BufReader::new(fs::open(archive_path)?))?;
+
+    let mut paths = vec![];
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        let path = if let Some(path) = entry.enclosed_name() {
+            path.to_path_buf()
+        } else {
+            return Err(InstallError::UnsafePath(entry.name().to_string()));
+        };
+        paths.push((i, path, entry.is_file()));
+    }
+
+    let roots = paths
+        .iter()
+        .filter(|(_, path, is_file)| *is_file && path.file_name() == Some(std::ffi::OsStr::new("info.toml")))
+        .filter_map(|(i, path, _)| path.parent().map(|parent| (*i, parent.to_path_buf())))
+        .filter(|(_, parent)| parent.components().count() <= 1)
+        .collect::<Vec<_>>();
+    let (info_index, root) = match &roots[..] {
+        [] => {
+            return Err(InstallError::MissingInfo);
+        }
+        [(info_index, root)] => (*info_index, root.clone()),
+        roots => {
+            return Err(InstallError::MultiplePatches(
+                roots
+                    .iter()
+                    .map(|(_, root)| root.display().to_string())
+                    .sorted()
+                    .collect(),
+            ));
+        }
+    };
+
+    let name = if let Some(name) = root.file_name() {
+        name.to_string_lossy().to_string()
+    } else {
+        archive_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    if name.is_empty() || name.starts_with('.') {
+        return Err(InstallError::InvalidName(name));
+    }
+
+    let info = {
+        let mut raw = vec![];
+        std::io::Read::read_to_end(&mut archive.by_index(info_index)?, &mut raw)?;
+        toml::from_slice::<Metadata>(&raw).map_err(InstallError::InvalidInfo)?
+    };
+
+    let mut version_dirs = std::collections::HashMap::new();
+    for v in info.versions.keys() {
+        let sv = semver::Version::parse(v).map_err(|_| InstallError::InvalidVersion(v.clone()))?;
+        if sv.to_string() != *v {
+            return Err(InstallError::InvalidVersion(v.clone()));
+        }
+        version_dirs.insert(std::path::PathBuf::from(format!("v{}", sv)), (v.clone(), 0usize));
+    }
+
+    let mut files = vec![];
+    for (i, path, is_file) in paths {
+        let relative_path = if let Ok(relative_path) = path.strip_prefix(&root) {
+            relative_path.to_path_buf()
+        } else {
+            continue;
+        };
+        if !is_file {
+            continue;
+        }
+
+        if relative_path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("bps"))
+            .unwrap_or(false)
+        {
+            let filename = relative_path
+                .file_name()
+                .map(|filename| filename.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if !PATCH_FILENAME_REGEX.is_match(&filename) {
+                return Err(InstallError::InvalidPatchFilename(path.display().to_string()));
+            }
+            match relative_path.parent().and_then(|parent| version_dirs.get_mut(parent)) {
+                Some((_, count)) => {
+                    *count += 1;
+                }
+                None => {
+                    return Err(InstallError::UnexpectedPatch(path.display().to_string()));
+                }
+            }
+        }
+
+        files.push((i, relative_path));
+    }
+
+    if let Some((v, _)) = version_dirs
+        .values()
+        .filter(|(_, count)| *count == 0)
+        .min_by(|(a, _), (b, _)| a.cmp(b))
+    {
+        return Err(InstallError::MissingVersion(v.clone()));
+    }
+
+    fs::create_dir_all(patches_path)?;
+    let tmp_dir = tempfile::Builder::new()
+        .prefix(".install-")
+        .tempdir_in(fs::extended(patches_path))?;
+    for (i, relative_path) in files {
+        // Extended paths don't treat / as a separator, so the archive's path has to be joined on a component at a time.
+        let output_path = relative_path
+            .components()
+            .fold(tmp_dir.path().to_path_buf(), |path, component| path.join(component));
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        std::io::copy(&mut archive.by_index(i)?, &mut fs::create(&output_path)?)?;
+    }
+
+    let patch_path = fs::extended(&patches_path.join(&name)).into_owned();
+    match std::fs::remove_dir_all(&patch_path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(e.into());
+        }
+    }
+    let tmp_path = tmp_dir.into_path();
+    if let Err(e) = std::fs::rename(&tmp_path, &patch_path) {
+        let _ = std::fs::remove_dir_all(&tmp_path);
+        return Err(e.into());
+    }
+
+    log::info!("installed patch {} from {}", name, archive_path.display());
+    Ok(name)
 }
@@ -60,6 +60,11 @@ fn read_signed_vlq(buf: &mut impl std::io::Read) -> Option<isize> {
 }
 
 pub fn apply(src: &[u8], mut patch: &[u8]) -> Result<Vec<u8>, Error> {
+    // The header and the trailer alone take up this much.
+    if patch.len() < 4 + 12 {
+        return Err(Error::UnexpectedPatchEOF);
+    }
+
     let actual_patch_checksum = crc32fast::hash(&patch[..patch.len() - 4]);
 
     // string "BPS1"
@@ -166,3 +171,245 @@ pub fn apply(src: &[u8], mut patch: &[u8]) -> Result<Vec<u8>, Error> {
 
     Ok(tgt)
 }
+
+fn write_vlq(buf: &mut Vec<u8>, mut data: usize) {
+    loop {
+        let x = (data & 0x7f) as u8;
+        data >>= 7;
+        if data == 0 {
+            buf.push(0x80 | x);
+            break;
+        }
+        buf.push(x);
+        data -= 1;
+    }
+}
+
+fn write_signed_vlq(buf: &mut Vec<u8>, data: isize) {
+    write_vlq(buf, (data.unsigned_abs() << 1) | if data < 0 { 1 } else { 0 });
+}
+
+fn write_command(buf: &mut Vec<u8>, action: u8, len: usize) {
+    write_vlq(buf, ((len - 1) << 2) | action as usize);
+}
+
+/// Matches shorter than this cost more to encode than the literal bytes they replace.
+const MIN_MATCH: usize = 4;
+
+/// Once a match at the same offset in the source is this long, we stop looking for better ones.
+const GOOD_MATCH: usize = 64;
+
+/// How many earlier occurrences of a hash are tried when looking for a match.
+const MAX_CHAIN: usize = 32;
+
+const HASH_BITS: u32 = 20;
+
+/// A hash chain index of where each MIN_MATCH byte sequence occurs in a buffer.
+struct Index {
+    heads: Vec<u32>,
+    prevs: Vec<u32>,
+}
+
+impl Index {
+    const NONE: u32 = u32::MAX;
+
+    fn new(len: usize) -> Self {
+        Self {
+            heads: vec![Self::NONE; 1 << HASH_BITS],
+            prevs: vec![Self::NONE; len],
+        }
+    }
+
+    fn hash(buf: &[u8]) -> usize {
+        (u32::from_le_bytes(buf[..MIN_MATCH].try_into().unwrap()).wrapping_mul(0x9e3779b1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, buf: &[u8], pos: usize) {
+        if pos + MIN_MATCH > buf.len() {
+            return;
+        }
+        let h = Self::hash(&buf[pos..]);
+        self.prevs[pos] = self.heads[h];
+        self.heads[h] = pos as u32;
+    }
+
+    fn candidates<'a>(&'a self, needle: &[u8]) -> impl Iterator<Item = usize> + 'a {
+        let mut next = if needle.len() >= MIN_MATCH {
+            self.heads[Self::hash(needle)]
+        } else {
+            Self::NONE
+        };
+        std::iter::from_fn(move || {
+            if next == Self::NONE {
+                return None;
+            }
+            let pos = next;
+            next = self.prevs[pos as usize];
+            Some(pos as usize)
+        })
+        .take(MAX_CHAIN)
+    }
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count()
+}
+
+enum Action {
+    SourceRead,
+    SourceCopy(usize),
+    TargetCopy(usize),
+}
+
+/// Creates a patch that turns src into tgt.
+///
+/// Runs of bytes that are unchanged in place are encoded as source reads, and bytes that occur elsewhere in the source or earlier in the target are encoded as copies, so patches for typical ROM hacks stay small.
+pub fn create(src: &[u8], tgt: &[u8]) -> Vec<u8> {
+    let mut patch = b"BPS1".to_vec();
+    write_vlq(&mut patch, src.len());
+    write_vlq(&mut patch, tgt.len());
+    write_vlq(&mut patch, 0);
+
+    let mut src_index = Index::new(src.len());
+    for pos in 0..src.len() {
+        src_index.insert(src, pos);
+    }
+    let mut tgt_index = Index::new(tgt.len());
+
+    let mut src_rel_offset = 0;
+    let mut tgt_rel_offset = 0;
+    let mut literal_start = 0;
+    let mut pos = 0;
+
+    while pos < tgt.len() {
+        let needle = &tgt[pos..];
+
+        let mut best = (
+            Action::SourceRead,
+            common_prefix_len(src.get(pos..).unwrap_or(&[]), needle),
+        );
+        if best.1 < GOOD_MATCH {
+            for candidate in src_index.candidates(needle) {
+                let len = common_prefix_len(&src[candidate..], needle);
+                if len > best.1 {
+                    best = (Action::SourceCopy(candidate), len);
+                }
+            }
+            // Target copies may overlap the bytes being written, since the decoder copies byte by byte.
+            for candidate in tgt_index.candidates(needle) {
+                let len = common_prefix_len(&tgt[candidate..], needle);
+                if len > best.1 {
+                    best = (Action::TargetCopy(candidate), len);
+                }
+            }
+        }
+
+        let (action, len) = best;
+        if len < MIN_MATCH {
+            tgt_index.insert(tgt, pos);
+            pos += 1;
+            continue;
+        }
+
+        if literal_start < pos {
+            write_command(&mut patch, 1, pos - literal_start);
+            patch.extend_from_slice(&tgt[literal_start..pos]);
+        }
+
+        match action {
+            Action::SourceRead => {
+                write_command(&mut patch, 0, len);
+            }
+            Action::SourceCopy(offset) => {
+                write_command(&mut patch, 2, len);
+                write_signed_vlq(&mut patch, offset as isize - src_rel_offset as isize);
+                src_rel_offset = offset + len;
+            }
+            Action::TargetCopy(offset) => {
+                write_command(&mut patch, 3, len);
+                write_signed_vlq(&mut patch, offset as isize - tgt_rel_offset as isize);
+                tgt_rel_offset = offset + len;
+            }
+        }
+
+        for i in pos..pos + len {
+            tgt_index.insert(tgt, i);
+        }
+        pos += len;
+        literal_start = pos;
+    }
+
+    if literal_start < tgt.len() {
+        write_command(&mut patch, 1, tgt.len() - literal_start);
+        patch.extend_from_slice(&tgt[literal_start..]);
+    }
+
+    patch.extend_from_slice(&crc32fast::hash(src).to_le_bytes());
+    patch.extend_from_slice(&crc32fast::hash(tgt).to_le_bytes());
+    let patch_checksum = crc32fast::hash(&patch);
+    patch.extend_from_slice(&patch_checksum.to_le_bytes());
+    patch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes that don't repeat within a few bytes of each other, so matches are only found where they're meant to be.
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    fn assert_round_trips(src: &[u8], tgt: &[u8]) {
+        let patch = create(src, tgt);
+        assert_eq!(apply(src, &patch).unwrap(), tgt);
+    }
+
+    #[test]
+    fn test_round_trip_empty() {
+        assert_round_trips(&[], &[]);
+        assert_round_trips(&[], &noise(100, 1));
+        assert_round_trips(&noise(100, 1), &[]);
+    }
+
+    #[test]
+    fn test_round_trip_identical() {
+        let src = noise(4096, 2);
+        assert_round_trips(&src, &src);
+    }
+
+    #[test]
+    fn test_round_trip_shifted() {
+        let src = noise(4096, 3);
+        let mut tgt = noise(7, 4);
+        tgt.extend_from_slice(&src[..2048]);
+        tgt.extend_from_slice(&src[2049..]);
+        assert_round_trips(&src, &tgt);
+    }
+
+    #[test]
+    fn test_round_trip_appended() {
+        let src = noise(4096, 5);
+        let mut tgt = src.clone();
+        tgt.extend_from_slice(&noise(1000, 6));
+        // Repeats of what was just written are copied from the target.
+        tgt.extend_from_within(4096..5096);
+        assert_round_trips(&src, &tgt);
+    }
+
+    #[test]
+    fn test_apply_rejects_wrong_source() {
+        let src = noise(256, 7);
+        let patch = create(&src, &noise(256, 8));
+        assert!(matches!(
+            apply(&noise(256, 9), &patch),
+            Err(Error::InvalidSourceChecksum(_))
+        ));
+    }
+}
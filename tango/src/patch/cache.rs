@@ -0,0 +1,158 @@
+use crate::{fs, game, scanner};
+
+/// How many patched ROMs to keep around. Each one is as large as the ROM it was made from, so this only needs to cover the local and remote selections plus a little switching back and forth.
+const CAPACITY: usize = 4;
+
+#[derive(Clone, PartialEq, Eq)]
+struct Key {
+    game: &'static (dyn game::Game + Send + Sync),
+    patch_name: String,
+    version: semver::Version,
+}
+
+struct Entry {
+    key: Key,
+    /// The ROM the patch was applied to. Entries are only reused for the very same ROM, so a rescanned ROM is patched again.
+    base: std::sync::Arc<[u8]>,
+    stamp: Option<scanner::FileStamp>,
+    rom: std::sync::Arc<[u8]>,
+}
+
+/// Patched ROMs, shared between everything that needs one, so the same patch applied to the same ROM is only held in memory once.
+///
+/// Only the most recently used few are kept, and entries are dropped if the patch file changes.
+#[derive(Clone)]
+pub struct PatchedRoms {
+    entries: std::sync::Arc<parking_lot::Mutex<std::collections::VecDeque<Entry>>>,
+}
+
+impl PatchedRoms {
+    pub fn new() -> Self {
+        Self {
+            entries: std::sync::Arc::new(parking_lot::Mutex::new(std::collections::VecDeque::new())),
+        }
+    }
+
+    /// Gets a ROM with a patch version applied, applying it only if it isn't already cached.
+    pub fn get_or_apply(
+        &self,
+        rom: &std::sync::Arc<[u8]>,
+        game: &'static (dyn game::Game + Send + Sync),
+        patches_path: &std::path::Path,
+        patch_name: &str,
+        version: &semver::Version,
+    ) -> Result<std::sync::Arc<[u8]>, anyhow::Error> {
+        let key = Key {
+            game,
+            patch_name: patch_name.to_string(),
+            version: version.clone(),
+        };
+        let stamp = fs::metadata(super::bps_path(patches_path, patch_name, version, game)?)
+            .ok()
+            .and_then(|metadata| scanner::FileStamp::of(&metadata));
+
+        let mut entries = self.entries.lock();
+        if let Some(i) = entries.iter().position(|entry| entry.key == key) {
+            let entry = entries.remove(i).unwrap();
+            if std::sync::Arc::ptr_eq(&entry.base, rom) && entry.stamp == stamp {
+                let patched = entry.rom.clone();
+                entries.push_front(entry);
+                return Ok(patched);
+            }
+        }
+        drop(entries);
+
+        let patched: std::sync::Arc<[u8]> =
+            super::apply_patch_from_disk(rom, game, patches_path, patch_name, version)?.into();
+
+        let mut entries = self.entries.lock();
+        entries.retain(|entry| entry.key != key);
+        entries.push_front(Entry {
+            key,
+            base: rom.clone(),
+            stamp,
+            rom: patched.clone(),
+        });
+        entries.truncate(CAPACITY);
+        Ok(patched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::{self, bps};
+
+    /// Writes a version 1.0.0 of each patch, each of which changes a different byte of the ROM.
+    fn write_patches(
+        patches_path: &std::path::Path,
+        game: &'static (dyn game::Game + Send + Sync),
+        rom: &[u8],
+        n: usize,
+    ) {
+        for i in 0..n {
+            let mut patched = rom.to_vec();
+            patched[i] ^= 0xff;
+            let path = patch::bps_path(
+                patches_path,
+                &format!("patch{}", i),
+                &semver::Version::new(1, 0, 0),
+                game,
+            )
+            .unwrap();
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, bps::create(rom, &patched)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reuses_patched_rom() {
+        let dir = tempfile::tempdir().unwrap();
+        let game = game::GAMES[0];
+        let rom: std::sync::Arc<[u8]> = vec![0u8; 1024].into();
+        write_patches(dir.path(), game, &rom, 1);
+
+        let patched_roms = PatchedRoms::new();
+        let version = semver::Version::new(1, 0, 0);
+        let first = patched_roms
+            .get_or_apply(&rom, game, dir.path(), "patch0", &version)
+            .unwrap();
+        assert_eq!(first[0], 0xff);
+        let second = patched_roms
+            .get_or_apply(&rom, game, dir.path(), "patch0", &version)
+            .unwrap();
+        assert!(std::sync::Arc::ptr_eq(&first, &second));
+
+        // A different ROM, even with the same contents, is patched again and replaces the old entry.
+        let rescanned: std::sync::Arc<[u8]> = rom.to_vec().into();
+        let third = patched_roms
+            .get_or_apply(&rescanned, game, dir.path(), "patch0", &version)
+            .unwrap();
+        assert!(!std::sync::Arc::ptr_eq(&first, &third));
+        assert_eq!(first, third);
+        assert_eq!(patched_roms.entries.lock().len(), 1);
+    }
+
+    #[test]
+    fn test_toggling_patches_stays_bounded() {
+        const NUM_PATCHES: usize = CAPACITY * 2;
+
+        let dir = tempfile::tempdir().unwrap();
+        let game = game::GAMES[0];
+        let rom: std::sync::Arc<[u8]> = vec![0u8; 1024].into();
+        write_patches(dir.path(), game, &rom, NUM_PATCHES);
+
+        let patched_roms = PatchedRoms::new();
+        let version = semver::Version::new(1, 0, 0);
+        for i in 0..NUM_PATCHES * 10 {
+            // Switch back and forth between two patches most of the time, and wander off to the others now and then.
+            let patch = if i % 3 == 0 { i % NUM_PATCHES } else { i % 2 };
+            let patched = patched_roms
+                .get_or_apply(&rom, game, dir.path(), &format!("patch{}", patch), &version)
+                .unwrap();
+            assert_eq!(patched[patch], 0xff);
+            assert!(patched_roms.entries.lock().len() <= CAPACITY);
+        }
+        assert_eq!(patched_roms.entries.lock().len(), CAPACITY);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{fs, rom};
+
+/// Display names a patch ships for a single language, overriding the names decoded from the ROM.
+///
+/// These are read from `strings/<language>.toml` in the patch version directory. Each table is keyed by index, and entries that are missing fall back to the name from the ROM.
+#[derive(Default, Debug, Clone)]
+pub struct StringOverrides {
+    pub chips: std::collections::HashMap<usize, String>,
+    pub navicust_parts: std::collections::HashMap<usize, String>,
+    pub modcard56s: std::collections::HashMap<usize, String>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+struct RawStringOverrides {
+    chips: std::collections::HashMap<String, String>,
+    navicust_parts: std::collections::HashMap<String, String>,
+    modcard56s: std::collections::HashMap<String, String>,
+}
+
+fn parse_table(
+    table: std::collections::HashMap<String, String>,
+) -> Result<std::collections::HashMap<usize, String>, anyhow::Error> {
+    table
+        .into_iter()
+        .map(|(k, v)| Ok((k.parse::<usize>().map_err(|e| anyhow::anyhow!("{}: {}", k, e))?, v)))
+        .collect()
+}
+
+impl StringOverrides {
+    /// Loads the string overrides for a language from a patch version directory, if the patch ships any.
+    pub fn load(
+        version_path: &std::path::Path,
+        language: &unic_langid::LanguageIdentifier,
+    ) -> Result<Option<Self>, anyhow::Error> {
+        let raw = match fs::read(version_path.join("strings").join(format!("{}.toml", language))) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(e.into());
+            }
+        };
+        let raw = toml::from_slice::<RawStringOverrides>(&raw)?;
+        Ok(Some(Self {
+            chips: parse_table(raw.chips)?,
+            navicust_parts: parse_table(raw.navicust_parts)?,
+            modcard56s: parse_table(raw.modcard56s)?,
+        }))
+    }
+}
+
+/// Assets with names replaced by a patch's string overrides.
+pub struct Assets {
+    inner: Box<dyn rom::Assets + Send + Sync>,
+    overrides: StringOverrides,
+}
+
+impl Assets {
+    pub fn new(inner: Box<dyn rom::Assets + Send + Sync>, overrides: StringOverrides) -> Self {
+        Self { inner, overrides }
+    }
+}
+
+struct Chip<'a> {
+    inner: Box<dyn rom::Chip + 'a>,
+    name: Option<&'a String>,
+}
+
+impl<'a> rom::Chip for Chip<'a> {
+    fn name(&self) -> String {
+        self.name.cloned().unwrap_or_else(|| self.inner.name())
+    }
+
+    fn description(&self) -> String {
+        self.inner.description()
+    }
+
+    fn icon(&self) -> image::RgbaImage {
+        self.inner.icon()
+    }
+
+    fn image(&self) -> image::RgbaImage {
+        self.inner.image()
+    }
+
+    fn codes(&self) -> Vec<u8> {
+        self.inner.codes()
+    }
+
+    fn element(&self) -> usize {
+        self.inner.element()
+    }
+
+    fn class(&self) -> rom::ChipClass {
+        self.inner.class()
+    }
+
+    fn dark(&self) -> bool {
+        self.inner.dark()
+    }
+
+    fn mb(&self) -> u8 {
+        self.inner.mb()
+    }
+
+    fn damage(&self) -> u32 {
+        self.inner.damage()
+    }
+}
+
+struct NavicustPart<'a> {
+    inner: Box<dyn rom::NavicustPart + 'a>,
+    name: Option<&'a String>,
+}
+
+impl<'a> rom::NavicustPart for NavicustPart<'a> {
+    fn name(&self) -> String {
+        self.name.cloned().unwrap_or_else(|| self.inner.name())
+    }
+
+    fn description(&self) -> String {
+        self.inner.description()
+    }
+
+    fn color(&self) -> Option<rom::NavicustPartColor> {
+        self.inner.color()
+    }
+
+    fn is_solid(&self) -> bool {
+        self.inner.is_solid()
+    }
+
+    fn compressed_bitmap(&self) -> rom::NavicustBitmap {
+        self.inner.compressed_bitmap()
+    }
+
+    fn uncompressed_bitmap(&self) -> rom::NavicustBitmap {
+        self.inner.uncompressed_bitmap()
+    }
+}
+
+struct Modcard56<'a> {
+    inner: Box<dyn rom::Modcard56 + 'a>,
+    name: Option<&'a String>,
+}
+
+impl<'a> rom::Modcard56 for Modcard56<'a> {
+    fn name(&self) -> String {
+        self.name.cloned().unwrap_or_else(|| self.inner.name())
+    }
+
+    fn mb(&self) -> u8 {
+        self.inner.mb()
+    }
+
+    fn effects(&self) -> Vec<rom::Modcard56Effect> {
+        self.inner.effects()
+    }
+}
+
+impl rom::Assets for Assets {
+    fn chip<'a>(&'a self, id: usize) -> Option<Box<dyn rom::Chip + 'a>> {
+        let inner = self.inner.chip(id)?;
+        Some(Box::new(Chip {
+            inner,
+            name: self.overrides.chips.get(&id),
+        }))
+    }
+
+    fn num_chips(&self) -> usize {
+        self.inner.num_chips()
+    }
+
+    fn element_icon(&self, id: usize) -> Option<image::RgbaImage> {
+        self.inner.element_icon(id)
+    }
+
+    fn modcard56<'a>(&'a self, id: usize) -> Option<Box<dyn rom::Modcard56 + 'a>> {
+        let inner = self.inner.modcard56(id)?;
+        Some(Box::new(Modcard56 {
+            inner,
+            name: self.overrides.modcard56s.get(&id),
+        }))
+    }
+
+    fn num_modcard56s(&self) -> usize {
+        self.inner.num_modcard56s()
+    }
+
+    fn modcard4<'a>(&'a self, id: usize) -> Option<Box<dyn rom::Modcard4 + 'a>> {
+        self.inner.modcard4(id)
+    }
+
+    fn num_modcard4s(&self) -> usize {
+        self.inner.num_modcard4s()
+    }
+
+    fn navicust_part<'a>(&'a self, id: usize, variant: usize) -> Option<Box<dyn rom::NavicustPart + 'a>> {
+        let inner = self.inner.navicust_part(id, variant)?;
+        Some(Box::new(NavicustPart {
+            inner,
+            name: self.overrides.navicust_parts.get(&id),
+        }))
+    }
+
+    fn num_navicust_parts(&self) -> (usize, usize) {
+        self.inner.num_navicust_parts()
+    }
+
+    fn navicust_bg(&self) -> Option<image::Rgba<u8>> {
+        self.inner.navicust_bg()
+    }
+
+    fn style<'a>(&'a self, id: usize) -> Option<Box<dyn rom::Style + 'a>> {
+        self.inner.style(id)
+    }
+
+    fn num_styles(&self) -> usize {
+        self.inner.num_styles()
+    }
+
+    fn navi<'a>(&'a self, id: usize) -> Option<Box<dyn rom::Navi + 'a>> {
+        self.inner.navi(id)
+    }
+
+    fn num_navis(&self) -> usize {
+        self.inner.num_navis()
+    }
+}
+
+/// Applies a patch version's string overrides for the given language to its assets, if it ships any.
+pub fn apply(
+    assets: Box<dyn rom::Assets + Send + Sync>,
+    version: &super::Version,
+    language: &unic_langid::LanguageIdentifier,
+) -> Box<dyn rom::Assets + Send + Sync> {
+    match StringOverrides::load(&version.path, language) {
+        Ok(Some(overrides)) => Box::new(Assets::new(assets, overrides)),
+        Ok(None) => assets,
+        Err(e) => {
+            log::warn!("{}: failed to load string overrides: {:?}", version.path.display(), e);
+            assets
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{fs, game, rom, scanner};
+
+/// What happened when a patch was applied to a ROM.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Applies,
+    /// The ROM isn't the one the patch was made from, e.g. it's a bad dump or already modified.
+    SourceChecksumMismatch,
+    /// The patch applied, but what came out isn't what the patch author got.
+    TargetChecksumMismatch,
+    /// The patch couldn't be read or is malformed.
+    Failed(String),
+}
+
+impl Outcome {
+    pub fn applies(&self) -> bool {
+        *self == Outcome::Applies
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    patch_name: String,
+    version: semver::Version,
+    rom_crc32: u32,
+}
+
+struct Entry {
+    path: std::path::PathBuf,
+    stamp: Option<scanner::FileStamp>,
+    outcome: Outcome,
+}
+
+impl Entry {
+    /// Whether the patch file is still the one this entry was recorded for.
+    fn is_current(&self) -> bool {
+        let stamp = fs::metadata(&self.path)
+            .ok()
+            .and_then(|metadata| scanner::FileStamp::of(&metadata));
+        stamp == self.stamp
+    }
+}
+
+struct Inner {
+    entries: std::collections::HashMap<Key, Entry>,
+    /// The ROMs each patch version was last validated against, by their checksums.
+    runs: std::collections::HashMap<(String, semver::Version), Vec<(&'static (dyn game::Game + Send + Sync), u32)>>,
+    running: std::collections::HashSet<(String, semver::Version)>,
+}
+
+/// Applies patches to the user's ROMs without keeping the results, to find out ahead of time whether they'll work.
+///
+/// Outcomes are remembered per patch version and ROM checksum, and forgotten if the patch file changes.
+#[derive(Clone)]
+pub struct Validator {
+    inner: std::sync::Arc<parking_lot::Mutex<Inner>>,
+}
+
+fn validate_one(
+    rom: &[u8],
+    game: &'static (dyn game::Game + Send + Sync),
+    patches_path: &std::path::Path,
+    patch_name: &str,
+    version: &semver::Version,
+) -> Outcome {
+    match super::apply_patch_from_disk(rom, game, patches_path, patch_name, version) {
+        Ok(_) => Outcome::Applies,
+        Err(e) => match e.downcast_ref::<super::bps::Error>() {
+            Some(super::bps::Error::InvalidSourceChecksum(_)) => Outcome::SourceChecksumMismatch,
+            Some(super::bps::Error::InvalidTargetChecksum(_)) => Outcome::TargetChecksumMismatch,
+            _ => Outcome::Failed(e.to_string()),
+        },
+    }
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self {
+            inner: std::sync::Arc::new(parking_lot::Mutex::new(Inner {
+                entries: std::collections::HashMap::new(),
+                runs: std::collections::HashMap::new(),
+                running: std::collections::HashSet::new(),
+            })),
+        }
+    }
+
+    /// Whether nothing has been validated yet, in which case there's no point checksumming ROMs to look anything up.
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().entries.is_empty()
+    }
+
+    pub fn is_running(&self, patch_name: &str, version: &semver::Version) -> bool {
+        self.inner
+            .lock()
+            .running
+            .contains(&(patch_name.to_string(), version.clone()))
+    }
+
+    /// Gets the outcome of applying a patch version to the ROM with the given checksum, if it's been validated since the patch last changed.
+    pub fn outcome(&self, patch_name: &str, version: &semver::Version, rom_crc32: u32) -> Option<Outcome> {
+        let inner = self.inner.lock();
+        let entry = inner.entries.get(&Key {
+            patch_name: patch_name.to_string(),
+            version: version.clone(),
+            rom_crc32,
+        })?;
+        if !entry.is_current() {
+            return None;
+        }
+        Some(entry.outcome.clone())
+    }
+
+    /// Gets the outcomes for each game the patch version was last validated against, if it ever was. Outcomes that are stale are `None`.
+    pub fn last_run(
+        &self,
+        patch_name: &str,
+        version: &semver::Version,
+    ) -> Option<Vec<(&'static (dyn game::Game + Send + Sync), Option<Outcome>)>> {
+        let run = self
+            .inner
+            .lock()
+            .runs
+            .get(&(patch_name.to_string(), version.clone()))
+            .cloned()?;
+        Some(
+            run.into_iter()
+                .map(|(game, rom_crc32)| (game, self.outcome(patch_name, version, rom_crc32)))
+                .collect(),
+        )
+    }
+
+    /// Validates a patch version against every ROM we have that it supports, in the background.
+    pub fn validate(
+        &self,
+        patches_path: &std::path::Path,
+        patch_name: &str,
+        version: &semver::Version,
+        supported_games: Vec<&'static (dyn game::Game + Send + Sync)>,
+        roms_scanner: rom::Scanner,
+        on_done: impl FnOnce() + Send + 'static,
+    ) {
+        let run_key = (patch_name.to_string(), version.clone());
+        if !self.inner.lock().running.insert(run_key.clone()) {
+            return;
+        }
+
+        let inner = self.inner.clone();
+        let patches_path = patches_path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let (patch_name, version) = &run_key;
+            let mut run = vec![];
+            for game in supported_games {
+                let rom = if let Some(rom) = roms_scanner.read().by_game.get(&game).cloned() {
+                    rom
+                } else {
+                    continue;
+                };
+
+                let path = match super::bps_path(&patches_path, patch_name, version, game) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        log::error!("failed to validate patch {} v{}: {:?}", patch_name, version, e);
+                        continue;
+                    }
+                };
+                let stamp = fs::metadata(&path)
+                    .ok()
+                    .and_then(|metadata| scanner::FileStamp::of(&metadata));
+                let outcome = validate_one(&rom, game, &patches_path, patch_name, version);
+                log::info!(
+                    "validated patch {} v{} against {:?}: {:?}",
+                    patch_name,
+                    version,
+                    game.family_and_variant(),
+                    outcome
+                );
+
+                let rom_crc32 = crc32fast::hash(&rom);
+                run.push((game, rom_crc32));
+                inner.lock().entries.insert(
+                    Key {
+                        patch_name: patch_name.clone(),
+                        version: version.clone(),
+                        rom_crc32,
+                    },
+                    Entry { path, stamp, outcome },
+                );
+            }
+
+            let mut inner = inner.lock();
+            inner.runs.insert(run_key.clone(), run);
+            inner.running.remove(&run_key);
+            drop(inner);
+            on_done();
+        });
+    }
+}
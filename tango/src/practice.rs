@@ -0,0 +1,243 @@
+use std::io::Write;
+
+use crate::{assist, game};
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct PlayerSample {
+    pub hp: Option<u16>,
+    pub invulnerability_frames: Option<u16>,
+    pub chip_in_hand: Option<Option<usize>>,
+}
+
+/// Training data read out of game memory on a single frame.
+///
+/// Fields are None where the game has no reader for them.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct Sample {
+    pub frame: u32,
+    pub tick: Option<u32>,
+    pub custom_gauge_frames_remaining: Option<u32>,
+    pub players: [PlayerSample; 2],
+    /// What the input assists did on this frame, so assisted play isn't mistaken for the player's own.
+    pub assist: assist::Applied,
+}
+
+impl Sample {
+    /// Reads a sample, if a battle is in progress.
+    pub fn read(
+        telemetry: &(dyn game::PracticeTelemetry + Send + Sync),
+        core: mgba::core::CoreMutRef,
+        assist: assist::Applied,
+    ) -> Option<Self> {
+        if !telemetry.in_battle(core) {
+            return None;
+        }
+
+        let read_player = |player_index| PlayerSample {
+            hp: telemetry.hp(core, player_index),
+            invulnerability_frames: telemetry.invulnerability_frames(core, player_index),
+            chip_in_hand: telemetry.chip_in_hand(core, player_index),
+        };
+
+        Some(Self {
+            frame: core.as_ref().frame_counter(),
+            tick: telemetry.current_tick(core),
+            custom_gauge_frames_remaining: telemetry.custom_gauge_frames_remaining(core),
+            players: [read_player(0), read_player(1)],
+            assist,
+        })
+    }
+
+    const CSV_HEADER: &'static str =
+        "frame,tick,custom_gauge_frames_remaining,p1_hp,p1_invulnerability_frames,p1_chip_in_hand,p2_hp,p2_invulnerability_frames,p2_chip_in_hand,assisted_keys,slow_motion";
+
+    fn to_csv_row(&self) -> String {
+        fn field<T: ToString>(v: Option<T>) -> String {
+            v.map(|v| v.to_string()).unwrap_or_default()
+        }
+
+        let mut fields = vec![
+            self.frame.to_string(),
+            field(self.tick),
+            field(self.custom_gauge_frames_remaining),
+        ];
+        for player in self.players.iter() {
+            fields.push(field(player.hp));
+            fields.push(field(player.invulnerability_frames));
+            fields.push(match player.chip_in_hand {
+                Some(Some(chip_id)) => chip_id.to_string(),
+                Some(None) => "none".to_string(),
+                None => "".to_string(),
+            });
+        }
+        fields.push(self.assist.keys.to_string());
+        fields.push(self.assist.slow_motion.to_string());
+        fields.join(",")
+    }
+}
+
+struct Log {
+    path: std::path::PathBuf,
+    writer: std::io::BufWriter<std::fs::File>,
+}
+
+/// Keeps the most recent sample for the overlay, and optionally logs every sample to a CSV file.
+pub struct Recorder {
+    telemetry: &'static (dyn game::PracticeTelemetry + Send + Sync),
+    latest: parking_lot::Mutex<Option<Sample>>,
+    log: parking_lot::Mutex<Option<Log>>,
+}
+
+impl Recorder {
+    pub fn new(telemetry: &'static (dyn game::PracticeTelemetry + Send + Sync)) -> Self {
+        Self {
+            telemetry,
+            latest: parking_lot::Mutex::new(None),
+            log: parking_lot::Mutex::new(None),
+        }
+    }
+
+    /// Samples the current frame, along with what the input assists did on it. This must be called from the emulator thread, once per frame.
+    pub fn record(&self, core: mgba::core::CoreMutRef, assist: assist::Applied) {
+        let sample = Sample::read(self.telemetry, core, assist);
+
+        if let Some(sample) = sample.as_ref() {
+            let mut current_log = self.log.lock();
+            if let Some(l) = current_log.as_mut() {
+                if let Err(e) = writeln!(l.writer, "{}", sample.to_csv_row()) {
+                    log::error!("failed to write practice log {}: {:?}", l.path.display(), e);
+                    *current_log = None;
+                }
+            }
+        }
+
+        *self.latest.lock() = sample;
+    }
+
+    pub fn latest(&self) -> Option<Sample> {
+        self.latest.lock().clone()
+    }
+
+    pub fn start_log(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(writer, "{}", Sample::CSV_HEADER)?;
+        log::info!("logging practice samples to {}", path.display());
+        *self.log.lock() = Some(Log {
+            path: path.to_path_buf(),
+            writer,
+        });
+        Ok(())
+    }
+
+    pub fn stop_log(&self) {
+        if let Some(mut l) = self.log.lock().take() {
+            if let Err(e) = l.writer.flush() {
+                log::error!("failed to flush practice log {}: {:?}", l.path.display(), e);
+            }
+        }
+    }
+
+    pub fn log_path(&self) -> Option<std::path::PathBuf> {
+        self.log.lock().as_ref().map(|l| l.path.clone())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stop_log();
+    }
+}
+
+/// A change to game state made from the practice panel.
+#[derive(Clone, Copy, Debug)]
+pub enum Manipulation {
+    FillCustomGauge,
+    SetHp { player_index: usize, hp: u16 },
+    SetChipInHand { player_index: usize, chip_id: u16 },
+    FreezeRng(bool),
+}
+
+/// Queues up manipulations from the GUI and makes them from the game's trap, where they're safe to make.
+///
+/// This only ever exists in single player sessions: a session with a match has no way to get at one.
+pub struct Manipulator {
+    manipulation: &'static (dyn game::PracticeManipulation + Send + Sync),
+    pending: parking_lot::Mutex<Vec<Manipulation>>,
+    frozen_rng_state: parking_lot::Mutex<Option<Vec<u32>>>,
+    freezing_rng: std::sync::atomic::AtomicBool,
+    modified: std::sync::atomic::AtomicBool,
+}
+
+impl Manipulator {
+    pub fn new(manipulation: &'static (dyn game::PracticeManipulation + Send + Sync)) -> Self {
+        Self {
+            manipulation,
+            pending: parking_lot::Mutex::new(vec![]),
+            frozen_rng_state: parking_lot::Mutex::new(None),
+            freezing_rng: std::sync::atomic::AtomicBool::new(false),
+            modified: std::sync::atomic::AtomicBool::new(false),
+        }
+    }
+
+    /// The trap to call [`Manipulator::apply`] from.
+    pub fn trap_address(&self) -> u32 {
+        self.manipulation.trap_address()
+    }
+
+    pub fn queue(&self, manipulation: Manipulation) {
+        if let Manipulation::FreezeRng(freeze) = manipulation {
+            self.freezing_rng.store(freeze, std::sync::atomic::Ordering::Relaxed);
+        }
+        self.pending.lock().push(manipulation);
+    }
+
+    /// Makes any queued manipulations, and keeps the RNG frozen if it's meant to be. This must be called from the emulator thread, at the trap.
+    pub fn apply(&self, core: mgba::core::CoreMutRef) {
+        let pending = std::mem::take(&mut *self.pending.lock());
+        let in_battle = self.manipulation.in_battle(core);
+        for manipulation in pending {
+            match manipulation {
+                Manipulation::FreezeRng(true) => {
+                    let state = self.manipulation.rng_state(core);
+                    log::info!("practice: freezing rng at {:08x?}", state);
+                    *self.frozen_rng_state.lock() = Some(state);
+                }
+                Manipulation::FreezeRng(false) => {
+                    log::info!("practice: unfreezing rng");
+                    *self.frozen_rng_state.lock() = None;
+                    continue;
+                }
+                _ if !in_battle => {
+                    log::info!("practice: not in battle, ignoring {:?}", manipulation);
+                    continue;
+                }
+                Manipulation::FillCustomGauge => {
+                    log::info!("practice: filling custom gauge");
+                    self.manipulation.fill_custom_gauge(core);
+                }
+                Manipulation::SetHp { player_index, hp } => {
+                    log::info!("practice: setting player {} hp to {}", player_index + 1, hp);
+                    self.manipulation.set_hp(core, player_index, hp);
+                }
+                Manipulation::SetChipInHand { player_index, chip_id } => {
+                    log::info!("practice: giving player {} chip #{:03}", player_index + 1, chip_id);
+                    self.manipulation.set_chip_in_hand(core, player_index, chip_id);
+                }
+            }
+            self.modified.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        if let Some(state) = self.frozen_rng_state.lock().as_ref() {
+            self.manipulation.set_rng_state(core, state);
+        }
+    }
+
+    pub fn is_rng_frozen(&self) -> bool {
+        self.freezing_rng.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Whether game state has been forced at any point. There's no telling what it would have been otherwise, so this stays set for the rest of the session.
+    pub fn is_modified(&self) -> bool {
+        self.modified.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
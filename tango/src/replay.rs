@@ -1,28 +1,25 @@
+use crate::fs;
 use crate::lockstep;
-use byteorder::ReadBytesExt;
-use byteorder::WriteBytesExt;
-use prost::Message;
-use std::io::Read;
-use std::io::Write;
-pub trait WriteSeek: std::io::Write + std::io::Seek {}
-impl<T: std::io::Write + std::io::Seek> WriteSeek for T {}
+use crate::save;
 
+pub mod dump;
 pub mod export;
+pub mod notes;
+pub mod upload;
 
-mod protos;
-mod replay10;
+pub use tango_replay::metadata;
+pub use tango_replay::Metadata;
 
-pub use protos::replay11::metadata;
-pub type Metadata = protos::replay11::Metadata;
+/// How many rollback depths to make room for up front, so recording them doesn't allocate while playing. This covers rounds of up to five minutes.
+const ROLLBACK_DEPTHS_CAPACITY: usize = 60 * 60 * 5;
 
 pub struct Writer {
-    encoder: Option<zstd::stream::write::Encoder<'static, Box<dyn WriteSeek + Send>>>,
-    num_inputs: u32,
+    path: std::path::PathBuf,
+    inner: Option<tango_replay::Writer<std::fs::File>>,
+    /// How far we rolled back for each input written so far, written out as a section once the replay is finished.
+    rollback_depths: Vec<u8>,
 }
 
-const HEADER: &[u8] = b"TOOT";
-const VERSION: u8 = 0x11;
-
 #[derive(Clone)]
 pub struct Replay {
     pub is_complete: bool,
@@ -31,34 +28,21 @@ pub struct Replay {
     pub local_state: Option<mgba::state::State>,
     pub remote_state: Option<mgba::state::State>,
     pub input_pairs: Vec<lockstep::Pair<lockstep::Input, lockstep::Input>>,
-}
-
-fn decode_metadata(version: u8, raw: &[u8]) -> Result<Metadata, std::io::Error> {
-    Ok(match version {
-        0x10 => replay10::decode_metadata(&raw[..])?,
-        0x11 => protos::replay11::Metadata::decode(&raw[..])?,
-        _ => {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("invalid version: {:02x}", version),
-            ));
-        }
-    })
+    /// How far the recording player rolled back for each input pair, if the replay recorded it.
+    pub rollback_depths: Option<Vec<u8>>,
 }
 
 pub fn read_metadata(r: &mut impl std::io::Read) -> Result<(usize, Metadata), std::io::Error> {
-    let mut header = [0u8; 4];
-    r.read_exact(&mut header)?;
-    if &header != HEADER {
-        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid header"));
-    }
+    let header = tango_replay::read_header(r)?;
+    Ok((header.num_inputs as usize, header.metadata))
+}
 
-    let version = r.read_u8()?;
-    let num_inputs = r.read_u32::<byteorder::LittleEndian>()? as usize;
-    let metadata_len = r.read_u32::<byteorder::LittleEndian>()?;
-    let mut raw = vec![0u8; metadata_len as usize];
-    r.read_exact(&mut raw[..])?;
-    Ok((num_inputs, decode_metadata(version, &raw)?))
+/// Rewrites the metadata of a replay file in place, leaving the rest of it untouched.
+///
+/// Only replays of the current version can be updated.
+pub fn update_metadata(path: &std::path::Path, f: impl FnOnce(&mut Metadata)) -> std::io::Result<()> {
+    let raw = fs::read(path)?;
+    save::write_atomically(path, &tango_replay::update_metadata(&raw, f)?)
 }
 
 impl Replay {
@@ -74,72 +58,33 @@ impl Replay {
         self
     }
 
-    pub fn decode(mut r: impl std::io::Read) -> std::io::Result<Self> {
-        let (num_inputs, metadata) = read_metadata(&mut r)?;
+    pub fn decode(r: impl std::io::Read) -> std::io::Result<Self> {
+        let mut reader = tango_replay::Reader::new(r)?;
 
-        let mut zr = zstd::stream::read::Decoder::new(r)?;
-
-        let local_player_index = zr.read_u8()?;
-
-        let input_raw_size = zr.read_u8()? as usize;
-
-        let mut local_state = vec![0u8; zr.read_u32::<byteorder::LittleEndian>()? as usize];
-        zr.read_exact(&mut local_state)?;
-        let local_state = if !local_state.is_empty() {
-            Some(mgba::state::State::from_slice(&local_state))
-        } else {
-            None
-        };
-
-        let mut remote_state = vec![0u8; zr.read_u32::<byteorder::LittleEndian>()? as usize];
-        zr.read_exact(&mut remote_state)?;
-        let remote_state = if !remote_state.is_empty() {
-            Some(mgba::state::State::from_slice(&remote_state))
-        } else {
-            None
-        };
+        let num_inputs = reader.header().num_inputs;
+        let metadata = reader.header().metadata.clone();
+        let rollback_depths = reader.header().rollback_depths.clone();
+        let local_player_index = reader.local_player_index();
+        let local_state = reader.local_state().map(mgba::state::State::from_slice);
+        let remote_state = reader.remote_state().map(mgba::state::State::from_slice);
 
         let mut input_pairs = vec![];
-
-        loop {
-            let local_tick = if let Ok(v) = zr.read_u32::<byteorder::LittleEndian>() {
-                v
-            } else {
-                break;
-            };
-            let remote_tick = if let Ok(v) = zr.read_u32::<byteorder::LittleEndian>() {
-                v
-            } else {
-                break;
+        for record in &mut reader {
+            let record = record?;
+
+            let p1_input = lockstep::Input {
+                local_tick: record.local_tick,
+                remote_tick: record.remote_tick,
+                joyflags: record.p1.joyflags,
+                packet: record.p1.packet,
             };
 
-            let mut p1_input = lockstep::Input {
-                local_tick,
-                remote_tick,
-                joyflags: if let Ok(v) = zr.read_u16::<byteorder::LittleEndian>() {
-                    v
-                } else {
-                    break;
-                },
-                packet: vec![0u8; input_raw_size],
-            };
-            if zr.read_exact(&mut p1_input.packet).is_err() {
-                break;
-            }
-
-            let mut p2_input = lockstep::Input {
-                local_tick,
-                remote_tick: local_tick,
-                joyflags: if let Ok(v) = zr.read_u16::<byteorder::LittleEndian>() {
-                    v
-                } else {
-                    break;
-                },
-                packet: vec![0u8; input_raw_size],
+            let p2_input = lockstep::Input {
+                local_tick: record.local_tick,
+                remote_tick: record.local_tick,
+                joyflags: record.p2.joyflags,
+                packet: record.p2.packet,
             };
-            if zr.read_exact(&mut p2_input.packet).is_err() {
-                break;
-            }
 
             let (local, remote) = if local_player_index == 0 {
                 (p1_input, p2_input)
@@ -157,56 +102,42 @@ impl Replay {
             local_state,
             remote_state,
             input_pairs,
+            rollback_depths,
         })
     }
 }
 
 impl Writer {
     pub fn new(
-        mut writer: Box<dyn WriteSeek + Send>,
+        path: &std::path::Path,
         metadata: Metadata,
         local_player_index: u8,
         raw_input_size: u8,
     ) -> std::io::Result<Self> {
-        writer.write_all(HEADER)?;
-        writer.write_u8(VERSION)?;
-        writer.write_u32::<byteorder::LittleEndian>(0)?;
-        let raw_metadata = metadata.encode_to_vec();
-        writer.write_u32::<byteorder::LittleEndian>(raw_metadata.len() as u32)?;
-        writer.write_all(&raw_metadata[..])?;
-        let mut encoder = zstd::Encoder::new(writer, 3)?;
-        encoder.write_u8(local_player_index)?;
-        encoder.write_u8(raw_input_size)?;
-        encoder.flush()?;
         Ok(Writer {
-            encoder: Some(encoder),
-            num_inputs: 0,
+            path: path.to_path_buf(),
+            inner: Some(tango_replay::Writer::new(
+                fs::create(path)?,
+                &metadata,
+                &[],
+                local_player_index,
+                raw_input_size,
+            )?),
+            rollback_depths: Vec::with_capacity(ROLLBACK_DEPTHS_CAPACITY),
         })
     }
 
     pub fn write_state(&mut self, state: &mgba::state::State) -> std::io::Result<()> {
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .write_u32::<byteorder::LittleEndian>(state.as_slice().len() as u32)?;
-        self.encoder.as_mut().unwrap().write_all(state.as_slice())?;
-        self.encoder.as_mut().unwrap().flush()?;
-        Ok(())
+        self.inner.as_mut().unwrap().write_state(state.as_slice())
     }
 
     pub fn write_input(
         &mut self,
         local_player_index: u8,
         ip: &lockstep::Pair<lockstep::Input, lockstep::Input>,
+        rollback_depth: u32,
     ) -> std::io::Result<()> {
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .write_u32::<byteorder::LittleEndian>(ip.local.local_tick)?;
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .write_u32::<byteorder::LittleEndian>(ip.local.remote_tick)?;
+        self.rollback_depths.push(rollback_depth.min(u8::MAX as u32) as u8);
 
         let (p1, p2) = if local_player_index == 0 {
             (&ip.local, &ip.remote)
@@ -214,34 +145,42 @@ impl Writer {
             (&ip.remote, &ip.local)
         };
 
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .write_u16::<byteorder::LittleEndian>(p1.joyflags)?;
-        self.encoder.as_mut().unwrap().write_all(&p1.packet)?;
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .write_u16::<byteorder::LittleEndian>(p2.joyflags)?;
-        self.encoder.as_mut().unwrap().write_all(&p2.packet)?;
-
-        self.num_inputs += 1;
-        Ok(())
+        self.inner.as_mut().unwrap().write_record(&tango_replay::Record {
+            local_tick: ip.local.local_tick,
+            remote_tick: ip.local.remote_tick,
+            p1: tango_replay::PlayerInput {
+                joyflags: p1.joyflags,
+                packet: p1.packet.clone(),
+            },
+            p2: tango_replay::PlayerInput {
+                joyflags: p2.joyflags,
+                packet: p2.packet.clone(),
+            },
+        })
     }
 
-    pub fn finish(mut self) -> std::io::Result<Box<dyn WriteSeek + Send>> {
-        let mut w = self.encoder.take().unwrap().finish()?;
-        w.seek(std::io::SeekFrom::Start((HEADER.len() + 1) as u64))?;
-        w.write_u32::<byteorder::LittleEndian>(self.num_inputs)?;
-        Ok(w)
+    /// Finishes the replay, then adds the rollback depths to it.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        drop(self.inner.take().unwrap().finish()?);
+
+        let raw = fs::read(&self.path)?;
+        save::write_atomically(
+            &self.path,
+            &tango_replay::set_section(
+                &raw,
+                &tango_replay::Section {
+                    tag: *tango_replay::ROLLBACK_DEPTHS_TAG,
+                    data: std::mem::take(&mut self.rollback_depths),
+                },
+            )?,
+        )
     }
 }
 
 impl Drop for Writer {
     fn drop(&mut self) {
-        if let Some(encoder) = self.encoder.take() {
+        if self.inner.take().is_some() {
             log::info!("writer was not finished before drop, this replay will be incomplete!");
-            encoder.finish().expect("finish");
         }
     }
 }
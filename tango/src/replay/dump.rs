@@ -0,0 +1,182 @@
+use crate::{assist, bios, determinism, game, practice, replay, replayer};
+
+/// How many input records either side of the requested tick are written out.
+const INPUT_CONTEXT: u32 = 10;
+
+#[derive(serde::Serialize)]
+struct PlayerInput {
+    joyflags: u16,
+    packet: String,
+}
+
+#[derive(serde::Serialize)]
+struct InputRecord {
+    local_tick: u32,
+    remote_tick: u32,
+    p1: PlayerInput,
+    p2: PlayerInput,
+}
+
+#[derive(serde::Serialize)]
+struct Summary {
+    tick: u32,
+    final_tick: u32,
+    frame: u32,
+    game: (String, u8),
+    patch: Option<(String, String)>,
+    /// Battle variables read from memory, if the game has readers for them.
+    battle: Option<practice::Sample>,
+}
+
+fn to_hex(raw: &[u8]) -> String {
+    raw.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The last tick a replay has inputs for.
+pub fn final_tick(replay: &replay::Replay) -> Option<u32> {
+    replay.input_pairs.last().map(|ip| ip.local.local_tick)
+}
+
+/// Plays a replay headlessly up to a tick, then writes what the emulator looked like there into a directory, for tracking down desyncs.
+///
+/// This writes:
+/// - `state.ss`: the full mGBA save state.
+/// - `summary.json`: where the replay was stopped, along with battle variables if the game can read them.
+/// - `inputs.json`: the input records around the tick.
+pub fn dump_state(
+    rom: &[u8],
+    bios_path: Option<&std::path::Path>,
+    game: &'static (dyn game::Game + Send + Sync),
+    replay: &replay::Replay,
+    tick: u32,
+    output_path: &std::path::Path,
+) -> anyhow::Result<()> {
+    let final_tick = final_tick(replay).ok_or_else(|| anyhow::anyhow!("replay has no inputs"))?;
+    if tick > final_tick {
+        anyhow::bail!(
+            "tick {} is past the end of the replay, whose final tick is {}",
+            tick,
+            final_tick
+        );
+    }
+
+    let local_state = replay
+        .local_state
+        .as_ref()
+        .ok_or(anyhow::anyhow!("missing local state"))?;
+
+    let mut core = mgba::core::Core::new_gba("tango")?;
+    determinism::normalize(&mut core);
+    let bios = bios::for_replay(&replay.metadata, bios_path)?;
+    if let Some(bios) = bios.as_ref() {
+        bios::load_into(&mut core, bios)?;
+    }
+    determinism::check_replay(&core, bios.as_deref(), &replay.metadata)?;
+    core.as_mut().load_rom(mgba::vfile::VFile::open_memory(rom))?;
+    core.as_mut().reset();
+    core.as_mut().set_audio_enabled(false);
+    core.as_mut().set_video_enabled(false);
+
+    let replayer_state = replayer::State::new(
+        replay.local_player_index,
+        replay.input_pairs.clone(),
+        0,
+        Box::new(|| {}),
+    );
+    replayer_state
+        .lock_inner()
+        .set_round_time_limit_tick(replay.metadata.round_time_limit.as_ref().map(|limit| limit.ticks));
+
+    let hooks = game.hooks();
+    hooks.patch(core.as_mut());
+    {
+        let mut traps = hooks.common_traps();
+        traps.extend(hooks.replayer_traps(replayer_state.clone()));
+        core.set_traps(traps);
+    }
+    if let Some(fixed_rtc) = replay.metadata.fixed_rtc.as_ref() {
+        core.as_mut().set_rtc_fixed(fixed_rtc.ms);
+    }
+    core.as_mut().load_state(local_state)?;
+
+    loop {
+        {
+            let replayer_state = replayer_state.lock_inner();
+            if replayer_state.current_tick() >= tick {
+                break;
+            }
+            if replayer_state.input_pairs_left() == 0 || replayer_state.is_round_ended() {
+                anyhow::bail!(
+                    "replay stopped at tick {} before reaching tick {}",
+                    replayer_state.current_tick(),
+                    tick
+                );
+            }
+        }
+
+        core.as_mut().run_frame();
+
+        if let Some(err) = replayer_state.lock_inner().take_error() {
+            return Err(err);
+        }
+    }
+
+    std::fs::create_dir_all(output_path)?;
+
+    let state = core.as_mut().save_state()?;
+    std::fs::write(output_path.join("state.ss"), state.as_slice())?;
+
+    let summary = Summary {
+        tick: replayer_state.lock_inner().current_tick(),
+        final_tick,
+        frame: core.as_ref().frame_counter(),
+        game: {
+            let (family, variant) = game.family_and_variant();
+            (family.to_string(), variant)
+        },
+        patch: replay
+            .metadata
+            .local_side
+            .as_ref()
+            .and_then(|side| side.game_info.as_ref())
+            .and_then(|game_info| game_info.patch.as_ref())
+            .map(|patch| (patch.name.clone(), patch.version.clone())),
+        battle: hooks
+            .practice_telemetry()
+            .and_then(|telemetry| practice::Sample::read(telemetry, core.as_mut(), assist::Applied::default())),
+    };
+    std::fs::write(output_path.join("summary.json"), serde_json::to_vec_pretty(&summary)?)?;
+
+    let inputs = replay
+        .input_pairs
+        .iter()
+        .filter(|ip| ip.local.local_tick + INPUT_CONTEXT >= tick && ip.local.local_tick <= tick + INPUT_CONTEXT)
+        .map(|ip| {
+            let (p1, p2) = if replay.local_player_index == 0 {
+                (&ip.local, &ip.remote)
+            } else {
+                (&ip.remote, &ip.local)
+            };
+            InputRecord {
+                local_tick: ip.local.local_tick,
+                remote_tick: ip.local.remote_tick,
+                p1: PlayerInput {
+                    joyflags: p1.joyflags,
+                    packet: to_hex(&p1.packet),
+                },
+                p2: PlayerInput {
+                    joyflags: p2.joyflags,
+                    packet: to_hex(&p2.packet),
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+    std::fs::write(output_path.join("inputs.json"), serde_json::to_vec_pretty(&inputs)?)?;
+
+    log::info!(
+        "dumped replay state at tick {} to {}",
+        summary.tick,
+        output_path.display()
+    );
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use byteorder::ByteOrder;
 use tokio::io::AsyncWriteExt;
 
-use crate::{game, replay, replayer, video};
+use crate::{bios, determinism, game, replay, replayer, video};
 
 pub struct Settings {
     pub ffmpeg: Option<std::path::PathBuf>,
@@ -10,6 +10,8 @@ pub struct Settings {
     pub ffmpeg_mux_flags: String,
     pub video_filter: String,
     pub disable_bgm: bool,
+    /// The BIOS to play back replays recorded on one with.
+    pub bios: Option<std::path::PathBuf>,
 }
 
 impl Settings {
@@ -21,6 +23,7 @@ impl Settings {
             ffmpeg_mux_flags: "-movflags +faststart".to_string(),
             video_filter: "".to_string(),
             disable_bgm: false,
+            bios: None,
         }
     }
 }
@@ -44,6 +47,12 @@ pub async fn export(
     });
 
     let mut core = mgba::core::Core::new_gba("tango")?;
+    determinism::normalize(&mut core);
+    let bios = bios::for_replay(&replay.metadata, settings.bios.as_deref())?;
+    if let Some(bios) = bios.as_ref() {
+        bios::load_into(&mut core, bios)?;
+    }
+    determinism::check_replay(&core, bios.as_deref(), &replay.metadata)?;
     core.enable_video_buffer();
 
     core.as_mut().load_rom(mgba::vfile::VFile::open_memory(&rom))?;
@@ -65,6 +74,9 @@ pub async fn export(
 
     let replayer_state = replayer::State::new(replay.local_player_index, input_pairs, 0, Box::new(|| {}));
     replayer_state.lock_inner().set_disable_bgm(settings.disable_bgm);
+    replayer_state
+        .lock_inner()
+        .set_round_time_limit_tick(replay.metadata.round_time_limit.as_ref().map(|limit| limit.ticks));
     let game = game::find_by_family_and_variant(&game_info.rom_family, game_info.rom_variant as u8)
         .ok_or(anyhow::anyhow!("game not found"))?;
 
@@ -76,6 +88,9 @@ pub async fn export(
         traps.extend(hooks.replayer_traps(replayer_state.clone()));
         core.set_traps(traps);
     }
+    if let Some(fixed_rtc) = replay.metadata.fixed_rtc.as_ref() {
+        core.as_mut().set_rtc_fixed(fixed_rtc.ms);
+    }
     core.as_mut().load_state(&local_state)?;
 
     #[cfg(windows)]
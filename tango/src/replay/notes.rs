@@ -0,0 +1,135 @@
+use sha3::digest::{ExtendableOutput, Update};
+
+use crate::{fs, replay, save};
+
+/// Where notes for replays are kept, in the replays directory so they move along with it.
+pub fn index_path(replays_path: &std::path::Path) -> std::path::PathBuf {
+    replays_path.join("notes.json")
+}
+
+/// Where an A/B loop over part of a replay starts and ends, as positions in its input pairs.
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+pub struct LoopMarkers {
+    pub a: Option<usize>,
+    pub b: Option<usize>,
+}
+
+impl LoopMarkers {
+    pub fn is_empty(&self) -> bool {
+        self.a.is_none() && self.b.is_none()
+    }
+}
+
+/// What the player wrote down about a replay.
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, PartialEq)]
+pub struct Entry {
+    pub notes: String,
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "LoopMarkers::is_empty")]
+    pub loop_markers: LoopMarkers,
+}
+
+impl Entry {
+    fn is_empty(&self) -> bool {
+        self.notes.is_empty() && self.tags.is_empty() && self.loop_markers.is_empty()
+    }
+}
+
+/// Identifies a replay by what's in its header, so its notes stay attached if it's renamed or moved.
+///
+/// Only what's fixed when the replay starts being written goes in: the rest of the metadata may still be updated afterwards, e.g. if the round desyncs.
+pub fn key(metadata: &replay::Metadata) -> String {
+    let mut shake128 = sha3::Shake128::default();
+    shake128.update(b"tango:replay:");
+    shake128.update(&metadata.ts.to_le_bytes());
+    for s in [
+        metadata.link_code.as_str(),
+        metadata
+            .local_side
+            .as_ref()
+            .map(|side| side.nickname.as_str())
+            .unwrap_or(""),
+        metadata
+            .remote_side
+            .as_ref()
+            .map(|side| side.nickname.as_str())
+            .unwrap_or(""),
+    ] {
+        shake128.update(&(s.len() as u32).to_le_bytes());
+        shake128.update(s.as_bytes());
+    }
+    let mut key = [0u8; 16];
+    shake128.finalize_xof_into(&mut key);
+    key.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Notes for all replays, stored as a single JSON file alongside them.
+pub struct Index {
+    path: std::path::PathBuf,
+    entries: std::collections::BTreeMap<String, Entry>,
+}
+
+impl Index {
+    /// Loads the index. If it can't be read, it starts out empty: notes are nice to have, but not worth keeping the replays from showing.
+    pub fn load(path: std::path::PathBuf) -> Self {
+        let entries = Self::read_entries(&path);
+        Self { path, entries }
+    }
+
+    fn read_entries(path: &std::path::Path) -> std::collections::BTreeMap<String, Entry> {
+        match fs::read(path) {
+            Ok(raw) => match serde_json::from_slice(&raw) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    log::warn!("failed to parse replay notes: {}", e);
+                    Default::default()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => {
+                log::warn!("failed to read replay notes: {}", e);
+                Default::default()
+            }
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.entries.get(key)
+    }
+
+    /// Changes the entry for a replay, removing it if there's nothing left in it.
+    ///
+    /// The index is read again first, as the replay being played back may have had its loop markers changed since this copy was loaded.
+    pub fn update(&mut self, key: &str, f: impl FnOnce(&mut Entry)) -> std::io::Result<()> {
+        self.entries = Self::read_entries(&self.path);
+        let mut entry = self.entries.get(key).cloned().unwrap_or_default();
+        f(&mut entry);
+        if entry.is_empty() {
+            return self.remove(key);
+        }
+        if self.entries.get(key) == Some(&entry) {
+            return Ok(());
+        }
+        self.entries.insert(key.to_string(), entry);
+        self.save()
+    }
+
+    pub fn remove(&mut self, key: &str) -> std::io::Result<()> {
+        if self.entries.remove(key).is_none() {
+            return Ok(());
+        }
+        self.save()
+    }
+
+    /// Every tag used on any replay, for suggesting when tagging another one.
+    pub fn all_tags(&self) -> std::collections::BTreeSet<&str> {
+        self.entries
+            .values()
+            .flat_map(|entry| entry.tags.iter().map(|tag| tag.as_str()))
+            .collect()
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        save::write_atomically(&self.path, &serde_json::to_vec_pretty(&self.entries)?)
+    }
+}
@@ -1,7 +0,0 @@
-pub mod replay11 {
-    include!(concat!(env!("OUT_DIR"), "/tango.replay.protos.replay11.rs"));
-}
-
-pub mod replay10 {
-    include!(concat!(env!("OUT_DIR"), "/tango.replay.protos.replay10.rs"));
-}
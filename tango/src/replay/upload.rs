@@ -0,0 +1,478 @@
+//! Uploading replays to an endpoint of the player's choosing, e.g. one a league collects its matches' replays at.
+//!
+//! Nothing is uploaded unless an endpoint is configured. Replays are queued on disk as matches end and uploaded one at a time in the background, so they still get there if the endpoint is down for a while or Tango is closed before it gets to them.
+
+use crate::{config, fs, i18n, replay, save};
+
+/// The most replays kept waiting to be uploaded. Past this, the oldest are given up on.
+const MAX_QUEUE_LENGTH: usize = 200;
+
+/// How long to wait before trying again after the endpoint couldn't be reached, doubling each time it still can't be.
+const INITIAL_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// How often to check if an endpoint has been configured since, when there's something queued and none is.
+const UNCONFIGURED_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Settings {
+    /// Where to POST replays to. Only HTTPS endpoints are uploaded to. Leave empty to not upload anything.
+    pub endpoint: String,
+    /// Sent as a bearer token, if set.
+    pub token: String,
+    /// Whether to send along the notes and tags written on replays, which otherwise never leave this machine.
+    pub include_notes: bool,
+}
+
+impl Settings {
+    /// The endpoint, if one is configured and it's HTTPS.
+    pub fn endpoint_url(&self) -> Option<url::Url> {
+        if self.endpoint.is_empty() {
+            return None;
+        }
+        url::Url::parse(&self.endpoint)
+            .ok()
+            .filter(|url| url.scheme() == "https")
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.endpoint_url().is_some()
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+/// A replay waiting to be uploaded.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Item {
+    pub replay_path: std::path::PathBuf,
+    /// Which match the replay is from, so a match's replays can be held back or dropped together.
+    pub match_id: String,
+    pub round_number: u8,
+    /// Unset if the round didn't finish.
+    pub outcome: Option<Outcome>,
+    pub finished_at: std::time::SystemTime,
+    /// How many times uploading it has failed so far.
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// What's sent along with each replay. Everything in it can also be read from the replay itself, except for the outcome and, if opted into, the notes.
+#[derive(serde::Serialize)]
+struct Metadata {
+    match_id: String,
+    round_number: u8,
+    link_code: String,
+    /// Milliseconds since the Unix epoch.
+    started_at: u64,
+    finished_at: u64,
+    /// Unset if the replay didn't record it.
+    match_type: Option<MatchType>,
+    outcome: Option<Outcome>,
+    desynced: bool,
+    local: Option<Side>,
+    remote: Option<Side>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<Notes>,
+}
+
+#[derive(serde::Serialize)]
+struct MatchType {
+    #[serde(rename = "type")]
+    typ: u32,
+    subtype: u32,
+}
+
+#[derive(serde::Serialize)]
+struct Side {
+    nickname: String,
+    game: Option<Game>,
+}
+
+#[derive(serde::Serialize)]
+struct Game {
+    family: String,
+    variant: u32,
+    patch: Option<Patch>,
+}
+
+#[derive(serde::Serialize)]
+struct Patch {
+    name: String,
+    version: String,
+}
+
+#[derive(serde::Serialize)]
+struct Notes {
+    notes: String,
+    tags: Vec<String>,
+}
+
+impl Side {
+    fn from_replay(side: &replay::metadata::Side) -> Self {
+        Self {
+            nickname: side.nickname.clone(),
+            game: side.game_info.as_ref().map(|game_info| Game {
+                family: game_info.rom_family.clone(),
+                variant: game_info.rom_variant,
+                patch: game_info.patch.as_ref().map(|patch| Patch {
+                    name: patch.name.clone(),
+                    version: patch.version.clone(),
+                }),
+            }),
+        }
+    }
+}
+
+/// What the player is told about uploads, through notifications.
+#[derive(Clone, Debug)]
+pub enum Event {
+    Uploaded {
+        remaining: usize,
+    },
+    /// Uploading failed, but will be tried again. Only the first failure of each replay is reported, so a long outage doesn't bury everything else.
+    Retrying {
+        error: String,
+    },
+    /// Uploading failed in a way trying again won't fix, so the replay has been dropped from the queue.
+    GaveUp {
+        replay_path: std::path::PathBuf,
+        error: String,
+    },
+}
+
+impl Event {
+    pub fn message(&self, language: &unic_langid::LanguageIdentifier) -> String {
+        match self {
+            Event::Uploaded { remaining } => i18n::LOCALES.lookup_with_args(
+                language,
+                "replay-upload-uploaded",
+                &std::collections::HashMap::from([("remaining", (*remaining).into())]),
+            ),
+            Event::Retrying { error } => i18n::LOCALES.lookup_with_args(
+                language,
+                "replay-upload-retrying",
+                &std::collections::HashMap::from([("error", error.as_str().into())]),
+            ),
+            Event::GaveUp { replay_path, error } => i18n::LOCALES.lookup_with_args(
+                language,
+                "replay-upload-gave-up",
+                &std::collections::HashMap::from([
+                    (
+                        "filename",
+                        replay_path
+                            .file_name()
+                            .map(|filename| filename.to_string_lossy().to_string())
+                            .unwrap_or_default()
+                            .into(),
+                    ),
+                    ("error", error.as_str().into()),
+                ]),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    /// Worth trying again later, e.g. the endpoint is down or we're offline.
+    #[error("{0}")]
+    Transient(String),
+
+    #[error("{0}")]
+    Permanent(String),
+}
+
+struct Inner {
+    path: std::path::PathBuf,
+    queue: std::collections::VecDeque<Item>,
+    /// Matches whose replays shouldn't be uploaded yet, as the player can still opt out.
+    held: std::collections::HashSet<String>,
+    events: Vec<Event>,
+}
+
+impl Inner {
+    fn save(&self) {
+        if let Err(e) = serde_json::to_vec_pretty(&self.queue)
+            .map_err(std::io::Error::from)
+            .and_then(|raw| save::write_atomically(&self.path, &raw))
+        {
+            log::error!("failed to save replay upload queue: {:?}", e);
+        }
+    }
+
+    fn next(&self) -> Option<Item> {
+        self.queue
+            .iter()
+            .find(|item| !self.held.contains(&item.match_id))
+            .cloned()
+    }
+
+    fn remove(&mut self, item: &Item) {
+        self.queue.retain(|queued| queued.replay_path != item.replay_path);
+        self.save();
+    }
+}
+
+/// Uploads queued replays in the background. Holds are only kept in memory: if Tango is closed while a match's replays are held, they're uploaded next time.
+#[derive(Clone)]
+pub struct Uploader {
+    inner: std::sync::Arc<parking_lot::Mutex<Inner>>,
+    wake: std::sync::Arc<tokio::sync::Notify>,
+}
+
+impl Uploader {
+    /// Loads the queue and starts uploading whatever's in it. If the queue can't be read, it starts out empty.
+    pub fn new(path: std::path::PathBuf, config: std::sync::Arc<parking_lot::RwLock<config::Config>>) -> Self {
+        let queue = match fs::read(&path) {
+            Ok(raw) => match serde_json::from_slice(&raw) {
+                Ok(queue) => queue,
+                Err(e) => {
+                    log::warn!("failed to parse replay upload queue: {}", e);
+                    Default::default()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => {
+                log::warn!("failed to read replay upload queue: {}", e);
+                Default::default()
+            }
+        };
+        let uploader = Self {
+            inner: std::sync::Arc::new(parking_lot::Mutex::new(Inner {
+                path,
+                queue,
+                held: std::collections::HashSet::new(),
+                events: vec![],
+            })),
+            wake: std::sync::Arc::new(tokio::sync::Notify::new()),
+        };
+        tokio::task::spawn({
+            let uploader = uploader.clone();
+            async move {
+                uploader.run(config).await;
+            }
+        });
+        uploader
+    }
+
+    /// Queues a match's replays, held back until the match is released.
+    pub fn enqueue_match(&self, match_id: &str, items: Vec<Item>) {
+        let mut inner = self.inner.lock();
+        inner.held.insert(match_id.to_string());
+        inner.queue.extend(items);
+        while inner.queue.len() > MAX_QUEUE_LENGTH {
+            if let Some(item) = inner.queue.pop_front() {
+                log::warn!(
+                    "replay upload queue is full, giving up on {}",
+                    item.replay_path.display()
+                );
+            }
+        }
+        inner.save();
+    }
+
+    /// Lets a match's replays be uploaded.
+    pub fn release_match(&self, match_id: &str) {
+        self.inner.lock().held.remove(match_id);
+        self.wake.notify_one();
+    }
+
+    /// Drops a match's replays from the queue without uploading them.
+    pub fn discard_match(&self, match_id: &str) {
+        let mut inner = self.inner.lock();
+        inner.held.remove(match_id);
+        inner.queue.retain(|item| item.match_id != match_id);
+        inner.save();
+    }
+
+    pub fn queue_length(&self) -> usize {
+        self.inner.lock().queue.len()
+    }
+
+    /// Takes what's happened since this was last called, to be shown as notifications.
+    pub fn take_events(&self) -> Vec<Event> {
+        std::mem::take(&mut self.inner.lock().events)
+    }
+
+    async fn run(&self, config: std::sync::Arc<parking_lot::RwLock<config::Config>>) {
+        let mut retry_delay = INITIAL_RETRY_DELAY;
+        loop {
+            let item = if let Some(item) = self.inner.lock().next() {
+                item
+            } else {
+                self.wake.notified().await;
+                continue;
+            };
+
+            let (settings, client, replays_path) = {
+                let config = config.read();
+                (
+                    config.replay_upload.clone(),
+                    config.network.http_client(),
+                    config.replays_path(),
+                )
+            };
+            if !settings.is_enabled() {
+                // Nothing's uploaded without an endpoint, but what's queued is kept for if one is configured.
+                let _ = tokio::time::timeout(UNCONFIGURED_POLL_INTERVAL, self.wake.notified()).await;
+                continue;
+            }
+
+            match upload(&client, &settings, &replays_path, &item).await {
+                Ok(()) => {
+                    log::info!("uploaded replay {}", item.replay_path.display());
+                    retry_delay = INITIAL_RETRY_DELAY;
+                    let mut inner = self.inner.lock();
+                    inner.remove(&item);
+                    let remaining = inner.queue.len();
+                    inner.events.push(Event::Uploaded { remaining });
+                }
+                Err(Error::Permanent(error)) => {
+                    log::error!("failed to upload replay {}: {}", item.replay_path.display(), error);
+                    let mut inner = self.inner.lock();
+                    inner.remove(&item);
+                    inner.events.push(Event::GaveUp {
+                        replay_path: item.replay_path.clone(),
+                        error,
+                    });
+                }
+                Err(Error::Transient(error)) => {
+                    log::warn!(
+                        "failed to upload replay {}, retrying in {:?}: {}",
+                        item.replay_path.display(),
+                        retry_delay,
+                        error
+                    );
+                    {
+                        let mut inner = self.inner.lock();
+                        if let Some(queued) = inner
+                            .queue
+                            .iter_mut()
+                            .find(|queued| queued.replay_path == item.replay_path)
+                        {
+                            queued.attempts += 1;
+                        }
+                        inner.save();
+                        if item.attempts == 0 {
+                            inner.events.push(Event::Retrying { error });
+                        }
+                    }
+                    let _ = tokio::time::timeout(retry_delay, self.wake.notified()).await;
+                    retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                }
+            }
+        }
+    }
+}
+
+fn unix_millis(time: std::time::SystemTime) -> u64 {
+    time.duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Checks the replay was written out in full, then uploads it along with its metadata.
+async fn upload(
+    client: &reqwest::Client,
+    settings: &Settings,
+    replays_path: &std::path::Path,
+    item: &Item,
+) -> Result<(), Error> {
+    let endpoint = settings
+        .endpoint_url()
+        .ok_or_else(|| Error::Transient("no endpoint configured".to_string()))?;
+
+    let raw = tokio::fs::read(fs::extended(&item.replay_path))
+        .await
+        .map_err(|e| Error::Permanent(e.to_string()))?;
+    let replay = tokio::task::spawn_blocking({
+        let raw = raw.clone();
+        move || replay::Replay::decode(&raw[..])
+    })
+    .await
+    .map_err(|e| Error::Permanent(e.to_string()))?
+    .map_err(|e| Error::Permanent(e.to_string()))?;
+    if !replay.is_complete {
+        return Err(Error::Permanent("replay is incomplete".to_string()));
+    }
+
+    // Notes live alongside replays rather than in them, so leaving them out of the metadata is all it takes to keep them here.
+    let notes = if settings.include_notes {
+        replay::notes::Index::load(replay::notes::index_path(replays_path))
+            .get(&replay::notes::key(&replay.metadata))
+            .map(|entry| Notes {
+                notes: entry.notes.clone(),
+                tags: entry.tags.clone(),
+            })
+    } else {
+        None
+    };
+
+    let metadata = Metadata {
+        match_id: item.match_id.clone(),
+        round_number: item.round_number,
+        link_code: replay.metadata.link_code.clone(),
+        started_at: replay.metadata.ts,
+        finished_at: unix_millis(item.finished_at),
+        match_type: replay.metadata.match_type.as_ref().map(|match_type| MatchType {
+            typ: match_type.r#type,
+            subtype: match_type.subtype,
+        }),
+        outcome: item.outcome,
+        desynced: replay.metadata.desync.is_some(),
+        local: replay.metadata.local_side.as_ref().map(Side::from_replay),
+        remote: replay.metadata.remote_side.as_ref().map(Side::from_replay),
+        notes,
+    };
+
+    let form = reqwest::multipart::Form::new()
+        .part(
+            "metadata",
+            reqwest::multipart::Part::text(
+                serde_json::to_string(&metadata).map_err(|e| Error::Permanent(e.to_string()))?,
+            )
+            .mime_str("application/json")
+            .map_err(|e| Error::Permanent(e.to_string()))?,
+        )
+        .part(
+            "replay",
+            reqwest::multipart::Part::bytes(raw)
+                .file_name(
+                    item.replay_path
+                        .file_name()
+                        .map(|filename| filename.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                )
+                .mime_str("application/x-tango-replay")
+                .map_err(|e| Error::Permanent(e.to_string()))?,
+        );
+
+    let mut request = client.post(endpoint).multipart(form);
+    if !settings.token.is_empty() {
+        request = request.bearer_auth(&settings.token);
+    }
+    let response = request.send().await.map_err(|e| Error::Transient(e.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else if status.is_server_error()
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+        // A wrong token is fixed in the settings, not by dropping everything queued.
+        || status == reqwest::StatusCode::UNAUTHORIZED
+        || status == reqwest::StatusCode::FORBIDDEN
+    {
+        Err(Error::Transient(status.to_string()))
+    } else {
+        Err(Error::Permanent(status.to_string()))
+    }
+}
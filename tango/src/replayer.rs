@@ -1,12 +1,20 @@
 use crate::battle;
+use crate::bios;
+use crate::determinism;
 use crate::game;
 use crate::lockstep;
+use crate::stats;
+
+/// How often, in ticks, the battle state is checksummed to detect desyncs.
+pub const STATE_HASH_INTERVAL: u32 = 60;
 
 pub struct InnerState {
     disable_bgm: bool,
     current_tick: u32,
     local_player_index: u8,
     input_pairs: std::collections::VecDeque<lockstep::Pair<lockstep::PartialInput, lockstep::PartialInput>>,
+    /// Every input pair played back, including those already popped, so a `FrameCheckpoint` can put them back.
+    all_input_pairs: std::sync::Arc<[lockstep::Pair<lockstep::PartialInput, lockstep::PartialInput>]>,
     output_pairs: Vec<lockstep::Pair<lockstep::Input, lockstep::Input>>,
     apply_shadow_input: Box<
         dyn FnMut(lockstep::Pair<lockstep::Input, lockstep::PartialInput>) -> anyhow::Result<Vec<u8>> + Sync + Send,
@@ -20,6 +28,10 @@ pub struct InnerState {
     phase: RoundPhase,
     on_round_ended: Option<Box<dyn FnOnce() + Send>>,
     error: Option<anyhow::Error>,
+    start_tick: u32,
+    state_hash_regions: Vec<std::ops::Range<u32>>,
+    state_hashes: Vec<(u32, u32)>,
+    round_time_limit_tick: Option<u32>,
 }
 
 impl InnerState {
@@ -35,6 +47,15 @@ impl InnerState {
         self.disable_bgm = disable_bgm;
     }
 
+    pub fn set_round_time_limit_tick(&mut self, round_time_limit_tick: Option<u32>) {
+        self.round_time_limit_tick = round_time_limit_tick;
+    }
+
+    /// Whether the round's time limit runs out on the current tick.
+    pub fn is_round_time_limit_reached(&self) -> bool {
+        self.round_time_limit_tick == Some(self.current_tick)
+    }
+
     pub fn commit_tick(&self) -> u32 {
         self.commit_tick
     }
@@ -65,6 +86,27 @@ impl InnerState {
         self.committed_state.take()
     }
 
+    /// Checksums the battle state if the current tick is a committed tick that's due for it.
+    ///
+    /// This must be called from the same trap as set_committed_state, so that both sides checksum at the same point in the tick. Each tick is only checksummed once, as the tick we started fastforwarding from was already seen by the previous fastforward.
+    pub fn record_state_hash(&mut self, mut core: mgba::core::CoreMutRef) {
+        if self.state_hash_regions.is_empty()
+            || self.current_tick == self.start_tick
+            || self.current_tick > self.commit_tick
+            || self.current_tick % STATE_HASH_INTERVAL != 0
+        {
+            return;
+        }
+
+        let mut hasher = crc32fast::Hasher::new();
+        for region in self.state_hash_regions.iter() {
+            for addr in region.clone() {
+                hasher.update(&[core.raw_read_8(addr, -1)]);
+            }
+        }
+        self.state_hashes.push((self.current_tick, hasher.finalize()));
+    }
+
     pub fn dirty_tick(&self) -> u32 {
         self.dirty_tick
     }
@@ -158,6 +200,122 @@ impl InnerState {
     pub fn increment_current_tick(&mut self) {
         self.current_tick += 1;
     }
+
+    /// Takes down where playback is, for restoring along with a save state taken at the same point.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            current_tick: self.current_tick,
+            input_pairs: self.input_pairs.clone(),
+            output_pairs: self.output_pairs.clone(),
+            local_packet: self.local_packet.clone(),
+            commit_tick: self.commit_tick,
+            committed_state: self.committed_state.clone(),
+            dirty_tick: self.dirty_tick,
+            dirty_state: self.dirty_state.clone(),
+            round_result: self.round_result,
+            phase: self.phase,
+            start_tick: self.start_tick,
+            state_hashes: self.state_hashes.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.current_tick = checkpoint.current_tick;
+        self.input_pairs = checkpoint.input_pairs;
+        self.output_pairs = checkpoint.output_pairs;
+        self.local_packet = checkpoint.local_packet;
+        self.commit_tick = checkpoint.commit_tick;
+        self.committed_state = checkpoint.committed_state;
+        self.dirty_tick = checkpoint.dirty_tick;
+        self.dirty_state = checkpoint.dirty_state;
+        self.round_result = checkpoint.round_result;
+        self.phase = checkpoint.phase;
+        self.start_tick = checkpoint.start_tick;
+        self.state_hashes = checkpoint.state_hashes;
+        self.error = None;
+    }
+
+    /// Takes down where playback is, like `checkpoint`, but only keeping track of how far along everything that only grows is, so it's cheap enough to take every frame.
+    pub fn frame_checkpoint(&self) -> FrameCheckpoint {
+        FrameCheckpoint {
+            current_tick: self.current_tick,
+            input_pairs_left: self.input_pairs.len(),
+            output_pairs_len: self.output_pairs.len(),
+            local_packet: self.local_packet.clone(),
+            commit_tick: self.commit_tick,
+            has_committed_state: self.committed_state.is_some(),
+            dirty_tick: self.dirty_tick,
+            has_dirty_state: self.dirty_state.is_some(),
+            round_result: self.round_result,
+            phase: self.phase,
+            start_tick: self.start_tick,
+            state_hashes_len: self.state_hashes.len(),
+        }
+    }
+
+    /// Goes back to a frame checkpoint taken earlier in playback.
+    ///
+    /// Only what was added since is undone, so this is only valid if playback hasn't been restored to a full `Checkpoint` in between. Committed and dirty states are only ever set once during playback, so they're kept unless the checkpoint is from before they were.
+    pub fn restore_frame(&mut self, checkpoint: &FrameCheckpoint) {
+        self.current_tick = checkpoint.current_tick;
+        let left = checkpoint.input_pairs_left.min(self.all_input_pairs.len());
+        while self.input_pairs.len() > left {
+            self.input_pairs.pop_front();
+        }
+        while self.input_pairs.len() < left {
+            self.input_pairs
+                .push_front(self.all_input_pairs[self.all_input_pairs.len() - self.input_pairs.len() - 1].clone());
+        }
+        self.output_pairs.truncate(checkpoint.output_pairs_len);
+        self.local_packet = checkpoint.local_packet.clone();
+        self.commit_tick = checkpoint.commit_tick;
+        if !checkpoint.has_committed_state {
+            self.committed_state = None;
+        }
+        self.dirty_tick = checkpoint.dirty_tick;
+        if !checkpoint.has_dirty_state {
+            self.dirty_state = None;
+        }
+        self.round_result = checkpoint.round_result;
+        self.phase = checkpoint.phase;
+        self.start_tick = checkpoint.start_tick;
+        self.state_hashes.truncate(checkpoint.state_hashes_len);
+        self.error = None;
+    }
+}
+
+/// Where playback was at some point, as taken by `InnerState::checkpoint`.
+#[derive(Clone)]
+pub struct Checkpoint {
+    current_tick: u32,
+    input_pairs: std::collections::VecDeque<lockstep::Pair<lockstep::PartialInput, lockstep::PartialInput>>,
+    output_pairs: Vec<lockstep::Pair<lockstep::Input, lockstep::Input>>,
+    local_packet: Option<lockstep::Packet>,
+    commit_tick: u32,
+    committed_state: Option<battle::CommittedState>,
+    dirty_tick: u32,
+    dirty_state: Option<battle::CommittedState>,
+    round_result: Option<RoundResult>,
+    phase: RoundPhase,
+    start_tick: u32,
+    state_hashes: Vec<(u32, u32)>,
+}
+
+/// Where playback was on a recent frame, as taken by `InnerState::frame_checkpoint`.
+#[derive(Clone)]
+pub struct FrameCheckpoint {
+    current_tick: u32,
+    input_pairs_left: usize,
+    output_pairs_len: usize,
+    local_packet: Option<lockstep::Packet>,
+    commit_tick: u32,
+    has_committed_state: bool,
+    dirty_tick: u32,
+    has_dirty_state: bool,
+    round_result: Option<RoundResult>,
+    phase: RoundPhase,
+    start_tick: u32,
+    state_hashes_len: usize,
 }
 
 pub struct FastforwardResult {
@@ -165,6 +323,8 @@ pub struct FastforwardResult {
     pub dirty_state: battle::CommittedState,
     pub round_result: Option<RoundResult>,
     pub output_pairs: Vec<lockstep::Pair<lockstep::Input, lockstep::Input>>,
+    /// Checksums of the battle state at committed ticks, as (tick, checksum).
+    pub state_hashes: Vec<(u32, u32)>,
 }
 
 #[derive(Clone, Copy, serde_repr::Serialize_repr)]
@@ -193,6 +353,10 @@ pub struct Fastforwarder {
     state: State,
     hooks: &'static (dyn game::Hooks + Send + Sync),
     local_player_index: u8,
+    state_hash_regions: Vec<std::ops::Range<u32>>,
+    round_time_limit_tick: Option<u32>,
+    tick_cost: stats::DeltaCounter,
+    last_ticks: u32,
 }
 
 #[derive(Clone)]
@@ -209,34 +373,40 @@ impl State {
             tick: ip.local.local_tick,
             packet: ip.local.packet.clone(),
         });
+        let all_input_pairs = input_pairs
+            .iter()
+            .map(|ip| lockstep::Pair {
+                local: lockstep::PartialInput {
+                    local_tick: ip.local.local_tick,
+                    remote_tick: ip.local.remote_tick,
+                    joyflags: ip.local.joyflags,
+                },
+                remote: lockstep::PartialInput {
+                    local_tick: ip.remote.local_tick,
+                    remote_tick: ip.remote.remote_tick,
+                    joyflags: ip.remote.joyflags,
+                },
+            })
+            .collect::<std::sync::Arc<[_]>>();
         State(std::sync::Arc::new(parking_lot::Mutex::new(Some(InnerState {
             disable_bgm: false,
             current_tick: 0,
             local_player_index,
-            input_pairs: input_pairs
-                .iter()
-                .map(|ip| lockstep::Pair {
-                    local: lockstep::PartialInput {
-                        local_tick: ip.local.local_tick,
-                        remote_tick: ip.local.remote_tick,
-                        joyflags: ip.local.joyflags,
-                    },
-                    remote: lockstep::PartialInput {
-                        local_tick: ip.remote.local_tick,
-                        remote_tick: ip.remote.remote_tick,
-                        joyflags: ip.remote.joyflags,
-                    },
-                })
-                .collect(),
+            input_pairs: all_input_pairs.iter().cloned().collect(),
+            all_input_pairs,
+            // Packets are looked up by tick rather than taken in order, so playback can be restored to a checkpoint.
             apply_shadow_input: Box::new({
-                let mut iq = input_pairs.into_iter().collect::<std::collections::VecDeque<_>>();
-                move |_| {
-                    let ip = if let Some(ip) = iq.pop_front() {
-                        ip
+                let packets = input_pairs
+                    .into_iter()
+                    .map(|ip| (ip.local.local_tick, ip.remote.packet))
+                    .collect::<std::collections::HashMap<_, _>>();
+                move |input| {
+                    let packet = if let Some(packet) = packets.get(&input.local.local_tick) {
+                        packet
                     } else {
-                        anyhow::bail!("no more committed inputs");
+                        anyhow::bail!("no committed input for tick {}", input.local.local_tick);
                     };
-                    Ok(ip.remote.packet)
+                    Ok(packet.clone())
                 }
             }),
             output_pairs: vec![],
@@ -249,6 +419,10 @@ impl State {
             phase: RoundPhase::InProgress,
             error: None,
             on_round_ended: Some(on_round_ended),
+            start_tick: 0,
+            state_hash_regions: vec![],
+            state_hashes: vec![],
+            round_time_limit_tick: None,
         }))))
     }
 
@@ -260,13 +434,24 @@ impl State {
 impl Fastforwarder {
     pub fn new(
         rom: &[u8],
+        bios: Option<&[u8]>,
         hooks: &'static (dyn game::Hooks + Send + Sync),
         local_player_index: u8,
+        fixed_rtc: Option<i64>,
+        hash_state: bool,
+        round_time_limit_tick: Option<u32>,
     ) -> anyhow::Result<Self> {
         let mut core = mgba::core::Core::new_gba("tango")?;
+        determinism::normalize(&mut core);
+        if let Some(bios) = bios {
+            bios::load_into(&mut core, bios)?;
+        }
         let rom_vf = mgba::vfile::VFile::open_memory(rom);
         core.as_mut().load_rom(rom_vf)?;
         hooks.patch(core.as_mut());
+        if let Some(fixed_rtc) = fixed_rtc {
+            core.as_mut().set_rtc_fixed(fixed_rtc);
+        }
 
         let state = State(std::sync::Arc::new(parking_lot::Mutex::new(None)));
 
@@ -275,14 +460,41 @@ impl Fastforwarder {
         core.set_traps(traps);
         core.as_mut().reset();
 
+        // Nothing this core produces is seen or heard: what's shown is the primary core after it loads the dirty state. It has no video buffer to render into either way, but audio would still be mixed for every tick it catches up on.
+        core.as_mut().set_audio_enabled(false);
+        core.as_mut().set_video_enabled(false);
+
         Ok(Fastforwarder {
             core,
             state,
             hooks,
             local_player_index,
+            state_hash_regions: if hash_state { hooks.state_hash_regions() } else { vec![] },
+            round_time_limit_tick,
+            tick_cost: stats::DeltaCounter::new(
+                stats::window::FASTFORWARD_TICK_COST,
+                std::time::Duration::from_millis(100),
+            ),
+            last_ticks: 0,
         })
     }
 
+    /// How long simulating a tick takes while fastforwarding, typically.
+    pub fn tick_cost(&self) -> std::time::Duration {
+        self.tick_cost.median()
+    }
+
+    /// How many ticks the last fastforward simulated.
+    pub fn last_ticks(&self) -> u32 {
+        self.last_ticks
+    }
+
+    /// Reads what the game's RNGs are at in a state.
+    pub fn rng_states(&mut self, state: &mgba::state::State) -> anyhow::Result<Vec<u32>> {
+        self.core.as_mut().load_state(state)?;
+        Ok(self.hooks.rng_states(self.core.as_mut()))
+    }
+
     pub fn fastforward(
         &mut self,
         state: &mgba::state::State,
@@ -295,6 +507,9 @@ impl Fastforwarder {
             dyn FnMut(lockstep::Pair<lockstep::Input, lockstep::PartialInput>) -> anyhow::Result<Vec<u8>> + Sync + Send,
         >,
     ) -> anyhow::Result<FastforwardResult> {
+        let start_time = std::time::Instant::now();
+        let ticks = dirty_tick.saturating_sub(current_tick).max(1);
+
         self.core.as_mut().load_state(state)?;
         self.hooks.prepare_for_fastforward(self.core.as_mut());
 
@@ -303,6 +518,8 @@ impl Fastforwarder {
             current_tick,
             local_player_index: self.local_player_index,
             input_pairs: input_pairs.into_iter().collect(),
+            // Fastforwarding never goes back to a frame checkpoint.
+            all_input_pairs: std::sync::Arc::from(vec![]),
             output_pairs: vec![],
             apply_shadow_input,
             local_packet: Some(lockstep::Packet {
@@ -317,6 +534,10 @@ impl Fastforwarder {
             phase: RoundPhase::InProgress,
             error: None,
             on_round_ended: Some(Box::new(|| {})),
+            start_tick: current_tick,
+            state_hash_regions: self.state_hash_regions.clone(),
+            state_hashes: vec![],
+            round_time_limit_tick: self.round_time_limit_tick,
         });
 
         loop {
@@ -325,11 +546,14 @@ impl Fastforwarder {
                 let mut inner_state = inner_state_guard.as_mut().unwrap();
                 if inner_state.committed_state.is_some() && inner_state.dirty_state.is_some() {
                     let state = inner_state_guard.take().expect("state");
+                    self.tick_cost.mark(start_time.elapsed() / ticks);
+                    self.last_ticks = ticks;
                     return Ok(FastforwardResult {
                         committed_state: state.committed_state.expect("committed state"),
                         dirty_state: state.dirty_state.expect("dirty state"),
                         round_result: state.round_result,
                         output_pairs: state.output_pairs,
+                        state_hashes: state.state_hashes,
                     });
                 }
                 inner_state.error = None;
@@ -275,4 +275,12 @@ impl MemoryMapper {
     }
 }
 
-pub type Scanner = scanner::Scanner<std::collections::HashMap<&'static (dyn game::Game + Send + Sync), Vec<u8>>>;
+#[derive(Clone, Default)]
+pub struct ScannedRoms {
+    /// The ROM for each game, read from the canonical copy if there's more than one.
+    pub by_game: std::collections::HashMap<&'static (dyn game::Game + Send + Sync), std::sync::Arc<[u8]>>,
+    /// ROM files that are copies of one another. Copies inside zip archives aren't included, as the archive may hold more than the copy.
+    pub duplicates: Vec<scanner::Duplicates>,
+}
+
+pub type Scanner = scanner::Scanner<ScannedRoms>;
@@ -1,25 +1,192 @@
 use std::io::Read;
 
-use crate::rom;
+use crate::{patch, rom};
 use byteorder::{ByteOrder, ReadBytesExt};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Part {
     String(String),
-    Command { op: u8, params: Vec<u8> },
+    Newline,
+    /// A character that's drawn as an icon rather than text, e.g. `MB` for the megabyte sign, which plain text shows in brackets.
+    Placeholder(String),
+    Command {
+        op: u8,
+        params: Vec<u8>,
+    },
+}
+
+/// A game's character set: the text each character code stands for.
+///
+/// Some characters stand for more than one letter (e.g. `V2`) or for an icon, written as its name in brackets (e.g. `[MB]`).
+#[derive(Clone, Debug)]
+pub struct Charset {
+    chars: Vec<String>,
+    /// The first code for each character, for encoding. Characters that can't be told apart when decoded are left out.
+    codes: std::collections::HashMap<String, usize>,
+    longest_char: usize,
+}
+
+impl Charset {
+    pub fn new(chars: Vec<String>) -> Self {
+        let mut codes = std::collections::HashMap::new();
+        for (code, c) in chars.iter().enumerate() {
+            if c.is_empty() || c == "�" {
+                continue;
+            }
+            codes.entry(c.clone()).or_insert(code);
+        }
+        let longest_char = codes.keys().map(|c| c.chars().count()).max().unwrap_or(0);
+        Self {
+            chars,
+            codes,
+            longest_char,
+        }
+    }
+
+    /// The game's own charset, unless the patch replaces it with one of its own.
+    pub fn with_overrides(default: &[&str], overrides: &patch::ROMOverrides) -> Self {
+        Self::new(
+            overrides
+                .charset
+                .as_ref()
+                .cloned()
+                .unwrap_or_else(|| default.iter().map(|s| s.to_string()).collect()),
+        )
+    }
+
+    pub fn get(&self, code: usize) -> Option<&str> {
+        self.chars.get(code).map(|c| c.as_str())
+    }
+
+    /// Finds the longest character at the start of some text, returning its code and how many chars of the text it covers.
+    #[allow(dead_code)]
+    fn longest_prefix(&self, text: &[char]) -> Option<(usize, usize)> {
+        (1..=self.longest_char.min(text.len())).rev().find_map(|n| {
+            self.codes
+                .get(&text[..n].iter().collect::<String>())
+                .map(|code| (*code, n))
+        })
+    }
+}
+
+/// Text that couldn't be written in a game's charset.
+#[allow(dead_code)]
+#[derive(thiserror::Error, Debug)]
+#[error("{text:?} at {offset} can't be written in this game's charset")]
+pub struct UnmappableChar {
+    pub text: String,
+    /// Where the text is, in chars.
+    pub offset: usize,
+}
+
+/// What to do when encoding text that isn't in the charset.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum Unmappable {
+    Error,
+    /// Writes this instead, which must itself be in the charset.
+    Replace(String),
+    Skip,
 }
 
 pub struct ParseOptions {
-    pub charset: Vec<String>,
+    pub charset: Charset,
     pub extension_ops: std::ops::RangeInclusive<u8>,
     pub eof_op: u8,
     pub newline_op: u8,
     pub commands: std::collections::HashMap<u8, usize>,
 }
 
+// Nothing writes text into games yet: this is here for replacing names in game.
+#[allow(dead_code)]
+impl ParseOptions {
+    fn is_op(&self, op: u8) -> bool {
+        op == self.eof_op
+            || op == self.newline_op
+            || self.commands.contains_key(&op)
+            || self.extension_ops.contains(&op)
+    }
+
+    fn encode_code(&self, code: usize, out: &mut Vec<u8>) -> bool {
+        if code < 0x100 && !self.is_op(code as u8) {
+            out.push(code as u8);
+            return true;
+        }
+        if let Some(op) = self
+            .extension_ops
+            .clone()
+            .find(|op| code >= *op as usize && code - *op as usize <= 0xff)
+        {
+            out.push(op);
+            out.push((code - op as usize) as u8);
+            return true;
+        }
+        false
+    }
+
+    /// Decodes text as plain text, stopping at the end of it. Commands are left out.
+    pub fn decode(&self, buf: &[u8]) -> Result<String, std::io::Error> {
+        Ok(plain_text(&parse(buf, self)?))
+    }
+
+    /// Encodes text in the game's charset, without the end of text marker.
+    ///
+    /// Characters are matched longest first, so e.g. `V2` is written as the single character for it where there is one.
+    pub fn encode(&self, text: &str, unmappable: &Unmappable) -> Result<Vec<u8>, UnmappableChar> {
+        let text = text.chars().collect::<Vec<_>>();
+        let mut out = vec![];
+        let mut i = 0;
+        while i < text.len() {
+            if text[i] == '\n' {
+                out.push(self.newline_op);
+                i += 1;
+                continue;
+            }
+
+            if let Some((code, n)) = self.charset.longest_prefix(&text[i..]) {
+                if self.encode_code(code, &mut out) {
+                    i += n;
+                    continue;
+                }
+            }
+
+            let error = UnmappableChar {
+                text: text[i].to_string(),
+                offset: i,
+            };
+            match unmappable {
+                Unmappable::Error => {
+                    return Err(error);
+                }
+                Unmappable::Replace(replacement) => {
+                    if !self
+                        .charset
+                        .codes
+                        .get(replacement)
+                        .map(|code| self.encode_code(*code, &mut out))
+                        .unwrap_or(false)
+                    {
+                        return Err(error);
+                    }
+                }
+                Unmappable::Skip => {}
+            }
+            i += 1;
+        }
+        Ok(out)
+    }
+}
+
 pub fn parse(mut buf: &[u8], options: &ParseOptions) -> Result<Vec<Part>, std::io::Error> {
     let mut parts = vec![];
     let mut out_buf = String::new();
+
+    let flush = |out_buf: &mut String, parts: &mut Vec<Part>| {
+        if !out_buf.is_empty() {
+            parts.push(Part::String(std::mem::take(out_buf)));
+        }
+    };
+
     while !buf.is_empty() {
         let op = buf.read_u8()?;
 
@@ -28,17 +195,13 @@ pub fn parse(mut buf: &[u8], options: &ParseOptions) -> Result<Vec<Part>, std::i
         }
 
         if op == options.newline_op {
-            out_buf.push('\n');
+            flush(&mut out_buf, &mut parts);
+            parts.push(Part::Newline);
             continue;
         }
 
         if let Some(len) = options.commands.get(&op) {
-            if !out_buf.is_empty() {
-                let mut next_buf = String::new();
-                std::mem::swap(&mut out_buf, &mut next_buf);
-                parts.push(Part::String(next_buf));
-            }
-
+            flush(&mut out_buf, &mut parts);
             let mut params = vec![0u8; *len];
             buf.read_exact(&mut params)?;
             parts.push(Part::Command { op, params });
@@ -47,15 +210,47 @@ pub fn parse(mut buf: &[u8], options: &ParseOptions) -> Result<Vec<Part>, std::i
             if options.extension_ops.contains(&op) {
                 c += buf.read_u8()? as usize;
             }
-            out_buf.push_str(&options.charset.get(c).cloned().unwrap_or_else(|| "�".to_string()));
+            match options.charset.get(c) {
+                Some(s) if s.len() > 2 && s.starts_with('[') && s.ends_with(']') => {
+                    flush(&mut out_buf, &mut parts);
+                    parts.push(Part::Placeholder(s[1..s.len() - 1].to_string()));
+                }
+                Some(s) => out_buf.push_str(s),
+                None => out_buf.push('�'),
+            }
         }
     }
-    if !out_buf.is_empty() {
-        parts.push(Part::String(out_buf));
-    }
+    flush(&mut out_buf, &mut parts);
     Ok(parts)
 }
 
+/// Flattens parsed text into a string, leaving commands out.
+pub fn plain_text(parts: &[Part]) -> String {
+    plain_text_with(parts, |_, _| None)
+}
+
+/// Flattens parsed text into a string, with commands replaced by whatever `command` gives for them.
+pub fn plain_text_with(parts: &[Part], mut command: impl FnMut(u8, &[u8]) -> Option<String>) -> String {
+    let mut out = String::new();
+    for part in parts {
+        match part {
+            Part::String(s) => out.push_str(s),
+            Part::Newline => out.push('\n'),
+            Part::Placeholder(name) => {
+                out.push('[');
+                out.push_str(name);
+                out.push(']');
+            }
+            Part::Command { op, params } => {
+                if let Some(s) = command(*op, params) {
+                    out.push_str(&s);
+                }
+            }
+        }
+    }
+    out
+}
+
 pub fn parse_entry(buf: &[u8], i: usize, options: &ParseOptions) -> Result<Vec<Part>, std::io::Error> {
     let offset = byteorder::LittleEndian::read_u16(&buf[i * 2..(i + 1) * 2]) as usize;
     let next_offset = byteorder::LittleEndian::read_u16(&buf[(i + 1) * 2..(i + 2) * 2]) as usize;
@@ -80,6 +275,8 @@ pub fn parse_modcard56_effect(parts: Vec<Part>, print_var_command: u8) -> rom::M
         .into_iter()
         .flat_map(|part| match part {
             Part::String(s) => vec![rom::Modcard56EffectTemplatePart::String(s)],
+            Part::Newline => vec![rom::Modcard56EffectTemplatePart::String("\n".to_string())],
+            Part::Placeholder(name) => vec![rom::Modcard56EffectTemplatePart::String(format!("[{}]", name))],
             Part::Command { op, params } if op == print_var_command => {
                 vec![rom::Modcard56EffectTemplatePart::PrintVar(params[2] as usize)]
             }
@@ -87,3 +284,120 @@ pub fn parse_modcard56_effect(parts: Vec<Part>, print_var_command: u8) -> rom::M
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTENSION_OP: u8 = 0xe4;
+    const EOF_OP: u8 = 0xe6;
+    const NEWLINE_OP: u8 = 0xe8;
+    const COMMAND_OP: u8 = 0xf0;
+
+    /// A made up charset, with a character past the first 256 and one whose code is an op, so both have to be written with the extension op.
+    fn options() -> ParseOptions {
+        let mut chars = vec!["�".to_string(); 0x120];
+        for (code, c) in [
+            (0x00, " "),
+            (0x01, "A"),
+            (0x02, "B"),
+            (0x03, "V"),
+            (0x04, "2"),
+            (0x05, "V2"),
+            (0x06, "[MB]"),
+            // Decodes the same as 0x01, so it's never encoded.
+            (0x07, "A"),
+            (0x08, "?"),
+            (EOF_OP as usize, "E"),
+            (0x110, "Z"),
+        ] {
+            chars[code] = c.to_string();
+        }
+        ParseOptions {
+            charset: Charset::new(chars),
+            extension_ops: EXTENSION_OP..=EXTENSION_OP,
+            eof_op: EOF_OP,
+            newline_op: NEWLINE_OP,
+            commands: std::collections::HashMap::from([(COMMAND_OP, 2)]),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let options = options();
+        for text in ["", "AB", "A B\nBA", "V2 V 2", "[MB]A", "ZEZ", "\n\n"] {
+            let encoded = options.encode(text, &Unmappable::Error).unwrap();
+            assert_eq!(options.decode(&encoded).unwrap(), text);
+            let mut terminated = encoded.clone();
+            terminated.extend([EOF_OP, 0x01]);
+            assert_eq!(options.decode(&terminated).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let options = options();
+        let encode = |text| options.encode(text, &Unmappable::Error).unwrap();
+        // The longest character is matched first.
+        assert_eq!(encode("V2"), vec![0x05]);
+        assert_eq!(encode("V 2"), vec![0x03, 0x00, 0x04]);
+        assert_eq!(encode("[MB]"), vec![0x06]);
+        // Of characters that decode the same, the first is written.
+        assert_eq!(encode("A"), vec![0x01]);
+        assert_eq!(encode("A\nB"), vec![0x01, NEWLINE_OP, 0x02]);
+        assert_eq!(encode("Z"), vec![EXTENSION_OP, (0x110 - EXTENSION_OP as usize) as u8]);
+        assert_eq!(encode("E"), vec![EXTENSION_OP, EOF_OP - EXTENSION_OP]);
+    }
+
+    #[test]
+    fn test_encode_unmappable() {
+        let options = options();
+        let error = options.encode("AB!A", &Unmappable::Error).unwrap_err();
+        assert_eq!((error.text.as_str(), error.offset), ("!", 2));
+        assert_eq!(
+            options.encode("AB!A", &Unmappable::Replace("?".to_string())).unwrap(),
+            vec![0x01, 0x02, 0x08, 0x01]
+        );
+        assert!(options.encode("AB!A", &Unmappable::Replace("!".to_string())).is_err());
+        assert_eq!(
+            options.encode("AB!A", &Unmappable::Skip).unwrap(),
+            vec![0x01, 0x02, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let options = options();
+        let parts = parse(
+            &[0x01, 0x06, 0x02, COMMAND_OP, 0xaa, 0xbb, NEWLINE_OP, 0x09, EOF_OP, 0x01],
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                Part::String("A".to_string()),
+                Part::Placeholder("MB".to_string()),
+                Part::String("B".to_string()),
+                Part::Command {
+                    op: COMMAND_OP,
+                    params: vec![0xaa, 0xbb]
+                },
+                Part::Newline,
+                Part::String("�".to_string()),
+            ]
+        );
+        assert_eq!(plain_text(&parts), "A[MB]B\n�");
+        assert_eq!(
+            plain_text_with(&parts, |op, params| Some(format!("<{:02x}:{}>", op, params.len()))),
+            "A[MB]B<f0:2>\n�"
+        );
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let options = options();
+        assert!(parse(&[0x01, COMMAND_OP, 0xaa], &options).is_err());
+        assert!(parse(&[0x01, EXTENSION_OP], &options).is_err());
+    }
+}
@@ -1,84 +1,290 @@
 use byteorder::ByteOrder;
+use rayon::prelude::*;
 
-use crate::{game, scanner};
+use crate::{fs, game, rom, scanner};
+
+pub mod diff;
+pub mod migration;
+pub mod registry;
+pub mod validation;
 
 #[derive(Clone)]
 pub struct ScannedSave {
     pub path: std::path::PathBuf,
     pub save: Box<dyn Save + Send + Sync>,
+    pub modified: Option<std::time::SystemTime>,
 }
 
-pub fn scan_saves(
-    path: &std::path::Path,
-) -> std::collections::HashMap<&'static (dyn game::Game + Send + Sync), Vec<ScannedSave>> {
-    let mut paths = std::collections::HashMap::new();
+/// A file in the saves directory that no game could parse.
+#[derive(Clone)]
+pub struct UnrecognizedSave {
+    pub path: std::path::PathBuf,
+    pub error: String,
+}
 
-    for entry in walkdir::WalkDir::new(path) {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(e) => {
-                log::error!("failed to read entry: {:?}", e);
-                continue;
-            }
-        };
+#[derive(Clone, Default)]
+pub struct ScannedSaves {
+    pub by_game: std::collections::HashMap<&'static (dyn game::Game + Send + Sync), Vec<ScannedSave>>,
+    pub unrecognized: Vec<UnrecognizedSave>,
+    /// Saves that are copies of one another.
+    pub duplicates: Vec<scanner::Duplicates>,
+}
 
-        if !entry.file_type().is_file() {
-            continue;
+impl ScannedSaves {
+    /// Gets a save as every game that recognized it.
+    pub fn find(&self, path: &std::path::Path) -> Vec<(&'static (dyn game::Game + Send + Sync), ScannedSave)> {
+        self.by_game
+            .iter()
+            .flat_map(|(game, saves)| {
+                saves
+                    .iter()
+                    .filter(|save| save.path == path)
+                    .map(move |save| (*game, save.clone()))
+            })
+            .collect()
+    }
+
+    /// Adds saves that were just written, e.g. copies of other saves.
+    pub fn insert(&mut self, saves: Vec<(&'static (dyn game::Game + Send + Sync), ScannedSave)>) {
+        for (game, save) in saves {
+            let game_saves = self.by_game.entry(game).or_insert_with(|| vec![]);
+            game_saves.retain(|s| s.path != save.path);
+            game_saves.push(save);
+            sort_saves(game_saves);
         }
+    }
 
-        let path = entry.path();
-        let buf = match std::fs::read(path) {
-            Ok(buf) => buf,
-            Err(e) => {
-                log::warn!("{}: {}", path.display(), e);
-                continue;
-            }
-        };
+    /// Takes out a save that was moved away, returning it as every game that recognized it.
+    pub fn remove(&mut self, path: &std::path::Path) -> Vec<(&'static (dyn game::Game + Send + Sync), ScannedSave)> {
+        let saves = self.find(path);
+        for game_saves in self.by_game.values_mut() {
+            game_saves.retain(|s| s.path != path);
+        }
+        saves
+    }
+
+    /// Moves a save that was renamed.
+    pub fn rename(&mut self, from: &std::path::Path, to: &std::path::Path) {
+        let saves = self
+            .remove(from)
+            .into_iter()
+            .map(|(game, save)| {
+                (
+                    game,
+                    ScannedSave {
+                        path: to.to_path_buf(),
+                        ..save
+                    },
+                )
+            })
+            .collect();
+        self.insert(saves);
+    }
+}
+
+fn sort_saves(saves: &mut [ScannedSave]) {
+    saves.sort_by_key(|s| {
+        let components = s
+            .path
+            .components()
+            .map(|c| c.as_os_str().to_os_string())
+            .collect::<Vec<_>>();
+        (-(components.len() as isize), components)
+    });
+}
 
-        let mut ok = false;
-        let mut errors = vec![];
-        for game in game::GAMES.iter() {
-            match game.parse_save(&buf) {
-                Ok(save) => {
-                    log::info!("{}: {:?}", path.display(), game.family_and_variant());
-                    let saves = paths.entry(*game).or_insert_with(|| vec![]);
-                    saves.push(ScannedSave {
-                        path: path.to_path_buf(),
-                        save,
-                    });
-                    ok = true;
+/// Scans for saves, in parallel.
+///
+/// Unless `full` is set, files that haven't changed since the last scan are not re-read if they're already loaded, and are only parsed as the games they were previously recognized as.
+pub fn scan_saves(path: &std::path::Path, full: bool, progress: &scanner::Progress<'_, ScannedSaves>) -> ScannedSaves {
+    let mut cache = scanner::Cache::<Vec<(String, u8)>>::load("saves");
+
+    let results = scanner::walk_files(path)
+        .into_par_iter()
+        .filter_map(|(path, stamp)| {
+            let cached_games = if !full {
+                stamp.as_ref().and_then(|stamp| cache.get(&path, stamp)).map(|cached| {
+                    cached
+                        .iter()
+                        .flat_map(|(family, variant)| game::find_by_family_and_variant(family, *variant))
+                        .collect::<Vec<_>>()
+                })
+            } else {
+                None
+            };
+
+            if let Some(cached_games) = cached_games.as_ref() {
+                let previous = progress.read();
+                if cached_games.is_empty() {
+                    // Files that no game recognized are cached too, but we need their error from the previous scan to list them.
+                    if let Some(unrecognized) = previous.unrecognized.iter().find(|s| s.path == path) {
+                        return Some((path, stamp, Err(unrecognized.clone())));
+                    }
+                } else {
+                    let saves = cached_games
+                        .iter()
+                        .flat_map(|game| {
+                            previous
+                                .by_game
+                                .get(game)
+                                .and_then(|saves| saves.iter().find(|s| s.path == path))
+                                .map(|save| (*game, save.clone()))
+                        })
+                        .collect::<Vec<_>>();
+                    if saves.len() == cached_games.len() {
+                        return Some((path, stamp, Ok(saves)));
+                    }
                 }
+            }
+
+            let buf = match fs::read(&path) {
+                Ok(buf) => buf,
                 Err(e) => {
-                    errors.push((*game, e));
+                    log::warn!("{}", e);
+                    return None;
+                }
+            };
+
+            let mut saves = vec![];
+            let mut errors = vec![];
+            for game in cached_games
+                .as_ref()
+                .filter(|games| !games.is_empty())
+                .map(|games| games.as_slice())
+                .unwrap_or(game::GAMES)
+            {
+                match game.parse_save(&buf) {
+                    Ok(save) => {
+                        log::info!("{}: {:?}", path.display(), game.family_and_variant());
+                        saves.push((
+                            *game,
+                            ScannedSave {
+                                path: path.clone(),
+                                save,
+                                modified: stamp.as_ref().map(|stamp| stamp.mtime()),
+                            },
+                        ));
+                    }
+                    Err(e) => {
+                        errors.push((*game, e));
+                    }
                 }
             }
-        }
 
-        if !ok {
-            log::warn!(
-                "{}:\n{}",
-                path.display(),
-                errors
+            if saves.is_empty() {
+                let error = errors
                     .iter()
                     .map(|(k, v)| format!("{:?}: {}", k.family_and_variant(), v))
                     .collect::<Vec<_>>()
-                    .join("\n")
+                    .join("\n");
+                log::warn!("{}:\n{}", path.display(), error);
+                return Some((path.clone(), stamp, Err(UnrecognizedSave { path, error })));
+            }
+
+            progress.update(|items| {
+                for (game, save) in saves.iter() {
+                    let game_saves = items.by_game.entry(*game).or_insert_with(|| vec![]);
+                    if let Some(existing) = game_saves.iter_mut().find(|s| s.path == save.path) {
+                        *existing = save.clone();
+                    } else {
+                        game_saves.push(save.clone());
+                        sort_saves(game_saves);
+                    }
+                }
+            });
+
+            Some((path, stamp, Ok(saves)))
+        })
+        .collect::<Vec<_>>();
+
+    let mut scanned = ScannedSaves::default();
+    let mut entries = std::collections::HashMap::new();
+    for (path, stamp, saves) in results {
+        let saves = match saves {
+            Ok(saves) => saves,
+            Err(unrecognized) => {
+                if let Some(stamp) = stamp {
+                    entries.insert(path, (stamp, vec![]));
+                }
+                scanned.unrecognized.push(unrecognized);
+                continue;
+            }
+        };
+
+        if let Some(stamp) = stamp {
+            entries.insert(
+                path,
+                (
+                    stamp,
+                    saves
+                        .iter()
+                        .map(|(game, _)| {
+                            let (family, variant) = game.family_and_variant();
+                            (family.to_string(), variant)
+                        })
+                        .collect::<Vec<_>>(),
+                ),
             );
         }
+
+        for (game, save) in saves {
+            scanned.by_game.entry(game).or_insert_with(|| vec![]).push(save);
+        }
+    }
+
+    if let Err(e) = cache.replace_and_save(entries) {
+        log::warn!("failed to save save scan cache: {:?}", e);
     }
 
-    for (_, saves) in paths.iter_mut() {
-        saves.sort_by_key(|s| {
-            let components = s
-                .path
-                .components()
-                .map(|c| c.as_os_str().to_os_string())
-                .collect::<Vec<_>>();
-            (-(components.len() as isize), components)
-        });
+    for (_, saves) in scanned.by_game.iter_mut() {
+        sort_saves(saves);
+    }
+    scanned.unrecognized.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let contents = scanned
+        .by_game
+        .values()
+        .flatten()
+        .map(|save| (save.path.as_path(), save.save.to_vec()))
+        .collect::<Vec<_>>();
+    // Saves recognized as more than one game are compared as each of them, so the same copies may be found more than once.
+    for duplicates in scanner::find_duplicates(contents.iter().map(|(path, contents)| (*path, contents.as_slice()))) {
+        if !scanned.duplicates.contains(&duplicates) {
+            scanned.duplicates.push(duplicates);
+        }
     }
 
-    paths
+    scanned
+}
+
+/// Writes a save file such that it's either entirely written or not at all, even if we crash partway through.
+pub fn write_atomically(path: &std::path::Path, data: &[u8]) -> std::io::Result<()> {
+    let with_path = |e: std::io::Error| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+    let mut f =
+        tempfile::NamedTempFile::new_in(fs::extended(path.parent().unwrap_or_else(|| std::path::Path::new("."))))
+            .map_err(with_path)?;
+    std::io::Write::write_all(&mut f, data).map_err(with_path)?;
+    f.as_file().sync_all().map_err(with_path)?;
+    f.persist(fs::extended(path)).map_err(|e| with_path(e.error))?;
+    Ok(())
+}
+
+/// A path next to a save to write changes to without touching the save itself, timestamped so it won't clobber anything.
+pub fn new_save_path(path: &std::path::Path) -> std::path::PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let suffix = time::OffsetDateTime::from(std::time::SystemTime::now())
+        .format(time::macros::format_description!(
+            "[year padding:zero][month padding:zero repr:numerical][day padding:zero][hour padding:zero][minute padding:zero][second padding:zero]"
+        ))
+        .expect("format time");
+    let mut new_path = path.with_file_name(format!("{}-{}", stem, suffix));
+    if let Some(extension) = path.extension() {
+        new_path.set_extension(extension);
+    }
+    new_path
 }
 
 pub trait SaveClone {
@@ -125,6 +331,24 @@ where
     fn view_navi(&self) -> Option<Box<dyn NaviView + '_>> {
         None
     }
+
+    fn view_progress(&self) -> Option<Box<dyn ProgressView + '_>> {
+        None
+    }
+
+    /// Checks the save for values the game could never have produced by itself, against the ROM it'll be played with.
+    ///
+    /// Games opt into this one at a time: by default every save is accepted.
+    fn validate(&self, _assets: &dyn rom::Assets) -> Result<(), validation::IllegalValue> {
+        Ok(())
+    }
+
+    /// Renumbers the chips, NaviCust parts and modcards in the save and fixes up its checksum, for when a patch has renumbered them.
+    ///
+    /// Ids with no mapping are reported all together: see [`migration::Remapper`].
+    fn remap_ids(&mut self, _table: &migration::Table) -> Result<(), migration::Error> {
+        Err(migration::Error::Unsupported)
+    }
 }
 
 impl Clone for Box<dyn Save + Send + Sync> {
@@ -149,7 +373,7 @@ pub fn compute_save_raw_checksum(buf: &[u8], checksum_offset: usize) -> u32 {
             .sum::<u32>()
 }
 
-#[derive(Clone, Debug, std::hash::Hash, Eq, PartialEq)]
+#[derive(serde::Serialize, Clone, Debug, std::hash::Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Chip {
     pub id: usize,
     pub code: usize,
@@ -166,6 +390,10 @@ pub trait ChipsView<'a> {
     fn regular_chip_index(&self, folder_index: usize) -> Option<usize>;
     fn tag_chip_indexes(&self, folder_index: usize) -> Option<[usize; 2]>;
     fn chip(&self, folder_index: usize, chip_index: usize) -> Option<Chip>;
+    /// The IDs of the chips with any copies in the pack, if the game's pack layout is known.
+    fn pack_ids(&self) -> Option<Vec<usize>> {
+        None
+    }
 }
 
 #[derive(Clone, Debug, std::hash::Hash, Eq, PartialEq)]
@@ -211,10 +439,24 @@ pub trait NavicustView<'a> {
     fn navicust_part(&self, i: usize) -> Option<NavicustPart>;
 }
 
+pub trait ProgressView<'a> {
+    fn flag(&self, id: usize) -> bool;
+    fn library_count(&self) -> usize;
+    /// The IDs of the chips entered into the library, if the game's library layout is known.
+    fn library(&self) -> Option<Vec<usize>> {
+        None
+    }
+    fn zenny(&self) -> Option<u32> {
+        None
+    }
+    fn bugfrags(&self) -> Option<u32> {
+        None
+    }
+}
+
 pub trait DarkAIView<'a> {
     fn chip_use_count(&self, id: usize) -> Option<u16>;
     fn secondary_chip_use_count(&self, id: usize) -> Option<u16>;
 }
 
-pub type Scanner =
-    scanner::Scanner<std::collections::HashMap<&'static (dyn game::Game + Send + Sync), Vec<ScannedSave>>>;
+pub type Scanner = scanner::Scanner<ScannedSaves>;
@@ -0,0 +1,283 @@
+use crate::save;
+
+/// What was added to and removed from a collection. Duplicates are counted, so a second copy of something that was already there shows up as added.
+#[derive(serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Changes<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+}
+
+impl<T> Changes<T>
+where
+    T: Ord,
+{
+    fn between(mut before: Vec<T>, mut after: Vec<T>) -> Self {
+        before.sort();
+        after.sort();
+
+        let mut added = vec![];
+        let mut removed = vec![];
+        let mut before = before.into_iter().peekable();
+        let mut after = after.into_iter().peekable();
+        loop {
+            match (before.peek(), after.peek()) {
+                (Some(b), Some(a)) => match b.cmp(a) {
+                    std::cmp::Ordering::Less => removed.push(before.next().unwrap()),
+                    std::cmp::Ordering::Greater => added.push(after.next().unwrap()),
+                    std::cmp::Ordering::Equal => {
+                        before.next();
+                        after.next();
+                    }
+                },
+                (Some(_), None) => removed.push(before.next().unwrap()),
+                (None, Some(_)) => added.push(after.next().unwrap()),
+                (None, None) => break,
+            }
+        }
+
+        Self { added, removed }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A value as it was in each save.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change<T> {
+    pub before: T,
+    pub after: T,
+}
+
+impl<T> Change<T>
+where
+    T: PartialEq,
+{
+    pub fn is_empty(&self) -> bool {
+        self.before == self.after
+    }
+}
+
+/// A navicust part, regardless of where it's placed.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, std::hash::Hash)]
+pub struct Part {
+    pub id: usize,
+    pub variant: usize,
+}
+
+/// The differences between two saves of the same game.
+///
+/// Categories the game doesn't expose are left out entirely, which is not the same as them being unchanged.
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SaveDiff {
+    /// The chips in each folder, by folder index. Folders only one of the saves has are compared against an empty folder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folders: Option<Vec<Changes<save::Chip>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub navicust_parts: Option<Changes<Part>>,
+    /// The IDs of chips entered into the library.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub library: Option<Changes<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zenny: Option<Change<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bugfrags: Option<Change<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub navi: Option<Change<usize>>,
+}
+
+impl SaveDiff {
+    /// Whether nothing that could be compared changed.
+    pub fn is_empty(&self) -> bool {
+        self.folders
+            .as_ref()
+            .map(|folders| folders.iter().all(|folder| folder.is_empty()))
+            .unwrap_or(true)
+            && self.navicust_parts.as_ref().map(|c| c.is_empty()).unwrap_or(true)
+            && self.library.as_ref().map(|c| c.is_empty()).unwrap_or(true)
+            && self.zenny.as_ref().map(|c| c.is_empty()).unwrap_or(true)
+            && self.bugfrags.as_ref().map(|c| c.is_empty()).unwrap_or(true)
+            && self.navi.as_ref().map(|c| c.is_empty()).unwrap_or(true)
+    }
+}
+
+fn folder_chips(chips_view: &dyn save::ChipsView<'_>, folder_index: usize) -> Vec<save::Chip> {
+    if folder_index >= chips_view.num_folders() {
+        return vec![];
+    }
+    (0..30)
+        .flat_map(|chip_index| chips_view.chip(folder_index, chip_index))
+        .collect()
+}
+
+fn navicust_parts(navicust_view: &dyn save::NavicustView<'_>) -> Vec<Part> {
+    (0..navicust_view.count())
+        .flat_map(|i| navicust_view.navicust_part(i))
+        .map(|part| Part {
+            id: part.id,
+            variant: part.variant,
+        })
+        .collect()
+}
+
+fn both<T>(before: Option<T>, after: Option<T>) -> Option<(T, T)> {
+    Some((before?, after?))
+}
+
+/// Compares two saves, which must be of the same game.
+pub fn diff(before: &dyn save::Save, after: &dyn save::Save) -> SaveDiff {
+    let folders = both(before.view_chips(), after.view_chips()).map(|(before, after)| {
+        (0..before.num_folders().max(after.num_folders()))
+            .map(|folder_index| {
+                Changes::between(
+                    folder_chips(before.as_ref(), folder_index),
+                    folder_chips(after.as_ref(), folder_index),
+                )
+            })
+            .collect()
+    });
+
+    let navicust_parts = both(before.view_navicust(), after.view_navicust())
+        .map(|(before, after)| Changes::between(navicust_parts(before.as_ref()), navicust_parts(after.as_ref())));
+
+    let navi = both(before.view_navi(), after.view_navi()).map(|(before, after)| Change {
+        before: before.navi(),
+        after: after.navi(),
+    });
+
+    let (library, zenny, bugfrags) = if let Some((before, after)) = both(before.view_progress(), after.view_progress())
+    {
+        (
+            both(before.library(), after.library()).map(|(before, after)| Changes::between(before, after)),
+            both(before.zenny(), after.zenny()).map(|(before, after)| Change { before, after }),
+            both(before.bugfrags(), after.bugfrags()).map(|(before, after)| Change { before, after }),
+        )
+    } else {
+        (None, None, None)
+    };
+
+    SaveDiff {
+        folders,
+        navicust_parts,
+        library,
+        zenny,
+        bugfrags,
+        navi,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::ByteOrder;
+
+    use super::*;
+    use crate::game;
+    use crate::save::compute_save_raw_checksum;
+
+    #[test]
+    fn test_changes_between() {
+        assert_eq!(
+            Changes::between(vec![3, 1, 2, 2], vec![2, 4, 1, 4]),
+            Changes {
+                added: vec![4, 4],
+                removed: vec![2, 3],
+            }
+        );
+        assert!(Changes::between(vec![1, 2], vec![2, 1]).is_empty());
+    }
+
+    /// Builds a US BN6 Gregar save. Its mask is left at zero, so only the checksum needs to be filled in.
+    fn bn6_save(folders: &[[(u16, u16); 30]], parts: &[(u8, u8)], library: &[usize]) -> Vec<u8> {
+        const SRAM_START_OFFSET: usize = 0x0100;
+        const SRAM_SIZE: usize = 0x6710;
+        const CHECKSUM_OFFSET: usize = 0x1c6c;
+
+        let mut buf = vec![0; 65536];
+        let sram = &mut buf[SRAM_START_OFFSET..SRAM_START_OFFSET + SRAM_SIZE];
+        sram[0x1c70..0x1c70 + 20].copy_from_slice(b"REXE6 G 20060110a US");
+        sram[0x1c09] = folders.len() as u8;
+        for (folder_index, folder) in folders.iter().enumerate() {
+            for (chip_index, (id, code)) in folder.iter().enumerate() {
+                let offset = 0x2178 + folder_index * (30 * 2) + chip_index * 2;
+                byteorder::LittleEndian::write_u16(&mut sram[offset..offset + 2], id | code << 9);
+            }
+        }
+        for (i, (id, variant)) in parts.iter().enumerate() {
+            sram[0x4190 + i * 8] = id * 4 + variant;
+        }
+        for id in library {
+            sram[0x0c00 + (id >> 3)] |= 0x80 >> (id & 7);
+        }
+        let checksum = compute_save_raw_checksum(sram, CHECKSUM_OFFSET) + 0x72;
+        byteorder::LittleEndian::write_u32(&mut sram[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4], checksum);
+        buf
+    }
+
+    fn parse(game: &'static (dyn game::Game + Send + Sync), raw: &[u8]) -> Box<dyn save::Save + Send + Sync> {
+        game.parse_save(raw).unwrap()
+    }
+
+    #[test]
+    fn test_diff() {
+        let game = game::find_by_family_and_variant("bn6", 0).unwrap();
+
+        let mut folder = [(0u16, 0u16); 30];
+        for (i, chip) in folder.iter_mut().enumerate() {
+            *chip = (i as u16 + 1, 0);
+        }
+        let before = parse(game, &bn6_save(&[folder], &[(1, 0), (2, 1)], &[1, 2, 3]));
+
+        let mut changed_folder = folder;
+        changed_folder[0] = (100, 3);
+        changed_folder[1] = (100, 3);
+        let after = parse(
+            game,
+            &bn6_save(&[changed_folder, folder], &[(2, 1), (3, 2)], &[1, 2, 3, 200]),
+        );
+
+        assert_eq!(
+            diff(before.as_ref(), after.as_ref()),
+            SaveDiff {
+                folders: Some(vec![
+                    Changes {
+                        added: vec![save::Chip { id: 100, code: 3 }, save::Chip { id: 100, code: 3 }],
+                        removed: vec![save::Chip { id: 1, code: 0 }, save::Chip { id: 2, code: 0 }],
+                    },
+                    // The folder the first save doesn't have is compared against an empty one.
+                    Changes {
+                        added: folder
+                            .iter()
+                            .map(|(id, code)| save::Chip {
+                                id: *id as usize,
+                                code: *code as usize,
+                            })
+                            .collect(),
+                        removed: vec![],
+                    },
+                ]),
+                navicust_parts: Some(Changes {
+                    added: vec![Part { id: 3, variant: 2 }],
+                    removed: vec![Part { id: 1, variant: 0 }],
+                }),
+                library: Some(Changes {
+                    added: vec![200],
+                    removed: vec![],
+                }),
+                // BN6 doesn't expose these, so they're left out rather than reported as unchanged.
+                zenny: None,
+                bugfrags: None,
+                navi: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        let game = game::find_by_family_and_variant("bn6", 0).unwrap();
+        let raw = bn6_save(&[[(1, 0); 30]], &[(1, 0)], &[1]);
+        let d = diff(parse(game, &raw).as_ref(), parse(game, &raw).as_ref());
+        assert!(d.is_empty());
+        assert!(d.folders.is_some() && d.navicust_parts.is_some() && d.library.is_some());
+    }
+}
@@ -0,0 +1,507 @@
+//! Bringing a save over to a newer version of a patch that renumbered chips, NaviCust parts or modcards.
+//!
+//! A patch version can ship a table mapping old ids to new ones, along with the version it migrates from, and tables chain so a save can be brought over several versions at once. A table that renumbers one kind of id has to list every id of that kind, even the ones that stay the same: an id it leaves out has no mapping, and a save containing one is refused rather than carried over with an id that now means something else.
+//!
+//! Besides folders, chips are kept in the pack as counts indexed by id, so renumbering chips moves each id's counts along with it: otherwise the pack would be left out of step with the folders.
+
+use byteorder::ByteOrder;
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::{fs, save};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Kind {
+    Chip,
+    NavicustPart,
+    Modcard,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Kind::Chip => "chip",
+            Kind::NavicustPart => "navicust part",
+            Kind::Modcard => "modcard",
+        })
+    }
+}
+
+/// TOML keys are always strings, so ids are written as e.g. `"12" = 14` and parsed back here.
+fn deserialize_id_map<'de, D>(deserializer: D) -> Result<Option<std::collections::BTreeMap<usize, usize>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<std::collections::BTreeMap<String, usize>>::deserialize(deserializer)?
+        .map(|ids| {
+            ids.into_iter()
+                .map(|(old, new)| Ok((old.parse().map_err(serde::de::Error::custom)?, new)))
+                .collect()
+        })
+        .transpose()
+}
+
+/// Where each id moved to between two versions of a patch. Kinds of id left out weren't renumbered.
+#[derive(serde::Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Table {
+    #[serde(deserialize_with = "deserialize_id_map")]
+    pub chips: Option<std::collections::BTreeMap<usize, usize>>,
+    #[serde(deserialize_with = "deserialize_id_map")]
+    pub navicust_parts: Option<std::collections::BTreeMap<usize, usize>>,
+    #[serde(deserialize_with = "deserialize_id_map")]
+    pub modcards: Option<std::collections::BTreeMap<usize, usize>>,
+}
+
+impl Table {
+    fn ids(&self, kind: Kind) -> Option<&std::collections::BTreeMap<usize, usize>> {
+        match kind {
+            Kind::Chip => self.chips.as_ref(),
+            Kind::NavicustPart => self.navicust_parts.as_ref(),
+            Kind::Modcard => self.modcards.as_ref(),
+        }
+    }
+
+    /// Where an id moved to, or None if it has no mapping. Id 0 stands for an empty slot, so it never moves.
+    pub fn map(&self, kind: Kind, id: usize) -> Option<usize> {
+        if id == 0 {
+            return Some(0);
+        }
+        match self.ids(kind) {
+            Some(ids) => ids.get(&id).cloned(),
+            None => Some(id),
+        }
+    }
+
+    /// The table that applies this one and then `next`. Ids that `next` has no mapping for are dropped, so they're still caught.
+    pub fn then(&self, next: &Table) -> Table {
+        let compose = |kind| match (self.ids(kind), next.ids(kind)) {
+            (Some(first), Some(second)) => Some(
+                first
+                    .iter()
+                    .flat_map(|(old, middle)| second.get(middle).map(|new| (*old, *new)))
+                    .collect(),
+            ),
+            (Some(first), None) => Some(first.clone()),
+            (None, second) => second.cloned(),
+        };
+        Table {
+            chips: compose(Kind::Chip),
+            navicust_parts: compose(Kind::NavicustPart),
+            modcards: compose(Kind::Modcard),
+        }
+    }
+}
+
+/// An id in a save that a table has no mapping for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Unmapped {
+    pub kind: Kind,
+    pub id: usize,
+}
+
+impl std::fmt::Display for Unmapped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.kind, self.id)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("saves for this game can't be migrated")]
+    Unsupported,
+
+    #[error("no mapping for {}", .0.iter().join(", "))]
+    Unmapped(Vec<Unmapped>),
+
+    #[error("{kind} {id} doesn't fit in the save")]
+    OutOfRange { kind: Kind, id: usize },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Applies a table to each part of a save in turn. Ids with no mapping are left where they are and collected up, so every one of them is reported by [`Remapper::finish`] rather than just the first.
+pub struct Remapper<'a> {
+    table: &'a Table,
+    unmapped: std::collections::BTreeSet<Unmapped>,
+}
+
+impl<'a> Remapper<'a> {
+    pub fn new(table: &'a Table) -> Self {
+        Self {
+            table,
+            unmapped: std::collections::BTreeSet::new(),
+        }
+    }
+
+    fn remap(&mut self, kind: Kind, id: usize, max: usize) -> Result<usize, Error> {
+        let new_id = if let Some(new_id) = self.table.map(kind, id) {
+            new_id
+        } else {
+            self.unmapped.insert(Unmapped { kind, id });
+            return Ok(id);
+        };
+        if new_id > max {
+            return Err(Error::OutOfRange { kind, id: new_id });
+        }
+        Ok(new_id)
+    }
+
+    /// Renumbers chips stored as 9 bits of id under 7 bits of code, as games from BN4 on store their folders.
+    pub fn packed_chips(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        for raw in buf.chunks_exact_mut(2) {
+            let v = byteorder::LittleEndian::read_u16(raw);
+            let id = self.remap(Kind::Chip, (v & 0x1ff) as usize, 0x1ff)?;
+            byteorder::LittleEndian::write_u16(raw, (v & !0x1ff) | id as u16);
+        }
+        Ok(())
+    }
+
+    /// Moves a pack kept as an `entry_size` byte entry of counts per chip id, in id order, so each chip's counts end up under its new id. The counts of chips moved onto the same id are added together.
+    pub fn pack(&mut self, buf: &mut [u8], entry_size: usize) -> Result<(), Error> {
+        let max = buf.len() / entry_size - 1;
+        let mut remapped = vec![0u8; buf.len()];
+        for (id, entry) in buf.chunks_exact(entry_size).enumerate() {
+            if entry.iter().all(|count| *count == 0) {
+                continue;
+            }
+            let new_id = self.remap(Kind::Chip, id, max)?;
+            for (count, moved) in remapped[new_id * entry_size..(new_id + 1) * entry_size]
+                .iter_mut()
+                .zip(entry)
+            {
+                *count = count.saturating_add(*moved);
+            }
+        }
+        buf.copy_from_slice(&remapped);
+        Ok(())
+    }
+
+    /// Renumbers NaviCust parts stored as 8 byte entries starting with the id times 4 plus the color.
+    pub fn navicust_parts(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        for entry in buf.chunks_exact_mut(8) {
+            let raw = entry[0];
+            if raw == 0 {
+                continue;
+            }
+            let id = self.remap(Kind::NavicustPart, (raw / 4) as usize, 0x3f)?;
+            entry[0] = (id * 4) as u8 + raw % 4;
+        }
+        Ok(())
+    }
+
+    /// Renumbers BN5 and BN6 modcards, stored a byte each with the top bit set if disabled.
+    pub fn modcard56s(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        for raw in buf.iter_mut() {
+            let id = self.remap(Kind::Modcard, (*raw & 0x7f) as usize, 0x7f)?;
+            *raw = (*raw & 0x80) | id as u8;
+        }
+        Ok(())
+    }
+
+    /// Fails with every id that had no mapping, if there were any.
+    pub fn finish(self) -> Result<(), Error> {
+        if !self.unmapped.is_empty() {
+            return Err(Error::Unmapped(self.unmapped.into_iter().collect()));
+        }
+        Ok(())
+    }
+}
+
+/// Finds every id in the save's folders, pack, NaviCust and modcards that the table has no mapping for.
+pub fn find_unmapped(save: &(dyn save::Save + Send + Sync), table: &Table) -> Vec<Unmapped> {
+    let mut ids = std::collections::BTreeSet::new();
+    if let Some(chips_view) = save.view_chips() {
+        for folder_index in 0..chips_view.num_folders() {
+            for chip in (0..30).flat_map(|i| chips_view.chip(folder_index, i)) {
+                ids.insert((Kind::Chip, chip.id));
+            }
+        }
+        for id in chips_view.pack_ids().unwrap_or_default() {
+            ids.insert((Kind::Chip, id));
+        }
+    }
+    if let Some(navicust_view) = save.view_navicust() {
+        for ncp in (0..navicust_view.count()).flat_map(|i| navicust_view.navicust_part(i)) {
+            ids.insert((Kind::NavicustPart, ncp.id));
+        }
+    }
+    if let Some(save::ModcardsView::Modcard56s(modcard56s_view)) = save.view_modcards() {
+        for modcard in (0..modcard56s_view.count()).flat_map(|i| modcard56s_view.modcard(i)) {
+            ids.insert((Kind::Modcard, modcard.id));
+        }
+    }
+    ids.into_iter()
+        .filter(|(kind, id)| table.map(*kind, *id).is_none())
+        .map(|(kind, id)| Unmapped { kind, id })
+        .collect()
+}
+
+/// Applies a table to a copy of the save. Nothing is migrated if any id in it has no mapping.
+pub fn migrate(
+    save: &(dyn save::Save + Send + Sync),
+    table: &Table,
+) -> Result<Box<dyn save::Save + Send + Sync>, Error> {
+    let unmapped = find_unmapped(save, table);
+    if !unmapped.is_empty() {
+        return Err(Error::Unmapped(unmapped));
+    }
+    let mut migrated = save.clone_box();
+    migrated.remap_ids(table)?;
+    Ok(migrated)
+}
+
+/// Writes a migrated save over the original, copying the original into `backups_path` first. Returns where the backup went.
+pub fn write_with_backup(
+    path: &std::path::Path,
+    backups_path: &std::path::Path,
+    from_version: &semver::Version,
+    migrated: &[u8],
+) -> Result<std::path::PathBuf, Error> {
+    fs::create_dir_all(backups_path)?;
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut backup_path = backups_path.join(format!("{}-v{}-{}", stem, from_version, ts));
+    if let Some(extension) = path.extension() {
+        backup_path.set_extension(extension);
+    }
+    fs::copy(path, &backup_path)?;
+    save::write_atomically(path, migrated)?;
+    Ok(backup_path)
+}
+
+/// The patch version a save was last played with.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct LastUsed {
+    pub patch: String,
+    pub version: semver::Version,
+}
+
+struct HistoryInner {
+    path: std::path::PathBuf,
+    entries: std::collections::BTreeMap<String, LastUsed>,
+}
+
+/// Which patch version each save was last played with, stored as a single JSON file in the data directory, so it can be noticed when the patch has since been updated.
+#[derive(Clone)]
+pub struct History(std::sync::Arc<parking_lot::Mutex<HistoryInner>>);
+
+impl History {
+    /// Loads the history. If it can't be read, it starts out empty: the worst that happens is a migration not being offered.
+    pub fn load(path: std::path::PathBuf) -> Self {
+        let entries = match fs::read(&path) {
+            Ok(raw) => match serde_json::from_slice(&raw) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    log::warn!("failed to parse save patch history: {}", e);
+                    Default::default()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => {
+                log::warn!("failed to read save patch history: {}", e);
+                Default::default()
+            }
+        };
+        Self(std::sync::Arc::new(parking_lot::Mutex::new(HistoryInner {
+            path,
+            entries,
+        })))
+    }
+
+    fn key(save_path: &std::path::Path) -> String {
+        save_path.to_string_lossy().to_string()
+    }
+
+    pub fn get(&self, save_path: &std::path::Path) -> Option<LastUsed> {
+        self.0.lock().entries.get(&Self::key(save_path)).cloned()
+    }
+
+    /// Records that a save was just played with a patch version, or without a patch.
+    pub fn record(&self, save_path: &std::path::Path, patch: Option<(&str, &semver::Version)>) -> std::io::Result<()> {
+        let mut inner = self.0.lock();
+        let key = Self::key(save_path);
+        let last_used = patch.map(|(patch, version)| LastUsed {
+            patch: patch.to_string(),
+            version: version.clone(),
+        });
+        if inner.entries.get(&key) == last_used.as_ref() {
+            return Ok(());
+        }
+        if let Some(last_used) = last_used {
+            inner.entries.insert(key, last_used);
+        } else {
+            inner.entries.remove(&key);
+        }
+        save::write_atomically(&inner.path, &serde_json::to_vec_pretty(&inner.entries)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::ByteOrder;
+
+    use super::*;
+    use crate::game;
+
+    /// Swaps chips 1 and 2 and moves chip 3 to 200, leaving every other chip where it was.
+    fn renumbering_table() -> Table {
+        let mut chips = (1..411)
+            .map(|id| (id, id))
+            .collect::<std::collections::BTreeMap<_, _>>();
+        chips.insert(1, 2);
+        chips.insert(2, 1);
+        chips.insert(3, 200);
+        chips.remove(&200);
+        Table {
+            chips: Some(chips),
+            ..Default::default()
+        }
+    }
+
+    fn inverse(table: &Table) -> Table {
+        Table {
+            chips: table
+                .chips
+                .as_ref()
+                .map(|chips| chips.iter().map(|(old, new)| (*new, *old)).collect()),
+            ..Default::default()
+        }
+    }
+
+    /// Builds a US BN6 Gregar save with one folder and the given pack entries, set as each id's count for its first code. Its mask is left at zero, so only the checksum needs to be filled in.
+    fn bn6_save(folder: &[(u16, u16); 30], pack: &[(usize, u8)]) -> Vec<u8> {
+        const SRAM_START_OFFSET: usize = 0x0100;
+        const SRAM_SIZE: usize = 0x6710;
+        const CHECKSUM_OFFSET: usize = 0x1c6c;
+
+        let mut buf = vec![0; 65536];
+        let sram = &mut buf[SRAM_START_OFFSET..SRAM_START_OFFSET + SRAM_SIZE];
+        sram[0x1c70..0x1c70 + 20].copy_from_slice(b"REXE6 G 20060110a US");
+        sram[0x1c09] = 1;
+        for (chip_index, (id, code)) in folder.iter().enumerate() {
+            let offset = 0x2178 + chip_index * 2;
+            byteorder::LittleEndian::write_u16(&mut sram[offset..offset + 2], id | code << 9);
+        }
+        for (id, count) in pack {
+            sram[0x2230 + id * 0xc] = *count;
+        }
+        let checksum = save::compute_save_raw_checksum(sram, CHECKSUM_OFFSET) + 0x72;
+        byteorder::LittleEndian::write_u32(&mut sram[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4], checksum);
+        buf
+    }
+
+    #[test]
+    fn test_pack_round_trip() {
+        let table = renumbering_table();
+        let count = |pack: &[u8], id: usize, code: usize| pack[id * 0xc + code];
+        let mut pack = vec![0u8; 411 * 0xc];
+        for (id, code, count) in [(1, 0, 3), (2, 1, 1), (3, 2, 2), (4, 0, 5)] {
+            pack[id * 0xc + code] = count;
+        }
+
+        let mut migrated = pack.clone();
+        let mut remapper = Remapper::new(&table);
+        remapper.pack(&mut migrated, 0xc).unwrap();
+        remapper.finish().unwrap();
+        assert_eq!(count(&migrated, 1, 1), 1);
+        assert_eq!(count(&migrated, 2, 0), 3);
+        assert_eq!(count(&migrated, 3, 2), 0);
+        assert_eq!(count(&migrated, 200, 2), 2);
+        assert_eq!(count(&migrated, 4, 0), 5);
+
+        let inverse = inverse(&table);
+        let mut remapper = Remapper::new(&inverse);
+        remapper.pack(&mut migrated, 0xc).unwrap();
+        remapper.finish().unwrap();
+        assert_eq!(migrated, pack);
+    }
+
+    #[test]
+    fn test_pack_adds_up_chips_moved_onto_the_same_id() {
+        let mut pack = vec![0, 0, 1, 0, 2, 0, 0, 0];
+        let table = Table {
+            chips: Some([(1, 3), (2, 3), (3, 1)].into_iter().collect()),
+            ..Default::default()
+        };
+        let mut remapper = Remapper::new(&table);
+        remapper.pack(&mut pack, 2).unwrap();
+        remapper.finish().unwrap();
+        assert_eq!(pack, vec![0, 0, 0, 0, 0, 0, 3, 0]);
+    }
+
+    #[test]
+    fn test_remapper_reports_every_unmapped_id() {
+        let table = Table {
+            chips: Some([(1, 1)].into_iter().collect()),
+            navicust_parts: Some([(1, 1)].into_iter().collect()),
+            ..Default::default()
+        };
+        let mut folder = vec![0u8; 3 * 2];
+        byteorder::LittleEndian::write_u16(&mut folder[0..2], 1);
+        byteorder::LittleEndian::write_u16(&mut folder[2..4], 5 | 2 << 9);
+        byteorder::LittleEndian::write_u16(&mut folder[4..6], 6);
+        let mut pack = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        let mut parts = vec![0u8; 2 * 8];
+        parts[0] = 4 * 4 + 1;
+
+        let mut remapper = Remapper::new(&table);
+        remapper.packed_chips(&mut folder).unwrap();
+        remapper.pack(&mut pack, 2).unwrap();
+        remapper.navicust_parts(&mut parts).unwrap();
+        assert_eq!(
+            remapper.finish().unwrap_err().to_string(),
+            "no mapping for chip 3, chip 5, chip 6, navicust part 4"
+        );
+    }
+
+    #[test]
+    fn test_migrate_round_trip() {
+        let game = game::find_by_family_and_variant("bn6", 0).unwrap();
+        let mut folder = [(0u16, 0u16); 30];
+        for (i, chip) in folder.iter_mut().enumerate() {
+            *chip = (i as u16 + 1, (i % 4) as u16);
+        }
+        let raw = bn6_save(&folder, &[(1, 2), (3, 1), (40, 4)]);
+        let save = game.parse_save(&raw).unwrap();
+
+        let table = renumbering_table();
+        let migrated = migrate(save.as_ref(), &table).unwrap();
+        let chips_view = migrated.view_chips().unwrap();
+        assert_eq!(chips_view.chip(0, 0), Some(save::Chip { id: 2, code: 0 }));
+        assert_eq!(chips_view.chip(0, 1), Some(save::Chip { id: 1, code: 1 }));
+        assert_eq!(chips_view.chip(0, 2), Some(save::Chip { id: 200, code: 2 }));
+        assert_eq!(chips_view.chip(0, 3), Some(save::Chip { id: 4, code: 3 }));
+        assert_eq!(chips_view.pack_ids(), Some(vec![2, 40, 200]));
+
+        // The migrated save has to load again, checksum and all.
+        let migrated = game.parse_save(&migrated.to_vec()).unwrap();
+        let restored = migrate(migrated.as_ref(), &inverse(&table)).unwrap();
+        assert_eq!(restored.to_vec(), save.to_vec());
+    }
+
+    #[test]
+    fn test_find_unmapped_checks_the_pack() {
+        let game = game::find_by_family_and_variant("bn6", 0).unwrap();
+        let save = game.parse_save(&bn6_save(&[(1, 0); 30], &[(1, 1), (7, 1)])).unwrap();
+        let table = Table {
+            chips: Some([(1, 1)].into_iter().collect()),
+            ..Default::default()
+        };
+        assert_eq!(
+            find_unmapped(save.as_ref(), &table),
+            vec![Unmapped {
+                kind: Kind::Chip,
+                id: 7
+            }]
+        );
+        assert!(matches!(migrate(save.as_ref(), &table), Err(Error::Unmapped(_))));
+    }
+}
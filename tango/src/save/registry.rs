@@ -0,0 +1,206 @@
+//! Which matches and replays each save was used in, so a save can still be told apart from its copies long after the fact.
+//!
+//! Saves are keyed by a hash of their contents rather than by path, so history follows a save that's renamed or moved. Whenever the saves are scanned, each entry is re-keyed to wherever its contents turned up, or to its save's new contents if the file it was last seen at changed.
+
+use sha2::Digest;
+
+use crate::{fs, save};
+
+/// A match a save was played in.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct MatchRecord {
+    pub played_at: std::time::SystemTime,
+    pub link_code: String,
+    pub opponent_nickname: String,
+    /// Who the opponent was recognized as in the opponents registry, if anyone.
+    pub opponent_id: Option<String>,
+    pub patch: Option<(String, semver::Version)>,
+}
+
+/// Everything known about where a save has been used.
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Entry {
+    /// Where the save was last seen.
+    pub path: std::path::PathBuf,
+    /// Oldest first.
+    pub matches: Vec<MatchRecord>,
+    /// Replays of rounds played with the save, oldest first.
+    pub replays: Vec<std::path::PathBuf>,
+    /// Every patch version the save was played with.
+    pub patches: Vec<(String, semver::Version)>,
+    pub last_used_at: Option<std::time::SystemTime>,
+}
+
+/// The hash entries are keyed by.
+pub fn content_hash(save: &(dyn save::Save + Send + Sync)) -> String {
+    sha2::Sha256::digest(&save.to_vec())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+struct Inner {
+    path: std::path::PathBuf,
+    entries: std::collections::BTreeMap<String, Entry>,
+    /// The hash of each save as of the last scan, so they don't have to be hashed again every frame they're shown.
+    hashes: std::collections::HashMap<std::path::PathBuf, String>,
+}
+
+impl Inner {
+    fn save(&self) -> std::io::Result<()> {
+        save::write_atomically(&self.path, &serde_json::to_vec_pretty(&self.entries)?)
+    }
+}
+
+/// Every save's history, stored as a single JSON file in the data directory.
+#[derive(Clone)]
+pub struct Registry(std::sync::Arc<parking_lot::Mutex<Inner>>);
+
+impl Registry {
+    /// Loads the registry. If it can't be read, it starts out empty: it's nice to have, but not worth keeping anyone from playing.
+    pub fn load(path: std::path::PathBuf) -> Self {
+        let entries = match fs::read(&path) {
+            Ok(raw) => match serde_json::from_slice(&raw) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    log::warn!("failed to parse save registry: {}", e);
+                    Default::default()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => {
+                log::warn!("failed to read save registry: {}", e);
+                Default::default()
+            }
+        };
+        Self(std::sync::Arc::new(parking_lot::Mutex::new(Inner {
+            path,
+            entries,
+            hashes: std::collections::HashMap::new(),
+        })))
+    }
+
+    /// Gets the history of the save at a path, as of the last scan.
+    pub fn get(&self, save_path: &std::path::Path) -> Option<Entry> {
+        let inner = self.0.lock();
+        inner
+            .hashes
+            .get(save_path)
+            .and_then(|hash| inner.entries.get(hash))
+            .cloned()
+    }
+
+    /// Finds the save a replay was recorded with.
+    pub fn find_by_replay(&self, replay_path: &std::path::Path) -> Option<Entry> {
+        self.0
+            .lock()
+            .entries
+            .values()
+            .find(|entry| entry.replays.iter().any(|path| path == replay_path))
+            .cloned()
+    }
+
+    /// Records that a save is being played in a match, returning where replays of the match's rounds should be recorded.
+    pub fn record_match(
+        &self,
+        save_path: &std::path::Path,
+        save: &(dyn save::Save + Send + Sync),
+        record: MatchRecord,
+    ) -> std::io::Result<Recorder> {
+        let hash = content_hash(save);
+        let mut inner = self.0.lock();
+        inner.hashes.insert(save_path.to_path_buf(), hash.clone());
+        let entry = inner.entries.entry(hash.clone()).or_default();
+        entry.path = save_path.to_path_buf();
+        if let Some(patch) = record.patch.as_ref() {
+            if !entry.patches.contains(patch) {
+                entry.patches.push(patch.clone());
+            }
+        }
+        entry.last_used_at = Some(record.played_at);
+        entry.matches.push(record);
+        inner.save()?;
+        Ok(Recorder {
+            registry: self.clone(),
+            hash,
+        })
+    }
+
+    fn record_replay(&self, hash: &str, replay_path: &std::path::Path) -> std::io::Result<()> {
+        let mut inner = self.0.lock();
+        let entry = if let Some(entry) = inner.entries.get_mut(hash) {
+            entry
+        } else {
+            return Ok(());
+        };
+        if entry.replays.iter().any(|path| path == replay_path) {
+            return Ok(());
+        }
+        entry.replays.push(replay_path.to_path_buf());
+        inner.save()
+    }
+
+    /// Catches up with the saves as they were just scanned: entries follow their saves to wherever they were moved, and saves that changed on disk keep their history under their new contents.
+    pub fn reconcile(&self, saves: &save::ScannedSaves) {
+        // A save recognized as more than one game is the same file, so it only needs hashing once.
+        let mut hashes = std::collections::HashMap::new();
+        for save in saves.by_game.values().flatten() {
+            hashes
+                .entry(save.path.clone())
+                .or_insert_with(|| content_hash(&*save.save));
+        }
+        let scanned_hashes = hashes.values().cloned().collect::<std::collections::HashSet<_>>();
+
+        let mut inner = self.0.lock();
+        let mut changed = false;
+        for (path, hash) in hashes.iter() {
+            if let Some(entry) = inner.entries.get_mut(hash) {
+                // If the save is still where it was last seen too, this is a copy of it: the history stays with the original.
+                if entry.path != *path && !hashes.contains_key(&entry.path) {
+                    log::info!("save registry: {} moved to {}", entry.path.display(), path.display());
+                    entry.path = path.clone();
+                    changed = true;
+                }
+                continue;
+            }
+
+            let old_hash = if let Some(old_hash) = inner
+                .entries
+                .iter()
+                .find(|(old_hash, entry)| entry.path == *path && !scanned_hashes.contains(*old_hash))
+                .map(|(old_hash, _)| old_hash.clone())
+            {
+                old_hash
+            } else {
+                continue;
+            };
+            log::info!("save registry: {} changed on disk", path.display());
+            let entry = inner.entries.remove(&old_hash).unwrap();
+            inner.entries.insert(hash.clone(), entry);
+            changed = true;
+        }
+        inner.hashes = hashes;
+
+        if changed {
+            if let Err(e) = inner.save() {
+                log::error!("failed to save save registry: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Where replays of a match's rounds go: the entry for the save it's being played with.
+#[derive(Clone)]
+pub struct Recorder {
+    registry: Registry,
+    hash: String,
+}
+
+impl Recorder {
+    pub fn record_replay(&self, replay_path: &std::path::Path) {
+        if let Err(e) = self.registry.record_replay(&self.hash, replay_path) {
+            log::error!("failed to record replay in save registry: {:?}", e);
+        }
+    }
+}
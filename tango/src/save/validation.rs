@@ -0,0 +1,93 @@
+use crate::{rom, save};
+
+/// A value in a save that the game could never have produced by itself, e.g. from a cheat device.
+///
+/// Saves like these can crash the other side's core partway through a match, so they're rejected before the match starts.
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum IllegalValue {
+    #[error("HP {hp} is above max HP {max_hp}")]
+    Hp { hp: u32, max_hp: u32 },
+
+    #[error("max HP {max_hp} is out of range")]
+    MaxHp { max_hp: u32 },
+
+    #[error("folder {folder} chip {index} has illegal chip ID {id}")]
+    ChipId { folder: usize, index: usize, id: usize },
+
+    #[error("folder {folder} chip {index} (ID {id}) has illegal code {code}")]
+    ChipCode {
+        folder: usize,
+        index: usize,
+        id: usize,
+        code: usize,
+    },
+
+    #[error("navicust part {index} has illegal ID {id} and color {variant}")]
+    NavicustPart { index: usize, id: usize, variant: usize },
+}
+
+/// Checks that current HP is within max HP, and max HP within what the game allows.
+pub fn check_hp(hp: u32, max_hp: u32, max_max_hp: u32) -> Result<(), IllegalValue> {
+    if max_hp == 0 || max_hp > max_max_hp {
+        return Err(IllegalValue::MaxHp { max_hp });
+    }
+    if hp > max_hp {
+        return Err(IllegalValue::Hp { hp, max_hp });
+    }
+    Ok(())
+}
+
+/// Checks that every chip in every folder exists in the ROM's chip table and comes in the code it has.
+pub fn check_chips(chips_view: &dyn save::ChipsView, assets: &dyn rom::Assets) -> Result<(), IllegalValue> {
+    let chip_codes = chips_view.chip_codes();
+    for folder in 0..chips_view.num_folders() {
+        for index in 0..30 {
+            let chip = if let Some(chip) = chips_view.chip(folder, index) {
+                chip
+            } else {
+                continue;
+            };
+
+            let info = if let Some(info) = assets.chip(chip.id) {
+                info
+            } else {
+                return Err(IllegalValue::ChipId {
+                    folder,
+                    index,
+                    id: chip.id,
+                });
+            };
+
+            // The ROM lists codes as indexes into the game's code table, with unused slots filled with 0xff.
+            if chip.code >= chip_codes.len() || !info.codes().contains(&(chip.code as u8)) {
+                return Err(IllegalValue::ChipCode {
+                    folder,
+                    index,
+                    id: chip.id,
+                    code: chip.code,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that every installed navicust part exists in the ROM in the color it has.
+pub fn check_navicust(navicust_view: &dyn save::NavicustView, assets: &dyn rom::Assets) -> Result<(), IllegalValue> {
+    for index in 0..navicust_view.count() {
+        let part = if let Some(part) = navicust_view.navicust_part(index) {
+            part
+        } else {
+            continue;
+        };
+
+        if assets.navicust_part(part.id, part.variant).is_none() {
+            return Err(IllegalValue::NavicustPart {
+                index,
+                id: part.id,
+                variant: part.variant,
+            });
+        }
+    }
+    Ok(())
+}
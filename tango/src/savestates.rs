@@ -0,0 +1,143 @@
+use crate::game;
+
+pub const NUM_SLOTS: usize = 10;
+
+pub const THUMBNAIL_WIDTH: u32 = mgba::gba::SCREEN_WIDTH / 2;
+pub const THUMBNAIL_HEIGHT: u32 = mgba::gba::SCREEN_HEIGHT / 2;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Metadata {
+    saved_at: std::time::SystemTime,
+    patch_version: Option<semver::Version>,
+}
+
+/// What's in a save state slot, for showing in the slot picker.
+pub struct SlotInfo {
+    pub saved_at: std::time::SystemTime,
+    pub patch_version: Option<semver::Version>,
+    pub thumbnail: Option<image::RgbaImage>,
+}
+
+/// Save state slots for a single game and patch.
+///
+/// Each slot is stored as `<slot>.state` along with a `<slot>.png` thumbnail and `<slot>.json` metadata in a directory named after the game and patch, so states for one game are never offered for another.
+pub struct Slots {
+    path: std::path::PathBuf,
+    patch_version: Option<semver::Version>,
+}
+
+impl Slots {
+    pub fn new(
+        states_path: &std::path::Path,
+        game: &'static (dyn game::Game + Send + Sync),
+        patch: Option<&(String, semver::Version)>,
+    ) -> Self {
+        let (family, variant) = game.family_and_variant();
+        let mut name = format!("{}_{}", family, variant);
+        if let Some((patch_name, _)) = patch {
+            name.push('_');
+            name.push_str(patch_name);
+        }
+        Self {
+            path: states_path.join(name),
+            patch_version: patch.map(|(_, version)| version.clone()),
+        }
+    }
+
+    /// The version of the patch the session is running, which is recorded alongside every state saved.
+    pub fn patch_version(&self) -> Option<&semver::Version> {
+        self.patch_version.as_ref()
+    }
+
+    fn slot_path(&self, slot: usize, extension: &str) -> std::path::PathBuf {
+        self.path.join(format!("{}.{}", slot, extension))
+    }
+
+    /// Reads what's in a slot, or None if the slot is empty.
+    pub fn info(&self, slot: usize) -> Option<SlotInfo> {
+        let metadata = match std::fs::read(self.slot_path(slot, "json")) {
+            Ok(raw) => match serde_json::from_slice::<Metadata>(&raw) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    log::warn!("failed to parse metadata for save state slot {}: {}", slot, e);
+                    return None;
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return None;
+            }
+            Err(e) => {
+                log::warn!("failed to read metadata for save state slot {}: {}", slot, e);
+                return None;
+            }
+        };
+
+        if !self.slot_path(slot, "state").exists() {
+            return None;
+        }
+
+        let thumbnail = image::open(self.slot_path(slot, "png"))
+            .map(|image| image.to_rgba8())
+            .map_err(|e| log::warn!("failed to read thumbnail for save state slot {}: {}", slot, e))
+            .ok();
+
+        Some(SlotInfo {
+            saved_at: metadata.saved_at,
+            patch_version: metadata.patch_version,
+            thumbnail,
+        })
+    }
+
+    /// Writes a state to a slot, along with a thumbnail made from the RGBA video buffer at the time.
+    pub fn write(&self, slot: usize, state: &mgba::state::State, vbuf: &[u8]) -> Result<(), anyhow::Error> {
+        if slot >= NUM_SLOTS {
+            anyhow::bail!("no such save state slot: {}", slot);
+        }
+
+        std::fs::create_dir_all(&self.path)?;
+
+        // The metadata is removed first and written last: a slot without it is treated as empty, so a partially written slot is never loaded.
+        match std::fs::remove_file(self.slot_path(slot, "json")) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e.into());
+            }
+        }
+
+        let screenshot = image::RgbaImage::from_raw(mgba::gba::SCREEN_WIDTH, mgba::gba::SCREEN_HEIGHT, vbuf.to_vec())
+            .ok_or_else(|| anyhow::anyhow!("video buffer has the wrong size"))?;
+        image::imageops::resize(
+            &screenshot,
+            THUMBNAIL_WIDTH,
+            THUMBNAIL_HEIGHT,
+            image::imageops::FilterType::Triangle,
+        )
+        .save_with_format(self.slot_path(slot, "png"), image::ImageFormat::Png)?;
+
+        std::fs::write(self.slot_path(slot, "state"), state.as_slice())?;
+
+        std::fs::write(
+            self.slot_path(slot, "json"),
+            serde_json::to_vec(&Metadata {
+                saved_at: std::time::SystemTime::now(),
+                patch_version: self.patch_version.clone(),
+            })?,
+        )?;
+        Ok(())
+    }
+
+    /// Reads the state in a slot.
+    pub fn read(&self, slot: usize) -> Result<mgba::state::State, anyhow::Error> {
+        let raw = std::fs::read(self.slot_path(slot, "state"))?;
+        if raw.len() != mgba::state::State::SIZE {
+            anyhow::bail!(
+                "save state in slot {} has the wrong size: expected {} bytes, got {}",
+                slot,
+                mgba::state::State::SIZE,
+                raw.len()
+            );
+        }
+        Ok(mgba::state::State::from_slice(&raw))
+    }
+}
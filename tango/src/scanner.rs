@@ -1,8 +1,13 @@
+use crate::{config, fs};
+
 struct Inner<T> {
-    items: T,
+    items: std::sync::Arc<T>,
     scanning: bool,
 }
 
+/// Scans a directory in the background and publishes what it found.
+///
+/// Readers get immutable snapshots: the lock is only held for as long as it takes to clone or swap an `Arc`, so neither a running scan nor a reader holding onto a snapshot for a whole frame ever blocks the other.
 pub struct Scanner<T> {
     inner: std::sync::Arc<parking_lot::RwLock<Inner<T>>>,
 }
@@ -15,6 +20,30 @@ impl<T> Clone for Scanner<T> {
     }
 }
 
+/// Handle given to progressive scans for publishing partial results before the scan completes.
+pub struct Progress<'a, T> {
+    inner: &'a parking_lot::RwLock<Inner<T>>,
+}
+
+impl<'a, T> Progress<'a, T> {
+    /// Reads the items as they currently are, i.e. the results of the previous scan plus anything published so far.
+    pub fn read(&self) -> std::sync::Arc<T> {
+        self.inner.read().items.clone()
+    }
+}
+
+impl<'a, T> Progress<'a, T>
+where
+    T: Clone,
+{
+    /// Applies a partial result to the items readers can currently see.
+    ///
+    /// Snapshots readers are still holding onto are left as they were: the items are copied first if any are.
+    pub fn update(&self, f: impl FnOnce(&mut T)) {
+        f(std::sync::Arc::make_mut(&mut self.inner.write().items));
+    }
+}
+
 impl<T> Scanner<T>
 where
     T: Default,
@@ -22,14 +51,15 @@ where
     pub fn new() -> Self {
         Self {
             inner: std::sync::Arc::new(parking_lot::RwLock::new(Inner {
-                items: T::default(),
+                items: std::sync::Arc::new(T::default()),
                 scanning: false,
             })),
         }
     }
 
-    pub fn read(&self) -> parking_lot::MappedRwLockReadGuard<'_, T> {
-        parking_lot::RwLockReadGuard::map(self.inner.read_recursive(), |g| &g.items)
+    /// Takes a snapshot of the items as of the last completed scan or update. It's cheap, and it's fine to hold onto for as long as needed, but it won't see anything published after it was taken.
+    pub fn read(&self) -> std::sync::Arc<T> {
+        self.inner.read().items.clone()
     }
 
     pub fn is_scanning(&self) -> bool {
@@ -37,6 +67,13 @@ where
     }
 
     pub fn rescan(&self, scan: impl Fn() -> Option<T>) {
+        self.rescan_progressively(|_| scan());
+    }
+
+    /// Like `rescan`, but the scan may publish partial results via the given `Progress` while it runs.
+    ///
+    /// Whatever the scan returns replaces the items entirely once it's done, so partial results may be stale (e.g. include files that have since been deleted) until then.
+    pub fn rescan_progressively(&self, scan: impl FnOnce(&Progress<'_, T>) -> Option<T>) {
         {
             let mut inner = self.inner.write();
             if inner.scanning {
@@ -45,12 +82,299 @@ where
             inner.scanning = true;
         }
 
-        let items = scan();
+        let items = scan(&Progress { inner: &self.inner });
 
         let mut inner = self.inner.write();
         if let Some(items) = items {
-            inner.items = items;
+            inner.items = std::sync::Arc::new(items);
         }
         inner.scanning = false;
     }
 }
+
+impl<T> Scanner<T>
+where
+    T: Clone,
+{
+    /// Changes the items, e.g. for a file that was just moved, without waiting on a rescan to pick it up.
+    ///
+    /// Snapshots already taken with `read` don't see the change.
+    pub fn update(&self, f: impl FnOnce(&mut T)) {
+        f(std::sync::Arc::make_mut(&mut self.inner.write().items));
+    }
+}
+
+/// Where duplicates are moved to when they're cleaned up, inside the directory they were found in. It's skipped when scanning, so they stop being found.
+pub const DUPLICATES_DIR: &str = "duplicates";
+
+/// Lists all files under `path`, along with their stamps if they could be determined.
+pub fn walk_files(path: &std::path::Path) -> Vec<(std::path::PathBuf, Option<FileStamp>)> {
+    walkdir::WalkDir::new(fs::extended(path))
+        .into_iter()
+        .filter_entry(|entry| {
+            !(entry.depth() == 1 && entry.file_type().is_dir() && entry.file_name() == DUPLICATES_DIR)
+        })
+        .filter_map(|entry| {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log::error!("failed to read entry: {:?}", e);
+                    return None;
+                }
+            };
+
+            if !entry.file_type().is_file() {
+                return None;
+            }
+
+            let stamp = entry.metadata().ok().and_then(|metadata| FileStamp::of(&metadata));
+            Some((fs::unextended(entry.into_path(), path), stamp))
+        })
+        .collect()
+}
+
+/// Files with the same contents, of which only `canonical` is used.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Duplicates {
+    pub canonical: std::path::PathBuf,
+    pub extras: Vec<std::path::PathBuf>,
+}
+
+/// Orders files by which is preferred among several with the same contents: the shortest path, then the first lexicographically.
+///
+/// This doesn't depend on the order files were found in, so every scan, on every machine, settles on the same one.
+pub fn canonical_order(a: &std::path::Path, b: &std::path::Path) -> std::cmp::Ordering {
+    a.as_os_str().len().cmp(&b.as_os_str().len()).then_with(|| a.cmp(b))
+}
+
+/// Groups files with the same contents, leaving out files that are the only ones with theirs.
+pub fn find_duplicates<'a>(files: impl IntoIterator<Item = (&'a std::path::Path, &'a [u8])>) -> Vec<Duplicates> {
+    let mut by_contents = std::collections::HashMap::<&[u8], Vec<&std::path::Path>>::new();
+    for (path, contents) in files {
+        let paths = by_contents.entry(contents).or_default();
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+
+    let mut duplicates = by_contents
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .map(|mut paths| {
+            paths.sort_by(|a, b| canonical_order(a, b));
+            Duplicates {
+                canonical: paths[0].to_path_buf(),
+                extras: paths[1..].iter().map(|path| path.to_path_buf()).collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+    duplicates.sort_by(|a, b| canonical_order(&a.canonical, &b.canonical));
+    duplicates
+}
+
+/// Moves the extra copies of duplicated files into `DUPLICATES_DIR`, keeping where they were relative to `root`.
+///
+/// Nothing is deleted: anything already there is kept, and the moved file gets a different name instead.
+pub fn move_duplicates(root: &std::path::Path, duplicates: &[Duplicates]) -> std::io::Result<()> {
+    let duplicates_path = root.join(DUPLICATES_DIR);
+    for path in duplicates.iter().flat_map(|duplicates| duplicates.extras.iter()) {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let mut new_path = duplicates_path.join(relative);
+        let mut n = 1;
+        while fs::metadata(&new_path).is_ok() {
+            n += 1;
+            let mut file_name = relative.file_stem().map(|stem| stem.to_os_string()).unwrap_or_default();
+            file_name.push(format!(" ({})", n));
+            if let Some(extension) = relative.extension() {
+                file_name.push(".");
+                file_name.push(extension);
+            }
+            new_path.set_file_name(file_name);
+        }
+        if let Some(parent) = new_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(path, &new_path)?;
+    }
+    Ok(())
+}
+
+/// Identifies a version of a file by its modification time and size.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FileStamp {
+    mtime: std::time::SystemTime,
+    size: u64,
+}
+
+impl FileStamp {
+    pub fn of(metadata: &std::fs::Metadata) -> Option<Self> {
+        Some(Self {
+            mtime: metadata.modified().ok()?,
+            size: metadata.len(),
+        })
+    }
+
+    pub fn mtime(&self) -> std::time::SystemTime {
+        self.mtime
+    }
+}
+
+/// Per-file scan results, persisted under the config directory so that unchanged files don't need to be examined again.
+///
+/// Entries are only valid for the `FileStamp` they were recorded with: if a file changes, its entry is ignored.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Cache<V> {
+    #[serde(skip)]
+    path: Option<std::path::PathBuf>,
+    entries: std::collections::HashMap<std::path::PathBuf, (FileStamp, V)>,
+}
+
+impl<V> Cache<V>
+where
+    V: serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Loads the named cache, or an empty cache if it doesn't exist or can't be read.
+    pub fn load(name: &str) -> Self {
+        let path = match config::get_scan_cache_path(name) {
+            Ok(path) => path,
+            Err(e) => {
+                log::warn!("failed to get scan cache path: {:?}", e);
+                return Self {
+                    path: None,
+                    entries: std::collections::HashMap::new(),
+                };
+            }
+        };
+
+        let entries = match fs::read(&path) {
+            Ok(raw) => match serde_json::from_slice::<Self>(&raw) {
+                Ok(cache) => cache.entries,
+                Err(e) => {
+                    log::warn!("failed to parse scan cache {}: {:?}", path.display(), e);
+                    std::collections::HashMap::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => std::collections::HashMap::new(),
+            Err(e) => {
+                log::warn!("failed to read scan cache: {:?}", e);
+                std::collections::HashMap::new()
+            }
+        };
+
+        Self {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    pub fn get(&self, path: &std::path::Path, stamp: &FileStamp) -> Option<&V> {
+        self.entries
+            .get(path)
+            .and_then(|(cached_stamp, v)| if cached_stamp == stamp { Some(v) } else { None })
+    }
+
+    /// Replaces all entries, dropping entries for files that no longer exist, and writes the cache back to disk.
+    pub fn replace_and_save(
+        &mut self,
+        entries: std::collections::HashMap<std::path::PathBuf, (FileStamp, V)>,
+    ) -> Result<(), anyhow::Error> {
+        self.entries = entries;
+        let path = if let Some(path) = self.path.as_ref() {
+            path
+        } else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Files = std::collections::BTreeMap<std::path::PathBuf, Vec<u8>>;
+
+    const NUM_FILES: usize = 500;
+    /// How long each synthetic file takes to scan, which adds up to a scan that lasts many frames.
+    const FILE_SCAN_TIME: std::time::Duration = std::time::Duration::from_millis(2);
+    const FRAME_TIME: std::time::Duration = std::time::Duration::from_millis(16);
+
+    /// Scans synthetic files slowly, publishing each as it's found, as the saves scanner does.
+    fn slow_scan(progress: &Progress<'_, Files>) -> Option<Files> {
+        let mut files = Files::new();
+        for i in 0..NUM_FILES {
+            std::thread::sleep(FILE_SCAN_TIME);
+            let path = std::path::PathBuf::from(format!("{}.sav", i));
+            let contents = vec![i as u8; 64];
+            progress.update(|items| {
+                items.insert(path.clone(), contents.clone());
+            });
+            files.insert(path, contents);
+        }
+        Some(files)
+    }
+
+    /// Does what the play pane does with a snapshot over a frame: holds onto it the whole time and looks through it.
+    fn frame(scanner: &Scanner<Files>) -> std::time::Duration {
+        let start = std::time::Instant::now();
+        let files = scanner.read();
+        let size = files.values().map(|contents| contents.len()).sum::<usize>();
+        std::thread::sleep(FRAME_TIME / 4);
+        assert_eq!(files.len() * 64, size);
+        start.elapsed()
+    }
+
+    #[test]
+    fn test_rescan_does_not_stall_frames() {
+        let scanner = Scanner::<Files>::new();
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        let scan_thread = std::thread::spawn({
+            let scanner = scanner.clone();
+            move || {
+                scanner.rescan_progressively(slow_scan);
+                done_tx.send(()).unwrap();
+            }
+        });
+
+        let mut frame_times = vec![];
+        while done_rx.try_recv().is_err() {
+            let frame_time = frame(&scanner);
+            frame_times.push(frame_time);
+            std::thread::sleep(FRAME_TIME.saturating_sub(frame_time));
+        }
+        scan_thread.join().unwrap();
+
+        assert_eq!(scanner.read().len(), NUM_FILES);
+        assert!(!scanner.is_scanning());
+        // The scan lasts about a second, so there should have been many frames during it, none of which waited on it.
+        assert!(frame_times.len() > 10, "{} frames", frame_times.len());
+        let slowest = frame_times.iter().max().unwrap();
+        assert!(*slowest < FRAME_TIME, "slowest frame took {:?}", slowest);
+    }
+
+    #[test]
+    fn test_snapshot_does_not_block_rescan() {
+        let scanner = Scanner::<Files>::new();
+        scanner.rescan(|| Some(Files::from([(std::path::PathBuf::from("old.sav"), vec![])])));
+
+        let snapshot = scanner.read();
+        scanner.rescan(|| Some(Files::from([(std::path::PathBuf::from("new.sav"), vec![])])));
+        scanner.update(|files| {
+            files.insert(std::path::PathBuf::from("moved.sav"), vec![]);
+        });
+
+        // The snapshot is left as it was when it was taken.
+        assert_eq!(
+            snapshot.keys().collect::<Vec<_>>(),
+            vec![std::path::Path::new("old.sav")]
+        );
+        assert_eq!(
+            scanner.read().keys().collect::<Vec<_>>(),
+            vec![std::path::Path::new("moved.sav"), std::path::Path::new("new.sav")]
+        );
+    }
+}
@@ -1,10 +1,21 @@
-use crate::{audio, battle, config, game, net, patch, replay, replayer, rom, save, stats, video};
+use crate::{
+    assist, audio, battle, bios, broadcast, config, crashreport, determinism, fairness, fs, game, net, opponents,
+    patch, practice, replay, replayer, rom, save, savestates, stats, throttle, video,
+};
 use parking_lot::Mutex;
-use rand::SeedableRng;
 use std::sync::Arc;
 
 pub const EXPECTED_FPS: f32 = 60.0;
 
+/// How long to wait on each step of forfeiting a hung session's match, as whatever hung may be holding onto it.
+const FORCE_TERMINATE_STEP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How many of the most recent frames of a replay can be stepped back through, i.e. two seconds at normal speed.
+const REPLAY_STEP_BACK_FRAMES: usize = 120;
+
+/// How often, in frames, a single player session's save is checked for changes, i.e. once a second at normal speed.
+const SAVE_SNAPSHOT_INTERVAL_FRAMES: u32 = 60;
+
 pub struct GameInfo {
     pub game: &'static (dyn game::Game + Send + Sync),
     pub patch: Option<(String, semver::Version)>,
@@ -13,14 +24,30 @@ pub struct GameInfo {
 pub struct Setup {
     pub game_lang: unic_langid::LanguageIdentifier,
     pub save: Box<dyn save::Save + Send + Sync>,
-    pub assets: Box<dyn rom::Assets + Send + Sync>,
+    pub assets: Arc<dyn rom::Assets + Send + Sync>,
+}
+
+/// The save file a session was started from, along with its contents at the time.
+///
+/// Sessions only ever operate on an in-memory copy of the save: whether changes are written back is up to whoever ends the session.
+pub struct SourceSave {
+    pub path: std::path::PathBuf,
+    pub original: Vec<u8>,
+    /// The save as it was when it was last checked for changes, which is all there is to go on once the emulator has stopped responding.
+    latest: Arc<Mutex<Vec<u8>>>,
+}
+
+impl SourceSave {
+    pub fn latest(&self) -> Vec<u8> {
+        self.latest.lock().clone()
+    }
 }
 
 pub struct Session {
     start_time: std::time::SystemTime,
     game_info: GameInfo,
-    vbuf: std::sync::Arc<Mutex<Vec<u8>>>,
-    _audio_binding: audio::Binding,
+    vbuf: std::sync::Arc<video::pacing::TripleBuffer>,
+    audio_binding: Option<audio::Binding>,
     thread: mgba::thread::Thread,
     joyflags: std::sync::Arc<std::sync::atomic::AtomicU32>,
     mode: Mode,
@@ -28,6 +55,34 @@ pub struct Session {
     pause_on_next_frame: std::sync::Arc<std::sync::atomic::AtomicBool>,
     opponent_setup: Option<Setup>,
     own_setup: Option<Setup>,
+    source_save: Option<SourceSave>,
+    practice_recorder: Option<Arc<practice::Recorder>>,
+    save_state_slots: Option<savestates::Slots>,
+    assist: Option<Arc<Mutex<assist::Transform>>>,
+    suspend_detector: Arc<stats::SuspendDetector>,
+    /// Set while the emulator is being held to its target tick rate because nothing's happening, e.g. waiting in the comm menu.
+    idle: Arc<std::sync::atomic::AtomicBool>,
+    watchdog: Arc<stats::Watchdog>,
+}
+
+/// Wraps traps so the watchdog knows which one ran last.
+fn watch_traps(
+    watchdog: &Arc<stats::Watchdog>,
+    traps: Vec<(u32, Box<dyn Fn(mgba::core::CoreMutRef<'_>)>)>,
+) -> Vec<(u32, Box<dyn Fn(mgba::core::CoreMutRef<'_>)>)> {
+    traps
+        .into_iter()
+        .map(|(addr, f)| {
+            let watchdog = watchdog.clone();
+            (
+                addr,
+                Box::new(move |core: mgba::core::CoreMutRef<'_>| {
+                    watchdog.trap(addr);
+                    f(core)
+                }) as Box<dyn Fn(mgba::core::CoreMutRef<'_>)>,
+            )
+        })
+        .collect()
 }
 
 pub struct CompletionToken {
@@ -43,14 +98,181 @@ impl CompletionToken {
 pub struct PvP {
     pub match_: std::sync::Arc<tokio::sync::Mutex<Option<std::sync::Arc<battle::Match>>>>,
     cancellation_token: tokio_util::sync::CancellationToken,
+    desync: std::sync::Arc<parking_lot::Mutex<Option<battle::DesyncError>>>,
+    score: std::sync::Arc<parking_lot::Mutex<battle::Score>>,
+    record: std::sync::Arc<parking_lot::Mutex<battle::Record>>,
+    link_code: String,
+    local_rom: Arc<[u8]>,
+    local_nickname: String,
+    remote_nickname: String,
+    remote_installation_id: Option<String>,
+    opponent_id: Option<String>,
+    fairness_report: Arc<Mutex<Option<std::path::PathBuf>>>,
+    custom_screen_cover: Arc<video::cover::CustomScreenCover>,
 }
 
-pub struct SinglePlayer {}
+impl PvP {
+    /// Turns covering the custom screen on or off. This follows streamer mode.
+    pub fn set_custom_screen_covered(&self, covered: bool) {
+        self.custom_screen_cover.set_enabled(covered);
+    }
+
+    /// The desync that ended the match, if any.
+    pub fn desync(&self) -> Option<battle::DesyncError> {
+        *self.desync.lock()
+    }
+
+    /// The score from the rounds played so far. This outlives the match, so it's still there after the match ends.
+    pub fn score(&self) -> battle::Score {
+        *self.score.lock()
+    }
+
+    /// The local and remote players' nicknames.
+    pub fn nicknames(&self) -> (&str, &str) {
+        (&self.local_nickname, &self.remote_nickname)
+    }
+
+    /// What's happened over the match so far, for summing it up. Like the score, this outlives the match.
+    pub fn record(&self) -> battle::Record {
+        self.record.lock().clone()
+    }
+
+    pub fn link_code(&self) -> &str {
+        &self.link_code
+    }
+
+    /// The ROM the local side is playing on, with its patch applied if there is one.
+    pub fn local_rom(&self) -> &Arc<[u8]> {
+        &self.local_rom
+    }
+
+    pub fn remote_installation_id(&self) -> Option<&str> {
+        self.remote_installation_id.as_deref()
+    }
+
+    /// Who the opponent was recognized as in the opponents registry, if anyone.
+    pub fn opponent_id(&self) -> Option<&str> {
+        self.opponent_id.as_deref()
+    }
+
+    /// Where the fairness report is once the match is over and it's been written.
+    pub fn fairness_report(&self) -> &Arc<Mutex<Option<std::path::PathBuf>>> {
+        &self.fairness_report
+    }
+}
+
+pub struct SinglePlayer {
+    practice_manipulator: Option<Arc<practice::Manipulator>>,
+}
+
+impl SinglePlayer {
+    /// The practice manipulator, if the game supports it.
+    ///
+    /// This is deliberately only reachable from single player sessions, so game state can never be forced while there's a match to desync.
+    pub fn practice_manipulator(&self) -> Option<&practice::Manipulator> {
+        self.practice_manipulator.as_deref()
+    }
+}
+
+/// Where an A/B loop jumps back to: playback as it was when it was at the start of the loop.
+struct LoopStart {
+    state: mgba::state::State,
+    checkpoint: replayer::Checkpoint,
+    vbuf: Vec<u8>,
+}
+
+/// A recent frame of a replay that playback can step back to.
+struct RecentFrame {
+    state: mgba::state::State,
+    checkpoint: replayer::FrameCheckpoint,
+    vbuf: Vec<u8>,
+}
+
+#[derive(Default)]
+struct ReplayLoop {
+    markers: replay::notes::LoopMarkers,
+    /// Only taken once playback is at A, so markers restored from the replay's notes take effect once it gets there.
+    start: Option<LoopStart>,
+}
+
+pub struct Replayer {
+    state: replayer::State,
+    path: std::path::PathBuf,
+    notes_key: String,
+    num_input_pairs: usize,
+    rollback_depths: Option<Vec<u8>>,
+    replay_loop: Arc<Mutex<ReplayLoop>>,
+    /// Oldest first. The last one is the frame being shown.
+    recent_frames: Arc<Mutex<std::collections::VecDeque<RecentFrame>>>,
+}
+
+impl Replayer {
+    /// Where the replay being played back was read from.
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// Which entry in the replay notes is for the replay being played back.
+    pub fn notes_key(&self) -> &str {
+        &self.notes_key
+    }
+
+    /// How many input pairs have been played back, out of how many there are.
+    pub fn progress(&self) -> (usize, usize) {
+        let left = self.state.lock_inner().input_pairs_left();
+        (
+            self.num_input_pairs - left.min(self.num_input_pairs),
+            self.num_input_pairs,
+        )
+    }
+
+    pub fn loop_markers(&self) -> replay::notes::LoopMarkers {
+        self.replay_loop.lock().markers
+    }
+
+    /// Sets loop markers without being at A, e.g. ones saved in the replay's notes. The loop only starts once playback reaches A.
+    pub fn restore_loop_markers(&self, markers: replay::notes::LoopMarkers) {
+        let mut replay_loop = self.replay_loop.lock();
+        if replay_loop.markers.a != markers.a {
+            replay_loop.start = None;
+        }
+        replay_loop.markers = markers;
+    }
+
+    /// Whether B can be set where playback is now: it has to come after A, and before the end, since reaching the end finishes playback.
+    pub fn can_set_loop_end(&self) -> bool {
+        let (position, total) = self.progress();
+        position + 1 < total && self.replay_loop.lock().markers.a.map(|a| position > a).unwrap_or(true)
+    }
+
+    /// Sets B where playback is now. Once playback reaches it, it jumps back to A.
+    pub fn set_loop_end(&self) {
+        if !self.can_set_loop_end() {
+            return;
+        }
+        let (position, _) = self.progress();
+        self.replay_loop.lock().markers.b = Some(position);
+    }
+
+    pub fn clear_loop(&self) {
+        *self.replay_loop.lock() = ReplayLoop::default();
+    }
+
+    /// How far the recording player rolled back for each input pair, if the replay recorded it.
+    pub fn rollback_depths(&self) -> Option<&[u8]> {
+        self.rollback_depths.as_deref()
+    }
+
+    /// Whether there's a frame before the one being shown to step back to.
+    pub fn can_step_back(&self) -> bool {
+        self.recent_frames.lock().len() > 1
+    }
+}
 
 pub enum Mode {
     SinglePlayer(SinglePlayer),
     PvP(PvP),
-    Replayer,
+    Replayer(Replayer),
 }
 
 impl Session {
@@ -62,23 +284,40 @@ impl Session {
         local_settings: net::protocol::Settings,
         local_game: &'static (dyn game::Game + Send + Sync),
         local_patch: Option<(String, semver::Version)>,
-        local_rom: &[u8],
+        local_rom: Arc<[u8]>,
         local_save: &[u8],
+        bios: Option<Arc<[u8]>>,
         remote_settings: net::protocol::Settings,
+        remote_client_info: Option<net::protocol::ClientInfo>,
+        remote_installation_id: Option<String>,
         remote_game: &'static (dyn game::Game + Send + Sync),
         remote_patch_overrides: &patch::ROMOverrides,
         remote_rom: &[u8],
         remote_save: &[u8],
         emu_tps_counter: Arc<Mutex<stats::Counter>>,
+        broadcast_server: broadcast::Server,
         sender: net::Sender,
         receiver: net::Receiver,
-        peer_conn: datachannel_wrapper::PeerConnection,
-        is_offerer: bool,
+        transport: net::Transport,
         replays_path: std::path::PathBuf,
         match_type: (u8, u8),
-        rng_seed: [u8; 16],
+        battle_backgrounds: Option<Vec<u8>>,
+        reveal: fairness::Reveal,
+        opponent: Option<opponents::Recorder>,
+        save_recorder: Option<save::registry::Recorder>,
     ) -> Result<Self, anyhow::Error> {
         let mut core = mgba::core::Core::new_gba("tango")?;
+        determinism::normalize(&mut core);
+        if let Some(bios) = bios.as_ref() {
+            bios::load_into(&mut core, bios)?;
+        }
+        let fingerprint = determinism::Fingerprint::of(&core, bios.as_deref());
+        if let Some(remote_fingerprint) = remote_settings.fingerprint.as_ref() {
+            let differences = fingerprint.differences(remote_fingerprint);
+            if !differences.is_empty() {
+                return Err(determinism::MismatchError(differences).into());
+            }
+        }
         core.enable_video_buffer();
 
         core.as_mut().load_rom(mgba::vfile::VFile::open_memory(&local_rom))?;
@@ -90,8 +329,10 @@ impl Session {
         let hooks = game.hooks();
         hooks.patch(core.as_mut());
 
+        let custom_screen_cover = Arc::new(video::cover::CustomScreenCover::new(hooks, config.read().streamer_mode));
+
         let match_ = std::sync::Arc::new(tokio::sync::Mutex::new(None));
-        let _ = std::fs::create_dir_all(replays_path.parent().unwrap());
+        let _ = fs::create_dir_all(replays_path.parent().unwrap());
         let mut traps = hooks.common_traps();
 
         let completion_flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
@@ -103,7 +344,9 @@ impl Session {
                 flag: completion_flag.clone(),
             },
         ));
-        core.set_traps(
+        let watchdog = Arc::new(stats::Watchdog::new());
+        core.set_traps(watch_traps(
+            &watchdog,
             traps
                 .into_iter()
                 .map(|(addr, f)| {
@@ -117,47 +360,90 @@ impl Session {
                     )
                 })
                 .collect(),
-        );
+        ));
 
         let reveal_setup = remote_settings.reveal_setup;
 
         let thread = mgba::thread::Thread::new(core);
 
         let cancellation_token = tokio_util::sync::CancellationToken::new();
+        let desync = std::sync::Arc::new(parking_lot::Mutex::new(None));
+        let score = std::sync::Arc::new(parking_lot::Mutex::new(battle::Score::default()));
+        let record = std::sync::Arc::new(parking_lot::Mutex::new(battle::Record::default()));
+        let local_nickname = local_settings.nickname.clone();
+        let remote_nickname = remote_settings.nickname.clone();
+        let opponent_id = opponent.as_ref().map(|opponent| opponent.id.clone());
+        let pvp_link_code = link_code.clone();
+        let fairness_report = Arc::new(Mutex::new(None));
+        let report_replays_path = replays_path.clone();
         let match_ = match_.clone();
+        let is_offerer = transport.is_offerer();
         *match_.try_lock().unwrap() = Some({
             let inner_match = battle::Match::new(
                 config,
                 link_code,
                 netplay_compatibility,
-                local_rom.to_vec(),
+                local_rom.clone(),
+                bios,
                 local_game,
                 local_settings,
                 remote_settings,
+                remote_client_info,
                 cancellation_token.clone(),
                 sender,
-                peer_conn,
-                rand_pcg::Mcg128Xsl64::from_seed(rng_seed),
+                transport,
+                fairness::AuditedRng::new(reveal.seed),
                 is_offerer,
                 thread.handle(),
                 remote_rom,
                 remote_save,
                 replays_path,
                 match_type,
+                battle_backgrounds,
+                fingerprint,
+                desync.clone(),
+                score.clone(),
+                record.clone(),
+                broadcast_server,
+                opponent,
+                save_recorder,
             )
             .expect("new match");
 
             {
                 let match_ = match_.clone();
                 let inner_match = inner_match.clone();
+                let record = record.clone();
+                let fairness_report = fairness_report.clone();
                 tokio::task::spawn(async move {
                     tokio::select! {
                         r = inner_match.run(receiver) => {
                             log::info!("match thread ending: {:?}", r);
+                            // The other side leaving once the match is over looks the same as a dropped connection, so only count it partway through a round.
+                            if r.is_err() && inner_match.lock_round_state().await.round.is_some() {
+                                record.lock().disconnected = true;
+                            }
                         }
                         _ = inner_match.cancelled() => {
                         }
                     }
+
+                    // The seed decides every round still to come, so this is only written once there are none.
+                    let report = fairness::Report::new(
+                        reveal,
+                        inner_match.is_offerer(),
+                        inner_match.rng_draws().await,
+                        &record.lock(),
+                    );
+                    match report.write(&report_replays_path) {
+                        Ok(path) => {
+                            *fairness_report.lock() = path;
+                        }
+                        Err(e) => {
+                            log::error!("failed to write fairness report: {:?}", e);
+                        }
+                    }
+
                     log::info!("match thread ended");
                     *match_.lock().await = None;
                 });
@@ -174,29 +460,72 @@ impl Session {
             audio_binder.sample_rate(),
         ))))?;
 
-        let vbuf = Arc::new(Mutex::new(vec![
-            0u8;
-            (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4)
-                as usize
-        ]));
+        let vbuf = Arc::new(video::pacing::TripleBuffer::new(
+            (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4) as usize,
+        ));
+        let suspend_detector = Arc::new(stats::SuspendDetector::new());
+        let idle = Arc::new(std::sync::atomic::AtomicBool::new(false));
         thread.set_frame_callback({
             let completion_flag = completion_flag.clone();
             let joyflags = joyflags.clone();
             let vbuf = vbuf.clone();
             let emu_tps_counter = emu_tps_counter.clone();
+            let suspend_detector = suspend_detector.clone();
+            let match_ = match_.clone();
+            let idle = idle.clone();
+            let custom_screen_cover = custom_screen_cover.clone();
+            let watchdog = watchdog.clone();
+            let throttle = Mutex::new(throttle::Throttle::new());
             move |mut core, video_buffer, mut thread_handle| {
-                let mut vbuf = vbuf.lock();
-                vbuf.copy_from_slice(video_buffer);
-                video::fix_vbuf_alpha(&mut *vbuf);
+                vbuf.publish(|vbuf| {
+                    vbuf.copy_from_slice(video_buffer);
+                    video::fix_vbuf_alpha(vbuf);
+                    custom_screen_cover.apply(core, vbuf);
+                });
                 core.set_keys(joyflags.load(std::sync::atomic::Ordering::Relaxed));
                 emu_tps_counter.lock().mark();
+                watchdog.tick();
+
+                // Between rounds there's nothing to keep in step with the opponent, so there's no need to run any faster than the game does.
+                let is_idle = match_
+                    .try_lock()
+                    .ok()
+                    .and_then(|match_| match_.as_ref().map(|match_| !match_.is_round_active()))
+                    .unwrap_or(false);
+                idle.store(is_idle, std::sync::atomic::Ordering::Relaxed);
+                let mut throttle = throttle.lock();
+                if is_idle {
+                    throttle.wait(EXPECTED_FPS);
+                } else {
+                    throttle.reset();
+                }
+                drop(throttle);
+
+                // There's no pausing a match: the other side has kept going, so all we can do is let the user know why things went wrong.
+                if suspend_detector.frame() {
+                    log::warn!("system was suspended during match");
+                }
 
                 if completion_flag.load(std::sync::atomic::Ordering::SeqCst) {
+                    watchdog.disarm();
                     thread_handle.pause();
                 }
             }
         });
 
+        crashreport::update_context(|context| {
+            *context = crashreport::Context {
+                mode: Some("pvp"),
+                game: Some({
+                    let (family, variant) = local_game.family_and_variant();
+                    (family.to_string(), variant)
+                }),
+                patch: local_patch.clone(),
+                match_type: Some(match_type),
+                ..Default::default()
+            };
+        });
+
         Ok(Session {
             start_time: std::time::SystemTime::now(),
             game_info: GameInfo {
@@ -204,12 +533,23 @@ impl Session {
                 patch: local_patch,
             },
             vbuf,
-            _audio_binding: audio_binding,
+            audio_binding: Some(audio_binding),
             thread,
             joyflags,
             mode: Mode::PvP(PvP {
                 match_,
                 cancellation_token,
+                desync,
+                score,
+                record,
+                link_code: pvp_link_code,
+                local_rom: local_rom.clone(),
+                local_nickname,
+                remote_nickname,
+                remote_installation_id,
+                opponent_id,
+                fairness_report,
+                custom_screen_cover,
             }),
             completion_flag,
             pause_on_next_frame: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
@@ -222,7 +562,7 @@ impl Session {
                         .clone()
                         .unwrap_or_else(|| game.language()),
                     save,
-                    assets,
+                    assets: assets.into(),
                 })
             },
             opponent_setup: if reveal_setup {
@@ -234,11 +574,19 @@ impl Session {
                         .clone()
                         .unwrap_or_else(|| game.language()),
                     save,
-                    assets,
+                    assets: assets.into(),
                 })
             } else {
                 None
             },
+            // Link battles never change the save, so there's nothing to write back.
+            source_save: None,
+            practice_recorder: None,
+            save_state_slots: None,
+            assist: None,
+            suspend_detector,
+            idle,
+            watchdog,
         })
     }
 
@@ -247,23 +595,46 @@ impl Session {
         game: &'static (dyn game::Game + Send + Sync),
         patch: Option<(String, semver::Version)>,
         rom: &[u8],
+        bios: Option<&[u8]>,
         save_path: &std::path::Path,
+        states_path: &std::path::Path,
         emu_tps_counter: Arc<Mutex<stats::Counter>>,
+        assist_settings: assist::Settings,
     ) -> Result<Self, anyhow::Error> {
         let mut core = mgba::core::Core::new_gba("tango")?;
+        if let Some(bios) = bios {
+            bios::load_into(&mut core, bios)?;
+        }
         core.enable_video_buffer();
 
         core.as_mut().load_rom(mgba::vfile::VFile::open_memory(rom))?;
 
-        let save_vf = mgba::vfile::VFile::open(save_path, mgba::vfile::flags::O_CREAT | mgba::vfile::flags::O_RDWR)?;
-
-        core.as_mut().load_save(save_vf)?;
+        let original_save = fs::read(save_path)?;
+        core.as_mut()
+            .load_save(mgba::vfile::VFile::open_memory(&original_save))?;
+        let latest_save = Arc::new(Mutex::new(original_save.clone()));
 
         let joyflags = Arc::new(std::sync::atomic::AtomicU32::new(0));
 
         let hooks = game.hooks();
         hooks.patch(core.as_mut());
 
+        let practice_manipulator = hooks
+            .practice_manipulation()
+            .map(|manipulation| Arc::new(practice::Manipulator::new(manipulation)));
+        let watchdog = Arc::new(stats::Watchdog::new());
+        if let Some(practice_manipulator) = practice_manipulator.as_ref() {
+            core.set_traps(watch_traps(
+                &watchdog,
+                vec![(practice_manipulator.trap_address(), {
+                    let practice_manipulator = practice_manipulator.clone();
+                    Box::new(move |core: mgba::core::CoreMutRef<'_>| {
+                        practice_manipulator.apply(core);
+                    }) as Box<dyn Fn(mgba::core::CoreMutRef<'_>)>
+                })],
+            ));
+        }
+
         let thread = mgba::thread::Thread::new(core);
 
         thread.start()?;
@@ -274,41 +645,117 @@ impl Session {
             audio_binder.sample_rate(),
         ))))?;
 
+        let practice_recorder = hooks
+            .practice_telemetry()
+            .map(|telemetry| Arc::new(practice::Recorder::new(telemetry)));
+
         let pause_on_next_frame = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-        let vbuf = Arc::new(Mutex::new(vec![
-            0u8;
-            (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4)
-                as usize
-        ]));
+        let vbuf = Arc::new(video::pacing::TripleBuffer::new(
+            (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4) as usize,
+        ));
+        let suspend_detector = Arc::new(stats::SuspendDetector::new());
+        let assist = Arc::new(Mutex::new(assist::Transform::new(assist_settings)));
         thread.set_frame_callback({
             let joyflags = joyflags.clone();
             let vbuf = vbuf.clone();
             let emu_tps_counter = emu_tps_counter.clone();
             let pause_on_next_frame = pause_on_next_frame.clone();
+            let practice_recorder = practice_recorder.clone();
+            let assist = assist.clone();
+            let suspend_detector = suspend_detector.clone();
+            let watchdog = watchdog.clone();
+            let latest_save = latest_save.clone();
             move |mut core, video_buffer, mut thread_handle| {
-                let mut vbuf = vbuf.lock();
-                vbuf.copy_from_slice(video_buffer);
-                video::fix_vbuf_alpha(&mut *vbuf);
-                core.set_keys(joyflags.load(std::sync::atomic::Ordering::Relaxed));
+                vbuf.publish(|vbuf| {
+                    vbuf.copy_from_slice(video_buffer);
+                    video::fix_vbuf_alpha(vbuf);
+                });
+                if core.as_ref().frame_counter() % SAVE_SNAPSHOT_INTERVAL_FRAMES == 0 {
+                    *latest_save.lock() = core.savedata_clone();
+                }
+                let assist_applied = {
+                    let mut assist = assist.lock();
+                    core.set_keys(assist.apply(joyflags.load(std::sync::atomic::Ordering::Relaxed)));
+                    assist.applied()
+                };
                 emu_tps_counter.lock().mark();
+                watchdog.tick();
 
-                if pause_on_next_frame.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                if let Some(practice_recorder) = practice_recorder.as_ref() {
+                    practice_recorder.record(core, assist_applied);
+                }
+
+                let suspended = suspend_detector.frame();
+                if pause_on_next_frame.swap(false, std::sync::atomic::Ordering::SeqCst) || suspended {
+                    watchdog.disarm();
                     thread_handle.pause();
                 }
             }
         });
+        crashreport::update_context(|context| {
+            *context = crashreport::Context {
+                mode: Some("singleplayer"),
+                game: Some({
+                    let (family, variant) = game.family_and_variant();
+                    (family.to_string(), variant)
+                }),
+                patch: patch.clone(),
+                match_type: None,
+                ..Default::default()
+            };
+        });
+        crashreport::set_save_rescue(Some(Box::new({
+            let save_path = save_path.to_path_buf();
+            let original_save = original_save.clone();
+            let latest_save = latest_save.clone();
+            move || {
+                // The save's own file is left alone, as there's no telling what state things were in.
+                let latest_save =
+                    if let Some(latest_save) = latest_save.try_lock_for(std::time::Duration::from_millis(100)) {
+                        latest_save.clone()
+                    } else {
+                        return;
+                    };
+                if latest_save == original_save {
+                    return;
+                }
+                let rescue_path = save::new_save_path(&save_path);
+                match save::write_atomically(&rescue_path, &latest_save) {
+                    Ok(()) => {
+                        log::info!("wrote unsaved changes to {}", rescue_path.display());
+                    }
+                    Err(e) => {
+                        log::error!("failed to write unsaved changes: {:?}", e);
+                    }
+                }
+            }
+        })));
+
+        let save_state_slots = savestates::Slots::new(states_path, game, patch.as_ref());
+
         Ok(Session {
             start_time: std::time::SystemTime::now(),
             game_info: GameInfo { game, patch },
             vbuf,
-            _audio_binding: audio_binding,
+            audio_binding: Some(audio_binding),
             thread,
             joyflags,
-            mode: Mode::SinglePlayer(SinglePlayer {}),
+            mode: Mode::SinglePlayer(SinglePlayer { practice_manipulator }),
             pause_on_next_frame,
             completion_flag: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
             own_setup: None,
             opponent_setup: None,
+            source_save: Some(SourceSave {
+                path: save_path.to_path_buf(),
+                original: original_save,
+                latest: latest_save,
+            }),
+            practice_recorder,
+            save_state_slots: Some(save_state_slots),
+            assist: Some(assist),
+            suspend_detector,
+            idle: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            watchdog,
         })
     }
 
@@ -317,10 +764,18 @@ impl Session {
         game: &'static (dyn game::Game + Send + Sync),
         patch: Option<(String, semver::Version)>,
         rom: &[u8],
+        bios_path: Option<&std::path::Path>,
         emu_tps_counter: Arc<Mutex<stats::Counter>>,
         replay: &replay::Replay,
+        replay_path: &std::path::Path,
     ) -> Result<Self, anyhow::Error> {
         let mut core = mgba::core::Core::new_gba("tango")?;
+        determinism::normalize(&mut core);
+        let bios = bios::for_replay(&replay.metadata, bios_path)?;
+        if let Some(bios) = bios.as_ref() {
+            bios::load_into(&mut core, bios)?;
+        }
+        determinism::check_replay(&core, bios.as_deref(), &replay.metadata)?;
         core.enable_video_buffer();
 
         core.as_mut().load_rom(mgba::vfile::VFile::open_memory(&rom))?;
@@ -330,6 +785,11 @@ impl Session {
 
         let completion_flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
 
+        let num_input_pairs = replay.input_pairs.len();
+        let replay_loop = Arc::new(Mutex::new(ReplayLoop::default()));
+        let recent_frames = Arc::new(Mutex::new(std::collections::VecDeque::with_capacity(
+            REPLAY_STEP_BACK_FRAMES,
+        )));
         let replay_is_complete = replay.is_complete;
         let input_pairs = replay.input_pairs.clone();
         let replayer_state = replayer::State::new(
@@ -343,9 +803,13 @@ impl Session {
                 }
             }),
         );
+        replayer_state
+            .lock_inner()
+            .set_round_time_limit_tick(replay.metadata.round_time_limit.as_ref().map(|limit| limit.ticks));
         let mut traps = hooks.common_traps();
         traps.extend(hooks.replayer_traps(replayer_state.clone()));
-        core.set_traps(traps);
+        let watchdog = Arc::new(stats::Watchdog::new());
+        core.set_traps(watch_traps(&watchdog, traps));
 
         let thread = mgba::thread::Thread::new(core);
 
@@ -359,53 +823,156 @@ impl Session {
         ))))?;
 
         let local_state = replay.local_state.clone();
+        let fixed_rtc = replay.metadata.fixed_rtc.as_ref().map(|fixed_rtc| fixed_rtc.ms);
         thread.handle().run_on_core(move |mut core| {
+            if let Some(fixed_rtc) = fixed_rtc {
+                core.set_rtc_fixed(fixed_rtc);
+            }
             core.load_state(local_state.as_ref().unwrap()).expect("load state");
         });
         thread.handle().unpause();
 
         let pause_on_next_frame = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-        let vbuf = Arc::new(Mutex::new(vec![
-            0u8;
-            (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4)
-                as usize
-        ]));
+        let vbuf = Arc::new(video::pacing::TripleBuffer::new(
+            (mgba::gba::SCREEN_WIDTH * mgba::gba::SCREEN_HEIGHT * 4) as usize,
+        ));
+        let suspend_detector = Arc::new(stats::SuspendDetector::new());
         thread.set_frame_callback({
             let vbuf = vbuf.clone();
             let emu_tps_counter = emu_tps_counter.clone();
             let completion_flag = completion_flag.clone();
             let replayer_state = replayer_state.clone();
             let pause_on_next_frame = pause_on_next_frame.clone();
-            move |_core, video_buffer, mut thread_handle| {
-                let mut vbuf = vbuf.lock();
-                vbuf.copy_from_slice(video_buffer);
-                video::fix_vbuf_alpha(&mut *vbuf);
+            let suspend_detector = suspend_detector.clone();
+            let replay_loop = replay_loop.clone();
+            let recent_frames = recent_frames.clone();
+            let watchdog = watchdog.clone();
+            move |mut core, video_buffer, mut thread_handle| {
+                // This is checked after every frame, whatever the speed, so stepping a frame past B wraps around to A too.
+                let mut replay_loop = replay_loop.lock();
+                let position = num_input_pairs - replayer_state.lock_inner().input_pairs_left().min(num_input_pairs);
+                let mut looped = false;
+                if replay_loop.markers.a == Some(position) && replay_loop.start.is_none() {
+                    match core.save_state() {
+                        Ok(state) => {
+                            replay_loop.start = Some(LoopStart {
+                                state,
+                                checkpoint: replayer_state.lock_inner().checkpoint(),
+                                vbuf: video_buffer.to_vec(),
+                            });
+                        }
+                        Err(e) => {
+                            log::error!("failed to save loop start: {:?}", e);
+                        }
+                    }
+                } else if replay_loop.markers.b.map(|b| position >= b).unwrap_or(false) {
+                    if let Some(start) = replay_loop.start.as_ref() {
+                        match core.load_state(&start.state) {
+                            Ok(()) => {
+                                replayer_state.lock_inner().restore(start.checkpoint.clone());
+                                looped = true;
+                            }
+                            Err(e) => {
+                                log::error!("failed to jump back to loop start: {:?}", e);
+                            }
+                        }
+                    }
+                }
+
+                let frame_vbuf = match replay_loop.start.as_ref() {
+                    Some(start) if looped => &start.vbuf,
+                    _ => video_buffer,
+                };
+                vbuf.publish(|vbuf| {
+                    vbuf.copy_from_slice(frame_vbuf);
+                    video::fix_vbuf_alpha(vbuf);
+                });
+
+                {
+                    let mut recent_frames = recent_frames.lock();
+                    // Frames from before jumping back to A aren't on the way back from here.
+                    if looped {
+                        recent_frames.clear();
+                    }
+                    match core.save_state() {
+                        Ok(state) => {
+                            let mut recycled_vbuf = if recent_frames.len() >= REPLAY_STEP_BACK_FRAMES {
+                                recent_frames.pop_front().map(|frame| frame.vbuf).unwrap_or_default()
+                            } else {
+                                vec![]
+                            };
+                            recycled_vbuf.clear();
+                            recycled_vbuf.extend_from_slice(frame_vbuf);
+                            recent_frames.push_back(RecentFrame {
+                                state,
+                                checkpoint: replayer_state.lock_inner().frame_checkpoint(),
+                                vbuf: recycled_vbuf,
+                            });
+                        }
+                        Err(e) => {
+                            log::error!("failed to save state to step back to: {:?}", e);
+                            recent_frames.clear();
+                        }
+                    }
+                }
+                drop(replay_loop);
                 emu_tps_counter.lock().mark();
+                watchdog.tick();
 
                 if !replay_is_complete && replayer_state.lock_inner().input_pairs_left() == 0 {
                     completion_flag.store(true, std::sync::atomic::Ordering::SeqCst);
                 }
 
+                let suspended = suspend_detector.frame();
                 if pause_on_next_frame.swap(false, std::sync::atomic::Ordering::SeqCst)
                     || completion_flag.load(std::sync::atomic::Ordering::SeqCst)
+                    || suspended
                 {
+                    watchdog.disarm();
                     thread_handle.pause();
                 }
             }
         });
 
+        crashreport::update_context(|context| {
+            *context = crashreport::Context {
+                mode: Some("replayer"),
+                game: Some({
+                    let (family, variant) = game.family_and_variant();
+                    (family.to_string(), variant)
+                }),
+                patch: patch.clone(),
+                ..Default::default()
+            };
+        });
+
         Ok(Session {
             start_time: std::time::SystemTime::now(),
             game_info: GameInfo { game, patch },
             vbuf,
-            _audio_binding: audio_binding,
+            audio_binding: Some(audio_binding),
             thread,
             joyflags: Arc::new(std::sync::atomic::AtomicU32::new(0)),
-            mode: Mode::Replayer,
+            mode: Mode::Replayer(Replayer {
+                state: replayer_state,
+                path: replay_path.to_path_buf(),
+                notes_key: replay::notes::key(&replay.metadata),
+                num_input_pairs,
+                rollback_depths: replay.rollback_depths.clone(),
+                replay_loop,
+                recent_frames,
+            }),
             completion_flag,
             pause_on_next_frame,
             own_setup: None,
             opponent_setup: None,
+            source_save: None,
+            practice_recorder: None,
+            save_state_slots: None,
+            assist: None,
+            suspend_detector,
+            idle: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            watchdog,
         })
     }
 
@@ -421,7 +988,10 @@ impl Session {
         let handle = self.thread.handle();
         if pause {
             handle.pause();
+            self.watchdog.disarm();
         } else {
+            self.suspend_detector.reset();
+            self.watchdog.arm();
             handle.unpause();
         }
     }
@@ -434,10 +1004,133 @@ impl Session {
     pub fn frame_step(&self) {
         self.pause_on_next_frame
             .store(true, std::sync::atomic::Ordering::SeqCst);
+        self.suspend_detector.reset();
+        self.watchdog.arm();
         let handle = self.thread.handle();
         handle.unpause();
     }
 
+    /// Whether the emulator is idling at its target tick rate, in which case there's no need to present any faster either.
+    pub fn is_idle(&self) -> bool {
+        self.idle.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Whether the system was suspended during the session. Local sessions are paused when this happens.
+    pub fn was_suspended(&self) -> bool {
+        self.suspend_detector.was_suspended()
+    }
+
+    /// Dismisses a suspension, resuming the session if it was paused because of it.
+    pub fn resume_after_suspend(&self) {
+        self.suspend_detector.clear();
+        if !matches!(self.mode, Mode::PvP(_)) {
+            self.watchdog.arm();
+            self.thread.handle().unpause();
+        }
+    }
+
+    /// Checks whether the emulator has gone longer than the timeout without completing a tick while it's supposed to be running, returning the hang only when it's first noticed.
+    pub fn check_watchdog(&self, timeout: std::time::Duration) -> Option<stats::Hang> {
+        self.watchdog.check(timeout)
+    }
+
+    /// The hang the emulator is stuck in, if it's stopped responding and hasn't recovered since. Nothing that waits on the emulator should be done in the meantime.
+    pub fn hang(&self) -> Option<stats::Hang> {
+        self.watchdog.hang()
+    }
+
+    /// Gives a hung emulator another timeout's worth of time to come back.
+    pub fn keep_waiting(&self) {
+        self.watchdog.arm();
+    }
+
+    /// Describes a hang for the log and crash reports, along with where the round in progress was if that can be had without waiting on anything.
+    pub fn describe_hang(&self, hang: &stats::Hang) -> String {
+        let mut description = hang.to_string();
+        if let Mode::PvP(pvp) = &self.mode {
+            let round = pvp.match_.try_lock().ok().and_then(|match_| {
+                let match_ = match_.as_ref()?;
+                let round_state = match_.try_lock_round_state()?;
+                Some(
+                    round_state
+                        .round
+                        .as_ref()
+                        .map(|round| (round_state.number, round.current_tick())),
+                )
+            });
+            description.push_str(&match round {
+                Some(Some((number, tick))) => format!(", round {} at tick {}", number, tick),
+                Some(None) => ", between rounds".to_string(),
+                None => ", match busy".to_string(),
+            });
+        }
+        description
+    }
+
+    /// Ends a session whose emulator stopped responding, without the UI waiting on it.
+    ///
+    /// In a match, the opponent is told we forfeited and the replay of the round in progress is finished with what's been recorded of it, if whatever hung isn't holding onto them. The emulator thread is then ended and joined on a thread of its own: if it never comes back, that thread is all that's lost.
+    pub fn force_terminate(self) {
+        let handle = tokio::runtime::Handle::current();
+        if let Err(e) = std::thread::Builder::new()
+            .name("session-teardown".to_string())
+            .spawn(move || {
+                if let Mode::PvP(pvp) = &self.mode {
+                    handle.block_on(async {
+                        let match_ = match tokio::time::timeout(FORCE_TERMINATE_STEP_TIMEOUT, pvp.match_.lock()).await {
+                            Ok(match_) => match_.as_ref().cloned(),
+                            Err(_) => {
+                                log::warn!("match is busy, ending it without forfeiting");
+                                None
+                            }
+                        };
+                        let match_ = if let Some(match_) = match_ {
+                            match_
+                        } else {
+                            return;
+                        };
+                        match tokio::time::timeout(FORCE_TERMINATE_STEP_TIMEOUT, match_.forfeit()).await {
+                            Ok(Ok(())) => {
+                                log::info!("forfeited match");
+                            }
+                            Ok(Err(e)) => {
+                                log::warn!("failed to forfeit match: {:?}", e);
+                            }
+                            Err(_) => {
+                                log::warn!("timed out forfeiting match");
+                            }
+                        }
+                        if tokio::time::timeout(FORCE_TERMINATE_STEP_TIMEOUT, match_.finish_partial_replay())
+                            .await
+                            .is_err()
+                        {
+                            log::warn!("round is busy, leaving its replay as far as it was written");
+                        }
+                    });
+                }
+                drop(self);
+                log::info!("hung session torn down");
+            })
+        {
+            log::error!("failed to start tearing down hung session: {:?}", e);
+        }
+    }
+
+    /// Ends the session and checks, in debug builds, that its emulator is really gone.
+    ///
+    /// Dropping a session tears it down just the same: this is for where sessions are closed or replaced, so a leak shows up where it happens rather than as memory that's never given back.
+    pub fn shutdown(self) {
+        // A match's tasks let go of the emulator on their own time once they see they've been cancelled, so only other sessions are expected to be gone right away.
+        let expect_released = !matches!(self.mode, Mode::PvP(_));
+        let thread = self.thread.downgrade();
+        let vbuf = Arc::downgrade(&self.vbuf);
+        drop(self);
+        if expect_released {
+            debug_assert!(!thread.is_alive(), "emulator thread outlived its session");
+            debug_assert!(vbuf.strong_count() == 0, "video buffer outlived its session");
+        }
+    }
+
     pub fn set_fps_target(&self, fps: f32) {
         let handle = self.thread.handle();
         let audio_guard = handle.lock_audio();
@@ -465,8 +1158,9 @@ impl Session {
         }
     }
 
-    pub fn lock_vbuf(&self) -> parking_lot::MutexGuard<Vec<u8>> {
-        self.vbuf.lock()
+    /// Gets the most recently completed frame, along with which frame it is.
+    pub fn latest_frame(&self) -> (u64, parking_lot::MutexGuard<Vec<u8>>) {
+        self.vbuf.latest()
     }
 
     pub fn thread_handle(&self) -> mgba::thread::Handle {
@@ -477,6 +1171,20 @@ impl Session {
         self.joyflags.store(joyflags, std::sync::atomic::Ordering::Relaxed);
     }
 
+    /// Updates the input assists, which only exist in single player sessions: netplay inputs are always sent as pressed.
+    pub fn set_assist(&self, settings: &assist::Settings, slow_motion: bool) {
+        if let Some(assist) = self.assist.as_ref() {
+            let mut assist = assist.lock();
+            assist.set_settings(settings);
+            assist.set_slow_motion(slow_motion);
+        }
+    }
+
+    /// The practice telemetry recorder, if this is a single player session of a game that supports it.
+    pub fn practice_recorder(&self) -> Option<&practice::Recorder> {
+        self.practice_recorder.as_deref()
+    }
+
     pub fn game_info(&self) -> &GameInfo {
         &self.game_info
     }
@@ -492,15 +1200,252 @@ impl Session {
     pub fn own_setup(&self) -> &Option<Setup> {
         &self.own_setup
     }
+
+    pub fn source_save(&self) -> Option<&SourceSave> {
+        self.source_save.as_ref()
+    }
+
+    /// Save state slots, which are only available in single player sessions: loading a state during a link battle would desync it.
+    pub fn save_state_slots(&self) -> Option<&savestates::Slots> {
+        self.save_state_slots.as_ref()
+    }
+
+    /// Sets A of a replay's A/B loop where playback is now, taking what's needed to jump back to it.
+    ///
+    /// The state is captured on the emulator thread, so this blocks until the current frame is done.
+    pub fn set_replay_loop_start(&self) -> Result<(), anyhow::Error> {
+        let replayer = if let Mode::Replayer(replayer) = &self.mode {
+            replayer
+        } else {
+            anyhow::bail!("not playing back a replay");
+        };
+        let captured = std::sync::Arc::new(Mutex::new(None));
+        self.thread.handle().run_on_core({
+            let captured = captured.clone();
+            let replayer_state = replayer.state.clone();
+            move |core| {
+                let replayer_state = replayer_state.lock_inner();
+                *captured.lock() = Some(
+                    core.save_state()
+                        .map(|state| (state, replayer_state.checkpoint(), replayer_state.input_pairs_left())),
+                );
+            }
+        });
+        let (state, checkpoint, input_pairs_left) = captured
+            .lock()
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("emulator did not save state"))??;
+        let position = replayer.num_input_pairs - input_pairs_left.min(replayer.num_input_pairs);
+
+        let mut replay_loop = replayer.replay_loop.lock();
+        replay_loop.markers.a = Some(position);
+        if replay_loop.markers.b.map(|b| b <= position).unwrap_or(false) {
+            replay_loop.markers.b = None;
+        }
+        replay_loop.start = Some(LoopStart {
+            state,
+            checkpoint,
+            vbuf: self.vbuf.latest().1.clone(),
+        });
+        Ok(())
+    }
+
+    /// Pauses a replay and steps it back a frame, exactly as it was when it was shown.
+    ///
+    /// Only the most recent frames can be stepped back through. The state is restored on the emulator thread, so this blocks until the current frame is done.
+    pub fn step_replay_back(&self) -> Result<(), anyhow::Error> {
+        let replayer = if let Mode::Replayer(replayer) = &self.mode {
+            replayer
+        } else {
+            anyhow::bail!("not playing back a replay");
+        };
+        self.set_paused(true);
+        let result = std::sync::Arc::new(Mutex::new(None));
+        self.thread.handle().run_on_core({
+            let result = result.clone();
+            let replayer_state = replayer.state.clone();
+            let recent_frames = replayer.recent_frames.clone();
+            let vbuf = self.vbuf.clone();
+            move |mut core| {
+                let mut recent_frames = recent_frames.lock();
+                if recent_frames.len() < 2 {
+                    *result.lock() = Some(Err(anyhow::anyhow!("no earlier frame to step back to")));
+                    return;
+                }
+                recent_frames.pop_back();
+                let frame = recent_frames.back().unwrap();
+                if let Err(e) = core.load_state(&frame.state) {
+                    // The emulator may be anywhere now: the frames left don't lead up to it any more.
+                    recent_frames.clear();
+                    *result.lock() = Some(Err(e.into()));
+                    return;
+                }
+                replayer_state.lock_inner().restore_frame(&frame.checkpoint);
+                vbuf.publish(|vbuf| {
+                    vbuf.copy_from_slice(&frame.vbuf);
+                    video::fix_vbuf_alpha(vbuf);
+                });
+                *result.lock() = Some(Ok(()));
+            }
+        });
+        let result = result
+            .lock()
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("emulator did not step back"))?;
+        result
+    }
+
+    /// Saves the emulator state to a slot.
+    ///
+    /// The state is captured on the emulator thread, so this blocks until the current frame is done.
+    pub fn save_state_to_slot(&self, slot: usize) -> Result<(), anyhow::Error> {
+        let slots = self
+            .save_state_slots
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("save states are not available in this session"))?;
+        let state = std::sync::Arc::new(Mutex::new(None));
+        self.thread.handle().run_on_core({
+            let state = state.clone();
+            move |core| {
+                *state.lock() = Some(core.save_state());
+            }
+        });
+        let state = state
+            .lock()
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("emulator did not save state"))??;
+        let vbuf = self.vbuf.latest().1.clone();
+        slots.write(slot, &state, &vbuf)
+    }
+
+    /// Loads the emulator state from a slot.
+    pub fn load_state_from_slot(&self, slot: usize) -> Result<(), anyhow::Error> {
+        let slots = self
+            .save_state_slots
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("save states are not available in this session"))?;
+        let state = Mutex::new(slots.read(slot)?);
+        let result = std::sync::Arc::new(Mutex::new(None));
+        self.thread.handle().run_on_core({
+            let result = result.clone();
+            move |mut core| {
+                *result.lock() = Some(core.load_state(&state.lock()));
+            }
+        });
+        let result = result
+            .lock()
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("emulator did not load state"))?;
+        result
+    }
+
+    /// Returns the save data as it currently is in the emulator.
+    pub fn current_save(&self) -> Vec<u8> {
+        let save = std::sync::Arc::new(Mutex::new(vec![]));
+        self.thread.handle().run_on_core({
+            let save = save.clone();
+            move |mut core| {
+                *save.lock() = core.savedata_clone();
+            }
+        });
+        let save = save.lock().clone();
+        save
+    }
 }
 
 impl Drop for Session {
     fn drop(&mut self) {
+        crashreport::update_context(|context| {
+            *context = crashreport::Context::default();
+        });
+        if self.source_save.is_some() {
+            crashreport::set_save_rescue(None);
+        }
         match &mut self.mode {
             Mode::PvP(pvp) => {
                 pvp.cancellation_token.cancel();
             }
             _ => {}
         }
+        // Unbind the audio first, so nothing reaches into the emulator while it's ending. Ending it drops its callbacks, which hold onto much of the session and often the emulator itself: the core goes once everything else does.
+        self.audio_binding = None;
+        self.thread.end();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Held by tests that make emulator cores, as the count of live cores is shared by the whole process.
+    pub(crate) static CORES_LOCK: Mutex<()> = parking_lot::const_mutex(());
+
+    const NUM_REPLAYS: usize = 10;
+
+    /// How much more memory may be in use after the last replay than after the first, to allow for the allocator holding onto some. A single leaked replay would take more than this, as it holds onto its ROM.
+    const RESIDENT_TOLERANCE: usize = 16 * 1024 * 1024;
+
+    /// How much memory the process is using, if it can be found out.
+    fn resident_size() -> Option<usize> {
+        let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+        let pages = statm.split_whitespace().nth(1)?.parse::<usize>().ok()?;
+        Some(pages * 4096)
+    }
+
+    /// This needs an unpatched ROM and a replay recorded on it, given as `TANGO_TEST_ROM` and `TANGO_TEST_REPLAY`, so it's skipped without them. Nothing is played out loud: the audio binder is never given an output.
+    #[test]
+    fn test_replays_release_their_cores() {
+        let (rom, replay_path) = match (
+            std::env::var_os("TANGO_TEST_ROM"),
+            std::env::var_os("TANGO_TEST_REPLAY"),
+        ) {
+            (Some(rom_path), Some(replay_path)) => {
+                (std::fs::read(rom_path).unwrap(), std::path::PathBuf::from(replay_path))
+            }
+            _ => {
+                eprintln!("skipping: TANGO_TEST_ROM and TANGO_TEST_REPLAY must be set");
+                return;
+            }
+        };
+        let replay = replay::Replay::decode(std::fs::File::open(&replay_path).unwrap()).unwrap();
+        let game = game::detect(&rom).unwrap();
+        let audio_binder = audio::LateBinder::new(48000);
+        let emu_tps_counter = Arc::new(Mutex::new(stats::Counter::new(stats::window::EMU_TPS)));
+
+        let _cores = CORES_LOCK.lock();
+        let baseline = mgba::core::live_count();
+        let mut after_first = None;
+        for i in 0..NUM_REPLAYS {
+            let session = Session::new_replayer(
+                audio_binder.clone(),
+                game,
+                None,
+                &rom,
+                None,
+                emu_tps_counter.clone(),
+                &replay,
+                &replay_path,
+            )
+            .unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            assert!(mgba::core::live_count() > baseline);
+
+            // In debug builds, this also checks that the emulator thread and video buffer went with the session.
+            session.shutdown();
+            assert_eq!(mgba::core::live_count(), baseline, "replay {} left cores behind", i);
+
+            if i == 0 {
+                after_first = resident_size();
+            }
+        }
+
+        if let (Some(after_first), Some(after_last)) = (after_first, resident_size()) {
+            assert!(
+                after_last < after_first + RESIDENT_TOLERANCE,
+                "{} bytes resident after the first replay, {} after the last",
+                after_first,
+                after_last
+            );
+        }
     }
 }
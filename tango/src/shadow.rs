@@ -1,7 +1,11 @@
-use crate::{battle, game, lockstep};
+use crate::{battle, bios, determinism, game, lockstep, replayer, video};
+
+/// How many checksums from either side are kept around waiting for the other's.
+const MAX_PENDING_STATE_HASHES: usize = 32;
 
 pub struct Round {
     current_tick: u32,
+    round_time_limit_tick: Option<u32>,
     local_player_index: u8,
     first_committed_state: Option<mgba::state::State>,
     pending_shadow_input: Option<lockstep::Pair<lockstep::Input, lockstep::PartialInput>>,
@@ -26,6 +30,11 @@ impl Round {
         self.current_tick += 1;
     }
 
+    /// Whether the round's time limit runs out on the current tick.
+    pub fn is_round_time_limit_reached(&self) -> bool {
+        self.round_time_limit_tick == Some(self.current_tick)
+    }
+
     pub fn local_player_index(&self) -> u8 {
         self.local_player_index
     }
@@ -90,6 +99,8 @@ struct AppliedState {
 struct InnerState {
     match_type: (u8, u8),
     is_offerer: bool,
+    round_time_limit_tick: Option<u32>,
+    battle_backgrounds: Option<Vec<u8>>,
     round_state: parking_lot::Mutex<RoundState>,
     rng: parking_lot::Mutex<rand_pcg::Mcg128Xsl64>,
     applied_state: parking_lot::Mutex<Option<AppliedState>>,
@@ -100,6 +111,86 @@ pub struct Shadow {
     core: mgba::core::Core,
     state: State,
     hooks: &'static (dyn game::Hooks + Send + Sync),
+    view: Option<View>,
+    state_hash_regions: Vec<std::ops::Range<u32>>,
+}
+
+/// The last frame the shadow core rendered.
+pub struct Frame {
+    /// The tick the frame was rendered on, which is behind the primary's by however long inputs take to be committed.
+    pub tick: u32,
+    pub pixels: Vec<u8>,
+    pub hash: u32,
+}
+
+/// How the shadow's battle state compared to the primary's, at the last tick both were checksummed on.
+#[derive(Clone, Copy)]
+pub struct Comparison {
+    pub tick: u32,
+    pub matches: bool,
+}
+
+#[derive(Default)]
+struct ViewInner {
+    frame: Option<Frame>,
+    shadow_state_hashes: std::collections::BTreeMap<u32, u32>,
+    primary_state_hashes: std::collections::BTreeMap<u32, u32>,
+    last_comparison: Option<Comparison>,
+}
+
+/// A window into what the shadow core is doing, for investigating desyncs.
+///
+/// The shadow core only renders if it's given one of these when it's made, so there's nothing to pay for it otherwise.
+#[derive(Clone)]
+pub struct View(std::sync::Arc<parking_lot::Mutex<ViewInner>>);
+
+impl View {
+    pub fn new() -> Self {
+        Self(std::sync::Arc::new(parking_lot::Mutex::new(ViewInner::default())))
+    }
+
+    /// Takes the frame the shadow rendered last, if it's rendered one since this was last called.
+    pub fn take_frame(&self) -> Option<Frame> {
+        self.0.lock().frame.take()
+    }
+
+    pub fn last_comparison(&self) -> Option<Comparison> {
+        self.0.lock().last_comparison
+    }
+
+    /// Adds a checksum the primary's fastforwarder took of a committed tick, to compare with the shadow's.
+    pub fn add_primary_state_hash(&self, tick: u32, hash: u32) {
+        let mut inner = self.0.lock();
+        inner.primary_state_hashes.insert(tick, hash);
+        Self::compare(&mut inner, tick);
+    }
+
+    fn add_shadow_state_hash(&self, tick: u32, hash: u32) {
+        let mut inner = self.0.lock();
+        inner.shadow_state_hashes.insert(tick, hash);
+        Self::compare(&mut inner, tick);
+    }
+
+    fn compare(inner: &mut ViewInner, tick: u32) {
+        if let (Some(primary_hash), Some(shadow_hash)) = (
+            inner.primary_state_hashes.get(&tick),
+            inner.shadow_state_hashes.get(&tick),
+        ) {
+            inner.last_comparison = Some(Comparison {
+                tick,
+                matches: primary_hash == shadow_hash,
+            });
+            inner.primary_state_hashes = inner.primary_state_hashes.split_off(&(tick + 1));
+            inner.shadow_state_hashes = inner.shadow_state_hashes.split_off(&(tick + 1));
+        }
+
+        for hashes in [&mut inner.primary_state_hashes, &mut inner.shadow_state_hashes] {
+            while hashes.len() > MAX_PENDING_STATE_HASHES {
+                let oldest_tick = *hashes.keys().next().unwrap();
+                hashes.remove(&oldest_tick);
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -111,10 +202,14 @@ impl State {
         is_offerer: bool,
         rng: rand_pcg::Mcg128Xsl64,
         last_result: battle::BattleResult,
+        round_time_limit_tick: Option<u32>,
+        battle_backgrounds: Option<Vec<u8>>,
     ) -> State {
         State(std::sync::Arc::new(InnerState {
             match_type,
             is_offerer,
+            round_time_limit_tick,
+            battle_backgrounds,
             rng: parking_lot::Mutex::new(rng),
             round_state: parking_lot::Mutex::new(RoundState {
                 round: None,
@@ -133,6 +228,10 @@ impl State {
         self.0.is_offerer
     }
 
+    pub fn battle_backgrounds(&self) -> Option<&[u8]> {
+        self.0.battle_backgrounds.as_deref()
+    }
+
     pub fn lock_rng(&self) -> parking_lot::MutexGuard<rand_pcg::Mcg128Xsl64> {
         self.0.rng.lock()
     }
@@ -150,6 +249,7 @@ impl State {
         log::info!("starting shadow round: local_player_index = {}", local_player_index);
         round_state.round = Some(Round {
             current_tick: 0,
+            round_time_limit_tick: self.0.round_time_limit_tick,
             local_player_index,
             first_committed_state: None,
             pending_shadow_input: None,
@@ -177,17 +277,36 @@ impl Shadow {
     pub fn new(
         rom: &[u8],
         save: &[u8],
+        bios: Option<&[u8]>,
         match_type: (u8, u8),
         is_offerer: bool,
         battle_result: battle::BattleResult,
         rng: rand_pcg::Mcg128Xsl64,
+        round_time_limit_tick: Option<u32>,
+        battle_backgrounds: Option<Vec<u8>>,
+        view: Option<View>,
     ) -> anyhow::Result<Self> {
         let mut core = mgba::core::Core::new_gba("tango")?;
+        determinism::normalize(&mut core);
+        if let Some(bios) = bios {
+            bios::load_into(&mut core, bios)?;
+        }
+        if view.is_some() {
+            // This has to be done before the core is reset for it to render at all. Rendering doesn't affect emulation, so the shadow stays in sync either way.
+            core.enable_video_buffer();
+        }
 
         core.as_mut().load_rom(mgba::vfile::VFile::open_memory(rom))?;
         core.as_mut().load_save(mgba::vfile::VFile::open_memory(save))?;
 
-        let state = State::new(match_type, is_offerer, rng, battle_result);
+        let state = State::new(
+            match_type,
+            is_offerer,
+            rng,
+            battle_result,
+            round_time_limit_tick,
+            battle_backgrounds,
+        );
 
         let game = game::find_by_rom_info(&core.as_mut().rom_code(), core.as_mut().rom_revision()).unwrap();
         let hooks = game.hooks();
@@ -198,7 +317,58 @@ impl Shadow {
         core.set_traps(traps);
         core.as_mut().reset();
 
-        Ok(Shadow { core, hooks, state })
+        Ok(Shadow {
+            core,
+            hooks,
+            state,
+            state_hash_regions: if view.is_some() {
+                hooks.state_hash_regions()
+            } else {
+                vec![]
+            },
+            view,
+        })
+    }
+
+    /// Hands the frame rendered getting to the given tick to the view, if there is one.
+    ///
+    /// This must be called before the applied state is loaded back, as loading a state doesn't restore what was on screen.
+    fn capture_frame(&self, tick: u32) {
+        let (view, pixels) = if let (Some(view), Some(pixels)) = (self.view.as_ref(), self.core.video_buffer()) {
+            (view, pixels)
+        } else {
+            return;
+        };
+        let mut pixels = pixels.to_vec();
+        video::fix_vbuf_alpha(&mut pixels);
+        view.0.lock().frame = Some(Frame {
+            tick,
+            hash: crc32fast::hash(&pixels),
+            pixels,
+        });
+    }
+
+    /// Checksums the battle state the same way the primary's fastforwarder does, so the view can tell if the two have diverged.
+    ///
+    /// This must be called with the applied state loaded, which was saved at the same point in the tick as the fastforwarder checksums.
+    fn record_state_hash(&mut self, tick: u32) {
+        let view = if let Some(view) = self.view.as_ref() {
+            view
+        } else {
+            return;
+        };
+        if self.state_hash_regions.is_empty() || tick % replayer::STATE_HASH_INTERVAL != 0 {
+            return;
+        }
+
+        let mut core = self.core.as_mut();
+        let mut hasher = crc32fast::Hasher::new();
+        for region in self.state_hash_regions.iter() {
+            for addr in region.clone() {
+                hasher.update(&[core.raw_read_8(addr, -1)]);
+            }
+        }
+        view.add_shadow_state_hash(tick, hasher.finalize());
     }
 
     pub fn advance_until_first_committed_state(&mut self) -> anyhow::Result<mgba::state::State> {
@@ -269,11 +439,290 @@ impl Shadow {
                 continue;
             };
 
+            self.capture_frame(applied_state.tick);
             self.core.as_mut().load_state(&applied_state.state).expect("load state");
+            self.record_state_hash(applied_state.tick);
             let mut round_state = self.state.lock_round_state();
             let round = round_state.round.as_mut().expect("round");
             round.current_tick = applied_state.tick;
             return Ok(pending_remote_packet);
         }
     }
+
+    /// The remote packet the next input will be applied against.
+    pub fn peek_remote_packet(&self) -> anyhow::Result<lockstep::Packet> {
+        self.state
+            .lock_round_state()
+            .round
+            .as_ref()
+            .and_then(|round| round.peek_remote_packet())
+            .ok_or_else(|| anyhow::anyhow!("shadow: no pending remote packet"))
+    }
+}
+
+/// How many requests may be queued for the shadow thread before the primary has to wait for it.
+const MAX_QUEUED_REQUESTS: usize = 4;
+
+enum Request {
+    AdvanceUntilFirstCommittedState(std::sync::mpsc::Sender<anyhow::Result<mgba::state::State>>),
+    AdvanceUntilRoundEnd,
+    PeekRemotePacket(std::sync::mpsc::Sender<anyhow::Result<lockstep::Packet>>),
+    ApplyInput(
+        lockstep::Pair<lockstep::Input, lockstep::PartialInput>,
+        std::sync::mpsc::Sender<anyhow::Result<lockstep::Packet>>,
+    ),
+}
+
+fn thread_exited() -> anyhow::Error {
+    anyhow::anyhow!("shadow: thread exited")
+}
+
+fn run(mut shadow: Shadow, rx: std::sync::mpsc::Receiver<Request>) {
+    // Errors from requests nobody waits on are reported to the next request that is waited on instead.
+    let mut deferred_error = None;
+
+    for request in rx {
+        match request {
+            Request::AdvanceUntilFirstCommittedState(reply_tx) => {
+                let _ = reply_tx.send(match deferred_error.take() {
+                    Some(err) => Err(err),
+                    None => shadow.advance_until_first_committed_state(),
+                });
+            }
+            Request::AdvanceUntilRoundEnd => {
+                if deferred_error.is_some() {
+                    continue;
+                }
+                if let Err(err) = shadow.advance_until_round_end() {
+                    deferred_error = Some(err);
+                }
+            }
+            Request::PeekRemotePacket(reply_tx) => {
+                let _ = reply_tx.send(match deferred_error.take() {
+                    Some(err) => Err(err),
+                    None => shadow.peek_remote_packet(),
+                });
+            }
+            Request::ApplyInput(ip, reply_tx) => {
+                let _ = reply_tx.send(match deferred_error.take() {
+                    Some(err) => Err(err),
+                    None => shadow.apply_input(ip).and_then(|_| shadow.peek_remote_packet()),
+                });
+            }
+        }
+    }
+}
+
+/// Runs the shadow core on its own thread, so that the primary core only has to wait on it when it needs a result.
+pub struct Thread {
+    tx: std::sync::mpsc::SyncSender<Request>,
+}
+
+impl Thread {
+    pub fn spawn(shadow: Shadow) -> anyhow::Result<Self> {
+        let (tx, rx) = std::sync::mpsc::sync_channel(MAX_QUEUED_REQUESTS);
+        std::thread::Builder::new()
+            .name("shadow".to_string())
+            .spawn(move || run(shadow, rx))?;
+        Ok(Self { tx })
+    }
+
+    fn send(&self, request: Request) -> anyhow::Result<()> {
+        self.tx.send(request).map_err(|_| thread_exited())
+    }
+
+    pub fn advance_until_first_committed_state(&self) -> anyhow::Result<mgba::state::State> {
+        let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+        self.send(Request::AdvanceUntilFirstCommittedState(reply_tx))?;
+        reply_rx.recv().map_err(|_| thread_exited())?
+    }
+
+    /// Queues advancing until the end of the round without waiting for it: any error is returned by the next request that is waited on.
+    pub fn advance_until_round_end(&self) -> anyhow::Result<()> {
+        self.send(Request::AdvanceUntilRoundEnd)
+    }
+
+    /// Starts applying a run of inputs, e.g. for a single fastforward.
+    pub fn inputs(&self) -> Inputs {
+        Inputs {
+            tx: self.tx.clone(),
+            in_flight: None,
+        }
+    }
+}
+
+/// Applies inputs to the shadow one tick behind the primary.
+///
+/// The remote packet for a tick only depends on the inputs before it, so the shadow can apply an input while the primary carries on with the same tick. The primary only waits when it needs the packet for the next tick.
+pub struct Inputs {
+    tx: std::sync::mpsc::SyncSender<Request>,
+    in_flight: Option<std::sync::mpsc::Receiver<anyhow::Result<lockstep::Packet>>>,
+}
+
+impl Inputs {
+    /// Returns the remote packet for the tick of the given input, and queues the input to be applied.
+    pub fn apply(
+        &mut self,
+        ip: lockstep::Pair<lockstep::Input, lockstep::PartialInput>,
+    ) -> anyhow::Result<lockstep::Packet> {
+        let packet = if let Some(in_flight) = self.in_flight.take() {
+            in_flight.recv().map_err(|_| thread_exited())??
+        } else {
+            let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+            self.tx
+                .send(Request::PeekRemotePacket(reply_tx))
+                .map_err(|_| thread_exited())?;
+            reply_rx.recv().map_err(|_| thread_exited())??
+        };
+
+        let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+        self.tx
+            .send(Request::ApplyInput(ip, reply_tx))
+            .map_err(|_| thread_exited())?;
+        self.in_flight = Some(reply_rx);
+        Ok(packet)
+    }
+
+    /// Waits for the last queued input to be applied, returning its error if it failed.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        if let Some(in_flight) = self.in_flight.take() {
+            in_flight.recv().map_err(|_| thread_exited())??;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How fast the scripted match is played, relative to the game's 60 ticks a second.
+    const SPEED: u32 = 10;
+
+    /// How many ticks the scripted match is played for. Nobody moves, so the round doesn't end before this.
+    const NUM_TICKS: u32 = 60 * 60;
+
+    fn new_shadow(rom: &[u8], save: &[u8]) -> Shadow {
+        Shadow::new(
+            rom,
+            save,
+            None,
+            (1, 0),
+            true,
+            battle::BattleResult::Win,
+            rand_pcg::Mcg128Xsl64::new(0x5eed),
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    /// The input for a tick of the scripted match: nobody presses anything, and the other side mirrors the shadow's packet from the tick before back at it.
+    ///
+    /// The packet for the tick itself isn't used, as the shadow thread only hands it back once it's been given the input.
+    fn scripted_input(tick: u32, packet: &[u8]) -> lockstep::Pair<lockstep::Input, lockstep::PartialInput> {
+        lockstep::Pair {
+            local: lockstep::Input {
+                local_tick: tick,
+                remote_tick: tick,
+                joyflags: 0,
+                packet: packet.to_vec(),
+            },
+            remote: lockstep::PartialInput {
+                local_tick: tick,
+                remote_tick: tick,
+                joyflags: 0,
+            },
+        }
+    }
+
+    /// Plays the scripted match on the shadow core serially, the way it was run before it had its own thread.
+    fn play_serial(shadow: &mut Shadow) -> Vec<lockstep::Packet> {
+        shadow.advance_until_first_committed_state().unwrap();
+        let mut packets: Vec<lockstep::Packet> = vec![];
+        for tick in 0..NUM_TICKS {
+            let packet = shadow.peek_remote_packet().unwrap();
+            let mirrored = packets.last().unwrap_or(&packet).packet.clone();
+            shadow.apply_input(scripted_input(tick, &mirrored)).unwrap();
+            packets.push(packet);
+        }
+        packets
+    }
+
+    /// Plays the scripted match on the shadow thread, waiting until each tick is due if paced.
+    fn play_threaded(thread: &Thread, paced: bool) -> Vec<lockstep::Packet> {
+        thread.advance_until_first_committed_state().unwrap();
+        let tick_duration = std::time::Duration::from_secs(1) / (60 * SPEED);
+        let start_time = std::time::Instant::now();
+        let mut inputs = thread.inputs();
+        let mut packets: Vec<lockstep::Packet> = vec![];
+        for tick in 0..NUM_TICKS {
+            if paced {
+                let deadline = start_time + tick_duration * tick;
+                if let Some(wait) = deadline.checked_duration_since(std::time::Instant::now()) {
+                    std::thread::sleep(wait);
+                }
+            }
+            let mirrored = match packets.last() {
+                Some(last) => last.packet.clone(),
+                None => {
+                    let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+                    thread.send(Request::PeekRemotePacket(reply_tx)).unwrap();
+                    reply_rx.recv().unwrap().unwrap().packet
+                }
+            };
+            packets.push(inputs.apply(scripted_input(tick, &mirrored)).unwrap());
+        }
+        inputs.finish().unwrap();
+        packets
+    }
+
+    /// Plays a scripted match through the shadow thread at 10x speed and checks that it produces exactly what the shadow produces run serially, tick for tick.
+    ///
+    /// This needs a ROM and a save to play, given as `TANGO_TEST_ROM` and `TANGO_TEST_SAVE`, so it's skipped without them. Run with `--nocapture` to see how long each way of running the shadow takes.
+    #[test]
+    fn test_threaded_shadow_does_not_diverge() {
+        let (rom, save) = match (std::env::var_os("TANGO_TEST_ROM"), std::env::var_os("TANGO_TEST_SAVE")) {
+            (Some(rom_path), Some(save_path)) => (std::fs::read(rom_path).unwrap(), std::fs::read(save_path).unwrap()),
+            _ => {
+                eprintln!("skipping: TANGO_TEST_ROM and TANGO_TEST_SAVE must be set");
+                return;
+            }
+        };
+
+        let _cores = crate::session::tests::CORES_LOCK.lock();
+        let start_time = std::time::Instant::now();
+        let expected = play_serial(&mut new_shadow(&rom, &save));
+        let serial_elapsed = start_time.elapsed();
+
+        let start_time = std::time::Instant::now();
+        let unpaced = play_threaded(&Thread::spawn(new_shadow(&rom, &save)).unwrap(), false);
+        let threaded_elapsed = start_time.elapsed();
+
+        let start_time = std::time::Instant::now();
+        let paced = play_threaded(&Thread::spawn(new_shadow(&rom, &save)).unwrap(), true);
+        let paced_elapsed = start_time.elapsed();
+
+        for packets in [&unpaced, &paced] {
+            assert_eq!(packets.len(), expected.len());
+            for (i, (packet, expected)) in packets.iter().zip(expected.iter()).enumerate() {
+                assert_eq!(packet.tick, i as u32, "tick diverged");
+                assert_eq!(packet.tick, expected.tick, "tick diverged");
+                assert_eq!(
+                    packet.packet, expected.packet,
+                    "packet diverged on tick {}",
+                    packet.tick
+                );
+            }
+        }
+
+        let real_time = std::time::Duration::from_secs(1) * NUM_TICKS / 60;
+        eprintln!(
+            "{} ticks ({:?} at 1x): serial {:?}, threaded {:?}, paced at {}x {:?}",
+            NUM_TICKS, real_time, serial_elapsed, threaded_elapsed, SPEED, paced_elapsed
+        );
+        // The thread has to keep up with the pace, give or take a few ticks of scheduling slop.
+        assert!(paced_elapsed < real_time / SPEED + std::time::Duration::from_millis(100));
+    }
 }
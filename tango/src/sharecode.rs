@@ -0,0 +1,508 @@
+use crate::{rom, save};
+
+// Codes for sharing folders and NaviCust layouts as text are a header line naming the kind of code and its format version, followed by one tab-separated line per entry. Entries carry both the ID and the name of what they refer to, so that codes can be matched against assets that number things differently, e.g. from a patch.
+
+pub const FOLDER_HEADER: &str = "tango-folder";
+pub const NAVICUST_HEADER: &str = "tango-navicust";
+
+/// The current version of both formats. Codes with a newer version are rejected, older versions must keep parsing.
+pub const VERSION: u32 = 1;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseError {
+    #[error("not a {0} code")]
+    WrongKind(&'static str),
+
+    #[error("unsupported code version: {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("malformed entry on line {0}")]
+    Malformed(usize),
+}
+
+/// Why an entry in a code couldn't be imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnmatchedReason {
+    /// Neither the ID nor the name refer to anything in this game.
+    Unknown,
+    /// The chip code is not one this game has.
+    InvalidCode(char),
+    /// The part doesn't fit in the NaviCust.
+    OutOfBounds,
+}
+
+/// An entry in a code that couldn't be imported.
+#[derive(Debug, Clone)]
+pub struct Unmatched {
+    /// The 1-based line number of the entry.
+    pub line: usize,
+    /// The name of the entry as given in the code, or its ID if it had no name.
+    pub name: String,
+    pub reason: UnmatchedReason,
+}
+
+fn parse_header(lines: &mut std::iter::Enumerate<std::str::Lines<'_>>, kind: &'static str) -> Result<(), ParseError> {
+    let header = lines
+        .find(|(_, line)| !line.trim().is_empty())
+        .map(|(_, line)| line.trim())
+        .ok_or(ParseError::WrongKind(kind))?;
+    let version = header
+        .strip_prefix(kind)
+        .and_then(|rest| rest.strip_prefix(' '))
+        .ok_or(ParseError::WrongKind(kind))?
+        .parse::<u32>()
+        .map_err(|_| ParseError::WrongKind(kind))?;
+    if version > VERSION {
+        return Err(ParseError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+/// Looks up an ID by name, preferring the given ID if its name matches or the name is unknown.
+fn resolve_id(id: usize, name: Option<&str>, count: usize, name_of: impl Fn(usize) -> Option<String>) -> Option<usize> {
+    let name = if let Some(name) = name {
+        name
+    } else {
+        return name_of(id).map(|_| id);
+    };
+    if name_of(id).as_deref() == Some(name) {
+        return Some(id);
+    }
+    (0..count).find(|i| name_of(*i).as_deref() == Some(name))
+}
+
+pub struct FolderEntry {
+    pub line: usize,
+    pub id: usize,
+    pub code: char,
+    pub is_regular: bool,
+    pub is_tag: bool,
+    pub name: Option<String>,
+}
+
+/// A folder as read from a code, before being matched against any assets.
+pub struct FolderCode {
+    pub entries: Vec<FolderEntry>,
+}
+
+impl FolderCode {
+    /// Makes a code from the equipped folder of a save.
+    pub fn export<'a>(chips_view: &(dyn save::ChipsView<'a> + 'a), assets: &(dyn rom::Assets + Send + Sync)) -> String {
+        let folder_index = chips_view.equipped_folder_index();
+        let regular_chip_index = chips_view.regular_chip_index(folder_index);
+        let tag_chip_indexes = chips_view.tag_chip_indexes(folder_index);
+
+        let mut lines = vec![format!("{} {}", FOLDER_HEADER, VERSION)];
+        for i in 0..30 {
+            let chip = if let Some(chip) = chips_view.chip(folder_index, i) {
+                chip
+            } else {
+                continue;
+            };
+            let mut flags = String::new();
+            if regular_chip_index == Some(i) {
+                flags.push('r');
+            }
+            if tag_chip_indexes.map_or(false, |is| is.contains(&i)) {
+                flags.push('t');
+            }
+            if flags.is_empty() {
+                flags.push('-');
+            }
+            lines.push(format!(
+                "{}\t{}\t{}\t{}",
+                chip.id,
+                chips_view.chip_codes()[chip.code] as char,
+                flags,
+                assets.chip(chip.id).map(|info| info.name()).unwrap_or_default(),
+            ));
+        }
+        lines.join("\n")
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut lines = text.lines().enumerate();
+        parse_header(&mut lines, FOLDER_HEADER)?;
+
+        let mut entries = vec![];
+        for (i, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut fields = line.splitn(4, '\t');
+            let (id, code, flags, name) = match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                (Some(id), Some(code), Some(flags), name) => (id, code, flags, name),
+                _ => {
+                    return Err(ParseError::Malformed(i + 1));
+                }
+            };
+            let mut code_chars = code.chars();
+            let code = match (code_chars.next(), code_chars.next()) {
+                (Some(code), None) => code,
+                _ => {
+                    return Err(ParseError::Malformed(i + 1));
+                }
+            };
+            entries.push(FolderEntry {
+                line: i + 1,
+                id: id.trim().parse().map_err(|_| ParseError::Malformed(i + 1))?,
+                code,
+                is_regular: flags.contains('r'),
+                is_tag: flags.contains('t'),
+                name: name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty()),
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    /// Matches the code against a game's assets, producing a folder that can be viewed in place of a save's along with the entries that couldn't be matched.
+    pub fn resolve(
+        &self,
+        chip_codes: &'static [u8],
+        chips_have_mb: bool,
+        assets: &(dyn rom::Assets + Send + Sync),
+    ) -> (Folder, Vec<Unmatched>) {
+        let mut chips = vec![];
+        let mut regular_chip_index = None;
+        let mut tag_chip_indexes = vec![];
+        let mut unmatched = vec![];
+
+        for entry in self.entries.iter() {
+            let display_name = entry.name.clone().unwrap_or_else(|| entry.id.to_string());
+            let id = if let Some(id) = resolve_id(entry.id, entry.name.as_deref(), assets.num_chips(), |id| {
+                assets.chip(id).map(|info| info.name())
+            }) {
+                id
+            } else {
+                unmatched.push(Unmatched {
+                    line: entry.line,
+                    name: display_name,
+                    reason: UnmatchedReason::Unknown,
+                });
+                continue;
+            };
+
+            let code = if let Some(code) = chip_codes.iter().position(|c| *c as char == entry.code) {
+                code
+            } else {
+                unmatched.push(Unmatched {
+                    line: entry.line,
+                    name: display_name,
+                    reason: UnmatchedReason::InvalidCode(entry.code),
+                });
+                continue;
+            };
+
+            if entry.is_regular && regular_chip_index.is_none() {
+                regular_chip_index = Some(chips.len());
+            }
+            if entry.is_tag && tag_chip_indexes.len() < 2 {
+                tag_chip_indexes.push(chips.len());
+            }
+            chips.push(save::Chip { id, code });
+        }
+
+        (
+            Folder {
+                chip_codes,
+                chips_have_mb,
+                chips,
+                regular_chip_index,
+                tag_chip_indexes: tag_chip_indexes.try_into().ok(),
+            },
+            unmatched,
+        )
+    }
+}
+
+/// A folder imported from a code, viewable like the folder in a save.
+pub struct Folder {
+    chip_codes: &'static [u8],
+    chips_have_mb: bool,
+    chips: Vec<save::Chip>,
+    regular_chip_index: Option<usize>,
+    tag_chip_indexes: Option<[usize; 2]>,
+}
+
+impl<'a> save::ChipsView<'a> for Folder {
+    fn chip_codes(&self) -> &'static [u8] {
+        self.chip_codes
+    }
+
+    fn num_folders(&self) -> usize {
+        1
+    }
+
+    fn equipped_folder_index(&self) -> usize {
+        0
+    }
+
+    fn regular_chip_is_in_place(&self) -> bool {
+        true
+    }
+
+    fn chips_have_mb(&self) -> bool {
+        self.chips_have_mb
+    }
+
+    fn regular_chip_index(&self, _folder_index: usize) -> Option<usize> {
+        self.regular_chip_index
+    }
+
+    fn tag_chip_indexes(&self, _folder_index: usize) -> Option<[usize; 2]> {
+        self.tag_chip_indexes
+    }
+
+    fn chip(&self, folder_index: usize, chip_index: usize) -> Option<save::Chip> {
+        if folder_index != 0 {
+            return None;
+        }
+        self.chips.get(chip_index).cloned()
+    }
+}
+
+pub struct NavicustStyle {
+    pub line: usize,
+    pub id: usize,
+    pub name: Option<String>,
+}
+
+pub struct NavicustEntry {
+    pub line: usize,
+    pub part: save::NavicustPart,
+    pub name: Option<String>,
+}
+
+/// A NaviCust layout as read from a code, before being matched against any assets.
+pub struct NavicustCode {
+    /// The style, for games that have them.
+    pub style: Option<NavicustStyle>,
+    pub entries: Vec<NavicustEntry>,
+}
+
+impl NavicustCode {
+    /// Makes a code from the NaviCust of a save.
+    pub fn export<'a>(
+        navicust_view: &(dyn save::NavicustView<'a> + 'a),
+        assets: &(dyn rom::Assets + Send + Sync),
+    ) -> String {
+        let mut lines = vec![format!("{} {}", NAVICUST_HEADER, VERSION)];
+        if let Some(style) = navicust_view.style() {
+            lines.push(format!(
+                "style\t{}\t{}",
+                style,
+                assets.style(style).map(|info| info.name()).unwrap_or_default()
+            ));
+        }
+        for i in 0..navicust_view.count() {
+            let ncp = if let Some(ncp) = navicust_view.navicust_part(i) {
+                ncp
+            } else {
+                continue;
+            };
+            lines.push(format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                ncp.id,
+                ncp.variant,
+                ncp.col,
+                ncp.row,
+                ncp.rot,
+                if ncp.compressed { 'c' } else { 'u' },
+                assets
+                    .navicust_part(ncp.id, ncp.variant)
+                    .map(|info| info.name())
+                    .unwrap_or_default(),
+            ));
+        }
+        lines.join("\n")
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut lines = text.lines().enumerate();
+        parse_header(&mut lines, NAVICUST_HEADER)?;
+
+        let mut style = None;
+        let mut entries = vec![];
+        for (i, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("style\t") {
+                let mut fields = rest.splitn(2, '\t');
+                let id = fields
+                    .next()
+                    .and_then(|id| id.trim().parse().ok())
+                    .ok_or(ParseError::Malformed(i + 1))?;
+                let name = fields
+                    .next()
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty());
+                style = Some(NavicustStyle { line: i + 1, id, name });
+                continue;
+            }
+
+            let fields = line.splitn(7, '\t').collect::<Vec<_>>();
+            if fields.len() < 6 {
+                return Err(ParseError::Malformed(i + 1));
+            }
+            let parse_field = |s: &str| s.trim().parse::<usize>().map_err(|_| ParseError::Malformed(i + 1));
+            let compressed = match fields[5].trim() {
+                "c" => true,
+                "u" => false,
+                _ => {
+                    return Err(ParseError::Malformed(i + 1));
+                }
+            };
+            entries.push(NavicustEntry {
+                line: i + 1,
+                part: save::NavicustPart {
+                    id: parse_field(fields[0])?,
+                    variant: parse_field(fields[1])?,
+                    col: parse_field(fields[2])?
+                        .try_into()
+                        .map_err(|_| ParseError::Malformed(i + 1))?,
+                    row: parse_field(fields[3])?
+                        .try_into()
+                        .map_err(|_| ParseError::Malformed(i + 1))?,
+                    rot: parse_field(fields[4])?
+                        .try_into()
+                        .map_err(|_| ParseError::Malformed(i + 1))?,
+                    compressed,
+                },
+                name: fields
+                    .get(6)
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty()),
+            });
+        }
+        Ok(Self { style, entries })
+    }
+
+    /// Matches the code against a game's assets and the shape of a save's NaviCust, producing a layout that can be viewed in place of the save's along with the entries that couldn't be matched.
+    pub fn resolve<'a>(
+        &self,
+        navicust_view: &(dyn save::NavicustView<'a> + 'a),
+        assets: &(dyn rom::Assets + Send + Sync),
+    ) -> (Navicust, Vec<Unmatched>) {
+        let mut parts = vec![];
+        let mut unmatched = vec![];
+
+        let style = if navicust_view.style().is_some() {
+            self.style.as_ref().and_then(|style| {
+                let resolved = resolve_id(style.id, style.name.as_deref(), assets.num_styles(), |id| {
+                    assets.style(id).map(|info| info.name())
+                });
+                if resolved.is_none() {
+                    unmatched.push(Unmatched {
+                        line: style.line,
+                        name: style.name.clone().unwrap_or_else(|| style.id.to_string()),
+                        reason: UnmatchedReason::Unknown,
+                    });
+                }
+                resolved
+            })
+        } else {
+            None
+        };
+
+        let (num_parts, num_variants) = assets.num_navicust_parts();
+        for entry in self.entries.iter() {
+            let display_name = entry.name.clone().unwrap_or_else(|| entry.part.id.to_string());
+            let id = resolve_id(entry.part.id, entry.name.as_deref(), num_parts, |id| {
+                if entry.part.variant >= num_variants {
+                    return None;
+                }
+                assets.navicust_part(id, entry.part.variant).map(|info| info.name())
+            });
+            let id = if let Some(id) = id {
+                id
+            } else {
+                unmatched.push(Unmatched {
+                    line: entry.line,
+                    name: display_name,
+                    reason: UnmatchedReason::Unknown,
+                });
+                continue;
+            };
+
+            if entry.part.col as usize >= navicust_view.width()
+                || entry.part.row as usize >= navicust_view.height()
+                || entry.part.rot > 3
+            {
+                unmatched.push(Unmatched {
+                    line: entry.line,
+                    name: display_name,
+                    reason: UnmatchedReason::OutOfBounds,
+                });
+                continue;
+            }
+
+            if parts.len() >= navicust_view.count() {
+                unmatched.push(Unmatched {
+                    line: entry.line,
+                    name: display_name,
+                    reason: UnmatchedReason::OutOfBounds,
+                });
+                continue;
+            }
+
+            parts.push(save::NavicustPart {
+                id,
+                ..entry.part.clone()
+            });
+        }
+
+        (
+            Navicust {
+                count: navicust_view.count(),
+                style,
+                width: navicust_view.width(),
+                height: navicust_view.height(),
+                command_line: navicust_view.command_line(),
+                parts,
+            },
+            unmatched,
+        )
+    }
+}
+
+/// A NaviCust layout imported from a code, viewable like the NaviCust in a save.
+pub struct Navicust {
+    count: usize,
+    style: Option<usize>,
+    width: usize,
+    height: usize,
+    command_line: usize,
+    parts: Vec<save::NavicustPart>,
+}
+
+impl<'a> save::NavicustView<'a> for Navicust {
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    fn style(&self) -> Option<usize> {
+        self.style
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn command_line(&self) -> usize {
+        self.command_line
+    }
+
+    fn has_out_of_bounds(&self) -> bool {
+        false
+    }
+
+    fn navicust_part(&self, i: usize) -> Option<save::NavicustPart> {
+        self.parts.get(i).cloned()
+    }
+}
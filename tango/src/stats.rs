@@ -1,3 +1,47 @@
+/// How many samples each counter keeps, all in one place so they can be tuned together.
+pub mod window {
+    /// Round trips to the opponent in the lobby, pinged once a second. It's long enough for the percentiles shown to catch the spikes that cause rollbacks.
+    pub const LOBBY_LATENCY: usize = 20;
+    /// Round trips to the opponent during a match, kept short so the ping shown follows the connection closely.
+    pub const MATCH_LATENCY: usize = 5;
+    pub const FPS: usize = 30;
+    pub const PRESENTATION: usize = 30;
+    pub const EMU_TPS: usize = 60;
+    pub const FASTFORWARD_TICK_COST: usize = 60;
+    pub const CONTROLLER_POLLING_INTERVAL: usize = 32;
+}
+
+/// Gets the sample at a percentile, from 0 to 100, of samples sorted in ascending order.
+///
+/// This is the nearest-rank method: the result is the smallest sample that at least that percent of the samples are at or below, so it's always one of the samples.
+pub fn percentile(sorted: &[std::time::Duration], p: f64) -> std::time::Duration {
+    if sorted.is_empty() {
+        return std::time::Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// The percentiles worth looking at for a window of samples. Spikes are what cause rollbacks and stutters, and the mean hides them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Percentiles {
+    pub p50: std::time::Duration,
+    pub p95: std::time::Duration,
+    pub p99: std::time::Duration,
+}
+
+impl Percentiles {
+    pub fn of(samples: impl Iterator<Item = std::time::Duration>) -> Self {
+        let mut sorted = samples.collect::<Vec<_>>();
+        sorted.sort_unstable();
+        Self {
+            p50: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+        }
+    }
+}
+
 pub struct Counter {
     marks: std::collections::VecDeque<std::time::Instant>,
     window_size: usize,
@@ -18,34 +62,80 @@ impl Counter {
         self.marks.push_back(std::time::Instant::now());
     }
 
+    fn durations(&self) -> impl Iterator<Item = std::time::Duration> + '_ {
+        self.marks.iter().zip(self.marks.iter().skip(1)).map(|(x, y)| *y - *x)
+    }
+
     pub fn mean_duration(&self) -> std::time::Duration {
-        let durations = self
-            .marks
-            .iter()
-            .zip(self.marks.iter().skip(1))
-            .map(|(x, y)| *y - *x)
-            .collect::<Vec<std::time::Duration>>();
+        let durations = self.durations().collect::<Vec<std::time::Duration>>();
         if durations.is_empty() {
             return std::time::Duration::ZERO;
         }
         durations.iter().sum::<std::time::Duration>() / durations.len() as u32
     }
+
+    /// The percentiles of the time between marks, e.g. of frame times.
+    pub fn percentiles(&self) -> Percentiles {
+        Percentiles::of(self.durations())
+    }
+}
+
+lazy_static! {
+    static ref MONOTONIC_EPOCH: std::time::Instant = std::time::Instant::now();
+}
+
+/// Returns an opaque timestamp from a monotonic clock, only meaningful to this process.
+///
+/// Pings carry these instead of wall clock times, so round trips can't be thrown off by the clock being changed.
+pub fn monotonic_timestamp() -> u64 {
+    MONOTONIC_EPOCH.elapsed().as_micros() as u64
+}
+
+/// Returns how long ago a timestamp from `monotonic_timestamp` was taken, or None if it can't have been one of ours.
+pub fn monotonic_elapsed(ts: u64) -> Option<std::time::Duration> {
+    MONOTONIC_EPOCH
+        .elapsed()
+        .checked_sub(std::time::Duration::from_micros(ts))
 }
 
+/// How many times the median a sample has to be to be considered an outlier.
+const OUTLIER_FACTOR: u32 = 10;
+
 pub struct DeltaCounter {
     marks: std::collections::VecDeque<std::time::Duration>,
     window_size: usize,
+    max_sample: std::time::Duration,
+    consecutive_outliers: usize,
 }
 
 impl DeltaCounter {
-    pub fn new(window_size: usize) -> Self {
+    pub fn new(window_size: usize, max_sample: std::time::Duration) -> Self {
         Self {
             marks: std::collections::VecDeque::with_capacity(window_size),
             window_size,
+            max_sample,
+            consecutive_outliers: 0,
         }
     }
 
+    /// Records a sample.
+    ///
+    /// Samples over the maximum are discarded, as are samples far above the median of a full window, unless there have been a window's worth of them in a row: then they're the new normal.
     pub fn mark(&mut self, d: std::time::Duration) {
+        if d > self.max_sample {
+            return;
+        }
+
+        if self.marks.len() >= self.window_size && d > self.median() * OUTLIER_FACTOR {
+            // The count isn't reset once outliers start being kept, so they keep being kept until the median catches up.
+            if self.consecutive_outliers < self.window_size {
+                self.consecutive_outliers += 1;
+                return;
+            }
+        } else {
+            self.consecutive_outliers = 0;
+        }
+
         while self.marks.len() >= self.window_size {
             self.marks.pop_front();
         }
@@ -54,6 +144,9 @@ impl DeltaCounter {
 
     #[allow(dead_code)]
     pub fn mean(&self) -> std::time::Duration {
+        if self.marks.is_empty() {
+            return std::time::Duration::ZERO;
+        }
         self.marks.iter().sum::<std::time::Duration>() / self.marks.len() as u32
     }
 
@@ -66,4 +159,342 @@ impl DeltaCounter {
         let (_, v, _) = marks.select_nth_unstable(self.marks.len() / 2);
         **v
     }
+
+    pub fn percentiles(&self) -> Percentiles {
+        Percentiles::of(self.marks.iter().copied())
+    }
+
+    /// How many samples are in the window.
+    pub fn sample_count(&self) -> usize {
+        self.marks.len()
+    }
+}
+
+/// A gap between frames longer than this means the system was suspended or the clock jumped: no frame legitimately takes this long.
+const SUSPEND_THRESHOLD: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Detects the system having been suspended by looking for long gaps between consecutive frames.
+///
+/// Both clocks are checked: on some platforms the monotonic clock doesn't advance while the system is suspended, but the wall clock always does.
+pub struct SuspendDetector {
+    last_frame: parking_lot::Mutex<Option<(std::time::Instant, std::time::SystemTime)>>,
+    suspended: std::sync::atomic::AtomicBool,
+}
+
+impl SuspendDetector {
+    pub fn new() -> Self {
+        Self {
+            last_frame: parking_lot::Mutex::new(None),
+            suspended: std::sync::atomic::AtomicBool::new(false),
+        }
+    }
+
+    /// Notes that a frame was run, returning true if the system was suspended since the last one.
+    pub fn frame(&self) -> bool {
+        self.frame_at(std::time::Instant::now(), std::time::SystemTime::now())
+    }
+
+    fn frame_at(&self, instant: std::time::Instant, time: std::time::SystemTime) -> bool {
+        let now = (instant, time);
+        let suspended = if let Some((last_instant, last_time)) = self.last_frame.lock().replace(now) {
+            now.0 - last_instant > SUSPEND_THRESHOLD
+                || now.1.duration_since(last_time).map_or(false, |d| d > SUSPEND_THRESHOLD)
+        } else {
+            false
+        };
+        if suspended {
+            self.suspended.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+        suspended
+    }
+
+    /// Forgets the last frame, for when frames stop on purpose, e.g. when pausing.
+    pub fn reset(&self) {
+        *self.last_frame.lock() = None;
+    }
+
+    pub fn was_suspended(&self) -> bool {
+        self.suspended.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    pub fn clear(&self) {
+        self.suspended.store(false, std::sync::atomic::Ordering::SeqCst);
+        self.reset();
+    }
+}
+
+/// What's known about the emulator having stopped responding.
+#[derive(Clone, Copy, Debug)]
+pub struct Hang {
+    /// How long it had been since a tick completed when the hang was noticed.
+    pub stalled_for: std::time::Duration,
+    /// How many ticks had completed.
+    pub ticks: u64,
+    /// The address of the last trap run, which is where it hung if it hung in one of our hooks.
+    pub last_trap: Option<u32>,
+}
+
+impl std::fmt::Display for Hang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no tick completed for {:.1}s after tick {}, last trap: ",
+            self.stalled_for.as_secs_f32(),
+            self.ticks
+        )?;
+        if let Some(last_trap) = self.last_trap {
+            write!(f, "{:08x}", last_trap)
+        } else {
+            write!(f, "none")
+        }
+    }
+}
+
+/// Detects the emulator having stopped responding, by noticing ticks not completing while they're supposed to be.
+///
+/// It's checked from the UI thread, so if the UI didn't get to check for as long either, it was the whole process that was held up, e.g. by the system being suspended, and not the emulator.
+pub struct Watchdog {
+    /// When the last tick completed, or None while ticks have stopped on purpose.
+    last_tick: parking_lot::Mutex<Option<std::time::Instant>>,
+    last_check: parking_lot::Mutex<Option<std::time::Instant>>,
+    ticks: std::sync::atomic::AtomicU64,
+    /// Traps are always in ROM, so 0 means none has run yet.
+    last_trap: std::sync::atomic::AtomicU32,
+    hang: parking_lot::Mutex<Option<Hang>>,
+}
+
+impl Watchdog {
+    /// Makes a watchdog that's already watching, so an emulator that hangs before its first tick is caught too.
+    pub fn new() -> Self {
+        Self {
+            last_tick: parking_lot::Mutex::new(Some(std::time::Instant::now())),
+            last_check: parking_lot::Mutex::new(None),
+            ticks: std::sync::atomic::AtomicU64::new(0),
+            last_trap: std::sync::atomic::AtomicU32::new(0),
+            hang: parking_lot::Mutex::new(None),
+        }
+    }
+
+    /// Notes that a tick completed. If the emulator had been found to be hung, it's since recovered on its own.
+    pub fn tick(&self) {
+        self.ticks.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.arm();
+    }
+
+    /// Notes that a trap is about to run.
+    pub fn trap(&self, addr: u32) {
+        self.last_trap.store(addr, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Starts watching again from now, e.g. when unpausing or when the user would rather keep waiting on a hang.
+    pub fn arm(&self) {
+        *self.last_tick.lock() = Some(std::time::Instant::now());
+        *self.hang.lock() = None;
+    }
+
+    /// Stops watching, for when ticks stop on purpose, e.g. when pausing.
+    pub fn disarm(&self) {
+        *self.last_tick.lock() = None;
+        *self.hang.lock() = None;
+    }
+
+    /// Checks whether ticks have stalled for longer than the timeout, returning the hang only when it's first noticed.
+    pub fn check(&self, timeout: std::time::Duration) -> Option<Hang> {
+        let now = std::time::Instant::now();
+        let last_check = self.last_check.lock().replace(now);
+        let mut last_tick = self.last_tick.lock();
+        let stalled_for = now - (*last_tick)?;
+        if last_check.map_or(false, |last_check| now - last_check > timeout) {
+            *last_tick = Some(now);
+            return None;
+        }
+        let mut hang = self.hang.lock();
+        if hang.is_some() || stalled_for < timeout {
+            return None;
+        }
+        *hang = Some(Hang {
+            stalled_for,
+            ticks: self.ticks.load(std::sync::atomic::Ordering::Relaxed),
+            last_trap: Some(self.last_trap.load(std::sync::atomic::Ordering::Relaxed)).filter(|addr| *addr != 0),
+        });
+        *hang
+    }
+
+    /// The hang the emulator is stuck in, if it's been found to be hung and hasn't recovered since.
+    pub fn hang(&self) -> Option<Hang> {
+        *self.hang.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> std::time::Duration {
+        std::time::Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&[], 50.0), std::time::Duration::ZERO);
+        assert_eq!(percentile(&[ms(7)], 0.0), ms(7));
+        assert_eq!(percentile(&[ms(7)], 100.0), ms(7));
+
+        let sorted = (1..=100).map(ms).collect::<Vec<_>>();
+        assert_eq!(percentile(&sorted, 0.0), ms(1));
+        assert_eq!(percentile(&sorted, 50.0), ms(50));
+        assert_eq!(percentile(&sorted, 95.0), ms(95));
+        assert_eq!(percentile(&sorted, 99.0), ms(99));
+        assert_eq!(percentile(&sorted, 99.5), ms(100));
+        assert_eq!(percentile(&sorted, 100.0), ms(100));
+    }
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        // With few samples, high percentiles are the largest sample rather than anything in between.
+        let sorted = [ms(10), ms(20), ms(30), ms(40), ms(50)];
+        assert_eq!(percentile(&sorted, 20.0), ms(10));
+        assert_eq!(percentile(&sorted, 21.0), ms(20));
+        assert_eq!(percentile(&sorted, 50.0), ms(30));
+        assert_eq!(percentile(&sorted, 80.0), ms(40));
+        assert_eq!(percentile(&sorted, 95.0), ms(50));
+        assert_eq!(percentile(&sorted, 99.0), ms(50));
+    }
+
+    #[test]
+    fn test_percentiles_of_unsorted() {
+        let samples = [ms(50), ms(10), ms(40), ms(20), ms(30), ms(500)];
+        assert_eq!(
+            Percentiles::of(samples.into_iter()),
+            Percentiles {
+                p50: ms(30),
+                p95: ms(500),
+                p99: ms(500),
+            }
+        );
+        assert_eq!(Percentiles::of(std::iter::empty()), Percentiles::default());
+    }
+
+    #[test]
+    fn test_delta_counter_percentiles_follow_window() {
+        let mut counter = DeltaCounter::new(4, ms(1000));
+        for d in [ms(10), ms(20), ms(30), ms(40)] {
+            counter.mark(d);
+        }
+        assert_eq!(counter.percentiles().p50, ms(20));
+        assert_eq!(counter.percentiles().p99, ms(40));
+        // The oldest sample falls out of the window.
+        counter.mark(ms(50));
+        assert_eq!(counter.sample_count(), 4);
+        assert_eq!(counter.percentiles().p50, ms(30));
+        assert_eq!(counter.percentiles().p99, ms(50));
+    }
+
+    #[test]
+    fn test_delta_counter_discards_samples_over_max() {
+        let mut counter = DeltaCounter::new(5, ms(1000));
+        for _ in 0..5 {
+            counter.mark(ms(50));
+        }
+        // A clock step makes for a sample no round trip could take.
+        counter.mark(ms(60 * 60 * 1000));
+        assert_eq!(counter.sample_count(), 5);
+        assert_eq!(counter.median(), ms(50));
+        assert_eq!(counter.percentiles().p99, ms(50));
+    }
+
+    #[test]
+    fn test_delta_counter_discards_outliers() {
+        let mut counter = DeltaCounter::new(5, ms(1000));
+        for _ in 0..5 {
+            counter.mark(ms(50));
+        }
+        counter.mark(ms(900));
+        counter.mark(ms(50));
+        assert_eq!(counter.median(), ms(50));
+        assert_eq!(counter.percentiles().p99, ms(50));
+    }
+
+    #[test]
+    fn test_delta_counter_adopts_a_window_of_outliers() {
+        let mut counter = DeltaCounter::new(5, ms(1000));
+        for _ in 0..5 {
+            counter.mark(ms(50));
+        }
+        for _ in 0..5 {
+            counter.mark(ms(900));
+        }
+        assert_eq!(counter.median(), ms(50));
+        // Once they're kept, it only takes enough of them to move the median.
+        for _ in 0..3 {
+            counter.mark(ms(900));
+        }
+        assert_eq!(counter.median(), ms(900));
+        counter.mark(ms(50));
+        assert_eq!(counter.median(), ms(900));
+    }
+
+    #[test]
+    fn test_delta_counter_empty() {
+        let counter = DeltaCounter::new(5, ms(1000));
+        assert_eq!(counter.sample_count(), 0);
+        assert_eq!(counter.mean(), std::time::Duration::ZERO);
+        assert_eq!(counter.median(), std::time::Duration::ZERO);
+        assert_eq!(counter.percentiles(), Percentiles::default());
+    }
+
+    #[test]
+    fn test_suspend_detector_steady_frames() {
+        let detector = SuspendDetector::new();
+        let (instant, time) = (std::time::Instant::now(), std::time::SystemTime::now());
+        for i in 0..60 {
+            assert!(!detector.frame_at(instant + ms(16) * i, time + ms(16) * i));
+        }
+        assert!(!detector.was_suspended());
+    }
+
+    #[test]
+    fn test_suspend_detector_wall_clock_step() {
+        let detector = SuspendDetector::new();
+        let (instant, time) = (std::time::Instant::now(), std::time::SystemTime::now());
+        assert!(!detector.frame_at(instant, time));
+        // The monotonic clock didn't advance while suspended, but the wall clock did.
+        assert!(detector.frame_at(instant + ms(16), time + ms(60 * 1000)));
+        assert!(detector.was_suspended());
+        assert!(!detector.frame_at(instant + ms(32), time + ms(60 * 1000 + 16)));
+        assert!(detector.was_suspended());
+
+        detector.clear();
+        assert!(!detector.was_suspended());
+    }
+
+    #[test]
+    fn test_suspend_detector_wall_clock_step_back() {
+        let detector = SuspendDetector::new();
+        let (instant, time) = (std::time::Instant::now(), std::time::SystemTime::now());
+        assert!(!detector.frame_at(instant, time + ms(60 * 60 * 1000)));
+        // The wall clock being set back, e.g. by NTP, isn't a suspend.
+        assert!(!detector.frame_at(instant + ms(16), time));
+        assert!(!detector.was_suspended());
+    }
+
+    #[test]
+    fn test_suspend_detector_monotonic_gap() {
+        let detector = SuspendDetector::new();
+        let (instant, time) = (std::time::Instant::now(), std::time::SystemTime::now());
+        assert!(!detector.frame_at(instant, time));
+        assert!(detector.frame_at(instant + ms(10 * 1000), time + ms(16)));
+        assert!(detector.was_suspended());
+    }
+
+    #[test]
+    fn test_suspend_detector_reset() {
+        let detector = SuspendDetector::new();
+        let (instant, time) = (std::time::Instant::now(), std::time::SystemTime::now());
+        assert!(!detector.frame_at(instant, time));
+        // Pausing on purpose isn't a suspend.
+        detector.reset();
+        assert!(!detector.frame_at(instant + ms(60 * 1000), time + ms(60 * 1000)));
+        assert!(!detector.was_suspended());
+    }
 }
@@ -0,0 +1,40 @@
+/// How much earlier than the deadline to stop sleeping and spin instead, to make up for how coarse OS sleeps are.
+const SPIN_MARGIN: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// Holds the emulator thread to its target tick rate by sleeping between frames, for when nothing needs it to run any faster.
+///
+/// This only changes when frames run, never how many do, so it's safe to use during netplay.
+pub struct Throttle {
+    deadline: Option<std::time::Instant>,
+}
+
+impl Throttle {
+    pub fn new() -> Self {
+        Self { deadline: None }
+    }
+
+    /// Waits until the next frame is due. This must be called once per frame.
+    pub fn wait(&mut self, fps_target: f32) {
+        let interval = std::time::Duration::from_secs_f32(1.0 / fps_target.max(1.0));
+        let now = std::time::Instant::now();
+        let deadline = match self.deadline {
+            // If we've fallen more than a frame behind, don't run a burst of frames to catch up.
+            Some(deadline) if deadline + interval > now => deadline,
+            _ => now,
+        };
+
+        if let Some(sleep_for) = deadline.checked_duration_since(now + SPIN_MARGIN) {
+            std::thread::sleep(sleep_for);
+        }
+        while std::time::Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+
+        self.deadline = Some(deadline + interval);
+    }
+
+    /// Forgets when the next frame is due, so waiting again doesn't try to make up for time spent running freely.
+    pub fn reset(&mut self) {
+        self.deadline = None;
+    }
+}
@@ -172,7 +172,7 @@ impl Updater {
                     let current_version = current_version.clone();
                     let config = config.clone();
                     if let Err(e) = (move || async move {
-                        let client = reqwest::Client::new();
+                        let client = config.read().network.http_client();
                         let releases = tokio::time::timeout(
                             // 30 second timeout to get release info.
                             std::time::Duration::from_secs(30),
@@ -256,7 +256,7 @@ impl Updater {
                         let resp = tokio::time::timeout(
                             // 30 second timeout to initiate connection.
                             std::time::Duration::from_secs(30),
-                            reqwest::get(&asset.browser_download_url),
+                            client.get(&asset.browser_download_url).send(),
                         )
                         .await??;
                         let mut current = 0u64;
@@ -1,5 +1,7 @@
+pub mod cover;
 pub mod hqx;
 pub mod mmpx;
+pub mod pacing;
 
 pub trait Filter {
     fn output_size(&self, size: (usize, usize)) -> (usize, usize);
@@ -0,0 +1,59 @@
+//! Covering the custom screen in streamer mode, so an opponent watching the stream can't see what's being picked.
+//!
+//! The cover is drawn over the frame as it's handed off to be shown, after the core is done with it: the core never sees it, and it never ends up in replays.
+
+use crate::game;
+
+/// The custom gauge when it's full.
+const FULL_CUSTOM_GAUGE: u16 = 0x4000;
+
+/// How far across the screen, from the left edge, the custom screen reaches.
+const WIDTH: usize = 120;
+
+pub struct CustomScreenCover {
+    hooks: &'static (dyn game::Hooks + Send + Sync),
+    enabled: std::sync::atomic::AtomicBool,
+    last_custom_gauge: parking_lot::Mutex<Option<u16>>,
+}
+
+impl CustomScreenCover {
+    pub fn new(hooks: &'static (dyn game::Hooks + Send + Sync), enabled: bool) -> Self {
+        Self {
+            hooks,
+            enabled: std::sync::atomic::AtomicBool::new(enabled),
+            last_custom_gauge: parking_lot::Mutex::new(None),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Whether the custom screen is open, going by the custom gauge: it's emptied when the screen opens and stops filling until it closes.
+    ///
+    /// The gauge also stops filling once it's full, which doesn't count. Anything else that holds the gauge still, like a time freeze, errs on the side of covering.
+    fn is_custom_screen_open(&self, core: mgba::core::CoreMutRef) -> bool {
+        let custom_gauge = self.hooks.custom_gauge(core);
+        let last_custom_gauge = std::mem::replace(&mut *self.last_custom_gauge.lock(), custom_gauge);
+        match (custom_gauge, last_custom_gauge) {
+            (Some(custom_gauge), Some(last_custom_gauge)) => {
+                custom_gauge < FULL_CUSTOM_GAUGE && custom_gauge <= last_custom_gauge
+            }
+            // Rounds start with the custom screen open.
+            (Some(custom_gauge), None) => custom_gauge == 0,
+            (None, _) => false,
+        }
+    }
+
+    /// Covers the custom screen in the frame the core just rendered, if it's open. This has to be called for every frame, so the gauge is followed even while the cover is off.
+    pub fn apply(&self, core: mgba::core::CoreMutRef, vbuf: &mut [u8]) {
+        if !self.is_custom_screen_open(core) || !self.enabled.load(std::sync::atomic::Ordering::Relaxed) {
+            return;
+        }
+        for row in vbuf.chunks_exact_mut(mgba::gba::SCREEN_WIDTH as usize * 4) {
+            for pixel in row[..WIDTH * 4].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&[0x00, 0x00, 0x00, 0xff]);
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use parking_lot::Mutex;
+
+struct Indexes {
+    back: usize,
+    middle: usize,
+    front: usize,
+    /// Whether the middle buffer holds a frame that hasn't been taken yet.
+    fresh: bool,
+    /// How many frames have been published.
+    published: u64,
+    /// Which published frame is in the front buffer, counting from 1.
+    front_frame: u64,
+}
+
+/// Hands frames from the emulator thread to the UI thread without either one waiting on the other.
+///
+/// The emulator draws into the back buffer, the most recently completed frame waits in the middle buffer, and the UI reads from the front buffer. Publishing and taking a frame only swap indexes, so the emulator keeps its own pace no matter how often the UI presents: frames replaced before the UI gets to them are dropped, and if nothing new has come in the UI keeps showing what it has.
+pub struct TripleBuffer {
+    buffers: [Mutex<Vec<u8>>; 3],
+    indexes: Mutex<Indexes>,
+}
+
+impl TripleBuffer {
+    pub fn new(size: usize) -> Self {
+        Self {
+            buffers: [
+                Mutex::new(vec![0u8; size]),
+                Mutex::new(vec![0u8; size]),
+                Mutex::new(vec![0u8; size]),
+            ],
+            indexes: Mutex::new(Indexes {
+                back: 0,
+                middle: 1,
+                front: 2,
+                fresh: false,
+                published: 0,
+                front_frame: 0,
+            }),
+        }
+    }
+
+    /// Draws a frame into the back buffer, then makes it the most recently completed frame.
+    ///
+    /// This must only be called from one thread.
+    pub fn publish(&self, f: impl FnOnce(&mut [u8])) {
+        let back = self.indexes.lock().back;
+        f(&mut self.buffers[back].lock()[..]);
+
+        let mut indexes = self.indexes.lock();
+        let Indexes { back, middle, .. } = &mut *indexes;
+        std::mem::swap(back, middle);
+        indexes.fresh = true;
+        indexes.published += 1;
+    }
+
+    /// Gets the most recently completed frame, along with which frame it is.
+    pub fn latest(&self) -> (u64, parking_lot::MutexGuard<Vec<u8>>) {
+        let mut indexes = self.indexes.lock();
+        if indexes.fresh {
+            let Indexes { middle, front, .. } = &mut *indexes;
+            std::mem::swap(middle, front);
+            indexes.fresh = false;
+            indexes.front_frame = indexes.published;
+        }
+        let (front, front_frame) = (indexes.front, indexes.front_frame);
+        drop(indexes);
+        (front_frame, self.buffers[front].lock())
+    }
+}
+
+/// Blends two frames of the same size into `output`, `t` of the way from `from` to `to`.
+pub fn interpolate(from: &[u8], to: &[u8], t: f32, output: &mut [u8]) {
+    let t = (t.clamp(0.0, 1.0) * 256.0) as u32;
+    for ((output, from), to) in output.iter_mut().zip(from.iter()).zip(to.iter()) {
+        *output = ((*from as u32 * (256 - t) + *to as u32 * t) >> 8) as u8;
+    }
+}
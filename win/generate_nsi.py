@@ -93,6 +93,10 @@ Section
 
     WriteRegDWORD HKCU "${{REGPATH_UNINSTSUBKEY}}" "NoModify" 1
     WriteRegDWORD HKCU "${{REGPATH_UNINSTSUBKEY}}" "NoRepair" 1
+    WriteRegStr HKCU "Software\\Classes\\tango" "" "URL:Tango"
+    WriteRegStr HKCU "Software\\Classes\\tango" "URL Protocol" ""
+    WriteRegStr HKCU "Software\\Classes\\tango\\DefaultIcon" "" "$INSTDIR\\tango.exe,0"
+    WriteRegStr HKCU "Software\\Classes\\tango\\shell\\open\\command" "" '"$INSTDIR\\tango.exe" "%1"'
     CreateShortcut "$SMPROGRAMS\\Tango.lnk" "$INSTDIR\\tango.exe"
     CreateShortcut "$DESKTOP\\Tango.lnk" "$INSTDIR\\tango.exe"
 SectionEnd
@@ -111,6 +115,7 @@ Section "uninstall"
     Delete "$INSTDIR\\uninstall.exe"
     RMDir $INSTDIR
     DeleteRegKey HKCU "${{REGPATH_UNINSTSUBKEY}}"
+    DeleteRegKey HKCU "Software\\Classes\\tango"
 SectionEnd
 """
 )